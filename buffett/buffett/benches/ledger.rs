@@ -2,6 +2,7 @@
 extern crate buffett;
 extern crate test;
 
+use buffett::entry::{next_entry, Entry};
 use buffett::hash::{hash, Hash};
 use buffett::ledger::{next_entries, reconstruct_entries_from_blobs, Block};
 use buffett::signature::{Keypair, KeypairUtil};
@@ -23,3 +24,19 @@ fn bench_block_to_blobs_to_block(bencher: &mut Bencher) {
         assert_eq!(reconstruct_entries_from_blobs(blobs).unwrap(), entries);
     });
 }
+
+#[bench]
+fn bench_block_verify(bencher: &mut Bencher) {
+    let zero = Hash::default();
+    let mut id = zero;
+    let entries: Vec<Entry> = (0..256)
+        .map(|_| {
+            let entry = next_entry(&id, 1_000, vec![]);
+            id = entry.id;
+            entry
+        }).collect();
+
+    bencher.iter(|| {
+        assert!(entries.verify(&zero));
+    });
+}
@@ -13,7 +13,7 @@ use buffett::crdt::Node;
 use buffett::token_service::DRONE_PORT;
 use buffett::fullnode::{Config, Fullnode, FullnodeReturnType};
 use buffett::logger;
-use buffett::metrics::set_panic_hook;
+use buffett::metrics::{set_panic_hook, set_prometheus_writer};
 use buffett::signature::{Keypair, KeypairUtil};
 use buffett::thin_client::poll_gossip_for_leader;
 use buffett::wallet::request_airdrop;
@@ -50,8 +50,22 @@ fn main() -> () {
                 .takes_value(true)
                 .required(true)
                 .help("use DIR as persistent ledger location"),
+        ).arg(
+            Arg::with_name("prometheus_port")
+                .long("prometheus-port")
+                .value_name("PORT")
+                .takes_value(true)
+                .help("Serve metrics on this port for Prometheus to scrape, instead of InfluxDB"),
         ).get_matches();
 
+    if matches.is_present("prometheus_port") {
+        let port = value_t_or_exit!(matches, "prometheus_port", u16);
+        set_prometheus_writer(&socketaddr!(0, port)).unwrap_or_else(|err| {
+            eprintln!("Unable to serve metrics on port {}: {}", port, err);
+            exit(1);
+        });
+    }
+
     let (keypair, ncp) = if let Some(i) = matches.value_of("identity") {
         let path = i.to_string();
         if let Ok(file) = File::open(path.clone()) {
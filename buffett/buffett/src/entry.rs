@@ -166,17 +166,21 @@ impl Entry {
         }
     }
 
-    /// Verifies self.id is the result of hashing a `start_hash` `self.num_hashes` times.
-    /// If the transaction is not a Tick, then hash that as well.
-    pub fn verify(&self, start_hash: &Hash) -> bool {
-        let tx_plans_verified = self.transactions.par_iter().all(|tx| {
+    /// Verifies the plans of all the transactions in this entry.
+    pub fn verify_plans(&self) -> bool {
+        self.transactions.par_iter().all(|tx| {
             let r = tx.verify_plan();
             if !r {
                 warn!("tx plan invalid: {:?}", tx);
             }
             r
-        });
-        if !tx_plans_verified {
+        })
+    }
+
+    /// Verifies self.id is the result of hashing a `start_hash` `self.num_hashes` times.
+    /// If the transaction is not a Tick, then hash that as well.
+    pub fn verify(&self, start_hash: &Hash) -> bool {
+        if !self.verify_plans() {
             return false;
         }
         let ref_hash = next_hash(start_hash, self.num_hashes, &self.transactions);
@@ -6,10 +6,11 @@ use bincode::{self, deserialize, deserialize_from, serialize_into, serialized_si
 use crate::budget_instruction::Vote;
 use crate::budget_transaction::BudgetTransaction;
 use crate::entry::Entry;
-use crate::hash::Hash;
+use crate::hash::{hashv, Hash};
 use log::Level::Trace;
 #[cfg(test)]
 use crate::coinery::Mint;
+use crate::multi_sha256;
 use crate::packet::{SharedBlob, BLOB_DATA_SIZE};
 use rayon::prelude::*;
 use crate::result::{Error, Result};
@@ -19,6 +20,7 @@ use buffett_program_interface::pubkey::Pubkey;
 use std::fs::{create_dir_all, remove_dir_all, File, OpenOptions};
 use std::io::prelude::*;
 use std::io::{self, BufReader, BufWriter, Seek, SeekFrom};
+use std::iter;
 use std::mem::size_of;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::path::Path;
@@ -118,6 +120,8 @@ impl LedgerWindow {
     }
 }
 
+/// Checks that the index and data files agree and that every entry follows from the id of the
+/// entry before it.  The chain is verified a window of entries at a time.
 pub fn verify_ledger(ledger_path: &str) -> io::Result<()> {
     let ledger_path = Path::new(&ledger_path);
 
@@ -141,6 +145,8 @@ pub fn verify_ledger(ledger_path: &str) -> io::Result<()> {
     let mut data_read = 0;
     let mut last_len = 0;
     let mut i = 0;
+    let mut last_id = None;
+    let mut pending = Vec::with_capacity(WINDOW_SIZE as usize);
 
     while index_offset < index_len {
         let data_offset = u64_at(&mut index, index_offset)?;
@@ -166,15 +172,25 @@ pub fn verify_ledger(ledger_path: &str) -> io::Result<()> {
                 ),
             ))?,
             Ok(entry) => {
-                last_len = serialized_size(&entry).map_err(err_bincode_to_io)? + SIZEOF_U64
+                last_len = serialized_size(&entry).map_err(err_bincode_to_io)? + SIZEOF_U64;
+                // the first entry has nothing before it to be checked against
+                if last_id.is_none() {
+                    last_id = Some(entry.id);
+                } else {
+                    pending.push(entry);
+                }
             }
         }
+        if pending.len() == WINDOW_SIZE as usize {
+            verify_entries(&mut last_id, &mut pending, i + 1)?;
+        }
 
         last_data_offset = data_offset;
         data_read += last_len;
         index_offset += SIZEOF_U64;
         i += 1;
     }
+    verify_entries(&mut last_id, &mut pending, i)?;
     let data = data.into_inner();
     if data_read != data.metadata()?.len() {
         Err(io::Error::new(
@@ -185,6 +201,30 @@ pub fn verify_ledger(ledger_path: &str) -> io::Result<()> {
     Ok(())
 }
 
+// verifies `entries`, the ones before entry[`end`], against `last_id` and moves `last_id` up to
+//  the last of them
+fn verify_entries(
+    last_id: &mut Option<Hash>,
+    entries: &mut Vec<Entry>,
+    end: u64,
+) -> io::Result<()> {
+    if let (Some(id), Some(last)) = (*last_id, entries.last()) {
+        if !entries.verify(&id) {
+            Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "entries[{}..{}] don't follow from the entry before them",
+                    end - entries.len() as u64,
+                    end
+                ),
+            ))?;
+        }
+        *last_id = Some(last.id);
+    }
+    entries.clear();
+    Ok(())
+}
+
 fn recover_ledger(ledger_path: &str) -> io::Result<()> {
     let ledger_path = Path::new(ledger_path);
     let mut index = OpenOptions::new()
@@ -408,20 +448,68 @@ pub trait Block {
     fn votes(&self) -> Vec<(Pubkey, Vote, Hash)>;
 }
 
-impl Block for [Entry] {
-    fn verify(&self, start_hash: &Hash) -> bool {
-        let genesis = [Entry::new_tick(0, start_hash)];
-        let entry_pairs = genesis.par_iter().chain(self).zip(self);
-        entry_pairs.all(|(x0, x1)| {
-            let r = x1.verify(&x0.id);
-            if !r {
+/// Number of plain hashes before an entry's final hash, which mixes in its transactions
+fn chain_len(num_hashes: u64, transactions: &[Transaction]) -> u64 {
+    if transactions.is_empty() {
+        num_hashes
+    } else {
+        num_hashes.saturating_sub(1)
+    }
+}
+
+/// Number of entries verified together, enough to fill the lanes of `multi_sha256` a few times
+/// over as chains finish at different times
+const VERIFY_CHUNK_SIZE: usize = 4 * multi_sha256::LANES;
+
+/// Verify `entries` against the id of the entry before them, hashing their chains in lockstep
+fn verify_chunk(start_hash: &Hash, entries: &[Entry]) -> bool {
+    let start_hashes: Vec<Hash> = iter::once(start_hash)
+        .chain(entries.iter().map(|entry| &entry.id))
+        .take(entries.len())
+        .cloned()
+        .collect();
+    let chain_lens: Vec<u64> = entries
+        .iter()
+        .map(|entry| chain_len(entry.num_hashes, &entry.transactions))
+        .collect();
+    let chain_hashes = multi_sha256::hash_chains(&start_hashes, &chain_lens);
+
+    entries
+        .iter()
+        .zip(start_hashes.iter().zip(chain_hashes))
+        .all(|(entry, (start_hash, chain_hash))| {
+            if !entry.verify_plans() {
+                return false;
+            }
+            let ref_hash = if entry.transactions.is_empty() {
+                chain_hash
+            } else {
+                let mixin = Transaction::hash(&entry.transactions);
+                hashv(&[chain_hash.as_ref(), mixin.as_ref()])
+            };
+            if entry.id != ref_hash {
                 warn!(
-                    "entry invalid!: {:?} num txs: {}",
-                    x1.id,
-                    x1.transactions.len()
+                    "entry invalid!: x0: {:?}, x1: {:?} num txs: {}, expected: {:?}",
+                    start_hash,
+                    entry.id,
+                    entry.transactions.len(),
+                    ref_hash
                 );
+                return false;
             }
-            r
+            true
+        })
+}
+
+impl Block for [Entry] {
+    fn verify(&self, start_hash: &Hash) -> bool {
+        self.par_chunks(VERIFY_CHUNK_SIZE).enumerate().all(|(i, entries)| {
+            let start_hash = if i == 0 {
+                start_hash
+            } else {
+                &self[i * VERIFY_CHUNK_SIZE - 1].id
+            };
+            verify_chunk(start_hash, entries)
         })
     }
 
@@ -590,6 +678,39 @@ mod tests {
         assert!(!bad_ticks.verify(&zero)); // inductive step, bad
     }
 
+    #[test]
+    fn test_verify_slice_chunks() {
+        let zero = Hash::default();
+        let one = hash(&zero.as_ref());
+        let keypair = Keypair::new();
+        let tx = Transaction::budget_new_timestamp(
+            &keypair,
+            keypair.pubkey(),
+            keypair.pubkey(),
+            Utc::now(),
+            one,
+        );
+
+        // chains of all sorts of lengths, with and without transactions, across many chunks
+        let mut id = zero;
+        let entries: Vec<Entry> = (0..3 * VERIFY_CHUNK_SIZE as u64 + 1)
+            .map(|i| {
+                let transactions = if i % 3 == 0 { vec![tx.clone()] } else { vec![] };
+                let entry = next_entry(&id, i % 5 + 1, transactions);
+                id = entry.id;
+                entry
+            }).collect();
+        assert!(entries.verify(&zero));
+        assert!(!entries.verify(&one));
+
+        // a bad entry in a later chunk, at the start of one and at the very end
+        for i in &[VERIFY_CHUNK_SIZE + 3, 2 * VERIFY_CHUNK_SIZE, entries.len() - 1] {
+            let mut bad_entries = entries.clone();
+            bad_entries[*i].id = one;
+            assert!(!bad_entries.verify(&zero));
+        }
+    }
+
     fn make_tiny_test_entries(num: usize) -> Vec<Entry> {
         let zero = Hash::default();
         let one = hash(&zero.as_ref());
@@ -862,10 +983,32 @@ mod tests {
             let mut writer = LedgerWriter::open(&ledger_path, true).unwrap();
             writer.write_entries(entries.clone()).unwrap();
         }
-        // TODO more cases that make ledger_verify() fail
-        // assert!(verify_ledger(&ledger_path).is_err());
+        assert!(verify_ledger(&ledger_path).is_ok());
 
+        // an entry that doesn't follow from the one before it
+        let mut bad_entries = entries.clone();
+        bad_entries[5].id = hash(&bad_entries[5].id.as_ref());
+        {
+            let mut writer = LedgerWriter::open(&ledger_path, true).unwrap();
+            writer.write_entries(bad_entries).unwrap();
+        }
+        assert!(verify_ledger(&ledger_path).is_err());
+
+        // the chain is checked across windows too
+        let entries = make_tiny_test_entries(WINDOW_SIZE as usize + 2);
+        {
+            let mut writer = LedgerWriter::open(&ledger_path, true).unwrap();
+            writer.write_entries(entries.clone()).unwrap();
+        }
         assert!(verify_ledger(&ledger_path).is_ok());
+
+        let mut bad_entries = entries.clone();
+        bad_entries[WINDOW_SIZE as usize + 1].num_hashes += 1;
+        {
+            let mut writer = LedgerWriter::open(&ledger_path, true).unwrap();
+            writer.write_entries(bad_entries).unwrap();
+        }
+        assert!(verify_ledger(&ledger_path).is_err());
         let _ignored = remove_dir_all(&ledger_path);
     }
 
@@ -27,6 +27,7 @@ pub mod hash;
 pub mod ledger;
 pub mod logger;
 pub mod metrics;
+pub mod multi_sha256;
 pub mod coinery;
 pub mod ncp;
 pub mod netutil;
@@ -97,6 +98,8 @@ extern crate solana_jsonrpc_http_server as jsonrpc_http_server;
 #[macro_use]
 extern crate solana_jsonrpc_macros as jsonrpc_macros;
 extern crate buffett_program_interface;
+extern crate soros_metrics;
+extern crate soros_sdk;
 extern crate sys_info;
 extern crate tokio;
 extern crate tokio_codec;
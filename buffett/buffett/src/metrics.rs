@@ -1,7 +1,11 @@
-//! The `metrics` module enables sending measurements to an InfluxDB instance
+//! The `metrics` module enables sending measurements to an InfluxDB instance, or to any other
+//! `MetricsWriter` installed with `set_writer`
 
 use influx_db_client as influxdb;
+use soros_metrics::prometheus::PrometheusMetricsWriter;
 use std::env;
+use std::io;
+use std::net::SocketAddr;
 use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
 use std::sync::{Arc, Barrier, Mutex, Once, ONCE_INIT};
 use std::thread;
@@ -9,17 +13,17 @@ use std::time::{Duration, Instant};
 use sys_info::hostname;
 use crate::timing;
 
-#[derive(Debug)]
 enum MetricsCommand {
     Submit(influxdb::Point),
     Flush(Arc<Barrier>),
+    SetWriter(Arc<MetricsWriter + Send + Sync>, Duration),
 }
 
 struct MetricsAgent {
     sender: Sender<MetricsCommand>,
 }
 
-trait MetricsWriter {
+pub trait MetricsWriter {
     // Write the points and empty the vector.  Called on the internal
     // MetricsAgent worker thread.
     fn write(&self, points: Vec<influxdb::Point>);
@@ -92,6 +96,8 @@ impl MetricsAgent {
         write_frequency: Duration,
     ) {
         trace!("run: enter");
+        let mut writer = writer.clone();
+        let mut write_frequency = write_frequency;
         let mut last_write_time = Instant::now();
         let mut points = Vec::new();
 
@@ -111,6 +117,16 @@ impl MetricsAgent {
                         debug!("run: submit {:?}", point);
                         points.push(point);
                     }
+                    MetricsCommand::SetWriter(new_writer, new_write_frequency) => {
+                        debug!("run: set writer");
+                        if !points.is_empty() {
+                            writer.write(points);
+                            points = Vec::new();
+                        }
+                        writer = new_writer;
+                        write_frequency = new_write_frequency;
+                        last_write_time = Instant::now();
+                    }
                 },
                 Err(RecvTimeoutError::Timeout) => {
                     trace!("run: receive timeout");
@@ -140,6 +156,12 @@ impl MetricsAgent {
         self.sender.send(MetricsCommand::Submit(point)).unwrap();
     }
 
+    pub fn set_writer(&self, writer: Arc<MetricsWriter + Send + Sync>, write_frequency: Duration) {
+        self.sender
+            .send(MetricsCommand::SetWriter(writer, write_frequency))
+            .unwrap();
+    }
+
     pub fn flush(&self) {
         debug!("Flush");
         let barrier = Arc::new(Barrier::new(2));
@@ -177,6 +199,35 @@ pub fn submit(point: influxdb::Point) {
     agent.submit(point);
 }
 
+/// Sends all points submitted from now on to `writer` instead of InfluxDB, in batches every
+/// `write_frequency`
+pub fn set_writer(writer: Arc<MetricsWriter + Send + Sync>, write_frequency: Duration) {
+    let agent_mutex = get_singleton_agent();
+    let agent = agent_mutex.lock().unwrap();
+    agent.set_writer(writer, write_frequency);
+}
+
+/// soros' Prometheus writer, naming metrics `buffett_<NAME>`
+struct BuffettPrometheusWriter(PrometheusMetricsWriter);
+
+impl MetricsWriter for BuffettPrometheusWriter {
+    fn write(&self, points: Vec<influxdb::Point>) {
+        soros_metrics::MetricsWriter::write(&self.0, points);
+    }
+}
+
+/// Serves metrics on `addr` for Prometheus to scrape instead of sending them to InfluxDB,
+/// returns the address actually bound
+pub fn set_prometheus_writer(addr: &SocketAddr) -> io::Result<SocketAddr> {
+    let writer = PrometheusMetricsWriter::with_prefix(addr, "buffett")?;
+    let local_addr = *writer.local_addr();
+    set_writer(
+        Arc::new(BuffettPrometheusWriter(writer)),
+        Duration::from_secs(1),
+    );
+    Ok(local_addr)
+}
+
 /// Blocks until all pending points from previous calls to `submit` have been
 /// transmitted.
 pub fn flush() {
@@ -322,6 +373,21 @@ mod test {
         assert_eq!(writer.points_written(), 42);
     }
 
+    #[test]
+    fn test_set_writer() {
+        let writer = Arc::new(MockMetricsWriter::new());
+        let new_writer = Arc::new(MockMetricsWriter::new());
+        let agent = MetricsAgent::new(writer.clone(), Duration::from_secs(10));
+
+        agent.submit(influxdb::Point::new("point 1"));
+        agent.set_writer(new_writer.clone(), Duration::from_secs(10));
+        agent.submit(influxdb::Point::new("point 2"));
+        agent.submit(influxdb::Point::new("point 3"));
+        agent.flush();
+        assert_eq!(writer.points_written(), 1);
+        assert_eq!(new_writer.points_written(), 2);
+    }
+
     #[test]
     fn test_flush_before_drop() {
         let writer = Arc::new(MockMetricsWriter::new());
@@ -0,0 +1,39 @@
+//! The `multi_sha256` module hashes many PoH chains at once, using the lockstep SHA-256 of
+//! `soros_sdk::multi_sha256` on buffett's hashes.
+
+use crate::hash::Hash;
+use soros_sdk::hash::Hash as SorosHash;
+use soros_sdk::multi_sha256;
+
+pub use soros_sdk::multi_sha256::LANES;
+
+/// Hash each of `start_hashes` the matching number of times in `num_hashes`
+pub fn hash_chains(start_hashes: &[Hash], num_hashes: &[u64]) -> Vec<Hash> {
+    let start_hashes: Vec<SorosHash> = start_hashes
+        .iter()
+        .map(|start_hash| SorosHash::new(start_hash.as_ref()))
+        .collect();
+    multi_sha256::hash_chains(&start_hashes, num_hashes)
+        .iter()
+        .map(|chain_hash| Hash::new(chain_hash.as_ref()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::hash;
+
+    #[test]
+    fn test_hash_chains() {
+        let start_hashes: Vec<Hash> = (0..2 * LANES as u8).map(|i| hash(&[i])).collect();
+        let num_hashes: Vec<u64> = (0..2 * LANES as u64).map(|i| i % 5).collect();
+        let expected: Vec<Hash> = start_hashes
+            .iter()
+            .zip(&num_hashes)
+            .map(|(start_hash, num_hashes)| {
+                (0..*num_hashes).fold(*start_hash, |current, _| hash(current.as_ref()))
+            }).collect();
+        assert_eq!(hash_chains(&start_hashes, &num_hashes), expected);
+    }
+}
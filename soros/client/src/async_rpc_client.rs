@@ -0,0 +1,512 @@
+//! The `async_rpc_client` module is a high throughput client of the JSON-RPC service. Requests
+//! made from any number of threads are collected into JSON-RPC batches, and each batch is sent
+//! without waiting on the responses to the ones before it, over connections that are reused
+//! across batches.
+
+use crate::async_rpc_client_request::AsyncRpcClientRequest;
+use crate::client_error::ClientError;
+use crate::generic_rpc_client_request::GenericAsyncRpcClientRequest;
+use crate::mock_rpc_client_request::MockRpcClientRequest;
+use crate::rpc_client::get_rpc_request_str;
+use crate::rpc_request::{RpcRequest, RpcResponse};
+use bincode::serialize;
+use futures::sync::oneshot;
+use futures::Future;
+use log::*;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use soros_sdk::account::Account;
+use soros_sdk::client::{AsyncClient, Client, SyncClient};
+use soros_sdk::hash::Hash;
+use soros_sdk::instruction::Instruction;
+use soros_sdk::message::Message;
+use soros_sdk::pubkey::Pubkey;
+use soros_sdk::signature::{Keypair, KeypairUtil, Signature};
+use soros_sdk::system_instruction;
+use soros_sdk::timing::{DEFAULT_TICKS_PER_SLOT, NUM_TICKS_PER_SECOND};
+use soros_sdk::transaction::{self, Transaction, TransactionError};
+use soros_sdk::transport::Result as TransportResult;
+use std::io;
+use std::mem::size_of;
+use std::net::SocketAddr;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+use std::thread::{sleep, Builder};
+use std::time::{Duration, Instant};
+use tokio::runtime::{Runtime, TaskExecutor};
+
+/// Most requests sent in a single JSON-RPC batch
+pub const MAX_BATCH_SIZE: usize = 100;
+
+/// Times a batch is resent if it can't be delivered
+const BATCH_RETRIES: usize = 5;
+
+/// Times `send_message` sends a transaction before giving up on it
+const SEND_RETRIES: usize = 5;
+
+/// Resolves to the result of a request made through an `AsyncRpcClient`
+pub type RpcFuture<T> = Box<dyn Future<Item = T, Error = ClientError> + Send>;
+
+type PendingRequest = (
+    RpcRequest,
+    Option<Value>,
+    oneshot::Sender<Result<Value, ClientError>>,
+);
+
+pub struct AsyncRpcClient {
+    url: String,
+    request_sender: Mutex<Sender<PendingRequest>>,
+}
+
+impl AsyncRpcClient {
+    pub fn new(url: String) -> Self {
+        Self::new_with_client(url.clone(), AsyncRpcClientRequest::new(url))
+    }
+
+    pub fn new_mock(url: String) -> Self {
+        Self::new_with_client(url.clone(), MockRpcClientRequest::new(url))
+    }
+
+    pub fn new_socket(addr: SocketAddr) -> Self {
+        Self::new(get_rpc_request_str(addr, false))
+    }
+
+    pub fn new_socket_with_timeout(addr: SocketAddr, timeout: Duration) -> Self {
+        let url = get_rpc_request_str(addr, false);
+        Self::new_with_client(
+            url.clone(),
+            AsyncRpcClientRequest::new_with_timeout(url, timeout),
+        )
+    }
+
+    fn new_with_client<T>(url: String, client: T) -> Self
+    where
+        T: GenericAsyncRpcClientRequest + Send + 'static,
+    {
+        let (request_sender, request_receiver) = channel();
+        Builder::new()
+            .name("soros-async-rpc-client".to_string())
+            .spawn(move || {
+                let runtime = Runtime::new().expect("rpc client runtime");
+                Self::run(&client, &runtime.executor(), &request_receiver);
+                // Let the batches already sent finish
+                runtime.shutdown_on_idle().wait().unwrap();
+            })
+            .unwrap();
+        Self {
+            url,
+            request_sender: Mutex::new(request_sender),
+        }
+    }
+
+    /// Batch up the pending requests until the client is dropped
+    fn run(
+        client: &dyn GenericAsyncRpcClientRequest,
+        executor: &TaskExecutor,
+        request_receiver: &Receiver<PendingRequest>,
+    ) {
+        while let Ok(pending_request) = request_receiver.recv() {
+            let mut pending_requests = vec![pending_request];
+            while pending_requests.len() < MAX_BATCH_SIZE {
+                match request_receiver.try_recv() {
+                    Ok(pending_request) => pending_requests.push(pending_request),
+                    Err(_) => break,
+                }
+            }
+
+            let (requests, senders): (Vec<_>, Vec<_>) = pending_requests
+                .into_iter()
+                .map(|(request, params, sender)| ((request, params), sender))
+                .unzip();
+            let batch = client
+                .send_batch(requests, BATCH_RETRIES)
+                .then(move |results| {
+                    match results {
+                        Ok(results) => {
+                            for (sender, result) in senders.into_iter().zip(results) {
+                                let _ = sender.send(result);
+                            }
+                        }
+                        Err(err) => {
+                            debug!("rpc batch failed: {:?}", err);
+                            for sender in senders {
+                                let _ = sender.send(Err(io::Error::new(
+                                    io::ErrorKind::Other,
+                                    format!("rpc batch failed: {:?}", err),
+                                )
+                                .into()));
+                            }
+                        }
+                    }
+                    Ok::<(), ()>(())
+                });
+            executor.spawn(batch);
+        }
+    }
+
+    /// Queue `request` for the next batch. The request is sent even if the returned future is
+    /// dropped. Don't wait on the future from a tokio runtime thread.
+    pub fn send(&self, request: RpcRequest, params: Option<Value>) -> RpcFuture<Value> {
+        let (sender, receiver) = oneshot::channel();
+        self.request_sender
+            .lock()
+            .unwrap()
+            .send((request, params, sender))
+            .unwrap();
+        Box::new(receiver.then(|result| {
+            result.unwrap_or_else(|_| {
+                Err(io::Error::new(io::ErrorKind::Other, "rpc request dropped").into())
+            })
+        }))
+    }
+
+    fn send_and_deserialize<T>(&self, request: RpcRequest, params: Option<Value>) -> RpcFuture<T>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let value = self.send(request, params);
+        Box::new(
+            value
+                .and_then(|value| -> Result<T, ClientError> { Ok(serde_json::from_value(value)?) }),
+        )
+    }
+
+    /// Like `send_and_deserialize` for the methods that respond with an `RpcResponse`
+    fn send_and_deserialize_value<T>(
+        &self,
+        request: RpcRequest,
+        params: Option<Value>,
+    ) -> RpcFuture<T>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let response = self.send_and_deserialize::<RpcResponse<T>>(request, params);
+        Box::new(response.map(|response| response.value))
+    }
+
+    /// Send `transaction`, resolves to its signature once the server accepts it
+    pub fn send_transaction(&self, transaction: &Transaction) -> RpcFuture<Signature> {
+        let signature = transaction.signatures.get(0).cloned().unwrap_or_default();
+        let params = json!([serialize(transaction).unwrap()]);
+        Box::new(
+            self.send_and_deserialize::<String>(RpcRequest::SendTransaction, Some(params))
+                .map(move |_| signature),
+        )
+    }
+
+    pub fn async_get_account(&self, pubkey: &Pubkey) -> RpcFuture<Account> {
+        let params = json!([pubkey.to_string()]);
+        self.send_and_deserialize_value(RpcRequest::GetAccountInfo, Some(params))
+    }
+
+    pub fn async_get_balance(&self, pubkey: &Pubkey) -> RpcFuture<u64> {
+        let params = json!([pubkey.to_string()]);
+        self.send_and_deserialize_value(RpcRequest::GetBalance, Some(params))
+    }
+
+    pub fn async_get_signature_status(
+        &self,
+        signature: &Signature,
+    ) -> RpcFuture<Option<transaction::Result<()>>> {
+        let params = json!([signature.to_string()]);
+        self.send_and_deserialize_value(RpcRequest::GetSignatureStatus, Some(params))
+    }
+
+    pub fn async_get_recent_blockhash(&self) -> RpcFuture<Hash> {
+        let blockhash = self.send_and_deserialize::<String>(RpcRequest::GetRecentBlockhash, None);
+        Box::new(
+            blockhash.and_then(|blockhash| -> Result<Hash, ClientError> {
+                let blockhash = bs58::decode(blockhash).into_vec().unwrap_or_default();
+                if blockhash.len() != size_of::<Hash>() {
+                    Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        "Received an invalid blockhash",
+                    ))?;
+                }
+                Ok(Hash::new(&blockhash))
+            }),
+        )
+    }
+
+    pub fn async_get_transaction_count(&self) -> RpcFuture<u64> {
+        self.send_and_deserialize(RpcRequest::GetTransactionCount, None)
+    }
+
+    pub fn async_confirm_transaction(&self, signature: &Signature) -> RpcFuture<bool> {
+        let params = json!([signature.to_string()]);
+        self.send_and_deserialize(RpcRequest::ConfirmTransaction, Some(params))
+    }
+
+    pub fn async_get_num_blocks_since_signature_confirmation(
+        &self,
+        signature: &Signature,
+    ) -> RpcFuture<usize> {
+        let params = json!([signature.to_string()]);
+        self.send_and_deserialize(
+            RpcRequest::GetNumBlocksSinceSignatureConfirmation,
+            Some(params),
+        )
+    }
+
+    /// Wait for the server to have a status for `signature`, `None` if it doesn't show up
+    /// within about two slots
+    fn poll_for_signature_status(
+        &self,
+        signature: &Signature,
+    ) -> TransportResult<Option<transaction::Result<()>>> {
+        for _ in 0..4 {
+            let status = self.async_get_signature_status(signature).wait()?;
+            if status.is_some() {
+                return Ok(status);
+            }
+            if cfg!(not(test)) {
+                // Retry ~twice during a slot
+                sleep(Duration::from_millis(
+                    500 * DEFAULT_TICKS_PER_SLOT / NUM_TICKS_PER_SECOND,
+                ));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Client for AsyncRpcClient {
+    fn transactions_addr(&self) -> String {
+        self.url.clone()
+    }
+}
+
+impl SyncClient for AsyncRpcClient {
+    fn send_message(&self, keypairs: &[&Keypair], message: Message) -> TransportResult<Signature> {
+        let blockhash = self.async_get_recent_blockhash().wait()?;
+        let mut transaction = Transaction::new(&keypairs, message, blockhash);
+        for _ in 0..SEND_RETRIES {
+            let signature = self.send_transaction(&transaction).wait()?;
+            match self.poll_for_signature_status(&signature)? {
+                Some(Ok(())) => return Ok(signature),
+                Some(Err(TransactionError::AccountInUse)) | None => {
+                    info!("transaction {} not processed, retrying", signature);
+                    let blockhash = self.async_get_recent_blockhash().wait()?;
+                    transaction.sign(keypairs, blockhash);
+                }
+                Some(Err(err)) => Err(err)?,
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("send_message failed in {} retries", SEND_RETRIES),
+        ))?
+    }
+
+    fn send_instruction(
+        &self,
+        keypair: &Keypair,
+        instruction: Instruction,
+    ) -> TransportResult<Signature> {
+        let message = Message::new(vec![instruction]);
+        self.send_message(&[keypair], message)
+    }
+
+    fn transfer(&self, dif: u64, keypair: &Keypair, pubkey: &Pubkey) -> TransportResult<Signature> {
+        let transfer_instruction = system_instruction::transfer(&keypair.pubkey(), pubkey, dif);
+        self.send_instruction(keypair, transfer_instruction)
+    }
+
+    fn get_account_data(&self, pubkey: &Pubkey) -> TransportResult<Option<Vec<u8>>> {
+        let account = self.async_get_account(pubkey).wait().ok();
+        Ok(account.map(|account| account.data))
+    }
+
+    fn get_balance(&self, pubkey: &Pubkey) -> TransportResult<u64> {
+        Ok(self.async_get_balance(pubkey).wait()?)
+    }
+
+    fn get_signature_status(
+        &self,
+        signature: &Signature,
+    ) -> TransportResult<Option<transaction::Result<()>>> {
+        Ok(self.async_get_signature_status(signature).wait()?)
+    }
+
+    fn get_recent_blockhash(&self) -> TransportResult<Hash> {
+        Ok(self.async_get_recent_blockhash().wait()?)
+    }
+
+    fn get_transaction_count(&self) -> TransportResult<u64> {
+        Ok(self.async_get_transaction_count().wait()?)
+    }
+
+    /// Poll the server until the signature has been confirmed by at least `min_confirmed_blocks`
+    fn poll_for_signature_confirmation(
+        &self,
+        signature: &Signature,
+        min_confirmed_blocks: usize,
+    ) -> TransportResult<()> {
+        let mut now = Instant::now();
+        let mut confirmed_blocks = 0;
+        loop {
+            let response = self
+                .async_get_num_blocks_since_signature_confirmation(signature)
+                .wait();
+            match response {
+                Ok(count) => {
+                    if confirmed_blocks != count {
+                        now = Instant::now();
+                        confirmed_blocks = count;
+                    }
+                    if count >= min_confirmed_blocks {
+                        return Ok(());
+                    }
+                }
+                Err(err) => {
+                    debug!("check_confirmations request failed: {:?}", err);
+                }
+            }
+            if now.elapsed().as_secs() > 15 {
+                Err(io::Error::new(io::ErrorKind::Other, "signature not found"))?;
+            }
+            sleep(Duration::from_millis(250));
+        }
+    }
+
+    fn poll_for_signature(&self, signature: &Signature) -> TransportResult<()> {
+        let now = Instant::now();
+        loop {
+            match self.async_confirm_transaction(signature).wait() {
+                Ok(true) => return Ok(()),
+                Ok(false) => (),
+                Err(err) => debug!("check_signature request failed: {:?}", err),
+            }
+            if now.elapsed().as_secs() > 15 {
+                Err(io::Error::new(io::ErrorKind::Other, "signature not found"))?;
+            }
+            sleep(Duration::from_millis(250));
+        }
+    }
+}
+
+impl AsyncClient for AsyncRpcClient {
+    fn async_send_transaction(&self, transaction: Transaction) -> io::Result<Signature> {
+        let signature = transaction.signatures.get(0).cloned().unwrap_or_default();
+        let params = json!([serialize(&transaction).unwrap()]);
+        // Queued for the next batch, the server's response is dropped
+        let _ = self.send(RpcRequest::SendTransaction, Some(params));
+        Ok(signature)
+    }
+
+    fn async_send_message(
+        &self,
+        keypairs: &[&Keypair],
+        message: Message,
+        recent_blockhash: Hash,
+    ) -> io::Result<Signature> {
+        let transaction = Transaction::new(&keypairs, message, recent_blockhash);
+        self.async_send_transaction(transaction)
+    }
+
+    fn async_send_instruction(
+        &self,
+        keypair: &Keypair,
+        instruction: Instruction,
+        recent_blockhash: Hash,
+    ) -> io::Result<Signature> {
+        let message = Message::new(vec![instruction]);
+        self.async_send_message(&[keypair], message, recent_blockhash)
+    }
+
+    fn async_transfer(
+        &self,
+        dif: u64,
+        keypair: &Keypair,
+        pubkey: &Pubkey,
+        recent_blockhash: Hash,
+    ) -> io::Result<Signature> {
+        let transfer_instruction = system_instruction::transfer(&keypair.pubkey(), pubkey, dif);
+        self.async_send_instruction(keypair, transfer_instruction, recent_blockhash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_rpc_client_request::PUBKEY;
+    use futures::future;
+    use soros_sdk::system_transaction;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_async_rpc_client_sync_client() {
+        let rpc_client = AsyncRpcClient::new_mock("succeeds".to_string());
+        let pubkey = Pubkey::new_rand();
+        let expected_blockhash = Hash::new(&bs58::decode(PUBKEY).into_vec().unwrap());
+
+        assert_eq!(rpc_client.get_balance(&pubkey).unwrap(), 50);
+        assert_eq!(rpc_client.get_transaction_count().unwrap(), 1234);
+        assert_eq!(
+            rpc_client.get_recent_blockhash().unwrap(),
+            expected_blockhash
+        );
+        assert!(rpc_client.get_account_data(&pubkey).unwrap().is_some());
+
+        let signature = Signature::default();
+        let status = rpc_client.get_signature_status(&signature).unwrap();
+        assert_eq!(status, Some(Ok(())));
+
+        let rpc_client = AsyncRpcClient::new_mock("fails".to_string());
+        assert!(rpc_client.get_balance(&pubkey).is_err());
+        assert!(rpc_client.get_recent_blockhash().is_err());
+        assert_eq!(rpc_client.get_account_data(&pubkey).unwrap(), None);
+    }
+
+    #[test]
+    fn test_async_rpc_client_send_message() {
+        let keypair = Keypair::new();
+        let to = Pubkey::new_rand();
+
+        let rpc_client = AsyncRpcClient::new_mock("succeeds".to_string());
+        let signature = rpc_client.transfer(50, &keypair, &to).unwrap();
+        assert_ne!(signature, Signature::default());
+
+        let rpc_client = AsyncRpcClient::new_mock("account_in_use".to_string());
+        assert!(rpc_client.transfer(50, &keypair, &to).is_err());
+
+        let rpc_client = AsyncRpcClient::new_mock("fails".to_string());
+        assert!(rpc_client.transfer(50, &keypair, &to).is_err());
+    }
+
+    #[test]
+    fn test_async_rpc_client_async_send_transaction() {
+        let rpc_client = AsyncRpcClient::new_mock("succeeds".to_string());
+        let keypair = Keypair::new();
+        let to = Pubkey::new_rand();
+        let tx = system_transaction::create_user_account(&keypair, &to, 50, Hash::default(), 0);
+
+        let signature = rpc_client.async_send_transaction(tx.clone()).unwrap();
+        assert_eq!(signature, tx.signatures[0]);
+        assert_eq!(rpc_client.send_transaction(&tx).wait().unwrap(), signature);
+    }
+
+    #[test]
+    fn test_async_rpc_client_batches_concurrent_requests() {
+        let rpc_client = Arc::new(AsyncRpcClient::new_mock("succeeds".to_string()));
+
+        let balances: Vec<_> = (0..MAX_BATCH_SIZE * 3)
+            .map(|_| rpc_client.async_get_balance(&Pubkey::new_rand()))
+            .collect();
+        assert_eq!(
+            future::join_all(balances).wait().unwrap(),
+            vec![50; MAX_BATCH_SIZE * 3]
+        );
+
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let rpc_client = rpc_client.clone();
+                thread::spawn(move || rpc_client.get_transaction_count().unwrap())
+            })
+            .collect();
+        for thread in threads {
+            assert_eq!(thread.join().unwrap(), 1234);
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use crate::client_error::ClientError;
+use crate::generic_rpc_client_request::{GenericAsyncRpcClientRequest, RpcBatchFuture};
+use crate::rpc_request::{RpcError, RpcRequest};
+use futures::{future, Future};
+use log::*;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::r#async::Client;
+use serde_json::Value;
+use soros_sdk::timing::{DEFAULT_TICKS_PER_SLOT, NUM_TICKS_PER_SECOND};
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tokio::timer::Delay;
+
+/// Wait before the first retry of a batch, doubled on each retry after that
+const INITIAL_BACKOFF_MS: u64 = 50;
+
+/// Longest wait between retries, approximately half a slot
+const MAX_BACKOFF_MS: u64 = 500 * DEFAULT_TICKS_PER_SLOT / NUM_TICKS_PER_SECOND;
+
+type ResponseFuture = Box<dyn Future<Item = Value, Error = ClientError> + Send>;
+
+/// Sends JSON-RPC batches over HTTP, the connections are kept alive and reused by all batches.
+/// Has to be used from within a tokio runtime.
+pub struct AsyncRpcClientRequest {
+    client: Client,
+    url: String,
+    next_request_id: AtomicUsize,
+}
+
+impl AsyncRpcClientRequest {
+    pub fn new(url: String) -> Self {
+        Self {
+            client: Client::new(),
+            url,
+            next_request_id: AtomicUsize::new(1),
+        }
+    }
+
+    pub fn new_with_timeout(url: String, timeout: Duration) -> Self {
+        let client = Client::builder()
+            .timeout(timeout)
+            .build()
+            .expect("build async rpc client");
+
+        Self {
+            client,
+            url,
+            next_request_id: AtomicUsize::new(1),
+        }
+    }
+}
+
+fn post_with_backoff(
+    client: Client,
+    url: String,
+    body: String,
+    retries: usize,
+    backoff: Duration,
+) -> ResponseFuture {
+    let response = client
+        .post(&url)
+        .header(CONTENT_TYPE, "application/json")
+        .body(body.clone())
+        .send()
+        .and_then(|mut response| response.json::<Value>());
+
+    Box::new(response.or_else(move |err| -> ResponseFuture {
+        if retries == 0 {
+            return Box::new(future::err(err.into()));
+        }
+        info!(
+            "rpc batch to {} failed, {} retries left: {:?}",
+            url, retries, err
+        );
+        let next_backoff = (backoff * 2).min(Duration::from_millis(MAX_BACKOFF_MS));
+        let retry = Delay::new(Instant::now() + backoff)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()).into())
+            .and_then(move |_| post_with_backoff(client, url, body, retries - 1, next_backoff));
+        Box::new(retry)
+    }))
+}
+
+fn response_result(response: Value) -> Result<Value, ClientError> {
+    if response["error"].is_object() {
+        Err(RpcError::RpcRequestError(format!(
+            "RPC Error response: {}",
+            serde_json::to_string(&response["error"]).unwrap()
+        )))?
+    }
+    Ok(response["result"].clone())
+}
+
+/// Match the responses of a batch to the `request_ids` it was sent with, a server may answer
+/// the requests of a batch in any order
+fn batch_results(
+    request_ids: &[u64],
+    response: Value,
+) -> Result<Vec<Result<Value, ClientError>>, ClientError> {
+    let responses = match response {
+        Value::Array(responses) => responses,
+        // The whole batch was rejected
+        response => {
+            let err = response_result(response).err().unwrap_or_else(|| {
+                RpcError::RpcRequestError("Unexpected batch response".to_string()).into()
+            });
+            return Err(err);
+        }
+    };
+
+    let mut responses: HashMap<_, _> = responses
+        .into_iter()
+        .filter_map(|response| Some((response["id"].as_u64()?, response)))
+        .collect();
+
+    Ok(request_ids
+        .iter()
+        .map(|request_id| match responses.remove(request_id) {
+            Some(response) => response_result(response),
+            None => Err(RpcError::RpcRequestError(format!(
+                "No response to request {}",
+                request_id
+            ))
+            .into()),
+        })
+        .collect())
+}
+
+impl GenericAsyncRpcClientRequest for AsyncRpcClientRequest {
+    fn send_batch(
+        &self,
+        requests: Vec<(RpcRequest, Option<Value>)>,
+        retries: usize,
+    ) -> RpcBatchFuture {
+        let first_request_id = self
+            .next_request_id
+            .fetch_add(requests.len(), Ordering::Relaxed) as u64;
+
+        let mut request_ids = vec![];
+        let batch: Vec<_> = requests
+            .into_iter()
+            .enumerate()
+            .map(|(i, (request, params))| {
+                let request_id = first_request_id + i as u64;
+                request_ids.push(request_id);
+                request.build_request_json(request_id, params)
+            })
+            .collect();
+
+        let response = post_with_backoff(
+            self.client.clone(),
+            self.url.clone(),
+            Value::Array(batch).to_string(),
+            retries,
+            Duration::from_millis(INITIAL_BACKOFF_MS),
+        );
+        Box::new(response.and_then(move |response| batch_results(&request_ids, response)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_batch_results() {
+        let response = json!([
+            {"jsonrpc": "2.0", "id": 3, "result": 50},
+            {"jsonrpc": "2.0", "id": 1, "result": "blockhash"},
+            {"jsonrpc": "2.0", "id": 2, "error": {"code": -32602, "message": "Invalid"}}
+        ]);
+        let results = batch_results(&[1, 2, 3, 4], response).unwrap();
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].as_ref().unwrap(), &json!("blockhash"));
+        assert!(results[1].is_err());
+        assert_eq!(results[2].as_ref().unwrap(), &json!(50));
+        assert!(results[3].is_err());
+
+        let response = json!({"jsonrpc": "2.0", "id": null, "error": {"code": -32600}});
+        assert!(batch_results(&[1], response).is_err());
+        assert!(batch_results(&[1], json!("not a batch")).is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use crate::rpc_request;
 use soros_sdk::transaction::TransactionError;
+use soros_sdk::transport::TransportError;
 use std::{fmt, io};
 
 #[derive(Debug)]
@@ -48,3 +49,15 @@ impl From<TransactionError> for ClientError {
         ClientError::TransactionError(err)
     }
 }
+
+impl From<ClientError> for TransportError {
+    fn from(err: ClientError) -> TransportError {
+        match err {
+            ClientError::Io(err) => TransportError::IoError(err),
+            ClientError::TransactionError(err) => TransportError::TransactionError(err),
+            err => {
+                TransportError::IoError(io::Error::new(io::ErrorKind::Other, format!("{:?}", err)))
+            }
+        }
+    }
+}
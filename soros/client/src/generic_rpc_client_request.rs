@@ -1,5 +1,6 @@
 use crate::client_error::ClientError;
 use crate::rpc_request::RpcRequest;
+use futures::Future;
 
 pub(crate) trait GenericRpcClientRequest {
     fn send(
@@ -9,3 +10,17 @@ pub(crate) trait GenericRpcClientRequest {
         retries: usize,
     ) -> Result<serde_json::Value, ClientError>;
 }
+
+/// Resolves to the result of each request of a batch, in request order
+pub(crate) type RpcBatchFuture =
+    Box<dyn Future<Item = Vec<Result<serde_json::Value, ClientError>>, Error = ClientError> + Send>;
+
+pub(crate) trait GenericAsyncRpcClientRequest {
+    /// Send `requests` as a single JSON-RPC batch, retrying up to `retries` times if the
+    /// batch can't be delivered
+    fn send_batch(
+        &self,
+        requests: Vec<(RpcRequest, Option<serde_json::Value>)>,
+        retries: usize,
+    ) -> RpcBatchFuture;
+}
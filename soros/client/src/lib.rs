@@ -1,7 +1,10 @@
+pub mod async_rpc_client;
+pub mod async_rpc_client_request;
 pub mod client_error;
 mod generic_rpc_client_request;
 pub mod mock_rpc_client_request;
 pub mod rpc_client;
 pub mod rpc_client_request;
+pub mod rpc_filter;
 pub mod rpc_request;
 pub mod thin_client;
@@ -1,12 +1,22 @@
 use crate::client_error::ClientError;
-use crate::generic_rpc_client_request::GenericRpcClientRequest;
-use crate::rpc_request::RpcRequest;
+use crate::generic_rpc_client_request::{
+    GenericAsyncRpcClientRequest, GenericRpcClientRequest, RpcBatchFuture,
+};
+use crate::rpc_filter::{RpcAccount, RpcKeyedAccount, RpcProgramAccountsConfig};
+use crate::rpc_request::{RpcRequest, RpcResponse, RpcSnapshot};
+use futures::future;
 use serde_json::{Number, Value};
+use soros_sdk::account::Account;
+use soros_sdk::hash::Hash;
+use soros_sdk::nonce_state::NonceState;
+use soros_sdk::pubkey::Pubkey;
+use soros_sdk::system_program;
 use soros_sdk::transaction::{self, TransactionError};
 
 pub const PUBKEY: &str = "7RoSF9fUmdphVCpabEoefH81WwrW7orsWonXWqTXkKV8";
 pub const SIGNATURE: &str =
     "43yNSFC6fYTuPgTNFFhF4axw7AfWxB2BPdurme8yrsWEYwm8299xh8n6TAHjGymiSub1XtyxTNyd9GBfY2hxoBw8";
+pub const SLOT: u64 = 1;
 
 pub struct MockRpcClientRequest {
     url: String,
@@ -40,9 +50,35 @@ impl GenericRpcClientRequest for MockRpcClientRequest {
                     Value::Null
                 }
             }
+            RpcRequest::GetAccountInfo => {
+                let space = NonceState::size() as usize;
+                let mut account = Account::new(50, space, &system_program::id());
+                if self.url == "nonce" {
+                    let nonce = Hash::new(&bs58::decode(PUBKEY).into_vec().unwrap());
+                    let state = NonceState::Initialized {
+                        authority: Pubkey::default(),
+                        nonce,
+                    };
+                    account.serialize_data(&state).unwrap();
+                }
+                serde_json::to_value(RpcResponse::new(SLOT, account)).unwrap()
+            }
             RpcRequest::GetBalance => {
                 let n = if self.url == "airdrop" { 0 } else { 50 };
-                Value::Number(Number::from(n))
+                serde_json::to_value(RpcResponse::new(SLOT, Value::Number(Number::from(n))))
+                    .unwrap()
+            }
+            RpcRequest::GetProgramAccounts => {
+                let config: RpcProgramAccountsConfig = params
+                    .and_then(|params| serde_json::from_value(params[1].clone()).ok())
+                    .unwrap_or_default();
+                let account = Account::new(50, 4, &system_program::id());
+                let encoding = config.encoding.unwrap_or_default();
+                let keyed_account = RpcKeyedAccount {
+                    pubkey: PUBKEY.to_string(),
+                    account: RpcAccount::encode(&account, encoding).unwrap(),
+                };
+                serde_json::to_value(vec![keyed_account]).unwrap()
             }
             RpcRequest::GetRecentBlockhash => Value::String(PUBKEY.to_string()),
             RpcRequest::GetSignatureStatus => {
@@ -53,8 +89,13 @@ impl GenericRpcClientRequest for MockRpcClientRequest {
                 } else {
                     Some(Ok(()))
                 };
-                serde_json::to_value(response).unwrap()
+                serde_json::to_value(RpcResponse::new(SLOT, response)).unwrap()
             }
+            RpcRequest::GetSnapshot => serde_json::to_value(RpcSnapshot {
+                slot: SLOT,
+                snapshot: base64::encode(&[1, 2, 3]),
+            })
+            .unwrap(),
             RpcRequest::GetTransactionCount => Value::Number(Number::from(1234)),
             RpcRequest::SendTransaction => Value::String(SIGNATURE.to_string()),
             _ => Value::Null,
@@ -62,3 +103,17 @@ impl GenericRpcClientRequest for MockRpcClientRequest {
         Ok(val)
     }
 }
+
+impl GenericAsyncRpcClientRequest for MockRpcClientRequest {
+    fn send_batch(
+        &self,
+        requests: Vec<(RpcRequest, Option<serde_json::Value>)>,
+        retries: usize,
+    ) -> RpcBatchFuture {
+        let results = requests
+            .into_iter()
+            .map(|(request, params)| self.send(&request, params, retries))
+            .collect();
+        Box::new(future::ok(results))
+    }
+}
@@ -2,13 +2,19 @@ use crate::client_error::ClientError;
 use crate::generic_rpc_client_request::GenericRpcClientRequest;
 use crate::mock_rpc_client_request::MockRpcClientRequest;
 use crate::rpc_client_request::RpcClientRequest;
-use crate::rpc_request::RpcRequest;
+use crate::rpc_filter::{RpcKeyedAccount, RpcProgramAccountsConfig, MAX_PROGRAM_ACCOUNTS};
+use crate::rpc_request::{
+    RpcEpochInfo, RpcLeaderSchedule, RpcRequest, RpcResponse, RpcSnapshot, RpcVoteAccountStatus,
+};
 use bincode::serialize;
 use bs58;
 use log::*;
 use serde_json::{json, Value};
 use soros_sdk::account::Account;
+use soros_sdk::commitment_config::CommitmentConfig;
+use soros_sdk::epoch_schedule::EpochSchedule;
 use soros_sdk::hash::Hash;
+use soros_sdk::nonce_state::NonceState;
 use soros_sdk::pubkey::Pubkey;
 use soros_sdk::signature::{Keypair, KeypairUtil, Signature};
 use soros_sdk::timing::{DEFAULT_TICKS_PER_SLOT, NUM_TICKS_PER_SECOND};
@@ -16,6 +22,7 @@ use soros_sdk::transaction::{self, Transaction, TransactionError};
 use std::error;
 use std::io;
 use std::net::SocketAddr;
+use std::str::FromStr;
 use std::thread::sleep;
 use std::time::{Duration, Instant};
 
@@ -70,15 +77,37 @@ impl RpcClient {
         let signature_status =
             self.client
                 .send(&RpcRequest::GetSignatureStatus, Some(params), 5)?;
-        let result: Option<transaction::Result<()>> =
+        let response: RpcResponse<Option<transaction::Result<()>>> =
             serde_json::from_value(signature_status).unwrap();
-        Ok(result)
+        Ok(response.value)
+    }
+
+    /// Request the status of `signature` in the bank `commitment_config` selects
+    pub fn get_signature_status_with_commitment(
+        &self,
+        signature: &str,
+        commitment_config: CommitmentConfig,
+    ) -> Result<RpcResponse<Option<transaction::Result<()>>>, ClientError> {
+        let params = json!([signature.to_string(), commitment_config]);
+        let signature_status =
+            self.client
+                .send(&RpcRequest::GetSignatureStatus, Some(params), 5)?;
+        Ok(serde_json::from_value(signature_status)?)
     }
 
     pub fn send_and_confirm_transaction<T: KeypairUtil>(
         &self,
         transaction: &mut Transaction,
         signer: &T,
+    ) -> Result<String, ClientError> {
+        self.send_and_confirm_transaction_with_signers(transaction, &[signer])
+    }
+
+    /// Like `send_and_confirm_transaction`, for transactions that need more than one signature
+    pub fn send_and_confirm_transaction_with_signers<T: KeypairUtil>(
+        &self,
+        transaction: &mut Transaction,
+        signer_keys: &[&T],
     ) -> Result<String, ClientError> {
         let mut send_retries = 5;
         loop {
@@ -106,7 +135,7 @@ impl RpcClient {
                     Ok(_) => return Ok(signature_str),
                     Err(TransactionError::AccountInUse) => {
                         // Fetch a new blockhash and re-sign the transaction before sending it again
-                        self.resign_transaction(transaction, signer)?;
+                        self.resign_transaction_with_signers(transaction, signer_keys)?;
                         send_retries - 1
                     }
                     Err(_) => 0,
@@ -203,9 +232,17 @@ impl RpcClient {
         &self,
         tx: &mut Transaction,
         signer_key: &T,
+    ) -> Result<(), ClientError> {
+        self.resign_transaction_with_signers(tx, &[signer_key])
+    }
+
+    pub fn resign_transaction_with_signers<T: KeypairUtil>(
+        &self,
+        tx: &mut Transaction,
+        signer_keys: &[&T],
     ) -> Result<(), ClientError> {
         let blockhash = self.get_new_blockhash(&tx.message().recent_blockhash)?;
-        tx.sign(&[signer_key], blockhash);
+        tx.sign(signer_keys, blockhash);
         Ok(())
     }
 
@@ -217,7 +254,7 @@ impl RpcClient {
         let params = json!([format!("{}", pubkey)]);
         let res = self
             .client
-            .send(&RpcRequest::GetBalance, Some(params), retries)?
+            .send(&RpcRequest::GetBalance, Some(params), retries)?["value"]
             .as_u64();
         Ok(res)
     }
@@ -229,9 +266,9 @@ impl RpcClient {
             .send(&RpcRequest::GetAccountInfo, Some(params), 0);
         match response {
             Ok(account_json) => {
-                let account: Account =
+                let response: RpcResponse<Account> =
                     serde_json::from_value(account_json).expect("deserialize account");
-                Ok(account.data)
+                Ok(response.value.data)
             }
             Err(error) => {
                 debug!("get_account_data failed: {:?}", error);
@@ -243,6 +280,109 @@ impl RpcClient {
         }
     }
 
+    pub fn get_account(&self, pubkey: &Pubkey) -> io::Result<Account> {
+        self.get_account_with_commitment(pubkey, CommitmentConfig::default())
+            .map(|response| response.value)
+    }
+
+    /// Request the account at `pubkey` from the bank `commitment_config` selects, the
+    /// response has the slot of that bank
+    pub fn get_account_with_commitment(
+        &self,
+        pubkey: &Pubkey,
+        commitment_config: CommitmentConfig,
+    ) -> io::Result<RpcResponse<Account>> {
+        let params = json!([format!("{}", pubkey), commitment_config]);
+        let response = self
+            .client
+            .send(&RpcRequest::GetAccountInfo, Some(params), 0);
+        response
+            .and_then(|account_json| Ok(serde_json::from_value(account_json)?))
+            .map_err(|error| {
+                debug!("get_account failed: {:?}", error);
+                io::Error::new(io::ErrorKind::Other, "AccountNotFound")
+            })
+    }
+
+    /// Request the balance of `pubkey` in the bank `commitment_config` selects
+    pub fn get_balance_with_commitment(
+        &self,
+        pubkey: &Pubkey,
+        commitment_config: CommitmentConfig,
+    ) -> io::Result<RpcResponse<u64>> {
+        let params = json!([format!("{}", pubkey), commitment_config]);
+        let response = self.client.send(&RpcRequest::GetBalance, Some(params), 0);
+        response
+            .and_then(|balance_json| Ok(serde_json::from_value(balance_json)?))
+            .map_err(|error| {
+                debug!("get_balance_with_commitment failed: {:?}", error);
+                io::Error::new(io::ErrorKind::Other, "AccountNotFound")
+            })
+    }
+
+    /// Request all the accounts `program_id` owns, a page at a time
+    pub fn get_program_accounts(&self, program_id: &Pubkey) -> io::Result<Vec<(Pubkey, Account)>> {
+        let mut accounts: Vec<(Pubkey, Account)> = vec![];
+        loop {
+            let config = RpcProgramAccountsConfig {
+                after: accounts.last().map(|(pubkey, _)| pubkey.to_string()),
+                ..RpcProgramAccountsConfig::default()
+            };
+            let page = self.get_program_accounts_with_config(program_id, config)?;
+            let last_page = page.len() < MAX_PROGRAM_ACCOUNTS;
+            accounts.extend(page);
+            if last_page {
+                return Ok(accounts);
+            }
+        }
+    }
+
+    /// Request a page of the accounts `program_id` owns that pass the filters of `config`, in
+    /// pubkey order
+    pub fn get_program_accounts_with_config(
+        &self,
+        program_id: &Pubkey,
+        config: RpcProgramAccountsConfig,
+    ) -> io::Result<Vec<(Pubkey, Account)>> {
+        let encoding = config.encoding.unwrap_or_default();
+        let params = json!([format!("{}", program_id), config]);
+        let response = self
+            .client
+            .send(&RpcRequest::GetProgramAccounts, Some(params), 0)
+            .map_err(|error| {
+                debug!("get_program_accounts failed: {:?}", error);
+                io::Error::new(io::ErrorKind::Other, "get_program_accounts failed")
+            })?;
+        let keyed_accounts: Vec<RpcKeyedAccount> = serde_json::from_value(response)?;
+        keyed_accounts
+            .into_iter()
+            .map(|keyed_account| {
+                let pubkey = Pubkey::from_str(&keyed_account.pubkey);
+                let account = keyed_account.account.decode(encoding);
+                match (pubkey, account) {
+                    (Ok(pubkey), Some(account)) => Ok((pubkey, account)),
+                    _ => Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        "invalid getProgramAccounts response",
+                    )),
+                }
+            })
+            .collect()
+    }
+
+    /// Request the current nonce of the nonce account at `nonce_pubkey`, a transaction
+    /// signed with it in place of a recent blockhash stays valid until the nonce advances
+    pub fn get_nonce(&self, nonce_pubkey: &Pubkey) -> io::Result<Hash> {
+        let account = self.get_account(nonce_pubkey)?;
+        match NonceState::from(&account) {
+            Some(NonceState::Initialized { nonce, .. }) => Ok(nonce),
+            _ => Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("{} is not an initialized nonce account", nonce_pubkey),
+            )),
+        }
+    }
+
     /// Request the balance of the user holding `pubkey`. This method blocks
     /// until the server sends a response. If the response packet is dropped
     /// by the network, this method will hang indefinitely.
@@ -254,8 +394,9 @@ impl RpcClient {
 
         response
             .and_then(|account_json| {
-                let account: Account =
+                let response: RpcResponse<Account> =
                     serde_json::from_value(account_json).expect("deserialize account");
+                let account = response.value;
                 trace!("Response account {:?} {:?}", pubkey, account);
                 //trace!("get_balance {:?}", account.lamports);
                 trace!("get_balance {:?}", account.dif);
@@ -268,6 +409,75 @@ impl RpcClient {
             })
     }
 
+    /// Request the latest snapshot of the node's root bank, its slot and the serialized
+    /// `BankSnapshot`. `None` if the node doesn't take snapshots.
+    pub fn get_snapshot(&self) -> io::Result<Option<(u64, Vec<u8>)>> {
+        let response = self
+            .client
+            .send(&RpcRequest::GetSnapshot, None, 0)
+            .map_err(|error| {
+                debug!("get_snapshot failed: {:?}", error);
+                io::Error::new(io::ErrorKind::Other, "get_snapshot failed")
+            })?;
+        let snapshot: Option<RpcSnapshot> = serde_json::from_value(response)?;
+        match snapshot {
+            Some(RpcSnapshot { slot, snapshot }) => {
+                let snapshot = base64::decode(&snapshot).map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData, "invalid base64 snapshot")
+                })?;
+                Ok(Some((slot, snapshot)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_epoch_info(&self) -> io::Result<RpcEpochInfo> {
+        let response = self
+            .client
+            .send(&RpcRequest::GetEpochInfo, None, 0)
+            .map_err(|error| {
+                debug!("get_epoch_info failed: {:?}", error);
+                io::Error::new(io::ErrorKind::Other, "get_epoch_info failed")
+            })?;
+        Ok(serde_json::from_value(response)?)
+    }
+
+    pub fn get_epoch_schedule(&self) -> io::Result<EpochSchedule> {
+        let response = self
+            .client
+            .send(&RpcRequest::GetEpochSchedule, None, 0)
+            .map_err(|error| {
+                debug!("get_epoch_schedule failed: {:?}", error);
+                io::Error::new(io::ErrorKind::Other, "get_epoch_schedule failed")
+            })?;
+        Ok(serde_json::from_value(response)?)
+    }
+
+    /// Request the leader schedule of `epoch`, the current epoch if `None`.  Only the epochs the
+    /// node has stakes for have a schedule.
+    pub fn get_leader_schedule(&self, epoch: Option<u64>) -> io::Result<Option<RpcLeaderSchedule>> {
+        let params = json!([epoch]);
+        let response = self
+            .client
+            .send(&RpcRequest::GetLeaderSchedule, Some(params), 0)
+            .map_err(|error| {
+                debug!("get_leader_schedule failed: {:?}", error);
+                io::Error::new(io::ErrorKind::Other, "get_leader_schedule failed")
+            })?;
+        Ok(serde_json::from_value(response)?)
+    }
+
+    pub fn get_vote_accounts(&self) -> io::Result<RpcVoteAccountStatus> {
+        let response = self
+            .client
+            .send(&RpcRequest::GetVoteAccounts, None, 0)
+            .map_err(|error| {
+                debug!("get_vote_accounts failed: {:?}", error);
+                io::Error::new(io::ErrorKind::Other, "get_vote_accounts failed")
+            })?;
+        Ok(serde_json::from_value(response)?)
+    }
+
     /// Request the transaction count.  If the response packet is dropped by the network,
     /// this method will try again 5 times.
     pub fn get_transaction_count(&self) -> io::Result<u64> {
@@ -546,12 +756,15 @@ pub fn get_rpc_request_str(rpc_addr: SocketAddr, tls: bool) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::mock_rpc_client_request::{PUBKEY, SIGNATURE};
+    use crate::mock_rpc_client_request::{PUBKEY, SIGNATURE, SLOT};
+    use crate::rpc_filter::RpcAccountEncoding;
     use jsonrpc_core::{Error, IoHandler, Params};
     use jsonrpc_http_server::{AccessControlAllowOrigin, DomainsValidation, ServerBuilder};
     use serde_json::Number;
     use soros_logger;
     use soros_sdk::signature::{Keypair, KeypairUtil};
+    use soros_sdk::system_instruction;
+    use soros_sdk::system_program;
     use soros_sdk::system_transaction;
     use soros_sdk::transaction::TransactionError;
     use std::sync::mpsc::channel;
@@ -685,6 +898,88 @@ mod tests {
         assert!(blockhash.is_err());
     }
 
+    #[test]
+    fn test_get_nonce() {
+        let rpc_client = RpcClient::new_mock("nonce".to_string());
+        let nonce_pubkey = Pubkey::new_rand();
+
+        let expected_nonce = Hash::new(&bs58::decode(PUBKEY).into_vec().unwrap());
+        assert_eq!(rpc_client.get_nonce(&nonce_pubkey).unwrap(), expected_nonce);
+
+        let rpc_client = RpcClient::new_mock("succeeds".to_string());
+        assert!(rpc_client.get_nonce(&nonce_pubkey).is_err());
+
+        let rpc_client = RpcClient::new_mock("fails".to_string());
+        assert!(rpc_client.get_nonce(&nonce_pubkey).is_err());
+    }
+
+    #[test]
+    fn test_get_with_commitment() {
+        let rpc_client = RpcClient::new_mock("succeeds".to_string());
+        let pubkey = Pubkey::new_rand();
+
+        let response = rpc_client
+            .get_balance_with_commitment(&pubkey, CommitmentConfig::root())
+            .unwrap();
+        assert_eq!(response, RpcResponse::new(SLOT, 50));
+
+        let response = rpc_client
+            .get_account_with_commitment(&pubkey, CommitmentConfig::confirmed())
+            .unwrap();
+        assert_eq!(response.context.slot, SLOT);
+        assert_eq!(response.value.dif, 50);
+
+        let response = rpc_client
+            .get_signature_status_with_commitment("good_signature", CommitmentConfig::root())
+            .unwrap();
+        assert_eq!(response, RpcResponse::new(SLOT, Some(Ok(()))));
+
+        let rpc_client = RpcClient::new_mock("fails".to_string());
+        assert!(rpc_client
+            .get_balance_with_commitment(&pubkey, CommitmentConfig::root())
+            .is_err());
+    }
+
+    #[test]
+    fn test_get_program_accounts() {
+        let rpc_client = RpcClient::new_mock("succeeds".to_string());
+        let program_id = system_program::id();
+        let expected = vec![(
+            Pubkey::from_str(PUBKEY).unwrap(),
+            Account::new(50, 4, &program_id),
+        )];
+        assert_eq!(
+            rpc_client.get_program_accounts(&program_id).unwrap(),
+            expected
+        );
+
+        let config = RpcProgramAccountsConfig {
+            encoding: Some(RpcAccountEncoding::Base64),
+            ..RpcProgramAccountsConfig::default()
+        };
+        assert_eq!(
+            rpc_client
+                .get_program_accounts_with_config(&program_id, config)
+                .unwrap(),
+            expected
+        );
+
+        let rpc_client = RpcClient::new_mock("fails".to_string());
+        assert!(rpc_client.get_program_accounts(&program_id).is_err());
+    }
+
+    #[test]
+    fn test_get_snapshot() {
+        let rpc_client = RpcClient::new_mock("succeeds".to_string());
+        assert_eq!(
+            rpc_client.get_snapshot().unwrap(),
+            Some((SLOT, vec![1, 2, 3]))
+        );
+
+        let rpc_client = RpcClient::new_mock("fails".to_string());
+        assert!(rpc_client.get_snapshot().is_err());
+    }
+
     #[test]
     fn test_get_signature_status() {
         let rpc_client = RpcClient::new_mock("succeeds".to_string());
@@ -724,6 +1019,31 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_send_and_confirm_transaction_with_signers() {
+        let (key0, key1) = (Keypair::new(), Keypair::new());
+        let instructions = vec![
+            system_instruction::transfer(&key0.pubkey(), &key1.pubkey(), 1),
+            system_instruction::transfer(&key1.pubkey(), &key0.pubkey(), 1),
+        ];
+        let mut tx =
+            Transaction::new_signed_instructions(&[&key0, &key1], instructions, Hash::default());
+
+        let rpc_client = RpcClient::new_mock("succeeds".to_string());
+        let result = rpc_client.send_and_confirm_transaction_with_signers(&mut tx, &[&key0, &key1]);
+        result.unwrap();
+
+        // Both signatures are redone with the new blockhash
+        let rpc_client = RpcClient::new_mock("account_in_use".to_string());
+        let prev_tx = tx.clone();
+        let result = rpc_client.send_and_confirm_transaction_with_signers(&mut tx, &[&key0, &key1]);
+        assert!(result.is_err());
+        assert_ne!(prev_tx.signatures, tx.signatures);
+        for (signature, key) in tx.signatures.iter().zip(&[&key0, &key1]) {
+            assert!(signature.verify(key.pubkey().as_ref(), &tx.message_data()));
+        }
+    }
+
     #[test]
     fn test_resign_transaction() {
         let rpc_client = RpcClient::new_mock("succeeds".to_string());
@@ -0,0 +1,389 @@
+//! Parameters and results of `getProgramAccounts`
+//! * `filters` narrow down the accounts a program owns, an account has to pass all of them
+//! * account data is returned base58 encoded by default, or base64 encoded, which is the only
+//!   choice for data over `MAX_BASE58_BYTES`
+//! * accounts are returned in pubkey order, at most `MAX_PROGRAM_ACCOUNTS` at a time, the next
+//!   page starts `after` the last pubkey of the one before it
+
+use serde_derive::{Deserialize, Serialize};
+use soros_sdk::account::Account;
+use soros_sdk::commitment_config::CommitmentConfig;
+use soros_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+use std::{error, fmt};
+
+/// Largest data base58 encoding is allowed for, it's quadratic in the data length
+pub const MAX_BASE58_BYTES: usize = 128;
+
+/// Most filters a request can have
+pub const MAX_FILTERS: usize = 4;
+
+/// Most accounts a request returns
+pub const MAX_PROGRAM_ACCOUNTS: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RpcFilterError {
+    /// The data is too large for base58
+    DataTooLarge,
+    /// `Memcmp` bytes that aren't valid base58 or are over `MAX_BASE58_BYTES`
+    InvalidBytes,
+    TooManyFilters,
+    /// `after` isn't a valid pubkey
+    InvalidAfter,
+    /// `limit` is zero or over `MAX_PROGRAM_ACCOUNTS`
+    InvalidLimit,
+}
+
+impl fmt::Display for RpcFilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RpcFilterError::DataTooLarge => write!(
+                f,
+                "account data over {} bytes has to be base64 encoded",
+                MAX_BASE58_BYTES
+            ),
+            RpcFilterError::InvalidBytes => write!(
+                f,
+                "memcmp bytes aren't valid base58 of at most {} bytes",
+                MAX_BASE58_BYTES
+            ),
+            RpcFilterError::TooManyFilters => write!(f, "more than {} filters", MAX_FILTERS),
+            RpcFilterError::InvalidAfter => write!(f, "after isn't a valid pubkey"),
+            RpcFilterError::InvalidLimit => {
+                write!(f, "limit has to be between 1 and {}", MAX_PROGRAM_ACCOUNTS)
+            }
+        }
+    }
+}
+
+impl error::Error for RpcFilterError {
+    fn description(&self) -> &str {
+        "invalid program accounts request"
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum RpcAccountEncoding {
+    Base58,
+    Base64,
+}
+
+impl Default for RpcAccountEncoding {
+    fn default() -> Self {
+        RpcAccountEncoding::Base58
+    }
+}
+
+/// Compare `bytes` to the account data at `offset`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Memcmp {
+    pub offset: usize,
+    /// Base58 encoded
+    pub bytes: String,
+}
+
+impl Memcmp {
+    pub fn new(offset: usize, bytes: &[u8]) -> Self {
+        Self {
+            offset,
+            bytes: bs58::encode(bytes).into_string(),
+        }
+    }
+
+    fn decode(&self) -> Result<Vec<u8>, RpcFilterError> {
+        let bytes = bs58::decode(&self.bytes)
+            .into_vec()
+            .map_err(|_| RpcFilterError::InvalidBytes)?;
+        if bytes.len() > MAX_BASE58_BYTES {
+            Err(RpcFilterError::InvalidBytes)?
+        }
+        Ok(bytes)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum RpcFilterType {
+    /// The account data is exactly this long
+    DataSize(u64),
+    Memcmp(Memcmp),
+}
+
+/// A filter checked and decoded, ready to be applied to any number of accounts
+#[derive(Debug, Clone, PartialEq)]
+pub enum AccountFilter {
+    DataSize(u64),
+    Memcmp { offset: usize, bytes: Vec<u8> },
+}
+
+impl AccountFilter {
+    pub fn allows(&self, account: &Account) -> bool {
+        match self {
+            AccountFilter::DataSize(size) => account.data.len() as u64 == *size,
+            AccountFilter::Memcmp { offset, bytes } => account
+                .data
+                .get(*offset..)
+                .map_or(false, |data| data.starts_with(bytes)),
+        }
+    }
+}
+
+impl RpcFilterType {
+    pub fn verify(&self) -> Result<AccountFilter, RpcFilterError> {
+        match self {
+            RpcFilterType::DataSize(size) => Ok(AccountFilter::DataSize(*size)),
+            RpcFilterType::Memcmp(memcmp) => Ok(AccountFilter::Memcmp {
+                offset: memcmp.offset,
+                bytes: memcmp.decode()?,
+            }),
+        }
+    }
+}
+
+/// Optional parameter of `getProgramAccounts`
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcProgramAccountsConfig {
+    pub filters: Option<Vec<RpcFilterType>>,
+    pub encoding: Option<RpcAccountEncoding>,
+    /// Most accounts to return, `MAX_PROGRAM_ACCOUNTS` by default
+    pub limit: Option<usize>,
+    /// Base58 pubkey, only accounts with a greater pubkey are returned
+    pub after: Option<String>,
+    #[serde(flatten)]
+    pub commitment: Option<CommitmentConfig>,
+}
+
+/// A `RpcProgramAccountsConfig` that has been checked
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgramAccountsQuery {
+    pub filters: Vec<AccountFilter>,
+    pub encoding: RpcAccountEncoding,
+    pub limit: usize,
+    pub after: Option<Pubkey>,
+}
+
+impl ProgramAccountsQuery {
+    /// Whether `account` passes all the filters
+    pub fn allows(&self, account: &Account) -> bool {
+        self.filters.iter().all(|filter| filter.allows(account))
+    }
+}
+
+impl RpcProgramAccountsConfig {
+    /// Checks the request once, before it's applied to any account
+    pub fn verify(&self) -> Result<ProgramAccountsQuery, RpcFilterError> {
+        let filters = self
+            .filters
+            .as_ref()
+            .map_or(&[][..], |filters| &filters[..]);
+        if filters.len() > MAX_FILTERS {
+            Err(RpcFilterError::TooManyFilters)?
+        }
+        let limit = self.limit.unwrap_or(MAX_PROGRAM_ACCOUNTS);
+        if limit == 0 || limit > MAX_PROGRAM_ACCOUNTS {
+            Err(RpcFilterError::InvalidLimit)?
+        }
+        let after = match &self.after {
+            Some(after) => Pubkey::from_str(after).map(Some),
+            None => Ok(None),
+        }
+        .map_err(|_| RpcFilterError::InvalidAfter)?;
+        Ok(ProgramAccountsQuery {
+            filters: filters
+                .iter()
+                .map(RpcFilterType::verify)
+                .collect::<Result<_, _>>()?,
+            encoding: self.encoding.unwrap_or_default(),
+            limit,
+            after,
+        })
+    }
+}
+
+/// An account with its data encoded, the owner is base58 encoded
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RpcAccount {
+    pub owner: String,
+    pub dif: u64,
+    pub data: String,
+    pub executable: bool,
+}
+
+impl RpcAccount {
+    pub fn encode(account: &Account, encoding: RpcAccountEncoding) -> Result<Self, RpcFilterError> {
+        let data = match encoding {
+            RpcAccountEncoding::Base58 => {
+                if account.data.len() > MAX_BASE58_BYTES {
+                    Err(RpcFilterError::DataTooLarge)?
+                }
+                bs58::encode(&account.data).into_string()
+            }
+            RpcAccountEncoding::Base64 => base64::encode(&account.data),
+        };
+        Ok(Self {
+            owner: account.owner.to_string(),
+            dif: account.dif,
+            data,
+            executable: account.executable,
+        })
+    }
+
+    pub fn decode(&self, encoding: RpcAccountEncoding) -> Option<Account> {
+        let data = match encoding {
+            RpcAccountEncoding::Base58 => bs58::decode(&self.data).into_vec().ok()?,
+            RpcAccountEncoding::Base64 => base64::decode(&self.data).ok()?,
+        };
+        Some(Account {
+            dif: self.dif,
+            data,
+            owner: Pubkey::from_str(&self.owner).ok()?,
+            executable: self.executable,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RpcKeyedAccount {
+    pub pubkey: String,
+    pub account: RpcAccount,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filters() {
+        let account = Account {
+            dif: 1,
+            data: vec![1, 2, 3, 4, 5],
+            owner: Pubkey::new_rand(),
+            executable: false,
+        };
+        let allows = |filter: RpcFilterType| filter.verify().unwrap().allows(&account);
+        assert!(allows(RpcFilterType::DataSize(5)));
+        assert!(!allows(RpcFilterType::DataSize(4)));
+
+        let memcmp = |offset, bytes: &[u8]| RpcFilterType::Memcmp(Memcmp::new(offset, bytes));
+        assert!(allows(memcmp(0, &[1, 2])));
+        assert!(allows(memcmp(3, &[4, 5])));
+        assert!(!allows(memcmp(3, &[4, 5, 6])));
+        assert!(!allows(memcmp(6, &[])));
+        assert!(!allows(memcmp(1, &[1])));
+
+        let bad_bytes = RpcFilterType::Memcmp(Memcmp {
+            offset: 0,
+            bytes: "0OIl".to_string(),
+        });
+        assert_eq!(bad_bytes.verify(), Err(RpcFilterError::InvalidBytes));
+        let too_many_bytes = memcmp(0, &[1; MAX_BASE58_BYTES + 1]);
+        assert_eq!(too_many_bytes.verify(), Err(RpcFilterError::InvalidBytes));
+
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![RpcFilterType::DataSize(5), memcmp(1, &[2])]),
+            ..RpcProgramAccountsConfig::default()
+        };
+        assert!(config.verify().unwrap().allows(&account));
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![RpcFilterType::DataSize(5), memcmp(1, &[3])]),
+            ..RpcProgramAccountsConfig::default()
+        };
+        assert!(!config.verify().unwrap().allows(&account));
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![RpcFilterType::DataSize(5); MAX_FILTERS + 1]),
+            ..RpcProgramAccountsConfig::default()
+        };
+        assert_eq!(config.verify(), Err(RpcFilterError::TooManyFilters));
+
+        // Invalid filters are rejected whatever comes before them
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![RpcFilterType::DataSize(4), bad_bytes]),
+            ..RpcProgramAccountsConfig::default()
+        };
+        assert_eq!(config.verify(), Err(RpcFilterError::InvalidBytes));
+    }
+
+    #[test]
+    fn test_config_verify() {
+        let query = RpcProgramAccountsConfig::default().verify().unwrap();
+        assert_eq!(
+            query,
+            ProgramAccountsQuery {
+                filters: vec![],
+                encoding: RpcAccountEncoding::Base58,
+                limit: MAX_PROGRAM_ACCOUNTS,
+                after: None,
+            }
+        );
+
+        let after = Pubkey::new_rand();
+        let config = RpcProgramAccountsConfig {
+            limit: Some(1),
+            after: Some(after.to_string()),
+            ..RpcProgramAccountsConfig::default()
+        };
+        let query = config.verify().unwrap();
+        assert_eq!((query.limit, query.after), (1, Some(after)));
+
+        for limit in &[0, MAX_PROGRAM_ACCOUNTS + 1] {
+            let config = RpcProgramAccountsConfig {
+                limit: Some(*limit),
+                ..RpcProgramAccountsConfig::default()
+            };
+            assert_eq!(config.verify(), Err(RpcFilterError::InvalidLimit));
+        }
+        let config = RpcProgramAccountsConfig {
+            after: Some("0".to_string()),
+            ..RpcProgramAccountsConfig::default()
+        };
+        assert_eq!(config.verify(), Err(RpcFilterError::InvalidAfter));
+    }
+
+    #[test]
+    fn test_config_json() {
+        let config: RpcProgramAccountsConfig = serde_json::from_str(
+            r#"{"filters":[{"dataSize":17},{"memcmp":{"offset":4,"bytes":"3Mc6vR"}}],
+                "encoding":"base64","commitment":"root"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            RpcProgramAccountsConfig {
+                filters: Some(vec![
+                    RpcFilterType::DataSize(17),
+                    RpcFilterType::Memcmp(Memcmp {
+                        offset: 4,
+                        bytes: "3Mc6vR".to_string(),
+                    }),
+                ]),
+                encoding: Some(RpcAccountEncoding::Base64),
+                limit: None,
+                after: None,
+                commitment: Some(CommitmentConfig::root()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_rpc_account_encoding() {
+        let mut account = Account::new(5, 0, &Pubkey::new_rand());
+        account.data = vec![7; MAX_BASE58_BYTES];
+        for encoding in &[RpcAccountEncoding::Base58, RpcAccountEncoding::Base64] {
+            let rpc_account = RpcAccount::encode(&account, *encoding).unwrap();
+            assert_eq!(rpc_account.decode(*encoding), Some(account.clone()));
+        }
+
+        account.data.push(7);
+        assert_eq!(
+            RpcAccount::encode(&account, RpcAccountEncoding::Base58),
+            Err(RpcFilterError::DataTooLarge)
+        );
+        let rpc_account = RpcAccount::encode(&account, RpcAccountEncoding::Base64).unwrap();
+        assert_eq!(
+            rpc_account.decode(RpcAccountEncoding::Base64),
+            Some(account)
+        );
+    }
+}
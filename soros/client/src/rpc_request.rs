@@ -1,4 +1,6 @@
+use serde_derive::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use std::collections::HashMap;
 use std::{error, fmt};
 
 #[derive(Debug, PartialEq)]
@@ -9,18 +11,25 @@ pub enum RpcRequest {
     GetAccountInfo,
     GetBalance,
     GetClusterNodes,
+    GetEpochInfo,
+    GetEpochSchedule,
+    GetLeaderSchedule,
     GetNumBlocksSinceSignatureConfirmation,
+    GetProgramAccounts,
     GetRecentBlockhash,
     GetSignatureStatus,
     GetSlotLeader,
+    GetSnapshot,
     GetStorageBlockhash,
     GetStorageEntryHeight,
     GetStoragePubkeysForEntryHeight,
     GetTransactionCount,
+    GetVoteAccounts,
     RegisterNode,
     RequestAirdrop,
     SendTransaction,
     SignVote,
+    SimulateTransaction,
 }
 
 impl RpcRequest {
@@ -33,20 +42,27 @@ impl RpcRequest {
             RpcRequest::GetAccountInfo => "getAccountInfo",
             RpcRequest::GetBalance => "getDif",
             RpcRequest::GetClusterNodes => "getClusterNodes",
+            RpcRequest::GetEpochInfo => "getEpochInfo",
+            RpcRequest::GetEpochSchedule => "getEpochSchedule",
+            RpcRequest::GetLeaderSchedule => "getLeaderSchedule",
             RpcRequest::GetNumBlocksSinceSignatureConfirmation => {
                 "getNumBlocksSinceSignatureConfirmation"
             }
+            RpcRequest::GetProgramAccounts => "getProgramAccounts",
             RpcRequest::GetRecentBlockhash => "getLatestBlockhash",
             RpcRequest::GetSignatureStatus => "getSignatureState",
             RpcRequest::GetSlotLeader => "getRoundLeader",
+            RpcRequest::GetSnapshot => "getSnapshot",
             RpcRequest::GetStorageBlockhash => "getStorageBlockhash",
             RpcRequest::GetStorageEntryHeight => "getStorageEntryHeight",
             RpcRequest::GetStoragePubkeysForEntryHeight => "getStoragePubkeysForEntryHeight",
             RpcRequest::GetTransactionCount => "getTxnCnt",
+            RpcRequest::GetVoteAccounts => "getVoteAccounts",
             RpcRequest::RegisterNode => "registerNode",
             RpcRequest::RequestAirdrop => "requestDif",
             RpcRequest::SendTransaction => "sendTxn",
             RpcRequest::SignVote => "signVote",
+            RpcRequest::SimulateTransaction => "simulateTransaction",
         };
         let mut request = json!({
            "jsonrpc": jsonrpc,
@@ -60,6 +76,72 @@ impl RpcRequest {
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RpcResponseContext {
+    /// Slot of the bank the request was served from
+    pub slot: u64,
+}
+
+/// Result of an RPC read method that takes a `CommitmentConfig`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RpcResponse<T> {
+    pub context: RpcResponseContext,
+    pub value: T,
+}
+
+impl<T> RpcResponse<T> {
+    pub fn new(slot: u64, value: T) -> Self {
+        Self {
+            context: RpcResponseContext { slot },
+            value,
+        }
+    }
+}
+
+/// The latest snapshot of a node's root bank
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RpcSnapshot {
+    pub slot: u64,
+    /// Base64 encoded, bincode serialized `BankSnapshot`
+    pub snapshot: String,
+}
+
+/// Where the bank a request was served from is in its epoch
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RpcEpochInfo {
+    pub epoch: u64,
+    /// Slot relative to the first slot of `epoch`
+    pub slot_index: u64,
+    pub slots_in_epoch: u64,
+    pub absolute_slot: u64,
+}
+
+/// Slot indexes in an epoch that each leader, a base58 node id, is scheduled for
+pub type RpcLeaderSchedule = HashMap<String, Vec<u64>>;
+
+/// A vote account staked in the current epoch
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RpcVoteAccountInfo {
+    /// Base58 id of the vote account
+    pub vote_pubkey: String,
+    /// Base58 id of the node voting with the account
+    pub node_pubkey: String,
+    /// Stake the account votes with in the current epoch
+    pub activated_stake: u64,
+    /// Fraction of std::u32::MAX of rewards the node keeps
+    pub commission: u32,
+    /// Most recent slot voted on, if any
+    pub last_vote: Option<u64>,
+    pub root_slot: Option<u64>,
+}
+
+/// Staked vote accounts, split by whether they have voted recently
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RpcVoteAccountStatus {
+    pub current: Vec<RpcVoteAccountInfo>,
+    pub delinquent: Vec<RpcVoteAccountInfo>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum RpcError {
     RpcRequestError(String),
@@ -98,14 +180,34 @@ mod tests {
         let request = test_request.build_request_json(1, Some(addr));
         assert_eq!(request["method"], "getDif");
 
+        let test_request = RpcRequest::GetEpochInfo;
+        let request = test_request.build_request_json(1, None);
+        assert_eq!(request["method"], "getEpochInfo");
+
+        let test_request = RpcRequest::GetLeaderSchedule;
+        let request = test_request.build_request_json(1, None);
+        assert_eq!(request["method"], "getLeaderSchedule");
+
+        let test_request = RpcRequest::GetProgramAccounts;
+        let request = test_request.build_request_json(1, None);
+        assert_eq!(request["method"], "getProgramAccounts");
+
         let test_request = RpcRequest::GetRecentBlockhash;
         let request = test_request.build_request_json(1, None);
         assert_eq!(request["method"], "getLatestBlockhash");
 
+        let test_request = RpcRequest::GetSnapshot;
+        let request = test_request.build_request_json(1, None);
+        assert_eq!(request["method"], "getSnapshot");
+
         let test_request = RpcRequest::GetTransactionCount;
         let request = test_request.build_request_json(1, None);
         assert_eq!(request["method"], "getTxnCnt");
 
+        let test_request = RpcRequest::GetVoteAccounts;
+        let request = test_request.build_request_json(1, None);
+        assert_eq!(request["method"], "getVoteAccounts");
+
         let test_request = RpcRequest::RequestAirdrop;
         let request = test_request.build_request_json(1, None);
         assert_eq!(request["method"], "requestDif");
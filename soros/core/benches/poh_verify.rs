@@ -0,0 +1,60 @@
+#![feature(test)]
+
+extern crate test;
+
+use rayon::prelude::*;
+use soros::entry::{next_entry_mut, Entry, EntrySlice};
+use soros_sdk::hash::{hash, Hash};
+use soros_sdk::multi_sha256::{hash_chain, hash_chains, LANES};
+use test::Bencher;
+
+const NUM_HASHES: u64 = 400;
+const NUM_ENTRIES: usize = 800;
+
+fn ticks() -> Vec<Entry> {
+    let mut cur_hash = Hash::default();
+    (0..NUM_ENTRIES)
+        .map(|_| next_entry_mut(&mut cur_hash, NUM_HASHES, vec![]))
+        .collect()
+}
+
+#[bench]
+fn bench_hash_chains_scalar(bencher: &mut Bencher) {
+    let start_hashes: Vec<Hash> = (0..LANES as u8).map(|i| hash(&[i])).collect();
+    bencher.iter(|| {
+        let _hashes: Vec<Hash> = start_hashes
+            .iter()
+            .map(|start_hash| hash_chain(start_hash, NUM_HASHES))
+            .collect();
+    })
+}
+
+#[bench]
+fn bench_hash_chains_multi(bencher: &mut Bencher) {
+    let start_hashes: Vec<Hash> = (0..LANES as u8).map(|i| hash(&[i])).collect();
+    let num_hashes = vec![NUM_HASHES; LANES];
+    bencher.iter(|| {
+        let _hashes = hash_chains(&start_hashes, &num_hashes);
+    })
+}
+
+#[bench]
+fn bench_poh_verify_ticks_per_entry(bencher: &mut Bencher) {
+    let entries = ticks();
+    let genesis = [Entry::new(&Hash::default(), 0, vec![])];
+    bencher.iter(|| {
+        assert!(genesis
+            .par_iter()
+            .chain(&entries)
+            .zip(&entries)
+            .all(|(x0, x1)| x1.verify(&x0.hash)));
+    })
+}
+
+#[bench]
+fn bench_poh_verify_ticks(bencher: &mut Bencher) {
+    let entries = ticks();
+    bencher.iter(|| {
+        assert!(entries.verify(&Hash::default()));
+    })
+}
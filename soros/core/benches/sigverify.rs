@@ -19,3 +19,21 @@ fn bench_sigverify(bencher: &mut Bencher) {
         let _ans = sigverify::ed25519_verify(&batches);
     })
 }
+
+#[bench]
+fn bench_sigverify_cpu(bencher: &mut Bencher) {
+    let tx = test_tx();
+    let batches = to_packets(&vec![tx; 128]);
+    bencher.iter(|| {
+        let _ans = sigverify::ed25519_verify_cpu(&batches);
+    })
+}
+
+#[bench]
+fn bench_sigverify_cpu_batch(bencher: &mut Bencher) {
+    let tx = test_tx();
+    let batches = to_packets(&vec![tx; 128]);
+    bencher.iter(|| {
+        let _ans = sigverify::ed25519_verify_cpu_batch(&batches);
+    })
+}
@@ -1,16 +1,32 @@
 //! The `bank_forks` module implments BankForks a DAG of checkpointed Banks
 
+use bincode::serialize;
 use hashbrown::{HashMap, HashSet};
 use soros_metrics::counter::Counter;
 use soros_runtime::bank::Bank;
 use soros_sdk::timing;
 use std::ops::Index;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread::{Builder, JoinHandle};
 use std::time::Instant;
 
 pub struct BankForks {
     banks: HashMap<u64, Arc<Bank>>,
     working_bank: Arc<Bank>,
+    root_bank: Arc<Bank>,
+    /// Highest slot a supermajority of the stake is locked out on
+    confirmed_slot: u64,
+    /// Take a snapshot of the root bank every this many slots
+    snapshot_interval_slots: Option<u64>,
+    /// Slot of the latest snapshot taken, it may still be getting serialized
+    snapshot_slot: Option<u64>,
+    /// Slot and serialized `BankSnapshot` of the latest snapshot, set by the snapshot thread
+    snapshot: Arc<RwLock<Option<(u64, Arc<Vec<u8>>)>>>,
+    /// Set while the snapshot thread serializes a snapshot
+    snapshot_in_progress: Arc<AtomicBool>,
+    /// Serializes the latest snapshot
+    snapshot_thread: Option<JoinHandle<()>>,
 }
 
 impl Index<u64> for BankForks {
@@ -27,7 +43,14 @@ impl BankForks {
         banks.insert(bank_slot, working_bank.clone());
         Self {
             banks,
+            root_bank: working_bank.clone(),
             working_bank,
+            confirmed_slot: bank_slot,
+            snapshot_interval_slots: None,
+            snapshot_slot: None,
+            snapshot: Arc::new(RwLock::new(None)),
+            snapshot_in_progress: Arc::new(AtomicBool::new(false)),
+            snapshot_thread: None,
         }
     }
 
@@ -85,9 +108,22 @@ impl BankForks {
         for bank in initial_banks {
             banks.insert(bank.slot(), bank.clone());
         }
+        // Rooted banks are squashed, so the oldest ancestor still linked is the root
+        let root_bank = working_bank
+            .parents()
+            .last()
+            .cloned()
+            .unwrap_or_else(|| working_bank.clone());
         Self {
             banks,
             working_bank,
+            confirmed_slot: root_bank.slot(),
+            root_bank,
+            snapshot_interval_slots: None,
+            snapshot_slot: None,
+            snapshot: Arc::new(RwLock::new(None)),
+            snapshot_in_progress: Arc::new(AtomicBool::new(false)),
+            snapshot_thread: None,
         }
     }
 
@@ -104,20 +140,115 @@ impl BankForks {
         self.working_bank.clone()
     }
 
+    pub fn root(&self) -> u64 {
+        self.root_bank.slot()
+    }
+
+    pub fn root_bank(&self) -> Arc<Bank> {
+        self.root_bank.clone()
+    }
+
+    /// Record that a supermajority of the stake is locked out on `slot`
+    pub fn set_confirmed_slot(&mut self, slot: u64) {
+        self.confirmed_slot = self.confirmed_slot.max(slot);
+    }
+
+    /// The most recent bank a supermajority of the stake is locked out on, a rooted bank is
+    /// confirmed too
+    pub fn confirmed_bank(&self) -> Arc<Bank> {
+        if self.confirmed_slot > self.root() {
+            if let Some(bank) = self.banks.get(&self.confirmed_slot) {
+                return bank.clone();
+            }
+        }
+        self.root_bank()
+    }
+
     pub fn set_root(&mut self, root: u64) {
         let set_root_start = Instant::now();
         let root_bank = self
             .banks
             .get(&root)
-            .expect("root bank didn't exist in bank_forks");
+            .expect("root bank didn't exist in bank_forks")
+            .clone();
         root_bank.squash();
+        self.root_bank = root_bank;
         self.prune_non_root(root);
+        self.snapshot_root();
         inc_new_counter_info!(
             "bank-forks_set_root_ms",
             timing::duration_as_ms(&set_root_start.elapsed()) as usize
         );
     }
 
+    /// Snapshot the root bank every `interval_slots` slots from now on, `None` stops taking
+    /// snapshots
+    pub fn set_snapshot_interval(&mut self, interval_slots: Option<u64>) {
+        self.snapshot_interval_slots = interval_slots;
+        self.snapshot_root();
+    }
+
+    /// Slot and serialized `BankSnapshot` of the latest snapshot of the root bank
+    pub fn snapshot(&self) -> Option<(u64, Arc<Vec<u8>>)> {
+        self.snapshot.read().unwrap().clone()
+    }
+
+    fn snapshot_root(&mut self) {
+        let interval_slots = match self.snapshot_interval_slots {
+            Some(interval_slots) => interval_slots,
+            None => return,
+        };
+        let root = self.root();
+        if let Some(slot) = self.snapshot_slot {
+            if root < slot + interval_slots {
+                return;
+            }
+        }
+        if self.snapshot_in_progress.swap(true, Ordering::SeqCst) {
+            // Still serializing the last one, try again at the next root
+            return;
+        }
+        self.snapshot_slot = Some(root);
+
+        // The accounts have to be captured before the next root is set, roots written after
+        // this one would show through and the versions they replace get purged.  Serializing
+        // them is what takes long, so that happens off the caller's lock.
+        let capture_start = Instant::now();
+        let bank_snapshot = self.root_bank.snapshot();
+        inc_new_counter_info!(
+            "bank-forks_snapshot_capture_ms",
+            timing::duration_as_ms(&capture_start.elapsed()) as usize
+        );
+        info!(
+            "snapshot of slot {}, snapshot hash {}",
+            root,
+            bank_snapshot.snapshot_hash()
+        );
+        let snapshot = self.snapshot.clone();
+        let snapshot_in_progress = self.snapshot_in_progress.clone();
+        let thread = Builder::new()
+            .name("soros-bank-forks-snapshot".to_string())
+            .spawn(move || {
+                let serialize_start = Instant::now();
+                let serialized = serialize(&bank_snapshot).unwrap();
+                inc_new_counter_info!(
+                    "bank-forks_snapshot_ms",
+                    timing::duration_as_ms(&serialize_start.elapsed()) as usize
+                );
+                *snapshot.write().unwrap() = Some((root, Arc::new(serialized)));
+                snapshot_in_progress.store(false, Ordering::SeqCst);
+            })
+            .unwrap();
+        self.snapshot_thread = Some(thread);
+    }
+
+    #[cfg(test)]
+    fn join_snapshot_thread(&mut self) {
+        if let Some(thread) = self.snapshot_thread.take() {
+            thread.join().unwrap();
+        }
+    }
+
     fn prune_non_root(&mut self, root: u64) {
         let descendants = self.descendants();
         self.banks
@@ -128,6 +259,7 @@ impl BankForks {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use soros_runtime::bank::BankSnapshot;
     use soros_sdk::genesis_block::GenesisBlock;
     use soros_sdk::hash::Hash;
     use soros_sdk::pubkey::Pubkey;
@@ -200,4 +332,51 @@ mod tests {
         assert_eq!(bank_forks.active_banks(), vec![1]);
     }
 
+    #[test]
+    fn test_bank_forks_commitment_banks() {
+        let (genesis_block, _) = GenesisBlock::new(10_000);
+        let bank = Bank::new(&genesis_block);
+        let mut bank_forks = BankForks::new(0, bank);
+        let bank0 = bank_forks[0].clone();
+        bank_forks.insert(Bank::new_from_parent(&bank0, &Pubkey::default(), 1));
+        let bank1 = bank_forks[1].clone();
+        bank_forks.insert(Bank::new_from_parent(&bank1, &Pubkey::default(), 2));
+        assert_eq!(bank_forks.root(), 0);
+        assert_eq!(bank_forks.confirmed_bank().slot(), 0);
+
+        bank_forks.set_confirmed_slot(2);
+        assert_eq!(bank_forks.confirmed_bank().slot(), 2);
+        bank_forks.set_confirmed_slot(1);
+        assert_eq!(bank_forks.confirmed_bank().slot(), 2);
+
+        bank_forks.set_root(1);
+        assert_eq!(bank_forks.root_bank().slot(), 1);
+        assert_eq!(bank_forks.confirmed_bank().slot(), 2);
+        assert_eq!(bank_forks.working_bank().slot(), 2);
+    }
+
+    #[test]
+    fn test_bank_forks_snapshot() {
+        let (genesis_block, _) = GenesisBlock::new(10_000);
+        let bank = Bank::new(&genesis_block);
+        let mut bank_forks = BankForks::new(0, bank);
+        assert!(bank_forks.snapshot().is_none());
+        bank_forks.set_snapshot_interval(Some(2));
+        bank_forks.join_snapshot_thread();
+        assert_eq!(bank_forks.snapshot().unwrap().0, 0);
+
+        let mut root_hashes = vec![];
+        for slot in 1..4 {
+            let parent = bank_forks.working_bank();
+            bank_forks.insert(Bank::new_from_parent(&parent, &Pubkey::default(), slot));
+            bank_forks.set_root(slot);
+            bank_forks.join_snapshot_thread();
+            root_hashes.push(bank_forks.root_bank().hash());
+        }
+        let (slot, snapshot) = bank_forks.snapshot().unwrap();
+        assert_eq!(slot, 2);
+        let snapshot: BankSnapshot = bincode::deserialize(&snapshot).unwrap();
+        assert_eq!(snapshot.slot, 2);
+        assert_eq!(snapshot.hash, root_hashes[1]);
+    }
 }
@@ -6,6 +6,7 @@ use crate::cluster_info::ClusterInfo;
 use crate::contact_info::ContactInfo;
 use crate::entry;
 use crate::entry::{hash_transactions, Entry};
+use crate::fault_injection;
 use crate::leader_schedule_cache::LeaderScheduleCache;
 use crate::packet;
 use crate::packet::{Packet, Packets};
@@ -14,14 +15,16 @@ use crate::poh_service::{PohService, PohServiceConfig};
 use crate::result::{Error, Result};
 use crate::service::Service;
 use crate::sigverify_stage::VerifiedPackets;
+use crate::transaction_scheduler::{self, QosConfig, Shares};
 use bincode::deserialize;
 use soros_metrics::counter::Counter;
 use soros_runtime::bank::Bank;
 use soros_runtime::locked_accounts_results::LockedAccountsResults;
 use soros_sdk::pubkey::Pubkey;
 use soros_sdk::timing::{self, duration_as_us, DEFAULT_TICKS_PER_SLOT, MAX_RECENT_BLOCKHASHES};
-use soros_sdk::transaction::{self, Transaction};
+use soros_sdk::transaction::{self, Transaction, TransactionError};
 use std::cmp;
+use std::collections::{HashSet, VecDeque};
 use std::net::UdpSocket;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{Receiver, RecvTimeoutError};
@@ -31,11 +34,16 @@ use std::time::Duration;
 use std::time::Instant;
 use sys_info;
 
-pub type UnprocessedPackets = Vec<(Packets, usize, Vec<u8>)>; // `usize` is the index of the first unprocessed packet in `SharedPackets`
+/// The `usize` is the index of the first unprocessed packet in `SharedPackets`, and the `Vec<u8>`
+/// is non-zero for each packet still to be processed
+pub type UnprocessedPackets = VecDeque<(Packets, usize, Vec<u8>)>;
 
 // number of threads is 1 until mt bank is ready
 pub const NUM_THREADS: u32 = 10;
 
+// Most packets a banking thread holds on to, beyond that the oldest ones are dropped
+pub const MAX_BUFFERED_PACKETS: usize = 100_000;
+
 /// Stores the stage's thread handle and output receiver.
 pub struct BankingStage {
     bank_thread_hdls: Vec<JoinHandle<()>>,
@@ -109,6 +117,7 @@ impl BankingStage {
                             &mut recv_start,
                             enable_forwarding,
                             leader_schedule_cache,
+                            &QosConfig::default(),
                         );
                         exit.store(true, Ordering::Relaxed);
                     })
@@ -121,63 +130,100 @@ impl BankingStage {
     fn forward_unprocessed_packets(
         socket: &std::net::UdpSocket,
         tpu_via_blobs: &std::net::SocketAddr,
-        unprocessed_packets: &[(Packets, usize, Vec<u8>)],
+        unprocessed_packets: &UnprocessedPackets,
     ) -> std::io::Result<()> {
-        let locked_packets: Vec<_> = unprocessed_packets
-            .iter()
-            .map(|(p, start_index, _)| (p, start_index))
-            .collect();
-        let packets: Vec<&Packet> = locked_packets
+        let packets: Vec<&Packet> = unprocessed_packets
             .iter()
-            .flat_map(|(p, start_index)| &p.packets[**start_index..])
+            .flat_map(|(p, start_index, vers)| {
+                p.packets[*start_index..]
+                    .iter()
+                    .zip(&vers[*start_index..])
+                    .filter(|(_, ver)| **ver != 0)
+                    .map(|(packet, _)| packet)
+            })
             .collect();
         inc_new_counter_info!("banking_stage-forwarded_packets", packets.len());
         let blobs = packet::packets_to_blobs(&packets);
 
         for blob in blobs {
-            socket.send_to(&blob.data[..blob.meta.size], tpu_via_blobs)?;
+            fault_injection::send_to(socket, &blob.data[..blob.meta.size], tpu_via_blobs)?;
         }
 
         Ok(())
     }
 
+    /// Number of packets from `offset` on still to be processed
+    fn num_unprocessed(vers: &[u8], offset: usize) -> usize {
+        vers[offset..].iter().filter(|ver| **ver != 0).count()
+    }
+
+    fn num_buffered_packets(buffered_packets: &UnprocessedPackets) -> usize {
+        buffered_packets
+            .iter()
+            .map(|(_, offset, vers)| Self::num_unprocessed(vers, *offset))
+            .sum()
+    }
+
+    /// Drop the oldest packets until no more than `max_packets` are buffered
+    fn drop_excess_buffered_packets(
+        buffered_packets: &mut UnprocessedPackets,
+        max_packets: usize,
+    ) -> usize {
+        let mut num_buffered = Self::num_buffered_packets(buffered_packets);
+        let mut dropped_packets = 0;
+        while num_buffered > max_packets {
+            let (_, offset, vers) = buffered_packets.pop_front().unwrap();
+            let num = Self::num_unprocessed(&vers, offset);
+            num_buffered -= num;
+            dropped_packets += num;
+        }
+        inc_new_counter_info!("banking_stage-dropped_packets", dropped_packets);
+        dropped_packets
+    }
+
     fn process_buffered_packets(
         poh_recorder: &Arc<Mutex<PohRecorder>>,
-        buffered_packets: &[(Packets, usize, Vec<u8>)],
+        buffered_packets: &UnprocessedPackets,
+        qos_config: &QosConfig,
+        shares: &mut Shares,
     ) -> Result<UnprocessedPackets> {
-        let mut unprocessed_packets = vec![];
+        let mut unprocessed_packets = UnprocessedPackets::new();
         let mut bank_shutdown = false;
         let mut rebuffered_packets = 0;
         let mut new_tx_count = 0;
         for (msgs, offset, vers) in buffered_packets {
             if bank_shutdown {
-                rebuffered_packets += vers.len() - *offset;
-                unprocessed_packets.push((msgs.to_owned(), *offset, vers.to_owned()));
+                rebuffered_packets += Self::num_unprocessed(vers, *offset);
+                unprocessed_packets.push_back((msgs.to_owned(), *offset, vers.to_owned()));
                 continue;
             }
 
             let bank = poh_recorder.lock().unwrap().bank();
             if bank.is_none() {
-                rebuffered_packets += vers.len() - *offset;
-                unprocessed_packets.push((msgs.to_owned(), *offset, vers.to_owned()));
+                rebuffered_packets += Self::num_unprocessed(vers, *offset);
+                unprocessed_packets.push_back((msgs.to_owned(), *offset, vers.to_owned()));
                 continue;
             }
             let bank = bank.unwrap();
 
-            let (processed, verified_txs, verified_indexes) =
-                Self::process_received_packets(&bank, &poh_recorder, &msgs, &vers, *offset)?;
+            let (processed, unprocessed_vers, max_height_reached) = Self::process_received_packets(
+                &bank,
+                &poh_recorder,
+                &msgs,
+                &vers,
+                *offset,
+                qos_config,
+                shares,
+            )?;
 
             new_tx_count += processed;
+            bank_shutdown = max_height_reached;
 
-            if processed < verified_txs.len() {
-                rebuffered_packets += verified_txs.len() - processed;
-                bank_shutdown = true;
+            let num_unprocessed = Self::num_unprocessed(&unprocessed_vers, *offset);
+            if num_unprocessed > 0 {
+                rebuffered_packets += num_unprocessed;
                 // Collect any unprocessed transactions in this batch for forwarding
-                unprocessed_packets.push((
-                    msgs.to_owned(),
-                    verified_indexes[processed],
-                    vers.to_owned(),
-                ));
+                unprocessed_packets.push_back((msgs.to_owned(), *offset, unprocessed_vers));
             }
         }
 
@@ -220,8 +266,10 @@ impl BankingStage {
         socket: &std::net::UdpSocket,
         poh_recorder: &Arc<Mutex<PohRecorder>>,
         cluster_info: &Arc<RwLock<ClusterInfo>>,
-        buffered_packets: &[(Packets, usize, Vec<u8>)],
+        buffered_packets: &UnprocessedPackets,
         enable_forwarding: bool,
+        qos_config: &QosConfig,
+        shares: &mut Shares,
     ) -> Result<UnprocessedPackets> {
         let rcluster_info = cluster_info.read().unwrap();
 
@@ -237,7 +285,7 @@ impl BankingStage {
 
         match decision {
             BufferedPacketsDecision::Consume => {
-                Self::process_buffered_packets(poh_recorder, buffered_packets)
+                Self::process_buffered_packets(poh_recorder, buffered_packets, qos_config, shares)
             }
             BufferedPacketsDecision::Forward => {
                 if enable_forwarding {
@@ -247,9 +295,9 @@ impl BankingStage {
                         &buffered_packets,
                     );
                 }
-                Ok(vec![])
+                Ok(UnprocessedPackets::new())
             }
-            _ => Ok(buffered_packets.to_vec()),
+            _ => Ok(buffered_packets.clone()),
         }
     }
 
@@ -289,9 +337,12 @@ impl BankingStage {
         recv_start: &mut Instant,
         enable_forwarding: bool,
         leader_schedule_cache: Arc<LeaderScheduleCache>,
+        qos_config: &QosConfig,
     ) {
         let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
-        let mut buffered_packets = vec![];
+        let mut buffered_packets = UnprocessedPackets::new();
+        // The thread's transactions from each sender and to each account so far this slot
+        let mut shares = Shares::default();
         loop {
             if !buffered_packets.is_empty() {
                 Self::handle_buffered_packets(
@@ -300,6 +351,8 @@ impl BankingStage {
                     cluster_info,
                     &buffered_packets,
                     enable_forwarding,
+                    qos_config,
+                    &mut shares,
                 )
                 .map(|packets| buffered_packets = packets)
                 .unwrap_or_else(|_| buffered_packets.clear());
@@ -315,8 +368,14 @@ impl BankingStage {
                 Duration::from_millis(100)
             };
 
-            match Self::process_packets(&verified_receiver, &poh_recorder, recv_start, recv_timeout)
-            {
+            match Self::process_packets(
+                &verified_receiver,
+                &poh_recorder,
+                recv_start,
+                recv_timeout,
+                qos_config,
+                &mut shares,
+            ) {
                 Err(Error::RecvTimeoutError(RecvTimeoutError::Timeout)) => (),
                 Ok(unprocessed_packets) => {
                     if unprocessed_packets.is_empty() {
@@ -327,12 +386,13 @@ impl BankingStage {
                         cluster_info,
                         &leader_schedule_cache,
                     ) {
-                        let num = unprocessed_packets
-                            .iter()
-                            .map(|(x, start, _)| x.packets.len().saturating_sub(*start))
-                            .sum();
+                        let num = Self::num_buffered_packets(&unprocessed_packets);
                         inc_new_counter_info!("banking_stage-buffered_packets", num);
-                        buffered_packets.extend_from_slice(&unprocessed_packets);
+                        buffered_packets.extend(unprocessed_packets);
+                        Self::drop_excess_buffered_packets(
+                            &mut buffered_packets,
+                            MAX_BUFFERED_PACKETS,
+                        );
                         continue;
                     }
 
@@ -352,10 +412,7 @@ impl BankingStage {
                 }
             }
 
-            let num = buffered_packets
-                .iter()
-                .map(|(x, start, _)| x.packets.len().saturating_sub(*start))
-                .sum();
+            let num = Self::num_buffered_packets(&buffered_packets);
             inc_new_counter_info!("banking_stage-total_buffered_packets", num);
         }
     }
@@ -406,7 +463,7 @@ impl BankingStage {
         txs: &[Transaction],
         poh: &Arc<Mutex<PohRecorder>>,
         lock_results: &LockedAccountsResults,
-    ) -> Result<()> {
+    ) -> Result<Vec<usize>> {
         let now = Instant::now();
         // Use a shorter maximum age when adding transactions into the pipeline.  This will reduce
         // the likelihood of any single thread getting starved and processing old ids.
@@ -437,14 +494,24 @@ impl BankingStage {
             txs.len(),
         );
 
-        Ok(())
+        // Transactions that lost the race for their accounts to another banking thread can
+        // try again
+        let retryable: Vec<usize> = results
+            .iter()
+            .enumerate()
+            .filter(|(_, result)| **result == Err(TransactionError::AccountInUse))
+            .map(|(i, _)| i)
+            .collect();
+        inc_new_counter_info!("banking_stage-account_in_use", retryable.len());
+        Ok(retryable)
     }
 
+    /// Returns the indexes of the transactions that couldn't lock their accounts
     pub fn process_and_record_transactions(
         bank: &Bank,
         txs: &[Transaction],
         poh: &Arc<Mutex<PohRecorder>>,
-    ) -> Result<()> {
+    ) -> Result<Vec<usize>> {
         let now = Instant::now();
         // Once accounts are locked, other threads cannot encode transactions that will modify the
         // same account state
@@ -472,13 +539,15 @@ impl BankingStage {
     /// Sends transactions to the bank.
     ///
     /// Returns the number of transactions successfully processed by the bank, which may be less
-    /// than the total number if max PoH height was reached and the bank halted
+    /// than the total number if max PoH height was reached and the bank halted, and the indexes
+    /// of those that couldn't lock their accounts
     fn process_transactions(
         bank: &Bank,
         transactions: &[Transaction],
         poh: &Arc<Mutex<PohRecorder>>,
-    ) -> Result<(usize)> {
+    ) -> Result<(usize, Vec<usize>)> {
         let mut chunk_start = 0;
+        let mut retryable = vec![];
         while chunk_start != transactions.len() {
             let chunk_end = chunk_start
                 + entry::num_will_fit(
@@ -493,7 +562,6 @@ impl BankingStage {
                 poh,
             );
             trace!("process_transactions: {:?}", result);
-            chunk_start = chunk_end;
             if let Err(Error::PohRecorderError(PohRecorderError::MaxHeightReached)) = result {
                 info!(
                     "process transactions: max height reached slot: {} height: {}",
@@ -502,22 +570,31 @@ impl BankingStage {
                 );
                 break;
             }
-            result?;
+            retryable.extend(result?.into_iter().map(|i| chunk_start + i));
+            chunk_start = chunk_end;
         }
-        Ok(chunk_start)
+        Ok((chunk_start, retryable))
     }
 
+    /// Process the packets of `msgs` from `offset` on that `vers` marks as still to be processed,
+    /// as `transaction_scheduler` schedules them.
+    ///
+    /// Returns the number of transactions processed, `vers` with the packets that were processed
+    /// or can never be cleared, and whether the bank reached its max height
     fn process_received_packets(
         bank: &Arc<Bank>,
         poh: &Arc<Mutex<PohRecorder>>,
         msgs: &Packets,
         vers: &[u8],
         offset: usize,
-    ) -> Result<(usize, Vec<Transaction>, Vec<usize>)> {
+        qos_config: &QosConfig,
+        shares: &mut Shares,
+    ) -> Result<(usize, Vec<u8>, bool)> {
         debug!("banking-stage-tx bank {}", bank.slot());
         let transactions =
             Self::deserialize_transactions(&Packets::new(msgs.packets[offset..].to_owned()));
 
+        let mut unprocessed_vers = vers.to_owned();
         let vers = vers[offset..].to_owned();
 
         debug!(
@@ -528,7 +605,7 @@ impl BankingStage {
         let (verified_transactions, verified_indexes): (Vec<_>, Vec<_>) = transactions
             .into_iter()
             .zip(vers)
-            .zip(0..)
+            .zip(offset..)
             .filter_map(|((tx, ver), index)| match tx {
                 None => None,
                 Some(tx) => {
@@ -547,9 +624,50 @@ impl BankingStage {
             verified_transactions.len()
         );
 
-        let processed = Self::process_transactions(&bank, &verified_transactions, poh)?;
+        // Only the scheduled transactions are left to process, the rest will never be
+        for ver in &mut unprocessed_vers[offset..] {
+            *ver = 0;
+        }
+        for index in &verified_indexes {
+            unprocessed_vers[*index] = 1;
+        }
 
-        Ok((processed, verified_transactions, verified_indexes))
+        shares.set_slot(bank.slot());
+        let schedule = transaction_scheduler::schedule(
+            &verified_transactions,
+            |tx| transaction_scheduler::fee_per_signature(tx, &bank.fee_calculator),
+            qos_config,
+            shares,
+        );
+        inc_new_counter_info!(
+            "banking_stage-deferred_transactions",
+            schedule.deferred.len()
+        );
+        inc_new_counter_info!("banking_stage-scheduled_batches", schedule.batches.len());
+
+        let mut processed = 0;
+        let mut verified_transactions: Vec<_> =
+            verified_transactions.into_iter().map(Some).collect();
+        for batch in schedule.batches {
+            let transactions: Vec<Transaction> = batch
+                .iter()
+                .map(|i| verified_transactions[*i].take().unwrap())
+                .collect();
+            let (batch_processed, retryable) =
+                Self::process_transactions(&bank, &transactions, poh)?;
+            let retryable: HashSet<_> = retryable.into_iter().collect();
+            for (j, i) in batch.iter().enumerate().take(batch_processed) {
+                if !retryable.contains(&j) {
+                    unprocessed_vers[verified_indexes[*i]] = 0;
+                    processed += 1;
+                }
+            }
+            if batch_processed < transactions.len() {
+                return Ok((processed, unprocessed_vers, true));
+            }
+        }
+
+        Ok((processed, unprocessed_vers, false))
     }
 
     /// Process the incoming packets
@@ -558,6 +676,8 @@ impl BankingStage {
         poh: &Arc<Mutex<PohRecorder>>,
         recv_start: &mut Instant,
         recv_timeout: Duration,
+        qos_config: &QosConfig,
+        shares: &mut Shares,
     ) -> Result<UnprocessedPackets> {
         let mms = verified_receiver
             .lock()
@@ -576,28 +696,28 @@ impl BankingStage {
         let proc_start = Instant::now();
         let mut new_tx_count = 0;
 
-        let mut unprocessed_packets = vec![];
+        let mut unprocessed_packets = UnprocessedPackets::new();
         let mut bank_shutdown = false;
         for (msgs, vers) in mms {
             if bank_shutdown {
-                unprocessed_packets.push((msgs, 0, vers));
+                unprocessed_packets.push_back((msgs, 0, vers));
                 continue;
             }
 
             let bank = poh.lock().unwrap().bank();
             if bank.is_none() {
-                unprocessed_packets.push((msgs, 0, vers));
+                unprocessed_packets.push_back((msgs, 0, vers));
                 continue;
             }
             let bank = bank.unwrap();
 
-            let (processed, verified_txs, verified_indexes) =
-                Self::process_received_packets(&bank, &poh, &msgs, &vers, 0)?;
+            let (processed, unprocessed_vers, max_height_reached) =
+                Self::process_received_packets(&bank, &poh, &msgs, &vers, 0, qos_config, shares)?;
 
-            if processed < verified_txs.len() {
-                bank_shutdown = true;
+            bank_shutdown = max_height_reached;
+            if Self::num_unprocessed(&unprocessed_vers, 0) > 0 {
                 // Collect any unprocessed transactions in this batch for forwarding
-                unprocessed_packets.push((msgs, verified_indexes[processed], vers));
+                unprocessed_packets.push_back((msgs, 0, unprocessed_vers));
             }
             new_tx_count += processed;
         }
@@ -1060,6 +1180,110 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_drop_excess_buffered_packets() {
+        let packets = Packets::new(vec![Packet::default(); 4]);
+        let mut buffered_packets = UnprocessedPackets::from(vec![
+            (packets.clone(), 1, vec![1, 1, 0, 1]),
+            (packets.clone(), 0, vec![1, 1, 1, 1]),
+            (packets, 3, vec![1, 1, 1, 1]),
+        ]);
+        assert_eq!(BankingStage::num_buffered_packets(&buffered_packets), 7);
+
+        assert_eq!(
+            BankingStage::drop_excess_buffered_packets(&mut buffered_packets, 7),
+            0
+        );
+        // The oldest packets go first
+        assert_eq!(
+            BankingStage::drop_excess_buffered_packets(&mut buffered_packets, 6),
+            2
+        );
+        assert_eq!(buffered_packets.len(), 2);
+        assert_eq!(BankingStage::num_buffered_packets(&buffered_packets), 5);
+    }
+
+    #[test]
+    fn test_process_received_packets_defers_over_share() {
+        let (genesis_block, mint_keypair) = GenesisBlock::new(10_000);
+        let bank = Arc::new(Bank::new(&genesis_block));
+        let working_bank = WorkingBank {
+            bank: bank.clone(),
+            min_tick_height: bank.tick_height(),
+            max_tick_height: bank.tick_height() + 1,
+        };
+        let ledger_path = get_tmp_ledger_path!();
+        {
+            let blocktree =
+                Blocktree::open(&ledger_path).expect("Expected to be able to open database ledger");
+            let (poh_recorder, _entry_receiver) = PohRecorder::new(
+                bank.tick_height(),
+                bank.last_blockhash(),
+                bank.slot(),
+                Some(4),
+                bank.ticks_per_slot(),
+                &Pubkey::default(),
+                &Arc::new(blocktree),
+                &Arc::new(LeaderScheduleCache::new_from_bank(&bank)),
+            );
+            let poh_recorder = Arc::new(Mutex::new(poh_recorder));
+            poh_recorder.lock().unwrap().set_working_bank(working_bank);
+
+            let transactions: Vec<_> = (0..4)
+                .map(|i| {
+                    system_transaction::transfer(
+                        &mint_keypair,
+                        &Pubkey::new_rand(),
+                        i + 1,
+                        genesis_block.hash(),
+                        0,
+                    )
+                })
+                .collect();
+            let mut packets = to_packets(&transactions[..3]).pop().unwrap();
+            packets.packets.push(Packet::default());
+            let vers = vec![1u8; packets.packets.len()];
+            let qos_config = QosConfig {
+                max_txs_per_sender: 2,
+                ..QosConfig::default()
+            };
+            let mut shares = Shares::default();
+
+            let (processed, unprocessed_vers, max_height_reached) =
+                BankingStage::process_received_packets(
+                    &bank,
+                    &poh_recorder,
+                    &packets,
+                    &vers,
+                    0,
+                    &qos_config,
+                    &mut shares,
+                )
+                .unwrap();
+            assert_eq!(processed, 2);
+            assert!(!max_height_reached);
+            // The sender's third transaction waits for the next slot, the packet that isn't a
+            // transaction is dropped
+            assert_eq!(unprocessed_vers, vec![0, 0, 1, 0]);
+
+            // The sender's share is used up for the slot, not just for one batch of packets
+            let packets = to_packets(&transactions[3..]).pop().unwrap();
+            let (processed, unprocessed_vers, _) = BankingStage::process_received_packets(
+                &bank,
+                &poh_recorder,
+                &packets,
+                &[1],
+                0,
+                &qos_config,
+                &mut shares,
+            )
+            .unwrap();
+            assert_eq!(processed, 0);
+            assert_eq!(unprocessed_vers, vec![1]);
+        }
+        Blocktree::destroy(&ledger_path).unwrap();
+    }
+
     #[test]
     fn test_bank_process_and_record_transactions() {
         soros_logger::setup();
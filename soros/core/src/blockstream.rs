@@ -1,22 +1,215 @@
 //! The `blockstream` module provides a method for streaming entries out via a
-//! local unix socket, to provide client services such as a block explorer with
-//! real-time access to entries.
+//! local unix socket, to rotating files or to TCP clients, to provide client services such as a
+//! block explorer with real-time access to entries.
+//!
+//! Every event is one line of JSON with a `v` field holding `BLOCKSTREAM_SCHEMA_VERSION`, see
+//! `schema()` for its layout.  Block events carry the slot's transactions decoded, along with
+//! their execution status and fee.
 
 use crate::entry::Entry;
-use crate::result::Result;
+use crate::result::{Error, Result};
 use bincode::serialize;
 use chrono::{SecondsFormat, Utc};
-use serde_json::json;
+use serde_json::{json, Value};
+use soros_budget_api::budget_instruction::BudgetInstruction;
+use soros_exchange_api::exchange_instruction::ExchangeInstruction;
+use soros_runtime::bank::Bank;
 use soros_sdk::hash::Hash;
 use soros_sdk::pubkey::Pubkey;
+use soros_sdk::system_instruction::SystemInstruction;
+use soros_sdk::system_program;
+use soros_sdk::transaction::Transaction;
+use soros_token_api::TokenInstruction;
+use soros_vote_api::vote_instruction::VoteInstruction;
 use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
 use std::io::prelude::*;
-use std::net::Shutdown;
+use std::io::{self, BufReader};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
 use std::os::unix::net::UnixStream;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::Builder;
+use std::time::{Duration, Instant};
+
+/// Bumped whenever the layout of the events changes
+pub const BLOCKSTREAM_SCHEMA_VERSION: u64 = 2;
+
+pub const DEFAULT_MAX_FILE_BYTES: u64 = 64 * 1024 * 1024;
+pub const DEFAULT_MAX_FILE_SECS: u64 = 60 * 60;
+pub const DEFAULT_RETAINED_SLOTS: u64 = 64;
+
+/// Events a TCP client can fall behind by before it's disconnected
+pub const MAX_CLIENT_BACKLOG: usize = 1024;
+
+/// Where the blockstream goes
+#[derive(Clone, Debug, PartialEq)]
+pub enum BlockstreamConfig {
+    /// Unix socket that is connected to for every event
+    Socket(String),
+    /// Directory of newline-delimited JSON files, a new file is started at the first slot after
+    /// the current one grows past `max_file_bytes` or gets older than `max_file_secs`
+    Files {
+        dir: PathBuf,
+        max_file_bytes: u64,
+        max_file_secs: u64,
+    },
+    /// TCP server, the events of the last `retained_slots` slots are kept for clients to resume
+    /// from
+    Tcp {
+        addr: SocketAddr,
+        retained_slots: u64,
+    },
+}
+
+impl FromStr for BlockstreamConfig {
+    type Err = String;
+
+    /// `file://DIR` for files, `tcp://HOST:PORT` for a TCP server, anything else is the path of
+    /// a Unix socket
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if s.starts_with("file://") {
+            Ok(BlockstreamConfig::Files {
+                dir: PathBuf::from(&s["file://".len()..]),
+                max_file_bytes: DEFAULT_MAX_FILE_BYTES,
+                max_file_secs: DEFAULT_MAX_FILE_SECS,
+            })
+        } else if s.starts_with("tcp://") {
+            let addr = s["tcp://".len()..]
+                .parse()
+                .map_err(|err| format!("Invalid blockstream address {}: {:?}", s, err))?;
+            Ok(BlockstreamConfig::Tcp {
+                addr,
+                retained_slots: DEFAULT_RETAINED_SLOTS,
+            })
+        } else {
+            Ok(BlockstreamConfig::Socket(s.to_string()))
+        }
+    }
+}
+
+impl BlockstreamConfig {
+    pub fn writer(&self) -> Result<Box<EntryWriter + Send>> {
+        Ok(match self {
+            BlockstreamConfig::Socket(socket) => Box::new(EntrySocket {
+                socket: socket.clone(),
+            }),
+            BlockstreamConfig::Files {
+                dir,
+                max_file_bytes,
+                max_file_secs,
+            } => Box::new(EntryFiles::new(
+                dir,
+                *max_file_bytes,
+                Duration::from_secs(*max_file_secs),
+            )?),
+            BlockstreamConfig::Tcp {
+                addr,
+                retained_slots,
+            } => Box::new(EntryTcpServer::new(addr, *retained_slots)?),
+        })
+    }
+}
+
+/// JSON Schema of the events
+pub fn schema() -> serde_json::Value {
+    let common = json!({
+        "v": { "type": "integer", "const": BLOCKSTREAM_SCHEMA_VERSION },
+        "dt": { "type": "string", "format": "date-time" },
+        "s": { "type": "integer", "description": "slot" },
+        "h": { "type": "integer", "description": "tick height" },
+        "l": { "type": "string", "description": "slot leader" },
+    });
+    let event = |t: &str, properties: serde_json::Value| {
+        let mut event_properties = common.clone();
+        event_properties["t"] = json!({ "const": t });
+        for (name, property) in properties.as_object().unwrap() {
+            event_properties[name] = property.clone();
+        }
+        json!({
+            "type": "object",
+            "properties": event_properties,
+            "required": ["v", "dt", "t", "s", "h", "l"],
+        })
+    };
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": format!("blockstream event v{}", BLOCKSTREAM_SCHEMA_VERSION),
+        "oneOf": [
+            event("entry", json!({
+                "entry": {
+                    "type": "object",
+                    "properties": {
+                        "num_hashes": { "type": "integer" },
+                        "hash": { "type": "array", "items": { "type": "integer" } },
+                        "transactions": {
+                            "type": "array",
+                            "description": "bincode serialized transactions",
+                            "items": { "type": "array", "items": { "type": "integer" } },
+                        },
+                    },
+                },
+            })),
+            event("block", json!({
+                "hash": { "type": "string", "description": "blockhash" },
+                "transactions": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "signatures": { "type": "array", "items": { "type": "string" } },
+                            "account_keys": { "type": "array", "items": { "type": "string" } },
+                            "recent_blockhash": { "type": "string" },
+                            "status": {
+                                "description": "\"ok\", {\"err\": ...}, or null if unknown",
+                            },
+                            "fee": { "type": ["integer", "null"] },
+                            "instructions": {
+                                "type": "array",
+                                "items": {
+                                    "type": "object",
+                                    "properties": {
+                                        "program_id": { "type": "string" },
+                                        "program": {
+                                            "enum": [
+                                                "system", "budget", "token", "vote", "exchange",
+                                                null,
+                                            ],
+                                        },
+                                        "accounts": {
+                                            "type": "array",
+                                            "items": { "type": "string" },
+                                        },
+                                        "data": {
+                                            "type": "array",
+                                            "items": { "type": "integer" },
+                                        },
+                                        "decoded": {
+                                            "description": "the program's instruction, or null",
+                                        },
+                                    },
+                                },
+                            },
+                        },
+                    },
+                },
+            })),
+        ],
+    })
+}
 
 pub trait EntryWriter: std::fmt::Debug {
-    fn write(&self, payload: String) -> Result<()>;
+    /// Write the event `payload` of `slot`
+    fn write(&self, slot: u64, payload: String) -> Result<()>;
+}
+
+impl<T: EntryWriter + ?Sized> EntryWriter for Box<T> {
+    fn write(&self, slot: u64, payload: String) -> Result<()> {
+        (**self).write(slot, payload)
+    }
 }
 
 #[derive(Debug, Default)]
@@ -25,7 +218,7 @@ pub struct EntryVec {
 }
 
 impl EntryWriter for EntryVec {
-    fn write(&self, payload: String) -> Result<()> {
+    fn write(&self, _slot: u64, payload: String) -> Result<()> {
         self.values.borrow_mut().push(payload);
         Ok(())
     }
@@ -51,7 +244,7 @@ pub struct EntrySocket {
 const MESSAGE_TERMINATOR: &str = "\n";
 
 impl EntryWriter for EntrySocket {
-    fn write(&self, payload: String) -> Result<()> {
+    fn write(&self, _slot: u64, payload: String) -> Result<()> {
         let mut socket = UnixStream::connect(Path::new(&self.socket))?;
         socket.write_all(payload.as_bytes())?;
         socket.write_all(MESSAGE_TERMINATOR.as_bytes())?;
@@ -60,6 +253,267 @@ impl EntryWriter for EntrySocket {
     }
 }
 
+const INDEX_FILE: &str = "index.json";
+
+/// A file in the `index.json` of an `EntryFiles` directory
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BlockstreamFile {
+    pub file: String,
+    pub first_slot: u64,
+    /// `None` while the file is still being written to
+    pub last_slot: Option<u64>,
+}
+
+#[derive(Debug)]
+struct CurrentFile {
+    file: File,
+    bytes: u64,
+    created: Instant,
+    slot: u64,
+}
+
+/// Writes events to newline-delimited JSON files, each holding whole slots, and keeps an index
+/// of which slots are in which file
+#[derive(Debug)]
+pub struct EntryFiles {
+    dir: PathBuf,
+    max_file_bytes: u64,
+    max_file_age: Duration,
+    index: RefCell<Vec<BlockstreamFile>>,
+    current: RefCell<Option<CurrentFile>>,
+}
+
+impl EntryFiles {
+    pub fn new(dir: &Path, max_file_bytes: u64, max_file_age: Duration) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        let index_path = dir.join(INDEX_FILE);
+        let mut index: Vec<BlockstreamFile> = if index_path.exists() {
+            serde_json::from_reader(File::open(&index_path)?)?
+        } else {
+            vec![]
+        };
+        // Whatever was being written to before a restart is done with
+        if let Some(last) = index.last_mut() {
+            if last.last_slot.is_none() {
+                let last_event = BufReader::new(File::open(dir.join(&last.file))?)
+                    .lines()
+                    .filter_map(|line| serde_json::from_str::<serde_json::Value>(&line.ok()?).ok())
+                    .last();
+                last.last_slot = last_event
+                    .and_then(|event| event["s"].as_u64())
+                    .or(Some(last.first_slot));
+            }
+        }
+        let schema_path = dir.join(format!("schema-v{}.json", BLOCKSTREAM_SCHEMA_VERSION));
+        fs::write(schema_path, serde_json::to_string_pretty(&schema())?)?;
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            max_file_bytes,
+            max_file_age,
+            index: RefCell::new(index),
+            current: RefCell::new(None),
+        })
+    }
+
+    pub fn index(&self) -> Vec<BlockstreamFile> {
+        self.index.borrow().clone()
+    }
+
+    fn save_index(&self) -> Result<()> {
+        let index_path = self.dir.join(INDEX_FILE);
+        let tmp_path = index_path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_string(&*self.index.borrow())?)?;
+        fs::rename(&tmp_path, &index_path)?;
+        Ok(())
+    }
+
+    /// Close the current file, if any, and start a new one at `slot`
+    fn rotate(&self, slot: u64) -> Result<()> {
+        let mut index = self.index.borrow_mut();
+        if let Some(current) = self.current.borrow().as_ref() {
+            index.last_mut().unwrap().last_slot = Some(current.slot);
+        }
+        let name = format!("blockstream-{:020}-{}.jsonl", slot, Utc::now().timestamp());
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(&name))?;
+        index.push(BlockstreamFile {
+            file: name,
+            first_slot: slot,
+            last_slot: None,
+        });
+        drop(index);
+        *self.current.borrow_mut() = Some(CurrentFile {
+            file,
+            bytes: 0,
+            created: Instant::now(),
+            slot,
+        });
+        self.save_index()
+    }
+}
+
+impl EntryWriter for EntryFiles {
+    fn write(&self, slot: u64, payload: String) -> Result<()> {
+        // Only rotate between slots so that each slot is entirely within one file
+        let rotate = self.current.borrow().as_ref().map_or(true, |current| {
+            slot != current.slot
+                && (current.bytes >= self.max_file_bytes
+                    || current.created.elapsed() >= self.max_file_age)
+        });
+        if rotate {
+            self.rotate(slot)?;
+        }
+
+        let mut current = self.current.borrow_mut();
+        let current = current.as_mut().unwrap();
+        current.file.write_all(payload.as_bytes())?;
+        current.file.write_all(MESSAGE_TERMINATOR.as_bytes())?;
+        current.bytes += payload.len() as u64 + 1;
+        current.slot = slot;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+struct TcpClients {
+    /// Events of the last `retained_slots` slots
+    events: VecDeque<(u64, Arc<String>)>,
+    /// Each client's backlog of events, written out by the client's own thread
+    senders: Vec<SyncSender<Arc<String>>>,
+}
+
+/// Serves the events to TCP clients.  A client sends one line holding the slot to resume from,
+/// or an empty line to only receive new events, and then receives one event per line.  A client
+/// that falls `MAX_CLIENT_BACKLOG` events behind is disconnected and has to resume.
+#[derive(Debug)]
+pub struct EntryTcpServer {
+    local_addr: SocketAddr,
+    retained_slots: u64,
+    clients: Arc<Mutex<TcpClients>>,
+}
+
+impl EntryTcpServer {
+    pub fn new(addr: &SocketAddr, retained_slots: u64) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let clients = Arc::new(Mutex::new(TcpClients::default()));
+        let thread_clients = clients.clone();
+        Builder::new()
+            .name("soros-blockstream-tcp".to_string())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    if let Err(err) = stream
+                        .map_err(Error::from)
+                        .and_then(|stream| Self::spawn_client(stream, thread_clients.clone()))
+                    {
+                        debug!("blockstream client failed: {:?}", err);
+                    }
+                }
+            })?;
+        Ok(Self {
+            local_addr,
+            retained_slots,
+            clients,
+        })
+    }
+
+    pub fn local_addr(&self) -> &SocketAddr {
+        &self.local_addr
+    }
+
+    /// Every client is served by its own thread, from the handshake on
+    fn spawn_client(stream: TcpStream, clients: Arc<Mutex<TcpClients>>) -> Result<()> {
+        Builder::new()
+            .name("soros-blockstream-client".to_string())
+            .spawn(move || {
+                if let Err(err) = Self::serve_client(stream, &clients) {
+                    debug!("blockstream client failed: {:?}", err);
+                }
+            })?;
+        Ok(())
+    }
+
+    fn serve_client(stream: TcpStream, clients: &Mutex<TcpClients>) -> Result<()> {
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+        stream.set_write_timeout(Some(Duration::from_secs(1)))?;
+        let mut request = String::new();
+        BufReader::new(&stream).read_line(&mut request)?;
+        let request = request.trim();
+        let from_slot = if request.is_empty() {
+            None
+        } else {
+            Some(
+                request
+                    .parse::<u64>()
+                    .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "expected a slot"))?,
+            )
+        };
+
+        // Pick the retained events and start the backlog together so none are missed
+        let (sender, receiver) = sync_channel(MAX_CLIENT_BACKLOG);
+        let resumed: Vec<_> = {
+            let mut clients = clients.lock().unwrap();
+            clients.senders.push(sender);
+            from_slot.map_or(vec![], |from_slot| {
+                clients
+                    .events
+                    .iter()
+                    .filter(|(slot, _)| *slot >= from_slot)
+                    .map(|(_, payload)| payload.clone())
+                    .collect()
+            })
+        };
+
+        let result = Self::write_events(&stream, resumed, &receiver);
+        let _ = stream.shutdown(Shutdown::Both);
+        result
+    }
+
+    /// Writes the resumed events and then the backlog until the server drops the client
+    fn write_events(
+        mut stream: &TcpStream,
+        resumed: Vec<Arc<String>>,
+        receiver: &Receiver<Arc<String>>,
+    ) -> Result<()> {
+        for payload in resumed.into_iter().chain(receiver.iter()) {
+            stream.write_all(payload.as_bytes())?;
+            stream.write_all(MESSAGE_TERMINATOR.as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+impl EntryWriter for EntryTcpServer {
+    fn write(&self, slot: u64, payload: String) -> Result<()> {
+        let payload = Arc::new(payload);
+        let mut clients = self.clients.lock().unwrap();
+        clients
+            .senders
+            .retain(|sender| match sender.try_send(payload.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    warn!("blockstream client fell behind, disconnecting it");
+                    false
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            });
+
+        clients.events.push_back((slot, payload));
+        let oldest_slot = slot.saturating_sub(self.retained_slots.saturating_sub(1));
+        while clients
+            .events
+            .front()
+            .map_or(false, |(slot, _)| *slot < oldest_slot)
+        {
+            clients.events.pop_front();
+        }
+        Ok(())
+    }
+}
+
 pub trait BlockstreamEvents {
     fn emit_entry_event(
         &self,
@@ -68,12 +522,16 @@ pub trait BlockstreamEvents {
         leader_id: &Pubkey,
         entries: &Entry,
     ) -> Result<()>;
+    /// `bank` is the bank of `slot`, if it's still around, for the status and fee of the
+    /// transactions in `entries`
     fn emit_block_event(
         &self,
         slot: u64,
         tick_height: u64,
         leader_id: &Pubkey,
         blockhash: Hash,
+        entries: &[Entry],
+        bank: Option<&Bank>,
     ) -> Result<()>;
 }
 
@@ -101,14 +559,15 @@ where
         });
         let json_entry = serde_json::to_string(&stream_entry)?;
         let payload = format!(
-            r#"{{"dt":"{}","t":"entry","s":{},"h":{},"l":"{:?}","entry":{}}}"#,
+            r#"{{"v":{},"dt":"{}","t":"entry","s":{},"h":{},"l":"{:?}","entry":{}}}"#,
+            BLOCKSTREAM_SCHEMA_VERSION,
             Utc::now().to_rfc3339_opts(SecondsFormat::Nanos, true),
             slot,
             tick_height,
             leader_id,
             json_entry,
         );
-        self.output.write(payload)?;
+        self.output.write(slot, payload)?;
         Ok(())
     }
 
@@ -118,16 +577,28 @@ where
         tick_height: u64,
         leader_id: &Pubkey,
         blockhash: Hash,
+        entries: &[Entry],
+        bank: Option<&Bank>,
     ) -> Result<()> {
+        let transactions: Vec<Value> = entries
+            .iter()
+            .flat_map(|entry| &entry.transactions)
+            .map(|tx| decode_transaction(tx, bank))
+            .collect();
         let payload = format!(
-            r#"{{"dt":"{}","t":"block","s":{},"h":{},"l":"{:?}","hash":"{:?}"}}"#,
+            concat!(
+                r#"{{"v":{},"dt":"{}","t":"block","s":{},"h":{},"l":"{:?}","hash":"{:?}","#,
+                r#""transactions":{}}}"#
+            ),
+            BLOCKSTREAM_SCHEMA_VERSION,
             Utc::now().to_rfc3339_opts(SecondsFormat::Nanos, true),
             slot,
             tick_height,
             leader_id,
             blockhash,
+            serde_json::to_string(&transactions)?,
         );
-        self.output.write(payload)?;
+        self.output.write(slot, payload)?;
         Ok(())
     }
 }
@@ -142,13 +613,24 @@ impl SocketBlockstream {
     }
 }
 
+/// Blockstream to wherever a `BlockstreamConfig` points
+pub type ConfiguredBlockstream = Blockstream<Box<EntryWriter + Send>>;
+
+impl ConfiguredBlockstream {
+    pub fn new(config: &BlockstreamConfig) -> Result<Self> {
+        Ok(Blockstream {
+            output: config.writer()?,
+        })
+    }
+}
+
 pub type MockBlockstream = Blockstream<EntryVec>;
 
 impl MockBlockstream {
-    pub fn new(_: String) -> Self {
-        Blockstream {
+    pub fn new(_: &BlockstreamConfig) -> Result<Self> {
+        Ok(Blockstream {
             output: EntryVec::new(),
-        }
+        })
     }
 
     pub fn entries(&self) -> Vec<String> {
@@ -164,16 +646,107 @@ fn serialize_transactions(entry: &Entry) -> Vec<Vec<u8>> {
         .collect()
 }
 
+/// Decode the instruction `data` of the programs the blockstream knows about, returning the
+/// name of the program and its instruction
+fn decode_instruction(program_id: &Pubkey, data: &[u8]) -> (Option<&'static str>, Value) {
+    fn decode<'a, T>(data: &'a [u8]) -> Value
+    where
+        T: serde::Deserialize<'a> + serde::Serialize,
+    {
+        bincode::deserialize::<T>(data)
+            .ok()
+            .and_then(|instruction| serde_json::to_value(instruction).ok())
+            .unwrap_or(Value::Null)
+    }
+
+    if system_program::check_id(program_id) {
+        (Some("system"), decode::<SystemInstruction>(data))
+    } else if *program_id == soros_budget_api::id() {
+        (Some("budget"), decode::<BudgetInstruction>(data))
+    } else if *program_id == soros_token_api::id() {
+        (Some("token"), decode::<TokenInstruction>(data))
+    } else if *program_id == soros_vote_api::id() {
+        (Some("vote"), decode::<VoteInstruction>(data))
+    } else if *program_id == soros_exchange_api::id() {
+        (Some("exchange"), decode::<ExchangeInstruction>(data))
+    } else {
+        (None, Value::Null)
+    }
+}
+
+/// Decode `tx` for the blockstream.  Entries aren't sanitized before they're streamed, so an
+/// instruction with an out of range program id index is reported without a program, and a
+/// transaction without signatures without a status.
+fn decode_transaction(tx: &Transaction, bank: Option<&Bank>) -> Value {
+    let message = tx.message();
+    let pubkey_str = |pubkey: &Pubkey| pubkey.to_string();
+    let instructions: Vec<Value> = message
+        .instructions
+        .iter()
+        .map(|instruction| {
+            let program_id = message
+                .program_ids()
+                .get(instruction.program_ids_index as usize);
+            let (program, decoded) = match program_id {
+                Some(program_id) => decode_instruction(program_id, &instruction.data),
+                None => (None, Value::Null),
+            };
+            let accounts: Vec<String> = instruction
+                .accounts
+                .iter()
+                .filter_map(|i| message.account_keys.get(*i as usize).map(pubkey_str))
+                .collect();
+            json!({
+                "program_id": program_id.map(pubkey_str),
+                "program": program,
+                "accounts": accounts,
+                "data": instruction.data,
+                "decoded": decoded,
+            })
+        })
+        .collect();
+
+    let status = bank.and_then(|bank| {
+        tx.signatures
+            .get(0)
+            .and_then(|signature| bank.get_signature_status(signature))
+    });
+    // Only transactions the bank executed were charged a fee
+    let fee = bank
+        .filter(|_| status.is_some())
+        .map(|bank| bank.fee_calculator.calculate_fee(message));
+    let status = status
+        .map(|status| match status {
+            Ok(()) => json!("ok"),
+            Err(err) => json!({ "err": format!("{:?}", err) }),
+        })
+        .unwrap_or(Value::Null);
+    let signatures: Vec<String> = tx.signatures.iter().map(ToString::to_string).collect();
+    let account_keys: Vec<String> = message.account_keys.iter().map(pubkey_str).collect();
+
+    json!({
+        "signatures": signatures,
+        "account_keys": account_keys,
+        "recent_blockhash": message.recent_blockhash.to_string(),
+        "status": status,
+        "fee": fee,
+        "instructions": instructions,
+    })
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::blocktree::get_tmp_ledger_path;
     use crate::entry::Entry;
     use chrono::{DateTime, FixedOffset};
     use serde_json::Value;
+    use soros_sdk::genesis_block::GenesisBlock;
     use soros_sdk::hash::Hash;
     use soros_sdk::signature::{Keypair, KeypairUtil};
     use soros_sdk::system_transaction;
     use std::collections::HashSet;
+    use std::io::BufRead;
 
     #[test]
     fn test_serialize_transactions() {
@@ -196,9 +769,69 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_decode_transaction() {
+        let (genesis_block, mint_keypair) = GenesisBlock::new(1000);
+        let bank = Bank::new(&genesis_block);
+        let to = Pubkey::new_rand();
+        let blockhash = bank.last_blockhash();
+        let tx = system_transaction::transfer(&mint_keypair, &to, 10, blockhash, 0);
+        bank.process_transaction(&tx).unwrap();
+        let failed_tx = system_transaction::transfer(&mint_keypair, &to, 5000, blockhash, 0);
+        assert!(bank.process_transaction(&failed_tx).is_err());
+
+        let json = decode_transaction(&tx, Some(&bank));
+        assert_eq!(json["signatures"][0], tx.signatures[0].to_string());
+        assert_eq!(json["account_keys"][1], to.to_string());
+        assert_eq!(json["status"], "ok");
+        assert_eq!(json["fee"], bank.fee_calculator.calculate_fee(tx.message()));
+        let instruction = &json["instructions"][0];
+        assert_eq!(instruction["program"], "system");
+        assert_eq!(instruction["accounts"][1], to.to_string());
+        assert_eq!(instruction["decoded"]["Transfer"]["dif"], 10);
+
+        let json = decode_transaction(&failed_tx, Some(&bank));
+        assert!(json["status"]["err"].is_string());
+        assert_eq!(json["fee"], bank.fee_calculator.calculate_fee(tx.message()));
+
+        // Without the bank, or if the bank never executed it, there's no status or fee
+        let json = decode_transaction(&tx, None);
+        assert_eq!(json["status"], Value::Null);
+        assert_eq!(json["fee"], Value::Null);
+        let unprocessed_tx = system_transaction::transfer(&mint_keypair, &to, 1, blockhash, 0);
+        let json = decode_transaction(&unprocessed_tx, Some(&bank));
+        assert_eq!(json["status"], Value::Null);
+        assert_eq!(json["fee"], Value::Null);
+
+        // Malformed transactions are decoded as far as they can be
+        let mut malformed_tx = tx.clone();
+        malformed_tx.signatures.clear();
+        malformed_tx.message.instructions[0].program_ids_index = 99;
+        let json = decode_transaction(&malformed_tx, Some(&bank));
+        assert_eq!(json["status"], Value::Null);
+        assert_eq!(json["fee"], Value::Null);
+        let instruction = &json["instructions"][0];
+        assert_eq!(instruction["program_id"], Value::Null);
+        assert_eq!(instruction["program"], Value::Null);
+        assert_eq!(instruction["accounts"][1], to.to_string());
+    }
+
+    #[test]
+    fn test_decode_instruction_unknown_program() {
+        let (program, decoded) = decode_instruction(&Pubkey::new_rand(), &[1, 2, 3]);
+        assert_eq!(program, None);
+        assert_eq!(decoded, Value::Null);
+
+        // Data a known program can't make sense of is left undecoded
+        let (program, decoded) = decode_instruction(&soros_vote_api::id(), &[255; 3]);
+        assert_eq!(program, Some("vote"));
+        assert_eq!(decoded, Value::Null);
+    }
+
     #[test]
     fn test_blockstream() -> () {
-        let blockstream = MockBlockstream::new("test_stream".to_string());
+        let config = BlockstreamConfig::Socket("test_stream".to_string());
+        let blockstream = MockBlockstream::new(&config).unwrap();
         let ticks_per_slot = 5;
 
         let mut blockhash = Hash::default();
@@ -213,7 +846,7 @@ mod test {
         for tick_height in tick_height_initial..=tick_height_final {
             if tick_height == 5 {
                 blockstream
-                    .emit_block_event(curr_slot, tick_height - 1, &leader_id, blockhash)
+                    .emit_block_event(curr_slot, tick_height - 1, &leader_id, blockhash, &[], None)
                     .unwrap();
                 curr_slot += 1;
             }
@@ -239,6 +872,7 @@ mod test {
 
         for item in blockstream.entries() {
             let json: Value = serde_json::from_str(&item).unwrap();
+            assert_eq!(json["v"], BLOCKSTREAM_SCHEMA_VERSION);
             let dt_str = json["dt"].as_str().unwrap();
 
             // Ensure `ts` field parses as valid DateTime
@@ -272,4 +906,111 @@ mod test {
         assert_eq!(matched_slots.len(), 2);
         assert_eq!(matched_blocks.len(), 1);
     }
+
+    #[test]
+    fn test_blockstream_config_from_str() {
+        assert_eq!(
+            "/tmp/blockstream.sock".parse(),
+            Ok(BlockstreamConfig::Socket(
+                "/tmp/blockstream.sock".to_string()
+            ))
+        );
+        assert_eq!(
+            "file:///var/blockstream".parse(),
+            Ok(BlockstreamConfig::Files {
+                dir: PathBuf::from("/var/blockstream"),
+                max_file_bytes: DEFAULT_MAX_FILE_BYTES,
+                max_file_secs: DEFAULT_MAX_FILE_SECS,
+            })
+        );
+        assert_eq!(
+            "tcp://127.0.0.1:8888".parse(),
+            Ok(BlockstreamConfig::Tcp {
+                addr: "127.0.0.1:8888".parse().unwrap(),
+                retained_slots: DEFAULT_RETAINED_SLOTS,
+            })
+        );
+        assert!("tcp://localhost".parse::<BlockstreamConfig>().is_err());
+    }
+
+    #[test]
+    fn test_entry_files_rotate_on_slot_boundary() {
+        let dir = get_tmp_ledger_path!();
+        let files = EntryFiles::new(Path::new(&dir), 20, Duration::from_secs(60)).unwrap();
+
+        // A slot is never split across files, even when it outgrows the limit
+        files.write(1, "a".repeat(8)).unwrap();
+        files.write(1, "b".repeat(8)).unwrap();
+        files.write(2, "c".repeat(8)).unwrap();
+        files.write(2, "d".repeat(1)).unwrap();
+        files.write(3, "e".repeat(8)).unwrap();
+
+        let index = files.index();
+        assert_eq!(index.len(), 2);
+        assert_eq!((index[0].first_slot, index[0].last_slot), (1, Some(2)));
+        assert_eq!((index[1].first_slot, index[1].last_slot), (3, None));
+
+        let saved_index: Vec<BlockstreamFile> =
+            serde_json::from_reader(File::open(Path::new(&dir).join(INDEX_FILE)).unwrap()).unwrap();
+        assert_eq!(saved_index, index);
+        let first_file = File::open(Path::new(&dir).join(&index[0].file)).unwrap();
+        assert_eq!(BufReader::new(first_file).lines().count(), 4);
+        assert!(Path::new(&dir)
+            .join(format!("schema-v{}.json", BLOCKSTREAM_SCHEMA_VERSION))
+            .exists());
+
+        // A restart closes off the last file
+        drop(files);
+        let files = EntryFiles::new(Path::new(&dir), 20, Duration::from_secs(60)).unwrap();
+        assert_eq!(files.index()[1].last_slot, Some(3));
+        files.write(4, "f".to_string()).unwrap();
+        assert_eq!(files.index().len(), 3);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_entry_tcp_server_resume() {
+        let server = EntryTcpServer::new(&"127.0.0.1:0".parse().unwrap(), 2).unwrap();
+        for slot in 1..=3 {
+            server.write(slot, format!("slot {}", slot)).unwrap();
+        }
+
+        let connect = |request: &str| {
+            let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+            stream.write_all(request.as_bytes()).unwrap();
+            // Wait for the server to register the client
+            let start = Instant::now();
+            while server.clients.lock().unwrap().senders.len() < 1 {
+                assert!(start.elapsed() < Duration::from_secs(5));
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            BufReader::new(stream).lines()
+        };
+
+        // Slot 1 is no longer retained
+        let mut resumed = connect("1\n");
+        server.write(4, "slot 4".to_string()).unwrap();
+        for slot in 2..=4 {
+            assert_eq!(resumed.next().unwrap().unwrap(), format!("slot {}", slot));
+        }
+        assert_eq!(server.clients.lock().unwrap().events.front().unwrap().0, 3);
+    }
+
+    #[test]
+    fn test_entry_tcp_server_drops_slow_client() {
+        let server = EntryTcpServer::new(&"127.0.0.1:0".parse().unwrap(), 2).unwrap();
+        let (sender, receiver) = sync_channel(MAX_CLIENT_BACKLOG);
+        server.clients.lock().unwrap().senders.push(sender);
+
+        for slot in 0..MAX_CLIENT_BACKLOG as u64 {
+            server.write(slot, format!("slot {}", slot)).unwrap();
+        }
+        assert_eq!(server.clients.lock().unwrap().senders.len(), 1);
+
+        // The client doesn't keep up and is dropped, after what it was sent so far
+        server.write(0, "one too many".to_string()).unwrap();
+        assert!(server.clients.lock().unwrap().senders.is_empty());
+        assert_eq!(receiver.iter().count(), MAX_CLIENT_BACKLOG);
+    }
 }
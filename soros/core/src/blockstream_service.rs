@@ -1,19 +1,21 @@
 //! The `blockstream_service` implements optional streaming of entries and block metadata
 //! using the `blockstream` module, providing client services such as a block explorer with
-//! real-time access to entries.
+//! real-time access to entries.  The bank of each slot, while it's still in `BankForks`, provides
+//! the status and fee of the slot's transactions.
 
-use crate::blockstream::BlockstreamEvents;
+use crate::bank_forks::BankForks;
+#[cfg(not(test))]
+use crate::blockstream::ConfiguredBlockstream as Blockstream;
 #[cfg(test)]
 use crate::blockstream::MockBlockstream as Blockstream;
-#[cfg(not(test))]
-use crate::blockstream::SocketBlockstream as Blockstream;
+use crate::blockstream::{BlockstreamConfig, BlockstreamEvents};
 use crate::blocktree::Blocktree;
 use crate::result::{Error, Result};
 use crate::service::Service;
 use soros_sdk::pubkey::Pubkey;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{Receiver, RecvTimeoutError};
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use std::thread::{self, Builder, JoinHandle};
 use std::time::Duration;
 
@@ -26,10 +28,11 @@ impl BlockstreamService {
     pub fn new(
         slot_full_receiver: Receiver<(u64, Pubkey)>,
         blocktree: Arc<Blocktree>,
-        blockstream_socket: String,
+        bank_forks: Arc<RwLock<BankForks>>,
+        blockstream_config: &BlockstreamConfig,
         exit: &Arc<AtomicBool>,
-    ) -> Self {
-        let mut blockstream = Blockstream::new(blockstream_socket);
+    ) -> Result<Self> {
+        let mut blockstream = Blockstream::new(blockstream_config)?;
         let exit = exit.clone();
         let t_blockstream = Builder::new()
             .name("soros-blockstream".to_string())
@@ -37,9 +40,12 @@ impl BlockstreamService {
                 if exit.load(Ordering::Relaxed) {
                     break;
                 }
-                if let Err(e) =
-                    Self::process_entries(&slot_full_receiver, &blocktree, &mut blockstream)
-                {
+                if let Err(e) = Self::process_entries(
+                    &slot_full_receiver,
+                    &blocktree,
+                    &bank_forks,
+                    &mut blockstream,
+                ) {
                     match e {
                         Error::RecvTimeoutError(RecvTimeoutError::Disconnected) => break,
                         Error::RecvTimeoutError(RecvTimeoutError::Timeout) => (),
@@ -48,15 +54,17 @@ impl BlockstreamService {
                 }
             })
             .unwrap();
-        Self { t_blockstream }
+        Ok(Self { t_blockstream })
     }
     fn process_entries(
         slot_full_receiver: &Receiver<(u64, Pubkey)>,
         blocktree: &Arc<Blocktree>,
+        bank_forks: &Arc<RwLock<BankForks>>,
         blockstream: &mut Blockstream,
     ) -> Result<()> {
         let timeout = Duration::new(1, 0);
         let (slot, slot_leader) = slot_full_receiver.recv_timeout(timeout)?;
+        let bank = bank_forks.read().unwrap().get(slot).cloned();
 
         let entries = blocktree.get_slot_entries(slot, 0, None).unwrap();
         let blocktree_meta = blocktree.meta(slot).unwrap().unwrap();
@@ -86,7 +94,14 @@ impl BlockstreamService {
                 });
             if i == entries.len() - 1 {
                 blockstream
-                    .emit_block_event(slot, tick_height, &slot_leader, entry.hash)
+                    .emit_block_event(
+                        slot,
+                        tick_height,
+                        &slot_leader,
+                        entry.hash,
+                        &entries,
+                        bank.as_ref().map(|bank| &**bank),
+                    )
                     .unwrap_or_else(|e| {
                         debug!("Blockstream error: {:?}, {:?}", e, blockstream.output);
                     });
@@ -112,6 +127,7 @@ mod test {
     use bincode::{deserialize, serialize};
     use chrono::{DateTime, FixedOffset};
     use serde_json::Value;
+    use soros_runtime::bank::Bank;
     use soros_sdk::genesis_block::GenesisBlock;
     use soros_sdk::hash::Hash;
     use soros_sdk::signature::{Keypair, KeypairUtil};
@@ -129,9 +145,11 @@ mod test {
 
         let (ledger_path, _blockhash) = create_new_tmp_ledger!(&genesis_block);
         let blocktree = Blocktree::open(&ledger_path).unwrap();
+        let bank_forks = Arc::new(RwLock::new(BankForks::new(0, Bank::new(&genesis_block))));
 
         // Set up blockstream
-        let mut blockstream = Blockstream::new("test_stream".to_string());
+        let config = BlockstreamConfig::Socket("test_stream".to_string());
+        let mut blockstream = Blockstream::new(&config).unwrap();
 
         // Set up dummy channel to receive a full-slot notification
         let (slot_full_sender, slot_full_receiver) = channel();
@@ -165,6 +183,7 @@ mod test {
         BlockstreamService::process_entries(
             &slot_full_receiver,
             &Arc::new(blocktree),
+            &bank_forks,
             &mut blockstream,
         )
         .unwrap();
@@ -223,6 +242,18 @@ mod test {
             assert_eq!(1, slot);
             let height = json["h"].as_u64().unwrap();
             assert_eq!(2 * ticks_per_slot - 1, height);
+
+            let transactions = json["transactions"].as_array().unwrap();
+            assert_eq!(transactions.len(), 1);
+            let tx = &expected_entries[4].transactions[0];
+            assert_eq!(
+                transactions[0]["signatures"][0],
+                tx.signatures[0].to_string()
+            );
+            assert_eq!(transactions[0]["instructions"][0]["program"], "system");
+            // There's no bank for slot 1, so no status or fee
+            assert_eq!(transactions[0]["status"], Value::Null);
+            assert_eq!(transactions[0]["fee"], Value::Null);
         }
     }
 }
@@ -1,5 +1,5 @@
 use crate::bank_forks::BankForks;
-use crate::blocktree::Blocktree;
+use crate::blocktree::{Blocktree, SlotMeta};
 use crate::entry::{Entry, EntrySlice};
 use crate::leader_schedule_cache::LeaderScheduleCache;
 use rayon::prelude::*;
@@ -7,6 +7,7 @@ use soros_metrics::counter::Counter;
 use soros_runtime::bank::Bank;
 use soros_runtime::locked_accounts_results::LockedAccountsResults;
 use soros_sdk::genesis_block::GenesisBlock;
+use soros_sdk::hash::Hash;
 use soros_sdk::timing::duration_as_ms;
 use soros_sdk::timing::MAX_RECENT_BLOCKHASHES;
 use soros_sdk::transaction::Result;
@@ -105,6 +106,8 @@ pub enum BlocktreeProcessorError {
     LedgerVerificationFailed,
 }
 
+type PendingSlot = (u64, SlotMeta, Arc<Bank>, u64, Hash);
+
 pub fn process_blocktree(
     genesis_block: &GenesisBlock,
     blocktree: &Blocktree,
@@ -113,7 +116,7 @@ pub fn process_blocktree(
     let now = Instant::now();
     info!("processing ledger...");
     // Setup bank for slot 0
-    let mut pending_slots = {
+    let pending_slots = {
         let slot = 0;
         let bank = Arc::new(Bank::new_with_paths(&genesis_block, account_paths));
         let entry_height = 0;
@@ -132,8 +135,84 @@ pub fn process_blocktree(
     };
 
     let leader_schedule_cache = LeaderScheduleCache::new(*pending_slots[0].2.epoch_schedule());
+    let fork_info =
+        process_pending_slots(blocktree, pending_slots, vec![], &leader_schedule_cache)?;
 
+    let (banks, bank_forks_info): (Vec<_>, Vec<_>) = fork_info.into_iter().unzip();
+    let bank_forks = BankForks::new_from_banks(&banks);
+    info!(
+        "processing ledger...complete in {}ms, forks={}...",
+        duration_as_ms(&now.elapsed()),
+        bank_forks_info.len(),
+    );
+
+    Ok((bank_forks, bank_forks_info, leader_schedule_cache))
+}
+
+/// Start from a bank restored from a snapshot and only replay the slots of the ledger that
+/// descend from it. The first entries after the snapshot have to chain from its last blockhash.
+pub fn process_blocktree_from_snapshot(
+    snapshot_bank: Bank,
+    blocktree: &Blocktree,
+) -> result::Result<(BankForks, Vec<BankForksInfo>, LeaderScheduleCache), BlocktreeProcessorError> {
+    let now = Instant::now();
+    let slot = snapshot_bank.slot();
+    info!("processing ledger from snapshot of slot {}...", slot);
+    let bank = Arc::new(snapshot_bank);
+    let leader_schedule_cache = LeaderScheduleCache::new(*bank.epoch_schedule());
+    // The entries up to the snapshot aren't replayed, so entry heights count from it
+    let entry_height = 0;
+
+    // A node that just joined may not have the snapshot slot in its ledger yet
+    let next_slots = blocktree
+        .meta(slot)
+        .map_err(|err| {
+            warn!("Failed to load meta for slot {}: {:?}", slot, err);
+            BlocktreeProcessorError::LedgerVerificationFailed
+        })?
+        .map(|meta| meta.next_slots)
+        .unwrap_or_default();
+
+    let mut pending_slots = vec![];
     let mut fork_info = vec![];
+    if next_slots.is_empty() {
+        let bfi = BankForksInfo {
+            bank_slot: slot,
+            entry_height,
+        };
+        fork_info.push((bank, bfi));
+    } else {
+        queue_next_slots(
+            blocktree,
+            &bank,
+            &next_slots,
+            entry_height,
+            bank.last_blockhash(),
+            &leader_schedule_cache,
+            &mut pending_slots,
+            &mut fork_info,
+        )?;
+    }
+    let fork_info =
+        process_pending_slots(blocktree, pending_slots, fork_info, &leader_schedule_cache)?;
+
+    let (banks, bank_forks_info): (Vec<_>, Vec<_>) = fork_info.into_iter().unzip();
+    let bank_forks = BankForks::new_from_banks(&banks);
+    info!(
+        "processing ledger from snapshot...complete in {}ms, forks={}...",
+        duration_as_ms(&now.elapsed()),
+        bank_forks_info.len(),
+    );
+
+    Ok((bank_forks, bank_forks_info, leader_schedule_cache))
+}
+
+fn process_pending_slots(
+    blocktree: &Blocktree,
+    mut pending_slots: Vec<PendingSlot>,
+    mut fork_info: Vec<(Arc<Bank>, BankForksInfo)>,
+    leader_schedule_cache: &LeaderScheduleCache,
+) -> result::Result<Vec<(Arc<Bank>, BankForksInfo)>, BlocktreeProcessorError> {
     let mut last_status_report = Instant::now();
     while !pending_slots.is_empty() {
         let (slot, meta, bank, mut entry_height, mut last_entry_hash) =
@@ -202,57 +281,73 @@ pub fn process_blocktree(
         }
 
         // This is a fork point, create a new child bank for each fork
-        for next_slot in meta.next_slots {
-            let next_meta = blocktree
-                .meta(next_slot)
-                .map_err(|err| {
-                    warn!("Failed to load meta for slot {}: {:?}", slot, err);
-                    BlocktreeProcessorError::LedgerVerificationFailed
-                })?
-                .unwrap();
-
-            // only process full slots in blocktree_processor, replay_stage
-            //  handles any partials
-            if next_meta.is_full() {
-                let next_bank = Arc::new(Bank::new_from_parent(
-                    &bank,
-                    &leader_schedule_cache
-                        .slot_leader_at_else_compute(next_slot, &bank)
-                        .unwrap(),
-                    next_slot,
-                ));
-                trace!("Add child bank for slot={}", next_slot);
-                // bank_forks.insert(*next_slot, child_bank);
-                pending_slots.push((
-                    next_slot,
-                    next_meta,
-                    next_bank,
-                    entry_height,
-                    last_entry_hash,
-                ));
-            } else {
-                let bfi = BankForksInfo {
-                    bank_slot: slot,
-                    entry_height,
-                };
-                fork_info.push((bank.clone(), bfi));
-            }
-        }
+        queue_next_slots(
+            blocktree,
+            &bank,
+            &meta.next_slots,
+            entry_height,
+            last_entry_hash,
+            leader_schedule_cache,
+            &mut pending_slots,
+            &mut fork_info,
+        )?;
 
         // reverse sort by slot, so the next slot to be processed can be pop()ed
         // TODO: remove me once leader_scheduler can hang with out-of-order slots?
         pending_slots.sort_by(|a, b| b.0.cmp(&a.0));
     }
+    Ok(fork_info)
+}
 
-    let (banks, bank_forks_info): (Vec<_>, Vec<_>) = fork_info.into_iter().unzip();
-    let bank_forks = BankForks::new_from_banks(&banks);
-    info!(
-        "processing ledger...complete in {}ms, forks={}...",
-        duration_as_ms(&now.elapsed()),
-        bank_forks_info.len(),
-    );
+#[allow(clippy::too_many_arguments)]
+fn queue_next_slots(
+    blocktree: &Blocktree,
+    bank: &Arc<Bank>,
+    next_slots: &[u64],
+    entry_height: u64,
+    last_entry_hash: Hash,
+    leader_schedule_cache: &LeaderScheduleCache,
+    pending_slots: &mut Vec<PendingSlot>,
+    fork_info: &mut Vec<(Arc<Bank>, BankForksInfo)>,
+) -> result::Result<(), BlocktreeProcessorError> {
+    let slot = bank.slot();
+    for next_slot in next_slots {
+        let next_meta = blocktree
+            .meta(*next_slot)
+            .map_err(|err| {
+                warn!("Failed to load meta for slot {}: {:?}", slot, err);
+                BlocktreeProcessorError::LedgerVerificationFailed
+            })?
+            .unwrap();
 
-    Ok((bank_forks, bank_forks_info, leader_schedule_cache))
+        // only process full slots in blocktree_processor, replay_stage
+        //  handles any partials
+        if next_meta.is_full() {
+            let next_bank = Arc::new(Bank::new_from_parent(
+                &bank,
+                &leader_schedule_cache
+                    .slot_leader_at_else_compute(*next_slot, &bank)
+                    .unwrap(),
+                *next_slot,
+            ));
+            trace!("Add child bank for slot={}", next_slot);
+            // bank_forks.insert(*next_slot, child_bank);
+            pending_slots.push((
+                *next_slot,
+                next_meta,
+                next_bank,
+                entry_height,
+                last_entry_hash,
+            ));
+        } else {
+            let bfi = BankForksInfo {
+                bank_slot: slot,
+                entry_height,
+            };
+            fork_info.push((bank.clone(), bfi));
+        }
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -557,6 +652,59 @@ mod tests {
         assert_eq!(bank.last_blockhash(), entries.last().unwrap().hash);
     }
 
+    #[test]
+    fn test_process_blocktree_from_snapshot() {
+        soros_logger::setup();
+        let (genesis_block, _mint_keypair) = GenesisBlock::new(10_000);
+        let ticks_per_slot = genesis_block.ticks_per_slot;
+        let (ledger_path, mut blockhash) = create_new_tmp_ledger!(&genesis_block);
+        let blocktree =
+            Blocktree::open(&ledger_path).expect("Expected to successfully open database ledger");
+        for slot in 1..4 {
+            blockhash = fill_blocktree_slot_with_ticks(
+                &blocktree,
+                ticks_per_slot,
+                slot,
+                slot - 1,
+                blockhash,
+            );
+        }
+
+        let (bank_forks, _, _) = process_blocktree(&genesis_block, &blocktree, None).unwrap();
+        let bank3 = bank_forks[3].clone();
+        let bank1 = bank3
+            .parents()
+            .into_iter()
+            .find(|bank| bank.slot() == 1)
+            .unwrap();
+        let snapshot = bank1.snapshot();
+        let snapshot_hash = snapshot.snapshot_hash();
+        let snapshot_bank = Bank::from_snapshot(snapshot, &snapshot_hash, None).unwrap();
+
+        let (bank_forks, bank_forks_info, _) =
+            process_blocktree_from_snapshot(snapshot_bank, &blocktree).unwrap();
+        assert_eq!(
+            bank_forks_info,
+            vec![BankForksInfo {
+                bank_slot: 3,
+                entry_height: 2 * ticks_per_slot,
+            }]
+        );
+        let bank = bank_forks[3].clone();
+        assert_eq!(bank.tick_height(), bank3.tick_height());
+        assert_eq!(bank.last_blockhash(), blockhash);
+        assert_eq!(bank.hash(), bank3.hash());
+
+        // With nothing after the snapshot slot in the ledger, the snapshot bank is all there is
+        let snapshot = bank3.snapshot();
+        let snapshot_hash = snapshot.snapshot_hash();
+        let snapshot_bank = Bank::from_snapshot(snapshot, &snapshot_hash, None).unwrap();
+        let (bank_forks, bank_forks_info, _) =
+            process_blocktree_from_snapshot(snapshot_bank, &blocktree).unwrap();
+        assert_eq!(bank_forks_info[0].bank_slot, 3);
+        assert_eq!(bank_forks.working_bank().hash(), bank3.hash());
+    }
+
     #[test]
     fn test_process_ledger_with_one_tick_per_slot() {
         let (mut genesis_block, _mint_keypair) = GenesisBlock::new(123);
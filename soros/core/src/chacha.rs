@@ -1,4 +1,6 @@
 use crate::blocktree::Blocktree;
+use byteorder::{ByteOrder, LittleEndian};
+use std::cmp;
 use std::fs::File;
 use std::io;
 use std::io::{BufWriter, Write};
@@ -9,7 +11,12 @@ use crate::storage_stage::ENTRIES_PER_SEGMENT;
 
 pub const CHACHA_BLOCK_SIZE: usize = 64;
 pub const CHACHA_KEY_SIZE: usize = 32;
+/// Number of rounds `cpu-crypt` applies to each block, rather than the 20 of ChaCha20
+pub const CHACHA_ROUNDS: usize = 500;
+/// Most bytes of a ledger segment read and encrypted at a time
+const LEDGER_CHUNK_SIZE: usize = 8 * 1024;
 
+#[cfg(feature = "chacha")]
 #[link(name = "cpu-crypt")]
 extern "C" {
     fn chacha20_cbc_encrypt(
@@ -21,16 +28,92 @@ extern "C" {
     );
 }
 
-pub fn chacha_cbc_encrypt(input: &[u8], output: &mut [u8], key: &[u8], ivec: &mut [u8]) {
+/// Encrypt `input` into `output` chaining from `ivec`, which is left holding the last
+/// encrypted block. The `chacha` feature uses the perf-libs `cpu-crypt` library, otherwise
+/// the encryption is done in Rust with the same output.
+#[cfg(feature = "chacha")]
+pub fn chacha_cbc_encrypt(input: &[u8], output: &mut [u8], ivec: &mut [u8]) {
+    // cpu-crypt writes whole blocks, give it room for a partial last one
+    let padded_len = (input.len() + CHACHA_BLOCK_SIZE - 1) / CHACHA_BLOCK_SIZE * CHACHA_BLOCK_SIZE;
+    let mut padded_output = vec![0; padded_len];
+    // the ledger is encrypted with an all-zero key, segments are told apart by their ivec
+    let key = [0u8; CHACHA_KEY_SIZE];
     unsafe {
         chacha20_cbc_encrypt(
             input.as_ptr(),
-            output.as_mut_ptr(),
+            padded_output.as_mut_ptr(),
             input.len(),
             key.as_ptr(),
             ivec.as_mut_ptr(),
         );
     }
+    output[..input.len()].copy_from_slice(&padded_output[..input.len()]);
+}
+
+/// Encrypt `input` into `output` chaining from `ivec`, which is left holding the last
+/// encrypted block. The `chacha` feature uses the perf-libs `cpu-crypt` library, otherwise
+/// the encryption is done in Rust with the same output.
+#[cfg(not(feature = "chacha"))]
+pub fn chacha_cbc_encrypt(input: &[u8], output: &mut [u8], ivec: &mut [u8]) {
+    chacha_cbc_encrypt_rust(input, output, ivec)
+}
+
+/// CBC over the ChaCha block function as `cpu-crypt` does it: each plaintext block is
+/// xor-ed with the previous ciphertext block and the result is the ChaCha state that gets
+/// permuted for `CHACHA_ROUNDS` rounds. A partial last block is filled out with the previous
+/// ciphertext. There is no key, the state is the chained block alone.
+pub fn chacha_cbc_encrypt_rust(input: &[u8], output: &mut [u8], ivec: &mut [u8]) {
+    assert!(output.len() >= input.len());
+    let mut block = [0u8; CHACHA_BLOCK_SIZE];
+    block.copy_from_slice(&ivec[..CHACHA_BLOCK_SIZE]);
+
+    for (in_chunk, out_chunk) in input
+        .chunks(CHACHA_BLOCK_SIZE)
+        .zip(output.chunks_mut(CHACHA_BLOCK_SIZE))
+    {
+        for (b, i) in block.iter_mut().zip(in_chunk) {
+            *b ^= i;
+        }
+        chacha_block(&mut block, CHACHA_ROUNDS);
+        out_chunk[..in_chunk.len()].copy_from_slice(&block[..in_chunk.len()]);
+    }
+
+    ivec[..CHACHA_BLOCK_SIZE].copy_from_slice(&block);
+}
+
+fn quarter_round(x: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    x[a] = x[a].wrapping_add(x[b]);
+    x[d] = (x[d] ^ x[a]).rotate_left(16);
+    x[c] = x[c].wrapping_add(x[d]);
+    x[b] = (x[b] ^ x[c]).rotate_left(12);
+    x[a] = x[a].wrapping_add(x[b]);
+    x[d] = (x[d] ^ x[a]).rotate_left(8);
+    x[c] = x[c].wrapping_add(x[d]);
+    x[b] = (x[b] ^ x[c]).rotate_left(7);
+}
+
+/// The ChaCha block function run for `rounds` rounds over a block read as 16 little-endian
+/// words, the block is replaced with its output
+fn chacha_block(block: &mut [u8; CHACHA_BLOCK_SIZE], rounds: usize) {
+    let mut input = [0u32; 16];
+    LittleEndian::read_u32_into(&block[..], &mut input);
+
+    let mut x = input;
+    for _ in 0..rounds / 2 {
+        quarter_round(&mut x, 0, 4, 8, 12);
+        quarter_round(&mut x, 1, 5, 9, 13);
+        quarter_round(&mut x, 2, 6, 10, 14);
+        quarter_round(&mut x, 3, 7, 11, 15);
+        quarter_round(&mut x, 0, 5, 10, 15);
+        quarter_round(&mut x, 1, 6, 11, 12);
+        quarter_round(&mut x, 2, 7, 8, 13);
+        quarter_round(&mut x, 3, 4, 9, 14);
+    }
+
+    for (word, input_word) in x.iter_mut().zip(input.iter()) {
+        *word = word.wrapping_add(*input_word);
+    }
+    LittleEndian::write_u32_into(&x, &mut block[..]);
 }
 
 pub fn chacha_cbc_encrypt_ledger(
@@ -41,10 +124,73 @@ pub fn chacha_cbc_encrypt_ledger(
 ) -> io::Result<usize> {
     let mut out_file =
         BufWriter::new(File::create(out_path).expect("Can't open ledger encrypted data file"));
-    const BUFFER_SIZE: usize = 8 * 1024;
-    let mut buffer = [0; BUFFER_SIZE];
-    let mut encrypted_buffer = [0; BUFFER_SIZE];
-    let key = [0; CHACHA_KEY_SIZE];
+    chacha_cbc_encrypt_ledger_to(blocktree, slice, &mut out_file, ivec)
+}
+
+/// Encrypt a ledger segment into any writer
+pub fn chacha_cbc_encrypt_ledger_to<W: Write>(
+    blocktree: &Arc<Blocktree>,
+    slice: u64,
+    out_file: &mut W,
+    ivec: &mut [u8; CHACHA_BLOCK_SIZE],
+) -> io::Result<usize> {
+    let mut encrypted_buffer = [0; LEDGER_CHUNK_SIZE];
+    for_each_ledger_chunk(blocktree, slice, |chunk| {
+        let size = chunk.len();
+        chacha_cbc_encrypt(chunk, &mut encrypted_buffer[..size], ivec);
+        if let Err(res) = out_file.write(&encrypted_buffer[..size]) {
+            warn!("Error writing file! {:?}", res);
+            return Err(res);
+        }
+        Ok(())
+    })
+}
+
+/// Read a ledger segment in the chunks it's encrypted in, validators keep a segment in memory
+/// to check replicator proofs against
+pub fn read_ledger_segment(blocktree: &Arc<Blocktree>, slice: u64) -> io::Result<Vec<Vec<u8>>> {
+    let mut chunks = vec![];
+    for_each_ledger_chunk(blocktree, slice, |chunk| {
+        chunks.push(chunk.to_vec());
+        Ok(())
+    })?;
+    Ok(chunks)
+}
+
+/// Encrypt the first `len` bytes of a segment read by `read_ledger_segment`, or all of it if
+/// it's shorter, with the same output as `chacha_cbc_encrypt_ledger_to`
+pub fn chacha_cbc_encrypt_segment_prefix(
+    chunks: &[Vec<u8>],
+    len: usize,
+    ivec: &mut [u8; CHACHA_BLOCK_SIZE],
+) -> Vec<u8> {
+    let mut encrypted = vec![];
+    for chunk in chunks {
+        if encrypted.len() >= len {
+            break;
+        }
+        // Only cut a chunk short on a block boundary, so the blocks before it encrypt the
+        // same as in the whole segment
+        let needed = len - encrypted.len();
+        let size = if needed < chunk.len() {
+            let blocks = (needed + CHACHA_BLOCK_SIZE - 1) / CHACHA_BLOCK_SIZE;
+            cmp::min(blocks * CHACHA_BLOCK_SIZE, chunk.len())
+        } else {
+            chunk.len()
+        };
+        let start = encrypted.len();
+        encrypted.resize(start + size, 0);
+        chacha_cbc_encrypt(&chunk[..size], &mut encrypted[start..], ivec);
+    }
+    encrypted
+}
+
+/// Call `f` with each chunk of a ledger segment as it's encrypted, returns the segment's size
+fn for_each_ledger_chunk<F>(blocktree: &Arc<Blocktree>, slice: u64, mut f: F) -> io::Result<usize>
+where
+    F: FnMut(&[u8]) -> io::Result<()>,
+{
+    let mut buffer = [0; LEDGER_CHUNK_SIZE];
     let mut total_entries = 0;
     let mut total_size = 0;
     let mut entry = slice;
@@ -63,18 +209,14 @@ pub fn chacha_cbc_encrypt_ledger(
                     break;
                 }
 
-                if size < BUFFER_SIZE {
+                if size < LEDGER_CHUNK_SIZE {
                     // We are on the last block, round to the nearest key_size
                     // boundary
                     size = (size + CHACHA_KEY_SIZE - 1) & !(CHACHA_KEY_SIZE - 1);
                 }
                 total_size += size;
 
-                chacha_cbc_encrypt(&buffer[..size], &mut encrypted_buffer[..size], &key, ivec);
-                if let Err(res) = out_file.write(&encrypted_buffer[..size]) {
-                    warn!("Error writing file! {:?}", res);
-                    return Err(res);
-                }
+                f(&buffer[..size])?;
 
                 total_entries += num_entries;
                 entry += num_entries;
@@ -92,12 +234,17 @@ pub fn chacha_cbc_encrypt_ledger(
 mod tests {
     use crate::blocktree::get_tmp_ledger_path;
     use crate::blocktree::Blocktree;
-    use crate::chacha::chacha_cbc_encrypt_ledger;
+    use crate::chacha::{
+        chacha_block, chacha_cbc_encrypt, chacha_cbc_encrypt_ledger, chacha_cbc_encrypt_ledger_to,
+        chacha_cbc_encrypt_rust, chacha_cbc_encrypt_segment_prefix, read_ledger_segment,
+        CHACHA_BLOCK_SIZE,
+    };
     use crate::entry::Entry;
     use crate::gen_keys::GenKeys;
     use soros_sdk::hash::{hash, Hash, Hasher};
     use soros_sdk::signature::KeypairUtil;
     use soros_sdk::system_transaction;
+    use std::cmp;
     use std::fs::remove_file;
     use std::fs::File;
     use std::io::Read;
@@ -158,6 +305,8 @@ mod tests {
 
         use bs58;
         //  golden needs to be updated if blob stuff changes....
+        //  it was computed with perf-libs cpu-crypt before the Rust implementation existed,
+        //  which has to keep producing it
         let golden = Hash::new(
             &bs58::decode("5Pz5KQyNht2nqkJhVd8F9zTFxzoDvbQSzaxQbtCPiyCo")
                 .into_vec()
@@ -166,4 +315,125 @@ mod tests {
         assert_eq!(hasher.result(), golden);
         remove_file(out_path).unwrap();
     }
+
+    #[test]
+    fn test_chacha_cbc_encrypt_segment_prefix() {
+        let ledger_path = get_tmp_ledger_path("chacha_test_encrypt_segment_prefix");
+        let blocktree = Arc::new(Blocktree::open(&ledger_path).unwrap());
+        let entries = make_tiny_deterministic_test_entries(32);
+        blocktree.write_entries(0, 0, 0, 16, &entries).unwrap();
+
+        let mut ivec = [3u8; CHACHA_BLOCK_SIZE];
+        let mut encrypted = vec![];
+        let size = chacha_cbc_encrypt_ledger_to(&blocktree, 0, &mut encrypted, &mut ivec).unwrap();
+        assert_eq!(size, encrypted.len());
+
+        let segment = read_ledger_segment(&blocktree, 0).unwrap();
+        assert_eq!(segment.iter().map(Vec::len).sum::<usize>(), size);
+        for len in &[
+            0,
+            1,
+            CHACHA_BLOCK_SIZE,
+            CHACHA_BLOCK_SIZE + 1,
+            size / 2,
+            size,
+            size + 1,
+        ] {
+            let mut ivec = [3u8; CHACHA_BLOCK_SIZE];
+            let prefix = chacha_cbc_encrypt_segment_prefix(&segment, *len, &mut ivec);
+            assert!(prefix.len() >= cmp::min(*len, size));
+            assert_eq!(&prefix[..], &encrypted[..prefix.len()]);
+        }
+    }
+
+    #[test]
+    fn test_chacha_block_rfc7539() {
+        // the ChaCha20 block function test vector of RFC 7539 section 2.3.2, serialized
+        let mut block = hex!(
+            "657870616e642033322d62797465206b000102030405060708090a0b0c0d0e0f
+             101112131415161718191a1b1c1d1e1f01000000000000090000004a00000000"
+        );
+        chacha_block(&mut block, 20);
+        assert_eq!(
+            &block[..],
+            &hex!(
+                "10f1e7e4d13b5915500fdd1fa32071c4c7d1f4c733c068030422aa9ac3d46c4e
+                 d2826446079faa0914c2d705d98b02a2b5129cd1de164eb9cbd083e8a2503c4e"
+            )[..]
+        );
+    }
+
+    #[test]
+    fn test_chacha_cbc_encrypt_chaining() {
+        let input: Vec<u8> = (0..CHACHA_BLOCK_SIZE * 3).map(|i| i as u8).collect();
+
+        let mut ivec = [7u8; CHACHA_BLOCK_SIZE];
+        let mut output = vec![0; input.len()];
+        chacha_cbc_encrypt_rust(&input, &mut output, &mut ivec);
+        assert_ne!(output, input);
+        assert_eq!(&ivec[..], &output[CHACHA_BLOCK_SIZE * 2..]);
+
+        // encrypting in pieces picks up where the last piece left off
+        let mut ivec = [7u8; CHACHA_BLOCK_SIZE];
+        let mut pieces = vec![0; input.len()];
+        chacha_cbc_encrypt_rust(
+            &input[..CHACHA_BLOCK_SIZE],
+            &mut pieces[..CHACHA_BLOCK_SIZE],
+            &mut ivec,
+        );
+        chacha_cbc_encrypt_rust(
+            &input[CHACHA_BLOCK_SIZE..],
+            &mut pieces[CHACHA_BLOCK_SIZE..],
+            &mut ivec,
+        );
+        assert_eq!(pieces, output);
+
+        // a different ivec changes every block
+        let mut ivec = [8u8; CHACHA_BLOCK_SIZE];
+        let mut other = vec![0; input.len()];
+        chacha_cbc_encrypt_rust(&input, &mut other, &mut ivec);
+        assert!(other
+            .chunks(CHACHA_BLOCK_SIZE)
+            .zip(output.chunks(CHACHA_BLOCK_SIZE))
+            .all(|(a, b)| a != b));
+    }
+
+    #[test]
+    fn test_chacha_cbc_encrypt_partial_block() {
+        const PARTIAL_BLOCK_SIZE: usize = CHACHA_BLOCK_SIZE / 2;
+        let input = [3u8; CHACHA_BLOCK_SIZE + PARTIAL_BLOCK_SIZE];
+        let mut ivec = [0u8; CHACHA_BLOCK_SIZE];
+        let mut output = [0u8; CHACHA_BLOCK_SIZE + PARTIAL_BLOCK_SIZE];
+        chacha_cbc_encrypt_rust(&input, &mut output, &mut ivec);
+        assert_eq!(&output[CHACHA_BLOCK_SIZE..], &ivec[..PARTIAL_BLOCK_SIZE]);
+
+        // a partial last block is filled out with the previous ciphertext, the same
+        // as padding the input with zeros
+        let mut padded = [0u8; CHACHA_BLOCK_SIZE * 2];
+        padded[..input.len()].copy_from_slice(&input);
+        let mut padded_ivec = [0u8; CHACHA_BLOCK_SIZE];
+        let mut padded_output = [0u8; CHACHA_BLOCK_SIZE * 2];
+        chacha_cbc_encrypt_rust(&padded, &mut padded_output, &mut padded_ivec);
+        assert_eq!(&padded_output[..input.len()], &output[..]);
+        assert_eq!(&padded_ivec[..], &ivec[..]);
+    }
+
+    #[test]
+    #[cfg(feature = "chacha")]
+    fn test_chacha_cbc_encrypt_matches_rust() {
+        let input: Vec<u8> = (0..CHACHA_BLOCK_SIZE * 4 + CHACHA_BLOCK_SIZE / 2)
+            .map(|i| (i * 7) as u8)
+            .collect();
+
+        let mut ivec = [1u8; CHACHA_BLOCK_SIZE];
+        let mut output = vec![0; input.len()];
+        chacha_cbc_encrypt(&input, &mut output, &mut ivec);
+
+        let mut rust_ivec = [1u8; CHACHA_BLOCK_SIZE];
+        let mut rust_output = vec![0; input.len()];
+        chacha_cbc_encrypt_rust(&input, &mut rust_output, &mut rust_ivec);
+
+        assert_eq!(output, rust_output);
+        assert_eq!(&ivec[..], &rust_ivec[..]);
+    }
 }
@@ -2,5 +2,8 @@ use soros_sdk::pubkey::Pubkey;
 
 pub trait Cluster {
     fn get_node_ids(&self) -> Vec<Pubkey>;
+    /// Shut down a node, it keeps its ledger so it can be restarted
+    fn kill_node(&mut self, pubkey: Pubkey);
+    /// Start a node again on new ports, killing it first if it's running
     fn restart_node(&mut self, pubkey: Pubkey);
 }
@@ -19,6 +19,7 @@ use crate::crds_gossip::CrdsGossip;
 use crate::crds_gossip_error::CrdsGossipError;
 use crate::crds_gossip_pull::CRDS_GOSSIP_PULL_CRDS_TIMEOUT_MS;
 use crate::crds_value::{CrdsValue, CrdsValueLabel, Vote};
+use crate::fault_injection;
 use crate::packet::{to_shared_blob, Blob, SharedBlob, BLOB_SIZE};
 use crate::repair_service::RepairType;
 use crate::result::Result;
@@ -677,7 +678,7 @@ impl ClusterInfo {
                 );
                 //TODO profile this, may need multiple sockets for par_iter
                 assert!(wblob.meta.size <= BLOB_SIZE);
-                s.send_to(&wblob.data[..wblob.meta.size], &v.tvu)
+                fault_injection::send_to(s, &wblob.data[..wblob.meta.size], &v.tvu)
             })
             .collect();
         // reset the blob to its old state. This avoids us having to copy the blob to modify it
@@ -725,7 +726,7 @@ impl ClusterInfo {
                 let send_errs_for_blob: Vec<_> = vs
                     .iter()
                     .map(move |v| {
-                        let e = s.send_to(&blob.data[..blob.meta.size], &v.tvu);
+                        let e = fault_injection::send_to(s, &blob.data[..blob.meta.size], &v.tvu);
                         trace!(
                             "{}: done broadcast {} to {:?}",
                             id,
@@ -9,10 +9,12 @@ use bincode::{deserialize, serialized_size};
 use chrono::prelude::Utc;
 use rayon::prelude::*;
 use soros_budget_api::budget_instruction;
-use soros_sdk::hash::{Hash, Hasher};
+use soros_sdk::hash::{hashv, Hash, Hasher};
+use soros_sdk::multi_sha256;
 use soros_sdk::signature::{Keypair, KeypairUtil};
 use soros_sdk::transaction::Transaction;
 use std::borrow::Borrow;
+use std::iter;
 use std::sync::mpsc::{Receiver, Sender};
 use std::sync::{Arc, RwLock};
 
@@ -184,6 +186,56 @@ fn next_hash(start_hash: &Hash, num_hashes: u64, transactions: &[Transaction]) -
     }
 }
 
+/// Number of plain hashes, the ones without a mixin, in the PoH chain of an entry
+fn chain_len(num_hashes: u64, transactions: &[Transaction]) -> u64 {
+    if transactions.is_empty() {
+        num_hashes
+    } else {
+        num_hashes.saturating_sub(1)
+    }
+}
+
+/// Number of entries verified together, enough to fill the lanes of `multi_sha256` a few times
+/// over as chains finish at different times
+const VERIFY_CHUNK_SIZE: usize = 4 * multi_sha256::LANES;
+
+/// Verify `entries` against the hash of the entry before them, hashing their chains in lockstep
+fn verify_chunk(start_hash: &Hash, entries: &[Entry]) -> bool {
+    let start_hashes: Vec<Hash> = iter::once(start_hash)
+        .chain(entries.iter().map(|entry| &entry.hash))
+        .take(entries.len())
+        .cloned()
+        .collect();
+    let chain_lens: Vec<u64> = entries
+        .iter()
+        .map(|entry| chain_len(entry.num_hashes, &entry.transactions))
+        .collect();
+    let chain_hashes = multi_sha256::hash_chains(&start_hashes, &chain_lens);
+
+    entries
+        .iter()
+        .zip(start_hashes.iter().zip(chain_hashes))
+        .all(|(entry, (start_hash, chain_hash))| {
+            let ref_hash = if entry.transactions.is_empty() {
+                chain_hash
+            } else {
+                let mixin = hash_transactions(&entry.transactions);
+                hashv(&[chain_hash.as_ref(), mixin.as_ref()])
+            };
+            if entry.hash != ref_hash {
+                warn!(
+                    "entry invalid!: x0: {:?}, x1: {:?} num txs: {}, expected: {:?}",
+                    start_hash,
+                    entry.hash,
+                    entry.transactions.len(),
+                    ref_hash
+                );
+                return false;
+            }
+            true
+        })
+}
+
 pub fn reconstruct_entries_from_blobs<I>(blobs: I) -> Result<(Vec<Entry>, u64)>
 where
     I: IntoIterator,
@@ -217,23 +269,13 @@ pub trait EntrySlice {
 
 impl EntrySlice for [Entry] {
     fn verify(&self, start_hash: &Hash) -> bool {
-        let genesis = [Entry {
-            num_hashes: 0,
-            hash: *start_hash,
-            transactions: vec![],
-        }];
-        let entry_pairs = genesis.par_iter().chain(self).zip(self);
-        entry_pairs.all(|(x0, x1)| {
-            let r = x1.verify(&x0.hash);
-            if !r {
-                warn!(
-                    "entry invalid!: x0: {:?}, x1: {:?} num txs: {}",
-                    x0.hash,
-                    x1.hash,
-                    x1.transactions.len()
-                );
-            }
-            r
+        self.par_chunks(VERIFY_CHUNK_SIZE).enumerate().all(|(i, entries)| {
+            let start_hash = if i == 0 {
+                start_hash
+            } else {
+                &self[i * VERIFY_CHUNK_SIZE - 1].hash
+            };
+            verify_chunk(start_hash, entries)
         })
     }
 
@@ -553,6 +595,38 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_verify_slice_across_chunks() {
+        let zero = Hash::default();
+        let keypair = Keypair::new();
+        let mut prev_hash = zero;
+        let entries: Vec<Entry> = (0..3 * VERIFY_CHUNK_SIZE as u64 + 1)
+            .map(|i| {
+                let transactions = if i % 3 == 0 {
+                    vec![create_sample_timestamp(&keypair, zero)]
+                } else {
+                    vec![]
+                };
+                let entry = next_entry(&prev_hash, i % 5 + 1, transactions);
+                prev_hash = entry.hash;
+                entry
+            })
+            .collect();
+        assert!(entries.verify(&zero));
+
+        // Bad entries are caught wherever they are in a chunk
+        for i in &[
+            0,
+            VERIFY_CHUNK_SIZE - 1,
+            VERIFY_CHUNK_SIZE,
+            entries.len() - 1,
+        ] {
+            let mut bad_entries = entries.clone();
+            bad_entries[*i].hash = zero;
+            assert!(!bad_entries.verify(&zero));
+        }
+    }
+
     #[test]
     fn test_verify_slice() {
         soros_logger::setup();
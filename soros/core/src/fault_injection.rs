@@ -0,0 +1,473 @@
+//! The `fault_injection` module makes the UDP traffic between registered nodes unreliable so
+//! stages like `retransmit_stage` and `repair_service` can be tested under packet loss, delay,
+//! duplication, reordering and network partitions.
+//!
+//! Nodes are registered with a `FaultyNetwork` by the addresses of their sockets and identified
+//! by the order they were registered in, so a `NetworkFaultConfig` can describe a cluster before
+//! any keypair exists. Every link between two nodes gets its own RNG seeded from the config seed
+//! and the two node indices, so a seed reproduces the same fate for the packets of a link.
+//!
+//! Sends go through `fault_injection::send_to`, which is a plain `UdpSocket::send_to` unless a
+//! network is installed and the socket belongs to one of its nodes.
+
+use lazy_static::lazy_static;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaChaRng;
+use soros_sdk::hash::hashv;
+use soros_sdk::pubkey::Pubkey;
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::{BinaryHeap, HashMap};
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::thread::{Builder, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How much longer than the link delay a reordered packet is held back
+pub const REORDER_DELAY_MS: u64 = 10;
+
+lazy_static! {
+    static ref NETWORKS: RwLock<Vec<Arc<FaultyNetwork>>> = RwLock::new(vec![]);
+}
+static NUM_NETWORKS: AtomicUsize = AtomicUsize::new(0);
+
+/// What happens to the packets sent to a node, rates are probabilities between 0 and 1
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LinkFaults {
+    pub drop_rate: f64,
+    /// Chance that a packet is sent twice
+    pub duplicate_rate: f64,
+    /// Chance that a packet is held back so the packets after it overtake it
+    pub reorder_rate: f64,
+    /// Latency added to every packet
+    pub delay_ms: u64,
+    /// Extra latency picked uniformly up to this for every packet
+    pub jitter_ms: u64,
+}
+
+impl LinkFaults {
+    fn is_reliable(&self) -> bool {
+        *self == LinkFaults::default()
+    }
+}
+
+/// For as long as it lasts, nodes in different groups can't reach each other. Nodes are the
+/// indices they were registered with, nodes that aren't in any group reach everyone.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Partition {
+    /// Since the network was installed
+    pub start: Duration,
+    pub duration: Duration,
+    pub groups: Vec<Vec<usize>>,
+}
+
+impl Partition {
+    fn group(&self, node: usize) -> Option<usize> {
+        self.groups.iter().position(|group| group.contains(&node))
+    }
+
+    fn separates(&self, from: usize, to: usize, elapsed: Duration) -> bool {
+        if elapsed < self.start || elapsed >= self.start + self.duration {
+            return false;
+        }
+        match (self.group(from), self.group(to)) {
+            (Some(from), Some(to)) => from != to,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NetworkFaultConfig {
+    pub seed: u64,
+    /// Faults of the links to the nodes without `destination_faults`
+    pub faults: LinkFaults,
+    /// Faults of the links to a node, by node index
+    pub destination_faults: HashMap<usize, LinkFaults>,
+    /// Partition schedule
+    pub partitions: Vec<Partition>,
+}
+
+struct DelayedPacket {
+    deadline: Instant,
+    socket: UdpSocket,
+    data: Vec<u8>,
+    addr: SocketAddr,
+}
+
+impl PartialEq for DelayedPacket {
+    fn eq(&self, other: &Self) -> bool {
+        self.deadline == other.deadline
+    }
+}
+
+impl Eq for DelayedPacket {}
+
+impl PartialOrd for DelayedPacket {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for DelayedPacket {
+    // Reversed so the `BinaryHeap` pops the earliest deadline first
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        other.deadline.cmp(&self.deadline)
+    }
+}
+
+type DelayQueue = Arc<(Mutex<BinaryHeap<DelayedPacket>>, Condvar)>;
+
+pub struct FaultyNetwork {
+    config: NetworkFaultConfig,
+    start: RwLock<Instant>,
+    node_indices: RwLock<HashMap<Pubkey, usize>>,
+    addr_nodes: RwLock<HashMap<SocketAddr, usize>>,
+    links: Mutex<HashMap<(usize, usize), ChaChaRng>>,
+    delayed: DelayQueue,
+    exit: Arc<AtomicBool>,
+    delay_thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl FaultyNetwork {
+    pub fn new(config: NetworkFaultConfig) -> Self {
+        let delayed: DelayQueue = Arc::new((Mutex::new(BinaryHeap::new()), Condvar::new()));
+        let exit = Arc::new(AtomicBool::new(false));
+        let delay_thread = {
+            let delayed = delayed.clone();
+            let exit = exit.clone();
+            Builder::new()
+                .name("soros-fault-injection".to_string())
+                .spawn(move || Self::send_delayed(&delayed, &exit))
+                .unwrap()
+        };
+        Self {
+            config,
+            start: RwLock::new(Instant::now()),
+            node_indices: RwLock::new(HashMap::new()),
+            addr_nodes: RwLock::new(HashMap::new()),
+            links: Mutex::new(HashMap::new()),
+            delayed,
+            exit,
+            delay_thread: Mutex::new(Some(delay_thread)),
+        }
+    }
+
+    /// Register the socket addresses of a node and return its index. A node registered again,
+    /// like after a restart, keeps its index and trades its old addresses for the new ones.
+    pub fn add_node(&self, id: &Pubkey, addrs: &[SocketAddr]) -> usize {
+        let index = {
+            let mut node_indices = self.node_indices.write().unwrap();
+            let num_nodes = node_indices.len();
+            *node_indices.entry(*id).or_insert(num_nodes)
+        };
+        let mut addr_nodes = self.addr_nodes.write().unwrap();
+        addr_nodes.retain(|_, node| *node != index);
+        addr_nodes.extend(addrs.iter().map(|addr| (*addr, index)));
+        index
+    }
+
+    pub fn node_index(&self, id: &Pubkey) -> Option<usize> {
+        self.node_indices.read().unwrap().get(id).cloned()
+    }
+
+    fn node(&self, addr: &SocketAddr) -> Option<usize> {
+        self.addr_nodes.read().unwrap().get(addr).cloned()
+    }
+
+    /// Time since the network was installed, which partitions are scheduled against
+    pub fn elapsed(&self) -> Duration {
+        self.start.read().unwrap().elapsed()
+    }
+
+    /// Delays of the copies of the next packet from `from` to `to`, none if it's dropped
+    fn fate(&self, from: usize, to: usize, elapsed: Duration) -> Vec<Duration> {
+        if self
+            .config
+            .partitions
+            .iter()
+            .any(|partition| partition.separates(from, to, elapsed))
+        {
+            return vec![];
+        }
+        let faults = self
+            .config
+            .destination_faults
+            .get(&to)
+            .unwrap_or(&self.config.faults);
+        if faults.is_reliable() {
+            return vec![Duration::from_millis(0)];
+        }
+
+        let config_seed = self.config.seed;
+        let mut links = self.links.lock().unwrap();
+        let rng = links.entry((from, to)).or_insert_with(|| {
+            let link_hash = hashv(&[
+                &config_seed.to_le_bytes(),
+                &(from as u64).to_le_bytes(),
+                &(to as u64).to_le_bytes(),
+            ]);
+            let mut seed = [0u8; 32];
+            seed.copy_from_slice(link_hash.as_ref());
+            ChaChaRng::from_seed(seed)
+        });
+        if rng.gen_bool(faults.drop_rate) {
+            return vec![];
+        }
+        let copies = if rng.gen_bool(faults.duplicate_rate) {
+            2
+        } else {
+            1
+        };
+        (0..copies)
+            .map(|_| {
+                let mut delay_ms = faults.delay_ms + rng.gen_range(0, faults.jitter_ms + 1);
+                if rng.gen_bool(faults.reorder_rate) {
+                    delay_ms += REORDER_DELAY_MS;
+                }
+                Duration::from_millis(delay_ms)
+            })
+            .collect()
+    }
+
+    fn send(
+        &self,
+        socket: &UdpSocket,
+        data: &[u8],
+        from: usize,
+        addr: &SocketAddr,
+    ) -> io::Result<usize> {
+        let to = match self.node(addr) {
+            Some(to) => to,
+            // Only the links between nodes of the network are faulty
+            None => return socket.send_to(data, addr),
+        };
+        let now = Instant::now();
+        for delay in self.fate(from, to, self.elapsed()) {
+            if delay == Duration::from_millis(0) {
+                socket.send_to(data, addr)?;
+            } else {
+                let packet = DelayedPacket {
+                    deadline: now + delay,
+                    socket: socket.try_clone()?,
+                    data: data.to_vec(),
+                    addr: *addr,
+                };
+                let (queue, cvar) = &*self.delayed;
+                queue.lock().unwrap().push(packet);
+                cvar.notify_one();
+            }
+        }
+        // Like UDP, lost packets don't fail the send
+        Ok(data.len())
+    }
+
+    fn send_delayed(delayed: &DelayQueue, exit: &AtomicBool) {
+        let (queue, cvar) = &**delayed;
+        let mut queue = queue.lock().unwrap();
+        while !exit.load(Ordering::Relaxed) {
+            let now = Instant::now();
+            let timeout = match queue.peek().map(|packet| packet.deadline) {
+                Some(deadline) if deadline <= now => {
+                    let packet = queue.pop().unwrap();
+                    if let Err(e) = packet.socket.send_to(&packet.data, &packet.addr) {
+                        debug!("delayed send_to {} error {:?}", packet.addr, e);
+                    }
+                    continue;
+                }
+                Some(deadline) => deadline - now,
+                None => Duration::from_millis(100),
+            };
+            queue = cvar.wait_timeout(queue, timeout).unwrap().0;
+        }
+    }
+
+    fn close(&self) {
+        self.exit.store(true, Ordering::Relaxed);
+        self.delayed.1.notify_all();
+        if let Some(delay_thread) = self.delay_thread.lock().unwrap().take() {
+            delay_thread.join().unwrap();
+        }
+    }
+}
+
+/// Start routing the traffic of the network's nodes through it, partitions are scheduled from now
+pub fn install(network: &Arc<FaultyNetwork>) {
+    *network.start.write().unwrap() = Instant::now();
+    NETWORKS.write().unwrap().push(network.clone());
+    NUM_NETWORKS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Stop faulting the traffic of the network's nodes, packets still delayed are lost
+pub fn uninstall(network: &Arc<FaultyNetwork>) {
+    let mut networks = NETWORKS.write().unwrap();
+    let num_networks = networks.len();
+    networks.retain(|installed| !Arc::ptr_eq(installed, network));
+    if networks.len() < num_networks {
+        NUM_NETWORKS.fetch_sub(1, Ordering::Relaxed);
+    }
+    network.close();
+}
+
+/// `UdpSocket::send_to` through the installed network the socket belongs to, if any
+pub fn send_to(socket: &UdpSocket, data: &[u8], addr: &SocketAddr) -> io::Result<usize> {
+    if NUM_NETWORKS.load(Ordering::Relaxed) == 0 {
+        return socket.send_to(data, addr);
+    }
+    let local_addr = socket.local_addr()?;
+    let network = NETWORKS
+        .read()
+        .unwrap()
+        .iter()
+        .filter_map(|network| {
+            network
+                .node(&local_addr)
+                .map(|from| (network.clone(), from))
+        })
+        .next();
+    match network {
+        Some((network, from)) => network.send(socket, data, from, addr),
+        None => socket.send_to(data, addr),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bind() -> UdpSocket {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_millis(200)))
+            .unwrap();
+        socket
+    }
+
+    fn network(config: NetworkFaultConfig, sockets: &[&UdpSocket]) -> Arc<FaultyNetwork> {
+        let network = Arc::new(FaultyNetwork::new(config));
+        for socket in sockets {
+            network.add_node(&Pubkey::new_rand(), &[socket.local_addr().unwrap()]);
+        }
+        install(&network);
+        network
+    }
+
+    fn num_received(socket: &UdpSocket) -> usize {
+        let mut buf = [0u8; 8];
+        let mut received = 0;
+        while socket.recv_from(&mut buf).is_ok() {
+            received += 1;
+        }
+        received
+    }
+
+    #[test]
+    fn test_add_node() {
+        let network = FaultyNetwork::new(NetworkFaultConfig::default());
+        let (id0, id1) = (Pubkey::new_rand(), Pubkey::new_rand());
+        let addr = |port| SocketAddr::from(([127, 0, 0, 1], port));
+        assert_eq!(network.add_node(&id0, &[addr(1), addr(2)]), 0);
+        assert_eq!(network.add_node(&id1, &[addr(3)]), 1);
+        assert_eq!(network.node(&addr(2)), Some(0));
+
+        // A restarted node keeps its index with new addresses
+        assert_eq!(network.add_node(&id0, &[addr(4)]), 0);
+        assert_eq!(network.node(&addr(1)), None);
+        assert_eq!(network.node(&addr(4)), Some(0));
+        assert_eq!(network.node_index(&id1), Some(1));
+        network.close();
+    }
+
+    #[test]
+    fn test_fate_is_seeded() {
+        let config = NetworkFaultConfig {
+            seed: 42,
+            faults: LinkFaults {
+                drop_rate: 0.3,
+                duplicate_rate: 0.3,
+                reorder_rate: 0.3,
+                delay_ms: 1,
+                jitter_ms: 5,
+            },
+            ..NetworkFaultConfig::default()
+        };
+        let fates = |config: &NetworkFaultConfig| {
+            let network = FaultyNetwork::new(config.clone());
+            let fates: Vec<_> = (0..100)
+                .map(|_| network.fate(0, 1, Duration::from_millis(0)))
+                .collect();
+            network.close();
+            fates
+        };
+        let first = fates(&config);
+        assert_eq!(first, fates(&config));
+        assert!(first.iter().any(|fate| fate.is_empty()));
+        assert!(first.iter().any(|fate| fate.len() == 2));
+
+        let other_seed = NetworkFaultConfig { seed: 43, ..config };
+        assert_ne!(first, fates(&other_seed));
+    }
+
+    #[test]
+    fn test_partition_schedule() {
+        let partition = Partition {
+            start: Duration::from_secs(1),
+            duration: Duration::from_secs(2),
+            groups: vec![vec![0, 1], vec![2]],
+        };
+        let at = |secs| Duration::from_millis(secs * 1000);
+        assert!(!partition.separates(0, 2, at(0)));
+        assert!(partition.separates(0, 2, at(1)));
+        assert!(partition.separates(2, 1, at(2)));
+        assert!(!partition.separates(0, 1, at(2)));
+        // Nodes outside the groups reach everyone
+        assert!(!partition.separates(3, 2, at(2)));
+        assert!(!partition.separates(0, 2, at(3)));
+    }
+
+    #[test]
+    fn test_send_to() {
+        let (sender, receiver, outsider) = (bind(), bind(), bind());
+        let receiver_addr = receiver.local_addr().unwrap();
+        let config = NetworkFaultConfig {
+            partitions: vec![Partition {
+                start: Duration::from_millis(0),
+                duration: Duration::from_secs(600),
+                groups: vec![vec![0], vec![1]],
+            }],
+            ..NetworkFaultConfig::default()
+        };
+        let network = network(config, &[&sender, &receiver]);
+
+        send_to(&sender, &[1], &receiver_addr).unwrap();
+        assert_eq!(num_received(&receiver), 0);
+        send_to(&outsider, &[1], &receiver_addr).unwrap();
+        assert_eq!(num_received(&receiver), 1);
+
+        uninstall(&network);
+        send_to(&sender, &[1], &receiver_addr).unwrap();
+        assert_eq!(num_received(&receiver), 1);
+    }
+
+    #[test]
+    fn test_send_to_delayed_duplicates() {
+        let (sender, receiver) = (bind(), bind());
+        let receiver_addr = receiver.local_addr().unwrap();
+        let config = NetworkFaultConfig {
+            faults: LinkFaults {
+                duplicate_rate: 1.0,
+                delay_ms: 50,
+                ..LinkFaults::default()
+            },
+            ..NetworkFaultConfig::default()
+        };
+        let network = network(config, &[&sender, &receiver]);
+
+        let now = Instant::now();
+        send_to(&sender, &[1], &receiver_addr).unwrap();
+        assert_eq!(num_received(&receiver), 2);
+        assert!(now.elapsed() >= Duration::from_millis(50));
+        uninstall(&network);
+    }
+}
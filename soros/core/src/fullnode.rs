@@ -1,6 +1,7 @@
 //! The `fullnode` module hosts all the fullnode microservices.
 
 use crate::bank_forks::BankForks;
+use crate::blockstream::BlockstreamConfig;
 use crate::blocktree::Blocktree;
 use crate::blocktree_processor::{self, BankForksInfo};
 use crate::cluster_info::{ClusterInfo, Node};
@@ -20,7 +21,10 @@ use crate::service::Service;
 use crate::storage_stage::StorageState;
 use crate::tpu::Tpu;
 use crate::tvu::{Sockets, Tvu};
+use bincode::deserialize;
+use soros_client::rpc_client::RpcClient;
 use soros_metrics::counter::Counter;
+use soros_runtime::bank::{Bank, BankSnapshot};
 use soros_sdk::genesis_block::GenesisBlock;
 use soros_sdk::hash::Hash;
 use soros_sdk::pubkey::Pubkey;
@@ -40,11 +44,17 @@ use std::thread::Result;
 pub struct FullnodeConfig {
     pub sigverify_disabled: bool,
     pub voting_disabled: bool,
-    pub blockstream: Option<String>,
+    pub blockstream: Option<BlockstreamConfig>,
     pub storage_rotate_count: u64,
     pub tick_config: PohServiceConfig,
     pub account_paths: Option<String>,
     pub rpc_config: JsonRpcConfig,
+    /// Snapshot the root bank every this many slots and serve the snapshot over RPC
+    pub snapshot_interval_slots: Option<u64>,
+    /// Start from the snapshot the node with this RPC address serves instead of replaying the
+    /// whole ledger, if the snapshot has this snapshot hash, see `BankSnapshot::snapshot_hash`.
+    /// The node serving the snapshot isn't trusted, the hash has to come from one that is.
+    pub trusted_snapshot: Option<(SocketAddr, Hash)>,
 }
 impl Default for FullnodeConfig {
     fn default() -> Self {
@@ -60,6 +70,8 @@ impl Default for FullnodeConfig {
             tick_config: PohServiceConfig::default(),
             account_paths: None,
             rpc_config: JsonRpcConfig::default(),
+            snapshot_interval_slots: None,
+            trusted_snapshot: None,
         }
     }
 }
@@ -95,8 +107,22 @@ impl Fullnode {
         let id = keypair.pubkey();
         assert_eq!(id, node.info.id);
 
-        let (bank_forks, bank_forks_info, blocktree, ledger_signal_receiver, leader_schedule_cache) =
-            new_banks_from_blocktree(ledger_path, config.account_paths.clone());
+        let snapshot_bank = config
+            .trusted_snapshot
+            .and_then(|(rpc_addr, trusted_hash)| {
+                fetch_snapshot_bank(&rpc_addr, &trusted_hash, config.account_paths.clone())
+            });
+        let (
+            mut bank_forks,
+            bank_forks_info,
+            blocktree,
+            ledger_signal_receiver,
+            leader_schedule_cache,
+        ) = match snapshot_bank {
+            Some(snapshot_bank) => new_banks_from_snapshot(ledger_path, snapshot_bank),
+            None => new_banks_from_blocktree(ledger_path, config.account_paths.clone()),
+        };
+        bank_forks.set_snapshot_interval(config.snapshot_interval_slots);
 
         let leader_schedule_cache = Arc::new(leader_schedule_cache);
         let exit = Arc::new(AtomicBool::new(false));
@@ -319,6 +345,68 @@ pub fn new_banks_from_blocktree(
     )
 }
 
+/// Fetch the snapshot the node at `rpc_addr` serves and restore its bank, `None` if there's no
+/// snapshot or it isn't the one with the snapshot hash `trusted_hash`, in which case the whole
+/// ledger gets replayed
+fn fetch_snapshot_bank(
+    rpc_addr: &SocketAddr,
+    trusted_hash: &Hash,
+    account_paths: Option<String>,
+) -> Option<Bank> {
+    info!("fetching snapshot from {}", rpc_addr);
+    let (slot, snapshot) = match RpcClient::new_socket(*rpc_addr).get_snapshot() {
+        Ok(Some(snapshot)) => snapshot,
+        Ok(None) => {
+            warn!("{} has no snapshot", rpc_addr);
+            return None;
+        }
+        Err(err) => {
+            warn!("failed to fetch snapshot from {}: {:?}", rpc_addr, err);
+            return None;
+        }
+    };
+    let snapshot: BankSnapshot = deserialize(&snapshot)
+        .map_err(|err| warn!("invalid snapshot of slot {}: {:?}", slot, err))
+        .ok()?;
+    let bank = Bank::from_snapshot(snapshot, trusted_hash, account_paths)
+        .map_err(|err| warn!("snapshot of slot {} rejected: {:?}", slot, err))
+        .ok()?;
+    info!(
+        "starting from snapshot of slot {}, bank hash {}",
+        slot,
+        bank.hash()
+    );
+    Some(bank)
+}
+
+/// Like `new_banks_from_blocktree`, but starting from `snapshot_bank` and only replaying the
+/// slots of the ledger after it
+pub fn new_banks_from_snapshot(
+    blocktree_path: &str,
+    snapshot_bank: Bank,
+) -> (
+    BankForks,
+    Vec<BankForksInfo>,
+    Blocktree,
+    Receiver<bool>,
+    LeaderScheduleCache,
+) {
+    let (blocktree, ledger_signal_receiver) = Blocktree::open_with_signal(blocktree_path)
+        .expect("Expected to successfully open database ledger");
+
+    let (bank_forks, bank_forks_info, leader_schedule_cache) =
+        blocktree_processor::process_blocktree_from_snapshot(snapshot_bank, &blocktree)
+            .expect("process_blocktree_from_snapshot failed");
+
+    (
+        bank_forks,
+        bank_forks_info,
+        blocktree,
+        ledger_signal_receiver,
+        leader_schedule_cache,
+    )
+}
+
 impl Service for Fullnode {
     type JoinReturnType = ();
 
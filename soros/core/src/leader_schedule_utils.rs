@@ -4,7 +4,7 @@ use soros_runtime::bank::Bank;
 use soros_sdk::pubkey::Pubkey;
 use soros_sdk::timing::NUM_CONSECUTIVE_LEADER_SLOTS;
 
-/// Return the leader schedule for the given epoch.
+/// Return the leader schedule for the given epoch, weighted by effective stake.
 pub fn leader_schedule(epoch_height: u64, bank: &Bank) -> Option<LeaderSchedule> {
     staking_utils::delegated_stakes_at_epoch(bank, epoch_height).map(|stakes| {
         let mut seed = [0u8; 32];
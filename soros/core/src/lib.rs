@@ -9,7 +9,6 @@ pub mod bank_forks;
 pub mod banking_stage;
 pub mod blob_fetch_stage;
 pub mod broadcast_stage;
-#[cfg(feature = "chacha")]
 pub mod chacha;
 #[cfg(all(feature = "chacha", feature = "cuda"))]
 pub mod chacha_cuda;
@@ -32,6 +31,7 @@ pub mod cluster_info;
 pub mod cluster_tests;
 pub mod entry;
 pub mod erasure;
+pub mod fault_injection;
 pub mod fetch_stage;
 pub mod fullnode;
 pub mod gen_keys;
@@ -65,12 +65,12 @@ pub mod storage_stage;
 pub mod streamer;
 pub mod test_tx;
 pub mod tpu;
+pub mod transaction_scheduler;
 pub mod tvu;
 pub mod voting_keypair;
 pub mod window_service;
 
 #[cfg(test)]
-#[cfg(any(feature = "chacha", feature = "cuda"))]
 #[macro_use]
 extern crate hex_literal;
 
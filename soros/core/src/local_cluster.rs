@@ -2,11 +2,13 @@ use crate::blocktree::{create_new_tmp_ledger, tmp_copy_blocktree};
 use crate::cluster::Cluster;
 use crate::cluster_info::{Node, FULLNODE_PORT_RANGE};
 use crate::contact_info::ContactInfo;
+use crate::fault_injection::{self, FaultyNetwork, NetworkFaultConfig};
 use crate::fullnode::{Fullnode, FullnodeConfig};
 use crate::gossip_service::discover_nodes;
 use crate::replicator::Replicator;
 use crate::service::Service;
 use soros_client::thin_client::create_client;
+use soros_drone::drone::run_local_drone;
 use soros_client::thin_client::ThinClient;
 use soros_sdk::client::SyncClient;
 use soros_sdk::genesis_block::GenesisBlock;
@@ -21,18 +23,24 @@ use soros_vote_api::vote_state::VoteState;
 use std::collections::HashMap;
 use std::fs::remove_dir_all;
 use std::io::{Error, ErrorKind, Result};
+use std::iter;
+use std::net::SocketAddr;
+use std::sync::mpsc::channel;
 use std::sync::Arc;
 
 pub struct FullnodeInfo {
     pub keypair: Arc<Keypair>,
     pub ledger_path: String,
+    /// Where the node can be reached, the ports change when it's restarted
+    pub contact_info: ContactInfo,
 }
 
 impl FullnodeInfo {
-    fn new(keypair: Arc<Keypair>, ledger_path: String) -> Self {
+    fn new(keypair: Arc<Keypair>, ledger_path: String, contact_info: ContactInfo) -> Self {
         Self {
             keypair,
             ledger_path,
+            contact_info,
         }
     }
 }
@@ -67,6 +75,11 @@ pub struct ClusterConfig {
     pub ticks_per_slot: u64,
     pub slots_per_epoch: u64,
     pub native_instruction_processors: Vec<(String, Pubkey)>,
+    /// Run a drone funded by the mint, the nodes' RPC airdrops go through it
+    pub start_drone: bool,
+    /// Make the network between the nodes faulty. Nodes are indexed in the order they start: the
+    /// bootstrap leader, the other voting nodes in `node_stakes` order, then the listeners.
+    pub network_faults: Option<NetworkFaultConfig>,
 }
 
 impl Default for ClusterConfig {
@@ -81,10 +94,35 @@ impl Default for ClusterConfig {
             ticks_per_slot: DEFAULT_TICKS_PER_SLOT,
             slots_per_epoch: DEFAULT_SLOTS_PER_EPOCH,
             native_instruction_processors: vec![],
+            start_drone: false,
+            network_faults: None,
         }
     }
 }
 
+/// Endpoints of a node of a `LocalCluster`, as reported by `LocalCluster::endpoints`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct NodeEndpoints {
+    pub pubkey: String,
+    pub gossip: SocketAddr,
+    pub tpu: SocketAddr,
+    pub rpc: SocketAddr,
+    pub rpc_pubsub: SocketAddr,
+    pub ledger_path: String,
+    /// Listeners don't vote
+    pub voting: bool,
+    /// Whether the node is running or was killed
+    pub running: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ClusterEndpoints {
+    /// Gossip entry point of the cluster
+    pub entry_point: SocketAddr,
+    pub drone: Option<SocketAddr>,
+    pub nodes: Vec<NodeEndpoints>,
+}
+
 pub struct LocalCluster {
     /// Keypair with funding to participate in the network
     pub funding_keypair: Keypair,
@@ -98,6 +136,25 @@ pub struct LocalCluster {
     pub genesis_block: GenesisBlock,
     replicators: Vec<Replicator>,
     pub replicator_infos: HashMap<Pubkey, ReplicatorInfo>,
+    /// Address of the drone if the cluster runs one
+    pub drone_addr: Option<SocketAddr>,
+    /// Faulty network the nodes talk over, if the cluster was configured with one
+    pub network: Option<Arc<FaultyNetwork>>,
+}
+
+/// Addresses of all the sockets of a node
+fn socket_addrs(node: &Node) -> Vec<SocketAddr> {
+    let sockets = &node.sockets;
+    iter::once(&sockets.gossip)
+        .chain(&sockets.tvu)
+        .chain(&sockets.tpu)
+        .chain(&sockets.tpu_via_blobs)
+        .chain(iter::once(&sockets.broadcast))
+        .chain(iter::once(&sockets.repair))
+        .chain(iter::once(&sockets.retransmit))
+        .chain(&sockets.storage)
+        .filter_map(|socket| socket.local_addr().ok())
+        .collect()
 }
 
 impl LocalCluster {
@@ -140,6 +197,25 @@ impl LocalCluster {
         let leader_ledger_path = tmp_copy_blocktree!(&genesis_ledger_path);
         let leader_contact_info = leader_node.info.clone();
 
+        let mut fullnode_config = config.fullnode_config.clone();
+        let drone_addr = if config.start_drone {
+            let drone_keypair = Keypair::from_bytes(&mint_keypair.to_bytes()).unwrap();
+            let (sender, receiver) = channel();
+            run_local_drone(drone_keypair, sender, None);
+            let drone_addr = receiver.recv().unwrap();
+            fullnode_config.rpc_config.drone_addr = Some(drone_addr);
+            Some(drone_addr)
+        } else {
+            None
+        };
+
+        let network = config.network_faults.as_ref().map(|network_faults| {
+            let network = Arc::new(FaultyNetwork::new(network_faults.clone()));
+            fault_injection::install(&network);
+            network.add_node(&leader_pubkey, &socket_addrs(&leader_node));
+            network
+        });
+
         let leader_server = Fullnode::new(
             leader_node,
             &leader_keypair,
@@ -147,7 +223,7 @@ impl LocalCluster {
             &voting_keypair.pubkey(),
             voting_keypair,
             None,
-            &config.fullnode_config,
+            &fullnode_config,
         );
 
         let mut fullnodes = HashMap::new();
@@ -155,7 +231,11 @@ impl LocalCluster {
         fullnodes.insert(leader_pubkey, leader_server);
         fullnode_infos.insert(
             leader_pubkey,
-            FullnodeInfo::new(leader_keypair.clone(), leader_ledger_path),
+            FullnodeInfo::new(
+                leader_keypair.clone(),
+                leader_ledger_path,
+                leader_contact_info.clone(),
+            ),
         );
 
         let mut cluster = Self {
@@ -167,18 +247,17 @@ impl LocalCluster {
             genesis_block,
             fullnode_infos,
             replicator_infos: HashMap::new(),
-            fullnode_config: config.fullnode_config.clone(),
+            fullnode_config: fullnode_config.clone(),
             listener_infos: HashMap::new(),
+            drone_addr,
+            network,
         };
 
         for stake in &config.node_stakes[1..] {
-            cluster.add_validator(&config.fullnode_config, *stake);
+            cluster.add_validator(&fullnode_config, *stake);
         }
 
-        let listener_config = FullnodeConfig {
-            voting_disabled: true,
-            ..config.fullnode_config.clone()
-        };
+        let listener_config = cluster.listener_config();
         (0..config.num_listeners).for_each(|_| cluster.add_validator(&listener_config, 0));
 
         discover_nodes(
@@ -206,6 +285,50 @@ impl LocalCluster {
         }
     }
 
+    /// Endpoints of all the nodes, killed ones included
+    pub fn endpoints(&self) -> ClusterEndpoints {
+        let node_endpoints = |(pubkey, info): (&Pubkey, &FullnodeInfo), voting| NodeEndpoints {
+            pubkey: pubkey.to_string(),
+            gossip: info.contact_info.gossip,
+            tpu: info.contact_info.tpu,
+            rpc: info.contact_info.rpc,
+            rpc_pubsub: info.contact_info.rpc_pubsub,
+            ledger_path: info.ledger_path.clone(),
+            voting,
+            running: self.fullnodes.contains_key(pubkey),
+        };
+        let mut nodes: Vec<_> = self
+            .fullnode_infos
+            .iter()
+            .map(|node| node_endpoints(node, true))
+            .chain(
+                self.listener_infos
+                    .iter()
+                    .map(|node| node_endpoints(node, false)),
+            )
+            .collect();
+        nodes.sort_by(|a, b| a.pubkey.cmp(&b.pubkey));
+
+        ClusterEndpoints {
+            entry_point: self.entry_point_info.gossip,
+            drone: self.drone_addr,
+            nodes,
+        }
+    }
+
+    fn add_to_network(&self, pubkey: &Pubkey, node: &Node) {
+        if let Some(network) = &self.network {
+            network.add_node(pubkey, &socket_addrs(node));
+        }
+    }
+
+    fn listener_config(&self) -> FullnodeConfig {
+        FullnodeConfig {
+            voting_disabled: true,
+            ..self.fullnode_config.clone()
+        }
+    }
+
     pub fn close_preserve_ledgers(&mut self) {
         self.exit();
         for (_, node) in self.fullnodes.drain() {
@@ -217,7 +340,8 @@ impl LocalCluster {
         }
     }
 
-    fn add_validator(&mut self, fullnode_config: &FullnodeConfig, stake: u64) {
+    /// Start another node from the genesis ledger, `stake` is ignored for listeners
+    pub fn add_validator(&mut self, fullnode_config: &FullnodeConfig, stake: u64) {
         let client = create_client(
             self.entry_point_info.client_facing_addr(),
             FULLNODE_PORT_RANGE,
@@ -228,7 +352,9 @@ impl LocalCluster {
         let voting_keypair = Keypair::new();
         let validator_pubkey = validator_keypair.pubkey();
         let validator_node = Node::new_localhost_with_pubkey(&validator_keypair.pubkey());
+        let contact_info = validator_node.info.clone();
         let ledger_path = tmp_copy_blocktree!(&self.genesis_ledger_path);
+        self.add_to_network(&validator_pubkey, &validator_node);
 
         if fullnode_config.voting_disabled {
             // setup as a listener
@@ -268,16 +394,13 @@ impl LocalCluster {
 
         self.fullnodes
             .insert(validator_keypair.pubkey(), validator_server);
+        let fullnode_info = FullnodeInfo::new(validator_keypair.clone(), ledger_path, contact_info);
         if fullnode_config.voting_disabled {
-            self.listener_infos.insert(
-                validator_keypair.pubkey(),
-                FullnodeInfo::new(validator_keypair.clone(), ledger_path),
-            );
+            self.listener_infos
+                .insert(validator_keypair.pubkey(), fullnode_info);
         } else {
-            self.fullnode_infos.insert(
-                validator_keypair.pubkey(),
-                FullnodeInfo::new(validator_keypair.clone(), ledger_path),
-            );
+            self.fullnode_infos
+                .insert(validator_keypair.pubkey(), fullnode_info);
         }
     }
 
@@ -317,14 +440,25 @@ impl LocalCluster {
         );
     }
 
-    fn close(&mut self) {
+    /// Shut down all the nodes and remove their ledgers, the drone keeps running until the
+    /// process exits
+    pub fn close(&mut self) {
         self.close_preserve_ledgers();
-        for ledger_path in self
+        if let Some(network) = self.network.take() {
+            fault_injection::uninstall(&network);
+        }
+        let ledger_paths: Vec<_> = self
             .fullnode_infos
-            .values()
-            .map(|f| &f.ledger_path)
-            .chain(self.replicator_infos.values().map(|info| &info.ledger_path))
-        {
+            .drain()
+            .chain(self.listener_infos.drain())
+            .map(|(_, info)| info.ledger_path)
+            .chain(
+                self.replicator_infos
+                    .drain()
+                    .map(|(_, info)| info.ledger_path),
+            )
+            .collect();
+        for ledger_path in ledger_paths {
             remove_dir_all(&ledger_path)
                 .unwrap_or_else(|_| panic!("Unable to remove {}", ledger_path));
         }
@@ -425,18 +559,38 @@ impl Cluster for LocalCluster {
         self.fullnodes.keys().cloned().collect()
     }
 
+    fn kill_node(&mut self, pubkey: Pubkey) {
+        if let Some(node) = self.fullnodes.remove(&pubkey) {
+            node.exit();
+            node.join().unwrap();
+        }
+    }
+
     fn restart_node(&mut self, pubkey: Pubkey) {
-        // Shut down the fullnode
-        let node = self.fullnodes.remove(&pubkey).unwrap();
-        node.exit();
-        node.join().unwrap();
+        // Shut down the fullnode if it's still running
+        self.kill_node(pubkey);
 
         // Restart the node
-        let fullnode_info = &self.fullnode_infos[&pubkey];
-        let node = Node::new_localhost_with_pubkey(&fullnode_info.keypair.pubkey());
-        if pubkey == self.entry_point_info.id {
+        let is_listener = self.listener_infos.contains_key(&pubkey);
+        let fullnode_config = if is_listener {
+            self.listener_config()
+        } else {
+            self.fullnode_config.clone()
+        };
+        let node = Node::new_localhost_with_pubkey(&pubkey);
+        self.add_to_network(&pubkey, &node);
+        let fullnode_info = if is_listener {
+            self.listener_infos.get_mut(&pubkey).unwrap()
+        } else {
+            self.fullnode_infos.get_mut(&pubkey).unwrap()
+        };
+        fullnode_info.contact_info = node.info.clone();
+        let entrypoint_info = if pubkey == self.entry_point_info.id {
             self.entry_point_info = node.info.clone();
-        }
+            None
+        } else {
+            Some(&self.entry_point_info)
+        };
         let new_voting_keypair = Keypair::new();
         let restarted_node = Fullnode::new(
             node,
@@ -444,8 +598,8 @@ impl Cluster for LocalCluster {
             &fullnode_info.ledger_path,
             &new_voting_keypair.pubkey(),
             new_voting_keypair,
-            None,
-            &self.fullnode_config,
+            entrypoint_info,
+            &fullnode_config,
         );
 
         self.fullnodes.insert(pubkey, restarted_node);
@@ -469,6 +623,17 @@ mod test {
         let cluster = LocalCluster::new_with_equal_stakes(num_nodes, 100, 3);
         assert_eq!(cluster.fullnodes.len(), num_nodes);
         assert_eq!(cluster.replicators.len(), 0);
+
+        let endpoints = cluster.endpoints();
+        assert_eq!(endpoints.entry_point, cluster.entry_point_info.gossip);
+        assert_eq!(endpoints.drone, None);
+        assert_eq!(endpoints.nodes.len(), num_nodes);
+        assert_eq!(
+            endpoints.nodes[0].pubkey,
+            cluster.entry_point_info.id.to_string()
+        );
+        assert_eq!(endpoints.nodes[0].rpc, cluster.entry_point_info.rpc);
+        assert!(endpoints.nodes[0].voting && endpoints.nodes[0].running);
     }
 
     #[test]
@@ -1,4 +1,5 @@
 //! The `packet` module defines data structures and methods to pull data from the network.
+use crate::fault_injection;
 use crate::recvmmsg::{recv_mmsg, NUM_RCVMMSGS};
 use crate::result::{Error, Result};
 use bincode;
@@ -255,7 +256,7 @@ impl Packets {
     pub fn send_to(&self, socket: &UdpSocket) -> Result<()> {
         for p in &self.packets {
             let a = p.meta.addr();
-            socket.send_to(&p.data[..p.meta.size], &a)?;
+            fault_injection::send_to(socket, &p.data[..p.meta.size], &a)?;
         }
         Ok(())
     }
@@ -577,7 +578,7 @@ impl Blob {
             {
                 let p = r.read().unwrap();
                 let a = p.meta.addr();
-                if let Err(e) = socket.send_to(&p.data[..p.meta.size], &a) {
+                if let Err(e) = fault_injection::send_to(socket, &p.data[..p.meta.size], &a) {
                     warn!(
                         "error sending {} byte packet to {:?}: {:?}",
                         p.meta.size, a, e
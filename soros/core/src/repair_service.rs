@@ -3,6 +3,7 @@
 
 use crate::blocktree::{Blocktree, SlotMeta};
 use crate::cluster_info::ClusterInfo;
+use crate::fault_injection;
 use crate::result::Result;
 use crate::service::Service;
 use soros_metrics::{influxdb, submit};
@@ -119,7 +120,7 @@ impl RepairService {
                         );
                     }
 
-                    repair_socket.send_to(&req, to).unwrap_or_else(|e| {
+                    fault_injection::send_to(repair_socket, &req, &to).unwrap_or_else(|e| {
                         info!("{} repair req send_to({}) error {:?}", id, to, e);
                         0
                     });
@@ -17,6 +17,7 @@ use hashbrown::HashMap;
 use soros_metrics::counter::Counter;
 use soros_metrics::influxdb;
 use soros_runtime::bank::Bank;
+use soros_sdk::commitment_config::CommitmentLevel;
 use soros_sdk::hash::Hash;
 use soros_sdk::pubkey::Pubkey;
 use soros_sdk::signature::KeypairUtil;
@@ -146,7 +147,7 @@ impl ReplayStage {
                         Self::generate_votable_banks(&bank_forks, &locktower, &mut progress);
 
                     if let Some((_, bank)) = votable.last() {
-                        subscriptions.notify_subscribers(&bank);
+                        subscriptions.notify_subscribers(CommitmentLevel::Recent, &bank);
 
                         Self::handle_votable_bank(
                             &bank,
@@ -157,8 +158,13 @@ impl ReplayStage {
                             &vote_account,
                             &cluster_info,
                             &blocktree,
+                            &subscriptions,
                         )?;
 
+                        let confirmed_bank = bank_forks.read().unwrap().confirmed_bank();
+                        subscriptions
+                            .notify_subscribers(CommitmentLevel::Confirmed, &confirmed_bank);
+
                         Self::reset_poh_recorder(
                             &my_id,
                             &blocktree,
@@ -308,11 +314,15 @@ impl ReplayStage {
         vote_account_pubkey: &Pubkey,
         cluster_info: &Arc<RwLock<ClusterInfo>>,
         blocktree: &Arc<Blocktree>,
+        subscriptions: &Arc<RpcSubscriptions>,
     ) -> Result<()>
     where
         T: 'static + KeypairUtil + Send + Sync,
     {
         if let Some(new_root) = locktower.record_vote(bank.slot()) {
+            // Notify before the new root is squashed, that drops the links to its parents
+            let root_bank = bank_forks.read().unwrap()[new_root].clone();
+            subscriptions.notify_subscribers(CommitmentLevel::Root, &root_bank);
             bank_forks.write().unwrap().set_root(new_root);
             blocktree.set_root(new_root)?;
             Self::handle_new_root(&bank_forks, progress);
@@ -477,6 +487,7 @@ impl ReplayStage {
                     .unwrap_or(true)
             {
                 info!("validator fork confirmed {} {}", *slot, duration);
+                bank_forks.write().unwrap().set_confirmed_slot(*slot);
                 soros_metrics::submit(
                     influxdb::Point::new(&"validator-confirmation")
                         .add_field("duration_ms", influxdb::Value::Integer(duration as i64))
@@ -1,6 +1,5 @@
 use crate::blob_fetch_stage::BlobFetchStage;
 use crate::blocktree::Blocktree;
-#[cfg(feature = "chacha")]
 use crate::chacha::{chacha_cbc_encrypt_ledger, CHACHA_BLOCK_SIZE};
 use crate::cluster_info::{ClusterInfo, Node, FULLNODE_PORT_RANGE};
 use crate::contact_info::ContactInfo;
@@ -9,7 +8,7 @@ use crate::packet::to_shared_blob;
 use crate::repair_service::RepairSlotRange;
 use crate::result::Result;
 use crate::service::Service;
-use crate::storage_stage::{get_segment_from_entry, ENTRIES_PER_SEGMENT};
+use crate::storage_stage::{get_sampling_offsets, get_segment_from_entry, ENTRIES_PER_SEGMENT};
 use crate::streamer::receiver;
 use crate::streamer::responder;
 use crate::window_service::WindowService;
@@ -26,10 +25,12 @@ use soros_sdk::signature::{Keypair, KeypairUtil, Signature};
 use soros_sdk::system_transaction;
 use soros_sdk::transaction::Transaction;
 use soros_sdk::transport::TransportError;
+use soros_storage_api::storage_contract::STORAGE_ACCOUNT_SPACE;
 use soros_storage_api::storage_instruction;
 use std::fs::File;
 use std::io;
 use std::io::BufReader;
+use std::io::Cursor;
 use std::io::Read;
 use std::io::Seek;
 use std::io::SeekFrom;
@@ -66,23 +67,31 @@ pub struct Replicator {
     ledger_data_file_encrypted: PathBuf,
     sampling_offsets: Vec<u64>,
     hash: Hash,
-    #[cfg(feature = "chacha")]
     num_chacha_blocks: usize,
-    #[cfg(feature = "chacha")]
     blocktree: Arc<Blocktree>,
 }
 
 pub fn sample_file(in_path: &Path, sample_offsets: &[u64]) -> io::Result<Hash> {
     let in_file = File::open(in_path)?;
     let metadata = in_file.metadata()?;
-    let mut buffer_file = BufReader::new(in_file);
+    sample(BufReader::new(in_file), metadata.len(), sample_offsets)
+}
+
+/// Same as `sample_file`, for an encrypted segment that's kept in memory
+pub fn sample_buffer(buffer: &[u8], sample_offsets: &[u64]) -> io::Result<Hash> {
+    sample(Cursor::new(buffer), buffer.len() as u64, sample_offsets)
+}
 
+fn sample<R: Read + Seek>(
+    mut reader: R,
+    file_len: u64,
+    sample_offsets: &[u64],
+) -> io::Result<Hash> {
     let mut hasher = Hasher::default();
     let sample_size = size_of::<Hash>();
     let sample_size64 = sample_size as u64;
     let mut buf = vec![0; sample_size];
 
-    let file_len = metadata.len();
     if file_len < sample_size64 {
         return Err(Error::new(ErrorKind::Other, "file too short!"));
     }
@@ -90,9 +99,9 @@ pub fn sample_file(in_path: &Path, sample_offsets: &[u64]) -> io::Result<Hash> {
         if *offset > (file_len - sample_size64) / sample_size64 {
             return Err(Error::new(ErrorKind::Other, "offset too large"));
         }
-        buffer_file.seek(SeekFrom::Start(*offset * sample_size64))?;
+        reader.seek(SeekFrom::Start(*offset * sample_size64))?;
         trace!("sampling @ {} ", *offset);
-        match buffer_file.read(&mut buf) {
+        match reader.read(&mut buf) {
             Ok(size) => {
                 assert_eq!(size, buf.len());
                 hasher.hash(&buf);
@@ -287,9 +296,7 @@ impl Replicator {
             ledger_data_file_encrypted: PathBuf::default(),
             sampling_offsets: vec![],
             hash: Hash::default(),
-            #[cfg(feature = "chacha")]
             num_chacha_blocks: 0,
-            #[cfg(feature = "chacha")]
             blocktree,
         })
     }
@@ -298,15 +305,21 @@ impl Replicator {
         self.encrypt_ledger()
             .expect("ledger encrypt not successful");
         loop {
-            self.create_sampling_offsets();
-            if self.sample_file_to_create_mining_hash().is_err() {
+            if self.mine().is_err() {
                 info!("Error sampling file, exiting...");
                 break;
             }
-            self.submit_mining_proof();
         }
     }
 
+    /// Sample the encrypted ledger and submit the resulting storage mining proof
+    pub fn mine(&mut self) -> Result<()> {
+        self.create_sampling_offsets();
+        self.sample_file_to_create_mining_hash()?;
+        self.submit_mining_proof();
+        Ok(())
+    }
+
     fn wait_for_ledger_download(
         start_slot: u64,
         blocktree: &Arc<Blocktree>,
@@ -351,49 +364,29 @@ impl Replicator {
         }
     }
 
-    fn encrypt_ledger(&mut self) -> Result<()> {
+    pub fn encrypt_ledger(&mut self) -> Result<()> {
         let ledger_path = Path::new(&self.ledger_path);
         self.ledger_data_file_encrypted = ledger_path.join("ledger.enc");
 
-        #[cfg(feature = "chacha")]
-        {
-            let mut ivec = [0u8; 64];
-            ivec.copy_from_slice(&self.signature.to_bytes());
+        let mut ivec = [0u8; CHACHA_BLOCK_SIZE];
+        ivec.copy_from_slice(&self.signature.to_bytes());
 
-            let num_encrypted_bytes = chacha_cbc_encrypt_ledger(
-                &self.blocktree,
-                self.slot,
-                &self.ledger_data_file_encrypted,
-                &mut ivec,
-            )?;
+        let num_encrypted_bytes = chacha_cbc_encrypt_ledger(
+            &self.blocktree,
+            self.slot,
+            &self.ledger_data_file_encrypted,
+            &mut ivec,
+        )?;
 
-            self.num_chacha_blocks = num_encrypted_bytes / CHACHA_BLOCK_SIZE;
-        }
+        self.num_chacha_blocks = num_encrypted_bytes / CHACHA_BLOCK_SIZE;
 
         info!("Done encrypting the ledger");
         Ok(())
     }
 
     fn create_sampling_offsets(&mut self) {
-        self.sampling_offsets.clear();
-
-        #[cfg(not(feature = "chacha"))]
-        self.sampling_offsets.push(0);
-
-        #[cfg(feature = "chacha")]
-        {
-            use crate::storage_stage::NUM_STORAGE_SAMPLES;
-            use rand::{Rng, SeedableRng};
-            use rand_chacha::ChaChaRng;
-
-            let mut rng_seed = [0u8; 32];
-            rng_seed.copy_from_slice(&self.signature.to_bytes()[0..32]);
-            let mut rng = ChaChaRng::from_seed(rng_seed);
-            for _ in 0..NUM_STORAGE_SAMPLES {
-                self.sampling_offsets
-                    .push(rng.gen_range(0, self.num_chacha_blocks) as u64);
-            }
-        }
+        self.sampling_offsets =
+            get_sampling_offsets(&self.signature.to_bytes(), self.num_chacha_blocks);
     }
 
     fn sample_file_to_create_mining_hash(&mut self) -> Result<()> {
@@ -419,13 +412,12 @@ impl Replicator {
         let bal = client.poll_get_balance(&storage_keypair.pubkey());
         if bal.is_err() || bal.unwrap() == 0 {
             let blockhash = client.get_recent_blockhash().expect("blockhash");
-            //TODO the account space needs to be well defined somewhere
             let tx = system_transaction::create_account(
                 keypair,
                 &storage_keypair.pubkey(),
                 blockhash,
                 1,
-                1024 * 4,
+                STORAGE_ACCOUNT_SPACE,
                 &soros_storage_api::id(),
                 0,
             );
@@ -520,7 +512,7 @@ impl Replicator {
 
 #[cfg(test)]
 mod tests {
-    use crate::replicator::sample_file;
+    use crate::replicator::{sample_buffer, sample_file};
     use soros_sdk::hash::Hash;
     use soros_sdk::signature::{Keypair, KeypairUtil};
     use std::fs::File;
@@ -570,6 +562,22 @@ mod tests {
         remove_file(&in_path).unwrap();
     }
 
+    #[test]
+    fn test_sample_buffer() {
+        let in_path = tmp_file_path("test_sample_buffer_input.txt");
+        let buffer = "123456foobar".repeat(4096).into_bytes();
+        File::create(&in_path).unwrap().write_all(&buffer).unwrap();
+
+        let samples = [0, 7, 1000];
+        assert_eq!(
+            sample_buffer(&buffer, &samples).unwrap(),
+            sample_file(&in_path, &samples).unwrap()
+        );
+        assert!(sample_buffer(&buffer, &[200000]).is_err());
+        assert!(sample_buffer(&[], &[0]).is_err());
+        remove_file(in_path).unwrap();
+    }
+
     #[test]
     fn test_sample_file_invalid_offset() {
         let in_path = tmp_file_path("test_sample_file_invalid_offset_input.txt");
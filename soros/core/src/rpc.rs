@@ -3,18 +3,31 @@
 use crate::bank_forks::BankForks;
 use crate::cluster_info::ClusterInfo;
 use crate::contact_info::ContactInfo;
+use crate::leader_schedule_utils;
 use crate::packet::PACKET_DATA_SIZE;
+use crate::staking_utils;
 use crate::storage_stage::StorageState;
 use bincode::{deserialize, serialize};
 use bs58;
 use jsonrpc_core::{Error, Metadata, Result};
 use jsonrpc_derive::rpc;
 use soros_drone::drone::request_airdrop_transaction;
-use soros_runtime::bank::Bank;
+use soros_client::rpc_filter::{
+    RpcAccount, RpcFilterError, RpcKeyedAccount, RpcProgramAccountsConfig,
+};
+use soros_client::rpc_request::{
+    RpcEpochInfo, RpcLeaderSchedule, RpcResponse, RpcSnapshot, RpcVoteAccountInfo,
+    RpcVoteAccountStatus,
+};
+use soros_runtime::bank::{Bank, TransactionSimulation};
 use soros_sdk::account::Account;
+use soros_sdk::commitment_config::{CommitmentConfig, CommitmentLevel};
+use soros_sdk::epoch_schedule::EpochSchedule;
 use soros_sdk::pubkey::Pubkey;
 use soros_sdk::signature::Signature;
 use soros_sdk::transaction::{self, Transaction};
+use soros_vote_api::vote_state::VoteState;
+use std::collections::HashMap;
 use std::mem;
 use std::net::{SocketAddr, UdpSocket};
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -22,6 +35,9 @@ use std::sync::{Arc, RwLock};
 use std::thread::sleep;
 use std::time::{Duration, Instant};
 
+/// A vote account whose last vote is this many slots or more behind the bank is delinquent
+pub const DELINQUENT_VALIDATOR_SLOT_DISTANCE: u64 = 128;
+
 #[derive(Debug, Clone)]
 pub struct JsonRpcConfig {
     pub enable_fullnode_exit: bool, // Enable the 'fullnodeExit' command
@@ -46,8 +62,14 @@ pub struct JsonRpcRequestProcessor {
 }
 
 impl JsonRpcRequestProcessor {
-    fn bank(&self) -> Arc<Bank> {
-        self.bank_forks.read().unwrap().working_bank()
+    /// The bank a read at `commitment` is served from, the working bank by default
+    fn bank(&self, commitment: Option<CommitmentConfig>) -> Arc<Bank> {
+        let bank_forks = self.bank_forks.read().unwrap();
+        match commitment.unwrap_or_default().commitment {
+            CommitmentLevel::Recent => bank_forks.working_bank(),
+            CommitmentLevel::Confirmed => bank_forks.confirmed_bank(),
+            CommitmentLevel::Root => bank_forks.root_bank(),
+        }
     }
 
     pub fn new(
@@ -64,40 +86,197 @@ impl JsonRpcRequestProcessor {
         }
     }
 
-    pub fn get_account_info(&self, pubkey: &Pubkey) -> Result<Account> {
-        self.bank()
-            .get_account(&pubkey)
+    /// The most recent bank that won't change anymore
+    fn frozen_bank(&self) -> Option<Arc<Bank>> {
+        self.bank_forks
+            .read()
+            .unwrap()
+            .frozen_banks()
+            .into_iter()
+            .max_by_key(|(slot, _)| *slot)
+            .map(|(_, bank)| bank)
+    }
+
+    pub fn get_account_info(
+        &self,
+        pubkey: &Pubkey,
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<RpcResponse<Account>> {
+        let bank = self.bank(commitment);
+        bank.get_account(&pubkey)
+            .map(|account| RpcResponse::new(bank.slot(), account))
             .ok_or_else(Error::invalid_request)
     }
 
-    pub fn get_balance(&self, pubkey: &Pubkey) -> u64 {
-        self.bank().get_balance(&pubkey)
+    pub fn get_balance(
+        &self,
+        pubkey: &Pubkey,
+        commitment: Option<CommitmentConfig>,
+    ) -> RpcResponse<u64> {
+        let bank = self.bank(commitment);
+        RpcResponse::new(bank.slot(), bank.get_balance(&pubkey))
+    }
+
+    pub fn get_program_accounts(
+        &self,
+        program_id: &Pubkey,
+        config: RpcProgramAccountsConfig,
+    ) -> Result<Vec<RpcKeyedAccount>> {
+        let invalid_params = |err: RpcFilterError| Error::invalid_params(err.to_string());
+        let query = config.verify().map_err(invalid_params)?;
+        let mut accounts: Vec<_> = self
+            .bank(config.commitment)
+            .get_program_accounts(program_id)
+            .into_iter()
+            .filter(|(pubkey, account)| {
+                query.after.map_or(true, |after| *pubkey > after) && query.allows(account)
+            })
+            .collect();
+        accounts.sort_by_key(|(pubkey, _)| *pubkey);
+        accounts
+            .into_iter()
+            .take(query.limit)
+            .map(|(pubkey, account)| {
+                Ok(RpcKeyedAccount {
+                    pubkey: pubkey.to_string(),
+                    account: RpcAccount::encode(&account, query.encoding)
+                        .map_err(invalid_params)?,
+                })
+            })
+            .collect()
     }
 
-    fn get_recent_blockhash(&self) -> String {
-        let id = self.bank().confirmed_last_blockhash();
+    fn get_recent_blockhash(&self, commitment: Option<CommitmentConfig>) -> String {
+        let id = self.bank(commitment).confirmed_last_blockhash();
         bs58::encode(id).into_string()
     }
 
-    pub fn get_signature_status(&self, signature: Signature) -> Option<transaction::Result<()>> {
-        self.get_signature_confirmation_status(signature)
-            .map(|x| x.1)
+    pub fn get_signature_status(
+        &self,
+        signature: Signature,
+        commitment: Option<CommitmentConfig>,
+    ) -> RpcResponse<Option<transaction::Result<()>>> {
+        let bank = self.bank(commitment);
+        let status = bank
+            .get_signature_confirmation_status(&signature)
+            .map(|x| x.1);
+        RpcResponse::new(bank.slot(), status)
     }
 
-    pub fn get_signature_confirmations(&self, signature: Signature) -> Option<usize> {
-        self.get_signature_confirmation_status(signature)
+    pub fn get_signature_confirmations(
+        &self,
+        signature: Signature,
+        commitment: Option<CommitmentConfig>,
+    ) -> Option<usize> {
+        self.get_signature_confirmation_status(signature, commitment)
             .map(|x| x.0)
     }
 
     pub fn get_signature_confirmation_status(
         &self,
         signature: Signature,
+        commitment: Option<CommitmentConfig>,
     ) -> Option<(usize, transaction::Result<()>)> {
-        self.bank().get_signature_confirmation_status(&signature)
+        self.bank(commitment)
+            .get_signature_confirmation_status(&signature)
+    }
+
+    fn get_transaction_count(&self, commitment: Option<CommitmentConfig>) -> Result<u64> {
+        Ok(self.bank(commitment).transaction_count() as u64)
+    }
+
+    fn get_snapshot(&self) -> Option<RpcSnapshot> {
+        let (slot, snapshot) = self.bank_forks.read().unwrap().snapshot()?;
+        Some(RpcSnapshot {
+            slot,
+            snapshot: base64::encode(snapshot.as_slice()),
+        })
+    }
+
+    /// Run `tx` on top of the most recent frozen bank without committing it, the working bank
+    /// can't be used because simulating freezes the bank it runs on top of
+    pub fn simulate_transaction(&self, tx: &Transaction) -> Result<TransactionSimulation> {
+        let bank = self.frozen_bank().ok_or_else(|| {
+            info!("simulate_transaction: no frozen bank yet");
+            Error::internal_error()
+        })?;
+        Ok(Bank::simulate_transaction(&bank, tx))
     }
 
-    fn get_transaction_count(&self) -> Result<u64> {
-        Ok(self.bank().transaction_count() as u64)
+    fn get_epoch_info(&self, commitment: Option<CommitmentConfig>) -> RpcEpochInfo {
+        let bank = self.bank(commitment);
+        let absolute_slot = bank.slot();
+        let (epoch, slot_index) = bank.get_epoch_and_slot_index(absolute_slot);
+        RpcEpochInfo {
+            epoch,
+            slot_index,
+            slots_in_epoch: bank.get_slots_in_epoch(epoch),
+            absolute_slot,
+        }
+    }
+
+    fn get_epoch_schedule(&self) -> EpochSchedule {
+        *self.bank(None).epoch_schedule()
+    }
+
+    /// The slot indexes of `epoch`, the bank's epoch by default, each leader is scheduled for.
+    /// `None` if the bank doesn't have the stakes the epoch's schedule is computed from.
+    fn get_leader_schedule(
+        &self,
+        epoch: Option<u64>,
+        commitment: Option<CommitmentConfig>,
+    ) -> Option<RpcLeaderSchedule> {
+        let bank = self.bank(commitment);
+        let epoch = epoch.unwrap_or_else(|| bank.get_epoch_and_slot_index(bank.slot()).0);
+        let leader_schedule = leader_schedule_utils::leader_schedule(epoch, &bank)?;
+        let mut schedule: RpcLeaderSchedule = HashMap::new();
+        for (slot_index, leader) in leader_schedule.get_slot_leaders().iter().enumerate() {
+            schedule
+                .entry(leader.to_string())
+                .or_default()
+                .push(slot_index as u64);
+        }
+        Some(schedule)
+    }
+
+    /// The vote accounts with stake in the bank's epoch, with their votes as of the bank itself.
+    /// One whose last vote is `DELINQUENT_VALIDATOR_SLOT_DISTANCE` or more slots behind the bank,
+    /// or that has never voted, is delinquent.
+    fn get_vote_accounts(&self, commitment: Option<CommitmentConfig>) -> RpcVoteAccountStatus {
+        let bank = self.bank(commitment);
+        let (epoch, _) = bank.get_epoch_and_slot_index(bank.slot());
+        // Stakes are fixed at the epoch's start, votes change every slot
+        let vote_accounts = bank.vote_accounts();
+        let mut status = RpcVoteAccountStatus {
+            current: vec![],
+            delinquent: vec![],
+        };
+        let staked_accounts = staking_utils::node_staked_accounts_at_epoch(&bank, epoch);
+        for (vote_pubkey, activated_stake, epoch_account) in staked_accounts.into_iter().flatten() {
+            let account = vote_accounts.get(vote_pubkey).unwrap_or(epoch_account);
+            let vote_state = match VoteState::deserialize(&account.data) {
+                Ok(vote_state) => vote_state,
+                Err(_) => continue,
+            };
+            let last_vote = vote_state.votes.back().map(|lockout| lockout.slot);
+            let info = RpcVoteAccountInfo {
+                vote_pubkey: vote_pubkey.to_string(),
+                node_pubkey: vote_state.node_id.to_string(),
+                activated_stake,
+                commission: vote_state.commission,
+                last_vote,
+                root_slot: vote_state.root_slot,
+            };
+            let is_current = last_vote.map_or(false, |last_vote| {
+                last_vote + DELINQUENT_VALIDATOR_SLOT_DISTANCE > bank.slot()
+            });
+            if is_current {
+                status.current.push(info);
+            } else {
+                status.delinquent.push(info);
+            }
+        }
+        status
     }
 
     fn get_storage_blockhash(&self) -> Result<String> {
@@ -189,29 +368,57 @@ pub trait RpcSol {
     type Metadata;
 
     #[rpc(meta, name = "confirmTxn")]
-    fn confirm_transaction(&self, _: Self::Metadata, _: String) -> Result<bool>;
+    fn confirm_transaction(
+        &self,
+        _: Self::Metadata,
+        _: String,
+        _: Option<CommitmentConfig>,
+    ) -> Result<bool>;
 
     #[rpc(meta, name = "getAccountInfo")]
-    fn get_account_info(&self, _: Self::Metadata, _: String) -> Result<Account>;
+    fn get_account_info(
+        &self,
+        _: Self::Metadata,
+        _: String,
+        _: Option<CommitmentConfig>,
+    ) -> Result<RpcResponse<Account>>;
 
     #[rpc(meta, name = "getDif")]
-    fn get_balance(&self, _: Self::Metadata, _: String) -> Result<u64>;
+    fn get_balance(
+        &self,
+        _: Self::Metadata,
+        _: String,
+        _: Option<CommitmentConfig>,
+    ) -> Result<RpcResponse<u64>>;
 
     #[rpc(meta, name = "getClusterNodes")]
     fn get_cluster_nodes(&self, _: Self::Metadata) -> Result<Vec<RpcContactInfo>>;
 
+    #[rpc(meta, name = "getProgramAccounts")]
+    fn get_program_accounts(
+        &self,
+        _: Self::Metadata,
+        _: String,
+        _: Option<RpcProgramAccountsConfig>,
+    ) -> Result<Vec<RpcKeyedAccount>>;
+
     #[rpc(meta, name = "getLatestBlockhash")]
-    fn get_recent_blockhash(&self, _: Self::Metadata) -> Result<String>;
+    fn get_recent_blockhash(
+        &self,
+        _: Self::Metadata,
+        _: Option<CommitmentConfig>,
+    ) -> Result<String>;
 
     #[rpc(meta, name = "getSignatureState")]
     fn get_signature_status(
         &self,
         _: Self::Metadata,
         _: String,
-    ) -> Result<Option<transaction::Result<()>>>;
+        _: Option<CommitmentConfig>,
+    ) -> Result<RpcResponse<Option<transaction::Result<()>>>>;
 
     #[rpc(meta, name = "getTxnCnt")]
-    fn get_transaction_count(&self, _: Self::Metadata) -> Result<u64>;
+    fn get_transaction_count(&self, _: Self::Metadata, _: Option<CommitmentConfig>) -> Result<u64>;
 
     #[rpc(meta, name = "requestDif")]
     fn request_airdrop(&self, _: Self::Metadata, _: String, _: u64) -> Result<String>;
@@ -219,9 +426,40 @@ pub trait RpcSol {
     #[rpc(meta, name = "sendTxn")]
     fn send_transaction(&self, _: Self::Metadata, _: Vec<u8>) -> Result<String>;
 
+    #[rpc(meta, name = "simulateTransaction")]
+    fn simulate_transaction(&self, _: Self::Metadata, _: Vec<u8>) -> Result<TransactionSimulation>;
+
     #[rpc(meta, name = "getRoundLeader")]
     fn get_slot_leader(&self, _: Self::Metadata) -> Result<String>;
 
+    #[rpc(meta, name = "getEpochInfo")]
+    fn get_epoch_info(
+        &self,
+        _: Self::Metadata,
+        _: Option<CommitmentConfig>,
+    ) -> Result<RpcEpochInfo>;
+
+    #[rpc(meta, name = "getEpochSchedule")]
+    fn get_epoch_schedule(&self, _: Self::Metadata) -> Result<EpochSchedule>;
+
+    #[rpc(meta, name = "getLeaderSchedule")]
+    fn get_leader_schedule(
+        &self,
+        _: Self::Metadata,
+        _: Option<u64>,
+        _: Option<CommitmentConfig>,
+    ) -> Result<Option<RpcLeaderSchedule>>;
+
+    #[rpc(meta, name = "getVoteAccounts")]
+    fn get_vote_accounts(
+        &self,
+        _: Self::Metadata,
+        _: Option<CommitmentConfig>,
+    ) -> Result<RpcVoteAccountStatus>;
+
+    #[rpc(meta, name = "getSnapshot")]
+    fn get_snapshot(&self, _: Self::Metadata) -> Result<Option<RpcSnapshot>>;
+
     #[rpc(meta, name = "getStorageBlockhash")]
     fn get_storage_blockhash(&self, _: Self::Metadata) -> Result<String>;
 
@@ -243,6 +481,7 @@ pub trait RpcSol {
         &self,
         _: Self::Metadata,
         _: String,
+        _: Option<CommitmentConfig>,
     ) -> Result<Option<usize>>;
 
     #[rpc(meta, name = "getSignatureConfirmation")]
@@ -250,6 +489,7 @@ pub trait RpcSol {
         &self,
         _: Self::Metadata,
         _: String,
+        _: Option<CommitmentConfig>,
     ) -> Result<Option<(usize, transaction::Result<()>)>>;
 }
 
@@ -257,29 +497,47 @@ pub struct RpcSolImpl;
 impl RpcSol for RpcSolImpl {
     type Metadata = Meta;
 
-    fn confirm_transaction(&self, meta: Self::Metadata, id: String) -> Result<bool> {
+    fn confirm_transaction(
+        &self,
+        meta: Self::Metadata,
+        id: String,
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<bool> {
         debug!("confirm_transaction rpc request received: {:?}", id);
-        self.get_signature_status(meta, id).map(|status_option| {
-            if status_option.is_none() {
-                return false;
-            }
-            status_option.unwrap().is_ok()
-        })
+        self.get_signature_status(meta, id, commitment)
+            .map(|response| match response.value {
+                Some(status) => status.is_ok(),
+                None => false,
+            })
     }
 
-    fn get_account_info(&self, meta: Self::Metadata, id: String) -> Result<Account> {
+    fn get_account_info(
+        &self,
+        meta: Self::Metadata,
+        id: String,
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<RpcResponse<Account>> {
         debug!("get_account_info rpc request received: {:?}", id);
         let pubkey = verify_pubkey(id)?;
         meta.request_processor
             .read()
             .unwrap()
-            .get_account_info(&pubkey)
+            .get_account_info(&pubkey, commitment)
     }
 
-    fn get_balance(&self, meta: Self::Metadata, id: String) -> Result<u64> {
+    fn get_balance(
+        &self,
+        meta: Self::Metadata,
+        id: String,
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<RpcResponse<u64>> {
         debug!("get_balance rpc request received: {:?}", id);
         let pubkey = verify_pubkey(id)?;
-        Ok(meta.request_processor.read().unwrap().get_balance(&pubkey))
+        Ok(meta
+            .request_processor
+            .read()
+            .unwrap()
+            .get_balance(&pubkey, commitment))
     }
 
     fn get_cluster_nodes(&self, meta: Self::Metadata) -> Result<Vec<RpcContactInfo>> {
@@ -309,30 +567,55 @@ impl RpcSol for RpcSolImpl {
             .collect())
     }
 
-    fn get_recent_blockhash(&self, meta: Self::Metadata) -> Result<String> {
+    fn get_program_accounts(
+        &self,
+        meta: Self::Metadata,
+        id: String,
+        config: Option<RpcProgramAccountsConfig>,
+    ) -> Result<Vec<RpcKeyedAccount>> {
+        debug!("get_program_accounts rpc request received: {:?}", id);
+        let program_id = verify_pubkey(id)?;
+        meta.request_processor
+            .read()
+            .unwrap()
+            .get_program_accounts(&program_id, config.unwrap_or_default())
+    }
+
+    fn get_recent_blockhash(
+        &self,
+        meta: Self::Metadata,
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<String> {
         debug!("get_recent_blockhash rpc request received");
         Ok(meta
             .request_processor
             .read()
             .unwrap()
-            .get_recent_blockhash())
+            .get_recent_blockhash(commitment))
     }
 
     fn get_signature_status(
         &self,
         meta: Self::Metadata,
         id: String,
-    ) -> Result<Option<transaction::Result<()>>> {
-        self.get_signature_confirmation(meta, id)
-            .map(|res| res.map(|x| x.1))
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<RpcResponse<Option<transaction::Result<()>>>> {
+        debug!("get_signature_status rpc request received: {:?}", id);
+        let signature = verify_signature(&id)?;
+        Ok(meta
+            .request_processor
+            .read()
+            .unwrap()
+            .get_signature_status(signature, commitment))
     }
 
     fn get_num_blocks_since_signature_confirmation(
         &self,
         meta: Self::Metadata,
         id: String,
+        commitment: Option<CommitmentConfig>,
     ) -> Result<Option<usize>> {
-        self.get_signature_confirmation(meta, id)
+        self.get_signature_confirmation(meta, id, commitment)
             .map(|res| res.map(|x| x.0))
     }
 
@@ -340,6 +623,7 @@ impl RpcSol for RpcSolImpl {
         &self,
         meta: Self::Metadata,
         id: String,
+        commitment: Option<CommitmentConfig>,
     ) -> Result<Option<(usize, transaction::Result<()>)>> {
         debug!("get_signature_confirmation rpc request received: {:?}", id);
         let signature = verify_signature(&id)?;
@@ -347,15 +631,19 @@ impl RpcSol for RpcSolImpl {
             .request_processor
             .read()
             .unwrap()
-            .get_signature_confirmation_status(signature))
+            .get_signature_confirmation_status(signature, commitment))
     }
 
-    fn get_transaction_count(&self, meta: Self::Metadata) -> Result<u64> {
+    fn get_transaction_count(
+        &self,
+        meta: Self::Metadata,
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<u64> {
         debug!("get_transaction_count rpc request received");
         meta.request_processor
             .read()
             .unwrap()
-            .get_transaction_count()
+            .get_transaction_count(commitment)
     }
 
     // fn request_airdrop(&self, meta: Self::Metadata, id: String, lamports: u64) -> Result<String> {
@@ -375,7 +663,7 @@ impl RpcSol for RpcSolImpl {
             .request_processor
             .read()
             .unwrap()
-            .bank()
+            .bank(None)
             .confirmed_last_blockhash();
         // let transaction = request_airdrop_transaction(&drone_addr, &pubkey, lamports, blockhash)
         let transaction = request_airdrop_transaction(&drone_addr, &pubkey, dif, blockhash)
@@ -406,7 +694,8 @@ impl RpcSol for RpcSolImpl {
                 .request_processor
                 .read()
                 .unwrap()
-                .get_signature_status(signature);
+                .get_signature_status(signature, None)
+                .value;
 
             if signature_status == Some(Ok(())) {
                 info!("airdrop signature ok");
@@ -450,6 +739,25 @@ impl RpcSol for RpcSolImpl {
         Ok(signature)
     }
 
+    fn simulate_transaction(
+        &self,
+        meta: Self::Metadata,
+        data: Vec<u8>,
+    ) -> Result<TransactionSimulation> {
+        let tx: Transaction = deserialize(&data).map_err(|err| {
+            info!("simulate_transaction: deserialize error: {:?}", err);
+            Error::invalid_request()
+        })?;
+        if !tx.verify_refs() {
+            info!("simulate_transaction: invalid account or program index");
+            return Err(Error::invalid_request());
+        }
+        meta.request_processor
+            .read()
+            .unwrap()
+            .simulate_transaction(&tx)
+    }
+
     fn get_slot_leader(&self, meta: Self::Metadata) -> Result<String> {
         let cluster_info = meta.cluster_info.read().unwrap();
         let leader_data_option = cluster_info.leader_data();
@@ -459,6 +767,56 @@ impl RpcSol for RpcSolImpl {
             .to_string())
     }
 
+    fn get_epoch_info(
+        &self,
+        meta: Self::Metadata,
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<RpcEpochInfo> {
+        debug!("get_epoch_info rpc request received");
+        Ok(meta
+            .request_processor
+            .read()
+            .unwrap()
+            .get_epoch_info(commitment))
+    }
+
+    fn get_epoch_schedule(&self, meta: Self::Metadata) -> Result<EpochSchedule> {
+        debug!("get_epoch_schedule rpc request received");
+        Ok(meta.request_processor.read().unwrap().get_epoch_schedule())
+    }
+
+    fn get_leader_schedule(
+        &self,
+        meta: Self::Metadata,
+        epoch: Option<u64>,
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<Option<RpcLeaderSchedule>> {
+        debug!("get_leader_schedule rpc request received: {:?}", epoch);
+        Ok(meta
+            .request_processor
+            .read()
+            .unwrap()
+            .get_leader_schedule(epoch, commitment))
+    }
+
+    fn get_vote_accounts(
+        &self,
+        meta: Self::Metadata,
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<RpcVoteAccountStatus> {
+        debug!("get_vote_accounts rpc request received");
+        Ok(meta
+            .request_processor
+            .read()
+            .unwrap()
+            .get_vote_accounts(commitment))
+    }
+
+    fn get_snapshot(&self, meta: Self::Metadata) -> Result<Option<RpcSnapshot>> {
+        debug!("get_snapshot rpc request received");
+        Ok(meta.request_processor.read().unwrap().get_snapshot())
+    }
+
     fn get_storage_blockhash(&self, meta: Self::Metadata) -> Result<String> {
         meta.request_processor
             .read()
@@ -494,12 +852,16 @@ mod tests {
     use super::*;
     use crate::contact_info::ContactInfo;
     use jsonrpc_core::{MetaIoHandler, Response};
-    use soros_sdk::genesis_block::GenesisBlock;
+    use serde_json::Value;
+    use soros_runtime::bank::BankSnapshot;
+    use soros_sdk::genesis_block::{GenesisBlock, BOOTSTRAP_LEADER_DIF};
     use soros_sdk::hash::{hash, Hash};
     use soros_sdk::instruction::InstructionError;
     use soros_sdk::signature::{Keypair, KeypairUtil};
     use soros_sdk::system_transaction;
     use soros_sdk::transaction::TransactionError;
+    use soros_vote_api::vote_instruction;
+    use soros_vote_api::vote_state::Vote;
     use std::thread;
 
     fn start_rpc_handler_with_tx(
@@ -558,7 +920,7 @@ mod tests {
         })
         .join()
         .unwrap();
-        assert_eq!(request_processor.get_transaction_count().unwrap(), 1);
+        assert_eq!(request_processor.get_transaction_count(None).unwrap(), 1);
     }
 
     #[test]
@@ -571,7 +933,8 @@ mod tests {
             bob_pubkey
         );
         let res = io.handle_request_sync(&req, meta);
-        let expected = format!(r#"{{"jsonrpc":"2.0","result":20,"id":1}}"#);
+        let expected =
+            format!(r#"{{"jsonrpc":"2.0","result":{{"context":{{"slot":0}},"value":20}},"id":1}}"#);
         let expected: Response =
             serde_json::from_str(&expected).expect("expected response deserialization");
         let result: Response = serde_json::from_str(&res.expect("actual response"))
@@ -579,6 +942,48 @@ mod tests {
         assert_eq!(expected, result);
     }
 
+    #[test]
+    fn test_rpc_request_processor_commitment() {
+        let bob_pubkey = Pubkey::new_rand();
+        let exit = Arc::new(AtomicBool::new(false));
+        let (bank_forks, alice) = new_bank_forks();
+        let bank0 = bank_forks.read().unwrap().working_bank();
+        let blockhash = bank0.confirmed_last_blockhash();
+        let bank1 = Bank::new_from_parent(&bank0, &Pubkey::default(), 1);
+        let tx = system_transaction::transfer(&alice, &bob_pubkey, 20, blockhash, 0);
+        bank1.process_transaction(&tx).expect("process transaction");
+        let bank2 = Bank::new_from_parent(&bank1, &Pubkey::default(), 2);
+        bank2.transfer(10, &alice, &bob_pubkey).unwrap();
+        bank_forks.write().unwrap().insert(bank1);
+        bank_forks.write().unwrap().insert(bank2);
+
+        let request_processor = JsonRpcRequestProcessor::new(
+            StorageState::default(),
+            JsonRpcConfig::default(),
+            bank_forks.clone(),
+            &exit,
+        );
+        let balance = |commitment| request_processor.get_balance(&bob_pubkey, Some(commitment));
+
+        let recent = balance(CommitmentConfig::recent());
+        assert_eq!((recent.context.slot, recent.value), (2, 30));
+        let confirmed = balance(CommitmentConfig::confirmed());
+        assert_eq!((confirmed.context.slot, confirmed.value), (0, 0));
+        assert_eq!(request_processor.get_balance(&bob_pubkey, None), recent);
+
+        bank_forks.write().unwrap().set_confirmed_slot(1);
+        let confirmed = balance(CommitmentConfig::confirmed());
+        assert_eq!((confirmed.context.slot, confirmed.value), (1, 20));
+        let root = balance(CommitmentConfig::root());
+        assert_eq!((root.context.slot, root.value), (0, 0));
+
+        let status = request_processor.get_signature_status(tx.signatures[0], None);
+        assert_eq!(status.value, Some(Ok(())));
+        let status = request_processor
+            .get_signature_status(tx.signatures[0], Some(CommitmentConfig::root()));
+        assert_eq!(status.value, None);
+    }
+
     #[test]
     fn test_rpc_get_cluster_nodes() {
         let bob_pubkey = Pubkey::new_rand();
@@ -649,10 +1054,13 @@ mod tests {
         let expected = r#"{
             "jsonrpc":"2.0",
             "result":{
-                "owner": [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],
-                "dif": 20,
-                "data": [],
-                "executable": false
+                "context":{"slot":0},
+                "value":{
+                    "owner": [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],
+                    "dif": 20,
+                    "data": [],
+                    "executable": false
+                }
             },
             "id":1}
         "#;
@@ -663,6 +1071,211 @@ mod tests {
         assert_eq!(expected, result);
     }
 
+    #[test]
+    fn test_rpc_get_program_accounts() {
+        let bob_pubkey = Pubkey::new_rand();
+        let (io, meta, blockhash, alice, _leader_id) = start_rpc_handler_with_tx(&bob_pubkey);
+        let program_id = Pubkey::new_rand();
+        let bank = meta.request_processor.read().unwrap().bank(None);
+        let key16 = Pubkey::new_rand();
+        let tx =
+            system_transaction::create_account(&alice, &key16, blockhash, 1, 16, &program_id, 0);
+        bank.process_transaction(&tx).unwrap();
+        let key8 = Pubkey::new_rand();
+        let tx = system_transaction::create_account(&alice, &key8, blockhash, 2, 8, &program_id, 0);
+        bank.process_transaction(&tx).unwrap();
+
+        let get_program_accounts = |config: Value| {
+            let req = json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "getProgramAccounts",
+                "params": [program_id.to_string(), config],
+            });
+            let res = io.handle_request_sync(&req.to_string(), meta.clone());
+            let result: Value = serde_json::from_str(&res.expect("actual response"))
+                .expect("actual response deserialization");
+            result
+        };
+
+        let result = get_program_accounts(json!({}));
+        let mut keyed_accounts: Vec<RpcKeyedAccount> =
+            serde_json::from_value(result["result"].clone()).unwrap();
+        keyed_accounts.sort_by_key(|keyed_account| keyed_account.account.dif);
+        assert_eq!(keyed_accounts.len(), 2);
+        assert_eq!(keyed_accounts[0].pubkey, key16.to_string());
+        assert_eq!(keyed_accounts[0].account.owner, program_id.to_string());
+        assert_eq!(
+            keyed_accounts[0].account.data,
+            bs58::encode(vec![0; 16]).into_string()
+        );
+
+        let result = get_program_accounts(json!({
+            "filters": [{"dataSize": 8}, {"memcmp": {"offset": 4, "bytes": "1111"}}],
+            "encoding": "base64",
+        }));
+        let keyed_accounts: Vec<RpcKeyedAccount> =
+            serde_json::from_value(result["result"].clone()).unwrap();
+        assert_eq!(keyed_accounts.len(), 1);
+        assert_eq!(keyed_accounts[0].pubkey, key8.to_string());
+        assert_eq!(keyed_accounts[0].account.data, "AAAAAAAAAAA=");
+
+        let result = get_program_accounts(json!({
+            "filters": [{"memcmp": {"offset": 0, "bytes": "2"}}],
+        }));
+        assert_eq!(result["result"], json!([]));
+
+        // Not base58
+        let result = get_program_accounts(json!({
+            "filters": [{"memcmp": {"offset": 0, "bytes": "0"}}],
+        }));
+        assert_eq!(result["error"]["code"], json!(-32602));
+
+        // Pages in pubkey order
+        let mut pubkeys = vec![key8, key16];
+        pubkeys.sort();
+        let pubkeys: Vec<_> = pubkeys.iter().map(|pubkey| pubkey.to_string()).collect();
+        let result = get_program_accounts(json!({"limit": 1}));
+        assert_eq!(result["result"][0]["pubkey"], json!(pubkeys[0]));
+        assert_eq!(result["result"].as_array().unwrap().len(), 1);
+        let result = get_program_accounts(json!({"limit": 1, "after": pubkeys[0]}));
+        assert_eq!(result["result"][0]["pubkey"], json!(pubkeys[1]));
+        let result = get_program_accounts(json!({"after": pubkeys[1]}));
+        assert_eq!(result["result"], json!([]));
+        let result = get_program_accounts(json!({"limit": 0}));
+        assert_eq!(result["error"]["code"], json!(-32602));
+    }
+
+    #[test]
+    fn test_rpc_get_snapshot() {
+        let bob_pubkey = Pubkey::new_rand();
+        let (io, meta, _blockhash, _alice, _leader_id) = start_rpc_handler_with_tx(&bob_pubkey);
+        let req = r#"{"jsonrpc":"2.0","id":1,"method":"getSnapshot"}"#;
+        let res = io.handle_request_sync(req, meta.clone());
+        let expected = r#"{"jsonrpc":"2.0","result":null,"id":1}"#;
+        assert_eq!(res, Some(expected.to_string()));
+
+        let bank_forks = meta.request_processor.read().unwrap().bank_forks.clone();
+        bank_forks.write().unwrap().set_snapshot_interval(Some(1));
+        let res = io.handle_request_sync(req, meta);
+        let result: Value = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        let rpc_snapshot: RpcSnapshot = serde_json::from_value(result["result"].clone()).unwrap();
+        assert_eq!(rpc_snapshot.slot, 0);
+        let snapshot: BankSnapshot =
+            deserialize(&base64::decode(&rpc_snapshot.snapshot).unwrap()).unwrap();
+        let snapshot_hash = snapshot.snapshot_hash();
+        let bank = Bank::from_snapshot(snapshot, &snapshot_hash, None).unwrap();
+        assert_eq!(bank.get_balance(&bob_pubkey), 20);
+    }
+
+    #[test]
+    fn test_rpc_get_epoch_info_and_schedule() {
+        let bob_pubkey = Pubkey::new_rand();
+        let (io, meta, _blockhash, _alice, _leader_id) = start_rpc_handler_with_tx(&bob_pubkey);
+        let bank = meta.request_processor.read().unwrap().bank(None);
+
+        let req = r#"{"jsonrpc":"2.0","id":1,"method":"getEpochInfo"}"#;
+        let res = io.handle_request_sync(req, meta.clone());
+        let result: Value = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        let epoch_info: RpcEpochInfo = serde_json::from_value(result["result"].clone()).unwrap();
+        assert_eq!(
+            epoch_info,
+            RpcEpochInfo {
+                epoch: 0,
+                slot_index: 0,
+                slots_in_epoch: bank.get_slots_in_epoch(0),
+                absolute_slot: 0,
+            }
+        );
+
+        let req = r#"{"jsonrpc":"2.0","id":1,"method":"getEpochSchedule"}"#;
+        let res = io.handle_request_sync(req, meta);
+        let result: Value = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        let epoch_schedule: EpochSchedule =
+            serde_json::from_value(result["result"].clone()).unwrap();
+        assert_eq!(epoch_schedule, *bank.epoch_schedule());
+    }
+
+    #[test]
+    fn test_rpc_get_leader_schedule_and_vote_accounts() {
+        let bob_pubkey = Pubkey::new_rand();
+        let (io, meta, _blockhash, _alice, _leader_id) = start_rpc_handler_with_tx(&bob_pubkey);
+        let bank = meta.request_processor.read().unwrap().bank(None);
+
+        // The bootstrap leader's vote account voted on slot 0 in the genesis block
+        let req = r#"{"jsonrpc":"2.0","id":1,"method":"getVoteAccounts"}"#;
+        let res = io.handle_request_sync(req, meta.clone());
+        let result: Value = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        let status: RpcVoteAccountStatus =
+            serde_json::from_value(result["result"].clone()).unwrap();
+        assert!(status.delinquent.is_empty());
+        assert_eq!(status.current.len(), 1);
+        let vote_account = &status.current[0];
+        assert_eq!(vote_account.activated_stake, BOOTSTRAP_LEADER_DIF - 1);
+        assert_eq!(vote_account.last_vote, Some(0));
+
+        // The bootstrap leader is the only staked node, so it leads every slot
+        let req = r#"{"jsonrpc":"2.0","id":1,"method":"getLeaderSchedule"}"#;
+        let res = io.handle_request_sync(req, meta.clone());
+        let result: Value = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        let schedule: Option<RpcLeaderSchedule> =
+            serde_json::from_value(result["result"].clone()).unwrap();
+        let schedule = schedule.unwrap();
+        assert_eq!(schedule.len(), 1);
+        let slot_indexes: Vec<u64> = (0..bank.get_slots_in_epoch(0)).collect();
+        assert_eq!(schedule[&vote_account.node_pubkey], slot_indexes);
+
+        // There are no stakes for an epoch this far ahead yet
+        let req = r#"{"jsonrpc":"2.0","id":1,"method":"getLeaderSchedule","params":[1000]}"#;
+        let res = io.handle_request_sync(req, meta);
+        let expected = r#"{"jsonrpc":"2.0","result":null,"id":1}"#;
+        assert_eq!(res, Some(expected.to_string()));
+    }
+
+    #[test]
+    fn test_rpc_get_vote_accounts_current_votes() {
+        let (mut genesis_block, _alice) = GenesisBlock::new(10_000);
+        let vote_keypair = Keypair::new();
+        genesis_block.bootstrap_leader_vote_account_id = vote_keypair.pubkey();
+        genesis_block.epoch_warmup = false;
+        let mut bank = Bank::new(&genesis_block);
+
+        // Vote on every slot, well past the delinquency distance from the epoch's start
+        for slot in 1..=DELINQUENT_VALIDATOR_SLOT_DISTANCE + 2 {
+            let parent = Arc::new(bank);
+            bank = Bank::new_from_parent(&parent, &Pubkey::default(), slot);
+            let vote = vote_instruction::vote(&vote_keypair.pubkey(), vec![Vote::new(slot)]);
+            let tx = Transaction::new_signed_instructions(
+                &[&vote_keypair],
+                vec![vote],
+                bank.last_blockhash(),
+            );
+            bank.process_transaction(&tx).unwrap();
+        }
+        let last_slot = bank.slot();
+        assert_eq!(bank.get_epoch_and_slot_index(last_slot).0, 0);
+
+        let exit = Arc::new(AtomicBool::new(false));
+        let request_processor = JsonRpcRequestProcessor::new(
+            StorageState::default(),
+            JsonRpcConfig::default(),
+            Arc::new(RwLock::new(BankForks::new(last_slot, bank))),
+            &exit,
+        );
+        let status = request_processor.get_vote_accounts(None);
+        assert!(status.delinquent.is_empty());
+        assert_eq!(status.current.len(), 1);
+        let vote_account = &status.current[0];
+        assert_eq!(vote_account.vote_pubkey, vote_keypair.pubkey().to_string());
+        assert_eq!(vote_account.activated_stake, BOOTSTRAP_LEADER_DIF - 1);
+        assert_eq!(vote_account.last_vote, Some(last_slot));
+    }
+
     #[test]
     fn test_rpc_confirm_tx() {
         let bob_pubkey = Pubkey::new_rand();
@@ -696,7 +1309,7 @@ mod tests {
         let expected_res: Option<transaction::Result<()>> = Some(Ok(()));
         let expected = json!({
             "jsonrpc": "2.0",
-            "result": expected_res,
+            "result": {"context": {"slot": 0}, "value": expected_res},
             "id": 1
         });
         let expected: Response =
@@ -715,7 +1328,7 @@ mod tests {
         let expected_res: Option<String> = None;
         let expected = json!({
             "jsonrpc": "2.0",
-            "result": expected_res,
+            "result": {"context": {"slot": 0}, "value": expected_res},
             "id": 1
         });
         let expected: Response =
@@ -736,7 +1349,7 @@ mod tests {
         ));
         let expected = json!({
             "jsonrpc": "2.0",
-            "result": expected_res,
+            "result": {"context": {"slot": 0}, "value": expected_res},
             "id": 1
         });
         let expected: Response =
@@ -781,6 +1394,40 @@ mod tests {
         assert_eq!(expected, result);
     }
 
+    #[test]
+    fn test_rpc_simulate_transaction() {
+        let bob_pubkey = Pubkey::new_rand();
+        let (io, meta, blockhash, alice, _leader_id) = start_rpc_handler_with_tx(&bob_pubkey);
+
+        let tx = system_transaction::transfer(&alice, &bob_pubkey, 1, blockhash, 0);
+        let req = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"simulateTransaction","params":[{}]}}"#,
+            serde_json::to_string(&serialize(&tx).unwrap()).unwrap()
+        );
+
+        // the working bank isn't frozen yet, there's nothing to simulate on
+        let res = io.handle_request_sync(&req, meta.clone());
+        let result: Value = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_eq!(result["error"]["code"], -32603);
+
+        let bank = meta.request_processor.read().unwrap().bank(None);
+        bank.freeze();
+        let res = io.handle_request_sync(&req, meta);
+        let result: Value = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        let simulation: TransactionSimulation =
+            serde_json::from_value(result["result"].clone()).expect("simulation");
+        assert_eq!(simulation.result, Ok(()));
+        assert_eq!(simulation.account_diffs.len(), 2);
+        assert_eq!(simulation.account_diffs[1].pubkey, bob_pubkey);
+        assert_eq!(simulation.account_diffs[1].post.dif, 21);
+        assert_eq!(simulation.logs.len(), 2);
+
+        // and nothing was committed
+        assert_eq!(bank.get_balance(&bob_pubkey), 20);
+    }
+
     #[test]
     fn test_rpc_send_bad_tx() {
         let exit = Arc::new(AtomicBool::new(false));
@@ -7,6 +7,7 @@ use jsonrpc_derive::rpc;
 use jsonrpc_pubsub::typed::Subscriber;
 use jsonrpc_pubsub::{Session, SubscriptionId};
 use soros_sdk::account::Account;
+use soros_sdk::commitment_config::CommitmentConfig;
 use soros_sdk::pubkey::Pubkey;
 use soros_sdk::signature::Signature;
 use soros_sdk::transaction;
@@ -18,13 +19,19 @@ pub trait RpcSolPubSub {
     type Metadata;
 
     // Get notification every time account data is changed
-    // Accepts pubkey parameter as base-58 encoded string
+    // Accepts pubkey parameter as base-58 encoded string, and an optional commitment
     #[pubsub(
         subscription = "accountNotification",
         subscribe,
         name = "accountSubscribe"
     )]
-    fn account_subscribe(&self, _: Self::Metadata, _: Subscriber<Account>, _: String);
+    fn account_subscribe(
+        &self,
+        _: Self::Metadata,
+        _: Subscriber<Account>,
+        _: String,
+        _: Option<CommitmentConfig>,
+    );
 
     // Unsubscribe from account notification subscription.
     #[pubsub(
@@ -35,13 +42,19 @@ pub trait RpcSolPubSub {
     fn account_unsubscribe(&self, _: Option<Self::Metadata>, _: SubscriptionId) -> Result<bool>;
 
     // Get notification every time account data owned by a particular program is changed
-    // Accepts pubkey parameter as base-58 encoded string
+    // Accepts pubkey parameter as base-58 encoded string, and an optional commitment
     #[pubsub(
         subscription = "programNotification",
         subscribe,
         name = "programSubscribe"
     )]
-    fn program_subscribe(&self, _: Self::Metadata, _: Subscriber<(String, Account)>, _: String);
+    fn program_subscribe(
+        &self,
+        _: Self::Metadata,
+        _: Subscriber<(String, Account)>,
+        _: String,
+        _: Option<CommitmentConfig>,
+    );
 
     // Unsubscribe from account notification subscription.
     #[pubsub(
@@ -52,7 +65,7 @@ pub trait RpcSolPubSub {
     fn program_unsubscribe(&self, _: Option<Self::Metadata>, _: SubscriptionId) -> Result<bool>;
 
     // Get notification when signature is verified
-    // Accepts signature parameter as base-58 encoded string
+    // Accepts signature parameter as base-58 encoded string, and an optional commitment
     #[pubsub(
         subscription = "signatureNotification",
         subscribe,
@@ -63,6 +76,7 @@ pub trait RpcSolPubSub {
         _: Self::Metadata,
         _: Subscriber<Option<transaction::Result<()>>>,
         _: String,
+        _: Option<CommitmentConfig>,
     );
 
     // Unsubscribe from signature notification subscription.
@@ -95,6 +109,7 @@ impl RpcSolPubSub for RpcSolPubSubImpl {
         _meta: Self::Metadata,
         subscriber: Subscriber<Account>,
         pubkey_str: String,
+        commitment: Option<CommitmentConfig>,
     ) {
         let pubkey_vec = bs58::decode(pubkey_str).into_vec().unwrap();
         if pubkey_vec.len() != mem::size_of::<Pubkey>() {
@@ -114,8 +129,12 @@ impl RpcSolPubSub for RpcSolPubSubImpl {
         info!("account_subscribe: account={:?} id={:?}", pubkey, sub_id);
         let sink = subscriber.assign_id(sub_id.clone()).unwrap();
 
-        self.subscriptions
-            .add_account_subscription(&pubkey, &sub_id, &sink)
+        self.subscriptions.add_account_subscription(
+            &pubkey,
+            &sub_id,
+            &sink,
+            commitment.unwrap_or_default().commitment,
+        )
     }
 
     fn account_unsubscribe(
@@ -140,6 +159,7 @@ impl RpcSolPubSub for RpcSolPubSubImpl {
         _meta: Self::Metadata,
         subscriber: Subscriber<(String, Account)>,
         pubkey_str: String,
+        commitment: Option<CommitmentConfig>,
     ) {
         let pubkey_vec = bs58::decode(pubkey_str).into_vec().unwrap();
         if pubkey_vec.len() != mem::size_of::<Pubkey>() {
@@ -159,8 +179,12 @@ impl RpcSolPubSub for RpcSolPubSubImpl {
         info!("program_subscribe: account={:?} id={:?}", pubkey, sub_id);
         let sink = subscriber.assign_id(sub_id.clone()).unwrap();
 
-        self.subscriptions
-            .add_program_subscription(&pubkey, &sub_id, &sink)
+        self.subscriptions.add_program_subscription(
+            &pubkey,
+            &sub_id,
+            &sink,
+            commitment.unwrap_or_default().commitment,
+        )
     }
 
     fn program_unsubscribe(
@@ -185,6 +209,7 @@ impl RpcSolPubSub for RpcSolPubSubImpl {
         _meta: Self::Metadata,
         subscriber: Subscriber<Option<transaction::Result<()>>>,
         signature_str: String,
+        commitment: Option<CommitmentConfig>,
     ) {
         info!("signature_subscribe");
         let signature_vec = bs58::decode(signature_str).into_vec().unwrap();
@@ -203,8 +228,12 @@ impl RpcSolPubSub for RpcSolPubSubImpl {
         let sub_id = SubscriptionId::Number(id as u64);
         let sink = subscriber.assign_id(sub_id.clone()).unwrap();
 
-        self.subscriptions
-            .add_signature_subscription(&signature, &sub_id, &sink);
+        self.subscriptions.add_signature_subscription(
+            &signature,
+            &sub_id,
+            &sink,
+            commitment.unwrap_or_default().commitment,
+        );
     }
 
     fn signature_unsubscribe(
@@ -234,6 +263,7 @@ mod tests {
     use soros_budget_api;
     use soros_budget_api::budget_instruction;
     use soros_runtime::bank::Bank;
+    use soros_sdk::commitment_config::CommitmentLevel;
     use soros_sdk::genesis_block::GenesisBlock;
     use soros_sdk::pubkey::Pubkey;
     use soros_sdk::signature::{Keypair, KeypairUtil};
@@ -249,7 +279,7 @@ mod tests {
         subscriptions: &RpcSubscriptions,
     ) -> transaction::Result<Arc<Bank>> {
         bank.process_transaction(tx)?;
-        subscriptions.notify_subscribers(&bank);
+        subscriptions.notify_subscribers(CommitmentLevel::Recent, &bank);
 
         // Simulate a block boundary
         Ok(Arc::new(Bank::new_from_parent(
@@ -280,7 +310,7 @@ mod tests {
         let session = create_session();
         let (subscriber, _id_receiver, mut receiver) =
             Subscriber::new_test("signatureNotification");
-        rpc.signature_subscribe(session, subscriber, tx.signatures[0].to_string());
+        rpc.signature_subscribe(session, subscriber, tx.signatures[0].to_string(), None);
 
         process_transaction_and_notify(&arc_bank, &tx, &rpc.subscriptions).unwrap();
         sleep(Duration::from_millis(200));
@@ -360,7 +390,12 @@ mod tests {
         let rpc = RpcSolPubSubImpl::default();
         let session = create_session();
         let (subscriber, _id_receiver, mut receiver) = Subscriber::new_test("accountNotification");
-        rpc.account_subscribe(session, subscriber, contract_state.pubkey().to_string());
+        rpc.account_subscribe(
+            session,
+            subscriber,
+            contract_state.pubkey().to_string(),
+            None,
+        );
 
         let tx = system_transaction::create_user_account(
             &alice,
@@ -123,7 +123,8 @@ mod tests {
                 .request_processor
                 .read()
                 .unwrap()
-                .get_balance(&alice.pubkey())
+                .get_balance(&alice.pubkey(), None)
+                .value
         );
         exit.store(true, Ordering::Relaxed);
         rpc_service.join().unwrap();
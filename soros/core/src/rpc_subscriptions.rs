@@ -7,38 +7,42 @@ use jsonrpc_pubsub::typed::Sink;
 use jsonrpc_pubsub::SubscriptionId;
 use soros_runtime::bank::Bank;
 use soros_sdk::account::Account;
+use soros_sdk::commitment_config::CommitmentLevel;
 use soros_sdk::pubkey::Pubkey;
 use soros_sdk::signature::Signature;
 use soros_sdk::transaction;
 use std::collections::HashMap;
-use std::sync::RwLock;
+use std::sync::{Arc, RwLock};
 
-type RpcAccountSubscriptions = RwLock<HashMap<Pubkey, HashMap<SubscriptionId, Sink<Account>>>>;
+type RpcAccountSubscriptions =
+    RwLock<HashMap<Pubkey, HashMap<SubscriptionId, (Sink<Account>, CommitmentLevel)>>>;
 type RpcProgramSubscriptions =
-    RwLock<HashMap<Pubkey, HashMap<SubscriptionId, Sink<(String, Account)>>>>;
-type RpcSignatureSubscriptions =
-    RwLock<HashMap<Signature, HashMap<SubscriptionId, Sink<Option<transaction::Result<()>>>>>>;
+    RwLock<HashMap<Pubkey, HashMap<SubscriptionId, (Sink<(String, Account)>, CommitmentLevel)>>>;
+type RpcSignatureSubscriptions = RwLock<
+    HashMap<
+        Signature,
+        HashMap<SubscriptionId, (Sink<Option<transaction::Result<()>>>, CommitmentLevel)>,
+    >,
+>;
 
 fn add_subscription<K, S>(
-    subscriptions: &mut HashMap<K, HashMap<SubscriptionId, Sink<S>>>,
+    subscriptions: &mut HashMap<K, HashMap<SubscriptionId, (Sink<S>, CommitmentLevel)>>,
     hashmap_key: &K,
     sub_id: &SubscriptionId,
     sink: &Sink<S>,
+    commitment_level: CommitmentLevel,
 ) where
     K: Eq + Hash + Clone + Copy,
     S: Clone,
 {
-    if let Some(current_hashmap) = subscriptions.get_mut(hashmap_key) {
-        current_hashmap.insert(sub_id.clone(), sink.clone());
-        return;
-    }
-    let mut hashmap = HashMap::new();
-    hashmap.insert(sub_id.clone(), sink.clone());
-    subscriptions.insert(*hashmap_key, hashmap);
+    subscriptions
+        .entry(*hashmap_key)
+        .or_insert_with(HashMap::new)
+        .insert(sub_id.clone(), (sink.clone(), commitment_level));
 }
 
 fn remove_subscription<K, S>(
-    subscriptions: &mut HashMap<K, HashMap<SubscriptionId, Sink<S>>>,
+    subscriptions: &mut HashMap<K, HashMap<SubscriptionId, (Sink<S>, CommitmentLevel)>>,
     sub_id: &SubscriptionId,
 ) -> bool
 where
@@ -62,6 +66,8 @@ pub struct RpcSubscriptions {
     pub account_subscriptions: RpcAccountSubscriptions,
     pub program_subscriptions: RpcProgramSubscriptions,
     signature_subscriptions: RpcSignatureSubscriptions,
+    /// Newest slot whose changes were sent to the subscribers of each commitment level
+    last_notified_slots: RwLock<HashMap<CommitmentLevel, u64>>,
 }
 
 impl Default for RpcSubscriptions {
@@ -70,39 +76,68 @@ impl Default for RpcSubscriptions {
             account_subscriptions: RpcAccountSubscriptions::default(),
             program_subscriptions: RpcProgramSubscriptions::default(),
             signature_subscriptions: RpcSignatureSubscriptions::default(),
+            last_notified_slots: RwLock::new(HashMap::new()),
         }
     }
 }
 
 impl RpcSubscriptions {
-    pub fn check_account(&self, pubkey: &Pubkey, account: &Account) {
+    pub fn check_account(
+        &self,
+        pubkey: &Pubkey,
+        account: &Account,
+        commitment_level: CommitmentLevel,
+    ) {
         let subscriptions = self.account_subscriptions.read().unwrap();
         if let Some(hashmap) = subscriptions.get(pubkey) {
-            for (_bank_sub_id, sink) in hashmap.iter() {
-                sink.notify(Ok(account.clone())).wait().unwrap();
+            for (_bank_sub_id, (sink, level)) in hashmap.iter() {
+                if *level == commitment_level {
+                    sink.notify(Ok(account.clone())).wait().unwrap();
+                }
             }
         }
     }
 
-    pub fn check_program(&self, program_id: &Pubkey, pubkey: &Pubkey, account: &Account) {
-        let subscriptions = self.program_subscriptions.write().unwrap();
+    pub fn check_program(
+        &self,
+        program_id: &Pubkey,
+        pubkey: &Pubkey,
+        account: &Account,
+        commitment_level: CommitmentLevel,
+    ) {
+        let subscriptions = self.program_subscriptions.read().unwrap();
         if let Some(hashmap) = subscriptions.get(program_id) {
-            for (_bank_sub_id, sink) in hashmap.iter() {
-                sink.notify(Ok((bs58::encode(pubkey).into_string(), account.clone())))
-                    .wait()
-                    .unwrap();
+            for (_bank_sub_id, (sink, level)) in hashmap.iter() {
+                if *level == commitment_level {
+                    sink.notify(Ok((bs58::encode(pubkey).into_string(), account.clone())))
+                        .wait()
+                        .unwrap();
+                }
             }
         }
     }
 
-    pub fn check_signature(&self, signature: &Signature, bank_error: &transaction::Result<()>) {
+    /// Notify the signature's subscribers at `commitment_level`, a signature is only
+    /// notified once so their subscriptions end here
+    pub fn check_signature(
+        &self,
+        signature: &Signature,
+        bank_error: &transaction::Result<()>,
+        commitment_level: CommitmentLevel,
+    ) {
         let mut subscriptions = self.signature_subscriptions.write().unwrap();
-        if let Some(hashmap) = subscriptions.get(signature) {
-            for (_bank_sub_id, sink) in hashmap.iter() {
+        if let Some(hashmap) = subscriptions.get_mut(signature) {
+            hashmap.retain(|_bank_sub_id, (sink, level)| {
+                if *level != commitment_level {
+                    return true;
+                }
                 sink.notify(Ok(Some(bank_error.clone()))).wait().unwrap();
+                false
+            });
+            if hashmap.is_empty() {
+                subscriptions.remove(&signature);
             }
         }
-        subscriptions.remove(&signature);
     }
 
     pub fn add_account_subscription(
@@ -110,9 +145,10 @@ impl RpcSubscriptions {
         pubkey: &Pubkey,
         sub_id: &SubscriptionId,
         sink: &Sink<Account>,
+        commitment_level: CommitmentLevel,
     ) {
         let mut subscriptions = self.account_subscriptions.write().unwrap();
-        add_subscription(&mut subscriptions, pubkey, sub_id, sink);
+        add_subscription(&mut subscriptions, pubkey, sub_id, sink, commitment_level);
     }
 
     pub fn remove_account_subscription(&self, id: &SubscriptionId) -> bool {
@@ -125,9 +161,16 @@ impl RpcSubscriptions {
         program_id: &Pubkey,
         sub_id: &SubscriptionId,
         sink: &Sink<(String, Account)>,
+        commitment_level: CommitmentLevel,
     ) {
         let mut subscriptions = self.program_subscriptions.write().unwrap();
-        add_subscription(&mut subscriptions, program_id, sub_id, sink);
+        add_subscription(
+            &mut subscriptions,
+            program_id,
+            sub_id,
+            sink,
+            commitment_level,
+        );
     }
 
     pub fn remove_program_subscription(&self, id: &SubscriptionId) -> bool {
@@ -140,9 +183,16 @@ impl RpcSubscriptions {
         signature: &Signature,
         sub_id: &SubscriptionId,
         sink: &Sink<Option<transaction::Result<()>>>,
+        commitment_level: CommitmentLevel,
     ) {
         let mut subscriptions = self.signature_subscriptions.write().unwrap();
-        add_subscription(&mut subscriptions, signature, sub_id, sink);
+        add_subscription(
+            &mut subscriptions,
+            signature,
+            sub_id,
+            sink,
+            commitment_level,
+        );
     }
 
     pub fn remove_signature_subscription(&self, id: &SubscriptionId) -> bool {
@@ -150,37 +200,65 @@ impl RpcSubscriptions {
         remove_subscription(&mut subscriptions, id)
     }
 
-    /// Notify subscribers of changes to any accounts or new signatures since
-    /// the bank's last checkpoint.
-    pub fn notify_subscribers(&self, bank: &Bank) {
+    /// Notify the subscribers at `commitment_level` of changes to any accounts or new
+    /// signatures in `bank`. A confirmed or rooted bank can skip over slots, the changes of
+    /// its ancestors that haven't been notified at that level yet are sent first.
+    pub fn notify_subscribers(&self, commitment_level: CommitmentLevel, bank: &Arc<Bank>) {
+        let banks = match commitment_level {
+            CommitmentLevel::Recent => vec![bank.clone()],
+            CommitmentLevel::Confirmed | CommitmentLevel::Root => {
+                let mut last_notified_slots = self.last_notified_slots.write().unwrap();
+                let last_notified_slot = last_notified_slots.get(&commitment_level).cloned();
+                if last_notified_slot.map_or(false, |slot| bank.slot() <= slot) {
+                    return;
+                }
+                last_notified_slots.insert(commitment_level, bank.slot());
+
+                let is_unnotified = |parent: &Arc<Bank>| {
+                    last_notified_slot.map_or(false, |slot| parent.slot() > slot)
+                };
+                let mut banks: Vec<_> = bank
+                    .parents()
+                    .into_iter()
+                    .take_while(is_unnotified)
+                    .collect();
+                banks.reverse();
+                banks.push(bank.clone());
+                banks
+            }
+        };
+
         let pubkeys: Vec<_> = {
             let subs = self.account_subscriptions.read().unwrap();
             subs.keys().cloned().collect()
         };
-        for pubkey in &pubkeys {
-            if let Some(account) = &bank.get_account_modified_since_parent(pubkey) {
-                self.check_account(pubkey, account);
-            }
-        }
-
         let programs: Vec<_> = {
             let subs = self.program_subscriptions.read().unwrap();
             subs.keys().cloned().collect()
         };
-        for program_id in &programs {
-            let accounts = &bank.get_program_accounts_modified_since_parent(program_id);
-            for (pubkey, account) in accounts.iter() {
-                self.check_program(program_id, pubkey, account);
+        for bank in &banks {
+            for pubkey in &pubkeys {
+                if let Some(account) = &bank.get_account_modified_since_parent(pubkey) {
+                    self.check_account(pubkey, account, commitment_level);
+                }
+            }
+            for program_id in &programs {
+                let accounts = &bank.get_program_accounts_modified_since_parent(program_id);
+                for (pubkey, account) in accounts.iter() {
+                    self.check_program(program_id, pubkey, account, commitment_level);
+                }
             }
         }
 
+        // The status cache of the newest bank covers its ancestors' signatures as well
         let signatures: Vec<_> = {
             let subs = self.signature_subscriptions.read().unwrap();
             subs.keys().cloned().collect()
         };
         for signature in &signatures {
-            let status = bank.get_signature_status(signature).unwrap();
-            self.check_signature(signature, &status);
+            if let Some(status) = bank.get_signature_status(signature) {
+                self.check_signature(signature, &status, commitment_level);
+            }
         }
     }
 }
@@ -217,7 +295,12 @@ mod tests {
         let sub_id = SubscriptionId::Number(0 as u64);
         let sink = subscriber.assign_id(sub_id.clone()).unwrap();
         let subscriptions = RpcSubscriptions::default();
-        subscriptions.add_account_subscription(&alice.pubkey(), &sub_id, &sink);
+        subscriptions.add_account_subscription(
+            &alice.pubkey(),
+            &sub_id,
+            &sink,
+            CommitmentLevel::Recent,
+        );
 
         assert!(subscriptions
             .account_subscriptions
@@ -226,7 +309,7 @@ mod tests {
             .contains_key(&alice.pubkey()));
 
         let account = bank.get_account(&alice.pubkey()).unwrap();
-        subscriptions.check_account(&alice.pubkey(), &account);
+        subscriptions.check_account(&alice.pubkey(), &account, CommitmentLevel::Recent);
         let string = transport_receiver.poll();
         if let Async::Ready(Some(response)) = string.unwrap() {
             let expected = format!(r#"{{"jsonrpc":"2.0","method":"accountNotification","params":{{"result":{{"data":[0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],"dif":1,"executable":false,"owner":[2,203,81,223,225,24,34,35,203,214,138,130,144,208,35,77,63,16,87,51,47,198,115,123,98,188,19,160,0,0,0,0]}},"subscription":0}}}}"#);    
@@ -263,7 +346,12 @@ mod tests {
         let sub_id = SubscriptionId::Number(0 as u64);
         let sink = subscriber.assign_id(sub_id.clone()).unwrap();
         let subscriptions = RpcSubscriptions::default();
-        subscriptions.add_program_subscription(&soros_budget_api::id(), &sub_id, &sink);
+        subscriptions.add_program_subscription(
+            &soros_budget_api::id(),
+            &sub_id,
+            &sink,
+            CommitmentLevel::Recent,
+        );
 
         assert!(subscriptions
             .program_subscriptions
@@ -272,7 +360,12 @@ mod tests {
             .contains_key(&soros_budget_api::id()));
 
         let account = bank.get_account(&alice.pubkey()).unwrap();
-        subscriptions.check_program(&soros_budget_api::id(), &alice.pubkey(), &account);
+        subscriptions.check_program(
+            &soros_budget_api::id(),
+            &alice.pubkey(),
+            &account,
+            CommitmentLevel::Recent,
+        );
         let string = transport_receiver.poll();
         if let Async::Ready(Some(response)) = string.unwrap() {
             let expected = format!(r#"{{"jsonrpc":"2.0","method":"programNotification","params":{{"result":["{:?}",{{"data":[0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],"dif":1,"executable":false,"owner":[2,203,81,223,225,24,34,35,203,214,138,130,144,208,35,77,63,16,87,51,47,198,115,123,98,188,19,160,0,0,0,0]}}],"subscription":0}}}}"#, alice.pubkey());
@@ -301,7 +394,12 @@ mod tests {
         let sub_id = SubscriptionId::Number(0 as u64);
         let sink = subscriber.assign_id(sub_id.clone()).unwrap();
         let subscriptions = RpcSubscriptions::default();
-        subscriptions.add_signature_subscription(&signature, &sub_id, &sink);
+        subscriptions.add_signature_subscription(
+            &signature,
+            &sub_id,
+            &sink,
+            CommitmentLevel::Recent,
+        );
 
         assert!(subscriptions
             .signature_subscriptions
@@ -309,7 +407,7 @@ mod tests {
             .unwrap()
             .contains_key(&signature));
 
-        subscriptions.check_signature(&signature, &Ok(()));
+        subscriptions.check_signature(&signature, &Ok(()), CommitmentLevel::Recent);
         let string = transport_receiver.poll();
         if let Async::Ready(Some(response)) = string.unwrap() {
             let expected_res: Option<transaction::Result<()>> = Some(Ok(()));
@@ -326,4 +424,53 @@ mod tests {
             .unwrap()
             .contains_key(&signature));
     }
+
+    #[test]
+    fn test_notify_subscribers_commitment() {
+        let (genesis_block, mint_keypair) = GenesisBlock::new(100);
+        let bank0 = Arc::new(Bank::new(&genesis_block));
+        let alice = Keypair::new();
+        let bank1 = Arc::new(Bank::new_from_parent(&bank0, &Pubkey::default(), 1));
+        bank1.transfer(10, &mint_keypair, &alice.pubkey()).unwrap();
+        let bank2 = Arc::new(Bank::new_from_parent(&bank1, &Pubkey::default(), 2));
+        bank2.transfer(20, &mint_keypair, &alice.pubkey()).unwrap();
+
+        let (subscriber, _id_receiver, mut transport_receiver) =
+            Subscriber::new_test("accountNotification");
+        let sub_id = SubscriptionId::Number(0 as u64);
+        let sink = subscriber.assign_id(sub_id.clone()).unwrap();
+        let subscriptions = RpcSubscriptions::default();
+        subscriptions.add_account_subscription(
+            &alice.pubkey(),
+            &sub_id,
+            &sink,
+            CommitmentLevel::Root,
+        );
+
+        // Other levels don't reach the subscription
+        subscriptions.notify_subscribers(CommitmentLevel::Recent, &bank2);
+        subscriptions.notify_subscribers(CommitmentLevel::Confirmed, &bank2);
+        assert_eq!(transport_receiver.poll(), Ok(Async::NotReady));
+
+        subscriptions.notify_subscribers(CommitmentLevel::Root, &bank1);
+        let dif = |response: String| {
+            let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+            response["params"]["result"]["dif"].as_u64().unwrap()
+        };
+        if let Async::Ready(Some(response)) = transport_receiver.poll().unwrap() {
+            assert_eq!(dif(response), 10);
+        } else {
+            panic!("expected a notification for slot 1");
+        }
+
+        // Slot 1 was already notified, the root moving to slot 2 only sends slot 2
+        subscriptions.notify_subscribers(CommitmentLevel::Root, &bank2);
+        subscriptions.notify_subscribers(CommitmentLevel::Root, &bank2);
+        if let Async::Ready(Some(response)) = transport_receiver.poll().unwrap() {
+            assert_eq!(dif(response), 30);
+        } else {
+            panic!("expected a notification for slot 2");
+        }
+        assert_eq!(transport_receiver.poll(), Ok(Async::NotReady));
+    }
 }
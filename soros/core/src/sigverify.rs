@@ -1,7 +1,7 @@
 //! The `sigverify` module provides digital signature verification functions.
 //! By default, signatures are verified in parallel using all available CPU
-//! cores.  When `--features=cuda` is enabled, signature verification is
-//! offloaded to the GPU.
+//! cores, in randomized batches once there are enough of them.  When
+//! `--features=cuda` is enabled, signature verification is offloaded to the GPU.
 //!
 
 use crate::packet::{Packet, Packets};
@@ -13,12 +13,21 @@ use soros_sdk::signature::Signature;
 #[cfg(test)]
 use soros_sdk::transaction::Transaction;
 use std::mem::size_of;
+use std::ops::Range;
 
 type TxOffsets = (Vec<u32>, Vec<u32>, Vec<u32>, Vec<u32>, Vec<Vec<u32>>);
 
 // The serialized size of Message::num_required_signatures.
 const NUM_REQUIRED_SIGNATURES_SIZE: usize = 1;
 
+// Number of packets whose signatures are checked by one batch verification.  A failed batch is
+// checked again one packet at a time, so this trades the speedup of batching against what a bad
+// signature costs.
+const BATCH_VERIFY_CHUNK_SIZE: usize = 64;
+
+// Below this many packets batch verification doesn't pay off
+const BATCH_VERIFY_MIN_PACKETS: usize = 16;
+
 #[cfg(feature = "cuda")]
 #[repr(C)]
 struct Elems {
@@ -66,39 +75,101 @@ pub fn init() {
     // stub
 }
 
-fn verify_packet(packet: &Packet) -> u8 {
-    use soros_sdk::pubkey::Pubkey;
-    use soros_sdk::signature::Signature;
-
+/// Where in `packet` its message and each of its signatures and public keys are, or `None` if
+/// they don't fit in the packet
+fn packet_signatures(packet: &Packet) -> Option<(Range<usize>, Vec<(Range<usize>, Range<usize>)>)> {
     let (sig_len, sig_start, msg_start, pubkey_start) = get_packet_offsets(packet, 0);
-    let mut sig_start = sig_start as usize;
-    let mut pubkey_start = pubkey_start as usize;
+    let sig_start = sig_start as usize;
+    let pubkey_start = pubkey_start as usize;
     let msg_start = msg_start as usize;
 
     if packet.meta.size <= msg_start {
-        return 0;
+        return None;
     }
 
-    let msg_end = packet.meta.size;
-    for _ in 0..sig_len {
-        let pubkey_end = pubkey_start as usize + size_of::<Pubkey>();
-        let sig_end = sig_start as usize + size_of::<Signature>();
+    (0..sig_len as usize)
+        .map(|i| {
+            let sig_start = sig_start + i * size_of::<Signature>();
+            let pubkey_start = pubkey_start + i * size_of::<Pubkey>();
+            let sig_end = sig_start + size_of::<Signature>();
+            let pubkey_end = pubkey_start + size_of::<Pubkey>();
+
+            if pubkey_end >= packet.meta.size || sig_end >= packet.meta.size {
+                None
+            } else {
+                Some((sig_start..sig_end, pubkey_start..pubkey_end))
+            }
+        })
+        .collect::<Option<Vec<_>>>()
+        .map(|signatures| (msg_start..packet.meta.size, signatures))
+}
+
+fn verify_packet(packet: &Packet) -> u8 {
+    let (msg, signatures) = match packet_signatures(packet) {
+        Some(packet_signatures) => packet_signatures,
+        None => return 0,
+    };
 
-        if pubkey_end >= packet.meta.size || sig_end >= packet.meta.size {
-            return 0;
-        }
+    let verified = signatures.into_iter().all(|(sig, pubkey)| {
+        Signature::new(&packet.data[sig]).verify(&packet.data[pubkey], &packet.data[msg.clone()])
+    });
+    verified as u8
+}
+
+/// Message, signature and public key of each of a packet's signatures
+type PacketKeys<'a> = Vec<(&'a [u8], ed25519_dalek::Signature, ed25519_dalek::PublicKey)>;
+
+/// `None` if the packet's signatures can't be found or parsed
+fn packet_keys(packet: &Packet) -> Option<PacketKeys> {
+    let (msg, packet_signatures) = packet_signatures(packet)?;
+    packet_signatures
+        .into_iter()
+        .map(|(sig, pubkey)| {
+            let signature = ed25519_dalek::Signature::from_bytes(&packet.data[sig]).ok()?;
+            let pubkey = ed25519_dalek::PublicKey::from_bytes(&packet.data[pubkey]).ok()?;
+            Some((&packet.data[msg.clone()], signature, pubkey))
+        })
+        .collect()
+}
+
+/// Verify the signatures of `packets` all at once.  If that fails the packets are split in
+/// half and each half is verified the same way, so the few bad packets are narrowed down
+/// without verifying every good packet on its own.
+fn verify_packets_batched(packets: &[Packet]) -> Vec<u8> {
+    let keys: Vec<_> = packets.iter().map(packet_keys).collect();
+    let mut rv = vec![0u8; packets.len()];
+    verify_keys_bisected(packets, &keys, &mut rv);
+    rv
+}
+
+fn verify_keys_bisected(packets: &[Packet], keys: &[Option<PacketKeys>], rv: &mut [u8]) {
+    let mut messages = vec![];
+    let mut signatures = vec![];
+    let mut pubkeys = vec![];
+    for (message, signature, pubkey) in keys.iter().flatten().flatten() {
+        messages.push(*message);
+        signatures.push(*signature);
+        pubkeys.push(*pubkey);
+    }
 
-        let signature = Signature::new(&packet.data[sig_start..sig_end]);
-        if !signature.verify(
-            &packet.data[pubkey_start..pubkey_end],
-            &packet.data[msg_start..msg_end],
-        ) {
-            return 0;
+    if messages.is_empty() || ed25519_dalek::verify_batch(&messages, &signatures, &pubkeys).is_ok()
+    {
+        for (verified, packet_keys) in rv.iter_mut().zip(keys) {
+            *verified = packet_keys.is_some() as u8;
+        }
+        return;
+    }
+    if packets.len() == 1 {
+        if keys[0].is_some() {
+            rv[0] = verify_packet(&packets[0]);
         }
-        pubkey_start += size_of::<Pubkey>();
-        sig_start += size_of::<Signature>();
+        return;
     }
-    1
+    inc_new_counter_info!("ed25519_verify_cpu_batch-fallback", packets.len());
+    let mid = packets.len() / 2;
+    let (rv_left, rv_right) = rv.split_at_mut(mid);
+    verify_keys_bisected(&packets[..mid], &keys[..mid], rv_left);
+    verify_keys_bisected(&packets[mid..], &keys[mid..], rv_right);
 }
 
 fn batch_size(batches: &[Packets]) -> usize {
@@ -107,7 +178,11 @@ fn batch_size(batches: &[Packets]) -> usize {
 
 #[cfg(not(feature = "cuda"))]
 pub fn ed25519_verify(batches: &[Packets]) -> Vec<Vec<u8>> {
-    ed25519_verify_cpu(batches)
+    if batch_size(batches) < BATCH_VERIFY_MIN_PACKETS {
+        ed25519_verify_cpu(batches)
+    } else {
+        ed25519_verify_cpu_batch(batches)
+    }
 }
 
 pub fn get_packet_offsets(packet: &Packet, current_offset: u32) -> (u32, u32, u32, u32) {
@@ -184,6 +259,26 @@ pub fn ed25519_verify_cpu(batches: &[Packets]) -> Vec<Vec<u8>> {
     rv
 }
 
+/// Like `ed25519_verify_cpu`, but checks the signatures of `BATCH_VERIFY_CHUNK_SIZE` packets
+/// at a time with randomized batch verification
+pub fn ed25519_verify_cpu_batch(batches: &[Packets]) -> Vec<Vec<u8>> {
+    use rayon::prelude::*;
+    let count = batch_size(batches);
+    debug!("CPU batch ECDSA for {}", count);
+    let rv = batches
+        .into_par_iter()
+        .map(|p| {
+            p.packets
+                .par_chunks(BATCH_VERIFY_CHUNK_SIZE)
+                .map(verify_packets_batched)
+                .collect::<Vec<_>>()
+                .concat()
+        })
+        .collect();
+    inc_new_counter_info!("ed25519_verify_cpu_batch", count);
+    rv
+}
+
 pub fn ed25519_verify_disabled(batches: &[Packets]) -> Vec<Vec<u8>> {
     use rayon::prelude::*;
     let count = batch_size(batches);
@@ -490,4 +585,52 @@ mod tests {
     fn test_verify_fail() {
         test_verify_n(5, true);
     }
+
+    #[test]
+    fn test_verify_fail_batched() {
+        test_verify_n(71, true);
+    }
+
+    #[test]
+    fn test_verify_cpu_batch_fallback() {
+        let packet = sigverify::make_packet_from_transaction(test_tx());
+        let mut batches = generate_packet_vec(&packet, 100, 2);
+
+        // A bad signature, and a packet too short for its signature, in the same chunk
+        let mut bad_packet = packet.clone();
+        bad_packet.data[20] = bad_packet.data[20].wrapping_add(10);
+        batches[0].packets[3] = bad_packet;
+        batches[0].packets[10].meta.size = 40;
+        // A signature that can't be parsed at all
+        batches[1].packets[99].data[64] = 0xff;
+
+        let mut expected = vec![vec![1u8; 100]; 2];
+        expected[0][3] = 0;
+        expected[0][10] = 0;
+        expected[1][99] = 0;
+        assert_eq!(sigverify::ed25519_verify_cpu_batch(&batches), expected);
+        assert_eq!(sigverify::ed25519_verify_cpu(&batches), expected);
+    }
+
+    #[test]
+    fn test_verify_cpu_batch_bisect() {
+        let packet = sigverify::make_packet_from_transaction(test_tx());
+        let mut bad_packet = packet.clone();
+        bad_packet.data[20] = bad_packet.data[20].wrapping_add(10);
+
+        // Bad packets at the edges of chunks and of the halves they're split into, and a
+        // whole half of bad packets
+        let mut batches = generate_packet_vec(&packet, 200, 1);
+        let mut expected = vec![vec![1u8; 200]];
+        let bad: Vec<usize> = vec![0, 31, 32, 63, 64, 128]
+            .into_iter()
+            .chain(160..192)
+            .collect();
+        for i in bad {
+            batches[0].packets[i] = bad_packet.clone();
+            expected[0][i] = 0;
+        }
+        assert_eq!(sigverify::ed25519_verify_cpu_batch(&batches), expected);
+        assert_eq!(sigverify::ed25519_verify_cpu(&batches), expected);
+    }
 }
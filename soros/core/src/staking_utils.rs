@@ -50,13 +50,15 @@ pub fn delegated_stakes_at_epoch(bank: &Bank, epoch_height: u64) -> Option<HashM
     node_staked_vote_states.map(to_delegated_stakes)
 }
 
-/// Collect the node account balance and vote states for nodes have non-zero balance in
-/// their corresponding staking accounts
+/// Collect the node account stake and vote states for nodes have non-zero stake, a vote
+/// account's stake is its balance plus the effective stake delegated to it
 fn node_staked_accounts(bank: &Bank) -> impl Iterator<Item = (Pubkey, u64, Account)> {
+    let effective_stakes = bank.effective_stakes();
     bank.vote_accounts()
         .into_iter()
-        .filter_map(|(account_id, account)| {
-            filter_zero_balances(&account).map(|stake| (account_id, stake, account))
+        .filter_map(move |(account_id, account)| {
+            filter_zero_stakes(&account_id, &account, &effective_stakes)
+                .map(|stake| (account_id, stake, account))
         })
 }
 
@@ -64,11 +66,15 @@ pub fn node_staked_accounts_at_epoch(
     bank: &Bank,
     epoch_height: u64,
 ) -> Option<impl Iterator<Item = (&Pubkey, u64, &Account)>> {
-    bank.epoch_vote_accounts(epoch_height).map(|epoch_state| {
+    let effective_stakes = bank
+        .epoch_effective_stakes(epoch_height)
+        .unwrap_or_default();
+    bank.epoch_vote_accounts(epoch_height).map(move |epoch_state| {
         epoch_state
             .into_iter()
-            .filter_map(|(account_id, account)| {
-                filter_zero_balances(account).map(|stake| (account_id, stake, account))
+            .filter_map(move |(account_id, account)| {
+                filter_zero_stakes(account_id, account, &effective_stakes)
+                    .map(|stake| (account_id, stake, account))
             })
             .filter(|(account_id, _, account)| filter_no_delegate(account_id, account))
     })
@@ -80,10 +86,15 @@ fn filter_no_delegate(account_id: &Pubkey, account: &Account) -> bool {
         .unwrap_or(false)
 }
 
-fn filter_zero_balances(account: &Account) -> Option<u64> {
-    let balance = Bank::read_balance(&account);
-    if balance > 0 {
-        Some(balance)
+fn filter_zero_stakes(
+    account_id: &Pubkey,
+    account: &Account,
+    effective_stakes: &HashMap<Pubkey, u64>,
+) -> Option<u64> {
+    let stake =
+        Bank::read_balance(&account) + effective_stakes.get(account_id).cloned().unwrap_or(0);
+    if stake > 0 {
+        Some(stake)
     } else {
         None
     }
@@ -4,10 +4,12 @@
 
 use crate::bank_forks::BankForks;
 use crate::blocktree::Blocktree;
+use crate::chacha::{chacha_cbc_encrypt_segment_prefix, read_ledger_segment, CHACHA_BLOCK_SIZE};
 #[cfg(all(feature = "chacha", feature = "cuda"))]
 use crate::chacha_cuda::chacha_cbc_encrypt_file_many_keys;
 use crate::cluster_info::ClusterInfo;
 use crate::entry::{Entry, EntryReceiver};
+use crate::replicator::sample_buffer;
 use crate::result::{Error, Result};
 use crate::service::Service;
 use bincode::deserialize;
@@ -19,8 +21,11 @@ use soros_sdk::pubkey::Pubkey;
 use soros_sdk::signature::{Keypair, KeypairUtil, Signature};
 use soros_sdk::system_instruction;
 use soros_sdk::transaction::Transaction;
+use soros_storage_api::storage_contract::{
+    CheckedProof, Proof, ProofStatus, STORAGE_ACCOUNT_SPACE,
+};
 use soros_storage_api::storage_instruction::{self, StorageInstruction};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::io;
 use std::mem::size_of;
 use std::net::UdpSocket;
@@ -35,12 +40,15 @@ use std::time::Duration;
 type StorageResults = Vec<Hash>;
 type StorageKeys = Vec<u8>;
 type ReplicatorMap = Vec<HashSet<Pubkey>>;
+// Replicator proofs checked by segment, waiting to be submitted for rewards
+type CheckedProofs = HashMap<usize, HashMap<Pubkey, CheckedProof>>;
 
 #[derive(Default)]
 pub struct StorageStateInner {
     storage_results: StorageResults,
     storage_keys: StorageKeys,
     replicator_map: ReplicatorMap,
+    checked_proofs: CheckedProofs,
     storage_blockhash: Hash,
     entry_height: u64,
 }
@@ -50,6 +58,30 @@ pub struct StorageState {
     state: Arc<RwLock<StorageStateInner>>,
 }
 
+/// What checking replicator proofs reads and computes, so a segment is read once for all of
+/// its proofs and a resubmitted proof isn't encrypted again
+#[derive(Default)]
+struct ProofCache {
+    /// The segment read last, by the entry height it starts at
+    segment: Option<(u64, Vec<Vec<u8>>)>,
+    /// Samples of encrypted segments by the entry height they start at and the replicator
+    /// signature they were encrypted with, `None` if the samples couldn't be taken
+    samples: HashMap<(u64, Signature), Option<Hash>>,
+}
+
+impl ProofCache {
+    fn segment(&mut self, blocktree: &Arc<Blocktree>, entry_height: u64) -> io::Result<&[Vec<u8>]> {
+        let cached = match self.segment {
+            Some((height, _)) => height == entry_height,
+            None => false,
+        };
+        if !cached {
+            self.segment = Some((entry_height, read_ledger_segment(blocktree, entry_height)?));
+        }
+        Ok(&self.segment.as_ref().unwrap().1)
+    }
+}
+
 pub struct StorageStage {
     t_storage_mining_verifier: JoinHandle<()>,
     t_storage_create_accounts: JoinHandle<()>,
@@ -67,6 +99,8 @@ const NUM_IDENTITIES: usize = 1024;
 pub const NUM_STORAGE_SAMPLES: usize = 4;
 pub const ENTRIES_PER_SEGMENT: u64 = 16;
 const KEY_SIZE: usize = 64;
+/// Most samples of replicator proofs kept by `ProofCache`
+const MAX_CACHED_SAMPLES: usize = 1024;
 
 type InstructionSender = Sender<Instruction>;
 
@@ -74,6 +108,17 @@ pub fn get_segment_from_entry(entry_height: u64) -> u64 {
     entry_height / ENTRIES_PER_SEGMENT
 }
 
+/// Offsets of the hash sized samples of an encrypted ledger segment that make up a mining
+/// proof, seeded by the replicator's signature so validators can take the same samples
+pub fn get_sampling_offsets(signature: &[u8], num_chacha_blocks: usize) -> Vec<u64> {
+    let mut seed = [0u8; 32];
+    seed.copy_from_slice(&signature[..32]);
+    let mut rng = ChaChaRng::from_seed(seed);
+    (0..NUM_STORAGE_SAMPLES)
+        .map(|_| rng.gen_range(0, num_chacha_blocks) as u64)
+        .collect()
+}
+
 fn get_identity_index_from_signature(key: &Signature) -> usize {
     let rkey = key.as_ref();
     let mut res: usize = (rkey[0] as usize)
@@ -94,6 +139,7 @@ impl StorageState {
             storage_keys,
             storage_results,
             replicator_map,
+            checked_proofs: HashMap::new(),
             entry_height: 0,
             storage_blockhash: Hash::default(),
         };
@@ -166,6 +212,7 @@ impl StorageStage {
                 let mut poh_height = 0;
                 let mut current_key = 0;
                 let mut entry_height = entry_height;
+                let mut proof_cache = ProofCache::default();
                 loop {
                     if let Some(ref some_blocktree) = blocktree {
                         if let Err(e) = Self::process_entries(
@@ -178,6 +225,7 @@ impl StorageStage {
                             &mut current_key,
                             storage_rotate_count,
                             &instruction_sender,
+                            &mut proof_cache,
                         ) {
                             match e {
                                 Error::RecvTimeoutError(RecvTimeoutError::Disconnected) => break,
@@ -254,12 +302,11 @@ impl StorageStage {
         let mut signing_keys = vec![];
         if let Some(account) = account_to_create {
             if working_bank.get_account(&account).is_none() {
-                // TODO the account space needs to be well defined somewhere
                 let create_instruction = system_instruction::create_account(
                     &keypair.pubkey(),
                     &storage_keypair.pubkey(),
                     1,
-                    1024 * 4,
+                    STORAGE_ACCOUNT_SPACE,
                     &soros_storage_api::id(),
                 );
                 instructions.push(create_instruction);
@@ -296,6 +343,17 @@ impl StorageStage {
         );
         instruction_sender.send(ix)?;
 
+        // Submit the replicator proofs checked since the last crossing
+        let checked_proofs: Vec<_> = state.write().unwrap().checked_proofs.drain().collect();
+        for (segment_index, proofs) in checked_proofs {
+            let ix = storage_instruction::proof_validation(
+                &keypair.pubkey(),
+                segment_index as u64 * ENTRIES_PER_SEGMENT,
+                proofs.into_iter().map(|(_, proof)| proof).collect(),
+            );
+            instruction_sender.send(ix)?;
+        }
+
         seed.copy_from_slice(&signature.to_bytes()[..32]);
 
         let mut rng = ChaChaRng::from_seed(seed);
@@ -349,11 +407,59 @@ impl StorageStage {
                 }
             }
         }
-        // TODO: bundle up mining submissions from replicators
-        // and submit them in a tx to the leader to get reward.
         Ok(())
     }
 
+    /// Check a replicator's proof by encrypting the segment with the replicator's
+    /// signature as the IV, the same way the replicator did, and taking the same samples.
+    /// Only as much of the segment as the samples cover is encrypted.
+    fn verify_mining_proof(
+        blocktree: &Arc<Blocktree>,
+        proof_cache: &mut ProofCache,
+        entry_height: u64,
+        signature: &Signature,
+        sha_state: &Hash,
+    ) -> ProofStatus {
+        let key = (entry_height, *signature);
+        let sample = match proof_cache.samples.get(&key) {
+            Some(sample) => *sample,
+            None => {
+                let segment = match proof_cache.segment(blocktree, entry_height) {
+                    Ok(segment) => segment,
+                    Err(e) => {
+                        info!("error reading segment: {:?}", e);
+                        return ProofStatus::Skipped;
+                    }
+                };
+                let segment_len: usize = segment.iter().map(Vec::len).sum();
+                let num_chacha_blocks = segment_len / CHACHA_BLOCK_SIZE;
+                if num_chacha_blocks == 0 {
+                    // nothing of this segment in the ledger to check against
+                    return ProofStatus::Skipped;
+                }
+
+                let sampling_offsets = get_sampling_offsets(signature.as_ref(), num_chacha_blocks);
+                // Samples are hash sized, encrypt up to the end of the last one
+                let sample_size = size_of::<Hash>() as u64;
+                let len = (sampling_offsets.iter().max().unwrap() + 1) * sample_size;
+                let mut ivec = [0u8; CHACHA_BLOCK_SIZE];
+                ivec.copy_from_slice(signature.as_ref());
+                let encrypted = chacha_cbc_encrypt_segment_prefix(segment, len as usize, &mut ivec);
+                let sample = sample_buffer(&encrypted, &sampling_offsets).ok();
+
+                if proof_cache.samples.len() >= MAX_CACHED_SAMPLES {
+                    proof_cache.samples.clear();
+                }
+                proof_cache.samples.insert(key, sample);
+                sample
+            }
+        };
+        match sample {
+            Some(hash) if hash == *sha_state => ProofStatus::Valid,
+            _ => ProofStatus::NotValid,
+        }
+    }
+
     fn process_entries(
         keypair: &Arc<Keypair>,
         storage_state: &Arc<RwLock<StorageStateInner>>,
@@ -364,6 +470,7 @@ impl StorageStage {
         current_key_idx: &mut usize,
         storage_rotate_count: u64,
         instruction_sender: &InstructionSender,
+        proof_cache: &mut ProofCache,
     ) -> Result<()> {
         let timeout = Duration::new(1, 0);
         let entries: Vec<Entry> = entry_receiver.recv_timeout(timeout)?;
@@ -379,7 +486,7 @@ impl StorageStage {
                             Ok(StorageInstruction::SubmitMiningProof {
                                 entry_height: proof_entry_height,
                                 signature,
-                                ..
+                                sha_state,
                             }) => {
                                 if proof_entry_height < *entry_height {
                                     {
@@ -396,20 +503,50 @@ impl StorageStage {
                                         *current_key_idx %= storage_keys.len();
                                     }
 
-                                    let mut statew = storage_state.write().unwrap();
                                     let max_segment_index =
                                         (*entry_height / ENTRIES_PER_SEGMENT) as usize;
+                                    let proof_segment_index =
+                                        (proof_entry_height / ENTRIES_PER_SEGMENT) as usize;
+                                    let status = if proof_segment_index < max_segment_index {
+                                        Self::verify_mining_proof(
+                                            blocktree,
+                                            proof_cache,
+                                            proof_entry_height,
+                                            &signature,
+                                            &sha_state,
+                                        )
+                                    } else {
+                                        ProofStatus::Skipped
+                                    };
+
+                                    let mut statew = storage_state.write().unwrap();
                                     if statew.replicator_map.len() <= max_segment_index {
                                         statew
                                             .replicator_map
                                             .resize(max_segment_index, HashSet::new());
                                     }
-                                    let proof_segment_index =
-                                        (proof_entry_height / ENTRIES_PER_SEGMENT) as usize;
                                     if proof_segment_index < statew.replicator_map.len() {
                                         statew.replicator_map[proof_segment_index]
                                             .insert(message.account_keys[0]);
                                     }
+                                    if status != ProofStatus::Skipped {
+                                        let id = message.account_keys[0];
+                                        statew
+                                            .checked_proofs
+                                            .entry(proof_segment_index)
+                                            .or_insert_with(HashMap::new)
+                                            .insert(
+                                                id,
+                                                CheckedProof {
+                                                    proof: Proof {
+                                                        id,
+                                                        signature,
+                                                        sha_state,
+                                                    },
+                                                    status,
+                                                },
+                                            );
+                                    }
                                 }
                                 debug!("storage proof: entry_height: {}", entry_height);
                             }
@@ -456,7 +593,8 @@ impl Service for StorageStage {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::blocktree::{create_new_tmp_ledger, Blocktree};
+    use crate::blocktree::{create_new_tmp_ledger, get_tmp_ledger_path, Blocktree};
+    use crate::chacha::chacha_cbc_encrypt_ledger_to;
     use crate::cluster_info::ClusterInfo;
     use crate::contact_info::ContactInfo;
     use crate::entry::{make_tiny_test_entries, Entry};
@@ -657,6 +795,52 @@ mod tests {
         remove_dir_all(ledger_path).unwrap();
     }
 
+    #[test]
+    fn test_verify_mining_proof() {
+        let ledger_path = get_tmp_ledger_path("test_verify_mining_proof");
+        let blocktree = Arc::new(Blocktree::open(&ledger_path).unwrap());
+        let entries = make_tiny_test_entries(ENTRIES_PER_SEGMENT as usize);
+        blocktree.write_entries(0, 0, 0, 16, &entries).unwrap();
+
+        // The proof a replicator takes from the whole encrypted segment
+        let signature = Keypair::new().sign_message(Hash::default().as_ref());
+        let mut ivec = [0u8; CHACHA_BLOCK_SIZE];
+        ivec.copy_from_slice(signature.as_ref());
+        let mut encrypted = vec![];
+        chacha_cbc_encrypt_ledger_to(&blocktree, 0, &mut encrypted, &mut ivec).unwrap();
+        let num_chacha_blocks = encrypted.len() / CHACHA_BLOCK_SIZE;
+        let sampling_offsets = get_sampling_offsets(signature.as_ref(), num_chacha_blocks);
+        let sha_state = sample_buffer(&encrypted, &sampling_offsets).unwrap();
+
+        let mut proof_cache = ProofCache::default();
+        let mut verify = |signature: &Signature, sha_state: &Hash| {
+            StorageStage::verify_mining_proof(&blocktree, &mut proof_cache, 0, signature, sha_state)
+        };
+        assert_eq!(verify(&signature, &sha_state), ProofStatus::Valid);
+        assert_eq!(verify(&signature, &Hash::default()), ProofStatus::NotValid);
+        let other_signature = Keypair::new().sign_message(Hash::default().as_ref());
+        assert_eq!(verify(&other_signature, &sha_state), ProofStatus::NotValid);
+
+        // One sample per replicator signature, from one read of the segment
+        assert_eq!(proof_cache.samples.len(), 2);
+        assert_eq!(proof_cache.segment.as_ref().unwrap().0, 0);
+
+        drop(blocktree);
+        remove_dir_all(ledger_path).unwrap();
+    }
+
+    #[test]
+    fn test_get_sampling_offsets() {
+        let keypair = Keypair::new();
+        let signature = keypair.sign_message(b"storage");
+        let offsets = get_sampling_offsets(signature.as_ref(), 10);
+        assert_eq!(offsets.len(), NUM_STORAGE_SAMPLES);
+        assert!(offsets.iter().all(|offset| *offset < 10));
+
+        // validators have to come up with the replicator's samples
+        assert_eq!(offsets, get_sampling_offsets(signature.as_ref(), 10));
+    }
+
     #[test]
     fn test_signature_distribution() {
         // See that signatures have an even-ish distribution..
@@ -0,0 +1,248 @@
+//! The `transaction_scheduler` module decides in what order, and in which batches, the banking
+//! stage hands transactions to the bank.  Transactions go by the fee they pay per signature,
+//! every sender and every account gets a limited share of each slot so a busy sender can't
+//! crowd out everyone else, and transactions are batched so that no two in a batch lock the same
+//! account.
+
+use soros_sdk::fee_calculator::FeeCalculator;
+use soros_sdk::pubkey::Pubkey;
+use soros_sdk::transaction::Transaction;
+use std::cmp::{self, Reverse};
+use std::collections::{HashMap, HashSet};
+
+pub const DEFAULT_MAX_TXS_PER_SENDER: usize = 64;
+pub const DEFAULT_MAX_TXS_PER_ACCOUNT: usize = 128;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QosConfig {
+    /// Most transactions scheduled in a slot from one fee payer
+    pub max_txs_per_sender: usize,
+    /// Most transactions scheduled in a slot that lock one account
+    pub max_txs_per_account: usize,
+}
+
+impl Default for QosConfig {
+    fn default() -> Self {
+        Self {
+            max_txs_per_sender: DEFAULT_MAX_TXS_PER_SENDER,
+            max_txs_per_account: DEFAULT_MAX_TXS_PER_ACCOUNT,
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Schedule {
+    /// Indexes of the transactions to process, in batches that lock none of the same accounts.
+    /// Of two transactions that lock the same account, the one that goes first is in an earlier
+    /// batch.
+    pub batches: Vec<Vec<usize>>,
+    /// Indexes of the transactions over their sender's or an account's share of this slot
+    pub deferred: Vec<usize>,
+}
+
+/// How many transactions each sender and each account had scheduled in a slot, so that their
+/// shares hold across all the packets scheduled in the slot, not just one batch of them
+#[derive(Debug, Default)]
+pub struct Shares {
+    slot: u64,
+    sender_txs: HashMap<Pubkey, usize>,
+    account_txs: HashMap<Pubkey, usize>,
+}
+
+impl Shares {
+    /// Start counting afresh if `slot` isn't the slot counted so far
+    pub fn set_slot(&mut self, slot: u64) {
+        if slot != self.slot {
+            self.slot = slot;
+            self.sender_txs.clear();
+            self.account_txs.clear();
+        }
+    }
+}
+
+impl Schedule {
+    pub fn num_scheduled(&self) -> usize {
+        self.batches.iter().map(Vec::len).sum()
+    }
+}
+
+/// The fee `tx` pays for each of its signatures
+pub fn fee_per_signature(tx: &Transaction, fee_calculator: &FeeCalculator) -> u64 {
+    let num_signatures = cmp::max(1, u64::from(tx.message().num_required_signatures));
+    fee_calculator.calculate_fee(tx.message()) / num_signatures
+}
+
+/// Schedule `transactions` highest `priority` first, transactions of the same priority in the
+/// order they came in.  `shares` has what was scheduled earlier in the slot and gets what's
+/// scheduled now added.
+pub fn schedule<F>(
+    transactions: &[Transaction],
+    priority: F,
+    config: &QosConfig,
+    shares: &mut Shares,
+) -> Schedule
+where
+    F: Fn(&Transaction) -> u64,
+{
+    let mut order: Vec<usize> = (0..transactions.len()).collect();
+    order.sort_by_key(|i| Reverse(priority(&transactions[*i])));
+
+    let mut schedule = Schedule::default();
+    let mut batch_accounts: Vec<HashSet<&Pubkey>> = vec![];
+    for i in order {
+        let account_keys = &transactions[i].message().account_keys;
+        let sender_over_share = account_keys.first().map_or(false, |sender| {
+            shares.sender_txs.get(sender).cloned().unwrap_or(0) >= config.max_txs_per_sender
+        });
+        let account_over_share = account_keys.iter().any(|key| {
+            shares.account_txs.get(key).cloned().unwrap_or(0) >= config.max_txs_per_account
+        });
+        if sender_over_share || account_over_share {
+            schedule.deferred.push(i);
+            continue;
+        }
+        if let Some(sender) = account_keys.first() {
+            *shares.sender_txs.entry(*sender).or_insert(0) += 1;
+        }
+        for key in account_keys.iter().collect::<HashSet<_>>() {
+            *shares.account_txs.entry(*key).or_insert(0) += 1;
+        }
+
+        // Go right after the last batch with a transaction locking any of the same accounts
+        let batch = batch_accounts
+            .iter()
+            .rposition(|accounts| account_keys.iter().any(|key| accounts.contains(key)))
+            .map_or(0, |last_conflict| last_conflict + 1);
+        if batch == schedule.batches.len() {
+            schedule.batches.push(vec![]);
+            batch_accounts.push(HashSet::new());
+        }
+        schedule.batches[batch].push(i);
+        batch_accounts[batch].extend(account_keys.iter());
+    }
+    schedule
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soros_sdk::hash::Hash;
+    use soros_sdk::signature::{Keypair, KeypairUtil};
+    use soros_sdk::system_instruction;
+    use soros_sdk::system_transaction;
+    use std::collections::HashMap;
+
+    fn transfer(from: &Keypair, to: &Pubkey, dif: u64) -> Transaction {
+        system_transaction::transfer(from, to, dif, Hash::default(), 0)
+    }
+
+    #[test]
+    fn test_fee_per_signature() {
+        let (a, b) = (Keypair::new(), Keypair::new());
+        let tx = transfer(&a, &Pubkey::new_rand(), 1);
+        assert_eq!(fee_per_signature(&tx, &FeeCalculator::new(3)), 3);
+        assert_eq!(fee_per_signature(&tx, &FeeCalculator::new(0)), 0);
+
+        let instructions = vec![
+            system_instruction::transfer(&a.pubkey(), &b.pubkey(), 1),
+            system_instruction::transfer(&b.pubkey(), &a.pubkey(), 1),
+        ];
+        let tx = Transaction::new_signed_instructions(&[&a, &b], instructions, Hash::default());
+        assert_eq!(fee_per_signature(&tx, &FeeCalculator::new(3)), 3);
+    }
+
+    #[test]
+    fn test_schedule_by_priority() {
+        let keypairs: Vec<_> = (0..4).map(|_| Keypair::new()).collect();
+        let to = Pubkey::new_rand();
+        let txs: Vec<_> = keypairs
+            .iter()
+            .map(|keypair| transfer(keypair, &to, 1))
+            .collect();
+        let priorities: HashMap<_, _> = keypairs
+            .iter()
+            .map(|keypair| keypair.pubkey())
+            .zip(vec![1, 3, 0, 2])
+            .collect();
+        let priority = |tx: &Transaction| priorities[&tx.message().account_keys[0]];
+
+        // They all lock `to`, so each gets a batch of its own
+        let schedule = schedule(
+            &txs,
+            priority,
+            &QosConfig::default(),
+            &mut Shares::default(),
+        );
+        assert_eq!(schedule.batches, vec![vec![1], vec![3], vec![0], vec![2]]);
+        assert!(schedule.deferred.is_empty());
+    }
+
+    #[test]
+    fn test_schedule_non_conflicting_batches() {
+        let (a, b, c) = (Keypair::new(), Keypair::new(), Keypair::new());
+        let txs = vec![
+            transfer(&a, &Pubkey::new_rand(), 1),
+            transfer(&b, &Pubkey::new_rand(), 1),
+            transfer(&a, &Pubkey::new_rand(), 1),
+            transfer(&c, &b.pubkey(), 1),
+            transfer(&c, &Pubkey::new_rand(), 1),
+        ];
+        let schedule = schedule(&txs, |_| 0, &QosConfig::default(), &mut Shares::default());
+        assert_eq!(schedule.batches, vec![vec![0, 1], vec![2, 3], vec![4]]);
+        assert_eq!(schedule.num_scheduled(), txs.len());
+    }
+
+    #[test]
+    fn test_schedule_fair_shares() {
+        let (spammer, other) = (Keypair::new(), Keypair::new());
+        let popular = Pubkey::new_rand();
+        let mut txs: Vec<_> = (0..5)
+            .map(|_| transfer(&spammer, &Pubkey::new_rand(), 1))
+            .collect();
+        txs.push(transfer(&other, &Pubkey::new_rand(), 1));
+        txs.extend((0..3).map(|_| transfer(&Keypair::new(), &popular, 1)));
+
+        let config = QosConfig {
+            max_txs_per_sender: 2,
+            max_txs_per_account: 2,
+        };
+        let schedule = schedule(&txs, |_| 0, &config, &mut Shares::default());
+        assert_eq!(schedule.deferred, vec![2, 3, 4, 8]);
+        assert_eq!(schedule.num_scheduled(), 5);
+    }
+
+    #[test]
+    fn test_schedule_shares_hold_for_the_slot() {
+        let spammer = Keypair::new();
+        let config = QosConfig {
+            max_txs_per_sender: 2,
+            ..QosConfig::default()
+        };
+        let mut shares = Shares::default();
+        shares.set_slot(1);
+        let txs: Vec<_> = (0..3)
+            .map(|_| transfer(&spammer, &Pubkey::new_rand(), 1))
+            .collect();
+
+        // Spreading transactions over batches of packets doesn't get the sender more
+        let schedule0 = schedule(&txs[..1], |_| 0, &config, &mut shares);
+        assert_eq!(schedule0.num_scheduled(), 1);
+        let schedule1 = schedule(&txs[1..], |_| 0, &config, &mut shares);
+        assert_eq!(schedule1.num_scheduled(), 1);
+        assert_eq!(schedule1.deferred, vec![1]);
+
+        // Same slot, same shares
+        shares.set_slot(1);
+        assert_eq!(
+            schedule(&txs[2..], |_| 0, &config, &mut shares).deferred,
+            vec![0]
+        );
+
+        // A new slot has new shares
+        shares.set_slot(2);
+        assert_eq!(
+            schedule(&txs[2..], |_| 0, &config, &mut shares).num_scheduled(),
+            1
+        );
+    }
+}
@@ -14,6 +14,7 @@
 
 use crate::bank_forks::BankForks;
 use crate::blob_fetch_stage::BlobFetchStage;
+use crate::blockstream::BlockstreamConfig;
 use crate::blockstream_service::BlockstreamService;
 use crate::blocktree::Blocktree;
 use crate::blocktree_processor::BankForksInfo;
@@ -67,7 +68,7 @@ impl Tvu {
         blocktree: Arc<Blocktree>,
         storage_rotate_count: u64,
         storage_state: &StorageState,
-        blockstream: Option<&String>,
+        blockstream: Option<&BlockstreamConfig>,
         ledger_signal_receiver: Receiver<bool>,
         subscriptions: &Arc<RpcSubscriptions>,
         poh_recorder: &Arc<Mutex<PohRecorder>>,
@@ -130,17 +131,18 @@ impl Tvu {
             leader_schedule_cache,
         );
 
-        let blockstream_service = if blockstream.is_some() {
-            let blockstream_service = BlockstreamService::new(
+        // The node goes on without its blockstream if the blockstream can't be opened
+        let blockstream_service = blockstream.and_then(|blockstream| {
+            BlockstreamService::new(
                 slot_full_receiver,
                 blocktree.clone(),
-                blockstream.unwrap().to_string(),
+                bank_forks.clone(),
+                blockstream,
                 &exit,
-            );
-            Some(blockstream_service)
-        } else {
-            None
-        };
+            )
+            .map_err(|err| error!("Unable to open blockstream {:?}: {:?}", blockstream, err))
+            .ok()
+        });
 
         let storage_keypair = Arc::new(Keypair::new());
         let storage_stage = StorageStage::new(
@@ -2,12 +2,19 @@ extern crate soros;
 
 use soros::cluster::Cluster;
 use soros::cluster_tests;
+use soros::fault_injection::{LinkFaults, NetworkFaultConfig, Partition};
 use soros::fullnode::FullnodeConfig;
 use soros::gossip_service::discover_nodes;
 use soros::local_cluster::{ClusterConfig, LocalCluster};
 use soros::poh_service::PohServiceConfig;
+use soros_client::rpc_client::RpcClient;
+use soros_drone::drone::request_airdrop_transaction;
+use soros_runtime::bank::{Bank, BankSnapshot};
+use soros_sdk::pubkey::Pubkey;
+use soros_sdk::signature::KeypairUtil;
 use soros_sdk::timing;
-use std::time::Duration;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
 
 #[test]
 fn test_spend_and_verify_all_nodes_1() {
@@ -208,3 +215,148 @@ fn test_listener_startup() {
     let cluster_nodes = discover_nodes(&cluster.entry_point_info.gossip, 4).unwrap();
     assert_eq!(cluster_nodes.len(), 4);
 }
+
+#[test]
+fn test_kill_and_restart_node_endpoints() {
+    soros_logger::setup();
+    let mut cluster = LocalCluster::new(&ClusterConfig {
+        node_stakes: vec![100; 2],
+        cluster_dif: 10_000,
+        start_drone: true,
+        ..ClusterConfig::default()
+    });
+    let endpoints = cluster.endpoints();
+    assert_eq!(endpoints.nodes.len(), 2);
+    let drone_addr = endpoints.drone.unwrap();
+
+    let validator = endpoints
+        .nodes
+        .iter()
+        .find(|node| node.pubkey != cluster.entry_point_info.id.to_string())
+        .unwrap()
+        .clone();
+    let validator_id: Pubkey = validator.pubkey.parse().unwrap();
+    let node_endpoints = |cluster: &LocalCluster| {
+        cluster
+            .endpoints()
+            .nodes
+            .into_iter()
+            .find(|node| node.pubkey == validator.pubkey)
+            .unwrap()
+    };
+    cluster.kill_node(validator_id);
+    assert!(!node_endpoints(&cluster).running);
+
+    cluster.restart_node(validator_id);
+    let restarted = node_endpoints(&cluster);
+    assert!(restarted.running);
+    assert_ne!(restarted.gossip, validator.gossip);
+    assert_eq!(restarted.ledger_path, validator.ledger_path);
+
+    // The drone hands out airdrops from the mint
+    let client = RpcClient::new_socket(cluster.entry_point_info.rpc);
+    let blockhash = client.get_recent_blockhash().unwrap();
+    let tx = request_airdrop_transaction(&drone_addr, &Pubkey::new_rand(), 1, blockhash).unwrap();
+    assert_eq!(
+        tx.message().account_keys[0],
+        cluster.funding_keypair.pubkey()
+    );
+}
+
+#[test]
+fn test_spend_and_verify_all_nodes_lossy_network() {
+    soros_logger::setup();
+    let num_nodes = 3;
+    let config = ClusterConfig {
+        node_stakes: vec![100; num_nodes],
+        cluster_dif: 10_000,
+        network_faults: Some(NetworkFaultConfig {
+            seed: 7,
+            faults: LinkFaults {
+                drop_rate: 0.05,
+                duplicate_rate: 0.05,
+                reorder_rate: 0.05,
+                delay_ms: 5,
+                jitter_ms: 20,
+            },
+            ..NetworkFaultConfig::default()
+        }),
+        ..ClusterConfig::default()
+    };
+    let local = LocalCluster::new(&config);
+    cluster_tests::spend_and_verify_all_nodes(
+        &local.entry_point_info,
+        &local.funding_keypair,
+        num_nodes,
+    );
+}
+
+#[test]
+fn test_partition_heals() {
+    soros_logger::setup();
+    let num_nodes = 3;
+    let partition_duration = Duration::from_secs(10);
+    let config = ClusterConfig {
+        node_stakes: vec![100; num_nodes],
+        cluster_dif: 10_000,
+        network_faults: Some(NetworkFaultConfig {
+            partitions: vec![Partition {
+                start: Duration::from_secs(0),
+                duration: partition_duration,
+                groups: vec![vec![0, 1], vec![2]],
+            }],
+            ..NetworkFaultConfig::default()
+        }),
+        ..ClusterConfig::default()
+    };
+    let local = LocalCluster::new(&config);
+    let network = local.network.as_ref().unwrap();
+    if let Some(remaining) = partition_duration.checked_sub(network.elapsed()) {
+        std::thread::sleep(remaining);
+    }
+    cluster_tests::spend_and_verify_all_nodes(
+        &local.entry_point_info,
+        &local.funding_keypair,
+        num_nodes,
+    );
+}
+
+#[test]
+fn test_validator_bootstrap_from_snapshot() {
+    soros_logger::setup();
+    let mut fullnode_config = FullnodeConfig::default();
+    fullnode_config.snapshot_interval_slots = Some(1);
+    let mut cluster = LocalCluster::new(&ClusterConfig {
+        node_stakes: vec![100],
+        cluster_dif: 10_000,
+        fullnode_config,
+        ticks_per_slot: 8,
+        slots_per_epoch: 16,
+        ..ClusterConfig::default()
+    });
+
+    // Wait for the leader to root a bank and snapshot it
+    let client = RpcClient::new_socket(cluster.entry_point_info.rpc);
+    let start = Instant::now();
+    let (slot, snapshot) = loop {
+        if let Some(snapshot) = client.get_snapshot().unwrap() {
+            break snapshot;
+        }
+        assert!(start.elapsed() < Duration::from_secs(60), "no snapshot");
+        sleep(Duration::from_millis(500));
+    };
+    let snapshot: BankSnapshot = bincode::deserialize(&snapshot).unwrap();
+    assert_eq!(snapshot.slot, slot);
+    let expected_hash = snapshot.hash;
+    let snapshot_hash = snapshot.snapshot_hash();
+    let bank = Bank::from_snapshot(snapshot, &snapshot_hash, None).unwrap();
+    assert_eq!(bank.hash(), expected_hash);
+
+    let mut validator_config = FullnodeConfig::default();
+    validator_config.voting_disabled = true;
+    validator_config.trusted_snapshot = Some((cluster.entry_point_info.rpc, snapshot_hash));
+    cluster.add_validator(&validator_config, 0);
+    let cluster_nodes = discover_nodes(&cluster.entry_point_info.gossip, 2).unwrap();
+    assert_eq!(cluster_nodes.len(), 2);
+    cluster_tests::send_many_transactions(&cluster.entry_point_info, &cluster.funding_keypair, 1);
+}
@@ -16,9 +16,15 @@ use soros::replicator::ReplicatorRequest;
 use soros::storage_stage::STORAGE_ROTATE_TEST_COUNT;
 use soros::streamer::blob_receiver;
 use soros_client::thin_client::create_client;
+use soros_config_api::cluster_config::StorageRewardConfig;
+use soros_sdk::client::SyncClient;
 use soros_sdk::genesis_block::GenesisBlock;
 use soros_sdk::hash::Hash;
+use soros_sdk::message::Message;
+use soros_sdk::pubkey::Pubkey;
 use soros_sdk::signature::{Keypair, KeypairUtil};
+use soros_storage_api::storage_contract::{ProofStatus, StorageContract};
+use soros_storage_api::storage_instruction;
 use std::fs::remove_dir_all;
 use std::net::SocketAddr;
 use std::net::UdpSocket;
@@ -249,3 +255,106 @@ fn test_account_setup() {
         );
     });
 }
+
+fn num_valid_proofs<C: SyncClient>(client: &C, storage_id: &Pubkey) -> usize {
+    client
+        .get_account_data(storage_id)
+        .unwrap()
+        .and_then(|data| deserialize(&data).ok())
+        .map(|contract| match contract {
+            StorageContract::ReplicatorStorage {
+                reward_validations, ..
+            } => reward_validations
+                .iter()
+                .flatten()
+                .filter(|checked_proof| checked_proof.status == ProofStatus::Valid)
+                .count(),
+            _ => 0,
+        })
+        .unwrap_or(0)
+}
+
+/// A replicator mines its segment, the validator checks the proof against its own ledger on
+/// CPU and the replicator then claims its reward from a mining pool
+#[test]
+fn test_replicator_proof_validation_and_reward() {
+    soros_logger::setup();
+    let mut fullnode_config = FullnodeConfig::default();
+    fullnode_config.storage_rotate_count = STORAGE_ROTATE_TEST_COUNT;
+    let config = ClusterConfig {
+        fullnode_config,
+        node_stakes: vec![100],
+        // cluster_lamports: 10_000,
+        cluster_dif: 10_000,
+        native_instruction_processors: vec![(
+            "soros_storage_program".to_string(),
+            soros_storage_api::id(),
+        )],
+        ..ClusterConfig::default()
+    };
+    let cluster = LocalCluster::new(&config);
+    let client = create_client(
+        cluster.entry_point_info.client_facing_addr(),
+        FULLNODE_PORT_RANGE,
+    );
+
+    let replicator_keypair = Arc::new(Keypair::new());
+    let storage_keypair = Arc::new(Keypair::new());
+    cluster.transfer(&cluster.funding_keypair, &replicator_keypair.pubkey(), 1);
+
+    let replicator_node = Node::new_localhost_replicator(&replicator_keypair.pubkey());
+    let (replicator_ledger_path, _blockhash) = create_new_tmp_ledger!(&cluster.genesis_block);
+    let mut replicator = Replicator::new(
+        &replicator_ledger_path,
+        replicator_node,
+        cluster.entry_point_info.clone(),
+        replicator_keypair,
+        storage_keypair.clone(),
+        None,
+    )
+    .unwrap();
+
+    // keep mining until the ledger segment is downloaded and a proof of it gets validated
+    let mut validated = false;
+    for _ in 0..30 {
+        replicator.encrypt_ledger().unwrap();
+        if replicator.mine().is_ok() {
+            sleep(Duration::from_secs(2));
+            if num_valid_proofs(&client, &storage_keypair.pubkey()) > 0 {
+                validated = true;
+                break;
+            }
+        } else {
+            sleep(Duration::from_secs(1));
+        }
+    }
+    assert!(validated);
+
+    let mining_pool_keypair = Keypair::new();
+    let ixs = storage_instruction::create_mining_pool_account(
+        &cluster.funding_keypair.pubkey(),
+        &mining_pool_keypair.pubkey(),
+        100,
+    );
+    client
+        .send_message(
+            &[&cluster.funding_keypair, &mining_pool_keypair],
+            Message::new(ixs),
+        )
+        .unwrap();
+
+    let balance = client.get_balance(&storage_keypair.pubkey()).unwrap();
+    let ix = storage_instruction::reward_claim(
+        &storage_keypair.pubkey(),
+        &mining_pool_keypair.pubkey(),
+        replicator.entry_height(),
+    );
+    client.send_instruction(&storage_keypair, ix).unwrap();
+    assert!(
+        client.get_balance(&storage_keypair.pubkey()).unwrap()
+            >= balance + StorageRewardConfig::default().replicator_reward
+    );
+
+    replicator.close();
+    let _ignored = remove_dir_all(&replicator_ledger_path);
+}
@@ -1,4 +1,4 @@
-use clap::{crate_description, crate_name, crate_version, App, Arg};
+use clap::{crate_description, crate_name, crate_version, value_t_or_exit, App, Arg};
 use log::*;
 use soros::cluster_info::{Node, FULLNODE_PORT_RANGE};
 use soros::contact_info::ContactInfo;
@@ -7,10 +7,12 @@ use soros::local_vote_signer_service::LocalVoteSignerService;
 use soros::service::Service;
 use soros::socketaddr;
 use soros_netutil::parse_port_range;
+use soros_sdk::hash::Hash;
 use soros_sdk::signature::{read_keypair, Keypair, KeypairUtil};
 use std::fs::File;
 use std::net::SocketAddr;
 use std::process::exit;
+use std::str::FromStr;
 use std::sync::Arc;
 
 fn port_range_validator(port_range: String) -> Result<(), String> {
@@ -34,8 +36,11 @@ fn main() {
             Arg::with_name("blockstream")
                 .long("blockstream")
                 .takes_value(true)
-                .value_name("UNIX DOMAIN SOCKET")
-                .help("Open blockstream at this unix domain socket location")
+                .value_name("UNIX DOMAIN SOCKET | file://DIR | tcp://HOST:PORT")
+                .help(
+                    "Stream entries and blocks to this unix domain socket, to rotating files \
+                     in DIR, or to TCP clients connecting to HOST:PORT",
+                ),
         )
         .arg(
             Arg::with_name("identity")
@@ -132,6 +137,29 @@ fn main() {
                 .takes_value(true)
                 .help("Comma separated persistent accounts location"),
         )
+        .arg(
+            Arg::with_name("snapshot_interval_slots")
+                .long("snapshot-interval-slots")
+                .value_name("NUM")
+                .takes_value(true)
+                .help("Snapshot the root bank every NUM slots and serve it over JSON RPC"),
+        )
+        .arg(
+            Arg::with_name("snapshot_rpc_address")
+                .long("snapshot-rpc-address")
+                .value_name("HOST:PORT")
+                .takes_value(true)
+                .requires("expected_snapshot_hash")
+                .help("Start from the snapshot served by this JSON RPC end point"),
+        )
+        .arg(
+            Arg::with_name("expected_snapshot_hash")
+                .long("expected-snapshot-hash")
+                .value_name("HASH")
+                .takes_value(true)
+                .requires("snapshot_rpc_address")
+                .help("Trusted hash of the snapshot, as logged by a node that took it"),
+        )
         .arg(
             clap::Arg::with_name("gossip_port")
                 .long("gossip-port")
@@ -148,8 +176,23 @@ fn main() {
                 .validator(port_range_validator)
                 .help("Range to use for dynamically assigned ports"),
         )
+        .arg(
+            Arg::with_name("prometheus_port")
+                .long("prometheus-port")
+                .value_name("PORT")
+                .takes_value(true)
+                .help("Serve metrics on this port for Prometheus to scrape, instead of InfluxDB"),
+        )
         .get_matches();
 
+    if matches.is_present("prometheus_port") {
+        let port = value_t_or_exit!(matches, "prometheus_port", u16);
+        soros_metrics::set_prometheus_writer(&socketaddr!(0, port)).unwrap_or_else(|err| {
+            eprintln!("Unable to serve metrics on port {}: {}", port, err);
+            exit(1);
+        });
+    }
+
     let mut fullnode_config = FullnodeConfig::default();
     let keypair = if let Some(identity) = matches.value_of("identity") {
         read_keypair(identity).unwrap_or_else(|err| {
@@ -204,6 +247,21 @@ fn main() {
     } else {
         fullnode_config.account_paths = None;
     }
+    if matches.is_present("snapshot_interval_slots") {
+        fullnode_config.snapshot_interval_slots =
+            Some(value_t_or_exit!(matches, "snapshot_interval_slots", u64));
+    }
+    // Both are required together, a snapshot is only started from with a hash to trust it by
+    fullnode_config.trusted_snapshot = matches.value_of("snapshot_rpc_address").map(|address| {
+        let rpc_addr =
+            soros_netutil::parse_host_port(address).expect("failed to parse snapshot RPC address");
+        let hash = matches.value_of("expected_snapshot_hash").unwrap();
+        let hash = Hash::from_str(hash).unwrap_or_else(|_| {
+            eprintln!("Invalid snapshot hash: {}", hash);
+            exit(1);
+        });
+        (rpc_addr, hash)
+    });
     let cluster_entrypoint = matches.value_of("network").map(|network| {
         let entrypoint_addr =
             soros_netutil::parse_host_port(network).expect("failed to parse network address");
@@ -222,7 +280,12 @@ fn main() {
         (Some(signer_service), signer_addr)
     };
     let init_complete_file = matches.value_of("init_complete_file");
-    fullnode_config.blockstream = matches.value_of("blockstream").map(ToString::to_string);
+    fullnode_config.blockstream = matches.value_of("blockstream").map(|blockstream| {
+        blockstream.parse().unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            exit(1);
+        })
+    });
 
     let keypair = Arc::new(keypair);
     let mut node = Node::new_with_external_ip(&keypair.pubkey(), &gossip_addr, dynamic_port_range);
@@ -111,6 +111,7 @@ fn main() -> Result<(), Box<dyn error::Error>> {
         .native_instruction_processors
         .extend_from_slice(&[
             ("soros_budget_program".to_string(), soros_budget_api::id()),
+            ("soros_stake_program".to_string(), soros_stake_api::id()),
             (
                 "soros_storage_program".to_string(),
                 soros_storage_api::id(),
@@ -144,6 +145,9 @@ mod tests {
         let bpf_loader = "BPFLoader1111111111111111111111111111111111"
             .parse::<Pubkey>()
             .unwrap();
+        let bpf_loader_upgradeable = "BPFLoaderUpgradeab1e11111111111111111111111"
+            .parse::<Pubkey>()
+            .unwrap();
         let budget = "Budget1111111111111111111111111111111111111"
             .parse::<Pubkey>()
             .unwrap();
@@ -169,6 +173,10 @@ mod tests {
         assert_eq!(soros_sdk::system_program::id(), system);
         assert_eq!(soros_sdk::native_loader::id(), native_loader);
         assert_eq!(soros_sdk::bpf_loader::id(), bpf_loader);
+        assert_eq!(
+            soros_sdk::bpf_loader_upgradeable::id(),
+            bpf_loader_upgradeable
+        );
         assert_eq!(soros_budget_api::id(), budget);
         assert_eq!(soros_stake_api::id(), stake);
         assert_eq!(soros_storage_api::id(), storage);
@@ -185,6 +193,7 @@ mod tests {
             soros_sdk::system_program::id(),
             soros_sdk::native_loader::id(),
             soros_sdk::bpf_loader::id(),
+            soros_sdk::bpf_loader_upgradeable::id(),
             soros_budget_api::id(),
             soros_storage_api::id(),
             soros_token_api::id(),
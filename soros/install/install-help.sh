@@ -11,7 +11,7 @@ soros-install --help
 echo "\`\`\`"
 echo ""
 
-commands=(init info deploy update run)
+commands=(init info deploy update list rollback channel run)
 
 for x in "${commands[@]}"; do
     echo "\`\`\`manpage"
@@ -4,6 +4,7 @@ use chrono::{Local, TimeZone};
 use console::{style, Emoji};
 use indicatif::{ProgressBar, ProgressStyle};
 use ring::digest::{Context, Digest, SHA256};
+use semver::Version;
 use soros_client::rpc_client::RpcClient;
 use soros_config_api::config_instruction;
 use soros_sdk::pubkey::Pubkey;
@@ -246,6 +247,27 @@ fn get_update_manifest(
     Ok(signed_update_manifest.manifest)
 }
 
+/// Point the active release at the release extracted into `release_dir`
+fn activate_release(config: &Config, release_dir: &Path) -> Result<(), String> {
+    let _ = fs::remove_dir_all(config.active_release_dir());
+    std::os::unix::fs::symlink(
+        release_dir.join("soros-release"),
+        config.active_release_dir(),
+    )
+    .map_err(|err| {
+        format!(
+            "Unable to symlink {:?} to {:?}: {}",
+            release_dir,
+            config.active_release_dir(),
+            err
+        )
+    })
+}
+
+fn parse_version(version: &str) -> Result<Version, String> {
+    Version::parse(version).map_err(|err| format!("Invalid version {}: {}", version, err))
+}
+
 /// Bug the user if active_release_bin_dir is not in their PATH
 fn check_env_path_for_bin_dir(config: &Config) {
     use std::env;
@@ -278,10 +300,11 @@ pub fn init(
     config_file: &str,
     data_dir: &str,
     json_rpc_url: &str,
+    channel: &str,
     update_manifest_pubkey: &Pubkey,
     no_modify_path: bool,
 ) -> Result<(), String> {
-    let config = Config::new(data_dir, json_rpc_url, update_manifest_pubkey);
+    let config = Config::new(data_dir, json_rpc_url, channel, update_manifest_pubkey);
     config.save(config_file)?;
     update(config_file)?;
 
@@ -378,14 +401,20 @@ pub fn init(
 
 pub fn info(config_file: &str, local_info_only: bool) -> Result<Option<UpdateManifest>, String> {
     let config = Config::load(config_file)?;
+    let update_manifest_pubkey = *config.update_manifest_pubkey()?;
     println_name_value("JSON RPC URL:", &config.json_rpc_url);
+    println_name_value("Channel:", &config.channel);
     println_name_value(
         "Update manifest pubkey:",
-        &config.update_manifest_pubkey.to_string(),
+        &update_manifest_pubkey.to_string(),
     );
 
     fn print_update_manifest(update_manifest: &UpdateManifest) {
         let when = Local.timestamp(update_manifest.timestamp_secs as i64, 0);
+        println_name_value(
+            &format!("{}version", BULLET),
+            &update_manifest.version.to_string(),
+        );
         println_name_value(&format!("{}release date", BULLET), &when.to_string());
         println_name_value(
             &format!("{}download URL", BULLET),
@@ -409,12 +438,21 @@ pub fn info(config_file: &str, local_info_only: bool) -> Result<Option<UpdateMan
         let progress_bar = new_spinner_progress_bar();
         progress_bar.set_message(&format!("{}Checking for updates...", LOOKING_GLASS));
         let rpc_client = RpcClient::new(config.json_rpc_url.clone());
-        let manifest = get_update_manifest(&rpc_client, &config.update_manifest_pubkey)?;
+        let manifest = get_update_manifest(&rpc_client, &update_manifest_pubkey)?;
         progress_bar.finish_and_clear();
 
-        if Some(&manifest) == config.current_update_manifest.as_ref() {
+        let up_to_date = config.current_version().map_or(false, |current_version| {
+            manifest.version <= *current_version
+        });
+        if Some(&manifest) == config.current_update_manifest.as_ref() || up_to_date {
             println!("\n{}", style("Installation is up to date").italic());
             Ok(None)
+        } else if config.rejected_versions.contains(&manifest.version) {
+            println!(
+                "\n{}",
+                style(format!("Skipping {}, it was rolled back", manifest.version)).italic()
+            );
+            Ok(None)
         } else {
             println!("\n{}", style("An update is available:").bold());
             print_update_manifest(&manifest);
@@ -428,7 +466,24 @@ pub fn deploy(
     from_keypair_file: &str,
     download_url: &str,
     update_manifest_keypair_file: &str,
+    version: &str,
+    min_version: Option<&str>,
 ) -> Result<(), String> {
+    let version = parse_version(version)?;
+    let min_version = match min_version {
+        Some(min_version) => Some(parse_version(min_version)?),
+        None => None,
+    };
+    if min_version
+        .as_ref()
+        .map_or(false, |min_version| *min_version >= version)
+    {
+        Err(format!(
+            "The minimum version must be older than {}",
+            version
+        ))?;
+    }
+
     let from_keypair = read_keypair(from_keypair_file)
         .map_err(|err| format!("Unable to read {}: {}", from_keypair_file, err))?;
     let update_manifest_keypair = read_keypair(update_manifest_keypair_file)
@@ -474,6 +529,7 @@ pub fn deploy(
 
     println_name_value("JSON RPC URL:", json_rpc_url);
     println_name_value("Update target:", &release_target);
+    println_name_value("Update version:", &version.to_string());
     println_name_value(
         "Update manifest pubkey:",
         &update_manifest_keypair.pubkey().to_string(),
@@ -491,6 +547,8 @@ pub fn deploy(
     update_manifest.manifest.timestamp_secs = timestamp_secs();
     update_manifest.manifest.download_url = download_url.to_string();
     update_manifest.manifest.download_sha256 = temp_archive_sha256;
+    update_manifest.manifest.version = version;
+    update_manifest.manifest.min_version = min_version;
 
     update_manifest.sign(&update_manifest_keypair);
     assert!(update_manifest.verify());
@@ -530,6 +588,7 @@ pub fn update(config_file: &str) -> Result<bool, String> {
             Err("Unable to update to an older version".to_string())?
         }
     }
+    update_manifest.check_update_from(config.current_version())?;
 
     let (_temp_dir, temp_archive, _temp_archive_sha256) = download_to_temp_archive(
         &update_manifest.download_url,
@@ -562,27 +621,104 @@ pub fn update(config_file: &str) -> Result<bool, String> {
         Err(format!("Incompatible update target: {}", release_target))?;
     }
 
-    let _ = fs::remove_dir_all(config.active_release_dir());
-    std::os::unix::fs::symlink(
-        release_dir.join("soros-release"),
-        config.active_release_dir(),
-    )
-    .map_err(|err| {
-        format!(
-            "Unable to symlink {:?} to {:?}: {}",
-            release_dir,
-            config.active_release_dir(),
-            err
-        )
-    })?;
+    activate_release(&config, &release_dir)?;
 
-    config.current_update_manifest = Some(update_manifest);
+    config.current_update_manifest = Some(update_manifest.clone());
+    for release in config.add_installed_release(update_manifest) {
+        let release_dir = config.release_dir(&release.download_sha256);
+        if let Err(err) = fs::remove_dir_all(&release_dir) {
+            eprintln!("Unable to remove {:?}: {}", release_dir, err);
+        }
+    }
     config.save(config_file)?;
 
     println!("  {}{}", SPARKLE, style("Update successful").bold());
     Ok(true)
 }
 
+pub fn list(config_file: &str) -> Result<(), String> {
+    let config = Config::load(config_file)?;
+    if config.installed_releases.is_empty() {
+        println!("No releases installed");
+    }
+    for release in config.installed_releases.iter().rev() {
+        let active = Some(&release.version) == config.current_version();
+        let when = Local.timestamp(release.timestamp_secs as i64, 0);
+        let release_line = format!("{}{} ({})", BULLET, release.version, when);
+        if active {
+            println!(
+                "{} {}",
+                style(release_line).bold(),
+                style("active").italic()
+            );
+        } else if config.rejected_versions.contains(&release.version) {
+            println!("{} {}", release_line, style("rolled back").italic());
+        } else {
+            println!("{}", release_line);
+        }
+    }
+    Ok(())
+}
+
+/// Switch back to `version`, or to the previous installed release.  A release that is rolled
+/// back from won't be installed again by `update`
+pub fn rollback(config_file: &str, version: Option<&str>) -> Result<(), String> {
+    let mut config = Config::load(config_file)?;
+    let current_version = config
+        .current_version()
+        .cloned()
+        .ok_or_else(|| "Nothing is installed".to_string())?;
+
+    let release = match version {
+        Some(version) => {
+            let version = parse_version(version)?;
+            config
+                .installed_release(&version)
+                .ok_or_else(|| format!("{} is not installed", version))?
+        }
+        None => config
+            .previous_release()
+            .ok_or_else(|| format!("No release older than {} is installed", current_version))?,
+    }
+    .clone();
+    if release.version == current_version {
+        Err(format!("{} is already active", current_version))?;
+    }
+
+    activate_release(&config, &config.release_dir(&release.download_sha256))?;
+    if release.version < current_version && !config.rejected_versions.contains(&current_version) {
+        config.rejected_versions.push(current_version);
+    }
+    config
+        .rejected_versions
+        .retain(|version| *version != release.version);
+    println_name_value("Active version:", &release.version.to_string());
+    config.current_update_manifest = Some(release);
+    config.save(config_file)?;
+    Ok(())
+}
+
+/// Follow `channel` from now on, optionally setting the update manifest it's published to
+pub fn channel(
+    config_file: &str,
+    channel: &str,
+    update_manifest_pubkey: Option<&Pubkey>,
+) -> Result<(), String> {
+    let mut config = Config::load(config_file)?;
+    if let Some(update_manifest_pubkey) = update_manifest_pubkey {
+        config
+            .update_manifest_pubkeys
+            .insert(channel.to_string(), *update_manifest_pubkey);
+    }
+    config.channel = channel.to_string();
+    let update_manifest_pubkey = config.update_manifest_pubkey()?.to_string();
+    config.save(config_file)?;
+
+    println_name_value("Channel:", channel);
+    println_name_value("Update manifest pubkey:", &update_manifest_pubkey);
+    Ok(())
+}
+
 pub fn run(
     config_file: &str,
     program_name: &str,
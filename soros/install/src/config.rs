@@ -1,27 +1,68 @@
 use crate::update_manifest::UpdateManifest;
+use semver::Version;
 use serde_derive::{Deserialize, Serialize};
 use soros_sdk::pubkey::Pubkey;
+use std::collections::BTreeMap;
 use std::fs::{create_dir_all, File};
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 
+/// Release channels, each with its own update manifest
+pub const CHANNELS: &[&str] = &["stable", "beta", "edge"];
+
+fn default_channel() -> String {
+    CHANNELS[0].to_string()
+}
+
+fn default_max_installed_releases() -> usize {
+    3
+}
+
+/// Fields added since the first release are defaulted, so configs written by older installers
+/// still load
 #[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
 pub struct Config {
     pub json_rpc_url: String,
-    pub update_manifest_pubkey: Pubkey,
+    #[serde(default = "default_channel")]
+    pub channel: String,
+    #[serde(default)]
+    pub update_manifest_pubkeys: BTreeMap<String, Pubkey>,
+    /// The single update manifest of older installers, `load` moves it to `channel`
+    #[serde(default, skip_serializing)]
+    update_manifest_pubkey: Option<Pubkey>,
     pub current_update_manifest: Option<UpdateManifest>,
     pub update_poll_secs: u64,
+    /// Releases available to `rollback` to, sorted by version
+    #[serde(default)]
+    pub installed_releases: Vec<UpdateManifest>,
+    #[serde(default = "default_max_installed_releases")]
+    pub max_installed_releases: usize,
+    /// Versions that were rolled back from, `update` won't install them again
+    #[serde(default)]
+    pub rejected_versions: Vec<Version>,
     releases_dir: PathBuf,
     active_release_dir: PathBuf,
 }
 
 impl Config {
-    pub fn new(data_dir: &str, json_rpc_url: &str, update_manifest_pubkey: &Pubkey) -> Self {
+    pub fn new(
+        data_dir: &str,
+        json_rpc_url: &str,
+        channel: &str,
+        update_manifest_pubkey: &Pubkey,
+    ) -> Self {
+        let mut update_manifest_pubkeys = BTreeMap::new();
+        update_manifest_pubkeys.insert(channel.to_string(), *update_manifest_pubkey);
         Self {
             json_rpc_url: json_rpc_url.to_string(),
-            update_manifest_pubkey: *update_manifest_pubkey,
+            channel: channel.to_string(),
+            update_manifest_pubkeys,
+            update_manifest_pubkey: None,
             current_update_manifest: None,
             update_poll_secs: 60, // check for updates once a minute
+            installed_releases: vec![],
+            max_installed_releases: default_max_installed_releases(),
+            rejected_versions: vec![],
             releases_dir: PathBuf::from(data_dir).join("releases"),
             active_release_dir: PathBuf::from(data_dir).join("active_release"),
         }
@@ -29,11 +70,22 @@ impl Config {
 
     fn _load(config_file: &str) -> Result<Self, io::Error> {
         let file = File::open(config_file.to_string())?;
-        let config = serde_yaml::from_reader(file)
+        let config = Self::from_reader(file)
             .map_err(|err| io::Error::new(io::ErrorKind::Other, format!("{:?}", err)))?;
         Ok(config)
     }
 
+    fn from_reader<R: io::Read>(reader: R) -> Result<Self, serde_yaml::Error> {
+        let mut config: Self = serde_yaml::from_reader(reader)?;
+        if let Some(update_manifest_pubkey) = config.update_manifest_pubkey.take() {
+            config
+                .update_manifest_pubkeys
+                .entry(config.channel.clone())
+                .or_insert(update_manifest_pubkey);
+        }
+        Ok(config)
+    }
+
     pub fn load(config_file: &str) -> Result<Self, String> {
         Self::_load(config_file).map_err(|err| format!("Unable to load {}: {:?}", config_file, err))
     }
@@ -67,4 +119,161 @@ impl Config {
     pub fn release_dir(&self, release_sha256: &str) -> PathBuf {
         self.releases_dir.join(release_sha256)
     }
+
+    /// The update manifest of the current channel
+    pub fn update_manifest_pubkey(&self) -> Result<&Pubkey, String> {
+        self.update_manifest_pubkeys
+            .get(&self.channel)
+            .ok_or_else(|| format!("No update manifest for the {} channel", self.channel))
+    }
+
+    pub fn current_version(&self) -> Option<&Version> {
+        self.current_update_manifest
+            .as_ref()
+            .map(|update_manifest| &update_manifest.version)
+    }
+
+    /// Record `update_manifest` as installed, returns the releases that no longer fit and
+    /// should be removed
+    pub fn add_installed_release(
+        &mut self,
+        update_manifest: UpdateManifest,
+    ) -> Vec<UpdateManifest> {
+        self.installed_releases
+            .retain(|release| release.version != update_manifest.version);
+        self.installed_releases.push(update_manifest);
+        self.installed_releases
+            .sort_by(|a, b| a.version.cmp(&b.version));
+
+        let mut removed = vec![];
+        while self.installed_releases.len() > self.max_installed_releases.max(1) {
+            // Drop the oldest release that isn't active
+            let current_version = self.current_version().cloned();
+            let oldest = self
+                .installed_releases
+                .iter()
+                .position(|release| Some(&release.version) != current_version.as_ref())
+                .unwrap();
+            removed.push(self.installed_releases.remove(oldest));
+        }
+        removed
+    }
+
+    pub fn installed_release(&self, version: &Version) -> Option<&UpdateManifest> {
+        self.installed_releases
+            .iter()
+            .find(|release| release.version == *version)
+    }
+
+    /// The newest installed release that is older than the active one
+    pub fn previous_release(&self) -> Option<&UpdateManifest> {
+        let current_version = self.current_version()?;
+        self.installed_releases
+            .iter()
+            .rev()
+            .find(|release| release.version < *current_version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update_manifest(version: &str) -> UpdateManifest {
+        UpdateManifest {
+            version: Version::parse(version).unwrap(),
+            download_sha256: version.to_string(),
+            ..UpdateManifest::default()
+        }
+    }
+
+    #[test]
+    fn test_config_update_manifest_pubkey() {
+        let pubkey = Pubkey::new_rand();
+        let mut config = Config::new("data", "http://127.0.0.1:10099", "beta", &pubkey);
+        assert_eq!(config.update_manifest_pubkey(), Ok(&pubkey));
+        config.channel = "stable".to_string();
+        assert!(config.update_manifest_pubkey().is_err());
+    }
+
+    #[test]
+    fn test_config_load_old_config() {
+        let pubkey = Pubkey::new_rand();
+        let mut old_config = serde_yaml::to_value(Config::new(
+            "data",
+            "http://127.0.0.1:10099",
+            "stable",
+            &pubkey,
+        ))
+        .unwrap();
+        // What an installer without channels or rollback wrote
+        let mapping = old_config.as_mapping_mut().unwrap();
+        for field in &[
+            "channel",
+            "update_manifest_pubkeys",
+            "installed_releases",
+            "max_installed_releases",
+            "rejected_versions",
+        ] {
+            mapping.remove(&(*field).into()).unwrap();
+        }
+        mapping.insert(
+            "update_manifest_pubkey".into(),
+            serde_yaml::to_value(pubkey).unwrap(),
+        );
+        let mut manifest = serde_yaml::to_value(update_manifest("0.1.0")).unwrap();
+        let manifest_mapping = manifest.as_mapping_mut().unwrap();
+        manifest_mapping.remove(&"version".into()).unwrap();
+        manifest_mapping.remove(&"min_version".into()).unwrap();
+        mapping.insert("current_update_manifest".into(), manifest);
+
+        let serialized = serde_yaml::to_string(&old_config).unwrap();
+        let config = Config::from_reader(serialized.as_bytes()).unwrap();
+        assert_eq!(config.channel, "stable");
+        assert_eq!(config.update_manifest_pubkey(), Ok(&pubkey));
+        assert_eq!(config.max_installed_releases, 3);
+        assert!(config.installed_releases.is_empty());
+        assert_eq!(config.current_version(), Some(&Version::new(0, 0, 0)));
+
+        // Saved again it's in the new format
+        let serialized = serde_yaml::to_string(&config).unwrap();
+        assert!(!serialized.contains("update_manifest_pubkey:"));
+        assert_eq!(Config::from_reader(serialized.as_bytes()).unwrap(), config);
+    }
+
+    #[test]
+    fn test_config_installed_releases() {
+        let pubkey = Pubkey::default();
+        let mut config = Config::new("data", "http://127.0.0.1:10099", "stable", &pubkey);
+        config.max_installed_releases = 2;
+        assert_eq!(config.previous_release(), None);
+
+        for version in &["0.1.0", "0.2.0"] {
+            config.current_update_manifest = Some(update_manifest(version));
+            assert!(config
+                .add_installed_release(update_manifest(version))
+                .is_empty());
+        }
+        assert_eq!(config.previous_release(), Some(&update_manifest("0.1.0")));
+
+        config.current_update_manifest = Some(update_manifest("0.3.0"));
+        assert_eq!(
+            config.add_installed_release(update_manifest("0.3.0")),
+            vec![update_manifest("0.1.0")]
+        );
+        assert_eq!(config.previous_release(), Some(&update_manifest("0.2.0")));
+
+        // The active release is kept even when it's the oldest one
+        config.current_update_manifest = Some(update_manifest("0.2.0"));
+        assert_eq!(
+            config.add_installed_release(update_manifest("0.4.0")),
+            vec![update_manifest("0.3.0")]
+        );
+        assert_eq!(config.previous_release(), None);
+        let version = Version::parse("0.4.0").unwrap();
+        assert_eq!(
+            config.installed_release(&version),
+            Some(&update_manifest("0.4.0"))
+        );
+    }
 }
@@ -17,6 +17,13 @@ fn url_validator(url: String) -> Result<(), String> {
     }
 }
 
+fn version_validator(version: String) -> Result<(), String> {
+    match semver::Version::parse(&version) {
+        Ok(_) => Ok(()),
+        Err(err) => Err(format!("{:?}", err)),
+    }
+}
+
 fn pubkey_validator(pubkey: String) -> Result<(), String> {
     match pubkey.parse::<Pubkey>() {
         Ok(_) => Ok(()),
@@ -75,6 +82,15 @@ fn main() -> Result<(), String> {
                         .long("no-modify-path")
                         .help("Don't configure the PATH environment variable"),
                 )
+                .arg(
+                    Arg::with_name("channel")
+                        .long("channel")
+                        .value_name("CHANNEL")
+                        .takes_value(true)
+                        .possible_values(config::CHANNELS)
+                        .default_value("stable")
+                        .help("Release channel to follow"),
+                )
                 .arg({
                     let arg = Arg::with_name("update_manifest_pubkey")
                         .short("p")
@@ -143,6 +159,23 @@ fn main() -> Result<(), String> {
                         .index(2)
                         .required(true)
                         .help("Keypair file for the update manifest (/path/to/keypair.json)"),
+                )
+                .arg(
+                    Arg::with_name("version")
+                        .long("version")
+                        .value_name("SEMVER")
+                        .takes_value(true)
+                        .required(true)
+                        .validator(version_validator)
+                        .help("Version of the release"),
+                )
+                .arg(
+                    Arg::with_name("min_version")
+                        .long("min-version")
+                        .value_name("SEMVER")
+                        .takes_value(true)
+                        .validator(version_validator)
+                        .help("Oldest installed version that may update to this release"),
                 ),
         )
         .subcommand(
@@ -150,6 +183,44 @@ fn main() -> Result<(), String> {
                 .about("checks for an update, and if available downloads and applies it")
                 .setting(AppSettings::DisableVersion),
         )
+        .subcommand(
+            SubCommand::with_name("list")
+                .about("lists the installed releases")
+                .setting(AppSettings::DisableVersion),
+        )
+        .subcommand(
+            SubCommand::with_name("rollback")
+                .about("switches back to a previously installed release")
+                .after_help("`update` won't install the release that was rolled back from again")
+                .setting(AppSettings::DisableVersion)
+                .arg(
+                    Arg::with_name("version")
+                        .index(1)
+                        .validator(version_validator)
+                        .help("installed version to switch to [default: the previous release]"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("channel")
+                .about("switches the release channel updates come from")
+                .setting(AppSettings::DisableVersion)
+                .arg(
+                    Arg::with_name("channel")
+                        .index(1)
+                        .required(true)
+                        .possible_values(config::CHANNELS)
+                        .help("release channel to follow"),
+                )
+                .arg(
+                    Arg::with_name("update_manifest_pubkey")
+                        .short("p")
+                        .long("pubkey")
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .validator(pubkey_validator)
+                        .help("Public key of the channel's update manifest"),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("run")
                 .about("Runs a program while periodically checking and applying software updates")
@@ -182,11 +253,13 @@ fn main() -> Result<(), String> {
                 .unwrap();
             let data_dir = matches.value_of("data_dir").unwrap();
             let no_modify_path = matches.is_present("no_modify_path");
+            let channel = matches.value_of("channel").unwrap();
 
             command::init(
                 config_file,
                 data_dir,
                 json_rpc_url,
+                channel,
                 &update_manifest_pubkey,
                 no_modify_path,
             )
@@ -201,14 +274,27 @@ fn main() -> Result<(), String> {
             let download_url = matches.value_of("download_url").unwrap();
             let update_manifest_keypair_file =
                 matches.value_of("update_manifest_keypair_file").unwrap();
+            let version = matches.value_of("version").unwrap();
+            let min_version = matches.value_of("min_version");
             command::deploy(
                 json_rpc_url,
                 from_keypair_file,
                 download_url,
                 update_manifest_keypair_file,
+                version,
+                min_version,
             )
         }
         ("update", Some(_matches)) => command::update(config_file).map(|_| ()),
+        ("list", Some(_matches)) => command::list(config_file),
+        ("rollback", Some(matches)) => command::rollback(config_file, matches.value_of("version")),
+        ("channel", Some(matches)) => {
+            let channel = matches.value_of("channel").unwrap();
+            let update_manifest_pubkey = matches
+                .value_of("update_manifest_pubkey")
+                .map(|pubkey| pubkey.parse::<Pubkey>().unwrap());
+            command::channel(config_file, channel, update_manifest_pubkey.as_ref())
+        }
         ("run", Some(matches)) => {
             let program_name = matches.value_of("program_name").unwrap();
             let program_arguments = matches
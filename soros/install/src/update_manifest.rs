@@ -1,3 +1,4 @@
+use semver::Version;
 use serde_derive::{Deserialize, Serialize};
 use soros_config_api::ConfigState;
 use soros_sdk::pubkey::Pubkey;
@@ -5,12 +6,58 @@ use soros_sdk::signature::{Signable, Signature};
 use std::error;
 use std::io;
 
-/// Information required to download and apply a given update
-#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+/// Information required to download and apply a given update.  Releases recorded by installers
+/// that predate versions have none, they're taken to be older than any versioned release.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct UpdateManifest {
     pub timestamp_secs: u64, // When the release was deployed in seconds since UNIX EPOCH
     pub download_url: String, // Download URL to the release tar.bz2
     pub download_sha256: String, // SHA256 digest of the release tar.bz2 file
+    #[serde(default = "unversioned")]
+    pub version: Version, // Version of the release
+    #[serde(default)]
+    pub min_version: Option<Version>, // Oldest installed version that may update to this release
+}
+
+fn unversioned() -> Version {
+    Version::new(0, 0, 0)
+}
+
+impl Default for UpdateManifest {
+    fn default() -> Self {
+        Self {
+            timestamp_secs: 0,
+            download_url: String::default(),
+            download_sha256: String::default(),
+            version: unversioned(),
+            min_version: None,
+        }
+    }
+}
+
+impl UpdateManifest {
+    /// Check that an installation of `installed`, if any, may update to this release
+    pub fn check_update_from(&self, installed: Option<&Version>) -> Result<(), String> {
+        let installed = match installed {
+            Some(installed) => installed,
+            None => return Ok(()),
+        };
+        if self.version <= *installed {
+            Err(format!(
+                "Unable to update from {} to the older version {}",
+                installed, self.version
+            ))?;
+        }
+        if let Some(ref min_version) = self.min_version {
+            if installed < min_version {
+                Err(format!(
+                    "Unable to update from {} to {}, update to at least {} first",
+                    installed, self.version, min_version
+                ))?;
+            }
+        }
+        Ok(())
+    }
 }
 
 /// Userdata of an Update Manifest program Account.
@@ -56,6 +103,66 @@ impl ConfigState for SignedUpdateManifest {
     fn max_space() -> u64 {
         // TODO: Use a fully populated manifest to compute a better value
         //      bincode::serialized_size(&Self::default()).unwrap()
-        256
+        512
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update_manifest(version: &str, min_version: Option<&str>) -> UpdateManifest {
+        UpdateManifest {
+            version: Version::parse(version).unwrap(),
+            min_version: min_version.map(|min_version| Version::parse(min_version).unwrap()),
+            ..UpdateManifest::default()
+        }
+    }
+
+    #[test]
+    fn test_check_update_from() {
+        let installed = Version::parse("0.2.0").unwrap();
+        assert!(update_manifest("0.1.0", None)
+            .check_update_from(None)
+            .is_ok());
+        assert!(update_manifest("0.3.0", None)
+            .check_update_from(Some(&installed))
+            .is_ok());
+        assert!(update_manifest("0.3.0", Some("0.2.0"))
+            .check_update_from(Some(&installed))
+            .is_ok());
+        assert!(update_manifest("0.2.0", None)
+            .check_update_from(Some(&installed))
+            .is_err());
+        assert!(update_manifest("0.1.9", None)
+            .check_update_from(Some(&installed))
+            .is_err());
+        assert!(update_manifest("0.4.0", Some("0.3.0"))
+            .check_update_from(Some(&installed))
+            .is_err());
+    }
+
+    #[test]
+    fn test_signed_update_manifest() {
+        use soros_sdk::signature::{Keypair, KeypairUtil};
+
+        let keypair = Keypair::new();
+        let mut signed_update_manifest = SignedUpdateManifest {
+            manifest: update_manifest("0.3.0", Some("0.2.0")),
+            account_pubkey: keypair.pubkey(),
+            ..SignedUpdateManifest::default()
+        };
+        signed_update_manifest.sign(&keypair);
+
+        let data = bincode::serialize(&signed_update_manifest).unwrap();
+        assert!(data.len() as u64 <= SignedUpdateManifest::max_space());
+        let deserialized = SignedUpdateManifest::deserialize(&keypair.pubkey(), &data).unwrap();
+        assert_eq!(deserialized, signed_update_manifest);
+
+        // The version is covered by the signature
+        let mut tampered = signed_update_manifest;
+        tampered.manifest.min_version = None;
+        let data = bincode::serialize(&tampered).unwrap();
+        assert!(SignedUpdateManifest::deserialize(&keypair.pubkey(), &data).is_err());
     }
 }
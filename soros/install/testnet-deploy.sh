@@ -26,7 +26,7 @@ if [[ -n $SOROS_INSTALL_UPDATE_MANIFEST_KEYPAIR_x86_64_unknown_linux_gnu ]]; the
 fi
 
 case $CHANNEL in
-nightly|beta)
+edge|beta)
   URL=https://nightly.bitconch.io
   ;;
 stable)
@@ -42,6 +42,6 @@ localhost)
 esac
 
 set -x
-soros-install deploy --url "$URL" \
+soros-install deploy --url "$URL" --version "${TAG#v}" \
   https://github.com/caesarchad/rustelo-rust/releases/download/"$TAG"/soros-release-x86_64-unknown-linux-gnu.tar.bz2 \
   update_manifest_keypair.json
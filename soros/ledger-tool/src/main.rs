@@ -2,8 +2,10 @@ use clap::{crate_description, crate_name, crate_version, App, Arg, SubCommand};
 use soros::blocktree::Blocktree;
 use soros::blocktree_processor::process_blocktree;
 use soros_sdk::genesis_block::GenesisBlock;
+use soros_sdk::multi_sha256;
 use std::io::{stdout, Write};
 use std::process::exit;
+use std::time::Instant;
 
 fn main() {
     soros_logger::setup();
@@ -111,14 +113,21 @@ fn main() {
             }
             stdout().write_all(b"\n]}\n").expect("close array");
         }
-        ("verify", _) => match process_blocktree(&genesis_block, &blocktree, None) {
-            Ok((_bank_forks, bank_forks_info, _)) => {
-                println!("{:?}", bank_forks_info);
+        ("verify", _) => {
+            if multi_sha256::simd_enabled() {
+                println!("Verifying PoH {} entries at a time", multi_sha256::LANES);
             }
-            Err(err) => {
-                eprintln!("Ledger verification failed: {:?}", err);
+            let start = Instant::now();
+            match process_blocktree(&genesis_block, &blocktree, None) {
+                Ok((_bank_forks, bank_forks_info, _)) => {
+                    println!("{:?}", bank_forks_info);
+                    println!("Verified in {:?}", start.elapsed());
+                }
+                Err(err) => {
+                    eprintln!("Ledger verification failed: {:?}", err);
+                }
             }
-        },
+        }
         ("", _) => {
             eprintln!("{}", matches.usage());
             exit(1);
@@ -0,0 +1,183 @@
+//! Launches a local test cluster and hands its endpoints to whoever drives it.
+//!
+//! The endpoints are printed as one line of JSON once the cluster is up, then commands are read
+//! from stdin one per line and each one is answered with one line of JSON on stdout:
+//! * `endpoints` - the current endpoints of the cluster
+//! * `kill <PUBKEY>` - stop a node, its ledger is kept
+//! * `restart <PUBKEY>` - start a node back up on new ports, from its ledger
+//! * `exit` - tear the cluster down, as does closing stdin
+//!
+//! Logs go to stderr so stdout only carries JSON.
+
+use clap::{crate_description, crate_name, crate_version, value_t_or_exit, App, Arg};
+use serde_derive::Deserialize;
+use serde_json::json;
+use soros::cluster::Cluster;
+use soros::local_cluster::{ClusterConfig, LocalCluster};
+use soros_sdk::pubkey::Pubkey;
+use std::fs::File;
+use std::io::{stdin, stdout, BufRead, Write};
+use std::process::exit;
+use std::str::FromStr;
+
+/// Cluster described by the `--config` file, missing fields take their defaults
+#[derive(Deserialize, Debug)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+struct TestClusterConfig {
+    /// Stake of each voting node, the first one is the bootstrap leader
+    node_stakes: Vec<u64>,
+    /// Number of nodes that are unstaked and not voting
+    num_listeners: u64,
+    cluster_dif: u64,
+    ticks_per_slot: u64,
+    slots_per_epoch: u64,
+    drone: bool,
+}
+
+impl Default for TestClusterConfig {
+    fn default() -> Self {
+        let cluster_config = ClusterConfig::default();
+        Self {
+            node_stakes: vec![100],
+            num_listeners: 0,
+            cluster_dif: 100_000_000,
+            ticks_per_slot: cluster_config.ticks_per_slot,
+            slots_per_epoch: cluster_config.slots_per_epoch,
+            drone: true,
+        }
+    }
+}
+
+impl TestClusterConfig {
+    fn cluster_config(&self) -> ClusterConfig {
+        let mut cluster_config = ClusterConfig {
+            num_listeners: self.num_listeners,
+            node_stakes: self.node_stakes.clone(),
+            cluster_dif: self.cluster_dif,
+            ticks_per_slot: self.ticks_per_slot,
+            slots_per_epoch: self.slots_per_epoch,
+            start_drone: self.drone,
+            ..ClusterConfig::default()
+        };
+        cluster_config
+            .fullnode_config
+            .rpc_config
+            .enable_fullnode_exit = true;
+        cluster_config
+    }
+}
+
+fn print_json(value: &serde_json::Value) {
+    let mut stdout = stdout();
+    writeln!(stdout, "{}", value).expect("write stdout");
+    stdout.flush().expect("flush stdout");
+}
+
+/// Runs one command, `None` means the cluster should be torn down
+fn run_command(cluster: &mut LocalCluster, line: &str) -> Option<serde_json::Value> {
+    let mut words = line.split_whitespace();
+    let command = words.next().unwrap_or("");
+    let pubkey = words.next().map(Pubkey::from_str);
+
+    let node = |pubkey| -> Result<Pubkey, String> {
+        match pubkey {
+            Some(Ok(pubkey)) => Ok(pubkey),
+            Some(Err(_)) => Err("invalid pubkey".to_string()),
+            None => Err(format!("usage: {} <PUBKEY>", command)),
+        }
+    };
+    let result = match command {
+        "endpoints" => Ok(()),
+        "kill" | "restart" => node(pubkey).and_then(|pubkey| {
+            let is_node = cluster.fullnode_infos.contains_key(&pubkey)
+                || cluster.listener_infos.contains_key(&pubkey);
+            if !is_node {
+                Err(format!("no node {}", pubkey))
+            } else if command == "kill" {
+                cluster.kill_node(pubkey);
+                Ok(())
+            } else {
+                cluster.restart_node(pubkey);
+                Ok(())
+            }
+        }),
+        "exit" => return None,
+        "" => Err("empty command".to_string()),
+        _ => Err(format!("unknown command: {}", command)),
+    };
+    Some(match result {
+        Ok(()) => json!({ "ok": cluster.endpoints() }),
+        Err(err) => json!({ "error": err }),
+    })
+}
+
+fn main() {
+    soros_logger::setup();
+    let matches = App::new(crate_name!())
+        .about(crate_description!())
+        .version(crate_version!())
+        .arg(
+            Arg::with_name("config")
+                .short("c")
+                .long("config")
+                .value_name("FILE")
+                .takes_value(true)
+                .conflicts_with("num_nodes")
+                .help("JSON file describing the cluster"),
+        )
+        .arg(
+            Arg::with_name("num_nodes")
+                .short("n")
+                .long("num-nodes")
+                .value_name("NUM")
+                .takes_value(true)
+                .help("Number of equally staked voting nodes [default: 1]"),
+        )
+        .arg(
+            Arg::with_name("no_drone")
+                .long("no-drone")
+                .help("Don't run a drone"),
+        )
+        .get_matches();
+
+    let mut config = match matches.value_of("config") {
+        Some(path) => {
+            let file = File::open(path).unwrap_or_else(|err| {
+                eprintln!("Unable to open {}: {:?}", path, err);
+                exit(1);
+            });
+            serde_json::from_reader(file).unwrap_or_else(|err| {
+                eprintln!("Invalid config {}: {:?}", path, err);
+                exit(1);
+            })
+        }
+        None => TestClusterConfig::default(),
+    };
+    if matches.is_present("num_nodes") {
+        let num_nodes = value_t_or_exit!(matches, "num_nodes", usize);
+        config.node_stakes = vec![config.node_stakes[0]; num_nodes];
+    }
+    if matches.is_present("no_drone") {
+        config.drone = false;
+    }
+    if config.node_stakes.is_empty() {
+        eprintln!("The cluster needs at least one voting node");
+        exit(1);
+    }
+
+    let mut cluster = LocalCluster::new(&config.cluster_config());
+    print_json(&json!(cluster.endpoints()));
+
+    let stdin = stdin();
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        match run_command(&mut cluster, &line) {
+            Some(response) => print_json(&response),
+            None => break,
+        }
+    }
+    cluster.close();
+}
@@ -0,0 +1,43 @@
+use assert_cmd::prelude::*;
+use soros::local_cluster::ClusterEndpoints;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+
+#[test]
+fn bad_arguments() {
+    let output = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .args(&["--config", "missing_config.json"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn endpoints_and_exit() {
+    let mut child = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .args(&["--num-nodes", "1", "--no-drone"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+
+    let mut line = String::new();
+    stdout.read_line(&mut line).unwrap();
+    let endpoints: ClusterEndpoints = serde_json::from_str(&line).unwrap();
+    assert_eq!(endpoints.nodes.len(), 1);
+    assert_eq!(endpoints.drone, None);
+    assert!(endpoints.nodes[0].running);
+
+    writeln!(stdin, "kill notapubkey").unwrap();
+    line.clear();
+    stdout.read_line(&mut line).unwrap();
+    let response: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert!(response["error"].is_string());
+
+    writeln!(stdin, "exit").unwrap();
+    assert!(child.wait().unwrap().success());
+}
@@ -2,9 +2,13 @@
 pub mod counter;
 
 mod metrics;
+pub mod prometheus;
 
 pub use crate::metrics::flush;
 pub use crate::metrics::query;
 pub use crate::metrics::set_panic_hook;
+pub use crate::metrics::set_prometheus_writer;
+pub use crate::metrics::set_writer;
 pub use crate::metrics::submit;
+pub use crate::metrics::MetricsWriter;
 pub use influx_db_client as influxdb;
@@ -1,11 +1,15 @@
-//! The `metrics` module enables sending measurements to an InfluxDB instance
+//! The `metrics` module enables sending measurements to an InfluxDB instance, or to any other
+//! `MetricsWriter` installed with `set_writer`
 
+use crate::prometheus::PrometheusMetricsWriter;
 use influx_db_client as influxdb;
 use lazy_static::lazy_static;
 use log::*;
 use soros_sdk::hash::hash;
 use soros_sdk::timing;
 use std::env;
+use std::io;
+use std::net::SocketAddr;
 use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
 use std::sync::{Arc, Barrier, Mutex, Once, ONCE_INIT};
 use std::thread;
@@ -26,17 +30,17 @@ lazy_static! {
     };
 }
 
-#[derive(Debug)]
 enum MetricsCommand {
     Submit(influxdb::Point),
     Flush(Arc<Barrier>),
+    SetWriter(Arc<MetricsWriter + Send + Sync>, Duration),
 }
 
 struct MetricsAgent {
     sender: Sender<MetricsCommand>,
 }
 
-trait MetricsWriter {
+pub trait MetricsWriter {
     // Write the points and empty the vector.  Called on the internal
     // MetricsAgent worker thread.
     fn write(&self, points: Vec<influxdb::Point>);
@@ -105,6 +109,8 @@ impl MetricsAgent {
         write_frequency: Duration,
     ) {
         trace!("run: enter");
+        let mut writer = writer.clone();
+        let mut write_frequency = write_frequency;
         let mut last_write_time = Instant::now();
         let mut points = Vec::new();
 
@@ -124,6 +130,16 @@ impl MetricsAgent {
                         debug!("run: submit {:?}", point);
                         points.push(point);
                     }
+                    MetricsCommand::SetWriter(new_writer, new_write_frequency) => {
+                        debug!("run: set writer");
+                        if !points.is_empty() {
+                            writer.write(points);
+                            points = Vec::new();
+                        }
+                        writer = new_writer;
+                        write_frequency = new_write_frequency;
+                        last_write_time = Instant::now();
+                    }
                 },
                 Err(RecvTimeoutError::Timeout) => {
                     trace!("run: receive timeout");
@@ -154,6 +170,12 @@ impl MetricsAgent {
         self.sender.send(MetricsCommand::Submit(point)).unwrap();
     }
 
+    pub fn set_writer(&self, writer: Arc<MetricsWriter + Send + Sync>, write_frequency: Duration) {
+        self.sender
+            .send(MetricsCommand::SetWriter(writer, write_frequency))
+            .unwrap();
+    }
+
     pub fn flush(&self) {
         debug!("Flush");
         let barrier = Arc::new(Barrier::new(2));
@@ -191,6 +213,23 @@ pub fn submit(point: influxdb::Point) {
     agent.submit(point);
 }
 
+/// Sends all points submitted from now on to `writer` instead of InfluxDB, in batches every
+/// `write_frequency`
+pub fn set_writer(writer: Arc<MetricsWriter + Send + Sync>, write_frequency: Duration) {
+    let agent_mutex = get_singleton_agent();
+    let agent = agent_mutex.lock().unwrap();
+    agent.set_writer(writer, write_frequency);
+}
+
+/// Serves metrics on `addr` for Prometheus to scrape instead of sending them to InfluxDB,
+/// returns the address actually bound
+pub fn set_prometheus_writer(addr: &SocketAddr) -> io::Result<SocketAddr> {
+    let writer = PrometheusMetricsWriter::new(addr)?;
+    let local_addr = *writer.local_addr();
+    set_writer(Arc::new(writer), Duration::from_secs(1));
+    Ok(local_addr)
+}
+
 fn get_env_settings() -> Result<(String, String, String, String), env::VarError> {
     let host =
         env::var("INFLUX_HOST").unwrap_or_else(|_| "https://metrics.soros.com:8086".to_string());
@@ -350,6 +389,21 @@ mod test {
         assert_eq!(writer.points_written(), 42);
     }
 
+    #[test]
+    fn test_set_writer() {
+        let writer = Arc::new(MockMetricsWriter::new());
+        let new_writer = Arc::new(MockMetricsWriter::new());
+        let agent = MetricsAgent::new(writer.clone(), Duration::from_secs(10));
+
+        agent.submit(influxdb::Point::new("point 1"));
+        agent.set_writer(new_writer.clone(), Duration::from_secs(10));
+        agent.submit(influxdb::Point::new("point 2"));
+        agent.submit(influxdb::Point::new("point 3"));
+        agent.flush();
+        assert_eq!(writer.points_written(), 1);
+        assert_eq!(new_writer.points_written(), 2);
+    }
+
     #[test]
     fn test_flush_before_drop() {
         let writer = Arc::new(MockMetricsWriter::new());
@@ -0,0 +1,396 @@
+//! The `prometheus` module aggregates submitted points in-process and serves them on a local
+//! `/metrics` HTTP endpoint in the Prometheus text exposition format.
+//!
+//! Points are mapped to Prometheus metrics as follows:
+//! * `counter-<NAME>` points, as submitted by `Counter`, accumulate into a `<PREFIX>_<NAME>_total`
+//!   counter
+//! * numeric fields ending in `_us` or `_ms` are observed into a
+//!   `<PREFIX>_<MEASUREMENT>_<FIELD>` histogram
+//! * any other numeric or boolean field sets a `<PREFIX>_<MEASUREMENT>_<FIELD>` gauge
+//!
+//! Tags become labels, string fields are dropped.  `<PREFIX>` is `soros` unless the writer was
+//! started `with_prefix`.
+
+use crate::metrics::MetricsWriter;
+use influx_db_client as influxdb;
+use log::*;
+use std::collections::BTreeMap;
+use std::fmt::Write as FmtWrite;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+const COUNTER_PREFIX: &str = "counter-";
+/// Most bytes read of a scrape request, the request line and headers
+const MAX_REQUEST_BYTES: u64 = 8 * 1024;
+/// How long a scraper gets to send its request and read the response
+const SCRAPE_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_METRIC_PREFIX: &str = "soros";
+/// Upper bounds of the histogram buckets, a `+Inf` bucket is always added
+const HISTOGRAM_BUCKETS: &[f64] = &[
+    1.0,
+    5.0,
+    10.0,
+    50.0,
+    100.0,
+    500.0,
+    1_000.0,
+    5_000.0,
+    10_000.0,
+    50_000.0,
+    100_000.0,
+    500_000.0,
+    1_000_000.0,
+];
+
+/// Metric name and its rendered labels
+type MetricKey = (String, String);
+
+#[derive(Default, Debug, Clone, PartialEq)]
+struct Histogram {
+    bucket_counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        if self.bucket_counts.is_empty() {
+            self.bucket_counts = vec![0; HISTOGRAM_BUCKETS.len()];
+        }
+        for (bucket_count, upper_bound) in self.bucket_counts.iter_mut().zip(HISTOGRAM_BUCKETS) {
+            if value <= *upper_bound {
+                *bucket_count += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+#[derive(Default, Debug)]
+struct Registry {
+    prefix: String,
+    counters: BTreeMap<MetricKey, f64>,
+    gauges: BTreeMap<MetricKey, f64>,
+    histograms: BTreeMap<MetricKey, Histogram>,
+}
+
+/// Replace the characters Prometheus doesn't allow in metric and label names with `_`
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+fn metric_name(prefix: &str, name: &str) -> String {
+    format!("{}_{}", sanitize(prefix), sanitize(name))
+}
+
+fn labels(point: &influxdb::Point) -> String {
+    let mut tags: Vec<_> = point
+        .tags
+        .iter()
+        .map(|(name, value)| {
+            let value = match value {
+                influxdb::Value::String(value) => value.clone(),
+                influxdb::Value::Integer(value) => value.to_string(),
+                influxdb::Value::Float(value) => value.to_string(),
+                influxdb::Value::Boolean(value) => value.to_string(),
+            };
+            (sanitize(name), value)
+        })
+        .collect();
+    if tags.is_empty() {
+        return String::new();
+    }
+    tags.sort();
+    let tags: Vec<_> = tags
+        .into_iter()
+        .map(|(name, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{}=\"{}\"", name, value)
+        })
+        .collect();
+    format!("{{{}}}", tags.join(","))
+}
+
+fn numeric_value(value: &influxdb::Value) -> Option<f64> {
+    match value {
+        influxdb::Value::Integer(value) => Some(*value as f64),
+        influxdb::Value::Float(value) => Some(*value),
+        influxdb::Value::Boolean(value) => Some(if *value { 1.0 } else { 0.0 }),
+        influxdb::Value::String(_) => None,
+    }
+}
+
+impl Registry {
+    fn new(prefix: &str) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+            ..Self::default()
+        }
+    }
+
+    fn record(&mut self, point: &influxdb::Point) {
+        let labels = labels(point);
+        if point.measurement.starts_with(COUNTER_PREFIX) {
+            let name =
+                metric_name(&self.prefix, &point.measurement[COUNTER_PREFIX.len()..]) + "_total";
+            if let Some(count) = point.fields.get("count").and_then(numeric_value) {
+                *self.counters.entry((name, labels)).or_insert(0.0) += count;
+            }
+            return;
+        }
+        for (field, value) in &point.fields {
+            let value = match numeric_value(value) {
+                Some(value) => value,
+                None => continue,
+            };
+            let name = metric_name(&self.prefix, &format!("{}_{}", point.measurement, field));
+            let key = (name, labels.clone());
+            if field.ends_with("_us") || field.ends_with("_ms") {
+                self.histograms.entry(key).or_default().observe(value);
+            } else {
+                self.gauges.insert(key, value);
+            }
+        }
+    }
+
+    fn render(&self) -> String {
+        fn render_type(out: &mut String, last_name: &mut String, name: &str, metric_type: &str) {
+            if *last_name != name {
+                writeln!(out, "# TYPE {} {}", name, metric_type).unwrap();
+                *last_name = name.to_string();
+            }
+        }
+
+        let mut out = String::new();
+        let mut last_name = String::new();
+        for ((name, labels), value) in &self.counters {
+            render_type(&mut out, &mut last_name, name, "counter");
+            writeln!(out, "{}{} {}", name, labels, value).unwrap();
+        }
+        for ((name, labels), value) in &self.gauges {
+            render_type(&mut out, &mut last_name, name, "gauge");
+            writeln!(out, "{}{} {}", name, labels, value).unwrap();
+        }
+        for ((name, labels), histogram) in &self.histograms {
+            render_type(&mut out, &mut last_name, name, "histogram");
+            // `le` goes last, after the point's own labels
+            let bucket_labels = |le: &str| {
+                if labels.is_empty() {
+                    format!("{{le=\"{}\"}}", le)
+                } else {
+                    format!("{},le=\"{}\"}}", &labels[..labels.len() - 1], le)
+                }
+            };
+            let buckets = histogram.bucket_counts.iter().zip(HISTOGRAM_BUCKETS);
+            for (bucket_count, upper_bound) in buckets {
+                let le = upper_bound.to_string();
+                writeln!(
+                    out,
+                    "{}_bucket{} {}",
+                    name,
+                    bucket_labels(&le),
+                    bucket_count
+                )
+                .unwrap();
+            }
+            writeln!(
+                out,
+                "{}_bucket{} {}",
+                name,
+                bucket_labels("+Inf"),
+                histogram.count
+            )
+            .unwrap();
+            writeln!(out, "{}_sum{} {}", name, labels, histogram.sum).unwrap();
+            writeln!(out, "{}_count{} {}", name, labels, histogram.count).unwrap();
+        }
+        out
+    }
+}
+
+/// A `MetricsWriter` that serves the aggregated points for Prometheus to scrape
+pub struct PrometheusMetricsWriter {
+    registry: Arc<Mutex<Registry>>,
+    local_addr: SocketAddr,
+    _thread_hdl: JoinHandle<()>,
+}
+
+impl PrometheusMetricsWriter {
+    /// Start serving `/metrics` on `addr`
+    pub fn new(addr: &SocketAddr) -> io::Result<Self> {
+        Self::with_prefix(addr, DEFAULT_METRIC_PREFIX)
+    }
+
+    /// Start serving `/metrics` on `addr`, with every metric name starting with `<prefix>_`
+    pub fn with_prefix(addr: &SocketAddr, prefix: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let registry = Arc::new(Mutex::new(Registry::new(prefix)));
+        let thread_registry = registry.clone();
+        let thread_hdl = thread::Builder::new()
+            .name("soros-metrics-prometheus".to_string())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    let result = stream.and_then(|stream| Self::serve(stream, &thread_registry));
+                    if let Err(err) = result {
+                        debug!("prometheus scrape failed: {:?}", err);
+                    }
+                }
+            })?;
+        info!("serving Prometheus metrics on {}", local_addr);
+        Ok(Self {
+            registry,
+            local_addr,
+            _thread_hdl: thread_hdl,
+        })
+    }
+
+    pub fn local_addr(&self) -> &SocketAddr {
+        &self.local_addr
+    }
+
+    fn serve(stream: TcpStream, registry: &Mutex<Registry>) -> io::Result<()> {
+        // Scrapes are served one at a time, a stalled scraper mustn't hold up the others
+        stream.set_read_timeout(Some(SCRAPE_TIMEOUT))?;
+        stream.set_write_timeout(Some(SCRAPE_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.take(MAX_REQUEST_BYTES));
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        // Drain the headers
+        let mut header = String::new();
+        while reader.read_line(&mut header)? > 2 {
+            header.clear();
+        }
+
+        let mut stream = reader.into_inner().into_inner();
+        let mut request = request_line.split_whitespace();
+        let (method, path) = (request.next(), request.next());
+        if method == Some("GET") && path == Some("/metrics") {
+            let body = registry.lock().unwrap().render();
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\n\
+                 Content-Type: text/plain; version=0.0.4\r\n\
+                 Content-Length: {}\r\n\
+                 Connection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+        } else {
+            write!(
+                stream,
+                "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            )
+        }
+    }
+}
+
+impl MetricsWriter for PrometheusMetricsWriter {
+    fn write(&self, points: Vec<influxdb::Point>) {
+        let mut registry = self.registry.lock().unwrap();
+        for point in &points {
+            registry.record(point);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metric_name() {
+        assert_eq!(
+            metric_name(DEFAULT_METRIC_PREFIX, "banking_stage-process_packets"),
+            "soros_banking_stage_process_packets"
+        );
+        assert_eq!(metric_name("buffett", "replay"), "buffett_replay");
+    }
+
+    #[test]
+    fn test_registry_counter() {
+        let mut registry = Registry::new(DEFAULT_METRIC_PREFIX);
+        let point = influxdb::Point::new("counter-bank-process_transactions")
+            .add_field("count", influxdb::Value::Integer(3))
+            .add_field("host_id", influxdb::Value::String("host".to_string()))
+            .to_owned();
+        registry.record(&point);
+        registry.record(&point);
+        assert_eq!(
+            registry.render(),
+            "# TYPE soros_bank_process_transactions_total counter\n\
+             soros_bank_process_transactions_total 6\n"
+        );
+    }
+
+    #[test]
+    fn test_registry_gauge_and_histogram() {
+        let mut registry = Registry::new(DEFAULT_METRIC_PREFIX);
+        for (slot, elapsed_ms) in &[(1, 3), (2, 70)] {
+            let point = influxdb::Point::new("replay")
+                .add_tag("id", influxdb::Value::String("a\"b".to_string()))
+                .add_field("slot", influxdb::Value::Integer(*slot))
+                .add_field("elapsed_ms", influxdb::Value::Integer(*elapsed_ms))
+                .to_owned();
+            registry.record(&point);
+        }
+        let rendered = registry.render();
+        let expected = [
+            "# TYPE soros_replay_slot gauge\n",
+            "soros_replay_slot{id=\"a\\\"b\"} 2\n",
+            "# TYPE soros_replay_elapsed_ms histogram\n",
+            "soros_replay_elapsed_ms_bucket{id=\"a\\\"b\",le=\"1\"} 0\n",
+            "soros_replay_elapsed_ms_bucket{id=\"a\\\"b\",le=\"5\"} 1\n",
+            "soros_replay_elapsed_ms_bucket{id=\"a\\\"b\",le=\"100\"} 2\n",
+            "soros_replay_elapsed_ms_bucket{id=\"a\\\"b\",le=\"+Inf\"} 2\n",
+            "soros_replay_elapsed_ms_sum{id=\"a\\\"b\"} 73\n",
+            "soros_replay_elapsed_ms_count{id=\"a\\\"b\"} 2\n",
+        ];
+        for line in expected.iter() {
+            assert!(
+                rendered.contains(line),
+                "{} missing from {}",
+                line,
+                rendered
+            );
+        }
+    }
+
+    #[test]
+    fn test_prometheus_metrics_writer_serves_metrics() {
+        let writer = PrometheusMetricsWriter::new(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        writer.write(vec![influxdb::Point::new("counter-test")
+            .add_field("count", influxdb::Value::Integer(1))
+            .to_owned()]);
+
+        let get = |path: &str| {
+            let mut stream = TcpStream::connect(writer.local_addr()).unwrap();
+            write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        let response = get("/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with("# TYPE soros_test_total counter\nsoros_test_total 1\n"));
+        assert!(get("/").starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+        // Endless headers are cut off instead of being read until the scraper stops
+        let mut stream = TcpStream::connect(writer.local_addr()).unwrap();
+        let mut request = "GET /metrics HTTP/1.1\r\nX: ".to_string();
+        request += &"x".repeat(MAX_REQUEST_BYTES as usize - request.len());
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+}
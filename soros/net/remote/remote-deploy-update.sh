@@ -30,8 +30,11 @@ loadConfigFile
 
 PATH="$HOME"/.cargo/bin:"$PATH"
 
+# The soros-install on the PATH is the one from the release being deployed
+updateVersion=$(soros-install --version | cut -d' ' -f2)
+[[ -n $updateVersion ]] || missing updateVersion
+
 set -x
 soros-wallet airdrop 42
 soros-install deploy "$updateDownloadUrl" update_manifest_keypair.json \
-#  --url http://localhost:8899
-   --url http://localhost:10099
+  --url http://localhost:10099 --version "$updateVersion"
@@ -2,7 +2,9 @@
 mod bpf {
     use soros_runtime::bank::Bank;
     use soros_runtime::bank_client::BankClient;
-    use soros_runtime::loader_utils::{create_invoke_instruction, load_program};
+    use soros_runtime::loader_utils::{
+        create_invoke_instruction, load_program, load_upgradeable_program, upgrade_program,
+    };
     use soros_sdk::genesis_block::GenesisBlock;
     use soros_sdk::native_loader;
     use std::env;
@@ -29,9 +31,18 @@ mod bpf {
         use super::*;
         use soros_sdk::bpf_loader;
         use soros_sdk::client::SyncClient;
+        use soros_sdk::instruction::AccountMeta;
+        use soros_sdk::pubkey::Pubkey;
         use soros_sdk::signature::KeypairUtil;
         use std::io::Read;
 
+        fn read_elf(name: &str) -> Vec<u8> {
+            let mut file = File::open(create_bpf_path(name)).expect("file open failed");
+            let mut elf = Vec::new();
+            file.read_to_end(&mut elf).unwrap();
+            elf
+        }
+
         #[test]
         fn test_program_bpf_c_noop() {
             soros_logger::setup();
@@ -52,6 +63,40 @@ mod bpf {
                 .unwrap();
         }
 
+        #[test]
+        fn test_program_bpf_c_upgrade() {
+            soros_logger::setup();
+
+            let (genesis_block, alice_keypair) = GenesisBlock::new(50);
+            let bank = Bank::new(&genesis_block);
+            let bank_client = BankClient::new(bank);
+
+            // multiple_static takes any number of accounts
+            let program_id =
+                load_upgradeable_program(&bank_client, &alice_keypair, read_elf("multiple_static"));
+            let new_two_account_instruction = || {
+                let mut instruction =
+                    create_invoke_instruction(alice_keypair.pubkey(), program_id, &1u8);
+                instruction
+                    .accounts
+                    .push(AccountMeta::new(Pubkey::new_rand(), false));
+                instruction
+            };
+            bank_client
+                .send_instruction(&alice_keypair, new_two_account_instruction())
+                .unwrap();
+
+            // noop takes a single account, at the same address
+            upgrade_program(&bank_client, &alice_keypair, &program_id, read_elf("noop"));
+            assert!(bank_client
+                .send_instruction(&alice_keypair, new_two_account_instruction())
+                .is_err());
+            let instruction = create_invoke_instruction(alice_keypair.pubkey(), program_id, &1u8);
+            bank_client
+                .send_instruction(&alice_keypair, instruction)
+                .unwrap();
+        }
+
         #[test]
         fn test_program_bpf_c() {
             soros_logger::setup();
@@ -1,12 +1,15 @@
 pub mod bpf_verifier;
+pub mod upgradeable_loader;
 
 use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
 use libc::c_char;
 use log::*;
 use bvm_filter::{EbpfVmRaw, MemoryRegion};
 use soros_sdk::account::KeyedAccount;
+use soros_sdk::bpf_loader_upgradeable;
 use soros_sdk::instruction::InstructionError;
 use soros_sdk::loader_instruction::LoaderInstruction;
+use soros_sdk::log_collector::program_log;
 use soros_sdk::pubkey::Pubkey;
 use soros_sdk::soros_entrypoint;
 use std::ffi::CStr;
@@ -14,6 +17,10 @@ use std::io::prelude::*;
 use std::io::{Error, ErrorKind};
 use std::mem;
 
+/// Most BPF instructions a program may execute per instruction
+// TODO 36000 is a wag, need to tune
+pub const MAX_INSTRUCTION_COUNT: u64 = 36000;
+
 // TODO use rbpf's disassemble
 #[allow(dead_code)]
 fn dump_program(key: &Pubkey, prog: &[u8]) {
@@ -99,24 +106,24 @@ pub fn helper_sol_log(addr: u64, _arg2: u64, _arg3: u64, _arg4: u64, _arg5: u64)
     let c_buf: *const c_char = addr as *const c_char;
     let c_str: &CStr = unsafe { CStr::from_ptr(c_buf) };
     match c_str.to_str() {
-        Ok(slice) => info!("sol_log: {:?}", slice),
+        Ok(slice) => program_log(slice),
         Err(e) => warn!("Error: Cannot print invalid string: {}", e),
     };
     0
 }
 
 pub fn helper_sol_log_u64(arg1: u64, arg2: u64, arg3: u64, arg4: u64, arg5: u64) -> u64 {
-    info!(
-        "sol_log_u64: {:#x}, {:#x}, {:#x}, {:#x}, {:#x}",
+    program_log(&format!(
+        "{:#x}, {:#x}, {:#x}, {:#x}, {:#x}",
         arg1, arg2, arg3, arg4, arg5
-    );
+    ));
     0
 }
 
 pub fn create_vm(prog: &[u8]) -> Result<EbpfVmRaw, Error> {
     let mut vm = EbpfVmRaw::new(None)?;
     vm.set_verifier(bpf_verifier::check)?;
-    vm.set_max_instruction_count(36000)?; // TODO 36000 is a wag, need to tune
+    vm.set_max_instruction_count(MAX_INSTRUCTION_COUNT)?;
     vm.set_elf(&prog)?;
     vm.register_helper_ex("abort", Some(helper_abort_verify), helper_abort)?;
     vm.register_helper_ex("sol_panic", Some(helper_sol_panic_verify), helper_sol_panic)?;
@@ -190,6 +197,10 @@ fn entrypoint(
 ) -> Result<(), InstructionError> {
     soros_logger::setup();
 
+    if bpf_loader_upgradeable::check_id(program_id) {
+        return upgradeable_loader::process_instruction(keyed_accounts, tx_data, tick_height);
+    }
+
     if keyed_accounts[0].account.executable {
         let (progs, params) = keyed_accounts.split_at_mut(1);
         let prog = &progs[0].account.data;
@@ -203,7 +214,13 @@ fn entrypoint(
             }
         };
         let mut v = serialize_parameters(program_id, params, &tx_data, tick_height);
-        match vm.execute_program(v.as_mut_slice()) {
+        let result = vm.execute_program(v.as_mut_slice());
+        program_log(&format!(
+            "BPF program consumed {} of {} instructions",
+            vm.get_last_instruction_count(),
+            MAX_INSTRUCTION_COUNT
+        ));
+        match result {
             Ok(status) => {
                 if 0 == status {
                     warn!("BPF program failed: {}", status);
@@ -212,14 +229,11 @@ fn entrypoint(
             }
             Err(e) => {
                 warn!("BPF VM failed to run program: {}", e);
+                program_log(&format!("BPF VM failed to run program: {}", e));
                 return Err(InstructionError::GenericError);
             }
         }
         deserialize_parameters(params, &v);
-        info!(
-            "BPF program executed {} instructions",
-            vm.get_last_instruction_count()
-        );
     } else if let Ok(instruction) = bincode::deserialize(tx_data) {
         if keyed_accounts[0].signer_key().is_none() {
             warn!("key[0] did not sign the transaction");
@@ -0,0 +1,504 @@
+//! Instructions of the upgradeable BPF loader.  The programs it deploys run like the ones the BPF
+//! loader finalizes, the runtime hands the loader their program data's ELF to run.
+
+use crate::create_vm;
+use log::*;
+use soros_sdk::account::KeyedAccount;
+use soros_sdk::bpf_loader_upgradeable::{
+    self, UpgradeableLoaderInstruction, UpgradeableLoaderState,
+};
+use soros_sdk::instruction::InstructionError;
+use soros_sdk::pubkey::Pubkey;
+
+fn get_state(keyed_account: &KeyedAccount) -> Result<UpgradeableLoaderState, InstructionError> {
+    if !bpf_loader_upgradeable::check_id(&keyed_account.account.owner) {
+        debug!("{} isn't owned by the loader", keyed_account.unsigned_key());
+        Err(InstructionError::IncorrectProgramId)?;
+    }
+    keyed_account
+        .account
+        .deserialize_data()
+        .map_err(|_| InstructionError::InvalidAccountData)
+}
+
+fn set_state(
+    keyed_account: &mut KeyedAccount,
+    state: &UpgradeableLoaderState,
+) -> Result<(), InstructionError> {
+    // Clear all of the state first, a `None` authority is shorter than the key it replaces
+    let metadata_size = match state {
+        UpgradeableLoaderState::Buffer { .. } => UpgradeableLoaderState::buffer_metadata_size(),
+        UpgradeableLoaderState::ProgramData { .. } => {
+            UpgradeableLoaderState::programdata_metadata_size()
+        }
+        _ => UpgradeableLoaderState::program_len(),
+    };
+    let data = &mut keyed_account.account.data;
+    if data.len() < metadata_size {
+        Err(InstructionError::AccountDataTooSmall)?;
+    }
+    for byte in data[..metadata_size].iter_mut() {
+        *byte = 0;
+    }
+    bincode::serialize_into(&mut data[..metadata_size], state)
+        .map_err(|_| InstructionError::AccountDataTooSmall)
+}
+
+fn check_authority(
+    authority: Option<Pubkey>,
+    keyed_account: &KeyedAccount,
+) -> Result<(), InstructionError> {
+    if authority.as_ref() != Some(keyed_account.unsigned_key()) {
+        debug!("{} isn't the authority", keyed_account.unsigned_key());
+        Err(InstructionError::InvalidArgument)?;
+    }
+    if keyed_account.signer_key().is_none() {
+        debug!("authority {} didn't sign", keyed_account.unsigned_key());
+        Err(InstructionError::MissingRequiredSignature)?;
+    }
+    Ok(())
+}
+
+/// Copy the ELF in `buffer` into `programdata` if it loads, and empty the buffer into it
+fn copy_buffer(
+    programdata: &mut KeyedAccount,
+    buffer: &mut KeyedAccount,
+) -> Result<(), InstructionError> {
+    let elf = &buffer.account.data[UpgradeableLoaderState::buffer_metadata_size()..];
+    let data = &mut programdata.account.data[UpgradeableLoaderState::programdata_metadata_size()..];
+    if elf.len() > data.len() {
+        debug!(
+            "ELF of {} bytes doesn't fit in {} bytes of program data",
+            elf.len(),
+            data.len()
+        );
+        Err(InstructionError::AccountDataTooSmall)?;
+    }
+    if let Err(err) = create_vm(elf) {
+        debug!("ELF in {} failed to load: {}", buffer.unsigned_key(), err);
+        Err(InstructionError::InvalidAccountData)?;
+    }
+
+    let (elf_data, rest) = data.split_at_mut(elf.len());
+    elf_data.copy_from_slice(elf);
+    for byte in rest.iter_mut() {
+        *byte = 0;
+    }
+
+    programdata.account.dif += buffer.account.dif;
+    buffer.account.dif = 0;
+    buffer.account.data.clear();
+    Ok(())
+}
+
+/// The program data, program, buffer and authority accounts of a deploy or an upgrade
+fn split_deploy_accounts<'a, 'b>(
+    keyed_accounts: &'a mut [KeyedAccount<'b>],
+) -> Result<[&'a mut KeyedAccount<'b>; 4], InstructionError> {
+    if keyed_accounts.len() < 4 {
+        Err(InstructionError::InvalidArgument)?;
+    }
+    let (programdata, rest) = keyed_accounts.split_at_mut(1);
+    let (program, rest) = rest.split_at_mut(1);
+    let (buffer, rest) = rest.split_at_mut(1);
+    Ok([
+        &mut programdata[0],
+        &mut program[0],
+        &mut buffer[0],
+        &mut rest[0],
+    ])
+}
+
+fn deploy(
+    keyed_accounts: &mut [KeyedAccount],
+    max_data_len: usize,
+    tick_height: u64,
+) -> Result<(), InstructionError> {
+    let [programdata, program, buffer, authority] = split_deploy_accounts(keyed_accounts)?;
+
+    if program.signer_key().is_none() {
+        debug!(
+            "Program account {} didn't sign the deploy",
+            program.unsigned_key()
+        );
+        Err(InstructionError::MissingRequiredSignature)?;
+    }
+    if get_state(program)? != UpgradeableLoaderState::Uninitialized
+        || get_state(programdata)? != UpgradeableLoaderState::Uninitialized
+    {
+        Err(InstructionError::AccountAlreadyInitialized)?;
+    }
+    let programdata_address = *programdata.unsigned_key();
+    let expected_address = bpf_loader_upgradeable::get_programdata_address(program.unsigned_key());
+    if programdata_address != expected_address {
+        debug!("{} isn't the program's data account", programdata_address);
+        Err(InstructionError::InvalidArgument)?;
+    }
+    if program.account.data.len() < UpgradeableLoaderState::program_len()
+        || programdata.account.data.len() < UpgradeableLoaderState::programdata_len(max_data_len)
+    {
+        Err(InstructionError::AccountDataTooSmall)?;
+    }
+    let upgrade_authority = match get_state(buffer)? {
+        UpgradeableLoaderState::Buffer { authority } => authority,
+        _ => Err(InstructionError::InvalidAccountData)?,
+    };
+    check_authority(upgrade_authority, authority)?;
+
+    copy_buffer(programdata, buffer)?;
+    set_state(
+        programdata,
+        &UpgradeableLoaderState::ProgramData {
+            tick_height,
+            upgrade_authority,
+        },
+    )?;
+    set_state(
+        program,
+        &UpgradeableLoaderState::Program {
+            programdata_address,
+        },
+    )?;
+    program.account.executable = true;
+    info!("Deployed program {}", program.unsigned_key());
+    Ok(())
+}
+
+fn upgrade(keyed_accounts: &mut [KeyedAccount], tick_height: u64) -> Result<(), InstructionError> {
+    let [programdata, program, buffer, authority] = split_deploy_accounts(keyed_accounts)?;
+
+    match get_state(program)? {
+        UpgradeableLoaderState::Program {
+            programdata_address,
+        } if programdata_address == *programdata.unsigned_key() => (),
+        _ => Err(InstructionError::InvalidArgument)?,
+    }
+    let upgrade_authority = match get_state(programdata)? {
+        UpgradeableLoaderState::ProgramData {
+            upgrade_authority, ..
+        } => upgrade_authority,
+        _ => Err(InstructionError::InvalidAccountData)?,
+    };
+    check_authority(upgrade_authority, authority)?;
+    match get_state(buffer)? {
+        UpgradeableLoaderState::Buffer {
+            authority: buffer_authority,
+        } => check_authority(buffer_authority, authority)?,
+        _ => Err(InstructionError::InvalidAccountData)?,
+    }
+
+    copy_buffer(programdata, buffer)?;
+    set_state(
+        programdata,
+        &UpgradeableLoaderState::ProgramData {
+            tick_height,
+            upgrade_authority,
+        },
+    )?;
+    info!("Upgraded program {}", program.unsigned_key());
+    Ok(())
+}
+
+fn set_authority(keyed_accounts: &mut [KeyedAccount]) -> Result<(), InstructionError> {
+    if keyed_accounts.len() < 2 {
+        Err(InstructionError::InvalidArgument)?;
+    }
+    let new_authority = keyed_accounts.get(2).map(|k| *k.unsigned_key());
+    let (account, rest) = keyed_accounts.split_at_mut(1);
+    let (account, current_authority) = (&mut account[0], &rest[0]);
+
+    let state = match get_state(account)? {
+        UpgradeableLoaderState::Buffer { authority } => {
+            if new_authority.is_none() {
+                debug!("A buffer can't be left without an authority");
+                Err(InstructionError::InvalidArgument)?;
+            }
+            check_authority(authority, current_authority)?;
+            UpgradeableLoaderState::Buffer {
+                authority: new_authority,
+            }
+        }
+        UpgradeableLoaderState::ProgramData {
+            tick_height,
+            upgrade_authority,
+        } => {
+            check_authority(upgrade_authority, current_authority)?;
+            UpgradeableLoaderState::ProgramData {
+                tick_height,
+                upgrade_authority: new_authority,
+            }
+        }
+        _ => Err(InstructionError::InvalidAccountData)?,
+    };
+    set_state(account, &state)
+}
+
+pub fn process_instruction(
+    keyed_accounts: &mut [KeyedAccount],
+    data: &[u8],
+    tick_height: u64,
+) -> Result<(), InstructionError> {
+    let instruction =
+        bincode::deserialize(data).map_err(|_| InstructionError::InvalidInstructionData)?;
+    match instruction {
+        UpgradeableLoaderInstruction::InitializeBuffer => {
+            if keyed_accounts.len() < 2 {
+                Err(InstructionError::InvalidArgument)?;
+            }
+            if keyed_accounts[0].signer_key().is_none() {
+                debug!(
+                    "Buffer account {} didn't sign its initialization",
+                    keyed_accounts[0].unsigned_key()
+                );
+                Err(InstructionError::MissingRequiredSignature)?;
+            }
+            if get_state(&keyed_accounts[0])? != UpgradeableLoaderState::Uninitialized {
+                Err(InstructionError::AccountAlreadyInitialized)?;
+            }
+            let authority = Some(*keyed_accounts[1].unsigned_key());
+            set_state(
+                &mut keyed_accounts[0],
+                &UpgradeableLoaderState::Buffer { authority },
+            )
+        }
+        UpgradeableLoaderInstruction::Write { offset, bytes } => {
+            if keyed_accounts.len() < 2 {
+                Err(InstructionError::InvalidArgument)?;
+            }
+            match get_state(&keyed_accounts[0])? {
+                UpgradeableLoaderState::Buffer { authority } => {
+                    check_authority(authority, &keyed_accounts[1])?
+                }
+                _ => Err(InstructionError::InvalidAccountData)?,
+            }
+            let start = UpgradeableLoaderState::buffer_metadata_size() + offset as usize;
+            let end = start + bytes.len();
+            debug!("Write: offset={} length={}", offset, bytes.len());
+            let data = &mut keyed_accounts[0].account.data;
+            if data.len() < end {
+                debug!("Write overflow: {} < {}", data.len(), end);
+                Err(InstructionError::AccountDataTooSmall)?;
+            }
+            data[start..end].copy_from_slice(&bytes);
+            Ok(())
+        }
+        UpgradeableLoaderInstruction::DeployWithMaxDataLen { max_data_len } => {
+            deploy(keyed_accounts, max_data_len as usize, tick_height)
+        }
+        UpgradeableLoaderInstruction::Upgrade => upgrade(keyed_accounts, tick_height),
+        UpgradeableLoaderInstruction::SetAuthority => set_authority(keyed_accounts),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soros_sdk::account::Account;
+
+    fn new_buffer(authority: &Pubkey, program_len: usize) -> Account {
+        let mut account = Account::new(
+            1,
+            UpgradeableLoaderState::buffer_len(program_len),
+            &bpf_loader_upgradeable::id(),
+        );
+        account
+            .serialize_data(&UpgradeableLoaderState::Buffer {
+                authority: Some(*authority),
+            })
+            .unwrap();
+        account
+    }
+
+    fn process(
+        keyed_accounts: &mut [KeyedAccount],
+        ix: &UpgradeableLoaderInstruction,
+    ) -> Result<(), InstructionError> {
+        process_instruction(keyed_accounts, &bincode::serialize(ix).unwrap(), 0)
+    }
+
+    #[test]
+    fn test_initialize_buffer_and_write() {
+        let (buffer_id, authority_id) = (Pubkey::new_rand(), Pubkey::new_rand());
+        let mut buffer = Account::new(
+            1,
+            UpgradeableLoaderState::buffer_len(4),
+            &bpf_loader_upgradeable::id(),
+        );
+        let mut authority = Account::default();
+        // Only the buffer account itself can initialize it
+        let initialize = UpgradeableLoaderInstruction::InitializeBuffer;
+        let mut keyed_accounts = vec![
+            KeyedAccount::new(&buffer_id, false, &mut buffer),
+            KeyedAccount::new(&authority_id, false, &mut authority),
+        ];
+        assert_eq!(
+            process(&mut keyed_accounts, &initialize),
+            Err(InstructionError::MissingRequiredSignature)
+        );
+        let mut keyed_accounts = vec![
+            KeyedAccount::new(&buffer_id, true, &mut buffer),
+            KeyedAccount::new(&authority_id, false, &mut authority),
+        ];
+        assert_eq!(process(&mut keyed_accounts, &initialize), Ok(()));
+        assert_eq!(
+            process(&mut keyed_accounts, &initialize),
+            Err(InstructionError::AccountAlreadyInitialized)
+        );
+
+        // Only the authority can write, and only within the buffer
+        let write = UpgradeableLoaderInstruction::Write {
+            offset: 1,
+            bytes: vec![1, 2],
+        };
+        assert_eq!(
+            process(&mut keyed_accounts, &write),
+            Err(InstructionError::MissingRequiredSignature)
+        );
+        let mut authority = Account::default();
+        let mut keyed_accounts = vec![
+            KeyedAccount::new(&buffer_id, false, &mut buffer),
+            KeyedAccount::new(&authority_id, true, &mut authority),
+        ];
+        assert_eq!(process(&mut keyed_accounts, &write), Ok(()));
+        let overflow = UpgradeableLoaderInstruction::Write {
+            offset: 3,
+            bytes: vec![1, 2],
+        };
+        assert_eq!(
+            process(&mut keyed_accounts, &overflow),
+            Err(InstructionError::AccountDataTooSmall)
+        );
+
+        let metadata_size = UpgradeableLoaderState::buffer_metadata_size();
+        assert_eq!(buffer.data[metadata_size..], [0, 1, 2, 0]);
+        assert_eq!(
+            buffer.deserialize_data::<UpgradeableLoaderState>().unwrap(),
+            UpgradeableLoaderState::Buffer {
+                authority: Some(authority_id)
+            }
+        );
+    }
+
+    #[test]
+    fn test_write_wrong_authority() {
+        let (buffer_id, authority_id) = (Pubkey::new_rand(), Pubkey::new_rand());
+        let mut buffer = new_buffer(&authority_id, 4);
+        let mut other = Account::default();
+        let other_id = Pubkey::new_rand();
+        let mut keyed_accounts = vec![
+            KeyedAccount::new(&buffer_id, false, &mut buffer),
+            KeyedAccount::new(&other_id, true, &mut other),
+        ];
+        let write = UpgradeableLoaderInstruction::Write {
+            offset: 0,
+            bytes: vec![1],
+        };
+        assert_eq!(
+            process(&mut keyed_accounts, &write),
+            Err(InstructionError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_deploy_checks() {
+        let program_id = Pubkey::new_rand();
+        let programdata_id = bpf_loader_upgradeable::get_programdata_address(&program_id);
+        let (buffer_id, authority_id) = (Pubkey::new_rand(), Pubkey::new_rand());
+        let loader_id = bpf_loader_upgradeable::id();
+        let mut program = Account::new(1, UpgradeableLoaderState::program_len(), &loader_id);
+        let mut programdata =
+            Account::new(1, UpgradeableLoaderState::programdata_len(8), &loader_id);
+        // Not an ELF
+        let mut buffer = new_buffer(&authority_id, 4);
+        let mut authority = Account::default();
+        let deploy = UpgradeableLoaderInstruction::DeployWithMaxDataLen { max_data_len: 8 };
+
+        // The program account must sign
+        let mut keyed_accounts = vec![
+            KeyedAccount::new(&programdata_id, false, &mut programdata),
+            KeyedAccount::new(&program_id, false, &mut program),
+            KeyedAccount::new(&buffer_id, false, &mut buffer),
+            KeyedAccount::new(&authority_id, true, &mut authority),
+        ];
+        assert_eq!(
+            process(&mut keyed_accounts, &deploy),
+            Err(InstructionError::MissingRequiredSignature)
+        );
+
+        // The program data account must be the program's
+        let wrong_programdata_id = Pubkey::new_rand();
+        let mut keyed_accounts = vec![
+            KeyedAccount::new(&wrong_programdata_id, false, &mut programdata),
+            KeyedAccount::new(&program_id, true, &mut program),
+            KeyedAccount::new(&buffer_id, false, &mut buffer),
+            KeyedAccount::new(&authority_id, true, &mut authority),
+        ];
+        assert_eq!(
+            process(&mut keyed_accounts, &deploy),
+            Err(InstructionError::InvalidArgument)
+        );
+
+        let mut keyed_accounts = vec![
+            KeyedAccount::new(&programdata_id, false, &mut programdata),
+            KeyedAccount::new(&program_id, true, &mut program),
+            KeyedAccount::new(&buffer_id, false, &mut buffer),
+            KeyedAccount::new(&authority_id, true, &mut authority),
+        ];
+        assert_eq!(
+            process(&mut keyed_accounts, &deploy),
+            Err(InstructionError::InvalidAccountData)
+        );
+        assert!(!program.executable);
+        assert_eq!(buffer.dif, 1);
+    }
+
+    #[test]
+    fn test_set_authority() {
+        let loader_id = bpf_loader_upgradeable::id();
+        let (programdata_id, authority_id, new_authority_id) =
+            (Pubkey::new_rand(), Pubkey::new_rand(), Pubkey::new_rand());
+        let mut programdata =
+            Account::new(1, UpgradeableLoaderState::programdata_len(8), &loader_id);
+        programdata
+            .serialize_data(&UpgradeableLoaderState::ProgramData {
+                tick_height: 3,
+                upgrade_authority: Some(authority_id),
+            })
+            .unwrap();
+        let mut authority = Account::default();
+        let mut new_authority = Account::default();
+        let set_authority = UpgradeableLoaderInstruction::SetAuthority;
+
+        let mut keyed_accounts = vec![
+            KeyedAccount::new(&programdata_id, false, &mut programdata),
+            KeyedAccount::new(&authority_id, true, &mut authority),
+            KeyedAccount::new(&new_authority_id, false, &mut new_authority),
+        ];
+        assert_eq!(process(&mut keyed_accounts, &set_authority), Ok(()));
+        // The old authority can't anymore
+        assert_eq!(
+            process(&mut keyed_accounts, &set_authority),
+            Err(InstructionError::InvalidArgument)
+        );
+
+        // Without a new authority the program is final
+        let mut keyed_accounts = vec![
+            KeyedAccount::new(&programdata_id, false, &mut programdata),
+            KeyedAccount::new(&new_authority_id, true, &mut new_authority),
+        ];
+        assert_eq!(process(&mut keyed_accounts, &set_authority), Ok(()));
+        assert_eq!(
+            process(&mut keyed_accounts, &set_authority),
+            Err(InstructionError::InvalidArgument)
+        );
+        assert_eq!(
+            programdata
+                .deserialize_data::<UpgradeableLoaderState>()
+                .unwrap(),
+            UpgradeableLoaderState::ProgramData {
+                tick_height: 3,
+                upgrade_authority: None,
+            }
+        );
+    }
+}
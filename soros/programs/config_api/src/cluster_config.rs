@@ -0,0 +1,311 @@
+//! Cluster config
+//! * typed, validated cluster parameters kept in well-known config accounts
+//! * an update is staged in the account and the Bank makes it effective at the next epoch
+//!   boundary
+//! * each account is controlled by a set of signers, a minimum number of which must sign
+
+use crate::{id, ConfigState};
+use bincode::serialized_size;
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use soros_sdk::account::Account;
+use soros_sdk::pubkey::Pubkey;
+
+/// Most signers a config account's authority can have
+pub const MAX_CONFIG_SIGNERS: usize = 8;
+
+const FEE_CONFIG_ID: [u8; 32] = [
+    3, 6, 148, 15, 107, 131, 24, 203, 192, 68, 41, 236, 3, 124, 13, 37, 81, 205, 178, 34, 205, 12,
+    127, 143, 219, 48, 142, 57, 0, 0, 0, 0,
+];
+
+const STAKE_REWARD_CONFIG_ID: [u8; 32] = [
+    3, 6, 253, 158, 66, 158, 159, 129, 41, 70, 124, 161, 60, 14, 209, 154, 91, 95, 241, 169, 231,
+    170, 172, 34, 10, 52, 242, 248, 0, 0, 0, 0,
+];
+
+const STORAGE_REWARD_CONFIG_ID: [u8; 32] = [
+    3, 6, 133, 245, 129, 130, 87, 94, 95, 145, 215, 115, 109, 244, 189, 221, 153, 194, 195, 154,
+    241, 191, 158, 70, 81, 151, 164, 10, 0, 0, 0, 0,
+];
+
+/// Parameters kept in a well-known cluster config account
+pub trait ClusterConfig: ConfigState + DeserializeOwned + Default + Clone + PartialEq {
+    /// Address of the config account
+    fn id() -> Pubkey;
+
+    /// Whether the parameters can be put into effect
+    fn is_valid(&self) -> bool;
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq, Clone)]
+pub struct ConfigAuthority {
+    pub signers: Vec<Pubkey>,
+    /// Number of `signers` that must sign a change
+    pub min_signatures: u8,
+}
+
+impl ConfigAuthority {
+    pub fn new(signers: &[Pubkey], min_signatures: u8) -> Self {
+        Self {
+            signers: signers.to_vec(),
+            min_signatures,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        let mut unique_signers = self.signers.clone();
+        unique_signers.sort();
+        unique_signers.dedup();
+
+        !self.signers.is_empty()
+            && self.signers.len() <= MAX_CONFIG_SIGNERS
+            && unique_signers.len() == self.signers.len()
+            && self.min_signatures > 0
+            && self.min_signatures as usize <= self.signers.len()
+    }
+
+    /// Whether enough of the authority's signers are in `signer_keys`
+    pub fn is_signed_by(&self, signer_keys: &[&Pubkey]) -> bool {
+        let signatures = self
+            .signers
+            .iter()
+            .filter(|signer| signer_keys.contains(signer))
+            .count();
+        signatures >= self.min_signatures as usize
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone)]
+pub struct ClusterConfigState<T> {
+    pub authority: ConfigAuthority,
+    /// Parameters in effect
+    pub params: T,
+    /// Parameters that take effect at the next epoch boundary
+    pub pending: Option<T>,
+}
+
+impl<T: ClusterConfig> ClusterConfigState<T> {
+    pub fn new(authority: ConfigAuthority, params: T) -> Self {
+        Self {
+            authority,
+            params,
+            pending: None,
+        }
+    }
+
+    /// Space a config account needs for the largest authority and a pending update
+    pub fn max_space() -> u64 {
+        serialized_size(&Self {
+            authority: ConfigAuthority::new(&[Pubkey::default(); MAX_CONFIG_SIGNERS], 1),
+            params: T::default(),
+            pending: Some(T::default()),
+        })
+        .unwrap()
+    }
+
+    pub fn from(account: &Account) -> Option<Self> {
+        if account.owner != id() {
+            return None;
+        }
+        account.deserialize_data().ok()
+    }
+
+    /// Put the pending parameters into effect, returns them if there were any
+    pub fn activate_pending(&mut self) -> Option<T> {
+        let params = self.pending.take()?;
+        self.params = params.clone();
+        Some(params)
+    }
+}
+
+/// Instructions a cluster config account accepts, it must be signed by
+/// `authority.min_signatures` of the account's authority signers
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum ClusterConfigInstruction<T> {
+    /// Stage new parameters, they take effect at the next epoch boundary
+    Update(T),
+    /// Hand the account over to a new authority, effective immediately
+    SetAuthority(ConfigAuthority),
+}
+
+/// Create a cluster config account, used to set up the well-known accounts at genesis
+pub fn create_account<T: ClusterConfig>(
+    dif: u64,
+    authority: &ConfigAuthority,
+    params: T,
+) -> Account {
+    let mut account = Account::new(dif, ClusterConfigState::<T>::max_space() as usize, &id());
+    account
+        .serialize_data(&ClusterConfigState::new(authority.clone(), params))
+        .unwrap();
+    account
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct FeeConfig {
+    pub dif_per_signature: u64,
+}
+
+impl FeeConfig {
+    pub fn new(dif_per_signature: u64) -> Self {
+        Self { dif_per_signature }
+    }
+}
+
+impl ConfigState for FeeConfig {
+    fn max_space() -> u64 {
+        serialized_size(&Self::default()).unwrap()
+    }
+}
+
+impl ClusterConfig for FeeConfig {
+    fn id() -> Pubkey {
+        Pubkey::new(&FEE_CONFIG_ID)
+    }
+
+    fn is_valid(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone, Copy)]
+pub struct StakeRewardConfig {
+    /// Annual rate at which effective stake earns rewards, 0.20 is 20%
+    pub reward_target_rate: f64,
+}
+
+impl StakeRewardConfig {
+    pub fn new(reward_target_rate: f64) -> Self {
+        Self { reward_target_rate }
+    }
+}
+
+impl ConfigState for StakeRewardConfig {
+    fn max_space() -> u64 {
+        serialized_size(&Self::default()).unwrap()
+    }
+}
+
+impl ClusterConfig for StakeRewardConfig {
+    fn id() -> Pubkey {
+        Pubkey::new(&STAKE_REWARD_CONFIG_ID)
+    }
+
+    fn is_valid(&self) -> bool {
+        self.reward_target_rate.is_finite()
+            && self.reward_target_rate >= 0.0
+            && self.reward_target_rate <= 1.0
+    }
+}
+
+/// Storage rewards paid per valid proof
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct StorageRewardConfig {
+    /// Paid to a validator for each proof it validated
+    pub validator_reward: u64,
+    /// Paid to a replicator for each of its proofs found valid
+    pub replicator_reward: u64,
+}
+
+impl Default for StorageRewardConfig {
+    fn default() -> Self {
+        Self::new(1, 1)
+    }
+}
+
+impl StorageRewardConfig {
+    pub fn new(validator_reward: u64, replicator_reward: u64) -> Self {
+        Self {
+            validator_reward,
+            replicator_reward,
+        }
+    }
+}
+
+impl ConfigState for StorageRewardConfig {
+    fn max_space() -> u64 {
+        serialized_size(&Self::default()).unwrap()
+    }
+}
+
+impl ClusterConfig for StorageRewardConfig {
+    fn id() -> Pubkey {
+        Pubkey::new(&STORAGE_REWARD_CONFIG_ID)
+    }
+
+    fn is_valid(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_authority_is_valid() {
+        let signers: Vec<_> = (0..MAX_CONFIG_SIGNERS + 1)
+            .map(|_| Pubkey::new_rand())
+            .collect();
+        assert!(ConfigAuthority::new(&signers[..1], 1).is_valid());
+        assert!(ConfigAuthority::new(&signers[..3], 2).is_valid());
+        assert!(ConfigAuthority::new(&signers[..MAX_CONFIG_SIGNERS], 8).is_valid());
+
+        assert!(!ConfigAuthority::new(&[], 0).is_valid());
+        assert!(!ConfigAuthority::new(&signers[..1], 0).is_valid());
+        assert!(!ConfigAuthority::new(&signers[..2], 3).is_valid());
+        assert!(!ConfigAuthority::new(&signers, 1).is_valid());
+        assert!(!ConfigAuthority::new(&[signers[0], signers[0]], 1).is_valid());
+    }
+
+    #[test]
+    fn test_config_authority_is_signed_by() {
+        let signers: Vec<_> = (0..3).map(|_| Pubkey::new_rand()).collect();
+        let authority = ConfigAuthority::new(&signers, 2);
+        let stranger = Pubkey::new_rand();
+
+        assert!(authority.is_signed_by(&[&signers[0], &signers[2]]));
+        assert!(authority.is_signed_by(&[&signers[0], &signers[1], &signers[2]]));
+        assert!(!authority.is_signed_by(&[&signers[1]]));
+        assert!(!authority.is_signed_by(&[&signers[1], &stranger]));
+        assert!(!authority.is_signed_by(&[&signers[1], &signers[1]]));
+    }
+
+    #[test]
+    fn test_cluster_config_account() {
+        let authority = ConfigAuthority::new(&[Pubkey::new_rand()], 1);
+        let account = create_account(1, &authority, StorageRewardConfig::new(2, 3));
+        assert_eq!(account.owner, id());
+
+        let mut state = ClusterConfigState::<StorageRewardConfig>::from(&account).unwrap();
+        assert_eq!(state.authority, authority);
+        assert_eq!(state.params, StorageRewardConfig::new(2, 3));
+        assert_eq!(state.activate_pending(), None);
+
+        state.pending = Some(StorageRewardConfig::new(4, 5));
+        assert_eq!(
+            state.activate_pending(),
+            Some(StorageRewardConfig::new(4, 5))
+        );
+        assert_eq!(state.params, StorageRewardConfig::new(4, 5));
+        assert_eq!(state.pending, None);
+
+        let mut not_config = account.clone();
+        not_config.owner = Pubkey::new_rand();
+        assert_eq!(
+            ClusterConfigState::<StorageRewardConfig>::from(&not_config),
+            None
+        );
+    }
+
+    #[test]
+    fn test_stake_reward_config_is_valid() {
+        assert!(StakeRewardConfig::new(0.0).is_valid());
+        assert!(StakeRewardConfig::new(0.15).is_valid());
+        assert!(StakeRewardConfig::new(1.0).is_valid());
+        assert!(!StakeRewardConfig::new(-0.1).is_valid());
+        assert!(!StakeRewardConfig::new(1.1).is_valid());
+        assert!(!StakeRewardConfig::new(std::f64::NAN).is_valid());
+    }
+}
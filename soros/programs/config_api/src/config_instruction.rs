@@ -1,3 +1,4 @@
+use crate::cluster_config::{ClusterConfig, ClusterConfigInstruction, ConfigAuthority};
 use crate::id;
 use crate::ConfigState;
 use soros_sdk::instruction::{AccountMeta, Instruction};
@@ -33,3 +34,49 @@ pub fn store<T: ConfigState>(
     ];
     Instruction::new(id(), data, account_metas)
 }
+
+fn cluster_config_account_metas<T: ClusterConfig>(
+    from_account_pubkey: &Pubkey,
+    authority_pubkeys: &[Pubkey],
+) -> Vec<AccountMeta> {
+    let mut account_metas = vec![
+        AccountMeta::new(*from_account_pubkey, true),
+        AccountMeta::new(T::id(), false),
+    ];
+    account_metas.extend(
+        authority_pubkeys
+            .iter()
+            .filter(|pubkey| *pubkey != from_account_pubkey)
+            .map(|pubkey| AccountMeta::new(*pubkey, true)),
+    );
+    account_metas
+}
+
+/// Stage new parameters in a well-known cluster config account, signed by enough of
+/// the account's authority signers, `from_account_pubkey` may be one of them
+pub fn update_cluster_config<T: ClusterConfig>(
+    from_account_pubkey: &Pubkey,
+    authority_pubkeys: &[Pubkey],
+    params: T,
+) -> Instruction {
+    let account_metas = cluster_config_account_metas::<T>(from_account_pubkey, authority_pubkeys);
+    Instruction::new(
+        id(),
+        &ClusterConfigInstruction::Update(params),
+        account_metas,
+    )
+}
+
+/// Hand a well-known cluster config account over to a new authority
+pub fn set_cluster_config_authority<T: ClusterConfig>(
+    from_account_pubkey: &Pubkey,
+    authority_pubkeys: &[Pubkey],
+    new_authority: ConfigAuthority,
+) -> Instruction {
+    let account_metas = cluster_config_account_metas::<T>(from_account_pubkey, authority_pubkeys);
+    Instruction::new(
+        id(),
+        &ClusterConfigInstruction::<T>::SetAuthority(new_authority),
+        account_metas,
+    )
+}
@@ -1,16 +1,86 @@
 //! Config program
 
+use crate::cluster_config::{
+    ClusterConfig, ClusterConfigInstruction, ClusterConfigState, FeeConfig, StakeRewardConfig,
+    StorageRewardConfig,
+};
+use bincode::deserialize;
 use log::*;
 use soros_sdk::account::KeyedAccount;
 use soros_sdk::instruction::InstructionError;
 use soros_sdk::pubkey::Pubkey;
 
+/// Update a well-known cluster config account, the parameters are checked before they're
+/// staged and the account's authority must have signed
+fn process_cluster_config<T: ClusterConfig>(
+    keyed_accounts: &mut [KeyedAccount],
+    data: &[u8],
+) -> Result<(), InstructionError> {
+    // The payer in account[0] may be one of the authority signers too
+    let signer_keys: Vec<_> = keyed_accounts
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != 1)
+        .filter_map(|(_, keyed_account)| keyed_account.signer_key().cloned())
+        .collect();
+    let signer_keys: Vec<_> = signer_keys.iter().collect();
+
+    let config_account = &mut keyed_accounts[1].account;
+    let mut state = ClusterConfigState::<T>::from(config_account).ok_or_else(|| {
+        error!("not a cluster config account");
+        InstructionError::InvalidAccountData
+    })?;
+
+    if !state.authority.is_signed_by(&signer_keys) {
+        error!("not enough of the config authority signed");
+        Err(InstructionError::MissingRequiredSignature)?;
+    }
+
+    let instruction: ClusterConfigInstruction<T> =
+        deserialize(data).map_err(|_| InstructionError::InvalidInstructionData)?;
+    match instruction {
+        ClusterConfigInstruction::Update(params) => {
+            if !params.is_valid() {
+                error!("invalid cluster config parameters");
+                Err(InstructionError::InvalidArgument)?;
+            }
+            state.pending = Some(params);
+        }
+        ClusterConfigInstruction::SetAuthority(authority) => {
+            if !authority.is_valid() {
+                error!("invalid cluster config authority");
+                Err(InstructionError::InvalidArgument)?;
+            }
+            state.authority = authority;
+        }
+    }
+
+    config_account
+        .serialize_data(&state)
+        .map_err(|_| InstructionError::AccountDataTooSmall)
+}
+
 pub fn process_instruction(
     _program_id: &Pubkey,
     keyed_accounts: &mut [KeyedAccount],
     data: &[u8],
     _tick_height: u64,
 ) -> Result<(), InstructionError> {
+    if keyed_accounts.len() < 2 {
+        Err(InstructionError::InvalidInstructionData)?;
+    }
+
+    let config_pubkey = *keyed_accounts[1].unsigned_key();
+    if config_pubkey == FeeConfig::id() {
+        return process_cluster_config::<FeeConfig>(keyed_accounts, data);
+    }
+    if config_pubkey == StakeRewardConfig::id() {
+        return process_cluster_config::<StakeRewardConfig>(keyed_accounts, data);
+    }
+    if config_pubkey == StorageRewardConfig::id() {
+        return process_cluster_config::<StorageRewardConfig>(keyed_accounts, data);
+    }
+
     if keyed_accounts[1].signer_key().is_none() {
         error!("account[1] should sign the transaction");
         Err(InstructionError::MissingRequiredSignature)?;
@@ -28,6 +98,7 @@ pub fn process_instruction(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cluster_config::ConfigAuthority;
     use crate::{config_instruction, id, ConfigState};
     use bincode::{deserialize, serialized_size};
     use serde_derive::{Deserialize, Serialize};
@@ -35,6 +106,7 @@ mod tests {
     use soros_runtime::bank_client::BankClient;
     use soros_sdk::client::SyncClient;
     use soros_sdk::genesis_block::GenesisBlock;
+    use soros_sdk::instruction::Instruction;
     use soros_sdk::message::Message;
     use soros_sdk::signature::{Keypair, KeypairUtil};
     use soros_sdk::system_instruction;
@@ -174,4 +246,205 @@ mod tests {
             .send_message(&[&system_keypair], message)
             .unwrap_err();
     }
+
+    fn get_cluster_config<T: ClusterConfig>(bank_client: &BankClient) -> ClusterConfigState<T> {
+        let data = bank_client.get_account_data(&T::id()).unwrap().unwrap();
+        deserialize(&data).unwrap()
+    }
+
+    fn send_cluster_config_instruction(
+        bank_client: &BankClient,
+        payer_keypair: &Keypair,
+        authority_keypairs: &[&Keypair],
+        instruction: Instruction,
+    ) -> bool {
+        let mut signers = vec![payer_keypair];
+        signers.extend_from_slice(authority_keypairs);
+        bank_client
+            .send_message(&signers, Message::new(vec![instruction]))
+            .is_ok()
+    }
+
+    #[test]
+    fn test_process_cluster_config_update() {
+        soros_logger::setup();
+        let (bank, mint_keypair) = create_bank(10_000);
+        let bank_client = BankClient::new(bank);
+        let mint_pubkey = mint_keypair.pubkey();
+
+        let state = get_cluster_config::<FeeConfig>(&bank_client);
+        assert_eq!(state.authority, ConfigAuthority::new(&[mint_pubkey], 1));
+        assert_eq!(state.pending, None);
+
+        // The mint pays for and signs the update
+        let instruction = config_instruction::update_cluster_config(
+            &mint_pubkey,
+            &[mint_pubkey],
+            FeeConfig::new(3),
+        );
+        assert!(send_cluster_config_instruction(
+            &bank_client,
+            &mint_keypair,
+            &[],
+            instruction
+        ));
+
+        // Staged until the next epoch boundary
+        let state = get_cluster_config::<FeeConfig>(&bank_client);
+        assert_eq!(state.params, FeeConfig::default());
+        assert_eq!(state.pending, Some(FeeConfig::new(3)));
+    }
+
+    #[test]
+    fn test_process_cluster_config_update_invalid() {
+        soros_logger::setup();
+        let (bank, mint_keypair) = create_bank(10_000);
+        let bank_client = BankClient::new(bank);
+        let mint_pubkey = mint_keypair.pubkey();
+
+        let instruction = config_instruction::update_cluster_config(
+            &mint_pubkey,
+            &[mint_pubkey],
+            StakeRewardConfig::new(2.0),
+        );
+        assert!(!send_cluster_config_instruction(
+            &bank_client,
+            &mint_keypair,
+            &[],
+            instruction
+        ));
+        assert_eq!(
+            get_cluster_config::<StakeRewardConfig>(&bank_client).pending,
+            None
+        );
+    }
+
+    #[test]
+    fn test_process_cluster_config_update_not_authority() {
+        soros_logger::setup();
+        let (bank, mint_keypair) = create_bank(10_000);
+        let bank_client = BankClient::new(bank);
+        let mint_pubkey = mint_keypair.pubkey();
+        let stranger_keypair = Keypair::new();
+
+        let instruction = config_instruction::update_cluster_config(
+            &mint_pubkey,
+            &[stranger_keypair.pubkey()],
+            StorageRewardConfig::new(5, 5),
+        );
+        assert!(!send_cluster_config_instruction(
+            &bank_client,
+            &mint_keypair,
+            &[&stranger_keypair],
+            instruction
+        ));
+
+        // The authority key has to actually sign
+        let mut instruction = config_instruction::update_cluster_config(
+            &stranger_keypair.pubkey(),
+            &[mint_pubkey],
+            StorageRewardConfig::new(5, 5),
+        );
+        instruction.accounts[2].is_signer = false;
+        bank_client
+            .transfer(42, &mint_keypair, &stranger_keypair.pubkey())
+            .unwrap();
+        assert!(!send_cluster_config_instruction(
+            &bank_client,
+            &stranger_keypair,
+            &[],
+            instruction
+        ));
+        assert_eq!(
+            get_cluster_config::<StorageRewardConfig>(&bank_client).pending,
+            None
+        );
+    }
+
+    #[test]
+    fn test_process_cluster_config_set_authority() {
+        soros_logger::setup();
+        let (bank, mint_keypair) = create_bank(10_000);
+        let bank_client = BankClient::new(bank);
+        let mint_pubkey = mint_keypair.pubkey();
+        let keypairs: Vec<_> = (0..3).map(|_| Keypair::new()).collect();
+        let pubkeys: Vec<_> = keypairs.iter().map(|keypair| keypair.pubkey()).collect();
+
+        // An authority nobody could ever satisfy is refused
+        let instruction = config_instruction::set_cluster_config_authority::<FeeConfig>(
+            &mint_pubkey,
+            &[mint_pubkey],
+            ConfigAuthority::new(&pubkeys, 4),
+        );
+        assert!(!send_cluster_config_instruction(
+            &bank_client,
+            &mint_keypair,
+            &[],
+            instruction
+        ));
+
+        // Hand the fee config over to 2 of 3 signers
+        let authority = ConfigAuthority::new(&pubkeys, 2);
+        let instruction = config_instruction::set_cluster_config_authority::<FeeConfig>(
+            &mint_pubkey,
+            &[mint_pubkey],
+            authority.clone(),
+        );
+        assert!(send_cluster_config_instruction(
+            &bank_client,
+            &mint_keypair,
+            &[],
+            instruction
+        ));
+        assert_eq!(
+            get_cluster_config::<FeeConfig>(&bank_client).authority,
+            authority
+        );
+
+        // The mint no longer controls it, and one signer isn't enough
+        let instruction = config_instruction::update_cluster_config(
+            &mint_pubkey,
+            &[mint_pubkey],
+            FeeConfig::new(7),
+        );
+        assert!(!send_cluster_config_instruction(
+            &bank_client,
+            &mint_keypair,
+            &[],
+            instruction
+        ));
+        let instruction = config_instruction::update_cluster_config(
+            &mint_pubkey,
+            &pubkeys[..1],
+            FeeConfig::new(7),
+        );
+        assert!(!send_cluster_config_instruction(
+            &bank_client,
+            &mint_keypair,
+            &[&keypairs[0]],
+            instruction
+        ));
+
+        let instruction = config_instruction::update_cluster_config(
+            &mint_pubkey,
+            &[pubkeys[0], pubkeys[2]],
+            FeeConfig::new(7),
+        );
+        assert!(send_cluster_config_instruction(
+            &bank_client,
+            &mint_keypair,
+            &[&keypairs[0], &keypairs[2]],
+            instruction
+        ));
+        assert_eq!(
+            get_cluster_config::<FeeConfig>(&bank_client).pending,
+            Some(FeeConfig::new(7))
+        );
+
+        // Other config accounts keep their own authority
+        assert_eq!(
+            get_cluster_config::<StorageRewardConfig>(&bank_client).authority,
+            ConfigAuthority::new(&[mint_pubkey], 1)
+        );
+    }
 }
@@ -1,6 +1,7 @@
 use serde::Serialize;
 use soros_sdk::pubkey::Pubkey;
 
+pub mod cluster_config;
 pub mod config_instruction;
 pub mod config_processor;
 
@@ -6,6 +6,16 @@ use serde_derive::{Deserialize, Serialize};
 use soros_sdk::instruction::{AccountMeta, Instruction};
 use soros_sdk::pubkey::Pubkey;
 
+/// How a trade request is filled
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TradeMode {
+    /// Record the trade order to be filled by a broker's `SwapRequest`
+    Swap,
+    /// Fill the trade order against the pair's order book right away and rest whatever
+    /// remains unfilled in the order book
+    Match,
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct TradeRequestInfo {
     /// Direction of trade
@@ -20,6 +30,9 @@ pub struct TradeRequestInfo {
     /// The price ratio the primary price over the secondary price.  The primary price is fixed
     /// and equal to the variable `SCALER`.
     pub price: u64,
+
+    /// How the trade is filled
+    pub mode: TradeMode,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
@@ -32,18 +45,21 @@ pub enum ExchangeInstruction {
     /// Transfer tokens between two accounts
     /// key 0 - Account to transfer tokens to
     /// key 1 - Account to transfer tokens from.  This can be the exchange program itself,
-    ///         the exchange has a limitless number of tokens it can transfer.
+    ///         the exchange has a limitless number of tokens of any mint it can transfer.
     TransferRequest(Token, u64),
 
     /// Trade request
     /// key 0 - Signer
     /// key 1 - Account in which to record the trade order
     /// key 2 - Token account to source tokens from
+    /// key 3 - Order book of the trade's token pair, at `TokenPair::order_book_address`.
+    ///         If the account is unallocated the order book is created for the pair.
     TradeRequest(TradeRequestInfo),
 
-    /// Trade cancellation
+    /// Trade cancellation, also claims the tokens settled to a filled resting trade order
     /// key 0 - Signer
     /// key 1 - Trade order to cancel
+    /// key 2 - Order book the trade order rests in, if any
     TradeCancellation,
 
     /// Trade swap request
@@ -51,6 +67,7 @@ pub enum ExchangeInstruction {
     /// key 2 - 'To' trade order
     /// key 3 - `From` trade order
     /// key 6 - Token account in which to deposit the brokers profit from the swap.
+    /// Trade orders resting in an order book can't be swapped.
     SwapRequest,
 }
 
@@ -81,19 +98,23 @@ pub fn transfer_request(
     )
 }
 
-pub fn trade_request(
+#[allow(clippy::too_many_arguments)]
+fn new_trade_request(
     owner: &Pubkey,
     trade: &Pubkey,
     direction: Direction,
     pair: TokenPair,
     tokens: u64,
     price: u64,
+    mode: TradeMode,
     src_account: &Pubkey,
+    order_book: &Pubkey,
 ) -> Instruction {
     let account_metas = vec![
         AccountMeta::new(*owner, true),
         AccountMeta::new(*trade, false),
         AccountMeta::new(*src_account, false),
+        AccountMeta::new(*order_book, false),
     ];
     Instruction::new(
         id(),
@@ -102,11 +123,62 @@ pub fn trade_request(
             pair,
             tokens,
             price,
+            mode,
         }),
         account_metas,
     )
 }
 
+/// Trade request to be filled by a broker's swap
+#[allow(clippy::too_many_arguments)]
+pub fn trade_request(
+    owner: &Pubkey,
+    trade: &Pubkey,
+    direction: Direction,
+    pair: TokenPair,
+    tokens: u64,
+    price: u64,
+    src_account: &Pubkey,
+    order_book: &Pubkey,
+) -> Instruction {
+    new_trade_request(
+        owner,
+        trade,
+        direction,
+        pair,
+        tokens,
+        price,
+        TradeMode::Swap,
+        src_account,
+        order_book,
+    )
+}
+
+/// Trade request to be filled by the order book
+#[allow(clippy::too_many_arguments)]
+pub fn match_trade_request(
+    owner: &Pubkey,
+    trade: &Pubkey,
+    direction: Direction,
+    pair: TokenPair,
+    tokens: u64,
+    price: u64,
+    src_account: &Pubkey,
+    order_book: &Pubkey,
+) -> Instruction {
+    new_trade_request(
+        owner,
+        trade,
+        direction,
+        pair,
+        tokens,
+        price,
+        TradeMode::Match,
+        src_account,
+        order_book,
+    )
+}
+
 pub fn trade_cancellation(owner: &Pubkey, trade: &Pubkey) -> Instruction {
     let account_metas = vec![
         AccountMeta::new(*owner, true),
@@ -115,6 +187,19 @@ pub fn trade_cancellation(owner: &Pubkey, trade: &Pubkey) -> Instruction {
     Instruction::new(id(), &ExchangeInstruction::TradeCancellation, account_metas)
 }
 
+pub fn resting_trade_cancellation(
+    owner: &Pubkey,
+    trade: &Pubkey,
+    order_book: &Pubkey,
+) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*owner, true),
+        AccountMeta::new(*trade, false),
+        AccountMeta::new(*order_book, false),
+    ];
+    Instruction::new(id(), &ExchangeInstruction::TradeCancellation, account_metas)
+}
+
 pub fn swap_request(
     owner: &Pubkey,
     to_trade: &Pubkey,
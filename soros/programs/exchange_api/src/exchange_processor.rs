@@ -49,7 +49,33 @@ impl ExchangeProcessor {
         }
     }
 
+    fn deserialize_order_book(data: &[u8]) -> Result<OrderBookInfo, InstructionError> {
+        let state: ExchangeState = bincode::deserialize(data).map_err(Self::map_to_invalid_arg)?;
+        if let ExchangeState::OrderBook(info) = state {
+            Ok(info)
+        } else {
+            error!("Not a valid order book");
+            Err(InstructionError::InvalidAccountData)?
+        }
+    }
+
+    #[allow(clippy::needless_pass_by_value)]
+    fn map_exchange_error(err: ExchangeError) -> InstructionError {
+        error!("{}", err);
+        InstructionError::GenericError
+    }
+
     fn serialize(state: &ExchangeState, data: &mut [u8]) -> Result<(), InstructionError> {
+        if let ExchangeState::Account(account) = state {
+            if account.tokens.len() > MAX_TOKENS_PER_ACCOUNT {
+                error!("Account holds too many different tokens");
+                Err(InstructionError::GenericError)?
+            }
+        }
+        if bincode::serialized_size(state).map_err(Self::map_to_invalid_arg)? > data.len() as u64 {
+            error!("Account data too small");
+            Err(InstructionError::AccountDataTooSmall)?
+        }
         let writer = std::io::BufWriter::new(data);
         match bincode::serialize_into(writer, state) {
             Ok(_) => Ok(()),
@@ -68,9 +94,9 @@ impl ExchangeProcessor {
             Direction::From => trade.pair.primary(),
         };
 
-        let mut account = TokenAccountInfo::default().owner(&trade.owner);
-        account.tokens[token] = trade.tokens_settled;
-        account
+        TokenAccountInfo::default()
+            .owner(&trade.owner)
+            .tokens(&[(token, trade.tokens_settled)])
     }
 
     fn calculate_swap(
@@ -149,12 +175,122 @@ impl ExchangeProcessor {
         from_trade.tokens -= secondary_cost;
         from_trade.tokens_settled += primary_tokens;
 
-        profit_account.tokens[primary_token] += primary_profit;
-        profit_account.tokens[secondary_token] += secondary_profit;
+        profit_account
+            .tokens
+            .credit(&primary_token, primary_profit)
+            .map_err(Self::map_exchange_error)?;
+        profit_account
+            .tokens
+            .credit(&secondary_token, secondary_profit)
+            .map_err(Self::map_exchange_error)?;
 
         Ok(())
     }
 
+    /// Computes `a * b / c` without overflowing the intermediate product
+    fn mul_div(a: u64, b: u64, c: u64) -> Result<u64, InstructionError> {
+        let result = u128::from(a) * u128::from(b) / u128::from(c);
+        if result > u128::from(std::u64::MAX) {
+            error!("Overflow computing {} * {} / {}", a, b, c);
+            Err(InstructionError::InvalidArgument)?
+        }
+        Ok(result as u64)
+    }
+
+    /// Fills an incoming order against the resting orders on the other side of the order book,
+    /// best price first and oldest first within a price.  Fills execute at the resting order's
+    /// price and secondary token amounts are rounded down.  Filled resting orders move off the
+    /// book until their owner claims them, matching stops once no more of them can be held.
+    /// Returns the number of the incoming order's tokens left unfilled and the number of tokens
+    /// it received.
+    fn match_order(
+        scaler: u64,
+        order_book: &mut OrderBookInfo,
+        direction: Direction,
+        mut tokens: u64,
+        price: u64,
+    ) -> Result<(u64, u64), InstructionError> {
+        let mut tokens_settled: u64 = 0;
+        let OrderBookInfo {
+            to_orders,
+            from_orders,
+            filled_orders,
+            ..
+        } = order_book;
+        let resting_orders = match direction {
+            Direction::To => from_orders,
+            Direction::From => to_orders,
+        };
+
+        let mut index = 0;
+        while tokens > 0 && index < resting_orders.len() {
+            let order = &mut resting_orders[index];
+            let crosses = match direction {
+                Direction::To => order.price >= price,
+                Direction::From => order.price <= price,
+            };
+            if !crosses {
+                break;
+            }
+
+            let primary_tokens = match direction {
+                Direction::To => {
+                    cmp::min(tokens, Self::mul_div(order.tokens, scaler, order.price)?)
+                }
+                Direction::From => {
+                    cmp::min(order.tokens, Self::mul_div(tokens, scaler, order.price)?)
+                }
+            };
+            let secondary_tokens = Self::mul_div(primary_tokens, order.price, scaler)?;
+            if primary_tokens == 0 || secondary_tokens == 0 {
+                index += 1;
+                continue;
+            }
+
+            // Tokens the incoming order spends are the ones the resting order receives
+            let (spent, received) = match direction {
+                Direction::To => (primary_tokens, secondary_tokens),
+                Direction::From => (secondary_tokens, primary_tokens),
+            };
+            let (order_spent, order_received) = (received, spent);
+            let filled = order.tokens == order_spent;
+            if filled && filled_orders.len() >= MAX_FILLED_ORDERS_PER_BOOK {
+                warn!("Too many filled orders waiting to be claimed");
+                break;
+            }
+
+            trace!(
+                "fill {} for {} at {}",
+                primary_tokens,
+                secondary_tokens,
+                order.price
+            );
+
+            tokens -= spent;
+            tokens_settled = tokens_settled.checked_add(received).ok_or_else(|| {
+                error!("Overflow settling {} tokens", received);
+                InstructionError::InvalidArgument
+            })?;
+            order.tokens -= order_spent;
+            order.tokens_settled = order
+                .tokens_settled
+                .checked_add(order_received)
+                .ok_or_else(|| {
+                    error!("Overflow settling {} tokens", order_received);
+                    InstructionError::InvalidArgument
+                })?;
+            inc_new_counter_info!("exchange_processor-fills", 1, 1000, 1000);
+
+            if filled {
+                filled_orders.push(resting_orders.remove(index));
+            } else {
+                index += 1;
+            }
+        }
+
+        Ok((tokens, tokens_settled))
+    }
+
     fn do_account_request(keyed_accounts: &mut [KeyedAccount]) -> Result<(), InstructionError> {
         const OWNER_INDEX: usize = 0;
         const NEW_ACCOUNT_INDEX: usize = 1;
@@ -167,9 +303,7 @@ impl ExchangeProcessor {
         Self::is_account_unallocated(&keyed_accounts[NEW_ACCOUNT_INDEX].account.data)?;
         Self::serialize(
             &ExchangeState::Account(
-                TokenAccountInfo::default()
-                    .owner(&keyed_accounts[OWNER_INDEX].unsigned_key())
-                    .tokens(100_000, 100_000, 100_000, 100_000),
+                TokenAccountInfo::default().owner(&keyed_accounts[OWNER_INDEX].unsigned_key()),
             ),
             &mut keyed_accounts[NEW_ACCOUNT_INDEX].account.data,
         )
@@ -193,7 +327,10 @@ impl ExchangeProcessor {
             Self::deserialize_account(&keyed_accounts[TO_ACCOUNT_INDEX].account.data)?;
 
         if &id() == keyed_accounts[FROM_ACCOUNT_INDEX].unsigned_key() {
-            to_account.tokens[token] += tokens;
+            to_account
+                .tokens
+                .credit(&token, tokens)
+                .map_err(Self::map_exchange_error)?;
         } else {
             let state: ExchangeState =
                 bincode::deserialize(&keyed_accounts[FROM_ACCOUNT_INDEX].account.data)
@@ -205,13 +342,14 @@ impl ExchangeProcessor {
                         Err(InstructionError::GenericError)?
                     }
 
-                    if from_account.tokens[token] < tokens {
-                        error!("From account balance too low");
-                        Err(InstructionError::GenericError)?
-                    }
-
-                    from_account.tokens[token] -= tokens;
-                    to_account.tokens[token] += tokens;
+                    from_account
+                        .tokens
+                        .debit(&token, tokens)
+                        .map_err(Self::map_exchange_error)?;
+                    to_account
+                        .tokens
+                        .credit(&token, tokens)
+                        .map_err(Self::map_exchange_error)?;
 
                     Self::serialize(
                         &ExchangeState::Account(from_account),
@@ -239,7 +377,10 @@ impl ExchangeProcessor {
                     }
 
                     from_trade.tokens_settled -= tokens;
-                    to_account.tokens[token] += tokens;
+                    to_account
+                        .tokens
+                        .credit(&token, tokens)
+                        .map_err(Self::map_exchange_error)?;
 
                     Self::serialize(
                         &ExchangeState::Trade(from_trade),
@@ -266,14 +407,45 @@ impl ExchangeProcessor {
         const OWNER_INDEX: usize = 0;
         const TRADE_INDEX: usize = 1;
         const ACCOUNT_INDEX: usize = 2;
+        const ORDER_BOOK_INDEX: usize = 3;
 
-        if keyed_accounts.len() < 3 {
+        if keyed_accounts.len() < 4 {
             error!("Not enough accounts");
             Err(InstructionError::InvalidArgument)?
         }
 
+        if !info.pair.is_valid() {
+            error!("Invalid token pair {}", info.pair);
+            Err(InstructionError::InvalidArgument)?
+        }
+
         Self::is_account_unallocated(&keyed_accounts[TRADE_INDEX].account.data)?;
 
+        // Order books are created on demand by the first trade of their pair, at the pair's
+        // address so there's only ever one per pair
+        if keyed_accounts[ORDER_BOOK_INDEX].unsigned_key() != &info.pair.order_book_address() {
+            error!("Not the order book address of pair {}", info.pair);
+            Err(InstructionError::InvalidArgument)?
+        }
+        let order_book_state: ExchangeState =
+            bincode::deserialize(&keyed_accounts[ORDER_BOOK_INDEX].account.data)
+                .map_err(Self::map_to_invalid_arg)?;
+        let create_order_book = order_book_state == ExchangeState::Unallocated;
+        let mut order_book = match order_book_state {
+            ExchangeState::Unallocated => OrderBookInfo::new(info.pair),
+            ExchangeState::OrderBook(order_book) => {
+                if order_book.pair != info.pair {
+                    error!("Order book is not for pair {}", info.pair);
+                    Err(InstructionError::InvalidArgument)?
+                }
+                order_book
+            }
+            _ => {
+                error!("Not a valid order book");
+                Err(InstructionError::InvalidAccountData)?
+            }
+        };
+
         let mut account = Self::deserialize_account(&keyed_accounts[ACCOUNT_INDEX].account.data)?;
 
         if &account.owner != keyed_accounts[OWNER_INDEX].unsigned_key() {
@@ -284,61 +456,146 @@ impl ExchangeProcessor {
             Direction::To => info.pair.primary(),
             Direction::From => info.pair.secondary(),
         };
-        if account.tokens[from_token] < info.tokens {
-            error!("From token balance is too low");
-            Err(InstructionError::GenericError)?
-        }
 
         if let Err(e) = check_trade(info.direction, info.tokens, info.price) {
             bincode::serialize(&e).unwrap();
         }
 
         // Trade holds the tokens in escrow
-        account.tokens[from_token] -= info.tokens;
+        account
+            .tokens
+            .debit(&from_token, info.tokens)
+            .map_err(Self::map_exchange_error)?;
 
         inc_new_counter_info!("exchange_processor-trades", 1, 1000, 1000);
 
-        Self::serialize(
-            &ExchangeState::Trade(TradeOrderInfo {
-                owner: *keyed_accounts[OWNER_INDEX].unsigned_key(),
-                direction: info.direction,
-                pair: info.pair,
-                tokens: info.tokens,
-                price: info.price,
-                tokens_settled: 0,
-            }),
-            &mut keyed_accounts[TRADE_INDEX].account.data,
-        )?;
+        let mut trade = TradeOrderInfo {
+            owner: *keyed_accounts[OWNER_INDEX].unsigned_key(),
+            direction: info.direction,
+            pair: info.pair,
+            tokens: info.tokens,
+            price: info.price,
+            tokens_settled: 0,
+            order_book: None,
+        };
+
+        let trade_state = match info.mode {
+            TradeMode::Swap => ExchangeState::Trade(trade),
+            TradeMode::Match => {
+                if info.price == 0 {
+                    error!("Trade price is zero");
+                    Err(InstructionError::InvalidArgument)?
+                }
+
+                let (tokens, tokens_settled) = Self::match_order(
+                    SCALER,
+                    &mut order_book,
+                    info.direction,
+                    info.tokens,
+                    info.price,
+                )?;
+                trade.tokens_settled = tokens_settled;
+
+                if tokens == 0 {
+                    // Filled right away, turn into token account
+                    ExchangeState::Account(Self::trade_to_token_account(&trade))
+                } else {
+                    // The order book holds the remainder in escrow until it is filled or canceled
+                    order_book
+                        .insert(
+                            info.direction,
+                            OrderInfo {
+                                trade: *keyed_accounts[TRADE_INDEX].unsigned_key(),
+                                owner: trade.owner,
+                                price: info.price,
+                                tokens,
+                                tokens_settled: 0,
+                            },
+                        )
+                        .map_err(Self::map_exchange_error)?;
+                    trade.tokens = 0;
+                    trade.order_book = Some(*keyed_accounts[ORDER_BOOK_INDEX].unsigned_key());
+                    ExchangeState::Trade(trade)
+                }
+            }
+        };
+
+        Self::serialize(&trade_state, &mut keyed_accounts[TRADE_INDEX].account.data)?;
         Self::serialize(
             &ExchangeState::Account(account),
             &mut keyed_accounts[ACCOUNT_INDEX].account.data,
-        )
+        )?;
+
+        if create_order_book {
+            inc_new_counter_info!("exchange_processor-order_books", 1, 1000, 1000);
+        }
+        if create_order_book || info.mode == TradeMode::Match {
+            Self::serialize(
+                &ExchangeState::OrderBook(order_book),
+                &mut keyed_accounts[ORDER_BOOK_INDEX].account.data,
+            )?;
+        }
+        Ok(())
     }
 
     fn do_trade_cancellation(keyed_accounts: &mut [KeyedAccount]) -> Result<(), InstructionError> {
         const OWNER_INDEX: usize = 0;
         const TRADE_INDEX: usize = 1;
+        const ORDER_BOOK_INDEX: usize = 2;
 
         if keyed_accounts.len() < 2 {
             error!("Not enough accounts");
             Err(InstructionError::InvalidArgument)?
         }
 
-        let trade = Self::deserialize_trade(&keyed_accounts[TRADE_INDEX].account.data)?;
+        let mut trade = Self::deserialize_trade(&keyed_accounts[TRADE_INDEX].account.data)?;
 
         if &trade.owner != keyed_accounts[OWNER_INDEX].unsigned_key() {
             error!("Signer does not own trade");
             Err(InstructionError::GenericError)?
         }
 
-        let token = match trade.direction {
-            Direction::To => trade.pair.primary(),
-            Direction::From => trade.pair.secondary(),
+        if let Some(order_book_key) = trade.order_book {
+            // Take the order out of the book along with its escrow and settlements
+            if keyed_accounts.len() < 3 {
+                error!("Not enough accounts");
+                Err(InstructionError::InvalidArgument)?
+            }
+            if keyed_accounts[ORDER_BOOK_INDEX].unsigned_key() != &order_book_key {
+                error!("Trade does not rest in order book");
+                Err(InstructionError::InvalidArgument)?
+            }
+            let mut order_book =
+                Self::deserialize_order_book(&keyed_accounts[ORDER_BOOK_INDEX].account.data)?;
+            let order = order_book
+                .remove(keyed_accounts[TRADE_INDEX].unsigned_key())
+                .ok_or_else(|| {
+                    error!("Trade not found in order book");
+                    InstructionError::InvalidAccountData
+                })?;
+            trade.tokens += order.tokens;
+            trade.tokens_settled += order.tokens_settled;
+            Self::serialize(
+                &ExchangeState::OrderBook(order_book),
+                &mut keyed_accounts[ORDER_BOOK_INDEX].account.data,
+            )?;
+        }
+
+        let (token, settled_token) = match trade.direction {
+            Direction::To => (trade.pair.primary(), trade.pair.secondary()),
+            Direction::From => (trade.pair.secondary(), trade.pair.primary()),
         };
 
+        // Return the escrowed tokens along with whatever has been settled so far
         let mut account = TokenAccountInfo::default().owner(&trade.owner);
-        account.tokens[token] = trade.tokens;
-        account.tokens[token] += trade.tokens_settled;
+        account
+            .tokens
+            .credit(&token, trade.tokens)
+            .map_err(Self::map_exchange_error)?;
+        account
+            .tokens
+            .credit(&settled_token, trade.tokens_settled)
+            .map_err(Self::map_exchange_error)?;
 
         // Turn trade order into a token account
         Self::serialize(
@@ -363,6 +620,10 @@ impl ExchangeProcessor {
         let mut profit_account =
             Self::deserialize_account(&keyed_accounts[PROFIT_ACCOUNT_INDEX].account.data)?;
 
+        if to_trade.order_book.is_some() || from_trade.order_book.is_some() {
+            error!("Trade rests in an order book");
+            Err(InstructionError::InvalidArgument)?
+        }
         if to_trade.direction != Direction::To {
             error!("To trade is not a To");
             Err(InstructionError::InvalidArgument)?
@@ -395,7 +656,7 @@ impl ExchangeProcessor {
         if to_trade.tokens == 0 {
             // Turn into token account
             Self::serialize(
-                &ExchangeState::Account(Self::trade_to_token_account(&from_trade)),
+                &ExchangeState::Account(Self::trade_to_token_account(&to_trade)),
                 &mut keyed_accounts[TO_TRADE_INDEX].account.data,
             )?;
         } else {
@@ -467,7 +728,18 @@ mod test {
     use soros_sdk::genesis_block::GenesisBlock;
     use soros_sdk::signature::{Keypair, KeypairUtil};
     use soros_sdk::system_instruction;
-    use std::mem;
+
+    fn primary_token() -> Token {
+        Pubkey::new(&[1; 32])
+    }
+
+    fn secondary_token() -> Token {
+        Pubkey::new(&[2; 32])
+    }
+
+    fn profit(primary: u64, secondary: u64) -> Tokens {
+        Tokens::new(&[(primary_token(), primary), (secondary_token(), secondary)])
+    }
 
     fn try_calc(
         scaler: u64,
@@ -488,8 +760,11 @@ mod test {
             secondary_tokens,
             secondary_price,
         );
-        let mut to_trade = TradeOrderInfo::default();
-        let mut from_trade = TradeOrderInfo::default().direction(Direction::From);
+        let pair = TokenPair::new(primary_token(), secondary_token());
+        let mut to_trade = TradeOrderInfo::default().pair(pair);
+        let mut from_trade = TradeOrderInfo::default()
+            .pair(pair)
+            .direction(Direction::From);
         let mut profit_account = TokenAccountInfo::default();
 
         to_trade.tokens = primary_tokens;
@@ -528,22 +803,157 @@ mod test {
     fn test_calculate_swap() {
         soros_logger::setup();
 
-        try_calc(1,     50,     2,   50,    1,  0, 0, 50,   50, Tokens::new(   0, 0, 0, 0)).unwrap_err();
-        try_calc(1,     50,     1,    0,    1,  0, 0, 50,   50, Tokens::new(   0, 0, 0, 0)).unwrap_err();
-        try_calc(1,      0,     1,   50,    1,  0, 0, 50,   50, Tokens::new(   0, 0, 0, 0)).unwrap_err();
-        try_calc(1,     50,     1,   50,    0,  0, 0, 50,   50, Tokens::new(   0, 0, 0, 0)).unwrap_err();
-        try_calc(1,     50,     0,   50,    1,  0, 0, 50,   50, Tokens::new(   0, 0, 0, 0)).unwrap_err();
-        try_calc(1,       1,    2,    2,    3,  1, 2,  0,    0, Tokens::new(   0, 0, 0, 0)).unwrap_err();
+        try_calc(1,     50,     2,   50,    1,  0, 0, 50,   50, profit(   0, 0)).unwrap_err();
+        try_calc(1,     50,     1,    0,    1,  0, 0, 50,   50, profit(   0, 0)).unwrap_err();
+        try_calc(1,      0,     1,   50,    1,  0, 0, 50,   50, profit(   0, 0)).unwrap_err();
+        try_calc(1,     50,     1,   50,    0,  0, 0, 50,   50, profit(   0, 0)).unwrap_err();
+        try_calc(1,     50,     0,   50,    1,  0, 0, 50,   50, profit(   0, 0)).unwrap_err();
+        try_calc(1,       1,    2,    2,    3,  1, 2,  0,    0, profit(   0, 0)).unwrap_err();
+
+        try_calc(1,     50,     1,   50,    1,  0, 0, 50,   50, profit(   0, 0)).unwrap();
+        try_calc(1,       1,    2,    3,    3,  0, 0,  2,    1, profit(   0, 1)).unwrap();
+        try_calc(1,       2,    2,    3,    3,  1, 0,  2,    1, profit(   0, 1)).unwrap();
+        try_calc(1,       3,    2,    3,    3,  2, 0,  2,    1, profit(   0, 1)).unwrap();
+        try_calc(1,       3,    2,    6,    3,  1, 0,  4,    2, profit(   0, 2)).unwrap();
+        try_calc(1000,    1, 2000,    3, 3000,  0, 0,  2,    1, profit(   0, 1)).unwrap();
+        try_calc(1,       3,    2,    7,    3,  1, 1,  4,    2, profit(   0, 2)).unwrap();
+        try_calc(1000, 3000,  333, 1000,  500,  0, 1,999, 1998, profit(1002, 0)).unwrap();
+        try_calc(1000,   50,  100,   50,  101,  0,45,  5,   49, profit(   1, 0)).unwrap();
+    }
 
-        try_calc(1,     50,     1,   50,    1,  0, 0, 50,   50, Tokens::new(   0, 0, 0, 0)).unwrap();
-        try_calc(1,       1,    2,    3,    3,  0, 0,  2,    1, Tokens::new(   0, 1, 0, 0)).unwrap();
-        try_calc(1,       2,    2,    3,    3,  1, 0,  2,    1, Tokens::new(   0, 1, 0, 0)).unwrap();
-        try_calc(1,       3,    2,    3,    3,  2, 0,  2,    1, Tokens::new(   0, 1, 0, 0)).unwrap();
-        try_calc(1,       3,    2,    6,    3,  1, 0,  4,    2, Tokens::new(   0, 2, 0, 0)).unwrap();
-        try_calc(1000,    1, 2000,    3, 3000,  0, 0,  2,    1, Tokens::new(   0, 1, 0, 0)).unwrap();
-        try_calc(1,       3,    2,    7,    3,  1, 1,  4,    2, Tokens::new(   0, 2, 0, 0)).unwrap();
-        try_calc(1000, 3000,  333, 1000,  500,  0, 1,999, 1998, Tokens::new(1002, 0, 0, 0)).unwrap();
-        try_calc(1000,   50,  100,   50,  101,  0,45,  5,   49, Tokens::new(   1, 0, 0, 0)).unwrap();
+    fn resting(price: u64, tokens: u64) -> OrderInfo {
+        OrderInfo {
+            trade: Pubkey::new_rand(),
+            price,
+            tokens,
+            ..OrderInfo::default()
+        }
+    }
+
+    #[test]
+    fn test_match_order_price_time_priority() {
+        let mut order_book = OrderBookInfo::default();
+        for order in &[
+            resting(2000, 4),
+            resting(2000, 4),
+            resting(1000, 2),
+            resting(3000, 10),
+        ] {
+            order_book.insert(Direction::To, *order).unwrap();
+        }
+
+        // Spend up to 16 secondary tokens on primary tokens at no more than 2000
+        assert_eq!(
+            ExchangeProcessor::match_order(1000, &mut order_book, Direction::From, 16, 2000),
+            Ok((0, 9))
+        );
+
+        // Best price first, then oldest first, filled orders leave the book
+        let fills = |orders: &[OrderInfo]| -> Vec<_> {
+            orders
+                .iter()
+                .map(|order| (order.price, order.tokens, order.tokens_settled))
+                .collect()
+        };
+        assert_eq!(
+            fills(&order_book.to_orders),
+            vec![(2000, 1, 6), (3000, 10, 0)]
+        );
+        assert_eq!(
+            fills(&order_book.filled_orders),
+            vec![(1000, 0, 2), (2000, 0, 8)]
+        );
+
+        assert_eq!(
+            ExchangeProcessor::match_order(1000, &mut order_book, Direction::From, 4, 2000),
+            Ok((2, 1))
+        );
+        assert_eq!(order_book.len(), 1);
+        assert_eq!(order_book.filled_orders.len(), 3);
+    }
+
+    #[test]
+    fn test_match_order_partial_fill() {
+        let mut order_book = OrderBookInfo::default();
+        order_book
+            .insert(Direction::From, resting(1000, 10))
+            .unwrap();
+        order_book
+            .insert(Direction::From, resting(1500, 30))
+            .unwrap();
+
+        // Sell 30 primary tokens at no less than 1200, only the 1500 order crosses
+        assert_eq!(
+            ExchangeProcessor::match_order(1000, &mut order_book, Direction::To, 30, 1200),
+            Ok((10, 30))
+        );
+        assert_eq!(order_book.filled_orders[0].tokens, 0);
+        assert_eq!(order_book.filled_orders[0].tokens_settled, 20);
+        assert_eq!(order_book.from_orders[0].tokens, 10);
+        assert_eq!(order_book.from_orders[0].tokens_settled, 0);
+
+        // Nothing crosses
+        assert_eq!(
+            ExchangeProcessor::match_order(1000, &mut order_book, Direction::To, 30, 1200),
+            Ok((30, 0))
+        );
+    }
+
+    #[test]
+    fn test_match_order_overflow() {
+        // Scaling the resting order's tokens overflows a u64 but the fill itself fits
+        let mut order_book = OrderBookInfo::default();
+        order_book
+            .insert(Direction::From, resting(1000, std::u64::MAX))
+            .unwrap();
+        assert_eq!(
+            ExchangeProcessor::match_order(1000, &mut order_book, Direction::To, 10, 1000),
+            Ok((0, 10))
+        );
+
+        // Buying more primary tokens than fit in a u64 is an error
+        let mut order_book = OrderBookInfo::default();
+        order_book
+            .insert(Direction::From, resting(1, std::u64::MAX))
+            .unwrap();
+        assert_eq!(
+            ExchangeProcessor::match_order(1000, &mut order_book, Direction::To, 10, 1),
+            Err(InstructionError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_match_order_filled_orders_full() {
+        let mut order_book = OrderBookInfo::default();
+        for _ in 0..MAX_FILLED_ORDERS_PER_BOOK {
+            order_book
+                .insert(Direction::From, resting(1000, 1))
+                .unwrap();
+            assert_eq!(
+                ExchangeProcessor::match_order(1000, &mut order_book, Direction::To, 1, 1000),
+                Ok((0, 1))
+            );
+            assert!(order_book.is_empty());
+        }
+
+        // Matching stops short of filling an order there is no room to hold
+        order_book
+            .insert(Direction::From, resting(1000, 1))
+            .unwrap();
+        assert_eq!(
+            ExchangeProcessor::match_order(1000, &mut order_book, Direction::To, 1, 1000),
+            Ok((1, 0))
+        );
+        assert_eq!(order_book.len(), 1);
+
+        // Claiming a filled order makes room again
+        let claimed = order_book.filled_orders[0].trade;
+        assert!(order_book.remove(&claimed).is_some());
+        assert_eq!(
+            ExchangeProcessor::match_order(1000, &mut order_book, Direction::To, 1, 1000),
+            Ok((0, 1))
+        );
+        assert!(order_book.is_empty());
     }
 
     // fn create_bank(lamports: u64) -> (Bank, Keypair) {
@@ -571,7 +981,22 @@ mod test {
             &owner.pubkey(),
             &new,
             1,
-            mem::size_of::<ExchangeState>() as u64,
+            exchange_account_size(),
+            &id(),
+        );
+        client
+            .send_instruction(&owner, instruction)
+            .expect(&format!("{}:{}", line!(), file!()));
+        new
+    }
+
+    fn create_order_book(client: &BankClient, owner: &Keypair, pair: TokenPair) -> Pubkey {
+        let new = pair.order_book_address();
+        let instruction = system_instruction::create_account(
+            &owner.pubkey(),
+            &new,
+            1,
+            order_book_account_size(),
             &id(),
         );
         client
@@ -602,13 +1027,17 @@ mod test {
         owner: &Keypair,
         direction: Direction,
         pair: TokenPair,
-        from_token: Token,
         src_tokens: u64,
         trade_tokens: u64,
         price: u64,
+        order_book: &Pubkey,
     ) -> (Pubkey, Pubkey) {
         let trade = create_account(&client, &owner);
         let src = create_token_account(&client, &owner);
+        let from_token = match direction {
+            Direction::To => pair.primary(),
+            Direction::From => pair.secondary(),
+        };
         transfer(&client, &owner, &src, from_token, src_tokens);
 
         let instruction = exchange_instruction::trade_request(
@@ -619,6 +1048,7 @@ mod test {
             trade_tokens,
             price,
             &src,
+            order_book,
         );
         client
             .send_instruction(owner, instruction)
@@ -626,6 +1056,11 @@ mod test {
         (trade, src)
     }
 
+    fn deserialize_state(client: &BankClient, pubkey: &Pubkey) -> ExchangeState {
+        let data = client.get_account_data(pubkey).unwrap().unwrap();
+        bincode::deserialize(&data).unwrap()
+    }
+
     #[test]
     fn test_exchange_new_account() {
         soros_logger::setup();
@@ -638,9 +1073,7 @@ mod test {
         // Check results
 
         assert_eq!(
-            TokenAccountInfo::default()
-                .owner(&owner.pubkey())
-                .tokens(100_000, 100_000, 100_000, 100_000),
+            TokenAccountInfo::default().owner(&owner.pubkey()),
             ExchangeProcessor::deserialize_account(&new_account_data).unwrap()
         );
     }
@@ -663,11 +1096,12 @@ mod test {
         soros_logger::setup();
         let (bank, mint_keypair) = create_bank(10_000);
         let (client, owner) = create_client(bank, mint_keypair);
+        let token = Pubkey::new_rand();
 
         let new = create_token_account(&client, &owner);
 
         let instruction =
-            exchange_instruction::transfer_request(&owner.pubkey(), &new, &id(), Token::A, 42);
+            exchange_instruction::transfer_request(&owner.pubkey(), &new, &id(), token, 42);
         client
             .send_instruction(&owner, instruction)
             .expect(&format!("{}:{}", line!(), file!()));
@@ -679,26 +1113,52 @@ mod test {
         assert_eq!(
             TokenAccountInfo::default()
                 .owner(&owner.pubkey())
-                .tokens(100_042, 100_000, 100_000, 100_000),
+                .tokens(&[(token, 42)]),
             ExchangeProcessor::deserialize_account(&new_account_data).unwrap()
         );
     }
 
+    #[test]
+    fn test_exchange_transfer_too_many_tokens() {
+        soros_logger::setup();
+        let (bank, mint_keypair) = create_bank(10_000);
+        let (client, owner) = create_client(bank, mint_keypair);
+
+        let new = create_token_account(&client, &owner);
+        for _ in 0..MAX_TOKENS_PER_ACCOUNT {
+            transfer(&client, &owner, &new, Pubkey::new_rand(), 1);
+        }
+
+        let instruction = exchange_instruction::transfer_request(
+            &owner.pubkey(),
+            &new,
+            &id(),
+            Pubkey::new_rand(),
+            1,
+        );
+        client
+            .send_instruction(&owner, instruction)
+            .expect_err(&format!("{}:{}", line!(), file!()));
+    }
+
     #[test]
     fn test_exchange_new_trade_request() {
         soros_logger::setup();
         let (bank, mint_keypair) = create_bank(10_000);
         let (client, owner) = create_client(bank, mint_keypair);
+        let (a, b) = (Pubkey::new_rand(), Pubkey::new_rand());
+        let pair = TokenPair::new(a, b);
+        let order_book = create_order_book(&client, &owner, pair);
 
         let (trade, src) = trade(
             &client,
             &owner,
             Direction::To,
-            TokenPair::AB,
-            Token::A,
+            pair,
             42,
             2,
             1000,
+            &order_book,
         );
 
         let trade_account_data = client.get_account_data(&trade).unwrap().unwrap();
@@ -710,47 +1170,186 @@ mod test {
             TradeOrderInfo {
                 owner: owner.pubkey(),
                 direction: Direction::To,
-                pair: TokenPair::AB,
+                pair,
                 tokens: 2,
                 price: 1000,
-                tokens_settled: 0
+                tokens_settled: 0,
+                order_book: None,
             },
             ExchangeProcessor::deserialize_trade(&trade_account_data).unwrap()
         );
         assert_eq!(
             TokenAccountInfo::default()
                 .owner(&owner.pubkey())
-                .tokens(100_040, 100_000, 100_000, 100_000),
+                .tokens(&[(a, 40)]),
             ExchangeProcessor::deserialize_account(&src_account_data).unwrap()
         );
     }
 
+    #[test]
+    fn test_exchange_order_book_created_on_demand() {
+        soros_logger::setup();
+        let (bank, mint_keypair) = create_bank(10_000);
+        let (client, owner) = create_client(bank, mint_keypair);
+        let (a, b) = (Pubkey::new_rand(), Pubkey::new_rand());
+        let pair = TokenPair::new(a, b);
+        let order_book = create_order_book(&client, &owner, pair);
+
+        trade(
+            &client,
+            &owner,
+            Direction::To,
+            pair,
+            2,
+            2,
+            1000,
+            &order_book,
+        );
+        assert_eq!(
+            ExchangeState::OrderBook(OrderBookInfo::new(pair)),
+            deserialize_state(&client, &order_book)
+        );
+
+        // The same order book serves later trades of the pair
+        trade(
+            &client,
+            &owner,
+            Direction::From,
+            pair,
+            2,
+            2,
+            1000,
+            &order_book,
+        );
+
+        // But not trades of any other pair
+        let trade = create_account(&client, &owner);
+        let src = create_token_account(&client, &owner);
+        transfer(&client, &owner, &src, b, 2);
+        let instruction = exchange_instruction::trade_request(
+            &owner.pubkey(),
+            &trade,
+            Direction::To,
+            TokenPair::new(b, a),
+            2,
+            1000,
+            &src,
+            &order_book,
+        );
+        client
+            .send_instruction(&owner, instruction)
+            .expect_err(&format!("{}:{}", line!(), file!()));
+    }
+
+    #[test]
+    fn test_exchange_one_order_book_per_pair() {
+        soros_logger::setup();
+        let (bank, mint_keypair) = create_bank(10_000);
+        let (client, owner) = create_client(bank, mint_keypair);
+        let (a, b) = (Pubkey::new_rand(), Pubkey::new_rand());
+        let pair = TokenPair::new(a, b);
+        let order_book = create_order_book(&client, &owner, pair);
+        trade(
+            &client,
+            &owner,
+            Direction::To,
+            pair,
+            2,
+            2,
+            1000,
+            &order_book,
+        );
+
+        // An account anywhere else can't become a second order book for the pair
+        let second_order_book = Pubkey::new_rand();
+        let instruction = system_instruction::create_account(
+            &owner.pubkey(),
+            &second_order_book,
+            1,
+            order_book_account_size(),
+            &id(),
+        );
+        client
+            .send_instruction(&owner, instruction)
+            .expect(&format!("{}:{}", line!(), file!()));
+
+        let trade = create_account(&client, &owner);
+        let src = create_token_account(&client, &owner);
+        transfer(&client, &owner, &src, a, 2);
+        let instruction = exchange_instruction::trade_request(
+            &owner.pubkey(),
+            &trade,
+            Direction::To,
+            pair,
+            2,
+            1000,
+            &src,
+            &second_order_book,
+        );
+        client
+            .send_instruction(&owner, instruction)
+            .expect_err(&format!("{}:{}", line!(), file!()));
+        assert_eq!(
+            ExchangeState::Unallocated,
+            deserialize_state(&client, &second_order_book)
+        );
+    }
+
+    #[test]
+    fn test_exchange_trade_request_invalid_pair() {
+        soros_logger::setup();
+        let (bank, mint_keypair) = create_bank(10_000);
+        let (client, owner) = create_client(bank, mint_keypair);
+        let a = Pubkey::new_rand();
+        let order_book = create_order_book(&client, &owner, TokenPair::new(a, a));
+
+        let trade = create_account(&client, &owner);
+        let src = create_token_account(&client, &owner);
+        transfer(&client, &owner, &src, a, 2);
+        let instruction = exchange_instruction::trade_request(
+            &owner.pubkey(),
+            &trade,
+            Direction::To,
+            TokenPair::new(a, a),
+            2,
+            1000,
+            &src,
+            &order_book,
+        );
+        client
+            .send_instruction(&owner, instruction)
+            .expect_err(&format!("{}:{}", line!(), file!()));
+    }
+
     #[test]
     fn test_exchange_new_swap_request() {
         soros_logger::setup();
         let (bank, mint_keypair) = create_bank(10_000);
         let (client, owner) = create_client(bank, mint_keypair);
+        let (a, b) = (Pubkey::new_rand(), Pubkey::new_rand());
+        let pair = TokenPair::new(a, b);
+        let order_book = create_order_book(&client, &owner, pair);
 
         let profit = create_token_account(&client, &owner);
         let (to_trade, _) = trade(
             &client,
             &owner,
             Direction::To,
-            TokenPair::AB,
-            Token::A,
+            pair,
             2,
             2,
             2000,
+            &order_book,
         );
         let (from_trade, _) = trade(
             &client,
             &owner,
             Direction::From,
-            TokenPair::AB,
-            Token::B,
+            pair,
             3,
             3,
             3000,
+            &order_book,
         );
 
         let instruction =
@@ -769,10 +1368,11 @@ mod test {
             TradeOrderInfo {
                 owner: owner.pubkey(),
                 direction: Direction::To,
-                pair: TokenPair::AB,
+                pair,
                 tokens: 1,
                 price: 2000,
                 tokens_settled: 2,
+                order_book: None,
             },
             ExchangeProcessor::deserialize_trade(&to_trade_account_data).unwrap()
         );
@@ -780,14 +1380,14 @@ mod test {
         assert_eq!(
             TokenAccountInfo::default()
                 .owner(&owner.pubkey())
-                .tokens(1, 0, 0, 0),
+                .tokens(&[(a, 1)]),
             ExchangeProcessor::deserialize_account(&from_trade_account_data).unwrap()
         );
 
         assert_eq!(
             TokenAccountInfo::default()
                 .owner(&owner.pubkey())
-                .tokens(100_000, 100_001, 100_000, 100_000),
+                .tokens(&[(b, 1)]),
             ExchangeProcessor::deserialize_account(&profit_account_data).unwrap()
         );
     }
@@ -797,27 +1397,30 @@ mod test {
         soros_logger::setup();
         let (bank, mint_keypair) = create_bank(10_000);
         let (client, owner) = create_client(bank, mint_keypair);
+        let (a, b) = (Pubkey::new_rand(), Pubkey::new_rand());
+        let pair = TokenPair::new(a, b);
+        let order_book = create_order_book(&client, &owner, pair);
 
         let profit = create_token_account(&client, &owner);
         let (to_trade, _) = trade(
             &client,
             &owner,
             Direction::To,
-            TokenPair::AB,
-            Token::A,
+            pair,
             3,
             3,
             2000,
+            &order_book,
         );
         let (from_trade, _) = trade(
             &client,
             &owner,
             Direction::From,
-            TokenPair::AB,
-            Token::B,
+            pair,
             3,
             3,
             3000,
+            &order_book,
         );
 
         let instruction =
@@ -829,13 +1432,13 @@ mod test {
         let new = create_token_account(&client, &owner);
 
         let instruction =
-            exchange_instruction::transfer_request(&owner.pubkey(), &new, &to_trade, Token::B, 1);
+            exchange_instruction::transfer_request(&owner.pubkey(), &new, &to_trade, b, 1);
         client
             .send_instruction(&owner, instruction)
             .expect(&format!("{}:{}", line!(), file!()));
 
         let instruction =
-            exchange_instruction::transfer_request(&owner.pubkey(), &new, &from_trade, Token::A, 1);
+            exchange_instruction::transfer_request(&owner.pubkey(), &new, &from_trade, a, 1);
         client
             .send_instruction(&owner, instruction)
             .expect(&format!("{}:{}", line!(), file!()));
@@ -847,8 +1450,224 @@ mod test {
         assert_eq!(
             TokenAccountInfo::default()
                 .owner(&owner.pubkey())
-                .tokens(100_001, 100_001, 100_000, 100_000),
+                .tokens(&[(a, 1), (b, 1)]),
             ExchangeProcessor::deserialize_account(&new_account_data).unwrap()
         );
     }
+
+    #[test]
+    fn test_exchange_trade_cancellation() {
+        soros_logger::setup();
+        let (bank, mint_keypair) = create_bank(10_000);
+        let (client, owner) = create_client(bank, mint_keypair);
+        let (a, b) = (Pubkey::new_rand(), Pubkey::new_rand());
+        let pair = TokenPair::new(a, b);
+        let order_book = create_order_book(&client, &owner, pair);
+
+        let profit = create_token_account(&client, &owner);
+        let (to_trade, _) = trade(
+            &client,
+            &owner,
+            Direction::To,
+            pair,
+            3,
+            3,
+            2000,
+            &order_book,
+        );
+        let (from_trade, _) = trade(
+            &client,
+            &owner,
+            Direction::From,
+            pair,
+            3,
+            3,
+            3000,
+            &order_book,
+        );
+
+        let instruction =
+            exchange_instruction::swap_request(&owner.pubkey(), &to_trade, &from_trade, &profit);
+        client
+            .send_instruction(&owner, instruction)
+            .expect(&format!("{}:{}", line!(), file!()));
+
+        let instruction = exchange_instruction::trade_cancellation(&owner.pubkey(), &to_trade);
+        client
+            .send_instruction(&owner, instruction)
+            .expect(&format!("{}:{}", line!(), file!()));
+
+        // Both the unfilled and the settled tokens are returned
+
+        let to_trade_account_data = client.get_account_data(&to_trade).unwrap().unwrap();
+        assert_eq!(
+            TokenAccountInfo::default()
+                .owner(&owner.pubkey())
+                .tokens(&[(a, 2), (b, 2)]),
+            ExchangeProcessor::deserialize_account(&to_trade_account_data).unwrap()
+        );
+    }
+
+    fn match_trade(
+        client: &BankClient,
+        owner: &Keypair,
+        direction: Direction,
+        pair: TokenPair,
+        tokens: u64,
+        price: u64,
+        order_book: &Pubkey,
+    ) -> Pubkey {
+        let trade = create_account(&client, &owner);
+        let src = create_token_account(&client, &owner);
+        let from_token = match direction {
+            Direction::To => pair.primary(),
+            Direction::From => pair.secondary(),
+        };
+        transfer(&client, &owner, &src, from_token, tokens);
+
+        let instruction = exchange_instruction::match_trade_request(
+            &owner.pubkey(),
+            &trade,
+            direction,
+            pair,
+            tokens,
+            price,
+            &src,
+            order_book,
+        );
+        client
+            .send_instruction(owner, instruction)
+            .expect(&format!("{}:{}", line!(), file!()));
+        trade
+    }
+
+    #[test]
+    fn test_exchange_match_trade_request() {
+        soros_logger::setup();
+        let (bank, mint_keypair) = create_bank(10_000);
+        let (client, owner) = create_client(bank, mint_keypair);
+        let (a, b) = (Pubkey::new_rand(), Pubkey::new_rand());
+        let pair = TokenPair::new(a, b);
+        let order_book = create_order_book(&client, &owner, pair);
+
+        // Nothing to match, the whole order rests in the book
+        let maker = match_trade(&client, &owner, Direction::To, pair, 10, 2000, &order_book);
+        let maker_account_data = client.get_account_data(&maker).unwrap().unwrap();
+        assert_eq!(
+            TradeOrderInfo {
+                owner: owner.pubkey(),
+                direction: Direction::To,
+                pair,
+                tokens: 0,
+                price: 2000,
+                tokens_settled: 0,
+                order_book: Some(order_book),
+            },
+            ExchangeProcessor::deserialize_trade(&maker_account_data).unwrap()
+        );
+
+        // Filled in full right away at the resting price
+        let taker = match_trade(
+            &client,
+            &owner,
+            Direction::From,
+            pair,
+            10,
+            2500,
+            &order_book,
+        );
+        let taker_account_data = client.get_account_data(&taker).unwrap().unwrap();
+        assert_eq!(
+            TokenAccountInfo::default()
+                .owner(&owner.pubkey())
+                .tokens(&[(a, 5)]),
+            ExchangeProcessor::deserialize_account(&taker_account_data).unwrap()
+        );
+
+        let order_book_data = client.get_account_data(&order_book).unwrap().unwrap();
+        let order_book_info = ExchangeProcessor::deserialize_order_book(&order_book_data).unwrap();
+        assert!(order_book_info.from_orders.is_empty());
+        assert_eq!(
+            order_book_info.to_orders,
+            vec![OrderInfo {
+                trade: maker,
+                owner: owner.pubkey(),
+                price: 2000,
+                tokens: 5,
+                tokens_settled: 10,
+            }]
+        );
+
+        // Canceling returns the unfilled tokens along with the settled ones
+        let instruction =
+            exchange_instruction::resting_trade_cancellation(&owner.pubkey(), &maker, &order_book);
+        client
+            .send_instruction(&owner, instruction)
+            .expect(&format!("{}:{}", line!(), file!()));
+
+        let maker_account_data = client.get_account_data(&maker).unwrap().unwrap();
+        assert_eq!(
+            TokenAccountInfo::default()
+                .owner(&owner.pubkey())
+                .tokens(&[(a, 5), (b, 10)]),
+            ExchangeProcessor::deserialize_account(&maker_account_data).unwrap()
+        );
+        let order_book_data = client.get_account_data(&order_book).unwrap().unwrap();
+        let order_book_info = ExchangeProcessor::deserialize_order_book(&order_book_data).unwrap();
+        assert!(order_book_info.is_empty());
+    }
+
+    #[test]
+    fn test_exchange_resting_trade_cancellation_needs_order_book() {
+        soros_logger::setup();
+        let (bank, mint_keypair) = create_bank(10_000);
+        let (client, owner) = create_client(bank, mint_keypair);
+        let pair = TokenPair::new(Pubkey::new_rand(), Pubkey::new_rand());
+        let order_book = create_order_book(&client, &owner, pair);
+        let other_pair = TokenPair::new(Pubkey::new_rand(), Pubkey::new_rand());
+        let other_order_book = create_order_book(&client, &owner, other_pair);
+        let trade = match_trade(&client, &owner, Direction::To, pair, 10, 2000, &order_book);
+
+        let instruction = exchange_instruction::trade_cancellation(&owner.pubkey(), &trade);
+        client
+            .send_instruction(&owner, instruction)
+            .expect_err(&format!("{}:{}", line!(), file!()));
+
+        let instruction = exchange_instruction::resting_trade_cancellation(
+            &owner.pubkey(),
+            &trade,
+            &other_order_book,
+        );
+        client
+            .send_instruction(&owner, instruction)
+            .expect_err(&format!("{}:{}", line!(), file!()));
+    }
+
+    #[test]
+    fn test_exchange_resting_trade_not_swappable() {
+        soros_logger::setup();
+        let (bank, mint_keypair) = create_bank(10_000);
+        let (client, owner) = create_client(bank, mint_keypair);
+        let pair = TokenPair::new(Pubkey::new_rand(), Pubkey::new_rand());
+        let order_book = create_order_book(&client, &owner, pair);
+
+        let profit = create_token_account(&client, &owner);
+        let to_trade = match_trade(&client, &owner, Direction::To, pair, 3, 2000, &order_book);
+        let (from_trade, _) = trade(
+            &client,
+            &owner,
+            Direction::From,
+            pair,
+            3,
+            3,
+            3000,
+            &order_book,
+        );
+
+        let instruction =
+            exchange_instruction::swap_request(&owner.pubkey(), &to_trade, &from_trade, &profit);
+        client
+            .send_instruction(&owner, instruction)
+            .expect_err(&format!("{}:{}", line!(), file!()));
+    }
 }
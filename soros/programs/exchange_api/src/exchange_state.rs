@@ -1,6 +1,9 @@
+use crate::id;
 use serde_derive::{Deserialize, Serialize};
+use soros_sdk::hash::hashv;
 use soros_sdk::pubkey::Pubkey;
-use std::{error, fmt};
+use std::collections::BTreeMap;
+use std::{cmp, error, fmt};
 
 /// Fixed-point scaler, 10 = one base 10 digit to the right of the decimal, 100 = 2, ...
 /// Used by both price and amount in their fixed point representation
@@ -9,105 +12,128 @@ pub const SCALER: u64 = 1000;
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum ExchangeError {
     InvalidTrade(String),
+    InsufficientFunds(String),
+    OrderBookFull(String),
+    BalanceOverflow(String),
 }
 impl error::Error for ExchangeError {}
 impl fmt::Display for ExchangeError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             ExchangeError::InvalidTrade(s) => write!(f, "{}", s),
+            ExchangeError::InsufficientFunds(s) => write!(f, "{}", s),
+            ExchangeError::OrderBookFull(s) => write!(f, "{}", s),
+            ExchangeError::BalanceOverflow(s) => write!(f, "{}", s),
         }
     }
 }
 
-/// Supported token types
-#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
-pub enum Token {
-    A,
-    B,
-    C,
-    D,
-}
-impl Default for Token {
-    fn default() -> Self {
-        Token::A
-    }
-}
+/// Tokens are identified by the pubkey of their mint account in the token program
+pub type Token = Pubkey;
 
-// Values of tokens, could be quantities, prices, etc...
-#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
-#[allow(non_snake_case)]
+/// Maximum number of distinct tokens a single token account can hold a balance of
+pub const MAX_TOKENS_PER_ACCOUNT: usize = 8;
+
+/// Token balances, keyed by the token's mint
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct Tokens {
-    pub A: u64,
-    pub B: u64,
-    pub C: u64,
-    pub D: u64,
+    balances: BTreeMap<Token, u64>,
 }
 impl Tokens {
-    pub fn new(a: u64, b: u64, c: u64, d: u64) -> Self {
-        Self {
-            A: a,
-            B: b,
-            C: c,
-            D: d,
+    /// Panics if the balances of a token add up to more than `u64::MAX`
+    pub fn new(balances: &[(Token, u64)]) -> Self {
+        let mut tokens = Self::default();
+        for (token, amount) in balances {
+            tokens.credit(token, *amount).unwrap();
         }
+        tokens
     }
-}
-impl std::ops::Index<Token> for Tokens {
-    type Output = u64;
-    fn index(&self, t: Token) -> &u64 {
-        match t {
-            Token::A => &self.A,
-            Token::B => &self.B,
-            Token::C => &self.C,
-            Token::D => &self.D,
+    /// Number of distinct tokens with a non-zero balance
+    pub fn len(&self) -> usize {
+        self.balances.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.balances.is_empty()
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (&Token, &u64)> {
+        self.balances.iter()
+    }
+    pub fn credit(&mut self, token: &Token, amount: u64) -> Result<(), ExchangeError> {
+        if amount > 0 {
+            let balance = self[token];
+            let balance = balance.checked_add(amount).ok_or_else(|| {
+                ExchangeError::BalanceOverflow(format!(
+                    "Balance of {} is {}, can't credit {} more",
+                    token, balance, amount
+                ))
+            })?;
+            self.balances.insert(*token, balance);
         }
+        Ok(())
     }
-}
-impl std::ops::IndexMut<Token> for Tokens {
-    fn index_mut(&mut self, t: Token) -> &mut u64 {
-        match t {
-            Token::A => &mut self.A,
-            Token::B => &mut self.B,
-            Token::C => &mut self.C,
-            Token::D => &mut self.D,
+    pub fn debit(&mut self, token: &Token, amount: u64) -> Result<(), ExchangeError> {
+        let balance = self[token];
+        if balance < amount {
+            Err(ExchangeError::InsufficientFunds(format!(
+                "Balance of {} is {}, {} required",
+                token, balance, amount
+            )))?
         }
+        if balance == amount {
+            self.balances.remove(token);
+        } else {
+            self.balances.insert(*token, balance - amount);
+        }
+        Ok(())
     }
 }
-
-#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
-#[allow(non_snake_case)]
-pub enum TokenPair {
-    AB,
-    AC,
-    AD,
-    BC,
-    BD,
-    CD,
-}
-impl Default for TokenPair {
-    fn default() -> Self {
-        TokenPair::AB
+impl<'a> std::ops::Index<&'a Token> for Tokens {
+    type Output = u64;
+    fn index(&self, t: &'a Token) -> &u64 {
+        static ZERO: u64 = 0;
+        self.balances.get(t).unwrap_or(&ZERO)
     }
 }
+
+/// Two tokens that can be exchanged for one another, first is primary
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct TokenPair {
+    primary: Token,
+    secondary: Token,
+}
 impl TokenPair {
+    pub fn new(primary: Token, secondary: Token) -> Self {
+        Self { primary, secondary }
+    }
     pub fn primary(self) -> Token {
-        match self {
-            TokenPair::AB | TokenPair::AC | TokenPair::AD => Token::A,
-            TokenPair::BC | TokenPair::BD => Token::B,
-            TokenPair::CD => Token::C,
-        }
+        self.primary
     }
     pub fn secondary(self) -> Token {
-        match self {
-            TokenPair::AB => Token::B,
-            TokenPair::AC | TokenPair::BC => Token::C,
-            TokenPair::AD | TokenPair::BD | TokenPair::CD => Token::D,
-        }
+        self.secondary
+    }
+    /// A token can't be exchanged for itself
+    pub fn is_valid(self) -> bool {
+        self.primary != self.secondary
+    }
+    /// Address of the pair's order book, each pair has exactly one
+    pub fn order_book_address(self) -> Pubkey {
+        let hash = hashv(&[
+            b"order_book",
+            self.primary.as_ref(),
+            self.secondary.as_ref(),
+            id().as_ref(),
+        ]);
+        Pubkey::new(hash.as_ref())
+    }
+}
+impl fmt::Display for TokenPair {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.primary, self.secondary)
     }
 }
 
 /// Token accounts are populated with this structure
-#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct TokenAccountInfo {
     /// Investor who owns this account
     pub owner: Pubkey,
@@ -119,13 +145,8 @@ impl TokenAccountInfo {
         self.owner = *owner;
         self
     }
-    pub fn tokens(mut self, a: u64, b: u64, c: u64, d: u64) -> Self {
-        self.tokens = Tokens {
-            A: a,
-            B: b,
-            C: c,
-            D: d,
-        };
+    pub fn tokens(mut self, balances: &[(Token, u64)]) -> Self {
+        self.tokens = Tokens::new(balances);
         self
     }
 }
@@ -166,16 +187,21 @@ pub struct TradeOrderInfo {
     /// Number of tokens that have been settled so far.  These nay be transferred to another
     /// token account by the owner.
     pub tokens_settled: u64,
+    /// Order book the trade order rests in.  The order book holds the unfilled tokens of a
+    /// resting trade order and the tokens settled after it was placed, they are returned by
+    /// canceling the trade order.
+    pub order_book: Option<Pubkey>,
 }
 impl Default for TradeOrderInfo {
     fn default() -> Self {
         Self {
             owner: Pubkey::default(),
-            pair: TokenPair::AB,
+            pair: TokenPair::default(),
             direction: Direction::To,
             tokens: 0,
             price: 0,
             tokens_settled: 0,
+            order_book: None,
         }
     }
 }
@@ -196,6 +222,10 @@ impl TradeOrderInfo {
         self.price = price;
         self
     }
+    pub fn order_book(mut self, order_book: Pubkey) -> Self {
+        self.order_book = Some(order_book);
+        self
+    }
 }
 
 pub fn check_trade(direction: Direction, tokens: u64, price: u64) -> Result<(), ExchangeError> {
@@ -220,8 +250,93 @@ pub fn check_trade(direction: Direction, tokens: u64, price: u64) -> Result<(),
     Ok(())
 }
 
+/// Maximum number of resting orders an order book can hold
+pub const MAX_ORDERS_PER_BOOK: usize = 64;
+
+/// Maximum number of filled orders an order book holds until their owners claim them
+pub const MAX_FILLED_ORDERS_PER_BOOK: usize = 64;
+
+/// A limit order resting in an order book
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct OrderInfo {
+    /// Trade order account that placed this order
+    pub trade: Pubkey,
+    /// Owner of the trade order
+    pub owner: Pubkey,
+    /// Scaled price, see `TradeOrderInfo::price`
+    pub price: u64,
+    /// Number of unfilled tokens held in escrow; primary or secondary depending on direction
+    pub tokens: u64,
+    /// Number of tokens this order has received from fills so far
+    pub tokens_settled: u64,
+}
+
+/// Order book accounts are populated with this structure, one order book exists per token pair
+/// and is created by the first trade request made against it
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct OrderBookInfo {
+    /// Token pair traded in this order book
+    pub pair: TokenPair,
+    /// Resting `To` orders, lowest price first and oldest first within a price
+    pub to_orders: Vec<OrderInfo>,
+    /// Resting `From` orders, highest price first and oldest first within a price
+    pub from_orders: Vec<OrderInfo>,
+    /// Orders taken off the book once filled, kept until their owner claims the settled tokens
+    pub filled_orders: Vec<OrderInfo>,
+}
+impl OrderBookInfo {
+    pub fn new(pair: TokenPair) -> Self {
+        Self {
+            pair,
+            ..Self::default()
+        }
+    }
+    /// Number of resting orders in the book
+    pub fn len(&self) -> usize {
+        self.to_orders.len() + self.from_orders.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Adds an order behind all orders of the same direction at the same or a better price
+    pub fn insert(&mut self, direction: Direction, order: OrderInfo) -> Result<(), ExchangeError> {
+        if self.len() >= MAX_ORDERS_PER_BOOK {
+            Err(ExchangeError::OrderBookFull(format!(
+                "Order book for {} is full",
+                self.pair
+            )))?
+        }
+        let orders = match direction {
+            Direction::To => &mut self.to_orders,
+            Direction::From => &mut self.from_orders,
+        };
+        let index = orders
+            .iter()
+            .position(|resting| match direction {
+                Direction::To => resting.price > order.price,
+                Direction::From => resting.price < order.price,
+            })
+            .unwrap_or_else(|| orders.len());
+        orders.insert(index, order);
+        Ok(())
+    }
+    /// Removes the order placed by a trade order account, resting or filled
+    pub fn remove(&mut self, trade: &Pubkey) -> Option<OrderInfo> {
+        for orders in &mut [
+            &mut self.to_orders,
+            &mut self.from_orders,
+            &mut self.filled_orders,
+        ] {
+            if let Some(index) = orders.iter().position(|o| o.trade == *trade) {
+                return Some(orders.remove(index));
+            }
+        }
+        None
+    }
+}
+
 /// Type of exchange account, account's user data is populated with this enum
-#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub enum ExchangeState {
     /// Account's Userdata is unallocated
     Unallocated,
@@ -229,6 +344,8 @@ pub enum ExchangeState {
     Account(TokenAccountInfo),
     // Trade order account
     Trade(TradeOrderInfo),
+    // Order book of a token pair
+    OrderBook(OrderBookInfo),
     Invalid,
 }
 impl Default for ExchangeState {
@@ -236,3 +353,142 @@ impl Default for ExchangeState {
         ExchangeState::Unallocated
     }
 }
+
+/// Number of bytes to allocate for a token account or a trade order account
+pub fn exchange_account_size() -> u64 {
+    let mut tokens = Tokens::default();
+    for i in 0..MAX_TOKENS_PER_ACCOUNT {
+        tokens
+            .credit(&Pubkey::new(&[i as u8 + 1; 32]), std::u64::MAX)
+            .unwrap();
+    }
+    let account = ExchangeState::Account(TokenAccountInfo {
+        owner: Pubkey::default(),
+        tokens,
+    });
+    let trade = ExchangeState::Trade(TradeOrderInfo::default().order_book(Pubkey::default()));
+    cmp::max(
+        bincode::serialized_size(&account).unwrap(),
+        bincode::serialized_size(&trade).unwrap(),
+    )
+}
+
+/// Number of bytes to allocate for an order book account
+pub fn order_book_account_size() -> u64 {
+    let mut order_book = OrderBookInfo::default();
+    order_book.to_orders = vec![OrderInfo::default(); MAX_ORDERS_PER_BOOK];
+    order_book.filled_orders = vec![OrderInfo::default(); MAX_FILLED_ORDERS_PER_BOOK];
+    bincode::serialized_size(&ExchangeState::OrderBook(order_book)).unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_tokens_credit_debit() {
+        let a = Pubkey::new_rand();
+        let b = Pubkey::new_rand();
+        let mut tokens = Tokens::default();
+        assert_eq!(tokens[&a], 0);
+
+        tokens.credit(&a, 10).unwrap();
+        tokens.credit(&b, 0).unwrap();
+        assert_eq!(tokens, Tokens::new(&[(a, 10)]));
+        assert_eq!(tokens.len(), 1);
+
+        // Balances can't overflow
+        tokens.credit(&a, std::u64::MAX).unwrap_err();
+        assert_eq!(tokens[&a], 10);
+        tokens.credit(&b, std::u64::MAX).unwrap();
+
+        tokens.debit(&a, 11).unwrap_err();
+        tokens.debit(&b, std::u64::MAX).unwrap();
+        tokens.debit(&b, 1).unwrap_err();
+        tokens.debit(&a, 4).unwrap();
+        assert_eq!(tokens[&a], 6);
+
+        // Emptied balances are dropped
+        tokens.debit(&a, 6).unwrap();
+        assert!(tokens.is_empty());
+        assert_eq!(tokens, Tokens::default());
+    }
+
+    #[test]
+    fn test_token_pair() {
+        let a = Pubkey::new_rand();
+        let b = Pubkey::new_rand();
+        let pair = TokenPair::new(a, b);
+        assert_eq!(pair.primary(), a);
+        assert_eq!(pair.secondary(), b);
+        assert!(pair.is_valid());
+        assert!(!TokenPair::new(a, a).is_valid());
+
+        assert_eq!(pair.order_book_address(), pair.order_book_address());
+        assert_ne!(
+            pair.order_book_address(),
+            TokenPair::new(b, a).order_book_address()
+        );
+    }
+
+    #[test]
+    fn test_order_book_price_time_priority() {
+        let pair = TokenPair::new(Pubkey::new_rand(), Pubkey::new_rand());
+        let mut order_book = OrderBookInfo::new(pair);
+        let order = |price| OrderInfo {
+            trade: Pubkey::new_rand(),
+            price,
+            ..OrderInfo::default()
+        };
+        let (to_2a, to_1, to_2b) = (order(2000), order(1000), order(2000));
+        let (from_2a, from_1, from_2b) = (order(2000), order(1000), order(2000));
+        for (direction, order) in &[
+            (Direction::To, to_2a),
+            (Direction::To, to_1),
+            (Direction::To, to_2b),
+            (Direction::From, from_2a),
+            (Direction::From, from_1),
+            (Direction::From, from_2b),
+        ] {
+            order_book.insert(*direction, *order).unwrap();
+        }
+        assert_eq!(order_book.to_orders, vec![to_1, to_2a, to_2b]);
+        assert_eq!(order_book.from_orders, vec![from_2a, from_2b, from_1]);
+
+        assert_eq!(order_book.remove(&to_2a.trade), Some(to_2a));
+        assert_eq!(order_book.remove(&from_1.trade), Some(from_1));
+        assert_eq!(order_book.remove(&from_1.trade), None);
+        assert_eq!(order_book.len(), 4);
+    }
+
+    #[test]
+    fn test_order_book_full() {
+        let mut order_book = OrderBookInfo::default();
+        for _ in 0..MAX_ORDERS_PER_BOOK {
+            order_book
+                .insert(Direction::To, OrderInfo::default())
+                .unwrap();
+        }
+        order_book
+            .insert(Direction::From, OrderInfo::default())
+            .unwrap_err();
+        order_book.filled_orders = vec![OrderInfo::default(); MAX_FILLED_ORDERS_PER_BOOK];
+
+        let mut data = vec![0; order_book_account_size() as usize];
+        bincode::serialize_into(&mut data[..], &ExchangeState::OrderBook(order_book)).unwrap();
+    }
+
+    #[test]
+    fn test_exchange_account_size_fits_full_account() {
+        let mut tokens = Tokens::default();
+        for _ in 0..MAX_TOKENS_PER_ACCOUNT {
+            tokens.credit(&Pubkey::new_rand(), 1).unwrap();
+        }
+        let state = ExchangeState::Account(TokenAccountInfo {
+            owner: Pubkey::new_rand(),
+            tokens,
+        });
+        let mut data = vec![0; exchange_account_size() as usize];
+        bincode::serialize_into(&mut data[..], &state).unwrap();
+    }
+}
@@ -0,0 +1,857 @@
+//! Stake state
+//! * delegate stakes to vote accounts
+//! * warm up and cool down effective stake across epochs
+//! * keep track of rewards
+//! * own mining pools
+
+use crate::config::{self, Config};
+use serde_derive::{Deserialize, Serialize};
+use soros_config_api::cluster_config::{ClusterConfig, ClusterConfigState, StakeRewardConfig};
+use soros_sdk::account::KeyedAccount;
+use soros_sdk::instruction::InstructionError;
+use soros_sdk::instruction_processor_utils::State;
+use soros_sdk::pubkey::Pubkey;
+use soros_vote_api::vote_state::VoteState;
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub enum StakeState {
+    Uninitialized,
+    Stake(Stake),
+    MiningPool,
+}
+
+impl Default for StakeState {
+    fn default() -> Self {
+        StakeState::Uninitialized
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub struct Stake {
+    pub voter_id: Pubkey,
+    pub credits_observed: u64,
+    /// Dif delegated to the voter
+    pub stake: u64,
+    /// Epoch the stake was delegated in, it starts warming up the epoch after
+    pub activation_epoch: u64,
+    /// Epoch the stake was deactivated in, `std::u64::MAX` if it hasn't been
+    pub deactivation_epoch: u64,
+}
+
+#[cfg(test)]
+const STAKE_GETS_PAID_EVERY_VOTE: u64 = 200_000_000; // if numbers in Config::default() move, fix this
+
+impl Stake {
+    pub fn new(voter_id: &Pubkey, credits_observed: u64, stake: u64, epoch: u64) -> Self {
+        Self {
+            voter_id: *voter_id,
+            credits_observed,
+            stake,
+            activation_epoch: epoch,
+            deactivation_epoch: std::u64::MAX,
+        }
+    }
+
+    pub fn is_deactivated(&self) -> bool {
+        self.deactivation_epoch != std::u64::MAX
+    }
+
+    fn warmed_up_stake(&self, epoch: u64, config: &Config) -> u64 {
+        if epoch <= self.activation_epoch {
+            return 0;
+        }
+        let fraction = (epoch - self.activation_epoch) as f64 * config.warmup_rate;
+        if fraction >= 1f64 {
+            self.stake
+        } else {
+            (self.stake as f64 * fraction) as u64
+        }
+    }
+
+    /// Stake that counts towards the voter's weight in the given epoch, ramps up after
+    /// activation and back down after deactivation
+    pub fn effective_stake(&self, epoch: u64, config: &Config) -> u64 {
+        if epoch <= self.deactivation_epoch {
+            return self.warmed_up_stake(epoch, config);
+        }
+        let stake = self.warmed_up_stake(self.deactivation_epoch, config);
+        let fraction = (epoch - self.deactivation_epoch) as f64 * config.cooldown_rate;
+        if fraction >= 1f64 {
+            0
+        } else {
+            (stake as f64 * (1f64 - fraction)) as u64
+        }
+    }
+
+    /// Stake that can't be withdrawn in the given epoch
+    pub fn locked_stake(&self, epoch: u64, config: &Config) -> u64 {
+        if self.is_deactivated() {
+            self.effective_stake(epoch, config)
+        } else {
+            self.stake
+        }
+    }
+
+    pub fn calculate_rewards(
+        &self,
+        effective_stake: u64,
+        vote_state: &VoteState,
+        config: &Config,
+        reward_config: &StakeRewardConfig,
+    ) -> Option<(u64, u64)> {
+        if self.credits_observed >= vote_state.credits() {
+            return None;
+        }
+
+        let total_rewards = effective_stake as f64
+            * reward_config.reward_target_rate
+            * (vote_state.credits() - self.credits_observed) as f64
+            / config.credits_per_year;
+
+        // don't bother trying to collect fractional dif
+        if total_rewards < 1f64 {
+            return None;
+        }
+
+        let (voter_rewards, staker_rewards, is_split) = vote_state.commission_split(total_rewards);
+
+        if (voter_rewards < 1f64 || staker_rewards < 1f64) && is_split {
+            // don't bother trying to collect fractional dif
+            return None;
+        }
+
+        Some((voter_rewards as u64, staker_rewards as u64))
+    }
+}
+
+fn get_config(config_account: &KeyedAccount) -> Result<Config, InstructionError> {
+    if !config::check_id(config_account.unsigned_key()) {
+        return Err(InstructionError::InvalidArgument);
+    }
+    Config::from(&config_account.account).ok_or(InstructionError::InvalidAccountData)
+}
+
+/// The reward rate in effect, kept in the stake reward cluster config account
+fn get_reward_config(
+    reward_config_account: &KeyedAccount,
+) -> Result<StakeRewardConfig, InstructionError> {
+    if *reward_config_account.unsigned_key() != StakeRewardConfig::id() {
+        return Err(InstructionError::InvalidArgument);
+    }
+    ClusterConfigState::<StakeRewardConfig>::from(&reward_config_account.account)
+        .map(|state| state.params)
+        .ok_or(InstructionError::InvalidAccountData)
+}
+
+pub trait StakeAccount {
+    /// Delegates all of the account's dif to a voter.  An active delegation can be moved to
+    /// another voter, its stake warms up again from the current epoch.  A deactivated one can
+    /// only be delegated again once it's done cooling down.
+    fn delegate_stake(
+        &mut self,
+        vote_account: &KeyedAccount,
+        config_account: &KeyedAccount,
+        tick_height: u64,
+    ) -> Result<(), InstructionError>;
+    fn deactivate_stake(
+        &mut self,
+        config_account: &KeyedAccount,
+        tick_height: u64,
+    ) -> Result<(), InstructionError>;
+    fn withdraw(
+        &mut self,
+        dif: u64,
+        to: &mut KeyedAccount,
+        config_account: &KeyedAccount,
+        tick_height: u64,
+    ) -> Result<(), InstructionError>;
+    fn redeem_vote_credits(
+        &mut self,
+        stake_account: &mut KeyedAccount,
+        vote_account: &mut KeyedAccount,
+        config_account: &KeyedAccount,
+        reward_config_account: &KeyedAccount,
+        tick_height: u64,
+    ) -> Result<(), InstructionError>;
+}
+
+impl<'a> StakeAccount for KeyedAccount<'a> {
+    fn delegate_stake(
+        &mut self,
+        vote_account: &KeyedAccount,
+        config_account: &KeyedAccount,
+        tick_height: u64,
+    ) -> Result<(), InstructionError> {
+        if self.signer_key().is_none() {
+            return Err(InstructionError::MissingRequiredSignature);
+        }
+        let config = get_config(config_account)?;
+        let epoch = config.epoch(tick_height);
+
+        match self.state()? {
+            StakeState::Uninitialized => (),
+            StakeState::Stake(stake) => {
+                if stake.is_deactivated() && stake.effective_stake(epoch, &config) > 0 {
+                    return Err(InstructionError::InvalidAccountData);
+                }
+            }
+            StakeState::MiningPool => return Err(InstructionError::InvalidAccountData),
+        }
+
+        let vote_state: VoteState = vote_account.state()?;
+        self.set_state(&StakeState::Stake(Stake::new(
+            vote_account.unsigned_key(),
+            vote_state.credits(),
+            self.account.dif,
+            epoch,
+        )))
+    }
+
+    fn deactivate_stake(
+        &mut self,
+        config_account: &KeyedAccount,
+        tick_height: u64,
+    ) -> Result<(), InstructionError> {
+        if self.signer_key().is_none() {
+            return Err(InstructionError::MissingRequiredSignature);
+        }
+        let config = get_config(config_account)?;
+
+        if let StakeState::Stake(mut stake) = self.state()? {
+            if stake.is_deactivated() {
+                return Err(InstructionError::InvalidAccountData);
+            }
+            stake.deactivation_epoch = config.epoch(tick_height);
+            self.set_state(&StakeState::Stake(stake))
+        } else {
+            Err(InstructionError::InvalidAccountData)
+        }
+    }
+
+    fn withdraw(
+        &mut self,
+        dif: u64,
+        to: &mut KeyedAccount,
+        config_account: &KeyedAccount,
+        tick_height: u64,
+    ) -> Result<(), InstructionError> {
+        if self.signer_key().is_none() {
+            return Err(InstructionError::MissingRequiredSignature);
+        }
+        let config = get_config(config_account)?;
+
+        let locked = match self.state()? {
+            StakeState::Uninitialized => 0,
+            StakeState::Stake(stake) => stake.locked_stake(config.epoch(tick_height), &config),
+            StakeState::MiningPool => return Err(InstructionError::InvalidAccountData),
+        };
+        if self.account.dif < locked || dif > self.account.dif - locked {
+            return Err(InstructionError::InvalidArgument);
+        }
+
+        self.account.dif -= dif;
+        to.account.dif += dif;
+        Ok(())
+    }
+
+    fn redeem_vote_credits(
+        &mut self,
+        stake_account: &mut KeyedAccount,
+        vote_account: &mut KeyedAccount,
+        config_account: &KeyedAccount,
+        reward_config_account: &KeyedAccount,
+        tick_height: u64,
+    ) -> Result<(), InstructionError> {
+        let config = get_config(config_account)?;
+        let reward_config = get_reward_config(reward_config_account)?;
+
+        if let (StakeState::MiningPool, StakeState::Stake(mut stake)) =
+            (self.state()?, stake_account.state()?)
+        {
+            let vote_state: VoteState = vote_account.state()?;
+
+            if stake.voter_id != *vote_account.unsigned_key() {
+                return Err(InstructionError::InvalidArgument);
+            }
+
+            if stake.credits_observed > vote_state.credits() {
+                return Err(InstructionError::InvalidAccountData);
+            }
+
+            let effective_stake = stake.effective_stake(config.epoch(tick_height), &config);
+            if let Some((voters_reward, stakers_reward)) =
+                stake.calculate_rewards(effective_stake, &vote_state, &config, &reward_config)
+            {
+                // if self.account.lamports < (stakers_reward + voters_reward) {
+                if self.account.dif < (stakers_reward + voters_reward) {
+                    return Err(InstructionError::UnbalancedInstruction);
+                }
+                // self.account.lamports -= stakers_reward + voters_reward;
+                self.account.dif -= stakers_reward + voters_reward;
+                // stake_account.account.lamports += stakers_reward;
+                stake_account.account.dif += stakers_reward;
+                // vote_account.account.lamports += voters_reward;
+                vote_account.account.dif += voters_reward;
+
+                // rewards of an active delegation are delegated along with it
+                if !stake.is_deactivated() {
+                    stake.stake += stakers_reward;
+                }
+                stake.credits_observed = vote_state.credits();
+                stake_account.set_state(&StakeState::Stake(stake))
+            } else {
+                // not worth collecting
+                Ok(())
+            }
+        } else {
+            Err(InstructionError::InvalidAccountData)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::id;
+    use soros_config_api::cluster_config::{self, ConfigAuthority};
+    use soros_sdk::account::Account;
+    use soros_sdk::epoch_schedule::EpochSchedule;
+    use soros_sdk::genesis_block::DEFAULT_STAKE_REWARD_TARGET_RATE;
+    use soros_sdk::pubkey::Pubkey;
+    use soros_sdk::signature::{Keypair, KeypairUtil};
+    use soros_sdk::timing::DEFAULT_TICKS_PER_SLOT;
+    use soros_vote_api::vote_state::{self, Vote};
+
+    // one slot per epoch, so that tick heights map to epochs simply
+    fn create_config_account() -> Account {
+        config::create_account(
+            1,
+            &Config {
+                epoch_schedule: EpochSchedule::new(1, 1, false),
+                ..Config::default()
+            },
+        )
+    }
+
+    fn create_reward_config_account() -> Account {
+        cluster_config::create_account(
+            1,
+            &ConfigAuthority::new(&[Pubkey::new_rand()], 1),
+            StakeRewardConfig::new(DEFAULT_STAKE_REWARD_TARGET_RATE),
+        )
+    }
+
+    fn tick_height(epoch: u64) -> u64 {
+        epoch * DEFAULT_TICKS_PER_SLOT
+    }
+
+    fn create_vote_account(vote_state: &VoteState) -> (Pubkey, Account) {
+        let vote_pubkey = Keypair::new().pubkey();
+        let mut vote_account =
+            vote_state::create_account(&vote_pubkey, &Pubkey::new_rand(), 0, 100);
+        KeyedAccount::new(&vote_pubkey, false, &mut vote_account)
+            .set_state(vote_state)
+            .unwrap();
+        (vote_pubkey, vote_account)
+    }
+
+    #[test]
+    fn test_stake_delegate_stake() {
+        let mut vote_state = VoteState::default();
+        for i in 0..1000 {
+            vote_state.process_vote(&Vote::new(i));
+        }
+        let (vote_pubkey, mut vote_account) = create_vote_account(&vote_state);
+        let vote_keyed_account = KeyedAccount::new(&vote_pubkey, false, &mut vote_account);
+
+        let config_pubkey = config::id();
+        let mut config_account = create_config_account();
+        let config_keyed_account = KeyedAccount::new(&config_pubkey, false, &mut config_account);
+
+        let stake_pubkey = Pubkey::default();
+        let mut stake_account = Account::new(42, std::mem::size_of::<StakeState>(), &id());
+
+        let mut stake_keyed_account = KeyedAccount::new(&stake_pubkey, false, &mut stake_account);
+
+        assert_eq!(
+            stake_keyed_account.delegate_stake(&vote_keyed_account, &config_keyed_account, 0),
+            Err(InstructionError::MissingRequiredSignature)
+        );
+
+        let mut stake_keyed_account = KeyedAccount::new(&stake_pubkey, true, &mut stake_account);
+
+        // not the stake config
+        assert_eq!(
+            stake_keyed_account.delegate_stake(&vote_keyed_account, &vote_keyed_account, 0),
+            Err(InstructionError::InvalidArgument)
+        );
+
+        assert!(stake_keyed_account
+            .delegate_stake(&vote_keyed_account, &config_keyed_account, tick_height(3))
+            .is_ok());
+
+        let stake_state: StakeState = stake_keyed_account.state().unwrap();
+        assert_eq!(
+            stake_state,
+            StakeState::Stake(Stake {
+                voter_id: vote_pubkey,
+                credits_observed: vote_state.credits(),
+                stake: 42,
+                activation_epoch: 3,
+                deactivation_epoch: std::u64::MAX,
+            })
+        );
+        let stake_state = StakeState::MiningPool;
+        stake_keyed_account.set_state(&stake_state).unwrap();
+        assert!(stake_keyed_account
+            .delegate_stake(&vote_keyed_account, &config_keyed_account, 0)
+            .is_err());
+    }
+
+    #[test]
+    fn test_stake_redelegate_stake() {
+        let vote_state = VoteState::default();
+        let (vote_pubkey, mut vote_account) = create_vote_account(&vote_state);
+        let vote_keyed_account = KeyedAccount::new(&vote_pubkey, false, &mut vote_account);
+        let (vote1_pubkey, mut vote1_account) = create_vote_account(&vote_state);
+        let vote1_keyed_account = KeyedAccount::new(&vote1_pubkey, false, &mut vote1_account);
+
+        let config_pubkey = config::id();
+        let mut config_account = create_config_account();
+        let config_keyed_account = KeyedAccount::new(&config_pubkey, false, &mut config_account);
+
+        let stake_pubkey = Pubkey::default();
+        let mut stake_account = Account::new(42, std::mem::size_of::<StakeState>(), &id());
+        let mut stake_keyed_account = KeyedAccount::new(&stake_pubkey, true, &mut stake_account);
+
+        stake_keyed_account
+            .delegate_stake(&vote_keyed_account, &config_keyed_account, tick_height(0))
+            .unwrap();
+
+        // an active delegation moves right away and warms up again
+        stake_keyed_account
+            .delegate_stake(&vote1_keyed_account, &config_keyed_account, tick_height(10))
+            .unwrap();
+        if let StakeState::Stake(stake) = stake_keyed_account.state().unwrap() {
+            assert_eq!(stake.voter_id, vote1_pubkey);
+            assert_eq!(stake.activation_epoch, 10);
+        } else {
+            panic!("not a stake");
+        }
+
+        // a deactivated one has to cool down first
+        stake_keyed_account
+            .deactivate_stake(&config_keyed_account, tick_height(20))
+            .unwrap();
+        assert_eq!(
+            stake_keyed_account.delegate_stake(
+                &vote_keyed_account,
+                &config_keyed_account,
+                tick_height(21)
+            ),
+            Err(InstructionError::InvalidAccountData)
+        );
+        stake_keyed_account
+            .delegate_stake(&vote_keyed_account, &config_keyed_account, tick_height(24))
+            .unwrap();
+        if let StakeState::Stake(stake) = stake_keyed_account.state().unwrap() {
+            assert_eq!(stake.voter_id, vote_pubkey);
+            assert!(!stake.is_deactivated());
+        } else {
+            panic!("not a stake");
+        }
+    }
+
+    #[test]
+    fn test_stake_warmup_cooldown() {
+        let config = Config::default();
+        let mut stake = Stake::new(&Pubkey::new_rand(), 0, 100, 10);
+
+        // warmup starts the epoch after delegation, 25% per epoch
+        let warmup: Vec<_> = (9..16)
+            .map(|epoch| stake.effective_stake(epoch, &config))
+            .collect();
+        assert_eq!(warmup, vec![0, 0, 25, 50, 75, 100, 100]);
+        assert_eq!(stake.locked_stake(11, &config), 100);
+
+        // cooldown from wherever warmup got to
+        stake.deactivation_epoch = 12;
+        let cooldown: Vec<_> = (12..18)
+            .map(|epoch| stake.effective_stake(epoch, &config))
+            .collect();
+        assert_eq!(cooldown, vec![50, 37, 25, 12, 0, 0]);
+        assert_eq!(stake.locked_stake(13, &config), 37);
+        assert_eq!(stake.locked_stake(16, &config), 0);
+    }
+
+    #[test]
+    fn test_stake_deactivate_withdraw() {
+        let vote_state = VoteState::default();
+        let (vote_pubkey, mut vote_account) = create_vote_account(&vote_state);
+        let vote_keyed_account = KeyedAccount::new(&vote_pubkey, false, &mut vote_account);
+
+        let config_pubkey = config::id();
+        let mut config_account = create_config_account();
+        let config_keyed_account = KeyedAccount::new(&config_pubkey, false, &mut config_account);
+
+        let to_pubkey = Pubkey::new_rand();
+        let mut to_account = Account::new(0, 0, &Pubkey::default());
+        let mut to_keyed_account = KeyedAccount::new(&to_pubkey, false, &mut to_account);
+
+        let stake_pubkey = Pubkey::new_rand();
+        let mut stake_account = Account::new(100, std::mem::size_of::<StakeState>(), &id());
+        let mut stake_keyed_account = KeyedAccount::new(&stake_pubkey, true, &mut stake_account);
+
+        // nothing delegated, nothing to deactivate, but free to withdraw
+        assert_eq!(
+            stake_keyed_account.deactivate_stake(&config_keyed_account, 0),
+            Err(InstructionError::InvalidAccountData)
+        );
+        stake_keyed_account
+            .withdraw(10, &mut to_keyed_account, &config_keyed_account, 0)
+            .unwrap();
+
+        stake_keyed_account
+            .delegate_stake(&vote_keyed_account, &config_keyed_account, tick_height(0))
+            .unwrap();
+        stake_keyed_account.account.dif += 10; // not delegated
+
+        // only what isn't delegated can be withdrawn from an active stake
+        assert_eq!(
+            stake_keyed_account.withdraw(
+                11,
+                &mut to_keyed_account,
+                &config_keyed_account,
+                tick_height(8)
+            ),
+            Err(InstructionError::InvalidArgument)
+        );
+        stake_keyed_account
+            .withdraw(
+                10,
+                &mut to_keyed_account,
+                &config_keyed_account,
+                tick_height(8),
+            )
+            .unwrap();
+
+        // once warmed up, deactivate
+        stake_keyed_account
+            .deactivate_stake(&config_keyed_account, tick_height(4))
+            .unwrap();
+        assert_eq!(
+            stake_keyed_account.deactivate_stake(&config_keyed_account, tick_height(4)),
+            Err(InstructionError::InvalidAccountData)
+        );
+
+        // half way through cooldown, half can be withdrawn
+        assert_eq!(
+            stake_keyed_account.withdraw(
+                46,
+                &mut to_keyed_account,
+                &config_keyed_account,
+                tick_height(6)
+            ),
+            Err(InstructionError::InvalidArgument)
+        );
+        stake_keyed_account
+            .withdraw(
+                45,
+                &mut to_keyed_account,
+                &config_keyed_account,
+                tick_height(6),
+            )
+            .unwrap();
+
+        // all of it once cooled down
+        stake_keyed_account
+            .withdraw(
+                45,
+                &mut to_keyed_account,
+                &config_keyed_account,
+                tick_height(8),
+            )
+            .unwrap();
+        assert_eq!(stake_account.dif, 0);
+        assert_eq!(to_account.dif, 110);
+    }
+
+    #[test]
+    fn test_stake_state_calculate_rewards() {
+        let config = Config::default();
+        let reward_config = StakeRewardConfig::new(DEFAULT_STAKE_REWARD_TARGET_RATE);
+        let mut vote_state = VoteState::default();
+        let mut vote_i = 0;
+        let stake = Stake::new(&Pubkey::default(), 0, 0, 0);
+
+        // put a credit in the vote_state
+        while vote_state.credits() == 0 {
+            vote_state.process_vote(&Vote::new(vote_i));
+            vote_i += 1;
+        }
+        // this guy can't collect now, not enough stake to get paid on 1 credit
+        assert_eq!(
+            None,
+            stake.calculate_rewards(100, &vote_state, &config, &reward_config)
+        );
+        // this guy can
+        assert_eq!(
+            Some((0, 1)),
+            stake.calculate_rewards(
+                STAKE_GETS_PAID_EVERY_VOTE,
+                &vote_state,
+                &config,
+                &reward_config
+            )
+        );
+        // but, there's not enough to split
+        vote_state.commission = std::u32::MAX / 2;
+        assert_eq!(
+            None,
+            stake.calculate_rewards(
+                STAKE_GETS_PAID_EVERY_VOTE,
+                &vote_state,
+                &config,
+                &reward_config
+            )
+        );
+
+        // put more credit in the vote_state
+        while vote_state.credits() < 10 {
+            vote_state.process_vote(&Vote::new(vote_i));
+            vote_i += 1;
+        }
+        vote_state.commission = 0;
+        assert_eq!(
+            Some((0, 10)),
+            stake.calculate_rewards(
+                STAKE_GETS_PAID_EVERY_VOTE,
+                &vote_state,
+                &config,
+                &reward_config
+            )
+        );
+        vote_state.commission = std::u32::MAX;
+        assert_eq!(
+            Some((10, 0)),
+            stake.calculate_rewards(
+                STAKE_GETS_PAID_EVERY_VOTE,
+                &vote_state,
+                &config,
+                &reward_config
+            )
+        );
+        vote_state.commission = std::u32::MAX / 2;
+        assert_eq!(
+            Some((5, 5)),
+            stake.calculate_rewards(
+                STAKE_GETS_PAID_EVERY_VOTE,
+                &vote_state,
+                &config,
+                &reward_config
+            )
+        );
+        // not even enough stake to get paid on 10 credits...
+        assert_eq!(
+            None,
+            stake.calculate_rewards(100, &vote_state, &config, &reward_config)
+        );
+
+        // rewards follow the reward rate
+        let reward_config = StakeRewardConfig::new(reward_config.reward_target_rate * 2f64);
+        vote_state.commission = 0;
+        assert_eq!(
+            Some((0, 20)),
+            stake.calculate_rewards(
+                STAKE_GETS_PAID_EVERY_VOTE,
+                &vote_state,
+                &config,
+                &reward_config
+            )
+        );
+    }
+
+    #[test]
+    fn test_stake_redeem_vote_credits() {
+        let mut vote_state = VoteState::default();
+        for i in 0..1000 {
+            vote_state.process_vote(&Vote::new(i));
+        }
+        let (vote_pubkey, mut vote_account) = create_vote_account(&vote_state);
+        let mut vote_keyed_account = KeyedAccount::new(&vote_pubkey, false, &mut vote_account);
+
+        let config_pubkey = config::id();
+        let mut config_account = create_config_account();
+        let config_keyed_account = KeyedAccount::new(&config_pubkey, false, &mut config_account);
+        let reward_config_pubkey = StakeRewardConfig::id();
+        let mut reward_config_account = create_reward_config_account();
+        let reward_config_keyed_account =
+            KeyedAccount::new(&reward_config_pubkey, false, &mut reward_config_account);
+
+        let pubkey = Pubkey::default();
+        let mut stake_account = Account::new(
+            STAKE_GETS_PAID_EVERY_VOTE,
+            std::mem::size_of::<StakeState>(),
+            &id(),
+        );
+        let mut stake_keyed_account = KeyedAccount::new(&pubkey, true, &mut stake_account);
+
+        // delegate the stake
+        assert!(stake_keyed_account
+            .delegate_stake(&vote_keyed_account, &config_keyed_account, tick_height(0))
+            .is_ok());
+
+        let mut mining_pool_account = Account::new(0, std::mem::size_of::<StakeState>(), &id());
+        let mut mining_pool_keyed_account =
+            KeyedAccount::new(&pubkey, true, &mut mining_pool_account);
+
+        // not a mining pool yet...
+        assert_eq!(
+            mining_pool_keyed_account.redeem_vote_credits(
+                &mut stake_keyed_account,
+                &mut vote_keyed_account,
+                &config_keyed_account,
+                &reward_config_keyed_account,
+                tick_height(4),
+            ),
+            Err(InstructionError::InvalidAccountData)
+        );
+
+        mining_pool_keyed_account
+            .set_state(&StakeState::MiningPool)
+            .unwrap();
+
+        // no movement in vote account, so no redemption needed
+        assert!(mining_pool_keyed_account
+            .redeem_vote_credits(
+                &mut stake_keyed_account,
+                &mut vote_keyed_account,
+                &config_keyed_account,
+                &reward_config_keyed_account,
+                tick_height(4),
+            )
+            .is_ok());
+
+        // move the vote account forward
+        vote_state.process_vote(&Vote::new(1000));
+        vote_keyed_account.set_state(&vote_state).unwrap();
+
+        // stake is still warming up, nothing to collect yet
+        assert!(mining_pool_keyed_account
+            .redeem_vote_credits(
+                &mut stake_keyed_account,
+                &mut vote_keyed_account,
+                &config_keyed_account,
+                &reward_config_keyed_account,
+                tick_height(1),
+            )
+            .is_ok());
+
+        // now, no dif in the pool!
+        assert_eq!(
+            mining_pool_keyed_account.redeem_vote_credits(
+                &mut stake_keyed_account,
+                &mut vote_keyed_account,
+                &config_keyed_account,
+                &reward_config_keyed_account,
+                tick_height(4),
+            ),
+            Err(InstructionError::UnbalancedInstruction)
+        );
+
+        // add a lamport to pool
+        // mining_pool_keyed_account.account.lamports = 2;
+        mining_pool_keyed_account.account.dif = 2;
+        assert!(mining_pool_keyed_account
+            .redeem_vote_credits(
+                &mut stake_keyed_account,
+                &mut vote_keyed_account,
+                &config_keyed_account,
+                &reward_config_keyed_account,
+                tick_height(4),
+            )
+            .is_ok()); // yay
+
+        // the reward is delegated along with the stake
+        if let StakeState::Stake(stake) = stake_keyed_account.state().unwrap() {
+            assert_eq!(stake.stake, stake_keyed_account.account.dif);
+            assert_eq!(stake.credits_observed, vote_state.credits());
+        } else {
+            panic!("not a stake");
+        }
+
+        // dif only shifted around, none made or lost
+        assert_eq!(
+            2 + 100 + STAKE_GETS_PAID_EVERY_VOTE,
+            // mining_pool_account.lamports + vote_account.lamports + stake_account.lamports
+            mining_pool_account.dif + vote_account.dif + stake_account.dif
+        );
+    }
+
+    #[test]
+    fn test_stake_redeem_vote_credits_vote_errors() {
+        let mut vote_state = VoteState::default();
+        for i in 0..1000 {
+            vote_state.process_vote(&Vote::new(i));
+        }
+        let (vote_pubkey, mut vote_account) = create_vote_account(&vote_state);
+        let mut vote_keyed_account = KeyedAccount::new(&vote_pubkey, false, &mut vote_account);
+
+        let config_pubkey = config::id();
+        let mut config_account = create_config_account();
+        let config_keyed_account = KeyedAccount::new(&config_pubkey, false, &mut config_account);
+        let reward_config_pubkey = StakeRewardConfig::id();
+        let mut reward_config_account = create_reward_config_account();
+        let reward_config_keyed_account =
+            KeyedAccount::new(&reward_config_pubkey, false, &mut reward_config_account);
+
+        let pubkey = Pubkey::default();
+        let mut stake_account = Account::new(0, std::mem::size_of::<StakeState>(), &id());
+        let mut stake_keyed_account = KeyedAccount::new(&pubkey, true, &mut stake_account);
+
+        // delegate the stake
+        assert!(stake_keyed_account
+            .delegate_stake(&vote_keyed_account, &config_keyed_account, 0)
+            .is_ok());
+
+        let mut mining_pool_account = Account::new(0, std::mem::size_of::<StakeState>(), &id());
+        let mut mining_pool_keyed_account =
+            KeyedAccount::new(&pubkey, true, &mut mining_pool_account);
+        mining_pool_keyed_account
+            .set_state(&StakeState::MiningPool)
+            .unwrap();
+
+        let mut vote_state = VoteState::default();
+        for i in 0..100 {
+            // go back in time, previous state had 1000 votes
+            vote_state.process_vote(&Vote::new(i));
+        }
+        vote_keyed_account.set_state(&vote_state).unwrap();
+        // voter credits lower than stake_delegate credits...  TODO: is this an error?
+        assert_eq!(
+            mining_pool_keyed_account.redeem_vote_credits(
+                &mut stake_keyed_account,
+                &mut vote_keyed_account,
+                &config_keyed_account,
+                &reward_config_keyed_account,
+                0
+            ),
+            Err(InstructionError::InvalidAccountData)
+        );
+
+        let (vote1_pubkey, mut vote1_account) = create_vote_account(&vote_state);
+        let mut vote1_keyed_account = KeyedAccount::new(&vote1_pubkey, false, &mut vote1_account);
+
+        // wrong voter_id...
+        assert_eq!(
+            mining_pool_keyed_account.redeem_vote_credits(
+                &mut stake_keyed_account,
+                &mut vote1_keyed_account,
+                &config_keyed_account,
+                &reward_config_keyed_account,
+                0
+            ),
+            Err(InstructionError::InvalidArgument)
+        );
+    }
+}
@@ -1,3 +1,4 @@
+pub mod config;
 pub mod stake_instruction;
 pub mod stake_state;
 
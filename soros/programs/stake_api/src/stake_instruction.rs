@@ -1,8 +1,10 @@
+use crate::config;
 use crate::id;
 use crate::stake_state::{StakeAccount, StakeState};
 use bincode::deserialize;
 use log::*;
 use serde_derive::{Deserialize, Serialize};
+use soros_config_api::cluster_config::{ClusterConfig, StakeRewardConfig};
 use soros_sdk::account::KeyedAccount;
 use soros_sdk::instruction::{AccountMeta, Instruction, InstructionError};
 use soros_sdk::pubkey::Pubkey;
@@ -11,15 +13,30 @@ use soros_sdk::system_instruction;
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub enum StakeInstruction {
     /// `Delegate` or `Assign` a stake account to a particular node
-    ///  expects 2 KeyedAccounts:
+    ///  expects 3 KeyedAccounts:
     ///     StakeAccount to be updated
     ///     VoteAccount to which this Stake will be delegated
+    ///     the stake Config account
     DelegateStake,
 
     /// Redeem credits in the stake account
-    ///  expects 3 KeyedAccounts: the StakeAccount to be updated
-    ///  and the VoteAccount to which this Stake will be delegated
+    ///  expects 5 KeyedAccounts: the MiningPool to pay from, the StakeAccount to be updated,
+    ///  the VoteAccount to which this Stake is delegated, the stake Config account and
+    ///  the StakeRewardConfig account
     RedeemVoteCredits,
+
+    /// Deactivate the stake, its effective stake cools down from the current epoch
+    ///  expects 2 KeyedAccounts:
+    ///     StakeAccount to be deactivated
+    ///     the stake Config account
+    DeactivateStake,
+
+    /// Withdraw dif that isn't locked up in an effective delegation
+    ///  expects 3 KeyedAccounts:
+    ///     StakeAccount to withdraw from
+    ///     Account to credit
+    ///     the stake Config account
+    Withdraw(u64),
 }
 
 // pub fn create_account(from_id: &Pubkey, staker_id: &Pubkey, lamports: u64) -> Vec<Instruction> {
@@ -45,6 +62,8 @@ pub fn redeem_vote_credits(
         AccountMeta::new(*mining_pool_id, false),
         AccountMeta::new(*stake_id, false),
         AccountMeta::new(*vote_id, false),
+        AccountMeta::new(config::id(), false),
+        AccountMeta::new(StakeRewardConfig::id(), false),
     ];
     Instruction::new(id(), &StakeInstruction::RedeemVoteCredits, account_metas)
 }
@@ -54,15 +73,35 @@ pub fn delegate_stake(from_id: &Pubkey, stake_id: &Pubkey, vote_id: &Pubkey) ->
         AccountMeta::new(*from_id, true),
         AccountMeta::new(*stake_id, true),
         AccountMeta::new(*vote_id, false),
+        AccountMeta::new(config::id(), false),
     ];
     Instruction::new(id(), &StakeInstruction::DelegateStake, account_metas)
 }
 
+pub fn deactivate_stake(from_id: &Pubkey, stake_id: &Pubkey) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*from_id, true),
+        AccountMeta::new(*stake_id, true),
+        AccountMeta::new(config::id(), false),
+    ];
+    Instruction::new(id(), &StakeInstruction::DeactivateStake, account_metas)
+}
+
+pub fn withdraw(from_id: &Pubkey, stake_id: &Pubkey, to_id: &Pubkey, dif: u64) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*from_id, true),
+        AccountMeta::new(*stake_id, true),
+        AccountMeta::new(*to_id, false),
+        AccountMeta::new(config::id(), false),
+    ];
+    Instruction::new(id(), &StakeInstruction::Withdraw(dif), account_metas)
+}
+
 pub fn process_instruction(
     _program_id: &Pubkey,
     keyed_accounts: &mut [KeyedAccount],
     data: &[u8],
-    _tick_height: u64,
+    tick_height: u64,
 ) -> Result<(), InstructionError> {
     soros_logger::setup();
 
@@ -81,21 +120,35 @@ pub fn process_instruction(
     // TODO: data-driven unpack and dispatch of KeyedAccounts
     match deserialize(data).map_err(|_| InstructionError::InvalidInstructionData)? {
         StakeInstruction::DelegateStake => {
-            if rest.len() != 1 {
+            if rest.len() != 2 {
                 Err(InstructionError::InvalidInstructionData)?;
             }
-            let vote = &rest[0];
-            me.delegate_stake(vote)
+            let (vote, config) = rest.split_at(1);
+            me.delegate_stake(&vote[0], &config[0], tick_height)
         }
         StakeInstruction::RedeemVoteCredits => {
-            if rest.len() != 2 {
+            if rest.len() != 4 {
                 Err(InstructionError::InvalidInstructionData)?;
             }
-            let (stake, vote) = rest.split_at_mut(1);
+            let (stake, rest) = rest.split_at_mut(1);
+            let (vote, config) = rest.split_at_mut(1);
             let stake = &mut stake[0];
             let vote = &mut vote[0];
 
-            me.redeem_vote_credits(stake, vote)
+            me.redeem_vote_credits(stake, vote, &config[0], &config[1], tick_height)
+        }
+        StakeInstruction::DeactivateStake => {
+            if rest.len() != 1 {
+                Err(InstructionError::InvalidInstructionData)?;
+            }
+            me.deactivate_stake(&rest[0], tick_height)
+        }
+        StakeInstruction::Withdraw(dif) => {
+            if rest.len() != 2 {
+                Err(InstructionError::InvalidInstructionData)?;
+            }
+            let (to, config) = rest.split_at_mut(1);
+            me.withdraw(dif, &mut to[0], &config[0], tick_height)
         }
     }
 }
@@ -153,6 +206,37 @@ mod tests {
             ),
             Err(InstructionError::InvalidInstructionData),
         );
+
+        // gets the check in deactivate_stake
+        assert_eq!(
+            process_instruction(
+                &Pubkey::default(),
+                &mut [
+                    KeyedAccount::new(&Pubkey::default(), false, &mut Account::default()),
+                    KeyedAccount::new(&Pubkey::default(), false, &mut Account::default()),
+                    KeyedAccount::new(&Pubkey::default(), false, &mut Account::default()),
+                    KeyedAccount::new(&Pubkey::default(), false, &mut Account::default()),
+                ],
+                &serialize(&StakeInstruction::DeactivateStake).unwrap(),
+                0,
+            ),
+            Err(InstructionError::InvalidInstructionData),
+        );
+
+        // gets the check in withdraw
+        assert_eq!(
+            process_instruction(
+                &Pubkey::default(),
+                &mut [
+                    KeyedAccount::new(&Pubkey::default(), false, &mut Account::default()),
+                    KeyedAccount::new(&Pubkey::default(), false, &mut Account::default()),
+                    KeyedAccount::new(&Pubkey::default(), false, &mut Account::default()),
+                ],
+                &serialize(&StakeInstruction::Withdraw(1)).unwrap(),
+                0,
+            ),
+            Err(InstructionError::InvalidInstructionData),
+        );
     }
 
 }
@@ -1,16 +1,16 @@
 use crate::{get_segment_from_entry, ENTRIES_PER_SEGMENT};
 use log::*;
 use serde_derive::{Deserialize, Serialize};
+use soros_config_api::cluster_config::StorageRewardConfig;
 use soros_sdk::account::Account;
 use soros_sdk::hash::Hash;
 use soros_sdk::instruction::InstructionError;
 use soros_sdk::instruction_processor_utils::State;
 use soros_sdk::pubkey::Pubkey;
 use soros_sdk::signature::Signature;
-use std::cmp;
 
-pub const TOTAL_VALIDATOR_REWARDS: u64 = 1;
-pub const TOTAL_REPLICATOR_REWARDS: u64 = 1;
+/// Size of the data of storage accounts, validator, replicator and mining pool alike
+pub const STORAGE_ACCOUNT_SPACE: u64 = 4 * 1024;
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum ProofStatus {
@@ -53,6 +53,8 @@ pub enum StorageContract {
         proofs: Vec<Proof>,
         reward_validations: Vec<Vec<CheckedProof>>,
     },
+    /// Pays out the storage rewards claimed by validators and replicators
+    MiningPool,
 }
 
 pub struct StorageAccount<'a> {
@@ -81,13 +83,8 @@ impl<'a> StorageAccount<'a> {
 
         if let StorageContract::ReplicatorStorage { proofs, .. } = &mut storage_contract {
             let segment_index = get_segment_from_entry(entry_height);
-            if segment_index > proofs.len() || proofs.is_empty() {
-                proofs.resize(cmp::max(1, segment_index), Proof::default());
-            }
-
-            if segment_index > proofs.len() {
-                // only possible if usize max < u64 max
-                return Err(InstructionError::InvalidArgument);
+            if segment_index >= proofs.len() {
+                proofs.resize(segment_index + 1, Proof::default());
             }
 
             debug!(
@@ -179,6 +176,9 @@ impl<'a> StorageAccount<'a> {
             }
 
             let segment_index = get_segment_from_entry(entry_height);
+            if segment_index >= lockout_validations.len() {
+                return Err(InstructionError::InvalidArgument);
+            }
             let mut previous_proofs = replicator_accounts
                 .iter_mut()
                 .filter_map(|account| {
@@ -187,9 +187,10 @@ impl<'a> StorageAccount<'a> {
                         .state()
                         .ok()
                         .map(move |contract| match contract {
-                            StorageContract::ReplicatorStorage { proofs, .. } => {
-                                Some((account, proofs[segment_index].clone()))
-                            }
+                            StorageContract::ReplicatorStorage { proofs, .. } => proofs
+                                .get(segment_index)
+                                .cloned()
+                                .map(|proof| (account, proof)),
                             _ => None,
                         })
                 })
@@ -222,8 +223,18 @@ impl<'a> StorageAccount<'a> {
         }
     }
 
+    pub fn initialize_mining_pool(&mut self) -> Result<(), InstructionError> {
+        if let StorageContract::Default = self.account.state()? {
+            self.account.set_state(&StorageContract::MiningPool)
+        } else {
+            Err(InstructionError::AccountAlreadyInitialized)
+        }
+    }
+
     pub fn claim_storage_reward(
         &mut self,
+        mining_pool: &mut StorageAccount,
+        reward_config: &StorageRewardConfig,
         entry_height: u64,
         tick_height: u64,
     ) -> Result<(), InstructionError> {
@@ -237,10 +248,13 @@ impl<'a> StorageAccount<'a> {
         } = &mut storage_contract
         {
             let claims_index = get_segment_from_entry(entry_height);
-            let _num_validations = count_valid_proofs(&reward_validations[claims_index]);
-            // TODO can't just create dif out of thin air
-            // self.account.dif += TOTAL_VALIDATOR_REWARDS * num_validations;
+            if claims_index >= reward_validations.len() {
+                return Err(InstructionError::InvalidArgument);
+            }
+            let num_validations = count_valid_proofs(&reward_validations[claims_index]);
             reward_validations.clear();
+            let reward = reward_config.validator_reward * num_validations;
+            mining_pool.pay_reward(reward, self.account)?;
             self.account.set_state(storage_contract)
         } else if let StorageContract::ReplicatorStorage {
             reward_validations, ..
@@ -259,17 +273,29 @@ impl<'a> StorageAccount<'a> {
                 );
                 return Err(InstructionError::InvalidArgument);
             }
-            let _num_validations = count_valid_proofs(&reward_validations[claims_index]);
-            // TODO can't just create dif out of thin air
-            // self.account.dif += num_validations
-            //     * TOTAL_REPLICATOR_REWARDS
-            //     * (num_validations / reward_validations[claims_index].len() as u64);
+            let num_validations = count_valid_proofs(&reward_validations[claims_index]);
             reward_validations.clear();
+            let reward = reward_config.replicator_reward * num_validations;
+            mining_pool.pay_reward(reward, self.account)?;
             self.account.set_state(storage_contract)
         } else {
             Err(InstructionError::InvalidArgument)?
         }
     }
+
+    /// Move `reward` dif out of this mining pool into the claimant's account
+    fn pay_reward(&mut self, reward: u64, claimant: &mut Account) -> Result<(), InstructionError> {
+        if let StorageContract::MiningPool = self.account.state()? {
+            if self.account.dif < reward {
+                return Err(InstructionError::UnbalancedInstruction);
+            }
+            self.account.dif -= reward;
+            claimant.dif += reward;
+            Ok(())
+        } else {
+            Err(InstructionError::InvalidArgument)
+        }
+    }
 }
 
 /// Store the result of a proof validation into the replicator account
@@ -288,8 +314,8 @@ fn store_validation_result(
             if segment_index >= proofs.len() {
                 return Err(InstructionError::InvalidAccountData);
             }
-            if segment_index > reward_validations.len() || reward_validations.is_empty() {
-                reward_validations.resize(cmp::max(1, segment_index), vec![]);
+            if segment_index >= reward_validations.len() {
+                reward_validations.resize(segment_index + 1, vec![]);
             }
             let result = proofs[segment_index].clone();
             reward_validations[segment_index].push(CheckedProof {
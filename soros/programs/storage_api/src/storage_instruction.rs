@@ -1,10 +1,12 @@
 use crate::id;
-use crate::storage_contract::CheckedProof;
+use crate::storage_contract::{CheckedProof, STORAGE_ACCOUNT_SPACE};
 use serde_derive::{Deserialize, Serialize};
+use soros_config_api::cluster_config::{ClusterConfig, StorageRewardConfig};
 use soros_sdk::hash::Hash;
 use soros_sdk::instruction::{AccountMeta, Instruction};
 use soros_sdk::pubkey::Pubkey;
 use soros_sdk::signature::Signature;
+use soros_sdk::system_instruction;
 
 // TODO maybe split this into StorageReplicator and StorageValidator
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -25,6 +27,8 @@ pub enum StorageInstruction {
         entry_height: u64,
         proofs: Vec<CheckedProof>,
     },
+    /// Turn an empty storage account into a pool that rewards are paid from
+    InitializeMiningPool,
 }
 
 pub fn mining_proof(
@@ -71,8 +75,37 @@ pub fn proof_validation(
     Instruction::new(id(), &storage_instruction, account_metas)
 }
 
-pub fn reward_claim(from_pubkey: &Pubkey, entry_height: u64) -> Instruction {
+pub fn reward_claim(
+    from_pubkey: &Pubkey,
+    mining_pool_pubkey: &Pubkey,
+    entry_height: u64,
+) -> Instruction {
     let storage_instruction = StorageInstruction::ClaimStorageReward { entry_height };
-    let account_metas = vec![AccountMeta::new(*from_pubkey, true)];
+    let account_metas = vec![
+        AccountMeta::new(*from_pubkey, true),
+        AccountMeta::new(*mining_pool_pubkey, false),
+        AccountMeta::new(StorageRewardConfig::id(), false),
+    ];
     Instruction::new(id(), &storage_instruction, account_metas)
 }
+
+pub fn create_mining_pool_account(
+    from_pubkey: &Pubkey,
+    mining_pool_pubkey: &Pubkey,
+    dif: u64,
+) -> Vec<Instruction> {
+    vec![
+        system_instruction::create_account(
+            from_pubkey,
+            mining_pool_pubkey,
+            dif,
+            STORAGE_ACCOUNT_SPACE,
+            &id(),
+        ),
+        Instruction::new(
+            id(),
+            &StorageInstruction::InitializeMiningPool,
+            vec![AccountMeta::new(*mining_pool_pubkey, true)],
+        ),
+    ]
+}
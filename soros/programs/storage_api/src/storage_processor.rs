@@ -5,6 +5,7 @@
 use crate::storage_contract::StorageAccount;
 use crate::storage_instruction::StorageInstruction;
 use log::*;
+use soros_config_api::cluster_config::{ClusterConfig, ClusterConfigState, StorageRewardConfig};
 use soros_sdk::account::KeyedAccount;
 use soros_sdk::instruction::InstructionError;
 use soros_sdk::pubkey::Pubkey;
@@ -28,6 +29,15 @@ pub fn process_instruction(
     }
     let storage_account_pubkey = *storage_account_pubkey.unwrap();
 
+    // Reward claims pay out at the rates in effect in the storage reward config account
+    let reward_config = rest.get(1).and_then(|keyed_account| {
+        if *keyed_account.unsigned_key() != StorageRewardConfig::id() {
+            return None;
+        }
+        ClusterConfigState::<StorageRewardConfig>::from(&*keyed_account.account)
+            .map(|state| state.params)
+    });
+
     let mut storage_account = StorageAccount::new(&mut me[0].account);
     let mut rest: Vec<_> = rest
         .iter_mut()
@@ -59,12 +69,22 @@ pub fn process_instruction(
             storage_account.advertise_storage_recent_blockhash(hash, entry_height)
         }
         StorageInstruction::ClaimStorageReward { entry_height } => {
-            if num_keyed_accounts != 1 {
+            if num_keyed_accounts != 3 {
                 // keyed_accounts[0] should be the main storage key
-                // to access its data
+                // to access its data, keyed_accounts[1] the mining pool
+                // and keyed_accounts[2] the storage reward config
                 Err(InstructionError::InvalidArgument)?;
             }
-            storage_account.claim_storage_reward(entry_height, tick_height)
+            let reward_config = reward_config.ok_or_else(|| {
+                info!("account[2] is not the storage reward config");
+                InstructionError::InvalidArgument
+            })?;
+            storage_account.claim_storage_reward(
+                &mut rest[0],
+                &reward_config,
+                entry_height,
+                tick_height,
+            )
         }
         StorageInstruction::ProofValidation {
             entry_height,
@@ -76,6 +96,12 @@ pub fn process_instruction(
             }
             storage_account.proof_validation(entry_height, proofs, &mut rest)
         }
+        StorageInstruction::InitializeMiningPool => {
+            if num_keyed_accounts != 1 {
+                Err(InstructionError::InvalidArgument)?;
+            }
+            storage_account.initialize_mining_pool()
+        }
     }
 }
 
@@ -83,17 +109,21 @@ pub fn process_instruction(
 mod tests {
     use super::*;
     use crate::id;
-    use crate::storage_contract::{CheckedProof, Proof, ProofStatus, StorageContract};
+    use crate::storage_contract::{
+        CheckedProof, Proof, ProofStatus, StorageContract, STORAGE_ACCOUNT_SPACE,
+    };
     use crate::storage_instruction;
     use crate::ENTRIES_PER_SEGMENT;
     use bincode::deserialize;
+    use soros_config_api::cluster_config::{self, ConfigAuthority};
     use soros_runtime::bank::Bank;
     use soros_runtime::bank_client::BankClient;
     use soros_sdk::account::{create_keyed_accounts, Account};
     use soros_sdk::client::SyncClient;
     use soros_sdk::genesis_block::GenesisBlock;
     use soros_sdk::hash::{hash, Hash};
-    use soros_sdk::instruction::Instruction;
+    use soros_sdk::instruction::{AccountMeta, Instruction};
+    use soros_sdk::message::Message;
     use soros_sdk::pubkey::Pubkey;
     use soros_sdk::signature::{Keypair, KeypairUtil, Signature};
     use soros_sdk::system_instruction;
@@ -185,7 +215,6 @@ mod tests {
     }
 
     #[test]
-    #[ignore]
     fn test_validate_mining() {
         soros_logger::setup();
         let (genesis_block, mint_keypair) = GenesisBlock::new(1000);
@@ -195,11 +224,23 @@ mod tests {
         let validator_keypair = Keypair::new();
         let validator = validator_keypair.pubkey();
 
+        let mining_pool_keypair = Keypair::new();
+        let mining_pool = mining_pool_keypair.pubkey();
+
         let mut bank = Bank::new(&genesis_block);
         bank.add_instruction_processor(id(), process_instruction);
+        // tick the bank into the next storage segment so that rewards can be claimed
+        for _ in 0..ENTRIES_PER_SEGMENT {
+            bank.register_tick(&bank.last_blockhash());
+        }
         let entry_height = 0;
         let bank_client = BankClient::new(bank);
 
+        let ixs = storage_instruction::create_mining_pool_account(&mint_pubkey, &mining_pool, 100);
+        bank_client
+            .send_message(&[&mint_keypair, &mining_pool_keypair], Message::new(ixs))
+            .unwrap();
+
         let ix = system_instruction::create_account(&mint_pubkey, &validator, 10, 4 * 1042, &id());
         bank_client.send_instruction(&mint_keypair, ix).unwrap();
 
@@ -260,27 +301,101 @@ mod tests {
             .send_instruction(&validator_keypair, ix)
             .unwrap();
 
-        let ix = storage_instruction::reward_claim(&validator, entry_height);
+        // the genesis block sets up the default reward rates
+        let reward_config = StorageRewardConfig::default();
+        let ix = storage_instruction::reward_claim(&validator, &mining_pool, entry_height);
         bank_client
             .send_instruction(&validator_keypair, ix)
             .unwrap();
+        assert_eq!(
+            bank_client.get_balance(&validator).unwrap(),
+            10 + reward_config.validator_reward
+        );
 
-        // TODO enable when rewards are working
-        // assert_eq!(bank_client.get_balance(&validator).unwrap(), TOTAL_VALIDATOR_REWARDS);
-
-        // TODO extend BankClient with a method to force a block boundary
-        // tick the bank into the next storage epoch so that rewards can be claimed
-        //for _ in 0..=ENTRIES_PER_SEGMENT {
-        //    bank.register_tick(&bank.last_blockhash());
-        //}
-
-        let ix = storage_instruction::reward_claim(&replicator, entry_height);
+        let ix = storage_instruction::reward_claim(&replicator, &mining_pool, entry_height);
         bank_client
             .send_instruction(&replicator_keypair, ix)
             .unwrap();
+        assert_eq!(
+            bank_client.get_balance(&replicator).unwrap(),
+            10 + reward_config.replicator_reward
+        );
+        assert_eq!(
+            bank_client.get_balance(&mining_pool).unwrap(),
+            100 - reward_config.validator_reward - reward_config.replicator_reward
+        );
+    }
+
+    #[test]
+    fn test_claim_storage_reward_needs_mining_pool() {
+        let validator = Pubkey::new_rand();
+        let mining_pool = Pubkey::new_rand();
+        let reward_config_account = cluster_config::create_account(
+            1,
+            &ConfigAuthority::default(),
+            StorageRewardConfig::default(),
+        );
+        let mut accounts = [
+            Account::default(),
+            Account::default(),
+            reward_config_account,
+        ];
+        accounts[0].data.resize(STORAGE_ACCOUNT_SPACE as usize, 0);
+        accounts[1].data.resize(STORAGE_ACCOUNT_SPACE as usize, 0);
+        accounts[1].dif = 10;
+
+        let ix = storage_instruction::advertise_recent_blockhash(
+            &validator,
+            Hash::default(),
+            ENTRIES_PER_SEGMENT * 2,
+        );
+        test_instruction(&ix, &mut accounts[..1]).unwrap();
+        let ix = storage_instruction::advertise_recent_blockhash(
+            &validator,
+            Hash::default(),
+            ENTRIES_PER_SEGMENT * 3,
+        );
+        test_instruction(&ix, &mut accounts[..1]).unwrap();
 
-        // TODO enable when rewards are working
-        // assert_eq!(bank_client.get_balance(&replicator).unwrap(), TOTAL_REPLICATOR_REWARDS);
+        // not a mining pool yet
+        let ix = storage_instruction::reward_claim(&validator, &mining_pool, 0);
+        assert_eq!(
+            test_instruction(&ix, &mut accounts),
+            Err(InstructionError::InvalidArgument)
+        );
+
+        let ix = Instruction::new(
+            id(),
+            &StorageInstruction::InitializeMiningPool,
+            vec![AccountMeta::new(mining_pool, true)],
+        );
+        test_instruction(&ix, &mut accounts[1..2]).unwrap();
+        assert_eq!(
+            test_instruction(&ix, &mut accounts[1..2]),
+            Err(InstructionError::AccountAlreadyInitialized)
+        );
+
+        // rewards are only paid at the rates in the storage reward config
+        let mut ix = storage_instruction::reward_claim(&validator, &mining_pool, 0);
+        ix.accounts[2].pubkey = Pubkey::new_rand();
+        assert_eq!(
+            test_instruction(&ix, &mut accounts),
+            Err(InstructionError::InvalidArgument)
+        );
+
+        // nothing was validated, nothing is paid
+        let ix = storage_instruction::reward_claim(&validator, &mining_pool, 0);
+        test_instruction(&ix, &mut accounts).unwrap();
+        assert_eq!(accounts[0].dif, 0);
+        assert_eq!(accounts[1].dif, 10);
+
+        // the claimant's segment has to exist
+        let ix =
+            storage_instruction::reward_claim(&validator, &mining_pool, ENTRIES_PER_SEGMENT * 8);
+        assert_eq!(
+            test_instruction(&ix, &mut accounts),
+            Err(InstructionError::InvalidArgument)
+        );
     }
 
     fn get_storage_entry_height<C: SyncClient>(client: &C, account: &Pubkey) -> u64 {
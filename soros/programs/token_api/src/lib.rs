@@ -1,6 +1,8 @@
 pub mod token_processor;
 mod token_state;
 
+pub use token_state::{TokenInfo, TokenInstruction};
+
 use soros_sdk::pubkey::Pubkey;
 
 const TOKEN_PROGRAM_ID: [u8; 32] = [
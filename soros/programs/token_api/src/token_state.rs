@@ -69,7 +69,7 @@ pub struct TokenAccountInfo {
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
-enum TokenInstruction {
+pub enum TokenInstruction {
     NewToken(TokenInfo),
     NewTokenAccount,
     Transfer(u64),
@@ -10,6 +10,7 @@ use hashbrown::{HashMap, HashSet};
 use log::*;
 use soros_metrics::counter::Counter;
 use soros_sdk::account::Account;
+use soros_sdk::bpf_loader_upgradeable::{self, UpgradeableLoaderState};
 use soros_sdk::fee_calculator::FeeCalculator;
 use soros_sdk::hash::{Hash, Hasher};
 use soros_sdk::native_loader;
@@ -215,6 +216,17 @@ impl Accounts {
                 error_counters.account_not_found += 1;
                 return Err(TransactionError::AccountNotFound);
             }
+            let program = if bpf_loader_upgradeable::check_id(&program.owner) {
+                match Self::load_upgradeable_program(storage, ancestors, accounts_index, program) {
+                    Some(program) => program,
+                    None => {
+                        error_counters.account_not_found += 1;
+                        return Err(TransactionError::AccountNotFound);
+                    }
+                }
+            } else {
+                program
+            };
 
             // add loader to chain
             program_id = program.owner;
@@ -223,6 +235,34 @@ impl Accounts {
         Ok(accounts)
     }
 
+    /// An upgradeable program's account with the ELF in its program data account as its data, for
+    /// the loader to run like any other program's.
+    fn load_upgradeable_program(
+        storage: &AccountStorage,
+        ancestors: &HashMap<Fork, usize>,
+        accounts_index: &AccountsIndex<AccountInfo>,
+        mut program: Account,
+    ) -> Option<Account> {
+        let programdata_address = match program.deserialize_data().ok()? {
+            UpgradeableLoaderState::Program {
+                programdata_address,
+            } => programdata_address,
+            _ => return None,
+        };
+        let programdata =
+            AccountsDB::load(storage, ancestors, accounts_index, &programdata_address)?;
+        if !bpf_loader_upgradeable::check_id(&programdata.owner) {
+            return None;
+        }
+        match programdata.deserialize_data().ok()? {
+            UpgradeableLoaderState::ProgramData { .. } => (),
+            _ => return None,
+        }
+        let metadata_size = UpgradeableLoaderState::programdata_metadata_size();
+        program.data = programdata.data[metadata_size..].to_vec();
+        Some(program)
+    }
+
     /// For each program_id in the transaction, load its loaders.
     fn load_loaders(
         storage: &AccountStorage,
@@ -320,6 +360,31 @@ impl Accounts {
         versions.into_iter().map(|s| (s.0, s.2)).collect()
     }
 
+    /// All the accounts `program_id` owns as of `ancestors`
+    pub fn load_by_program_slow(
+        &self,
+        ancestors: &HashMap<Fork, usize>,
+        program_id: &Pubkey,
+    ) -> Vec<(Pubkey, Account)> {
+        self.accounts_db
+            .load_by_program_slow(ancestors, program_id)
+            .into_iter()
+            .filter(|(_, account)| account.dif != 0)
+            .collect()
+    }
+
+    /// All the accounts as of `ancestors`, sorted by pubkey
+    pub fn load_all_slow(&self, ancestors: &HashMap<Fork, usize>) -> Vec<(Pubkey, Account)> {
+        let mut accounts: Vec<_> = self
+            .accounts_db
+            .load_all_slow(ancestors)
+            .into_iter()
+            .filter(|(_, account)| account.dif != 0)
+            .collect();
+        accounts.sort_by_key(|(pubkey, _)| *pubkey);
+        accounts
+    }
+
     /// Slow because lock is held for 1 operation instead of many
     pub fn store_slow(&self, fork: Fork, pubkey: &Pubkey, account: &Account) {
         self.accounts_db.store(fork, &[(pubkey, account)]);
@@ -871,6 +936,75 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_load_accounts_upgradeable_program() {
+        let mut accounts: Vec<(Pubkey, Account)> = Vec::new();
+        let mut error_counters = ErrorCounters::default();
+
+        let keypair = Keypair::new();
+        let key0 = keypair.pubkey();
+        let loader_id = bpf_loader_upgradeable::id();
+        let program_id = Pubkey::new(&[5u8; 32]);
+        let programdata_id = bpf_loader_upgradeable::get_programdata_address(&program_id);
+
+        let account = Account::new(1, 1, &Pubkey::default());
+        accounts.push((key0, account));
+
+        let mut account = Account::new(40, 1, &native_loader::id());
+        account.executable = true;
+        accounts.push((loader_id, account));
+
+        let mut program = Account::new(41, UpgradeableLoaderState::program_len(), &loader_id);
+        program.executable = true;
+        program
+            .serialize_data(&UpgradeableLoaderState::Program {
+                programdata_address: programdata_id,
+            })
+            .unwrap();
+        accounts.push((program_id, program));
+
+        let mut programdata =
+            Account::new(42, UpgradeableLoaderState::programdata_len(3), &loader_id);
+        programdata
+            .serialize_data(&UpgradeableLoaderState::ProgramData {
+                tick_height: 0,
+                upgrade_authority: None,
+            })
+            .unwrap();
+        let metadata_size = UpgradeableLoaderState::programdata_metadata_size();
+        programdata.data[metadata_size..].copy_from_slice(&[1, 2, 3]);
+        accounts.push((programdata_id, programdata));
+
+        let new_tx = || {
+            Transaction::new_with_compiled_instructions(
+                &[&keypair],
+                &[],
+                Hash::default(),
+                vec![program_id],
+                vec![CompiledInstruction::new(0, &(), vec![0])],
+            )
+        };
+
+        // The loader runs the ELF in the program data account
+        let loaded_accounts = load_accounts(new_tx(), &accounts, &mut error_counters);
+        assert_eq!(error_counters.account_not_found, 0);
+        match &loaded_accounts[0] {
+            Ok((_, l)) => {
+                assert_eq!(l[0].len(), 2);
+                assert_eq!(l[0][1].0, loader_id);
+                assert_eq!(l[0][1].1.data, vec![1, 2, 3]);
+                assert_eq!(l[0][1].1.dif, 41);
+            }
+            Err(e) => Err(e).unwrap(),
+        }
+
+        // No program without its program data
+        accounts.pop();
+        let loaded_accounts = load_accounts(new_tx(), &accounts, &mut error_counters);
+        assert_eq!(error_counters.account_not_found, 1);
+        assert_eq!(loaded_accounts[0], Err(TransactionError::AccountNotFound));
+    }
+
     #[test]
     fn test_load_account_pay_to_self() {
         let mut accounts: Vec<(Pubkey, Account)> = Vec::new();
@@ -925,6 +1059,56 @@ mod tests {
         assert_eq!(loaded, vec![]);
     }
 
+    #[test]
+    fn test_load_by_program_slow() {
+        let accounts = Accounts::new(None);
+        let program_id = Pubkey::new(&[2; 32]);
+
+        let pubkey0 = Pubkey::new_rand();
+        let account0 = Account::new(1, 0, &program_id);
+        accounts.store_slow(0, &pubkey0, &account0);
+        let pubkey1 = Pubkey::new_rand();
+        let account1 = Account::new(1, 0, &Pubkey::new(&[3; 32]));
+        accounts.store_slow(0, &pubkey1, &account1);
+        // Emptied accounts are gone
+        let pubkey2 = Pubkey::new_rand();
+        accounts.store_slow(0, &pubkey2, &Account::new(0, 0, &program_id));
+
+        let ancestors = vec![(0, 0)].into_iter().collect();
+        assert_eq!(
+            accounts.load_by_program_slow(&ancestors, &program_id),
+            vec![(pubkey0, account0)]
+        );
+        assert!(accounts
+            .load_by_program_slow(&ancestors, &Pubkey::new(&[4; 32]))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_load_all_slow() {
+        let accounts = Accounts::new(None);
+        let pubkey0 = Pubkey::new_rand();
+        let account0 = Account::new(1, 0, &Pubkey::default());
+        accounts.store_slow(0, &pubkey0, &account0);
+        let pubkey1 = Pubkey::new_rand();
+        accounts.store_slow(0, &pubkey1, &Account::new(0, 0, &Pubkey::default()));
+        // Only visible from fork 1
+        let pubkey2 = Pubkey::new_rand();
+        let account2 = Account::new(2, 0, &Pubkey::default());
+        accounts.store_slow(1, &pubkey2, &account2);
+
+        let ancestors = vec![(0, 0)].into_iter().collect();
+        assert_eq!(
+            accounts.load_all_slow(&ancestors),
+            vec![(pubkey0, account0.clone())]
+        );
+
+        let ancestors = vec![(0, 1), (1, 0)].into_iter().collect();
+        let mut expected = vec![(pubkey0, account0), (pubkey2, account2)];
+        expected.sort_by_key(|(pubkey, _)| *pubkey);
+        assert_eq!(accounts.load_all_slow(&ancestors), expected);
+    }
+
     #[test]
     fn test_accounts_account_not_found() {
         let accounts = Accounts::new(None);
@@ -274,6 +274,43 @@ impl AccountsDB {
         Self::load(&storage, ancestors, &accounts_index, pubkey)
     }
 
+    /// Load the accounts `program_id` owns as of `ancestors`, found through the owner index
+    /// instead of scanning the storage
+    pub fn load_by_program_slow(
+        &self,
+        ancestors: &HashMap<Fork, usize>,
+        program_id: &Pubkey,
+    ) -> Vec<(Pubkey, Account)> {
+        let accounts_index = self.accounts_index.read().unwrap();
+        let storage = self.storage.read().unwrap();
+        accounts_index
+            .program_accounts(program_id)
+            .into_iter()
+            .filter_map(|pubkey| {
+                let account = Self::load(&storage, ancestors, &accounts_index, &pubkey)?;
+                if account.owner == *program_id {
+                    Some((pubkey, account))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Load every account as of `ancestors`
+    pub fn load_all_slow(&self, ancestors: &HashMap<Fork, usize>) -> Vec<(Pubkey, Account)> {
+        let accounts_index = self.accounts_index.read().unwrap();
+        let storage = self.storage.read().unwrap();
+        accounts_index
+            .pubkeys()
+            .into_iter()
+            .filter_map(|pubkey| {
+                let account = Self::load(&storage, ancestors, &accounts_index, &pubkey)?;
+                Some((pubkey, account))
+            })
+            .collect()
+    }
+
     fn fork_storage(&self, fork_id: Fork) -> Arc<AccountStorageEntry> {
         let mut candidates: Vec<Arc<AccountStorageEntry>> = {
             let stores = self.storage.read().unwrap();
@@ -360,7 +397,13 @@ impl AccountsDB {
         let mut index = self.accounts_index.write().unwrap();
         let mut reclaims = vec![];
         for (i, info) in infos.into_iter().enumerate() {
-            let key = &accounts[i].0;
+            let (key, account) = &accounts[i];
+            let owner = if account.dif == 0 {
+                None
+            } else {
+                Some(&account.owner)
+            };
+            index.update_owner(fork_id, key, owner);
             reclaims.extend(index.insert(fork_id, key, info).into_iter())
         }
         reclaims
@@ -486,6 +529,42 @@ mod tests {
         assert_eq!(db.load_slow(&ancestors, &key), Some(account0));
     }
 
+    #[test]
+    fn test_accountsdb_load_by_program_slow() {
+        soros_logger::setup();
+        let paths = get_tmp_accounts_path!();
+        let db = AccountsDB::new(&paths.paths);
+        let program0 = Pubkey::new_rand();
+        let program1 = Pubkey::new_rand();
+        let key0 = Pubkey::new_rand();
+        let key1 = Pubkey::new_rand();
+        let account0 = Account::new(1, 0, &program0);
+        let account1 = Account::new(2, 0, &program0);
+
+        db.store(0, &[(&key0, &account0), (&key1, &account1)]);
+        // key1 is handed over to program1 on fork 1 only
+        let reassigned = Account::new(2, 0, &program1);
+        db.store(1, &[(&key1, &reassigned)]);
+
+        let ancestors = vec![(0, 0)].into_iter().collect();
+        let mut loaded = db.load_by_program_slow(&ancestors, &program0);
+        loaded.sort_by_key(|(pubkey, _)| *pubkey);
+        let mut expected = vec![(key0, account0.clone()), (key1, account1)];
+        expected.sort_by_key(|(pubkey, _)| *pubkey);
+        assert_eq!(loaded, expected);
+        assert!(db.load_by_program_slow(&ancestors, &program1).is_empty());
+
+        let ancestors = vec![(0, 0), (1, 1)].into_iter().collect();
+        assert_eq!(
+            db.load_by_program_slow(&ancestors, &program0),
+            vec![(key0, account0)]
+        );
+        assert_eq!(
+            db.load_by_program_slow(&ancestors, &program1),
+            vec![(key1, reassigned)]
+        );
+    }
+
     #[test]
     fn test_accountsdb_latest_ancestor() {
         soros_logger::setup();
@@ -7,6 +7,11 @@ pub type Fork = u64;
 #[derive(Default)]
 pub struct AccountsIndex<T> {
     account_maps: HashMap<Pubkey, Vec<(Fork, T)>>,
+    /// Accounts each program owns in some version that can still be loaded, callers check the
+    /// owner of the version they load since other forks may have reassigned the account
+    program_accounts: HashMap<Pubkey, HashSet<Pubkey>>,
+    /// Owner of each version of an account, `None` for a version stored without any dif
+    account_owners: HashMap<Pubkey, Vec<(Fork, Option<Pubkey>)>>,
     roots: HashSet<Fork>,
     //This value that needs to be stored to recover the index from AppendVec
     pub last_root: Fork,
@@ -59,6 +64,58 @@ impl<T: Clone> AccountsIndex<T> {
         };
         rv
     }
+
+    /// Record the owner `pubkey` was stored with on `fork`, or `None` if it was stored without
+    /// any dif.  The account leaves the index of programs none of its versions that can still be
+    /// loaded are owned by.
+    pub fn update_owner(&mut self, fork: Fork, pubkey: &Pubkey, owner: Option<&Pubkey>) {
+        let mut owners = self.account_owners.remove(pubkey).unwrap_or_default();
+        let stale: Vec<_> = owners.iter().filter_map(|(_, owner)| *owner).collect();
+
+        owners.retain(|(f, _)| *f != fork && !self.is_purged(*f));
+        // Only the latest root's version is loaded, the older roots' are shadowed by it
+        let max_root = owners
+            .iter()
+            .map(|(f, _)| *f)
+            .filter(|f| self.is_root(*f))
+            .max();
+        if let Some(max_root) = max_root {
+            owners.retain(|(f, _)| *f >= max_root || !self.is_root(*f));
+        }
+        owners.push((fork, owner.cloned()));
+
+        for program_id in stale {
+            if let Some(pubkeys) = self.program_accounts.get_mut(&program_id) {
+                pubkeys.remove(pubkey);
+                if pubkeys.is_empty() {
+                    self.program_accounts.remove(&program_id);
+                }
+            }
+        }
+        for program_id in owners.iter().filter_map(|(_, owner)| *owner) {
+            self.program_accounts
+                .entry(program_id)
+                .or_insert_with(HashSet::new)
+                .insert(*pubkey);
+        }
+        if owners.iter().any(|(_, owner)| owner.is_some()) {
+            self.account_owners.insert(*pubkey, owners);
+        }
+    }
+
+    /// Accounts that have been owned by `program_id`
+    pub fn program_accounts(&self, program_id: &Pubkey) -> Vec<Pubkey> {
+        self.program_accounts
+            .get(program_id)
+            .map(|pubkeys| pubkeys.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Every account stored on any fork
+    pub fn pubkeys(&self) -> Vec<Pubkey> {
+        self.account_maps.keys().cloned().collect()
+    }
+
     pub fn is_purged(&self, fork: Fork) -> bool {
         !self.is_root(fork) && fork < self.last_root
     }
@@ -230,4 +287,58 @@ mod tests {
         let ancestors = vec![].into_iter().collect();
         assert_eq!(index.get(&key.pubkey(), &ancestors), Some(&false));
     }
+
+    #[test]
+    fn test_program_accounts() {
+        let program_id = Pubkey::new_rand();
+        let key0 = Pubkey::new_rand();
+        let key1 = Pubkey::new_rand();
+        let mut index = AccountsIndex::<bool>::default();
+        assert!(index.program_accounts(&program_id).is_empty());
+
+        index.update_owner(0, &key0, Some(&program_id));
+        index.update_owner(0, &key1, Some(&program_id));
+        index.update_owner(0, &key0, Some(&program_id));
+        index.update_owner(1, &key1, Some(&Pubkey::new_rand()));
+
+        let mut program_accounts = index.program_accounts(&program_id);
+        program_accounts.sort();
+        let mut expected = vec![key0, key1];
+        expected.sort();
+        assert_eq!(program_accounts, expected);
+    }
+
+    #[test]
+    fn test_program_accounts_pruned() {
+        let program0 = Pubkey::new_rand();
+        let program1 = Pubkey::new_rand();
+        let key = Pubkey::new_rand();
+        let mut index = AccountsIndex::<bool>::default();
+
+        // Reassigned on the same fork
+        index.update_owner(0, &key, Some(&program0));
+        index.update_owner(0, &key, Some(&program1));
+        assert!(index.program_accounts(&program0).is_empty());
+        assert_eq!(index.program_accounts(&program1), vec![key]);
+
+        // Reassigned on a later fork, the earlier one still loads the old owner until it is
+        // shadowed by a root
+        index.add_root(0);
+        index.update_owner(1, &key, Some(&program0));
+        assert_eq!(index.program_accounts(&program0), vec![key]);
+        assert_eq!(index.program_accounts(&program1), vec![key]);
+        index.add_root(1);
+        index.update_owner(2, &key, Some(&program0));
+        assert_eq!(index.program_accounts(&program0), vec![key]);
+        assert!(index.program_accounts(&program1).is_empty());
+
+        // Versions on purged forks are dropped, as are accounts emptied of dif once rooted
+        index.add_root(3);
+        index.update_owner(4, &key, None);
+        assert_eq!(index.program_accounts(&program0), vec![key]);
+        index.add_root(4);
+        index.update_owner(5, &key, None);
+        assert!(index.program_accounts(&program0).is_empty());
+        assert!(index.account_owners.is_empty());
+    }
 }
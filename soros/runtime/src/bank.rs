@@ -12,102 +12,102 @@ use crate::status_cache::StatusCache;
 use bincode::serialize;
 use hashbrown::HashMap;
 use log::*;
+use soros_config_api::cluster_config::{
+    self, ClusterConfig, ClusterConfigState, ConfigAuthority, FeeConfig, StakeRewardConfig,
+    StorageRewardConfig,
+};
 use soros_metrics::counter::Counter;
 use soros_metrics::influxdb;
 use soros_sdk::account::Account;
+pub use soros_sdk::epoch_schedule::EpochSchedule;
 use soros_sdk::fee_calculator::FeeCalculator;
-use soros_sdk::genesis_block::GenesisBlock;
-use soros_sdk::hash::{extend_and_hash, Hash};
+use soros_sdk::genesis_block::{GenesisBlock, GENESIS_CONFIG_DIF};
+use soros_sdk::hash::{extend_and_hash, Hash, Hasher};
+use soros_sdk::log_collector::LogCollector;
 use soros_sdk::native_loader;
+use soros_sdk::nonce_state::{self, NonceState};
 use soros_sdk::pubkey::Pubkey;
 use soros_sdk::signature::{Keypair, Signature};
 use soros_sdk::system_transaction;
 use soros_sdk::timing::{duration_as_ms, duration_as_us, MAX_RECENT_BLOCKHASHES};
 use soros_sdk::transaction::{Result, Transaction, TransactionError};
+use soros_stake_api::config::{self as stake_config, Config as StakeConfig};
+use soros_stake_api::stake_state::StakeState;
 use soros_vote_api::vote_state::{self, Vote};
 use std::cmp;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, RwLock};
 use std::time::Instant;
 
-#[derive(Default, Debug, PartialEq, Eq, Clone, Copy)]
-pub struct EpochSchedule {
-    /// The maximum number of slots in each epoch.
-    pub slots_per_epoch: u64,
-
-    /// A number of slots before slot_index 0. Used to calculate finalized staked nodes.
-    pub stakers_slot_offset: u64,
-
-    /// basically: log2(slots_per_epoch)
-    pub first_normal_epoch: u64,
+type BankStatusCache = StatusCache<Result<()>>;
 
-    /// basically: 2.pow(first_normal_epoch)
-    pub first_normal_slot: u64,
+/// An account a simulated transaction would change
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AccountDiff {
+    pub pubkey: Pubkey,
+    pub pre: Account,
+    pub post: Account,
 }
 
-impl EpochSchedule {
-    pub fn new(slots_per_epoch: u64, stakers_slot_offset: u64, warmup: bool) -> Self {
-        let (first_normal_epoch, first_normal_slot) = if warmup {
-            let next_power_of_two = slots_per_epoch.next_power_of_two();
-            let log2_slots_per_epoch = next_power_of_two.trailing_zeros();
-
-            (u64::from(log2_slots_per_epoch), next_power_of_two - 1)
-        } else {
-            (0, 0)
-        };
-        EpochSchedule {
-            slots_per_epoch,
-            stakers_slot_offset,
-            first_normal_epoch,
-            first_normal_slot,
-        }
-    }
-
-    /// get the length of the given epoch (in slots)
-    pub fn get_slots_in_epoch(&self, epoch: u64) -> u64 {
-        if epoch < self.first_normal_epoch {
-            2u64.pow(epoch as u32)
-        } else {
-            self.slots_per_epoch
-        }
-    }
+/// What a transaction would do if it were committed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TransactionSimulation {
+    pub result: Result<()>,
+    /// Fee the transaction would pay, 0 if it fails before its instructions run
+    pub fee: u64,
+    /// Accounts the transaction would change, only the fee payer's if an instruction fails
+    pub account_diffs: Vec<AccountDiff>,
+    /// Lines logged by the programs the transaction invoked
+    pub logs: Vec<String>,
+}
 
-    /// get the epoch for which the given slot should save off
-    ///  information about stakers
-    pub fn get_stakers_epoch(&self, slot: u64) -> u64 {
-        if slot < self.first_normal_slot {
-            // until we get to normal slots, behave as if stakers_slot_offset == slots_per_epoch
+/// The state of a rooted bank, everything a validator needs to start from its slot instead of
+/// replaying the ledger up to it
+#[derive(Serialize, Deserialize)]
+pub struct BankSnapshot {
+    pub slot: u64,
+    /// Hash of the bank the snapshot was taken from
+    pub hash: Hash,
+    /// Hash of `accounts`, checked when the bank is restored
+    pub accounts_hash: Hash,
+    bank_height: u64,
+    tick_height: u64,
+    ticks_per_slot: u64,
+    transaction_count: u64,
+    collector_id: Pubkey,
+    dif_per_signature: u64,
+    epoch_schedule: EpochSchedule,
+    blockhash_queue: BlockhashQueue,
+    status_cache: BankStatusCache,
+    epoch_vote_accounts: HashMap<u64, HashMap<Pubkey, Account>>,
+    epoch_stake_accounts: HashMap<u64, HashMap<Pubkey, Account>>,
+    accounts: Vec<(Pubkey, Account)>,
+}
 
-            self.get_epoch_and_slot_index(slot).0 + 1
-        } else {
-            self.first_normal_epoch
-                + (slot - self.first_normal_slot + self.stakers_slot_offset) / self.slots_per_epoch
-        }
+impl BankSnapshot {
+    /// The hash a snapshot is trusted by, it covers the accounts as well as the bank hash,
+    /// which only follows from the parent bank and can't be checked against the snapshot
+    pub fn snapshot_hash(&self) -> Hash {
+        extend_and_hash(&self.hash, self.accounts_hash.as_ref())
     }
+}
 
-    /// get epoch and offset into the epoch for the given slot
-    pub fn get_epoch_and_slot_index(&self, slot: u64) -> (u64, u64) {
-        if slot < self.first_normal_slot {
-            let epoch = if slot < 2 {
-                slot as u32
-            } else {
-                (slot + 2).next_power_of_two().trailing_zeros() - 1
-            };
-
-            let epoch_len = 2u64.pow(epoch);
+#[derive(Debug, PartialEq, Eq)]
+pub enum BankSnapshotError {
+    /// The snapshot isn't the one with the trusted snapshot hash
+    SnapshotHashMismatch,
+    /// The accounts of the snapshot don't hash to its accounts hash
+    AccountsHashMismatch,
+}
 
-            (u64::from(epoch), slot - (epoch_len - 1))
-        } else {
-            (
-                self.first_normal_epoch + ((slot - self.first_normal_slot) / self.slots_per_epoch),
-                (slot - self.first_normal_slot) % self.slots_per_epoch,
-            )
-        }
+fn hash_accounts(accounts: &[(Pubkey, Account)]) -> Hash {
+    let mut hasher = Hasher::default();
+    for keyed_account in accounts {
+        hasher.hash(&serialize(keyed_account).unwrap());
     }
+    hasher.result()
 }
 
-type BankStatusCache = StatusCache<Result<()>>;
-
 /// Manager for the state of all accounts and programs after processing its entries.
 #[derive(Default)]
 pub struct Bank {
@@ -166,12 +166,22 @@ pub struct Bank {
     ///   a leader schedule boundary
     epoch_vote_accounts: HashMap<u64, HashMap<Pubkey, Account>>,
 
+    /// cache of stake_account state for this fork
+    stake_accounts: RwLock<HashMap<Pubkey, Account>>,
+
+    /// stake delegations on epoch boundaries, saved off along with epoch_vote_accounts
+    epoch_stake_accounts: HashMap<u64, HashMap<Pubkey, Account>>,
+
     /// A boolean reflecting whether any entries were recorded into the PoH
     /// stream for the slot == self.slot
     is_delta: AtomicBool,
 
     /// The Message processor
     message_processor: MessageProcessor,
+
+    /// A throwaway child that only runs transactions for `simulate_transaction`, the real
+    /// child in its slot may be storing into the slot's fork
+    is_simulation: bool,
 }
 
 impl Default for BlockhashQueue {
@@ -193,18 +203,37 @@ impl Bank {
         // genesis needs stakes for all epochs up to the epoch implied by
         //  slot = 0 and genesis configuration
         let vote_accounts = bank.vote_accounts();
+        let stake_accounts = bank.stake_accounts();
         for i in 0..=bank.get_stakers_epoch(bank.slot) {
             bank.epoch_vote_accounts.insert(i, vote_accounts.clone());
+            bank.epoch_stake_accounts.insert(i, stake_accounts.clone());
         }
         bank
     }
 
     /// Create a new bank that points to an immutable checkpoint of another bank.
     pub fn new_from_parent(parent: &Arc<Bank>, collector_id: &Pubkey, slot: u64) -> Self {
+        Self::new_child(parent, collector_id, slot, false)
+    }
+
+    /// A child of `parent` in the next slot that only reads: it doesn't see, store into or
+    /// purge that slot's fork. Cluster config updates due at an epoch boundary aren't
+    /// activated in the accounts, only the fees are read from the pending update.
+    fn new_simulation_child(parent: &Arc<Bank>) -> Self {
+        Self::new_child(parent, &parent.collector_id, parent.slot() + 1, true)
+    }
+
+    fn new_child(
+        parent: &Arc<Bank>,
+        collector_id: &Pubkey,
+        slot: u64,
+        is_simulation: bool,
+    ) -> Self {
         parent.freeze();
         assert_ne!(slot, parent.slot());
 
         let mut bank = Self::default();
+        bank.is_simulation = is_simulation;
         bank.blockhash_queue = RwLock::new(parent.blockhash_queue.read().unwrap().clone());
         bank.status_cache = parent.status_cache.clone();
         bank.bank_height = parent.bank_height + 1;
@@ -212,6 +241,7 @@ impl Bank {
         bank.transaction_count
             .store(parent.transaction_count() as usize, Ordering::Relaxed);
         bank.vote_accounts = RwLock::new(parent.vote_accounts());
+        bank.stake_accounts = RwLock::new(parent.stake_accounts());
 
         bank.tick_height
             .store(parent.tick_height.load(Ordering::SeqCst), Ordering::SeqCst);
@@ -220,15 +250,17 @@ impl Bank {
 
         bank.slot = slot;
         bank.max_tick_height = (bank.slot + 1) * bank.ticks_per_slot - 1;
-        soros_metrics::submit(
-            influxdb::Point::new("bank-new_from_parent-heights")
-                .add_field("slot_height", influxdb::Value::Integer(slot as i64))
-                .add_field(
-                    "bank_height",
-                    influxdb::Value::Integer(bank.bank_height as i64),
-                )
-                .to_owned(),
-        );
+        if !is_simulation {
+            soros_metrics::submit(
+                influxdb::Point::new("bank-new_from_parent-heights")
+                    .add_field("slot_height", influxdb::Value::Integer(slot as i64))
+                    .add_field(
+                        "bank_height",
+                        influxdb::Value::Integer(bank.bank_height as i64),
+                    )
+                    .to_owned(),
+            );
+        }
 
         bank.parent = RwLock::new(Some(parent.clone()));
         bank.parent_hash = parent.hash();
@@ -247,14 +279,99 @@ impl Bank {
             }
             epoch_vote_accounts
         };
-        bank.ancestors.insert(bank.slot(), 0);
+        bank.epoch_stake_accounts = {
+            let mut epoch_stake_accounts = parent.epoch_stake_accounts.clone();
+            let epoch = bank.get_stakers_epoch(bank.slot);
+            if epoch_stake_accounts.get(&epoch).is_none() {
+                epoch_stake_accounts.insert(epoch, bank.stake_accounts());
+            }
+            epoch_stake_accounts
+        };
+        if !is_simulation {
+            bank.ancestors.insert(bank.slot(), 0);
+        }
         bank.parents().iter().enumerate().for_each(|(i, p)| {
             bank.ancestors.insert(p.slot(), i + 1);
         });
 
+        let is_new_epoch = bank.get_epoch_and_slot_index(bank.slot).0
+            != parent.get_epoch_and_slot_index(parent.slot).0;
+        if is_new_epoch && !is_simulation {
+            bank.activate_cluster_config();
+        }
+        bank.fee_calculator = bank.fee_calculator_from_config(is_new_epoch);
+
         bank
     }
 
+    /// Capture this bank for validators to start from. Accounts are loaded through the
+    /// ancestors, so the bank should be a squashed root.
+    pub fn snapshot(&self) -> BankSnapshot {
+        self.freeze();
+        let accounts = self.accounts.load_all_slow(&self.ancestors);
+        BankSnapshot {
+            slot: self.slot,
+            hash: self.hash(),
+            accounts_hash: hash_accounts(&accounts),
+            bank_height: self.bank_height,
+            tick_height: self.tick_height(),
+            ticks_per_slot: self.ticks_per_slot,
+            transaction_count: self.transaction_count(),
+            collector_id: self.collector_id,
+            dif_per_signature: self.fee_calculator.dif_per_signature,
+            epoch_schedule: self.epoch_schedule,
+            blockhash_queue: self.blockhash_queue.read().unwrap().clone(),
+            status_cache: self.status_cache.read().unwrap().clone(),
+            epoch_vote_accounts: self.epoch_vote_accounts.clone(),
+            epoch_stake_accounts: self.epoch_stake_accounts.clone(),
+            accounts,
+        }
+    }
+
+    /// Restore a frozen root bank from a snapshot, once it checks out against
+    /// `trusted_snapshot_hash` and its accounts against its accounts hash
+    pub fn from_snapshot(
+        snapshot: BankSnapshot,
+        trusted_snapshot_hash: &Hash,
+        paths: Option<String>,
+    ) -> std::result::Result<Self, BankSnapshotError> {
+        if snapshot.snapshot_hash() != *trusted_snapshot_hash {
+            Err(BankSnapshotError::SnapshotHashMismatch)?
+        }
+        if hash_accounts(&snapshot.accounts) != snapshot.accounts_hash {
+            Err(BankSnapshotError::AccountsHashMismatch)?
+        }
+
+        let mut bank = Self::default();
+        bank.slot = snapshot.slot;
+        bank.ancestors.insert(bank.slot, 0);
+        bank.accounts = Arc::new(Accounts::new(paths));
+        for (pubkey, account) in &snapshot.accounts {
+            bank.store(pubkey, account);
+        }
+        bank.accounts.add_root(bank.slot);
+
+        let mut status_cache = snapshot.status_cache;
+        status_cache.add_root(bank.slot);
+        bank.status_cache = Arc::new(RwLock::new(status_cache));
+        bank.blockhash_queue = RwLock::new(snapshot.blockhash_queue);
+
+        bank.bank_height = snapshot.bank_height;
+        bank.tick_height
+            .store(snapshot.tick_height as usize, Ordering::SeqCst);
+        bank.ticks_per_slot = snapshot.ticks_per_slot;
+        bank.max_tick_height = (bank.slot + 1) * bank.ticks_per_slot - 1;
+        bank.transaction_count
+            .store(snapshot.transaction_count as usize, Ordering::Relaxed);
+        bank.collector_id = snapshot.collector_id;
+        bank.fee_calculator = FeeCalculator::new(snapshot.dif_per_signature);
+        bank.epoch_schedule = snapshot.epoch_schedule;
+        bank.epoch_vote_accounts = snapshot.epoch_vote_accounts;
+        bank.epoch_stake_accounts = snapshot.epoch_stake_accounts;
+        *bank.hash.write().unwrap() = snapshot.hash;
+        Ok(bank)
+    }
+
     pub fn collector_id(&self) -> Pubkey {
         self.collector_id
     }
@@ -329,7 +446,7 @@ impl Bank {
         assert!(genesis_block.bootstrap_leader_id != Pubkey::default());
         assert!(genesis_block.bootstrap_leader_vote_account_id != Pubkey::default());
         // assert!(genesis_block.lamports >= genesis_block.bootstrap_leader_lamports);
-        assert!(genesis_block.dif >= genesis_block.bootstrap_leader_dif);
+        assert!(genesis_block.dif >= genesis_block.bootstrap_leader_dif + GENESIS_CONFIG_DIF);
         // assert!(genesis_block.bootstrap_leader_lamports >= 2);
         assert!(genesis_block.bootstrap_leader_dif >= 2);
 
@@ -337,7 +454,8 @@ impl Bank {
         self.collector_id = genesis_block.bootstrap_leader_id;
 
         // let mint_lamports = genesis_block.lamports - genesis_block.bootstrap_leader_lamports;
-        let mint_dif = genesis_block.dif - genesis_block.bootstrap_leader_dif;
+        // the mint pays for the network config accounts stored below
+        let mint_dif = genesis_block.dif - genesis_block.bootstrap_leader_dif - GENESIS_CONFIG_DIF;
         // self.deposit(&genesis_block.mint_id, mint_lamports);
         self.deposit(&genesis_block.mint_id, mint_dif);
 
@@ -390,6 +508,36 @@ impl Bank {
             genesis_block.epoch_warmup,
         );
 
+        // Network parameters the stake program works with
+        self.store(
+            &stake_config::id(),
+            &stake_config::create_account(1, &StakeConfig::new_from_genesis_block(genesis_block)),
+        );
+
+        // Cluster parameters that can change at epoch boundaries, the mint controls them
+        //  until it hands them over to another authority
+        let authority = ConfigAuthority::new(&[genesis_block.mint_id], 1);
+        self.store(
+            &FeeConfig::id(),
+            &cluster_config::create_account(
+                1,
+                &authority,
+                FeeConfig::new(self.fee_calculator.dif_per_signature),
+            ),
+        );
+        self.store(
+            &StakeRewardConfig::id(),
+            &cluster_config::create_account(
+                1,
+                &authority,
+                StakeRewardConfig::new(genesis_block.stake_reward_target_rate),
+            ),
+        );
+        self.store(
+            &StorageRewardConfig::id(),
+            &cluster_config::create_account(1, &authority, StorageRewardConfig::default()),
+        );
+
         // Add native programs mandatory for the MessageProcessor to function
         self.register_native_instruction_processor(
             "soros_system_program",
@@ -399,6 +547,10 @@ impl Bank {
             "soros_bpf_loader",
             &soros_sdk::bpf_loader::id(),
         );
+        self.register_native_instruction_processor(
+            "soros_bpf_loader",
+            &soros_sdk::bpf_loader_upgradeable::id(),
+        );
         self.register_native_instruction_processor("soros_vote_program", &soros_vote_api::id());
 
         // Add additional native programs specified in the genesis block
@@ -407,6 +559,40 @@ impl Bank {
         }
     }
 
+    /// Put cluster parameter updates staged in the well-known config accounts into effect,
+    ///  called when this bank starts a new epoch
+    fn activate_cluster_config(&self) {
+        // the stake and storage programs read the parameters in effect straight from
+        //  the accounts, the bank reads the fees back in `new_from_parent`
+        self.activate_pending_config::<FeeConfig>();
+        self.activate_pending_config::<StakeRewardConfig>();
+        self.activate_pending_config::<StorageRewardConfig>();
+    }
+
+    fn activate_pending_config<T: ClusterConfig>(&self) {
+        if let Some(mut account) = self.get_account(&T::id()) {
+            if let Some(mut state) = ClusterConfigState::<T>::from(&account) {
+                if state.activate_pending().is_some() {
+                    account.serialize_data(&state).unwrap();
+                    self.store(&T::id(), &account);
+                }
+            }
+        }
+    }
+
+    /// The fees in effect, kept in the fee cluster config account, a pending update is in
+    /// effect if the bank starts a new epoch and hasn't activated it
+    fn fee_calculator_from_config(&self, is_new_epoch: bool) -> FeeCalculator {
+        self.get_account(&FeeConfig::id())
+            .and_then(|account| ClusterConfigState::<FeeConfig>::from(&account))
+            .map(|state| match state.pending {
+                Some(pending) if is_new_epoch => pending,
+                _ => state.params,
+            })
+            .map(|fee_config| FeeCalculator::new(fee_config.dif_per_signature))
+            .unwrap_or_default()
+    }
+
     pub fn register_native_instruction_processor(&self, name: &str, program_id: &Pubkey) {
         debug!("Adding native program {} under {:?}", name, program_id);
         let account = native_loader::create_loadable_account(name);
@@ -589,6 +775,7 @@ impl Bank {
             .map(|(tx, lock_res)| {
                 if lock_res.is_ok()
                     && !hash_queue.check_hash_age(tx.message().recent_blockhash, max_age)
+                    && self.check_nonce(tx).is_none()
                 {
                     error_counters.reserve_blockhash += 1;
                     Err(TransactionError::BlockhashNotFound)
@@ -598,6 +785,37 @@ impl Bank {
             })
             .collect()
     }
+    /// The nonce account of a transaction that uses its stored nonce in place of a recent
+    ///  blockhash, the nonce has to match and the nonce authority has to have signed
+    fn check_nonce<'a>(&self, tx: &'a Transaction) -> Option<&'a Pubkey> {
+        let nonce_pubkey = nonce_state::transaction_nonce_pubkey(tx)?;
+        let account = self.get_account(nonce_pubkey)?;
+        match NonceState::from(&account)? {
+            NonceState::Initialized { authority, nonce }
+                if nonce == tx.message().recent_blockhash
+                    && tx.signer_key(0, 1) == Some(&authority) =>
+            {
+                Some(nonce_pubkey)
+            }
+            _ => None,
+        }
+    }
+
+    /// Advance the nonce of a nonce transaction that failed, the failed transaction is not
+    ///  committed but must not be replayable either
+    fn advance_nonce(&self, nonce_pubkey: &Pubkey) {
+        if let Some(mut account) = self.get_account(nonce_pubkey) {
+            let state = NonceState::from(&account);
+            if let Some(NonceState::Initialized { authority, nonce }) = state {
+                let nonce = NonceState::next_nonce(&nonce, self.tick_height());
+                account
+                    .serialize_data(&NonceState::Initialized { authority, nonce })
+                    .unwrap();
+                self.store(nonce_pubkey, &account);
+            }
+        }
+    }
+
     fn check_signatures(
         &self,
         txs: &[Transaction],
@@ -638,37 +856,15 @@ impl Bank {
         Vec<Result<(InstructionAccounts, InstructionLoaders)>>,
         Vec<Result<()>>,
     ) {
-        debug!("processing transactions: {}", txs.len());
         let mut error_counters = ErrorCounters::default();
-        let now = Instant::now();
-        let refs_results = self.check_refs(txs, lock_results, &mut error_counters);
-        let age_results = self.check_age(txs, refs_results, max_age, &mut error_counters);
-        let sig_results = self.check_signatures(txs, age_results, &mut error_counters);
-        let mut loaded_accounts = self.load_accounts(txs, sig_results, &mut error_counters);
-        let tick_height = self.tick_height();
-
-        let load_elapsed = now.elapsed();
-        let now = Instant::now();
-        let executed: Vec<Result<()>> =
-            loaded_accounts
-                .iter_mut()
-                .zip(txs.iter())
-                .map(|(accs, tx)| match accs {
-                    Err(e) => Err(e.clone()),
-                    Ok((ref mut accounts, ref mut loaders)) => self
-                        .message_processor
-                        .process_message(tx.message(), loaders, accounts, tick_height),
-                })
-                .collect();
-
-        let execution_elapsed = now.elapsed();
-
-        debug!(
-            "load: {}us execute: {}us txs_len={}",
-            duration_as_us(&load_elapsed),
-            duration_as_us(&execution_elapsed),
-            txs.len(),
+        let (loaded_accounts, executed) = self.load_and_execute_transactions_with_log_collector(
+            txs,
+            lock_results,
+            max_age,
+            None,
+            &mut error_counters,
         );
+
         let mut tx_count = 0;
         let mut err_count = 0;
         for (r, tx) in executed.iter().zip(txs.iter()) {
@@ -732,6 +928,67 @@ impl Bank {
         (loaded_accounts, executed)
     }
 
+    /// Check, load and execute `txs` without counting them, the lines the programs log are
+    /// collected into `log_collector`
+    #[allow(clippy::type_complexity)]
+    fn load_and_execute_transactions_with_log_collector(
+        &self,
+        txs: &[Transaction],
+        lock_results: &LockedAccountsResults,
+        max_age: usize,
+        log_collector: Option<&LogCollector>,
+        error_counters: &mut ErrorCounters,
+    ) -> (
+        Vec<Result<(InstructionAccounts, InstructionLoaders)>>,
+        Vec<Result<()>>,
+    ) {
+        debug!("processing transactions: {}", txs.len());
+        let now = Instant::now();
+        let refs_results = self.check_refs(txs, lock_results, error_counters);
+        let age_results = self.check_age(txs, refs_results, max_age, error_counters);
+        let sig_results = self.check_signatures(txs, age_results, error_counters);
+        let mut loaded_accounts = self.load_accounts(txs, sig_results, error_counters);
+        let tick_height = self.tick_height();
+
+        let load_elapsed = now.elapsed();
+        let now = Instant::now();
+        let executed: Vec<Result<()>> = loaded_accounts
+            .iter_mut()
+            .zip(txs.iter())
+            .map(|(accs, tx)| match accs {
+                Err(e) => Err(e.clone()),
+                Ok((ref mut accounts, ref mut loaders)) => self.message_processor.process_message(
+                    tx.message(),
+                    loaders,
+                    accounts,
+                    tick_height,
+                    log_collector,
+                ),
+            })
+            .collect();
+
+        let execution_elapsed = now.elapsed();
+
+        debug!(
+            "load: {}us execute: {}us txs_len={}",
+            duration_as_us(&load_elapsed),
+            duration_as_us(&execution_elapsed),
+            txs.len(),
+        );
+        (loaded_accounts, executed)
+    }
+
+    /// The fee `tx` pays given how it was executed, transactions that fail before their
+    /// instructions run don't pay one
+    fn transaction_fee(&self, tx: &Transaction, executed: &Result<()>) -> Option<u64> {
+        match executed {
+            Ok(()) | Err(TransactionError::InstructionError(_, _)) => {
+                Some(self.fee_calculator.calculate_fee(tx.message()))
+            }
+            _ => None,
+        }
+    }
+
     fn filter_program_errors_and_collect_fee(
         &self,
         txs: &[Transaction],
@@ -751,6 +1008,10 @@ impl Bank {
                         // work of doing so (in accounts.load()) is ignored by store()
                         self.withdraw(&message.account_keys[0], fee)?;
                         fees += fee;
+                        // the nonce is used up all the same
+                        if let Some(nonce_pubkey) = self.check_nonce(tx) {
+                            self.advance_nonce(nonce_pubkey);
+                        }
                         Ok(())
                     }
                     Ok(()) => {
@@ -798,6 +1059,61 @@ impl Bank {
         self.filter_program_errors_and_collect_fee(txs, executed)
     }
 
+    /// Run `tx` in a throwaway child of `parent` through the same checks, fee and execution
+    /// as a committed transaction, without storing anything. `parent` gets frozen, so it
+    /// shouldn't be a bank that is still being worked on.
+    pub fn simulate_transaction(parent: &Arc<Bank>, tx: &Transaction) -> TransactionSimulation {
+        let bank = Self::new_simulation_child(parent);
+        let txs = [tx.clone()];
+        let lock_results = bank.lock_accounts(&txs);
+        let log_collector = LogCollector::default();
+        let (loaded_accounts, executed) = bank.load_and_execute_transactions_with_log_collector(
+            &txs,
+            &lock_results,
+            MAX_RECENT_BLOCKHASHES,
+            Some(&log_collector),
+            &mut ErrorCounters::default(),
+        );
+
+        let result = executed[0].clone();
+        let fee = bank.transaction_fee(tx, &result).unwrap_or(0);
+        let account_diffs = match (&result, &loaded_accounts[0]) {
+            (Ok(()), Ok((accounts, _))) => tx
+                .message()
+                .account_keys
+                .iter()
+                .zip(accounts)
+                .filter_map(|(pubkey, post)| {
+                    let pre = bank.get_account(pubkey).unwrap_or_default();
+                    if pre != *post {
+                        Some(AccountDiff {
+                            pubkey: *pubkey,
+                            pre,
+                            post: post.clone(),
+                        })
+                    } else {
+                        None
+                    }
+                })
+                .collect(),
+            // the fee payer pays for a failed instruction all the same
+            (Err(TransactionError::InstructionError(_, _)), _) if fee > 0 => {
+                let pubkey = tx.message().account_keys[0];
+                let pre = bank.get_account(&pubkey).unwrap_or_default();
+                let mut post = pre.clone();
+                post.dif -= fee;
+                vec![AccountDiff { pubkey, pre, post }]
+            }
+            _ => vec![],
+        };
+        TransactionSimulation {
+            result,
+            fee,
+            account_diffs,
+            logs: log_collector.messages(),
+        }
+    }
+
     /// Process a batch of transactions.
     #[must_use]
     pub fn load_execute_and_commit_transactions(
@@ -861,6 +1177,14 @@ impl Bank {
                 vote_accounts.remove(pubkey);
             }
         }
+        if soros_stake_api::check_id(&account.owner) {
+            let mut stake_accounts = self.stake_accounts.write().unwrap();
+            if account.dif != 0 {
+                stake_accounts.insert(*pubkey, account.clone());
+            } else {
+                stake_accounts.remove(pubkey);
+            }
+        }
     }
 
     // pub fn withdraw(&self, pubkey: &Pubkey, lamports: u64) -> Result<()> {
@@ -894,6 +1218,12 @@ impl Bank {
         self.accounts.load_slow(&self.ancestors, pubkey)
     }
 
+    /// All the accounts `program_id` owns in this bank
+    pub fn get_program_accounts(&self, program_id: &Pubkey) -> Vec<(Pubkey, Account)> {
+        self.accounts
+            .load_by_program_slow(&self.ancestors, program_id)
+    }
+
     pub fn get_program_accounts_modified_since_parent(
         &self,
         program_id: &Pubkey,
@@ -973,7 +1303,7 @@ impl Bank {
         self.epoch_schedule.get_stakers_epoch(slot)
     }
 
-    /// a bank-level cache of vote accounts
+    /// a bank-level cache of vote and stake accounts
     fn store_vote_accounts(
         &self,
         txs: &[Transaction],
@@ -981,6 +1311,7 @@ impl Bank {
         loaded: &[Result<(InstructionAccounts, InstructionLoaders)>],
     ) {
         let mut vote_accounts = self.vote_accounts.write().unwrap();
+        let mut stake_accounts = self.stake_accounts.write().unwrap();
 
         for (i, raccs) in loaded.iter().enumerate() {
             if res[i].is_err() || raccs.is_err() {
@@ -993,13 +1324,19 @@ impl Bank {
                 .account_keys
                 .iter()
                 .zip(acc.0.iter())
-                .filter(|(_, account)| soros_vote_api::check_id(&account.owner))
             {
+                let accounts = if soros_vote_api::check_id(&account.owner) {
+                    &mut vote_accounts
+                } else if soros_stake_api::check_id(&account.owner) {
+                    &mut stake_accounts
+                } else {
+                    continue;
+                };
                 // if account.lamports != 0 {
                 if account.dif != 0 {
-                    vote_accounts.insert(*key, account.clone());
+                    accounts.insert(*key, account.clone());
                 } else {
-                    vote_accounts.remove(key);
+                    accounts.remove(key);
                 }
             }
         }
@@ -1015,6 +1352,51 @@ impl Bank {
         self.epoch_vote_accounts.get(&epoch)
     }
 
+    /// current stake accounts for this bank
+    pub fn stake_accounts(&self) -> HashMap<Pubkey, Account> {
+        self.stake_accounts.read().unwrap().clone()
+    }
+
+    ///  stake accounts for the specific epoch
+    pub fn epoch_stake_accounts(&self, epoch: u64) -> Option<&HashMap<Pubkey, Account>> {
+        self.epoch_stake_accounts.get(&epoch)
+    }
+
+    /// effective stake currently delegated to each vote account
+    pub fn effective_stakes(&self) -> HashMap<Pubkey, u64> {
+        let (epoch, _) = self.get_epoch_and_slot_index(self.slot());
+        self.to_effective_stakes(&self.stake_accounts(), epoch)
+    }
+
+    /// effective stake delegated to each vote account in the specific epoch, warmup and
+    ///  cooldown are applied to the stake accounts saved off for that epoch
+    pub fn epoch_effective_stakes(&self, epoch: u64) -> Option<HashMap<Pubkey, u64>> {
+        self.epoch_stake_accounts(epoch)
+            .map(|stake_accounts| self.to_effective_stakes(stake_accounts, epoch))
+    }
+
+    fn to_effective_stakes(
+        &self,
+        stake_accounts: &HashMap<Pubkey, Account>,
+        epoch: u64,
+    ) -> HashMap<Pubkey, u64> {
+        let config = self
+            .get_account(&stake_config::id())
+            .and_then(|account| StakeConfig::from(&account))
+            .unwrap_or_default();
+
+        let mut stakes = HashMap::new();
+        for account in stake_accounts.values() {
+            if let Ok(StakeState::Stake(stake)) = account.deserialize_data() {
+                let effective_stake = stake.effective_stake(epoch, &config);
+                if effective_stake > 0 {
+                    *stakes.entry(stake.voter_id).or_insert(0) += effective_stake;
+                }
+            }
+        }
+        stakes
+    }
+
     /// given a slot, return the epoch and offset into the epoch this slot falls
     /// e.g. with a fixed number for slots_per_epoch, the calculation is simply:
     ///
@@ -1045,6 +1427,10 @@ impl Bank {
 
 impl Drop for Bank {
     fn drop(&mut self) {
+        // A simulation never stored into its slot's fork, which belongs to the real child
+        if self.is_simulation {
+            return;
+        }
         // For root forks this is a noop
         self.accounts.purge_fork(self.slot());
     }
@@ -1053,13 +1439,15 @@ impl Drop for Bank {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use bincode::deserialize;
     // use soros_sdk::genesis_block::{GenesisBlock, BOOTSTRAP_LEADER_LAMPORTS};
-    use soros_sdk::genesis_block::{GenesisBlock, BOOTSTRAP_LEADER_DIF};
+    use soros_sdk::genesis_block::{GenesisBlock, BOOTSTRAP_LEADER_DIF, GENESIS_CONFIG_DIF};
     use soros_sdk::hash;
     use soros_sdk::instruction::InstructionError;
     use soros_sdk::signature::{Keypair, KeypairUtil};
-    use soros_sdk::system_instruction;
+    use soros_sdk::system_instruction::{self, SystemError};
     use soros_sdk::system_transaction;
+    use soros_stake_api::stake_state::Stake;
     use soros_vote_api::vote_instruction;
     use soros_vote_api::vote_state::VoteState;
 
@@ -1676,6 +2064,117 @@ mod tests {
         assert_eq!(parent.get_balance(&key1.pubkey()), 1);
     }
 
+    #[test]
+    fn test_bank_get_program_accounts() {
+        let (genesis_block, _mint_keypair) = GenesisBlock::new(500);
+        let parent = Arc::new(Bank::new(&genesis_block));
+        let program_id = Pubkey::new_rand();
+
+        let key0 = Pubkey::new_rand();
+        let account0 = Account::new(1, 0, &program_id);
+        parent.store(&key0, &account0);
+
+        let bank = Arc::new(new_from_parent(&parent));
+        let key1 = Pubkey::new_rand();
+        let account1 = Account::new(2, 0, &program_id);
+        bank.store(&key1, &account1);
+
+        assert_eq!(
+            parent.get_program_accounts(&program_id),
+            vec![(key0, account0.clone())]
+        );
+        let mut accounts = bank.get_program_accounts(&program_id);
+        accounts.sort_by_key(|(pubkey, _)| *pubkey);
+        let mut expected = vec![(key0, account0), (key1, account1)];
+        expected.sort_by_key(|(pubkey, _)| *pubkey);
+        assert_eq!(accounts, expected);
+
+        bank.squash();
+        assert_eq!(bank.get_program_accounts(&program_id).len(), 2);
+        assert!(bank.get_program_accounts(&Pubkey::new_rand()).is_empty());
+    }
+
+    #[test]
+    fn test_bank_snapshot() {
+        let (genesis_block, mint_keypair) = GenesisBlock::new(500);
+        let parent = Arc::new(Bank::new(&genesis_block));
+        let key1 = Keypair::new();
+        let tx = system_transaction::transfer(
+            &mint_keypair,
+            &key1.pubkey(),
+            10,
+            genesis_block.hash(),
+            0,
+        );
+        assert_eq!(parent.process_transaction(&tx), Ok(()));
+        let bank = Arc::new(new_from_parent(&parent));
+        let key2 = Pubkey::new_rand();
+        bank.transfer(20, &mint_keypair, &key2).unwrap();
+        bank.squash();
+
+        let snapshot: BankSnapshot = deserialize(&serialize(&bank.snapshot()).unwrap()).unwrap();
+        assert_eq!(snapshot.slot, bank.slot());
+        assert_eq!(snapshot.hash, bank.hash());
+        let snapshot_hash = snapshot.snapshot_hash();
+        let restored = Arc::new(Bank::from_snapshot(snapshot, &snapshot_hash, None).unwrap());
+        assert_eq!(restored.hash(), bank.hash());
+        assert_eq!(restored.tick_height(), bank.tick_height());
+        assert_eq!(restored.last_blockhash(), bank.last_blockhash());
+        assert_eq!(restored.transaction_count(), bank.transaction_count());
+        assert_eq!(restored.vote_accounts(), bank.vote_accounts());
+        assert_eq!(restored.get_balance(&key1.pubkey()), 10);
+        assert_eq!(restored.get_balance(&key2), 20);
+        assert_eq!(
+            restored.get_balance(&mint_keypair.pubkey()),
+            bank.get_balance(&mint_keypair.pubkey())
+        );
+
+        // Children of the restored bank still reject what the snapshot recorded
+        let child = new_from_parent(&restored);
+        assert_eq!(
+            child.process_transaction(&tx),
+            Err(TransactionError::DuplicateSignature)
+        );
+        child.transfer(5, &mint_keypair, &key2).unwrap();
+        assert_eq!(child.get_balance(&key2), 25);
+    }
+
+    #[test]
+    fn test_bank_snapshot_accounts_hash_mismatch() {
+        let (genesis_block, _mint_keypair) = GenesisBlock::new(500);
+        let bank = Bank::new(&genesis_block);
+        let mut snapshot = bank.snapshot();
+        let snapshot_hash = snapshot.snapshot_hash();
+        snapshot.accounts[0].1.dif += 1;
+        assert_eq!(
+            Bank::from_snapshot(snapshot, &snapshot_hash, None).err(),
+            Some(BankSnapshotError::AccountsHashMismatch)
+        );
+    }
+
+    #[test]
+    fn test_bank_snapshot_forged_accounts() {
+        let (genesis_block, mint_keypair) = GenesisBlock::new(500);
+        let bank = Bank::new(&genesis_block);
+        let mut snapshot = bank.snapshot();
+        let snapshot_hash = snapshot.snapshot_hash();
+
+        // The bank hash is the real one and the accounts hash matches the forged accounts
+        let mint_pubkey = mint_keypair.pubkey();
+        let (_, mint_account) = snapshot
+            .accounts
+            .iter_mut()
+            .find(|(pubkey, _)| *pubkey == mint_pubkey)
+            .unwrap();
+        mint_account.dif += 1_000_000;
+        snapshot.accounts_hash = hash_accounts(&snapshot.accounts);
+        assert_eq!(snapshot.hash, bank.hash());
+        assert_eq!(
+            Bank::from_snapshot(snapshot, &snapshot_hash, None).err(),
+            Some(BankSnapshotError::SnapshotHashMismatch)
+        );
+    }
+
     #[test]
     fn test_bank_epoch_vote_accounts() {
         let leader_id = Pubkey::new_rand();
@@ -1740,6 +2239,131 @@ mod tests {
         assert!(child.epoch_vote_accounts(i).is_some());
     }
 
+    #[test]
+    fn test_bank_epoch_effective_stakes() {
+        let (mut genesis_block, _) = GenesisBlock::new(500);
+        const SLOTS_PER_EPOCH: u64 = 8;
+        genesis_block.slots_per_epoch = SLOTS_PER_EPOCH;
+        genesis_block.stakers_slot_offset = SLOTS_PER_EPOCH;
+        genesis_block.epoch_warmup = false;
+
+        let bank = Bank::new(&genesis_block);
+        // only the stake config so far
+        assert_eq!(bank.stake_accounts().len(), 1);
+        assert!(bank.effective_stakes().is_empty());
+
+        let vote_id = Pubkey::new_rand();
+        let mut stake_account = Account::new(
+            100,
+            std::mem::size_of::<StakeState>(),
+            &soros_stake_api::id(),
+        );
+        stake_account
+            .serialize_data(&StakeState::Stake(Stake::new(&vote_id, 0, 100, 0)))
+            .unwrap();
+        bank.store(&Pubkey::new_rand(), &stake_account);
+        assert_eq!(bank.stake_accounts().len(), 2);
+
+        // delegated in epoch 0, still warming up
+        assert!(bank.effective_stakes().is_empty());
+
+        // several epochs later, all of it is effective
+        let slot = SLOTS_PER_EPOCH * 4;
+        let child = Bank::new_from_parent(&Arc::new(bank), &Pubkey::default(), slot);
+        let epoch = child.get_stakers_epoch(slot);
+        assert_eq!(
+            child
+                .epoch_effective_stakes(epoch)
+                .and_then(|stakes| stakes.get(&vote_id).cloned()),
+            Some(100)
+        );
+        assert_eq!(child.effective_stakes().get(&vote_id), Some(&100));
+    }
+
+    fn stage_cluster_config<T: ClusterConfig>(bank: &Bank, params: T) {
+        let mut account = bank.get_account(&T::id()).unwrap();
+        let mut state = ClusterConfigState::<T>::from(&account).unwrap();
+        state.pending = Some(params);
+        account.serialize_data(&state).unwrap();
+        bank.store(&T::id(), &account);
+    }
+
+    #[test]
+    fn test_bank_genesis_config_accounts_funded_by_mint() {
+        let (genesis_block, _mint_keypair) = GenesisBlock::new(10_000);
+        let bank = Bank::new(&genesis_block);
+        assert_eq!(bank.get_balance(&genesis_block.mint_id), 10_000);
+
+        let config_dif: u64 = [
+            stake_config::id(),
+            FeeConfig::id(),
+            StakeRewardConfig::id(),
+            StorageRewardConfig::id(),
+        ]
+        .iter()
+        .map(|id| bank.get_balance(id))
+        .sum();
+        assert_eq!(config_dif, GENESIS_CONFIG_DIF);
+        assert_eq!(
+            bank.get_balance(&genesis_block.mint_id) + config_dif,
+            genesis_block.dif - genesis_block.bootstrap_leader_dif
+        );
+    }
+
+    #[test]
+    fn test_bank_cluster_config_epoch_boundary() {
+        let (mut genesis_block, mint_keypair) = GenesisBlock::new(500);
+        const SLOTS_PER_EPOCH: u64 = 8;
+        genesis_block.slots_per_epoch = SLOTS_PER_EPOCH;
+        genesis_block.stakers_slot_offset = SLOTS_PER_EPOCH;
+        genesis_block.epoch_warmup = false;
+
+        let bank = Bank::new(&genesis_block);
+        let state =
+            ClusterConfigState::<FeeConfig>::from(&bank.get_account(&FeeConfig::id()).unwrap())
+                .unwrap();
+        assert_eq!(
+            state.authority,
+            ConfigAuthority::new(&[mint_keypair.pubkey()], 1)
+        );
+        assert_eq!(state.params, FeeConfig::new(0));
+
+        stage_cluster_config(&bank, FeeConfig::new(3));
+        stage_cluster_config(&bank, StakeRewardConfig::new(0.5));
+        stage_cluster_config(&bank, StorageRewardConfig::new(2, 4));
+
+        // nothing changes within the epoch
+        let bank = Arc::new(Bank::new_from_parent(
+            &Arc::new(bank),
+            &Pubkey::default(),
+            1,
+        ));
+        assert_eq!(bank.fee_calculator.dif_per_signature, 0);
+
+        // the next epoch puts the updates into effect
+        let bank = Arc::new(Bank::new_from_parent(
+            &bank,
+            &Pubkey::default(),
+            SLOTS_PER_EPOCH,
+        ));
+        assert_eq!(bank.fee_calculator.dif_per_signature, 3);
+        let state = ClusterConfigState::<StakeRewardConfig>::from(
+            &bank.get_account(&StakeRewardConfig::id()).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(state.params, StakeRewardConfig::new(0.5));
+        let state = ClusterConfigState::<StorageRewardConfig>::from(
+            &bank.get_account(&StorageRewardConfig::id()).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(state.params, StorageRewardConfig::new(2, 4));
+        assert_eq!(state.pending, None);
+
+        // and children keep them
+        let bank = Bank::new_from_parent(&bank, &Pubkey::default(), SLOTS_PER_EPOCH + 1);
+        assert_eq!(bank.fee_calculator.dif_per_signature, 3);
+    }
+
     #[test]
     fn test_zero_signatures() {
         soros_logger::setup();
@@ -1987,4 +2611,212 @@ mod tests {
 
         assert!(bank.is_delta.load(Ordering::Relaxed));
     }
+
+    #[test]
+    fn test_bank_simulate_transaction() {
+        let (genesis_block, mint_keypair) = GenesisBlock::new(100);
+        let mint_pubkey = mint_keypair.pubkey();
+        let bank = Arc::new(Bank::new(&genesis_block));
+        let key = Pubkey::new_rand();
+        let program_id = soros_sdk::system_program::id();
+
+        let tx = system_transaction::transfer(&mint_keypair, &key, 1, genesis_block.hash(), 0);
+        let simulation = Bank::simulate_transaction(&bank, &tx);
+        assert_eq!(simulation.result, Ok(()));
+        let diffs: Vec<_> = simulation
+            .account_diffs
+            .iter()
+            .map(|diff| (diff.pubkey, diff.pre.dif, diff.post.dif))
+            .collect();
+        assert_eq!(diffs, vec![(mint_pubkey, 100, 99), (key, 0, 1)]);
+        assert_eq!(
+            simulation.logs,
+            vec![
+                format!("Program {} invoke", program_id),
+                format!("Program {} success", program_id)
+            ]
+        );
+
+        // nothing was committed
+        assert_eq!(bank.get_balance(&key), 0);
+        assert_eq!(bank.transaction_count(), 0);
+        assert_eq!(bank.get_signature_status(&tx.signatures[0]), None);
+
+        let tx = system_transaction::transfer(&mint_keypair, &key, 101, genesis_block.hash(), 0);
+        let simulation = Bank::simulate_transaction(&bank, &tx);
+        assert_eq!(
+            simulation.result,
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::CustomError(SystemError::ResultWithNegativeDif as u32)
+            ))
+        );
+        assert!(simulation.account_diffs.is_empty());
+        assert!(simulation.logs[1].starts_with(&format!("Program {} failed", program_id)));
+    }
+
+    #[test]
+    fn test_bank_simulate_transaction_leaves_child_fork() {
+        let (genesis_block, mint_keypair) = GenesisBlock::new(100);
+        let parent = Arc::new(Bank::new(&genesis_block));
+        let child = Bank::new_from_parent(&parent, &Pubkey::default(), parent.slot() + 1);
+        let stored_pubkey = Pubkey::new_rand();
+        child.deposit(&stored_pubkey, 10);
+
+        let key = Pubkey::new_rand();
+        let tx = system_transaction::transfer(&mint_keypair, &key, 1, genesis_block.hash(), 0);
+        assert_eq!(Bank::simulate_transaction(&parent, &tx).result, Ok(()));
+
+        // The fork the simulation runs next to keeps what it stored
+        assert_eq!(child.get_balance(&stored_pubkey), 10);
+        assert_eq!(parent.get_balance(&key), 0);
+        assert_eq!(parent.transaction_count(), 0);
+    }
+
+    #[test]
+    fn test_bank_simulate_transaction_fee() {
+        let (mut genesis_block, mint_keypair) = GenesisBlock::new(100);
+        let mint_pubkey = mint_keypair.pubkey();
+        const SLOTS_PER_EPOCH: u64 = 8;
+        genesis_block.slots_per_epoch = SLOTS_PER_EPOCH;
+        genesis_block.stakers_slot_offset = SLOTS_PER_EPOCH;
+        genesis_block.epoch_warmup = false;
+        let bank = Arc::new(Bank::new(&genesis_block));
+        let bank = Arc::new(Bank::new_from_parent(
+            &bank,
+            &Pubkey::default(),
+            SLOTS_PER_EPOCH - 1,
+        ));
+
+        // the simulation runs in the next epoch, where the staged fees are in effect
+        stage_cluster_config(&bank, FeeConfig::new(3));
+        let key = Pubkey::new_rand();
+        let tx = system_transaction::transfer(&mint_keypair, &key, 101, genesis_block.hash(), 0);
+        let simulation = Bank::simulate_transaction(&bank, &tx);
+        assert!(simulation.result.is_err());
+        assert_eq!(simulation.fee, 3);
+        let diffs: Vec<_> = simulation
+            .account_diffs
+            .iter()
+            .map(|diff| (diff.pubkey, diff.pre.dif, diff.post.dif))
+            .collect();
+        assert_eq!(diffs, vec![(mint_pubkey, 100, 97)]);
+
+        // without activating them
+        let state =
+            ClusterConfigState::<FeeConfig>::from(&bank.get_account(&FeeConfig::id()).unwrap())
+                .unwrap();
+        assert_eq!(state.pending, Some(FeeConfig::new(3)));
+        assert_eq!(bank.get_balance(&mint_pubkey), 100);
+    }
+
+    fn get_nonce(bank: &Bank, nonce_pubkey: &Pubkey) -> Hash {
+        match NonceState::from(&bank.get_account(nonce_pubkey).unwrap()).unwrap() {
+            NonceState::Initialized { nonce, .. } => nonce,
+            NonceState::Uninitialized => panic!("nonce account not initialized"),
+        }
+    }
+
+    #[test]
+    fn test_bank_nonce_transaction() {
+        let (genesis_block, mint_keypair) = GenesisBlock::new(100);
+        let bank = Bank::new(&genesis_block);
+        let nonce_keypair = Keypair::new();
+        let nonce_pubkey = nonce_keypair.pubkey();
+        let to_pubkey = Pubkey::new_rand();
+
+        let tx = system_transaction::create_nonce_account(
+            &mint_keypair,
+            &nonce_keypair,
+            &mint_keypair.pubkey(),
+            10,
+            genesis_block.hash(),
+        );
+        assert_eq!(bank.process_transaction(&tx), Ok(()));
+        let nonce = get_nonce(&bank, &nonce_pubkey);
+
+        // the nonce isn't a recent blockhash, a plain transfer signed with it is refused
+        let tx = system_transaction::transfer(&mint_keypair, &to_pubkey, 5, nonce, 0);
+        assert_eq!(
+            bank.process_transaction(&tx),
+            Err(TransactionError::BlockhashNotFound)
+        );
+
+        // advancing the nonce first makes it acceptable
+        let tx =
+            system_transaction::nonced_transfer(&mint_keypair, &to_pubkey, 5, &nonce_pubkey, nonce);
+        assert_eq!(bank.process_transaction(&tx), Ok(()));
+        assert_eq!(bank.get_balance(&to_pubkey), 5);
+        let advanced_nonce = get_nonce(&bank, &nonce_pubkey);
+        assert_ne!(advanced_nonce, nonce);
+
+        // and only once
+        assert_eq!(
+            bank.process_transaction(&tx),
+            Err(TransactionError::BlockhashNotFound)
+        );
+        assert_eq!(bank.get_balance(&to_pubkey), 5);
+
+        // only the nonce authority can use it
+        let other_keypair = Keypair::new();
+        bank.transfer(10, &mint_keypair, &other_keypair.pubkey())
+            .unwrap();
+        let tx = system_transaction::nonced_transfer(
+            &other_keypair,
+            &to_pubkey,
+            5,
+            &nonce_pubkey,
+            advanced_nonce,
+        );
+        assert_eq!(
+            bank.process_transaction(&tx),
+            Err(TransactionError::BlockhashNotFound)
+        );
+        assert_eq!(get_nonce(&bank, &nonce_pubkey), advanced_nonce);
+    }
+
+    #[test]
+    fn test_bank_failed_nonce_transaction_advances_nonce() {
+        let (genesis_block, mint_keypair) = GenesisBlock::new(100);
+        let mut bank = Bank::new(&genesis_block);
+        bank.fee_calculator.dif_per_signature = 1;
+        let nonce_keypair = Keypair::new();
+        let nonce_pubkey = nonce_keypair.pubkey();
+        let to_pubkey = Pubkey::new_rand();
+
+        let tx = system_transaction::create_nonce_account(
+            &mint_keypair,
+            &nonce_keypair,
+            &mint_keypair.pubkey(),
+            10,
+            genesis_block.hash(),
+        );
+        assert_eq!(bank.process_transaction(&tx), Ok(()));
+        let nonce = get_nonce(&bank, &nonce_pubkey);
+        let balance = bank.get_balance(&mint_keypair.pubkey());
+
+        // more than the mint has, the transfer fails
+        let tx = system_transaction::nonced_transfer(
+            &mint_keypair,
+            &to_pubkey,
+            1_000,
+            &nonce_pubkey,
+            nonce,
+        );
+        assert_eq!(
+            bank.process_transaction(&tx),
+            Err(TransactionError::InstructionError(
+                1,
+                InstructionError::new_result_with_negative_dif(),
+            ))
+        );
+
+        // the fee is paid and the nonce is used up
+        assert_eq!(bank.get_balance(&mint_keypair.pubkey()), balance - 1);
+        assert_ne!(get_nonce(&bank, &nonce_pubkey), nonce);
+        assert_eq!(
+            bank.process_transaction(&tx),
+            Err(TransactionError::BlockhashNotFound)
+        );
+    }
 }
@@ -2,14 +2,14 @@ use hashbrown::HashMap;
 use soros_sdk::hash::Hash;
 use soros_sdk::timing::timestamp;
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 struct HashAge {
     timestamp: u64,
     hash_height: u64,
 }
 
 /// Low memory overhead, so can be cloned for every checkpoint
-#[derive(Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct BlockhashQueue {
     /// updated whenever an hash is registered
     hash_height: u64,
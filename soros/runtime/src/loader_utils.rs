@@ -1,8 +1,10 @@
 use crate::bank_client::BankClient;
 use serde::Serialize;
+use soros_sdk::bpf_loader_upgradeable;
 use soros_sdk::client::SyncClient;
 use soros_sdk::instruction::{AccountMeta, Instruction};
 use soros_sdk::loader_instruction;
+use soros_sdk::message::Message;
 use soros_sdk::pubkey::Pubkey;
 use soros_sdk::signature::{Keypair, KeypairUtil};
 
@@ -47,6 +49,79 @@ pub fn load_program(
     program_pubkey
 }
 
+fn load_upgradeable_buffer(
+    bank_client: &BankClient,
+    from_keypair: &Keypair,
+    program: &[u8],
+) -> Pubkey {
+    let buffer_keypair = Keypair::new();
+    let buffer_pubkey = buffer_keypair.pubkey();
+    let message = Message::new(bpf_loader_upgradeable::create_buffer(
+        &from_keypair.pubkey(),
+        &buffer_pubkey,
+        &from_keypair.pubkey(),
+        1,
+        program.len(),
+    ));
+    bank_client
+        .send_message(&[from_keypair, &buffer_keypair], message)
+        .unwrap();
+
+    let chunk_size = 256; // Size of chunk just needs to fit into tx
+    let mut offset = 0;
+    for chunk in program.chunks(chunk_size) {
+        let instruction = bpf_loader_upgradeable::write(
+            &buffer_pubkey,
+            &from_keypair.pubkey(),
+            offset,
+            chunk.to_vec(),
+        );
+        bank_client
+            .send_instruction(&from_keypair, instruction)
+            .unwrap();
+        offset += chunk_size as u32;
+    }
+    buffer_pubkey
+}
+
+/// Deploy `program` with the upgradeable loader, with `from_keypair` as its upgrade authority
+pub fn load_upgradeable_program(
+    bank_client: &BankClient,
+    from_keypair: &Keypair,
+    program: Vec<u8>,
+) -> Pubkey {
+    let buffer_pubkey = load_upgradeable_buffer(bank_client, from_keypair, &program);
+    let program_keypair = Keypair::new();
+    let program_pubkey = program_keypair.pubkey();
+    let message = Message::new(bpf_loader_upgradeable::deploy_with_max_program_len(
+        &from_keypair.pubkey(),
+        &program_pubkey,
+        &buffer_pubkey,
+        &from_keypair.pubkey(),
+        1,
+        program.len() * 2,
+    ));
+    bank_client
+        .send_message(&[from_keypair, &program_keypair], message)
+        .unwrap();
+    program_pubkey
+}
+
+/// Replace the ELF of a program deployed by `load_upgradeable_program` with `program`
+pub fn upgrade_program(
+    bank_client: &BankClient,
+    from_keypair: &Keypair,
+    program_pubkey: &Pubkey,
+    program: Vec<u8>,
+) {
+    let buffer_pubkey = load_upgradeable_buffer(bank_client, from_keypair, &program);
+    let instruction =
+        bpf_loader_upgradeable::upgrade(program_pubkey, &buffer_pubkey, &from_keypair.pubkey());
+    bank_client
+        .send_instruction(&from_keypair, instruction)
+        .unwrap();
+}
+
 // Return an Instruction that invokes `program_id` with `data` and required
 // a signature from `from_pubkey`.
 pub fn create_invoke_instruction<T: Serialize>(
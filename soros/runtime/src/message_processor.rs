@@ -2,7 +2,8 @@ use crate::native_loader;
 use crate::system_instruction_processor;
 use soros_sdk::account::{create_keyed_accounts, Account, KeyedAccount};
 use soros_sdk::instruction::{CompiledInstruction, InstructionError};
-use soros_sdk::instruction_processor_utils;
+use soros_sdk::instruction_processor_utils::{Entrypoint, SetLogCollector};
+use soros_sdk::log_collector::{self, LogCollector};
 use soros_sdk::message::Message;
 use soros_sdk::pubkey::Pubkey;
 use soros_sdk::system_program;
@@ -85,7 +86,10 @@ fn verify_instruction(
 pub type ProcessInstruction =
     fn(&Pubkey, &mut [KeyedAccount], &[u8], u64) -> Result<(), InstructionError>;
 
-pub type SymbolCache = RwLock<HashMap<Vec<u8>, Symbol<instruction_processor_utils::Entrypoint>>>;
+/// A native program's entrypoint, and its log collector setter unless it predates them
+pub type NativeSymbols = (Symbol<Entrypoint>, Option<Symbol<SetLogCollector>>);
+
+pub type SymbolCache = RwLock<HashMap<Vec<u8>, NativeSymbols>>;
 
 pub struct MessageProcessor {
     instruction_processors: Vec<(Pubkey, ProcessInstruction)>,
@@ -126,6 +130,7 @@ impl MessageProcessor {
         executable_accounts: &mut [(Pubkey, Account)],
         program_accounts: &mut [&mut Account],
         tick_height: u64,
+        log_collector: Option<&LogCollector>,
     ) -> Result<(), InstructionError> {
         let program_id = instruction.program_id(message.program_ids());
 
@@ -145,12 +150,14 @@ impl MessageProcessor {
 
         for (id, process_instruction) in &self.instruction_processors {
             if id == program_id {
-                return process_instruction(
-                    &program_id,
-                    &mut keyed_accounts[1..],
-                    &instruction.data,
-                    tick_height,
-                );
+                return log_collector::with_log_collector(log_collector, || {
+                    process_instruction(
+                        &program_id,
+                        &mut keyed_accounts[1..],
+                        &instruction.data,
+                        tick_height,
+                    )
+                });
             }
         }
 
@@ -160,6 +167,7 @@ impl MessageProcessor {
             &instruction.data,
             tick_height,
             &self.symbol_cache,
+            log_collector,
         )
     }
 
@@ -174,6 +182,7 @@ impl MessageProcessor {
         executable_accounts: &mut [(Pubkey, Account)],
         program_accounts: &mut [&mut Account],
         tick_height: u64,
+        log_collector: Option<&LogCollector>,
     ) -> Result<(), InstructionError> {
         let program_id = instruction.program_id(message.program_ids());
         // TODO: the runtime should be checking read/write access to memory
@@ -191,6 +200,7 @@ impl MessageProcessor {
             executable_accounts,
             program_accounts,
             tick_height,
+            log_collector,
         )?;
 
         // Verify the instruction
@@ -217,25 +227,41 @@ impl MessageProcessor {
     /// Process a message.
     /// This method calls each instruction in the message over the set of loaded Accounts
     /// The accounts are committed back to the bank only if every instruction succeeds
+    /// If given a `log_collector`, the lines logged by the programs are collected into it
     pub fn process_message(
         &self,
         message: &Message,
         loaders: &mut [Vec<(Pubkey, Account)>],
         accounts: &mut [Account],
         tick_height: u64,
+        log_collector: Option<&LogCollector>,
     ) -> Result<(), TransactionError> {
         for (instruction_index, instruction) in message.instructions.iter().enumerate() {
+            let program_id = instruction.program_id(message.program_ids());
+            if let Some(log_collector) = log_collector {
+                log_collector.log(&format!("Program {} invoke", program_id));
+            }
             let executable_accounts = &mut loaders[instruction.program_ids_index as usize];
             let mut program_accounts = get_subset_unchecked_mut(accounts, &instruction.accounts)
                 .map_err(|err| TransactionError::InstructionError(instruction_index as u8, err))?;
-            self.execute_instruction(
+            let result = self.execute_instruction(
                 message,
                 instruction,
                 executable_accounts,
                 &mut program_accounts,
                 tick_height,
-            )
-            .map_err(|err| TransactionError::InstructionError(instruction_index as u8, err))?;
+                log_collector,
+            );
+            if let Some(log_collector) = log_collector {
+                match &result {
+                    Ok(()) => log_collector.log(&format!("Program {} success", program_id)),
+                    Err(err) => {
+                        log_collector.log(&format!("Program {} failed: {:?}", program_id, err))
+                    }
+                }
+            }
+            result
+                .map_err(|err| TransactionError::InstructionError(instruction_index as u8, err))?;
         }
         Ok(())
     }
@@ -244,6 +270,7 @@ impl MessageProcessor {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use soros_sdk::instruction::{AccountMeta, Instruction};
 
     #[test]
     fn test_has_duplicates() {
@@ -327,4 +354,68 @@ mod tests {
             "malicious Mallory should not be able to change the account data"
         );
     }
+
+    #[test]
+    fn test_process_message_log_collector() {
+        fn mock_process_instruction(
+            _program_id: &Pubkey,
+            _keyed_accounts: &mut [KeyedAccount],
+            data: &[u8],
+            _tick_height: u64,
+        ) -> Result<(), InstructionError> {
+            log_collector::program_log("mock log");
+            if data[0] == 0 {
+                Ok(())
+            } else {
+                Err(InstructionError::GenericError)
+            }
+        }
+
+        let program_id = Pubkey::new_rand();
+        let mut message_processor = MessageProcessor::default();
+        message_processor.add_instruction_processor(program_id, mock_process_instruction);
+
+        let process_message = |data: u8, log_collector: Option<&LogCollector>| {
+            let from = Pubkey::new_rand();
+            let account_metas = vec![AccountMeta::new(from, true)];
+            let instruction = Instruction::new(program_id, &data, account_metas);
+            let message = Message::new(vec![instruction]);
+            let mut loaders = vec![vec![(program_id, Account::default())]];
+            let mut accounts = vec![Account::new(1, 0, &system_program::id())];
+            message_processor.process_message(
+                &message,
+                &mut loaders,
+                &mut accounts,
+                0,
+                log_collector,
+            )
+        };
+
+        let log_collector = LogCollector::default();
+        assert_eq!(process_message(0, Some(&log_collector)), Ok(()));
+        assert_eq!(
+            log_collector.messages(),
+            vec![
+                format!("Program {} invoke", program_id),
+                "mock log".to_string(),
+                format!("Program {} success", program_id),
+            ]
+        );
+
+        let log_collector = LogCollector::default();
+        assert_eq!(
+            process_message(1, Some(&log_collector)),
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::GenericError
+            ))
+        );
+        assert_eq!(
+            log_collector.messages()[2],
+            format!("Program {} failed: GenericError", program_id)
+        );
+
+        // nothing to collect into
+        assert_eq!(process_message(0, None), Ok(()));
+    }
 }
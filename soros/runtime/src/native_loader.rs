@@ -1,5 +1,5 @@
 //! Native loader
-use crate::message_processor::SymbolCache;
+use crate::message_processor::{NativeSymbols, SymbolCache};
 use bincode::deserialize;
 #[cfg(unix)]
 use libloading::os::unix::*;
@@ -8,8 +8,9 @@ use libloading::os::windows::*;
 use log::*;
 use soros_sdk::account::KeyedAccount;
 use soros_sdk::instruction::InstructionError;
-use soros_sdk::instruction_processor_utils;
+use soros_sdk::instruction_processor_utils::{self, SetLogCollector};
 use soros_sdk::loader_instruction::LoaderInstruction;
+use soros_sdk::log_collector::{LogCollector, LogCollectorHandle};
 use soros_sdk::pubkey::Pubkey;
 use std::env;
 use std::path::PathBuf;
@@ -48,20 +49,65 @@ fn create_path(name: &str) -> PathBuf {
     }
 }
 
+// Puts the program's previous collector back even if the program panics
+struct RestoreLogCollector<'a> {
+    set_log_collector: &'a Symbol<SetLogCollector>,
+    previous: LogCollectorHandle,
+}
+
+impl<'a> Drop for RestoreLogCollector<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            (self.set_log_collector)(self.previous);
+        }
+    }
+}
+
+/// Call a native program, with the runtime's log collector installed in the program's copy of
+/// the sdk if there is one to install
+unsafe fn invoke(
+    symbols: &NativeSymbols,
+    program_id: &Pubkey,
+    params: &mut [KeyedAccount],
+    ix_data: &[u8],
+    tick_height: u64,
+    log_collector: Option<&LogCollector>,
+) -> Result<(), InstructionError> {
+    let (entrypoint, set_log_collector) = symbols;
+    match (set_log_collector, log_collector) {
+        (Some(set_log_collector), Some(log_collector)) => {
+            let _restore = RestoreLogCollector {
+                set_log_collector,
+                previous: set_log_collector(LogCollectorHandle::new(log_collector)),
+            };
+            entrypoint(program_id, params, ix_data, tick_height)
+        }
+        _ => entrypoint(program_id, params, ix_data, tick_height),
+    }
+}
+
 pub fn entrypoint(
     program_id: &Pubkey,
     keyed_accounts: &mut [KeyedAccount],
     ix_data: &[u8],
     tick_height: u64,
     symbol_cache: &SymbolCache,
+    log_collector: Option<&LogCollector>,
 ) -> Result<(), InstructionError> {
     if keyed_accounts[0].account.executable {
         // dispatch it
         let (names, params) = keyed_accounts.split_at_mut(1);
         let name_vec = &names[0].account.data;
-        if let Some(entrypoint) = symbol_cache.read().unwrap().get(name_vec) {
+        if let Some(symbols) = symbol_cache.read().unwrap().get(name_vec) {
             unsafe {
-                return entrypoint(program_id, params, ix_data, tick_height);
+                return invoke(
+                    symbols,
+                    program_id,
+                    params,
+                    ix_data,
+                    tick_height,
+                    log_collector,
+                );
             }
         }
         let name = match str::from_utf8(name_vec) {
@@ -88,11 +134,22 @@ pub fn entrypoint(
                             return Err(InstructionError::GenericError);
                         }
                     };
-                let ret = entrypoint(program_id, params, ix_data, tick_height);
+                let set_log_collector = library
+                    .get(instruction_processor_utils::SET_LOG_COLLECTOR.as_bytes())
+                    .ok();
+                let symbols = (entrypoint, set_log_collector);
+                let ret = invoke(
+                    &symbols,
+                    program_id,
+                    params,
+                    ix_data,
+                    tick_height,
+                    log_collector,
+                );
                 symbol_cache
                     .write()
                     .unwrap()
-                    .insert(name_vec.to_vec(), entrypoint);
+                    .insert(name_vec.to_vec(), symbols);
                 return ret;
             },
             Err(e) => {
@@ -11,6 +11,7 @@ pub type ForkStatus<T> = Vec<(ForkId, T)>;
 type SignatureMap<T> = HashMap<Signature, ForkStatus<T>>;
 type StatusMap<T> = HashMap<Hash, (ForkId, SignatureMap<T>)>;
 
+#[derive(Serialize, Deserialize, Clone)]
 pub struct StatusCache<T: Clone> {
     /// all signatures seen during a hash period
     cache: StatusMap<T>,
@@ -1,12 +1,17 @@
 use log::*;
 use soros_sdk::account::KeyedAccount;
+use soros_sdk::hash::Hash;
 use soros_sdk::instruction::InstructionError;
+use soros_sdk::instruction_processor_utils::State;
+use soros_sdk::nonce_state::NonceState;
 use soros_sdk::pubkey::Pubkey;
-use soros_sdk::system_instruction::{SystemError, SystemInstruction};
+use soros_sdk::system_instruction::{create_address_with_seed, SystemError, SystemInstruction};
 use soros_sdk::system_program;
 
 const FROM_ACCOUNT_INDEX: usize = 0;
 const TO_ACCOUNT_INDEX: usize = 1;
+const BASE_ACCOUNT_INDEX: usize = 2;
+const NONCE_ACCOUNT_INDEX: usize = 0;
 
 fn create_system_account(
     keyed_accounts: &mut [KeyedAccount],
@@ -49,10 +54,23 @@ fn create_system_account(
     Ok(())
 }
 
+/// Whether the account holds an initialized nonce, its dif only leave by withdrawal and it
+///  stays with the system program
+fn is_nonce_account(keyed_account: &KeyedAccount) -> bool {
+    match NonceState::from(&keyed_account.account) {
+        Some(NonceState::Initialized { .. }) => true,
+        _ => false,
+    }
+}
+
 fn assign_account_to_program(
     keyed_accounts: &mut [KeyedAccount],
     program_id: &Pubkey,
 ) -> Result<(), SystemError> {
+    if is_nonce_account(&keyed_accounts[FROM_ACCOUNT_INDEX]) {
+        debug!("Assign: account[from] is a nonce account");
+        Err(SystemError::AccountAlreadyInUse)?;
+    }
     keyed_accounts[FROM_ACCOUNT_INDEX].account.owner = *program_id;
     Ok(())
 }
@@ -62,6 +80,14 @@ fn transfer_dif(
     // lamports: u64,
     dif: u64,
 ) -> Result<(), SystemError> {
+    if is_nonce_account(&keyed_accounts[FROM_ACCOUNT_INDEX]) {
+        debug!("Transfer: account[from] is a nonce account");
+        Err(SystemError::AccountAlreadyInUse)?;
+    }
+    move_dif(keyed_accounts, dif)
+}
+
+fn move_dif(keyed_accounts: &mut [KeyedAccount], dif: u64) -> Result<(), SystemError> {
     // if lamports > keyed_accounts[FROM_ACCOUNT_INDEX].account.lamports {
     if dif > keyed_accounts[FROM_ACCOUNT_INDEX].account.dif {
         debug!(
@@ -79,18 +105,176 @@ fn transfer_dif(
     Ok(())
 }
 
+/// Whether `authority` signed among the instruction's accounts
+fn is_signed_by(keyed_accounts: &[KeyedAccount], authority: &Pubkey) -> bool {
+    keyed_accounts
+        .iter()
+        .any(|keyed_account| keyed_account.signer_key() == Some(authority))
+}
+
+/// Check that `address` is derived from `base`, `seed` and `program_id` and that `base` signed
+fn verify_address_with_seed(
+    keyed_accounts: &[KeyedAccount],
+    address: &Pubkey,
+    base: &Pubkey,
+    seed: &str,
+    program_id: &Pubkey,
+) -> Result<(), InstructionError> {
+    let expected_address = create_address_with_seed(base, seed, program_id)
+        .map_err(|e| InstructionError::CustomError(e as u32))?;
+    if *address != expected_address {
+        debug!(
+            "address {} doesn't match derived address {}",
+            address, expected_address
+        );
+        Err(InstructionError::CustomError(
+            SystemError::AddressWithSeedMismatch as u32,
+        ))?;
+    }
+    if !is_signed_by(keyed_accounts, base) {
+        debug!("base {} is unsigned", base);
+        Err(InstructionError::MissingRequiredSignature)?;
+    }
+    Ok(())
+}
+
+fn create_system_account_with_seed(
+    keyed_accounts: &mut [KeyedAccount],
+    base: &Pubkey,
+    seed: &str,
+    dif: u64,
+    space: u64,
+    program_id: &Pubkey,
+) -> Result<(), InstructionError> {
+    if keyed_accounts.len() < 2 {
+        Err(InstructionError::InvalidArgument)?;
+    }
+    let to = *keyed_accounts[TO_ACCOUNT_INDEX].unsigned_key();
+    verify_address_with_seed(keyed_accounts, &to, base, seed, program_id)?;
+    create_system_account(keyed_accounts, dif, space, program_id)
+        .map_err(|e| InstructionError::CustomError(e as u32))
+}
+
+fn transfer_dif_with_seed(
+    keyed_accounts: &mut [KeyedAccount],
+    dif: u64,
+    from_seed: &str,
+    from_owner: &Pubkey,
+) -> Result<(), InstructionError> {
+    if keyed_accounts.len() < 3 {
+        Err(InstructionError::InvalidArgument)?;
+    }
+    let from = *keyed_accounts[FROM_ACCOUNT_INDEX].unsigned_key();
+    let base = *keyed_accounts[BASE_ACCOUNT_INDEX].unsigned_key();
+    verify_address_with_seed(keyed_accounts, &from, &base, from_seed, from_owner)?;
+    transfer_dif(keyed_accounts, dif).map_err(|e| InstructionError::CustomError(e as u32))
+}
+
+/// The nonce account's authority and current nonce, it has to be initialized
+fn initialized_nonce(keyed_accounts: &[KeyedAccount]) -> Result<(Pubkey, Hash), InstructionError> {
+    if keyed_accounts.is_empty() {
+        Err(InstructionError::InvalidArgument)?;
+    }
+    match keyed_accounts[NONCE_ACCOUNT_INDEX].state()? {
+        NonceState::Initialized { authority, nonce } => {
+            if !is_signed_by(keyed_accounts, &authority) {
+                debug!("nonce authority {} is unsigned", authority);
+                Err(InstructionError::MissingRequiredSignature)?;
+            }
+            Ok((authority, nonce))
+        }
+        NonceState::Uninitialized => {
+            debug!("nonce account is not initialized");
+            Err(InstructionError::InvalidAccountData)
+        }
+    }
+}
+
+fn initialize_nonce_account(
+    keyed_accounts: &mut [KeyedAccount],
+    authority: &Pubkey,
+    tick_height: u64,
+) -> Result<(), InstructionError> {
+    let nonce_account = &mut keyed_accounts[NONCE_ACCOUNT_INDEX];
+    if !system_program::check_id(&nonce_account.account.owner)
+        || nonce_account.account.data.len() < NonceState::size() as usize
+    {
+        debug!("InitializeNonceAccount: not a system account with room for a nonce");
+        Err(InstructionError::InvalidArgument)?;
+    }
+    if let NonceState::Initialized { .. } = nonce_account.state()? {
+        Err(InstructionError::AccountAlreadyInitialized)?;
+    }
+    let nonce = NonceState::initial_nonce(nonce_account.unsigned_key(), tick_height);
+    nonce_account.set_state(&NonceState::Initialized {
+        authority: *authority,
+        nonce,
+    })
+}
+
+fn advance_nonce_account(
+    keyed_accounts: &mut [KeyedAccount],
+    tick_height: u64,
+) -> Result<(), InstructionError> {
+    let (authority, nonce) = initialized_nonce(keyed_accounts)?;
+    keyed_accounts[NONCE_ACCOUNT_INDEX].set_state(&NonceState::Initialized {
+        authority,
+        nonce: NonceState::next_nonce(&nonce, tick_height),
+    })
+}
+
+fn withdraw_nonce_account(
+    keyed_accounts: &mut [KeyedAccount],
+    dif: u64,
+) -> Result<(), InstructionError> {
+    if keyed_accounts.len() < 3 {
+        Err(InstructionError::InvalidArgument)?;
+    }
+    match keyed_accounts[NONCE_ACCOUNT_INDEX].state()? {
+        NonceState::Initialized { .. } => {
+            initialized_nonce(keyed_accounts)?;
+        }
+        // Nobody has been given authority yet, the account's own key has to sign
+        NonceState::Uninitialized => {
+            if keyed_accounts[NONCE_ACCOUNT_INDEX].signer_key().is_none() {
+                Err(InstructionError::MissingRequiredSignature)?;
+            }
+        }
+    }
+    move_dif(keyed_accounts, dif).map_err(|e| InstructionError::CustomError(e as u32))
+}
+
+fn authorize_nonce_account(
+    keyed_accounts: &mut [KeyedAccount],
+    new_authority: &Pubkey,
+) -> Result<(), InstructionError> {
+    let (_, nonce) = initialized_nonce(keyed_accounts)?;
+    keyed_accounts[NONCE_ACCOUNT_INDEX].set_state(&NonceState::Initialized {
+        authority: *new_authority,
+        nonce,
+    })
+}
+
 pub fn process_instruction(
     _program_id: &Pubkey,
     keyed_accounts: &mut [KeyedAccount],
     data: &[u8],
-    _tick_height: u64,
+    tick_height: u64,
 ) -> Result<(), InstructionError> {
     if let Ok(instruction) = bincode::deserialize(data) {
         trace!("process_instruction: {:?}", instruction);
         trace!("keyed_accounts: {:?}", keyed_accounts);
 
-        // All system instructions require that accounts_keys[0] be a signer
-        if keyed_accounts[FROM_ACCOUNT_INDEX].signer_key().is_none() {
+        // All system instructions require that accounts_keys[0] be a signer, except the ones
+        // a nonce account's authority or an address's base key signs for
+        let signed_by_authority = match instruction {
+            SystemInstruction::AdvanceNonceAccount
+            | SystemInstruction::WithdrawNonceAccount { .. }
+            | SystemInstruction::AuthorizeNonceAccount { .. }
+            | SystemInstruction::TransferWithSeed { .. } => true,
+            _ => false,
+        };
+        if !signed_by_authority && keyed_accounts[FROM_ACCOUNT_INDEX].signer_key().is_none() {
             debug!("account[from] is unsigned");
             Err(InstructionError::MissingRequiredSignature)?;
         }
@@ -102,17 +286,50 @@ pub fn process_instruction(
                 space,
                 program_id,
             // } => create_system_account(keyed_accounts, lamports, space, &program_id),
-            } => create_system_account(keyed_accounts, dif, space, &program_id),
+            } => create_system_account(keyed_accounts, dif, space, &program_id)
+                .map_err(|e| InstructionError::CustomError(e as u32)),
             SystemInstruction::Assign { program_id } => {
                 if !system_program::check_id(&keyed_accounts[FROM_ACCOUNT_INDEX].account.owner) {
                     Err(InstructionError::IncorrectProgramId)?;
                 }
                 assign_account_to_program(keyed_accounts, &program_id)
+                    .map_err(|e| InstructionError::CustomError(e as u32))
             }
             // SystemInstruction::Transfer { lamports } => transfer_lamports(keyed_accounts, lamports),
-            SystemInstruction::Transfer { dif } => transfer_dif(keyed_accounts, dif),
+            SystemInstruction::Transfer { dif } => transfer_dif(keyed_accounts, dif)
+                .map_err(|e| InstructionError::CustomError(e as u32)),
+            SystemInstruction::InitializeNonceAccount { authority } => {
+                initialize_nonce_account(keyed_accounts, &authority, tick_height)
+            }
+            SystemInstruction::AdvanceNonceAccount => {
+                advance_nonce_account(keyed_accounts, tick_height)
+            }
+            SystemInstruction::WithdrawNonceAccount { dif } => {
+                withdraw_nonce_account(keyed_accounts, dif)
+            }
+            SystemInstruction::AuthorizeNonceAccount { authority } => {
+                authorize_nonce_account(keyed_accounts, &authority)
+            }
+            SystemInstruction::CreateAccountWithSeed {
+                base,
+                seed,
+                dif,
+                space,
+                program_id,
+            } => create_system_account_with_seed(
+                keyed_accounts,
+                &base,
+                &seed,
+                dif,
+                space,
+                &program_id,
+            ),
+            SystemInstruction::TransferWithSeed {
+                dif,
+                from_seed,
+                from_owner,
+            } => transfer_dif_with_seed(keyed_accounts, dif, &from_seed, &from_owner),
         }
-        .map_err(|e| InstructionError::CustomError(e as u32))
     } else {
         debug!("Invalid instruction data: {:?}", data);
         Err(InstructionError::InvalidInstructionData)
@@ -129,7 +346,9 @@ mod tests {
     use soros_sdk::client::SyncClient;
     use soros_sdk::genesis_block::GenesisBlock;
     use soros_sdk::instruction::{AccountMeta, Instruction, InstructionError};
+    use soros_sdk::message::Message;
     use soros_sdk::signature::{Keypair, KeypairUtil};
+    use soros_sdk::system_instruction;
     use soros_sdk::system_program;
     use soros_sdk::transaction::TransactionError;
 
@@ -350,4 +569,273 @@ mod tests {
         assert_eq!(bank_client.get_balance(&alice_pubkey).unwrap(), 50);
         assert_eq!(bank_client.get_balance(&mallory_pubkey).unwrap(), 50);
     }
+
+    #[test]
+    fn test_account_with_seed() {
+        let (genesis_block, mint_keypair) = GenesisBlock::new(1_000);
+        let mint_pubkey = mint_keypair.pubkey();
+        let base_keypair = Keypair::new();
+        let base_pubkey = base_keypair.pubkey();
+        let bob_pubkey = Pubkey::new_rand();
+        let bank_client = BankClient::new(Bank::new(&genesis_block));
+        bank_client
+            .transfer(10, &mint_keypair, &base_pubkey)
+            .unwrap();
+
+        let program_id = system_program::id();
+        let derived_pubkey = create_address_with_seed(&base_pubkey, "seed", &program_id).unwrap();
+
+        // the address has to match the seed
+        let ix = system_instruction::create_account_with_seed(
+            &mint_pubkey,
+            &derived_pubkey,
+            &base_pubkey,
+            "other seed",
+            100,
+            0,
+            &program_id,
+        );
+        assert_eq!(
+            bank_client
+                .send_message(&[&mint_keypair, &base_keypair], Message::new(vec![ix]))
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::CustomError(SystemError::AddressWithSeedMismatch as u32)
+            )
+        );
+
+        let ix = system_instruction::create_account_with_seed(
+            &mint_pubkey,
+            &derived_pubkey,
+            &base_pubkey,
+            "seed",
+            100,
+            0,
+            &program_id,
+        );
+        bank_client
+            .send_message(&[&mint_keypair, &base_keypair], Message::new(vec![ix]))
+            .unwrap();
+        assert_eq!(bank_client.get_balance(&derived_pubkey).unwrap(), 100);
+
+        // only the base key signs for the derived account
+        let ix = system_instruction::transfer_with_seed(
+            &derived_pubkey,
+            &base_pubkey,
+            "seed",
+            &program_id,
+            &bob_pubkey,
+            30,
+        );
+        bank_client.send_instruction(&base_keypair, ix).unwrap();
+        assert_eq!(bank_client.get_balance(&derived_pubkey).unwrap(), 70);
+        assert_eq!(bank_client.get_balance(&bob_pubkey).unwrap(), 30);
+
+        // which it must
+        let ix = Instruction::new(
+            system_program::id(),
+            &SystemInstruction::TransferWithSeed {
+                dif: 30,
+                from_seed: "seed".to_string(),
+                from_owner: program_id,
+            },
+            vec![
+                AccountMeta::new(derived_pubkey, false),
+                AccountMeta::new(bob_pubkey, false),
+                AccountMeta::new(base_pubkey, false),
+            ],
+        );
+        let ixs = vec![
+            system_instruction::transfer(&mint_pubkey, &bob_pubkey, 1),
+            ix,
+        ];
+        assert_eq!(
+            bank_client
+                .send_message(&[&mint_keypair], Message::new(ixs))
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(1, InstructionError::MissingRequiredSignature)
+        );
+        assert_eq!(bank_client.get_balance(&derived_pubkey).unwrap(), 70);
+    }
+
+    fn get_nonce_state(bank_client: &BankClient, nonce_pubkey: &Pubkey) -> NonceState {
+        let data = bank_client.get_account_data(nonce_pubkey).unwrap().unwrap();
+        bincode::deserialize(&data).unwrap()
+    }
+
+    fn get_nonce(bank_client: &BankClient, nonce_pubkey: &Pubkey) -> Hash {
+        match get_nonce_state(bank_client, nonce_pubkey) {
+            NonceState::Initialized { nonce, .. } => nonce,
+            NonceState::Uninitialized => panic!("nonce account not initialized"),
+        }
+    }
+
+    #[test]
+    fn test_nonce_account() {
+        let (genesis_block, mint_keypair) = GenesisBlock::new(100);
+        let mint_pubkey = mint_keypair.pubkey();
+        let nonce_keypair = Keypair::new();
+        let nonce_pubkey = nonce_keypair.pubkey();
+        let authority_keypair = Keypair::new();
+        let authority_pubkey = authority_keypair.pubkey();
+        let bank_client = BankClient::new(Bank::new(&genesis_block));
+
+        let ixs = system_instruction::create_nonce_account(
+            &mint_pubkey,
+            &nonce_pubkey,
+            &authority_pubkey,
+            42,
+        );
+        bank_client
+            .send_message(&[&mint_keypair, &nonce_keypair], Message::new(ixs))
+            .unwrap();
+        let nonce = get_nonce(&bank_client, &nonce_pubkey);
+        assert_eq!(
+            get_nonce_state(&bank_client, &nonce_pubkey),
+            NonceState::Initialized {
+                authority: authority_pubkey,
+                nonce
+            }
+        );
+
+        // only once
+        let ix = Instruction::new(
+            system_program::id(),
+            &SystemInstruction::InitializeNonceAccount {
+                authority: mint_pubkey,
+            },
+            vec![AccountMeta::new(nonce_pubkey, true)],
+        );
+        assert_eq!(
+            bank_client
+                .send_message(&[&mint_keypair, &nonce_keypair], Message::new(vec![ix]))
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(0, InstructionError::AccountAlreadyInitialized)
+        );
+
+        // the authority has to sign an advance
+        let mut ix = system_instruction::advance_nonce_account(&nonce_pubkey, &authority_pubkey);
+        ix.accounts[1].is_signer = false;
+        assert_eq!(
+            bank_client
+                .send_instruction(&mint_keypair, ix)
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(0, InstructionError::MissingRequiredSignature)
+        );
+        assert_eq!(get_nonce(&bank_client, &nonce_pubkey), nonce);
+
+        let ix = system_instruction::advance_nonce_account(&nonce_pubkey, &authority_pubkey);
+        bank_client
+            .send_message(&[&mint_keypair, &authority_keypair], Message::new(vec![ix]))
+            .unwrap();
+        let advanced_nonce = get_nonce(&bank_client, &nonce_pubkey);
+        assert_ne!(advanced_nonce, nonce);
+
+        // hand it over to the mint, the old authority can't use it anymore
+        let ix = system_instruction::authorize_nonce_account(
+            &nonce_pubkey,
+            &authority_pubkey,
+            &mint_pubkey,
+        );
+        bank_client
+            .send_message(&[&mint_keypair, &authority_keypair], Message::new(vec![ix]))
+            .unwrap();
+        let to_pubkey = Pubkey::new_rand();
+        let ix = system_instruction::withdraw_nonce_account(
+            &nonce_pubkey,
+            &to_pubkey,
+            &authority_pubkey,
+            10,
+        );
+        assert_eq!(
+            bank_client
+                .send_message(&[&mint_keypair, &authority_keypair], Message::new(vec![ix]))
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(0, InstructionError::MissingRequiredSignature)
+        );
+
+        let ix =
+            system_instruction::withdraw_nonce_account(&nonce_pubkey, &to_pubkey, &mint_pubkey, 10);
+        bank_client.send_instruction(&mint_keypair, ix).unwrap();
+        assert_eq!(bank_client.get_balance(&nonce_pubkey).unwrap(), 32);
+        assert_eq!(bank_client.get_balance(&to_pubkey).unwrap(), 10);
+        assert_eq!(get_nonce(&bank_client, &nonce_pubkey), advanced_nonce);
+    }
+
+    #[test]
+    fn test_nonce_account_needs_room() {
+        let (genesis_block, mint_keypair) = GenesisBlock::new(100);
+        let nonce_keypair = Keypair::new();
+        let bank_client = BankClient::new(Bank::new(&genesis_block));
+        bank_client
+            .transfer(10, &mint_keypair, &nonce_keypair.pubkey())
+            .unwrap();
+
+        let ix = Instruction::new(
+            system_program::id(),
+            &SystemInstruction::InitializeNonceAccount {
+                authority: mint_keypair.pubkey(),
+            },
+            vec![AccountMeta::new(nonce_keypair.pubkey(), true)],
+        );
+        assert_eq!(
+            bank_client
+                .send_instruction(&nonce_keypair, ix)
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(0, InstructionError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_nonce_account_transfer_and_assign() {
+        let (genesis_block, mint_keypair) = GenesisBlock::new(100);
+        let nonce_keypair = Keypair::new();
+        let nonce_pubkey = nonce_keypair.pubkey();
+        let bank_client = BankClient::new(Bank::new(&genesis_block));
+
+        let ixs = system_instruction::create_nonce_account(
+            &mint_keypair.pubkey(),
+            &nonce_pubkey,
+            &mint_keypair.pubkey(),
+            42,
+        );
+        bank_client
+            .send_message(&[&mint_keypair, &nonce_keypair], Message::new(ixs))
+            .unwrap();
+        let nonce = get_nonce(&bank_client, &nonce_pubkey);
+        let already_in_use = TransactionError::InstructionError(
+            0,
+            InstructionError::CustomError(SystemError::AccountAlreadyInUse as u32),
+        );
+
+        // dif only leave a nonce account by withdrawal, even with the account's own signature
+        let to_pubkey = Pubkey::new_rand();
+        let ix = system_instruction::transfer(&nonce_pubkey, &to_pubkey, 10);
+        assert_eq!(
+            bank_client
+                .send_message(&[&mint_keypair, &nonce_keypair], Message::new(vec![ix]))
+                .unwrap_err()
+                .unwrap(),
+            already_in_use
+        );
+        assert_eq!(bank_client.get_balance(&nonce_pubkey).unwrap(), 42);
+
+        // and it can't be handed to another program
+        let ix = system_instruction::assign(&nonce_pubkey, &Pubkey::new_rand());
+        assert_eq!(
+            bank_client
+                .send_message(&[&mint_keypair, &nonce_keypair], Message::new(vec![ix]))
+                .unwrap_err()
+                .unwrap(),
+            already_in_use
+        );
+        assert_eq!(get_nonce(&bank_client, &nonce_pubkey), nonce);
+    }
 }
@@ -37,6 +37,7 @@ BIN_CRATES=(
   install
   keygen
   ledger-tool
+  local-cluster
   wallet
 )
 
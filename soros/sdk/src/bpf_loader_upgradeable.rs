@@ -0,0 +1,308 @@
+//! The upgradeable BPF loader keeps a program's ELF out of the program account, in a program data
+//! account the program account points at, so the ELF can be replaced without changing the
+//! program's address.  ELFs are written into buffer accounts first, a chunk per transaction, and
+//! copied from there into the program data account by `DeployWithMaxDataLen` and `Upgrade`.
+
+use crate::instruction::{AccountMeta, Instruction};
+use crate::pubkey::Pubkey;
+use crate::system_instruction;
+use bincode::serialized_size;
+
+const BPF_LOADER_UPGRADEABLE_PROGRAM_ID: [u8; 32] = [
+    2, 168, 246, 145, 78, 136, 161, 176, 226, 16, 21, 62, 247, 99, 174, 43, 0, 194, 185, 61, 22,
+    193, 36, 210, 192, 83, 122, 16, 4, 128, 0, 0,
+];
+
+pub fn id() -> Pubkey {
+    Pubkey::new(&BPF_LOADER_UPGRADEABLE_PROGRAM_ID)
+}
+
+pub fn check_id(program_id: &Pubkey) -> bool {
+    program_id.as_ref() == BPF_LOADER_UPGRADEABLE_PROGRAM_ID
+}
+
+/// Seed the program data account's address is derived from the program's with
+const PROGRAMDATA_SEED: &str = "programdata";
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum UpgradeableLoaderState {
+    /// Created by the system program and not yet used
+    Uninitialized,
+    /// Holds an ELF being written, the ELF follows the state
+    Buffer {
+        /// The only key allowed to write to the buffer or deploy from it
+        authority: Option<Pubkey>,
+    },
+    /// An executable program
+    Program { programdata_address: Pubkey },
+    /// Holds a program's ELF, the ELF follows the state
+    ProgramData {
+        /// Tick height of the bank the ELF was last deployed in
+        tick_height: u64,
+        /// The only key allowed to upgrade the program, the program can't be upgraded anymore
+        /// if `None`
+        upgrade_authority: Option<Pubkey>,
+    },
+}
+
+impl UpgradeableLoaderState {
+    /// Size of a buffer account's state, the ELF starts at this offset
+    pub fn buffer_metadata_size() -> usize {
+        serialized_size(&Self::Buffer {
+            authority: Some(Pubkey::default()),
+        })
+        .unwrap() as usize
+    }
+
+    /// Size of a buffer account holding an ELF of `program_len` bytes
+    pub fn buffer_len(program_len: usize) -> usize {
+        Self::buffer_metadata_size() + program_len
+    }
+
+    /// Size of a program account
+    pub fn program_len() -> usize {
+        serialized_size(&Self::Program {
+            programdata_address: Pubkey::default(),
+        })
+        .unwrap() as usize
+    }
+
+    /// Size of a program data account's state, the ELF starts at this offset
+    pub fn programdata_metadata_size() -> usize {
+        serialized_size(&Self::ProgramData {
+            tick_height: 0,
+            upgrade_authority: Some(Pubkey::default()),
+        })
+        .unwrap() as usize
+    }
+
+    /// Size of a program data account with room for an ELF of up to `max_data_len` bytes
+    pub fn programdata_len(max_data_len: usize) -> usize {
+        Self::programdata_metadata_size() + max_data_len
+    }
+}
+
+/// The address of `program_address`'s program data account
+pub fn get_programdata_address(program_address: &Pubkey) -> Pubkey {
+    system_instruction::create_address_with_seed(program_address, PROGRAMDATA_SEED, &id()).unwrap()
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum UpgradeableLoaderInstruction {
+    /// Initialize a buffer account created by the system program
+    ///
+    /// * key[0] - the buffer account
+    /// * key[1] - the buffer's authority
+    ///
+    /// The transaction must be signed by key[0], so no one else can claim a buffer created for
+    /// someone else
+    InitializeBuffer,
+
+    /// Write an ELF chunk into a buffer, `offset` is relative to the start of the ELF
+    ///
+    /// * key[0] - the buffer account
+    /// * key[1] - the buffer's authority
+    ///
+    /// The transaction must be signed by key[1]
+    Write { offset: u32, bytes: Vec<u8> },
+
+    /// Deploy the ELF in a buffer as a new program.  The program and program data accounts must
+    /// have been created by the system program with room for the program's state and an ELF of
+    /// up to `max_data_len` bytes.  The buffer's dif goes to the program data account and its
+    /// authority becomes the program's upgrade authority.
+    ///
+    /// * key[0] - the program data account
+    /// * key[1] - the program account
+    /// * key[2] - the buffer account
+    /// * key[3] - the buffer's authority
+    ///
+    /// The transaction must be signed by key[1] and key[3]
+    DeployWithMaxDataLen { max_data_len: u64 },
+
+    /// Replace a program's ELF with the one in a buffer.  The buffer's dif goes to the program
+    /// data account.
+    ///
+    /// * key[0] - the program data account
+    /// * key[1] - the program account
+    /// * key[2] - the buffer account
+    /// * key[3] - the program's upgrade authority, which must also be the buffer's authority
+    ///
+    /// The transaction must be signed by key[3]
+    Upgrade,
+
+    /// Hand a buffer or program over to a new authority, or without a new authority make a
+    /// program immutable
+    ///
+    /// * key[0] - the buffer or program data account
+    /// * key[1] - the current authority
+    /// * key[2] - the new authority, if any
+    ///
+    /// The transaction must be signed by key[1]
+    SetAuthority,
+}
+
+/// Create and initialize a buffer for an ELF of `program_len` bytes
+pub fn create_buffer(
+    payer_id: &Pubkey,
+    buffer_id: &Pubkey,
+    authority_id: &Pubkey,
+    dif: u64,
+    program_len: usize,
+) -> Vec<Instruction> {
+    vec![
+        system_instruction::create_account(
+            payer_id,
+            buffer_id,
+            dif,
+            UpgradeableLoaderState::buffer_len(program_len) as u64,
+            &id(),
+        ),
+        Instruction::new(
+            id(),
+            &UpgradeableLoaderInstruction::InitializeBuffer,
+            vec![
+                AccountMeta::new(*buffer_id, true),
+                AccountMeta::new(*authority_id, false),
+            ],
+        ),
+    ]
+}
+
+pub fn write(
+    buffer_id: &Pubkey,
+    authority_id: &Pubkey,
+    offset: u32,
+    bytes: Vec<u8>,
+) -> Instruction {
+    Instruction::new(
+        id(),
+        &UpgradeableLoaderInstruction::Write { offset, bytes },
+        vec![
+            AccountMeta::new(*buffer_id, false),
+            AccountMeta::new(*authority_id, true),
+        ],
+    )
+}
+
+/// Create the program and program data accounts and deploy the ELF in `buffer_id` to them.  The
+/// program data account is derived from the program's address, so the program must sign too.
+pub fn deploy_with_max_program_len(
+    payer_id: &Pubkey,
+    program_id: &Pubkey,
+    buffer_id: &Pubkey,
+    authority_id: &Pubkey,
+    dif: u64,
+    max_data_len: usize,
+) -> Vec<Instruction> {
+    let programdata_id = get_programdata_address(program_id);
+    vec![
+        system_instruction::create_account(
+            payer_id,
+            program_id,
+            dif,
+            UpgradeableLoaderState::program_len() as u64,
+            &id(),
+        ),
+        system_instruction::create_account_with_seed(
+            payer_id,
+            &programdata_id,
+            program_id,
+            PROGRAMDATA_SEED,
+            dif,
+            UpgradeableLoaderState::programdata_len(max_data_len) as u64,
+            &id(),
+        ),
+        Instruction::new(
+            id(),
+            &UpgradeableLoaderInstruction::DeployWithMaxDataLen {
+                max_data_len: max_data_len as u64,
+            },
+            vec![
+                AccountMeta::new(programdata_id, false),
+                AccountMeta::new(*program_id, true),
+                AccountMeta::new(*buffer_id, false),
+                AccountMeta::new(*authority_id, true),
+            ],
+        ),
+    ]
+}
+
+pub fn upgrade(program_id: &Pubkey, buffer_id: &Pubkey, authority_id: &Pubkey) -> Instruction {
+    Instruction::new(
+        id(),
+        &UpgradeableLoaderInstruction::Upgrade,
+        vec![
+            AccountMeta::new(get_programdata_address(program_id), false),
+            AccountMeta::new(*program_id, false),
+            AccountMeta::new(*buffer_id, false),
+            AccountMeta::new(*authority_id, true),
+        ],
+    )
+}
+
+pub fn set_buffer_authority(
+    buffer_id: &Pubkey,
+    current_authority_id: &Pubkey,
+    new_authority_id: &Pubkey,
+) -> Instruction {
+    Instruction::new(
+        id(),
+        &UpgradeableLoaderInstruction::SetAuthority,
+        vec![
+            AccountMeta::new(*buffer_id, false),
+            AccountMeta::new(*current_authority_id, true),
+            AccountMeta::new(*new_authority_id, false),
+        ],
+    )
+}
+
+/// Hand the program over to `new_authority_id`, or make it immutable if `None`
+pub fn set_upgrade_authority(
+    program_id: &Pubkey,
+    current_authority_id: &Pubkey,
+    new_authority_id: Option<&Pubkey>,
+) -> Instruction {
+    let mut account_metas = vec![
+        AccountMeta::new(get_programdata_address(program_id), false),
+        AccountMeta::new(*current_authority_id, true),
+    ];
+    if let Some(new_authority_id) = new_authority_id {
+        account_metas.push(AccountMeta::new(*new_authority_id, false));
+    }
+    Instruction::new(
+        id(),
+        &UpgradeableLoaderInstruction::SetAuthority,
+        account_metas,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metadata_sizes() {
+        assert_eq!(UpgradeableLoaderState::buffer_metadata_size(), 37);
+        assert_eq!(UpgradeableLoaderState::program_len(), 36);
+        assert_eq!(UpgradeableLoaderState::programdata_metadata_size(), 45);
+        assert_eq!(UpgradeableLoaderState::buffer_len(10), 47);
+        assert_eq!(UpgradeableLoaderState::programdata_len(10), 55);
+    }
+
+    #[test]
+    fn test_uninitialized_is_zeroed() {
+        // The system program zeroes the accounts it creates
+        let state: UpgradeableLoaderState = bincode::deserialize(&[0u8; 36]).unwrap();
+        assert_eq!(state, UpgradeableLoaderState::Uninitialized);
+    }
+
+    #[test]
+    fn test_programdata_address() {
+        let program_id = Pubkey::new_rand();
+        assert_eq!(
+            get_programdata_address(&program_id),
+            get_programdata_address(&program_id)
+        );
+        assert_ne!(get_programdata_address(&program_id), program_id);
+    }
+}
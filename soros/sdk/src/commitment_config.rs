@@ -0,0 +1,58 @@
+//! How settled the state an RPC read is served from has to be
+//! * `Recent`, the node's most recent bank, which may be on a fork the cluster abandons
+//! * `Confirmed`, the most recent bank a supermajority of the stake is locked out on
+//! * `Root`, the most recent bank the node has rooted, it can't be rolled back
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum CommitmentLevel {
+    Recent,
+    Confirmed,
+    Root,
+}
+
+impl Default for CommitmentLevel {
+    fn default() -> Self {
+        CommitmentLevel::Recent
+    }
+}
+
+/// Optional parameter of the RPC read methods and subscriptions, `{"commitment": "root"}`
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CommitmentConfig {
+    pub commitment: CommitmentLevel,
+}
+
+impl CommitmentConfig {
+    pub fn recent() -> Self {
+        Self::new(CommitmentLevel::Recent)
+    }
+
+    pub fn confirmed() -> Self {
+        Self::new(CommitmentLevel::Confirmed)
+    }
+
+    pub fn root() -> Self {
+        Self::new(CommitmentLevel::Root)
+    }
+
+    pub fn new(commitment: CommitmentLevel) -> Self {
+        Self { commitment }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commitment_config_json() {
+        let config: CommitmentConfig = serde_json::from_str(r#"{"commitment":"root"}"#).unwrap();
+        assert_eq!(config, CommitmentConfig::root());
+        assert_eq!(
+            serde_json::to_string(&CommitmentConfig::confirmed()).unwrap(),
+            r#"{"commitment":"confirmed"}"#
+        );
+        assert_eq!(CommitmentConfig::default(), CommitmentConfig::recent());
+    }
+}
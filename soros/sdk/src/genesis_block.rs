@@ -14,6 +14,32 @@ use std::path::Path;
 // pub const BOOTSTRAP_LEADER_LAMPORTS: u64 = 2;
 pub const BOOTSTRAP_LEADER_DIF: u64 = 2;
 
+// The dif the mint pays to create the network config accounts:
+// * 1 dif for the stake config account
+// * 1 dif each for the fee, stake reward and storage reward cluster config accounts
+pub const GENESIS_CONFIG_DIF: u64 = 4;
+
+/// Annual rate at which delegated stake earns rewards
+pub const DEFAULT_STAKE_REWARD_TARGET_RATE: f64 = 0.20;
+
+/// Fraction of a stake delegation that becomes effective each epoch, 4 epochs to warm up
+pub const DEFAULT_STAKE_WARMUP_RATE: f64 = 0.25;
+
+/// Fraction of a deactivated stake delegation that stops being effective each epoch
+pub const DEFAULT_STAKE_COOLDOWN_RATE: f64 = 0.25;
+
+fn default_stake_reward_target_rate() -> f64 {
+    DEFAULT_STAKE_REWARD_TARGET_RATE
+}
+
+fn default_stake_warmup_rate() -> f64 {
+    DEFAULT_STAKE_WARMUP_RATE
+}
+
+fn default_stake_cooldown_rate() -> f64 {
+    DEFAULT_STAKE_COOLDOWN_RATE
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GenesisBlock {
     pub bootstrap_leader_id: Pubkey,
@@ -27,6 +53,13 @@ pub struct GenesisBlock {
     pub slots_per_epoch: u64,
     pub stakers_slot_offset: u64,
     pub epoch_warmup: bool,
+    // Genesis blocks written before stake rates were configurable use the defaults
+    #[serde(default = "default_stake_reward_target_rate")]
+    pub stake_reward_target_rate: f64,
+    #[serde(default = "default_stake_warmup_rate")]
+    pub stake_warmup_rate: f64,
+    #[serde(default = "default_stake_cooldown_rate")]
+    pub stake_cooldown_rate: f64,
     pub native_instruction_processors: Vec<(String, Pubkey)>,
 }
 
@@ -51,6 +84,8 @@ impl GenesisBlock {
         // bootstrap_leader_lamports: u64,
         bootstrap_leader_dif: u64,
     ) -> (Self, Keypair) {
+        // the mint is left with the dif it was given once it has funded the config accounts
+        let dif = dif.checked_add(GENESIS_CONFIG_DIF).unwrap_or(dif);
         let mint_keypair = Keypair::new();
         let bootstrap_leader_vote_account_keypair = Keypair::new();
         (
@@ -66,6 +101,9 @@ impl GenesisBlock {
                 slots_per_epoch: DEFAULT_SLOTS_PER_EPOCH,
                 stakers_slot_offset: DEFAULT_SLOTS_PER_EPOCH,
                 epoch_warmup: true,
+                stake_reward_target_rate: DEFAULT_STAKE_REWARD_TARGET_RATE,
+                stake_warmup_rate: DEFAULT_STAKE_WARMUP_RATE,
+                stake_cooldown_rate: DEFAULT_STAKE_COOLDOWN_RATE,
                 native_instruction_processors: vec![],
             },
             mint_keypair,
@@ -98,7 +136,10 @@ mod tests {
     fn test_genesis_block_new() {
         let (genesis_block, mint) = GenesisBlock::new(10_000);
         // assert_eq!(genesis_block.lamports, 10_000 + BOOTSTRAP_LEADER_LAMPORTS);
-        assert_eq!(genesis_block.dif, 10_000 + BOOTSTRAP_LEADER_DIF);
+        assert_eq!(
+            genesis_block.dif,
+            10_000 + BOOTSTRAP_LEADER_DIF + GENESIS_CONFIG_DIF
+        );
         assert_eq!(genesis_block.mint_id, mint.pubkey());
         assert!(genesis_block.bootstrap_leader_id != Pubkey::default());
         assert!(genesis_block.bootstrap_leader_vote_account_id != Pubkey::default());
@@ -117,10 +158,31 @@ mod tests {
             GenesisBlock::new_with_leader(20_000, &leader_keypair.pubkey(), 123);
 
         // assert_eq!(genesis_block.lamports, 20_000);
-        assert_eq!(genesis_block.dif, 20_000);
+        assert_eq!(genesis_block.dif, 20_000 + GENESIS_CONFIG_DIF);
         assert_eq!(genesis_block.mint_id, mint.pubkey());
         assert_eq!(genesis_block.bootstrap_leader_id, leader_keypair.pubkey());
         // assert_eq!(genesis_block.bootstrap_leader_lamports, 123);
         assert_eq!(genesis_block.bootstrap_leader_dif, 123);
     }
+
+    #[test]
+    fn test_genesis_block_without_stake_rates() {
+        let (mut genesis_block, _mint) = GenesisBlock::new(10_000);
+        genesis_block.stake_warmup_rate = 0.5;
+        let mut value = serde_json::to_value(&genesis_block).unwrap();
+        let fields = value.as_object_mut().unwrap();
+        fields.remove("stake_reward_target_rate");
+        fields.remove("stake_cooldown_rate");
+
+        let genesis_block: GenesisBlock = serde_json::from_value(value).unwrap();
+        assert_eq!(
+            genesis_block.stake_reward_target_rate,
+            DEFAULT_STAKE_REWARD_TARGET_RATE
+        );
+        assert_eq!(genesis_block.stake_warmup_rate, 0.5);
+        assert_eq!(
+            genesis_block.stake_cooldown_rate,
+            DEFAULT_STAKE_COOLDOWN_RATE
+        );
+    }
 }
@@ -1,5 +1,6 @@
 use crate::account::{Account, KeyedAccount};
 use crate::instruction::InstructionError;
+use crate::log_collector::LogCollectorHandle;
 use crate::pubkey::Pubkey;
 use bincode::ErrorKind;
 use num_traits::FromPrimitive;
@@ -15,6 +16,13 @@ pub type Entrypoint = unsafe extern "C" fn(
     tick_height: u64,
 ) -> Result<(), InstructionError>;
 
+// Native programs also export a symbol that installs the runtime's log collector
+pub const SET_LOG_COLLECTOR: &str = "set_log_collector";
+
+// Prototype of the SET_LOG_COLLECTOR symbol, see `log_collector::set_log_collector`
+pub type SetLogCollector =
+    unsafe extern "C" fn(log_collector: LogCollectorHandle) -> LogCollectorHandle;
+
 // Convenience macro to define the native program entrypoint.  Supply a fn to this macro that
 // conforms to the `Entrypoint` type signature.
 #[macro_export]
@@ -29,6 +37,13 @@ macro_rules! soros_entrypoint(
         ) -> Result<(), soros_sdk::instruction::InstructionError> {
             $entrypoint(program_id, keyed_accounts, data, tick_height)
         }
+
+        #[no_mangle]
+        pub extern "C" fn set_log_collector(
+            log_collector: soros_sdk::log_collector::LogCollectorHandle
+        ) -> soros_sdk::log_collector::LogCollectorHandle {
+            soros_sdk::log_collector::set_log_collector(log_collector)
+        }
     )
 );
 
@@ -1,14 +1,20 @@
 pub mod account;
 pub mod bpf_loader;
+pub mod bpf_loader_upgradeable;
 pub mod client;
+pub mod commitment_config;
+pub mod epoch_schedule;
 pub mod fee_calculator;
 pub mod genesis_block;
 pub mod hash;
 pub mod instruction;
 pub mod instruction_processor_utils;
 pub mod loader_instruction;
+pub mod log_collector;
 pub mod message;
+pub mod multi_sha256;
 pub mod native_loader;
+pub mod nonce_state;
 pub mod packet;
 pub mod pubkey;
 pub mod rpc_port;
@@ -0,0 +1,163 @@
+//! Program logs
+//! * programs report what they did with `program_log`, the lines always go to the debug log
+//! * the runtime can hand a `LogCollector` to the programs processing a message, e.g. to
+//!   return the lines of a simulated transaction to an RPC client
+
+use log::*;
+use std::cell::{Cell, RefCell};
+use std::os::raw::c_void;
+use std::{ptr, slice};
+
+/// Most lines collected per message, a runaway program shouldn't exhaust memory
+pub const MAX_LOG_MESSAGES: usize = 1_000;
+
+#[derive(Debug, Default)]
+pub struct LogCollector {
+    messages: RefCell<Vec<String>>,
+}
+
+impl LogCollector {
+    pub fn log(&self, message: &str) {
+        let mut messages = self.messages.borrow_mut();
+        if messages.len() < MAX_LOG_MESSAGES {
+            messages.push(message.to_string());
+        } else if messages.len() == MAX_LOG_MESSAGES {
+            messages.push("Log truncated".to_string());
+        }
+    }
+
+    pub fn messages(&self) -> Vec<String> {
+        self.messages.borrow().clone()
+    }
+}
+
+/// A `LogCollector` as programs see it. Native programs are built against their own copy of
+/// the sdk, so they get a C layout handle that calls back into the runtime's copy to log
+/// rather than a pointer to a `LogCollector` whose layout they can't rely on.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct LogCollectorHandle {
+    collector: *const c_void,
+    log: Option<extern "C" fn(collector: *const c_void, message: *const u8, len: usize)>,
+}
+
+impl Default for LogCollectorHandle {
+    fn default() -> Self {
+        Self {
+            collector: ptr::null(),
+            log: None,
+        }
+    }
+}
+
+impl LogCollectorHandle {
+    /// The handle must not be used after `log_collector` is dropped
+    pub fn new(log_collector: &LogCollector) -> Self {
+        Self {
+            collector: log_collector as *const LogCollector as *const c_void,
+            log: Some(log_to_collector),
+        }
+    }
+
+    fn log(&self, message: &str) {
+        if let Some(log) = self.log {
+            log(self.collector, message.as_ptr(), message.len());
+        }
+    }
+}
+
+extern "C" fn log_to_collector(collector: *const c_void, message: *const u8, len: usize) {
+    // Only called through a `LogCollectorHandle` made by `LogCollectorHandle::new`
+    let (collector, message) = unsafe {
+        (
+            &*(collector as *const LogCollector),
+            slice::from_raw_parts(message, len),
+        )
+    };
+    collector.log(&String::from_utf8_lossy(message));
+}
+
+thread_local!(
+    static LOG_COLLECTOR: Cell<LogCollectorHandle> = Cell::new(LogCollectorHandle::default())
+);
+
+/// Install `log_collector` for the current thread, returns the one it replaces. Native
+/// programs have their own copy of this thread local, so they export this function for the
+/// runtime to call, see `soros_entrypoint!`
+pub fn set_log_collector(log_collector: LogCollectorHandle) -> LogCollectorHandle {
+    LOG_COLLECTOR.with(|current| current.replace(log_collector))
+}
+
+// Puts the previous collector back even if the program panics
+struct LogCollectorGuard(LogCollectorHandle);
+
+impl Drop for LogCollectorGuard {
+    fn drop(&mut self) {
+        set_log_collector(self.0);
+    }
+}
+
+/// Collect the lines programs log while `f` runs into `log_collector`
+pub fn with_log_collector<T, F>(log_collector: Option<&LogCollector>, f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    let log_collector =
+        log_collector.map_or_else(LogCollectorHandle::default, LogCollectorHandle::new);
+    let _guard = LogCollectorGuard(set_log_collector(log_collector));
+    f()
+}
+
+/// Log a line from a program
+pub fn program_log(message: &str) {
+    debug!("Program log: {}", message);
+    // Only set while the runtime's `LogCollector` is alive, see `with_log_collector`
+    LOG_COLLECTOR.with(|current| current.get().log(message));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic::{self, AssertUnwindSafe};
+
+    #[test]
+    fn test_with_log_collector() {
+        program_log("not collected");
+
+        let log_collector = LogCollector::default();
+        let result = with_log_collector(Some(&log_collector), || {
+            program_log("one");
+            program_log("two");
+            42
+        });
+        assert_eq!(result, 42);
+        program_log("not collected either");
+        assert_eq!(log_collector.messages(), vec!["one", "two"]);
+
+        with_log_collector(None, || program_log("dropped"));
+        assert_eq!(log_collector.messages().len(), 2);
+    }
+
+    #[test]
+    fn test_with_log_collector_panic() {
+        let log_collector = LogCollector::default();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            with_log_collector(Some(&log_collector), || panic!("program panicked"))
+        }));
+        assert!(result.is_err());
+
+        program_log("not collected");
+        assert!(log_collector.messages().is_empty());
+    }
+
+    #[test]
+    fn test_log_collector_limit() {
+        let log_collector = LogCollector::default();
+        for _ in 0..MAX_LOG_MESSAGES + 10 {
+            log_collector.log("x");
+        }
+        let messages = log_collector.messages();
+        assert_eq!(messages.len(), MAX_LOG_MESSAGES + 1);
+        assert_eq!(messages.last().unwrap(), "Log truncated");
+    }
+}
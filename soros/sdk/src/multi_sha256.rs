@@ -0,0 +1,255 @@
+//! The `multi_sha256` module hashes many PoH chains at once.  Every step of a PoH chain is the
+//! SHA-256 of the previous 32 byte hash, which always fits in a single block, so `LANES` chains
+//! are laid out side by side and their blocks are compressed in lockstep, one lane per chain,
+//! letting the compiler turn each round into SIMD instructions.
+
+use crate::hash::{hash, Hash};
+
+/// Number of chains hashed in lockstep
+pub const LANES: usize = 8;
+
+type Lanes = [u32; LANES];
+
+const IV: [u32; 8] = [
+    0x6a09_e667,
+    0xbb67_ae85,
+    0x3c6e_f372,
+    0xa54f_f53a,
+    0x510e_527f,
+    0x9b05_688c,
+    0x1f83_d9ab,
+    0x5be0_cd19,
+];
+
+#[rustfmt::skip]
+const K: [u32; 64] = [
+    0x428a_2f98, 0x7137_4491, 0xb5c0_fbcf, 0xe9b5_dba5, 0x3956_c25b, 0x59f1_11f1, 0x923f_82a4,
+    0xab1c_5ed5, 0xd807_aa98, 0x1283_5b01, 0x2431_85be, 0x550c_7dc3, 0x72be_5d74, 0x80de_b1fe,
+    0x9bdc_06a7, 0xc19b_f174, 0xe49b_69c1, 0xefbe_4786, 0x0fc1_9dc6, 0x240c_a1cc, 0x2de9_2c6f,
+    0x4a74_84aa, 0x5cb0_a9dc, 0x76f9_88da, 0x983e_5152, 0xa831_c66d, 0xb003_27c8, 0xbf59_7fc7,
+    0xc6e0_0bf3, 0xd5a7_9147, 0x06ca_6351, 0x1429_2967, 0x27b7_0a85, 0x2e1b_2138, 0x4d2c_6dfc,
+    0x5338_0d13, 0x650a_7354, 0x766a_0abb, 0x81c2_c92e, 0x9272_2c85, 0xa2bf_e8a1, 0xa81a_664b,
+    0xc24b_8b70, 0xc76c_51a3, 0xd192_e819, 0xd699_0624, 0xf40e_3585, 0x106a_a070, 0x19a4_c116,
+    0x1e37_6c08, 0x2748_774c, 0x34b0_bcb5, 0x391c_0cb3, 0x4ed8_aa4a, 0x5b9c_ca4f, 0x682e_6ff3,
+    0x748f_82ee, 0x78a5_636f, 0x84c8_7814, 0x8cc7_0208, 0x90be_fffa, 0xa450_6ceb, 0xbef9_a3f7,
+    0xc671_78f2,
+];
+
+/// Replace the 32 byte message in every lane with its SHA-256
+#[allow(clippy::needless_range_loop)]
+#[inline(always)]
+fn hash_lanes(message: &mut [Lanes; 8]) {
+    // The message is followed by the padding bit and its length, 256 bits
+    let mut w = [[0u32; LANES]; 64];
+    w[..8].copy_from_slice(message);
+    w[8] = [0x8000_0000; LANES];
+    w[15] = [256; LANES];
+    for t in 16..64 {
+        for l in 0..LANES {
+            let w15 = w[t - 15][l];
+            let w2 = w[t - 2][l];
+            let s0 = w15.rotate_right(7) ^ w15.rotate_right(18) ^ (w15 >> 3);
+            let s1 = w2.rotate_right(17) ^ w2.rotate_right(19) ^ (w2 >> 10);
+            w[t][l] = w[t - 16][l]
+                .wrapping_add(s0)
+                .wrapping_add(w[t - 7][l])
+                .wrapping_add(s1);
+        }
+    }
+
+    let mut s = [[0u32; LANES]; 8];
+    for (s, iv) in s.iter_mut().zip(IV.iter()) {
+        *s = [*iv; LANES];
+    }
+    for (k, w) in K.iter().zip(w.iter()) {
+        let [a, b, c, d, e, f, g, h] = s;
+        let mut t1 = [0u32; LANES];
+        let mut t2 = [0u32; LANES];
+        for l in 0..LANES {
+            let s1 = e[l].rotate_right(6) ^ e[l].rotate_right(11) ^ e[l].rotate_right(25);
+            let ch = (e[l] & f[l]) ^ (!e[l] & g[l]);
+            t1[l] = h[l]
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(*k)
+                .wrapping_add(w[l]);
+            let s0 = a[l].rotate_right(2) ^ a[l].rotate_right(13) ^ a[l].rotate_right(22);
+            let maj = (a[l] & b[l]) ^ (a[l] & c[l]) ^ (b[l] & c[l]);
+            t2[l] = s0.wrapping_add(maj);
+        }
+        for l in 0..LANES {
+            s[7][l] = g[l];
+            s[6][l] = f[l];
+            s[5][l] = e[l];
+            s[4][l] = d[l].wrapping_add(t1[l]);
+            s[3][l] = c[l];
+            s[2][l] = b[l];
+            s[1][l] = a[l];
+            s[0][l] = t1[l].wrapping_add(t2[l]);
+        }
+    }
+
+    for (message, (s, iv)) in message.iter_mut().zip(s.iter().zip(IV.iter())) {
+        for l in 0..LANES {
+            message[l] = s[l].wrapping_add(*iv);
+        }
+    }
+}
+
+#[inline(always)]
+fn hash_lanes_n(message: &mut [Lanes; 8], num_hashes: u64) {
+    for _ in 0..num_hashes {
+        hash_lanes(message);
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn hash_lanes_n_avx2(message: &mut [Lanes; 8], num_hashes: u64) {
+    hash_lanes_n(message, num_hashes)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn hash_lanes_n_simd(message: &mut [Lanes; 8], num_hashes: u64) {
+    if is_x86_feature_detected!("avx2") {
+        unsafe { hash_lanes_n_avx2(message, num_hashes) }
+    } else {
+        // SSE2 is always there
+        hash_lanes_n(message, num_hashes)
+    }
+}
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+fn hash_lanes_n_simd(message: &mut [Lanes; 8], num_hashes: u64) {
+    // NEON is always there on aarch64, elsewhere this is never called
+    hash_lanes_n(message, num_hashes)
+}
+
+/// True if chains are hashed in lockstep, otherwise each is hashed on its own
+pub fn simd_enabled() -> bool {
+    cfg!(any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "aarch64"
+    ))
+}
+
+fn load_lane(message: &mut [Lanes; 8], lane: usize, hash: &Hash) {
+    for (word, bytes) in message.iter_mut().zip(hash.as_ref().chunks(4)) {
+        word[lane] = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+}
+
+fn store_lane(message: &[Lanes; 8], lane: usize) -> Hash {
+    let mut bytes = [0u8; 32];
+    for (word, bytes) in message.iter().zip(bytes.chunks_mut(4)) {
+        bytes.copy_from_slice(&word[lane].to_be_bytes());
+    }
+    Hash::new(&bytes)
+}
+
+/// Hash `start_hash` `num_hashes` times, one hash at a time
+pub fn hash_chain(start_hash: &Hash, num_hashes: u64) -> Hash {
+    let mut current = *start_hash;
+    for _ in 0..num_hashes {
+        current = hash(current.as_ref());
+    }
+    current
+}
+
+/// Hash each of `start_hashes` the matching number of times in `num_hashes`.  A lane that
+/// finishes its chain picks up the next one, so chains of different lengths keep all lanes busy.
+pub fn hash_chains(start_hashes: &[Hash], num_hashes: &[u64]) -> Vec<Hash> {
+    assert_eq!(start_hashes.len(), num_hashes.len());
+    if !simd_enabled() {
+        return start_hashes
+            .iter()
+            .zip(num_hashes)
+            .map(|(start_hash, num_hashes)| hash_chain(start_hash, *num_hashes))
+            .collect();
+    }
+
+    let mut results = start_hashes.to_vec();
+    let mut pending = (0..start_hashes.len()).filter(|i| num_hashes[*i] > 0);
+    let mut message = [[0u32; LANES]; 8];
+    // Chain and number of hashes left in each lane
+    let mut lanes: [Option<(usize, u64)>; LANES] = [None; LANES];
+    loop {
+        for (lane, chain) in lanes.iter_mut().enumerate() {
+            if chain.is_none() {
+                *chain = pending.next().map(|i| {
+                    load_lane(&mut message, lane, &start_hashes[i]);
+                    (i, num_hashes[i])
+                });
+            }
+        }
+
+        let active: Vec<(usize, usize, u64)> = lanes
+            .iter()
+            .enumerate()
+            .filter_map(|(lane, chain)| chain.map(|(i, left)| (lane, i, left)))
+            .collect();
+        match active.len() {
+            0 => break,
+            1 => {
+                // Nothing to run alongside the last chain
+                let (lane, i, left) = active[0];
+                results[i] = hash_chain(&store_lane(&message, lane), left);
+                lanes[lane] = None;
+            }
+            _ => {
+                let steps = active.iter().map(|(_, _, left)| *left).min().unwrap();
+                hash_lanes_n_simd(&mut message, steps);
+                for (lane, i, left) in active {
+                    if left == steps {
+                        results[i] = store_lane(&message, lane);
+                        lanes[lane] = None;
+                    } else {
+                        lanes[lane] = Some((i, left - steps));
+                    }
+                }
+            }
+        }
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_lanes() {
+        let mut message = [[0u32; LANES]; 8];
+        let hashes: Vec<Hash> = (0..LANES as u8).map(|i| hash(&[i])).collect();
+        for (lane, start_hash) in hashes.iter().enumerate() {
+            load_lane(&mut message, lane, start_hash);
+        }
+        hash_lanes(&mut message);
+        for (lane, start_hash) in hashes.iter().enumerate() {
+            assert_eq!(store_lane(&message, lane), hash(start_hash.as_ref()));
+        }
+    }
+
+    #[test]
+    fn test_hash_chain() {
+        let zero = Hash::default();
+        assert_eq!(hash_chain(&zero, 0), zero);
+        assert_eq!(hash_chain(&zero, 2), hash(hash(zero.as_ref()).as_ref()));
+    }
+
+    #[test]
+    fn test_hash_chains() {
+        assert!(hash_chains(&[], &[]).is_empty());
+
+        // More chains than lanes, of all sorts of lengths, including none
+        let start_hashes: Vec<Hash> = (0..3 * LANES as u8).map(|i| hash(&[i])).collect();
+        let num_hashes: Vec<u64> = (0..3 * LANES as u64).map(|i| (i * 7) % 20).collect();
+        let expected: Vec<Hash> = start_hashes
+            .iter()
+            .zip(&num_hashes)
+            .map(|(start_hash, num_hashes)| hash_chain(start_hash, *num_hashes))
+            .collect();
+        assert_eq!(hash_chains(&start_hashes, &num_hashes), expected);
+    }
+}
@@ -0,0 +1,127 @@
+//! Durable nonces
+//! * a nonce account is a system account holding a hash that a transaction can be signed
+//!   with in place of a recent blockhash, so it can be signed offline and sent much later
+//! * the transaction's first instruction has to advance the nonce, which makes replaying it
+//!   impossible
+
+use crate::account::Account;
+use crate::hash::{extend_and_hash, hashv, Hash};
+use crate::pubkey::Pubkey;
+use crate::system_instruction::SystemInstruction;
+use crate::system_program;
+use crate::transaction::Transaction;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum NonceState {
+    Uninitialized,
+    Initialized {
+        /// Key that has to sign to advance, withdraw from or reassign the nonce account
+        authority: Pubkey,
+        nonce: Hash,
+    },
+}
+
+impl Default for NonceState {
+    fn default() -> Self {
+        NonceState::Uninitialized
+    }
+}
+
+impl NonceState {
+    /// Data size of a nonce account
+    pub fn size() -> u64 {
+        bincode::serialized_size(&NonceState::Initialized {
+            authority: Pubkey::default(),
+            nonce: Hash::default(),
+        })
+        .unwrap()
+    }
+
+    pub fn from(account: &Account) -> Option<Self> {
+        if !system_program::check_id(&account.owner) {
+            return None;
+        }
+        account.deserialize_data().ok()
+    }
+
+    /// First nonce of a newly initialized account
+    pub fn initial_nonce(nonce_pubkey: &Pubkey, tick_height: u64) -> Hash {
+        hashv(&[nonce_pubkey.as_ref(), &tick_height.to_le_bytes()])
+    }
+
+    /// Each nonce is derived from the one before it, so a used nonce doesn't come back
+    pub fn next_nonce(nonce: &Hash, tick_height: u64) -> Hash {
+        extend_and_hash(nonce, &tick_height.to_le_bytes())
+    }
+}
+
+/// The nonce account a transaction uses in place of a recent blockhash, if its first
+/// instruction advances one
+pub fn transaction_nonce_pubkey(tx: &Transaction) -> Option<&Pubkey> {
+    let message = tx.message();
+    let instruction = message.instructions.get(0)?;
+    let program_id = message
+        .program_ids()
+        .get(instruction.program_ids_index as usize)?;
+    if !system_program::check_id(program_id) {
+        return None;
+    }
+    match bincode::deserialize(&instruction.data) {
+        Ok(SystemInstruction::AdvanceNonceAccount) => tx.key(0, 0),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::Message;
+    use crate::signature::{Keypair, KeypairUtil};
+    use crate::system_instruction;
+
+    #[test]
+    fn test_nonce_state_from() {
+        let authority = Pubkey::new_rand();
+        let nonce = Hash::default();
+        let mut account = Account::new(1, NonceState::size() as usize, &system_program::id());
+        assert_eq!(NonceState::from(&account), Some(NonceState::Uninitialized));
+
+        let state = NonceState::Initialized { authority, nonce };
+        account.serialize_data(&state).unwrap();
+        assert_eq!(NonceState::from(&account), Some(state));
+
+        account.owner = Pubkey::new_rand();
+        assert_eq!(NonceState::from(&account), None);
+    }
+
+    #[test]
+    fn test_next_nonce() {
+        let nonce = NonceState::initial_nonce(&Pubkey::new_rand(), 0);
+        let next = NonceState::next_nonce(&nonce, 0);
+        assert_ne!(next, nonce);
+        assert_ne!(NonceState::next_nonce(&next, 0), nonce);
+        assert_ne!(NonceState::next_nonce(&nonce, 1), next);
+    }
+
+    #[test]
+    fn test_transaction_nonce_pubkey() {
+        let from_keypair = Keypair::new();
+        let nonce_pubkey = Pubkey::new_rand();
+        let to_pubkey = Pubkey::new_rand();
+
+        let ixs = vec![
+            system_instruction::advance_nonce_account(&nonce_pubkey, &from_keypair.pubkey()),
+            system_instruction::transfer(&from_keypair.pubkey(), &to_pubkey, 1),
+        ];
+        let tx = Transaction::new(&[&from_keypair], Message::new(ixs), Hash::default());
+        assert_eq!(transaction_nonce_pubkey(&tx), Some(&nonce_pubkey));
+
+        // the advance has to come first
+        let ixs = vec![
+            system_instruction::transfer(&from_keypair.pubkey(), &to_pubkey, 1),
+            system_instruction::advance_nonce_account(&nonce_pubkey, &from_keypair.pubkey()),
+        ];
+        let tx = Transaction::new(&[&from_keypair], Message::new(ixs), Hash::default());
+        assert_eq!(transaction_nonce_pubkey(&tx), None);
+    }
+}
@@ -1,5 +1,7 @@
+use crate::hash::hashv;
 use crate::instruction::{AccountMeta, Instruction};
 use crate::instruction_processor_utils::DecodeError;
+use crate::nonce_state::NonceState;
 use crate::pubkey::Pubkey;
 use crate::system_program;
 use num_derive::FromPrimitive;
@@ -10,6 +12,8 @@ pub enum SystemError {
     // ResultWithNegativeLamports,
     ResultWithNegativeDif,
     SourceNotSystemAccount,
+    AddressWithSeedMismatch,
+    MaxSeedLengthExceeded,
 }
 
 impl<T> DecodeError<T> for SystemError {
@@ -25,6 +29,23 @@ impl std::fmt::Display for SystemError {
 }
 impl std::error::Error for SystemError {}
 
+/// Longest seed `create_address_with_seed` accepts
+pub const MAX_ADDRESS_SEED_LEN: usize = 32;
+
+/// Derive an account address from a base key, a seed and the program that will own the
+/// account, only the base key has to sign for the derived account
+pub fn create_address_with_seed(
+    base: &Pubkey,
+    seed: &str,
+    program_id: &Pubkey,
+) -> Result<Pubkey, SystemError> {
+    if seed.len() > MAX_ADDRESS_SEED_LEN {
+        Err(SystemError::MaxSeedLengthExceeded)?;
+    }
+    let hash = hashv(&[base.as_ref(), seed.as_bytes(), program_id.as_ref()]);
+    Ok(Pubkey::new(hash.as_ref()))
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum SystemInstruction {
     /// Create a new account
@@ -47,6 +68,44 @@ pub enum SystemInstruction {
     /// * Transaction::keys[1] - destination
     // Transfer { lamports: u64 },
     Transfer { dif: u64 },
+    /// Turn a system account with `NonceState::size()` bytes of data into a nonce account
+    /// * Transaction::keys[0] - nonce account
+    /// * authority - key that has to sign to use the nonce account from now on
+    InitializeNonceAccount { authority: Pubkey },
+    /// Replace the stored nonce, a transaction using the nonce in place of a recent
+    /// blockhash must do this in its first instruction
+    /// * Transaction::keys[0] - nonce account
+    /// * Transaction::keys[1] - nonce authority
+    AdvanceNonceAccount,
+    /// Withdraw dif from a nonce account
+    /// * Transaction::keys[0] - nonce account
+    /// * Transaction::keys[1] - destination
+    /// * Transaction::keys[2] - nonce authority
+    WithdrawNonceAccount { dif: u64 },
+    /// Hand a nonce account over to a new authority
+    /// * Transaction::keys[0] - nonce account
+    /// * Transaction::keys[1] - nonce authority
+    AuthorizeNonceAccount { authority: Pubkey },
+    /// Create a new account at an address derived from `base`, `seed` and `program_id`
+    /// * Transaction::keys[0] - source
+    /// * Transaction::keys[1] - new account key, `create_address_with_seed(base, seed, program_id)`
+    /// * Transaction::keys[2] - base, unless it's also the source
+    CreateAccountWithSeed {
+        base: Pubkey,
+        seed: String,
+        dif: u64,
+        space: u64,
+        program_id: Pubkey,
+    },
+    /// Transfer dif from an account derived from a base key, only the base key signs
+    /// * Transaction::keys[0] - source, `create_address_with_seed(base, from_seed, from_owner)`
+    /// * Transaction::keys[1] - destination
+    /// * Transaction::keys[2] - base
+    TransferWithSeed {
+        dif: u64,
+        from_seed: String,
+        from_owner: Pubkey,
+    },
 }
 
 pub fn create_account(
@@ -73,6 +132,35 @@ pub fn create_account(
     )
 }
 
+pub fn create_account_with_seed(
+    from_id: &Pubkey,
+    to_id: &Pubkey,
+    base: &Pubkey,
+    seed: &str,
+    dif: u64,
+    space: u64,
+    program_id: &Pubkey,
+) -> Instruction {
+    let mut account_metas = vec![
+        AccountMeta::new(*from_id, true),
+        AccountMeta::new(*to_id, false),
+    ];
+    if base != from_id {
+        account_metas.push(AccountMeta::new(*base, true));
+    }
+    Instruction::new(
+        system_program::id(),
+        &SystemInstruction::CreateAccountWithSeed {
+            base: *base,
+            seed: seed.to_string(),
+            dif,
+            space,
+            program_id: *program_id,
+        },
+        account_metas,
+    )
+}
+
 /// Create and sign a transaction to create a system account
 // pub fn create_user_account(from_id: &Pubkey, to_id: &Pubkey, lamports: u64) -> Instruction {
 pub fn create_user_account(from_id: &Pubkey, to_id: &Pubkey, dif: u64) -> Instruction {
@@ -106,6 +194,30 @@ pub fn transfer(from_id: &Pubkey, to_id: &Pubkey, dif: u64) -> Instruction {
     )
 }
 
+pub fn transfer_with_seed(
+    from_id: &Pubkey,
+    from_base: &Pubkey,
+    from_seed: &str,
+    from_owner: &Pubkey,
+    to_id: &Pubkey,
+    dif: u64,
+) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*from_id, false),
+        AccountMeta::new(*to_id, false),
+        AccountMeta::new(*from_base, true),
+    ];
+    Instruction::new(
+        system_program::id(),
+        &SystemInstruction::TransferWithSeed {
+            dif,
+            from_seed: from_seed.to_string(),
+            from_owner: *from_owner,
+        },
+        account_metas,
+    )
+}
+
 /// Create and sign new SystemInstruction::Transfer transaction to many destinations
 // pub fn transfer_many(from_id: &Pubkey, to_lamports: &[(Pubkey, u64)]) -> Vec<Instruction> {
 pub fn transfer_many(from_id: &Pubkey, to_dif: &[(Pubkey, u64)]) -> Vec<Instruction> {
@@ -117,6 +229,79 @@ pub fn transfer_many(from_id: &Pubkey, to_dif: &[(Pubkey, u64)]) -> Vec<Instruct
         .collect()
 }
 
+/// Create a nonce account and initialize it, `nonce_id` has to sign too
+pub fn create_nonce_account(
+    from_id: &Pubkey,
+    nonce_id: &Pubkey,
+    authority_id: &Pubkey,
+    dif: u64,
+) -> Vec<Instruction> {
+    vec![
+        create_account(
+            from_id,
+            nonce_id,
+            dif,
+            NonceState::size(),
+            &system_program::id(),
+        ),
+        Instruction::new(
+            system_program::id(),
+            &SystemInstruction::InitializeNonceAccount {
+                authority: *authority_id,
+            },
+            vec![AccountMeta::new(*nonce_id, true)],
+        ),
+    ]
+}
+
+pub fn advance_nonce_account(nonce_id: &Pubkey, authority_id: &Pubkey) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*nonce_id, false),
+        AccountMeta::new(*authority_id, true),
+    ];
+    Instruction::new(
+        system_program::id(),
+        &SystemInstruction::AdvanceNonceAccount,
+        account_metas,
+    )
+}
+
+pub fn withdraw_nonce_account(
+    nonce_id: &Pubkey,
+    to_id: &Pubkey,
+    authority_id: &Pubkey,
+    dif: u64,
+) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*nonce_id, false),
+        AccountMeta::new(*to_id, false),
+        AccountMeta::new(*authority_id, true),
+    ];
+    Instruction::new(
+        system_program::id(),
+        &SystemInstruction::WithdrawNonceAccount { dif },
+        account_metas,
+    )
+}
+
+pub fn authorize_nonce_account(
+    nonce_id: &Pubkey,
+    authority_id: &Pubkey,
+    new_authority_id: &Pubkey,
+) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*nonce_id, false),
+        AccountMeta::new(*authority_id, true),
+    ];
+    Instruction::new(
+        system_program::id(),
+        &SystemInstruction::AuthorizeNonceAccount {
+            authority: *new_authority_id,
+        },
+        account_metas,
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -139,4 +324,52 @@ mod tests {
         assert_eq!(get_keys(&instructions[0]), vec![alice_pubkey, bob_pubkey]);
         assert_eq!(get_keys(&instructions[1]), vec![alice_pubkey, carol_pubkey]);
     }
+
+    #[test]
+    fn test_create_address_with_seed() {
+        let base = Pubkey::new_rand();
+        let program_id = Pubkey::new_rand();
+
+        let address = create_address_with_seed(&base, "orders", &program_id).unwrap();
+        assert_eq!(
+            create_address_with_seed(&base, "orders", &program_id),
+            Ok(address)
+        );
+        assert_ne!(
+            create_address_with_seed(&base, "orders2", &program_id),
+            Ok(address)
+        );
+        assert_ne!(
+            create_address_with_seed(&base, "orders", &Pubkey::new_rand()),
+            Ok(address)
+        );
+        assert_ne!(
+            create_address_with_seed(&Pubkey::new_rand(), "orders", &program_id),
+            Ok(address)
+        );
+
+        let max_seed = "x".repeat(MAX_ADDRESS_SEED_LEN);
+        assert!(create_address_with_seed(&base, &max_seed, &program_id).is_ok());
+        let long_seed = "x".repeat(MAX_ADDRESS_SEED_LEN + 1);
+        assert_eq!(
+            create_address_with_seed(&base, &long_seed, &program_id),
+            Err(SystemError::MaxSeedLengthExceeded)
+        );
+    }
+
+    #[test]
+    fn test_create_account_with_seed_keys() {
+        let from = Pubkey::new_rand();
+        let base = Pubkey::new_rand();
+        let program_id = Pubkey::new_rand();
+        let to = create_address_with_seed(&base, "seed", &program_id).unwrap();
+
+        let instruction = create_account_with_seed(&from, &to, &base, "seed", 1, 0, &program_id);
+        assert_eq!(get_keys(&instruction), vec![from, to, base]);
+
+        // base doubles as the source
+        let to = create_address_with_seed(&from, "seed", &program_id).unwrap();
+        let instruction = create_account_with_seed(&from, &to, &from, "seed", 1, 0, &program_id);
+        assert_eq!(get_keys(&instruction), vec![from, to]);
+    }
 }
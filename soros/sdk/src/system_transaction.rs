@@ -76,3 +76,41 @@ pub fn transfer(
     let instructions = vec![transfer_instruction];
     Transaction::new_signed_instructions(&[from_keypair], instructions, recent_blockhash)
 }
+
+/// Create and sign a transaction that creates a nonce account controlled by `authority`
+pub fn create_nonce_account(
+    from_keypair: &Keypair,
+    nonce_keypair: &Keypair,
+    authority: &Pubkey,
+    dif: u64,
+    recent_blockhash: Hash,
+) -> Transaction {
+    let instructions = system_instruction::create_nonce_account(
+        &from_keypair.pubkey(),
+        &nonce_keypair.pubkey(),
+        authority,
+        dif,
+    );
+    Transaction::new_signed_instructions(
+        &[from_keypair, nonce_keypair],
+        instructions,
+        recent_blockhash,
+    )
+}
+
+/// Create and sign a transfer that uses the nonce stored in `nonce_pubkey` in place of a
+/// recent blockhash, `from_keypair` has to be the nonce authority
+pub fn nonced_transfer(
+    from_keypair: &Keypair,
+    to: &Pubkey,
+    dif: u64,
+    nonce_pubkey: &Pubkey,
+    nonce: Hash,
+) -> Transaction {
+    let from_pubkey = from_keypair.pubkey();
+    let instructions = vec![
+        system_instruction::advance_nonce_account(nonce_pubkey, &from_pubkey),
+        system_instruction::transfer(&from_pubkey, to, dif),
+    ];
+    Transaction::new_signed_instructions(&[from_keypair], instructions, nonce)
+}
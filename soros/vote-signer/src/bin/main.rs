@@ -1,10 +1,14 @@
 use clap::{crate_description, crate_name, crate_version, App, Arg};
+use soros_vote_signer::keystore::KeystoreVoteSigner;
 use soros_vote_signer::rpc::VoteSignerRpcService;
+use std::env;
 use std::error;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::process::exit;
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 pub const RPC_PORT: u16 = 8989;
+pub const PASSPHRASE_ENV: &str = "SOROS_VOTE_SIGNER_PASSPHRASE";
 
 fn main() -> Result<(), Box<error::Error>> {
     soros_metrics::set_panic_hook("vote-signer");
@@ -19,6 +23,16 @@ fn main() -> Result<(), Box<error::Error>> {
                 .takes_value(true)
                 .help("JSON RPC listener port"),
         )
+        .arg(
+            Arg::with_name("keystore")
+                .long("keystore")
+                .value_name("DIR")
+                .takes_value(true)
+                .help(
+                    "Keep voting keypairs in this directory, encrypted with the passphrase in \
+                     SOROS_VOTE_SIGNER_PASSPHRASE, and refuse to sign votes for old slots",
+                ),
+        )
         .get_matches();
 
     let port = if let Some(p) = matches.value_of("port") {
@@ -29,11 +43,21 @@ fn main() -> Result<(), Box<error::Error>> {
         RPC_PORT
     };
 
+    let rpc_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), port);
     let exit = Arc::new(AtomicBool::new(false));
-    let service = VoteSignerRpcService::new(
-        SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), port),
-        &exit,
-    );
+    let service = if let Some(keystore) = matches.value_of("keystore") {
+        let passphrase = env::var(PASSPHRASE_ENV).unwrap_or_else(|_| {
+            eprintln!("{} must be set to use a keystore", PASSPHRASE_ENV);
+            exit(1);
+        });
+        let signer = KeystoreVoteSigner::new(keystore, &passphrase).unwrap_or_else(|err| {
+            eprintln!("Unable to open keystore {}: {}", keystore, err);
+            exit(1);
+        });
+        VoteSignerRpcService::new_with_signer(rpc_addr, Arc::new(signer), &exit)
+    } else {
+        VoteSignerRpcService::new(rpc_addr, &exit)
+    };
 
     service.join().unwrap();
     Ok(())
@@ -0,0 +1,470 @@
+//! The `keystore` module implements a `VoteSigner` that keeps voting keypairs encrypted on disk,
+//! only signs vote transactions for the node's own vote account, and never signs a vote below
+//! the last slot the node voted for.
+//!
+//! Layout of the keystore directory:
+//! * `<NODE>.key` - the node's voting keypair, sealed with a key derived from the passphrase
+//! * `<NODE>.vote` - the highest slot the node voted for, and the message that vote was in.
+//!   Kept across deregistration so a re-registered node can't vote for old slots again
+//! * `audit.log` - one line of JSON per signed vote
+
+use crate::rpc::{verify_signature, SignedVote, VoteSigner};
+use bincode::deserialize;
+use jsonrpc_core::{Error, Result};
+use ring::aead::{self, OpeningKey, SealingKey, CHACHA20_POLY1305};
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::{digest, pbkdf2};
+use serde_derive::{Deserialize, Serialize};
+use soros_sdk::hash::{hash, Hash};
+use soros_sdk::message::Message;
+use soros_sdk::pubkey::Pubkey;
+use soros_sdk::signature::{Keypair, KeypairUtil, Signature};
+use soros_sdk::timing::timestamp;
+use soros_vote_api::vote_instruction::VoteInstruction;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Mutex, RwLock};
+
+const AUDIT_LOG: &str = "audit.log";
+const PBKDF2_ITERATIONS: u32 = 100_000;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// Voting keypair as stored in `<NODE>.key`
+#[derive(Serialize, Deserialize)]
+struct SealedKeypair {
+    salt: Vec<u8>,
+    nonce: Vec<u8>,
+    /// Keypair bytes followed by the authentication tag
+    ciphertext: Vec<u8>,
+}
+
+/// High-water mark of a node's votes, as stored in `<NODE>.vote`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct LastVote {
+    slot: u64,
+    message_hash: Hash,
+}
+
+struct VotingNode {
+    keypair: Keypair,
+    last_vote: Option<LastVote>,
+}
+
+pub struct KeystoreVoteSigner {
+    path: PathBuf,
+    passphrase: String,
+    nodes: RwLock<HashMap<Pubkey, VotingNode>>,
+    audit_log: Mutex<File>,
+}
+
+fn invalid_data<E: ToString>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    pbkdf2::derive(
+        &digest::SHA256,
+        PBKDF2_ITERATIONS,
+        salt,
+        passphrase.as_bytes(),
+        &mut key,
+    );
+    key
+}
+
+/// Seal `keypair` with `passphrase`, binding it to `node` so it can't be passed off as another
+/// node's voting keypair
+fn seal_keypair(keypair: &Keypair, node: &Pubkey, passphrase: &str) -> io::Result<SealedKeypair> {
+    let rng = SystemRandom::new();
+    let mut salt = vec![0u8; SALT_LEN];
+    let mut nonce = vec![0u8; NONCE_LEN];
+    rng.fill(&mut salt).map_err(invalid_data)?;
+    rng.fill(&mut nonce).map_err(invalid_data)?;
+
+    let key = SealingKey::new(&CHACHA20_POLY1305, &derive_key(passphrase, &salt))
+        .map_err(invalid_data)?;
+    let tag_len = CHACHA20_POLY1305.tag_len();
+    let mut ciphertext = keypair.to_bytes().to_vec();
+    ciphertext.resize(ciphertext.len() + tag_len, 0);
+    aead::seal_in_place(&key, &nonce, node.as_ref(), &mut ciphertext, tag_len)
+        .map_err(invalid_data)?;
+    Ok(SealedKeypair {
+        salt,
+        nonce,
+        ciphertext,
+    })
+}
+
+fn open_keypair(sealed: &SealedKeypair, node: &Pubkey, passphrase: &str) -> io::Result<Keypair> {
+    let key = OpeningKey::new(&CHACHA20_POLY1305, &derive_key(passphrase, &sealed.salt))
+        .map_err(invalid_data)?;
+    let mut ciphertext = sealed.ciphertext.clone();
+    let bytes = aead::open_in_place(&key, &sealed.nonce, node.as_ref(), 0, &mut ciphertext)
+        .map_err(|_| invalid_data(format!("unable to unseal the voting keypair of {}", node)))?;
+    Keypair::from_bytes(bytes).map_err(invalid_data)
+}
+
+/// The highest slot `msg` votes for, `None` unless `msg` is a message made up of nothing but
+/// vote instructions for `vote_account`
+fn vote_slot(msg: &[u8], vote_account: &Pubkey) -> Option<u64> {
+    let message: Message = deserialize(msg).ok()?;
+    let mut slot = None;
+    for instruction in &message.instructions {
+        let program_id = message
+            .program_ids()
+            .get(instruction.program_ids_index as usize)?;
+        if *program_id != soros_vote_api::id() {
+            return None;
+        }
+        let account_index = *instruction.accounts.first()?;
+        if message.account_keys.get(account_index as usize)? != vote_account {
+            return None;
+        }
+        match deserialize(&instruction.data).ok()? {
+            VoteInstruction::Vote(votes) => {
+                slot = votes.iter().map(|vote| vote.slot).chain(slot).max();
+            }
+            _ => return None,
+        }
+    }
+    slot
+}
+
+impl KeystoreVoteSigner {
+    /// Open the keystore at `path`, creating it if needed, and unseal all its voting keypairs
+    pub fn new<P: AsRef<Path>>(path: P, passphrase: &str) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        fs::create_dir_all(&path)?;
+
+        let mut nodes = HashMap::new();
+        for entry in fs::read_dir(&path)? {
+            let entry_path = entry?.path();
+            if entry_path
+                .extension()
+                .map_or(true, |extension| extension != "key")
+            {
+                continue;
+            }
+            let node = entry_path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| Pubkey::from_str(stem).ok())
+                .ok_or_else(|| invalid_data(format!("unexpected {:?}", entry_path)))?;
+            let sealed: SealedKeypair =
+                serde_json::from_reader(File::open(&entry_path)?).map_err(invalid_data)?;
+            let keypair = open_keypair(&sealed, &node, passphrase)?;
+
+            let last_vote = Self::read_last_vote(&entry_path.with_extension("vote"))?;
+            nodes.insert(node, VotingNode { keypair, last_vote });
+        }
+
+        let audit_log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path.join(AUDIT_LOG))?;
+        Ok(Self {
+            path,
+            passphrase: passphrase.to_string(),
+            nodes: RwLock::new(nodes),
+            audit_log: Mutex::new(audit_log),
+        })
+    }
+
+    fn node_path(&self, node: &Pubkey, extension: &str) -> PathBuf {
+        self.path.join(format!("{}.{}", node, extension))
+    }
+
+    fn read_last_vote(vote_path: &Path) -> io::Result<Option<LastVote>> {
+        if vote_path.exists() {
+            let last_vote =
+                serde_json::from_reader(File::open(vote_path)?).map_err(invalid_data)?;
+            Ok(Some(last_vote))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Write `contents` to `path` such that a crash leaves either the old or the new contents
+    fn write_durably(path: &Path, contents: &[u8]) -> io::Result<()> {
+        let tmp_path = path.with_extension("tmp");
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+    }
+
+    fn register_node(&self, node: &Pubkey) -> io::Result<Pubkey> {
+        let mut nodes = self.nodes.write().unwrap();
+        if let Some(voting_node) = nodes.get(node) {
+            return Ok(voting_node.keypair.pubkey());
+        }
+        let keypair = Keypair::new();
+        let sealed = seal_keypair(&keypair, node, &self.passphrase)?;
+        let contents = serde_json::to_vec(&sealed).map_err(invalid_data)?;
+        Self::write_durably(&self.node_path(node, "key"), &contents)?;
+
+        let voting_pubkey = keypair.pubkey();
+        let last_vote = Self::read_last_vote(&self.node_path(node, "vote"))?;
+        nodes.insert(*node, VotingNode { keypair, last_vote });
+        Ok(voting_pubkey)
+    }
+
+    fn record_vote(
+        &self,
+        node: &Pubkey,
+        last_vote: &LastVote,
+        signed_vote: &SignedVote,
+    ) -> io::Result<()> {
+        let contents = serde_json::to_vec(last_vote).map_err(invalid_data)?;
+        Self::write_durably(&self.node_path(node, "vote"), &contents)?;
+
+        let mut audit_log = self.audit_log.lock().unwrap();
+        let mut line = serde_json::to_vec(signed_vote).map_err(invalid_data)?;
+        line.push(b'\n');
+        audit_log.write_all(&line)?;
+        audit_log.sync_data()
+    }
+
+    fn read_audit_log(&self, node: &Pubkey) -> io::Result<Vec<SignedVote>> {
+        let file = File::open(self.path.join(AUDIT_LOG))?;
+        let mut signed_votes = vec![];
+        for line in BufReader::new(file).lines() {
+            let signed_vote: SignedVote = serde_json::from_str(&line?).map_err(invalid_data)?;
+            if signed_vote.node == *node {
+                signed_votes.push(signed_vote);
+            }
+        }
+        Ok(signed_votes)
+    }
+}
+
+impl VoteSigner for KeystoreVoteSigner {
+    fn register(&self, pubkey: &Pubkey, sig: &Signature, msg: &[u8]) -> Result<Pubkey> {
+        verify_signature(&sig, &pubkey, &msg)?;
+        self.register_node(pubkey).map_err(|err| {
+            warn!("unable to register {}: {:?}", pubkey, err);
+            Error::internal_error()
+        })
+    }
+
+    fn sign(&self, pubkey: &Pubkey, sig: &Signature, msg: &[u8]) -> Result<Signature> {
+        verify_signature(&sig, &pubkey, &msg)?;
+        let mut nodes = self.nodes.write().unwrap();
+        let voting_node = nodes.get_mut(pubkey).ok_or_else(Error::invalid_request)?;
+        let slot = vote_slot(msg, &voting_node.keypair.pubkey()).ok_or_else(|| {
+            Error::invalid_params("only votes for the node's vote account are signed")
+        })?;
+        let message_hash = hash(msg);
+
+        if let Some(last_vote) = &voting_node.last_vote {
+            if slot == last_vote.slot && message_hash == last_vote.message_hash {
+                // The same vote again, it was already recorded
+                return Ok(voting_node.keypair.sign_message(msg));
+            }
+            // A different message for the last voted slot is let through, a vote that timed out
+            // is resent with a new recent blockhash
+            if slot < last_vote.slot {
+                warn!(
+                    "refusing to sign a vote by {} for slot {}, already voted for slot {}",
+                    pubkey, slot, last_vote.slot
+                );
+                return Err(Error::invalid_params(format!(
+                    "slot {} is below the last voted slot {}",
+                    slot, last_vote.slot
+                )));
+            }
+        }
+
+        let signature = voting_node.keypair.sign_message(msg);
+        let last_vote = LastVote { slot, message_hash };
+        let signed_vote = SignedVote {
+            node: *pubkey,
+            vote_account: voting_node.keypair.pubkey(),
+            slot,
+            message_hash,
+            signature,
+            timestamp: timestamp(),
+        };
+        // The vote is only handed out once it's on disk, so a restart can't forget it
+        self.record_vote(pubkey, &last_vote, &signed_vote)
+            .map_err(|err| {
+                warn!("unable to record a vote by {}: {:?}", pubkey, err);
+                Error::internal_error()
+            })?;
+        voting_node.last_vote = Some(last_vote);
+        Ok(signature)
+    }
+
+    fn deregister(&self, pubkey: &Pubkey, sig: &Signature, msg: &[u8]) -> Result<()> {
+        verify_signature(&sig, &pubkey, &msg)?;
+        let mut nodes = self.nodes.write().unwrap();
+        if nodes.remove(pubkey).is_some() {
+            // The `.vote` high-water mark stays behind for when the node registers again
+            let path = self.node_path(pubkey, "key");
+            if let Err(err) = fs::remove_file(&path) {
+                if err.kind() != io::ErrorKind::NotFound {
+                    warn!("unable to remove {:?}: {:?}", path, err);
+                    return Err(Error::internal_error());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn signed_votes(&self, pubkey: &Pubkey) -> Result<Vec<SignedVote>> {
+        self.read_audit_log(pubkey).map_err(|err| {
+            warn!("unable to read the audit log: {:?}", err);
+            Error::internal_error()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bincode::serialize;
+    use soros_vote_api::vote_instruction;
+    use soros_vote_api::vote_state::Vote;
+    use tempfile::tempdir;
+
+    fn vote_message(vote_account: &Pubkey, slots: &[u64], blockhash: Hash) -> Vec<u8> {
+        let votes = slots.iter().map(|slot| Vote::new(*slot)).collect();
+        let mut message = Message::new(vec![vote_instruction::vote(vote_account, votes)]);
+        message.recent_blockhash = blockhash;
+        serialize(&message).unwrap()
+    }
+
+    fn register(signer: &KeystoreVoteSigner, node_keypair: &Keypair) -> Pubkey {
+        let msg = b"Registering a new node";
+        let sig = node_keypair.sign_message(msg);
+        signer.register(&node_keypair.pubkey(), &sig, msg).unwrap()
+    }
+
+    fn sign(signer: &KeystoreVoteSigner, node_keypair: &Keypair, msg: &[u8]) -> Result<Signature> {
+        let sig = node_keypair.sign_message(msg);
+        signer.sign(&node_keypair.pubkey(), &sig, msg)
+    }
+
+    #[test]
+    fn test_vote_slot() {
+        let vote_account = Pubkey::new_rand();
+        let msg = vote_message(&vote_account, &[3, 5, 4], Hash::default());
+        assert_eq!(vote_slot(&msg, &vote_account), Some(5));
+        assert_eq!(vote_slot(&msg, &Pubkey::new_rand()), None);
+        assert_eq!(vote_slot(b"This is a test", &vote_account), None);
+
+        let message = Message::new(vec![vote_instruction::authorize_voter(
+            &vote_account,
+            &Pubkey::new_rand(),
+        )]);
+        assert_eq!(
+            vote_slot(&serialize(&message).unwrap(), &vote_account),
+            None
+        );
+    }
+
+    #[test]
+    fn test_keystore_register_persists() {
+        let dir = tempdir().unwrap();
+        let node_keypair = Keypair::new();
+        let vote_account = {
+            let signer = KeystoreVoteSigner::new(dir.path(), "passphrase").unwrap();
+            let vote_account = register(&signer, &node_keypair);
+            assert_eq!(register(&signer, &node_keypair), vote_account);
+            vote_account
+        };
+
+        let signer = KeystoreVoteSigner::new(dir.path(), "passphrase").unwrap();
+        assert_eq!(register(&signer, &node_keypair), vote_account);
+        assert!(KeystoreVoteSigner::new(dir.path(), "wrong passphrase").is_err());
+    }
+
+    #[test]
+    fn test_keystore_sign_refuses_conflicting_votes() {
+        let dir = tempdir().unwrap();
+        let node_keypair = Keypair::new();
+        let signer = KeystoreVoteSigner::new(dir.path(), "passphrase").unwrap();
+        let vote_account = register(&signer, &node_keypair);
+
+        assert!(sign(&signer, &node_keypair, b"This is a test").is_err());
+        let other_vote = vote_message(&Pubkey::new_rand(), &[2], Hash::default());
+        assert!(sign(&signer, &node_keypair, &other_vote).is_err());
+
+        let fork_a = Hash::new(&[1; 32]);
+        let fork_b = Hash::new(&[2; 32]);
+        let vote = vote_message(&vote_account, &[2], fork_a);
+        let signature = sign(&signer, &node_keypair, &vote).unwrap();
+        assert!(signature.verify(vote_account.as_ref(), &vote));
+
+        // Resending the same vote is fine, so is resending it with a new blockhash, voting for
+        // an older slot isn't
+        assert_eq!(sign(&signer, &node_keypair, &vote), Ok(signature));
+        let resent_vote = vote_message(&vote_account, &[2], fork_b);
+        assert!(sign(&signer, &node_keypair, &resent_vote).is_ok());
+        let old_vote = vote_message(&vote_account, &[1], fork_a);
+        assert!(sign(&signer, &node_keypair, &old_vote).is_err());
+        let next_vote = vote_message(&vote_account, &[3], fork_b);
+        assert!(sign(&signer, &node_keypair, &next_vote).is_ok());
+
+        // The high-water mark survives a restart
+        drop(signer);
+        let signer = KeystoreVoteSigner::new(dir.path(), "passphrase").unwrap();
+        assert!(sign(&signer, &node_keypair, &resent_vote).is_err());
+        let next_vote = vote_message(&vote_account, &[4], fork_b);
+        assert!(sign(&signer, &node_keypair, &next_vote).is_ok());
+    }
+
+    #[test]
+    fn test_keystore_audit_log() {
+        let dir = tempdir().unwrap();
+        let node_keypair = Keypair::new();
+        let signer = KeystoreVoteSigner::new(dir.path(), "passphrase").unwrap();
+        let vote_account = register(&signer, &node_keypair);
+        let other_keypair = Keypair::new();
+        let other_vote_account = register(&signer, &other_keypair);
+
+        let vote = vote_message(&vote_account, &[1], Hash::default());
+        let signature = sign(&signer, &node_keypair, &vote).unwrap();
+        sign(&signer, &node_keypair, &vote).unwrap();
+        let other_vote = vote_message(&other_vote_account, &[1], Hash::default());
+        sign(&signer, &other_keypair, &other_vote).unwrap();
+
+        let signed_votes = signer.signed_votes(&node_keypair.pubkey()).unwrap();
+        assert_eq!(signed_votes.len(), 1);
+        assert_eq!(signed_votes[0].vote_account, vote_account);
+        assert_eq!(signed_votes[0].slot, 1);
+        assert_eq!(signed_votes[0].message_hash, hash(&vote));
+        assert_eq!(signed_votes[0].signature, signature);
+    }
+
+    #[test]
+    fn test_keystore_deregister() {
+        let dir = tempdir().unwrap();
+        let node_keypair = Keypair::new();
+        let signer = KeystoreVoteSigner::new(dir.path(), "passphrase").unwrap();
+        let vote_account = register(&signer, &node_keypair);
+        let vote = vote_message(&vote_account, &[2], Hash::default());
+        assert!(sign(&signer, &node_keypair, &vote).is_ok());
+
+        let msg = b"Deregistering a node";
+        let sig = node_keypair.sign_message(msg);
+        signer
+            .deregister(&node_keypair.pubkey(), &sig, msg)
+            .unwrap();
+        assert!(sign(&signer, &node_keypair, &vote).is_err());
+
+        // Registering again hands out a new vote account, but not a clean slate
+        let signer = KeystoreVoteSigner::new(dir.path(), "passphrase").unwrap();
+        let new_vote_account = register(&signer, &node_keypair);
+        assert_ne!(new_vote_account, vote_account);
+        let old_vote = vote_message(&new_vote_account, &[1], Hash::default());
+        assert!(sign(&signer, &node_keypair, &old_vote).is_err());
+        let next_vote = vote_message(&new_vote_account, &[3], Hash::default());
+        assert!(sign(&signer, &node_keypair, &next_vote).is_ok());
+    }
+}
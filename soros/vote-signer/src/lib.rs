@@ -1,3 +1,4 @@
+pub mod keystore;
 pub mod rpc;
 
 #[macro_use]
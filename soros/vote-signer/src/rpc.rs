@@ -3,6 +3,8 @@
 use jsonrpc_core::{Error, MetaIoHandler, Metadata, Result};
 use jsonrpc_derive::rpc;
 use jsonrpc_http_server::{hyper, AccessControlAllowOrigin, DomainsValidation, ServerBuilder};
+use serde_derive::{Deserialize, Serialize};
+use soros_sdk::hash::Hash;
 use soros_sdk::pubkey::Pubkey;
 use soros_sdk::signature::{Keypair, KeypairUtil, Signature};
 use std::collections::HashMap;
@@ -18,7 +20,14 @@ pub struct VoteSignerRpcService {
 
 impl VoteSignerRpcService {
     pub fn new(rpc_addr: SocketAddr, exit: &Arc<AtomicBool>) -> Self {
-        let request_processor = LocalVoteSigner::default();
+        Self::new_with_signer(rpc_addr, Arc::new(LocalVoteSigner::default()), exit)
+    }
+
+    pub fn new_with_signer(
+        rpc_addr: SocketAddr,
+        request_processor: Arc<VoteSigner + Send + Sync>,
+        exit: &Arc<AtomicBool>,
+    ) -> Self {
         let exit = exit.clone();
         let thread_hdl = Builder::new()
             .name("soros-vote-signer-jsonrpc".to_string())
@@ -55,7 +64,7 @@ impl VoteSignerRpcService {
 
 #[derive(Clone)]
 pub struct Meta {
-    pub request_processor: LocalVoteSigner,
+    pub request_processor: Arc<VoteSigner + Send + Sync>,
 }
 impl Metadata for Meta {}
 
@@ -71,6 +80,9 @@ pub trait VoteSignerRpc {
 
     #[rpc(meta, name = "deregisterNode")]
     fn deregister(&self, _: Self::Metadata, _: Pubkey, _: Signature, _: Vec<u8>) -> Result<()>;
+
+    #[rpc(meta, name = "getSignedVotes")]
+    fn signed_votes(&self, _: Self::Metadata, _: Pubkey) -> Result<Vec<SignedVote>>;
 }
 
 pub struct VoteSignerRpcImpl;
@@ -109,9 +121,14 @@ impl VoteSignerRpc for VoteSignerRpcImpl {
         info!("deregister rpc request received: {:?}", id);
         meta.request_processor.deregister(&id, &sig, &signed_msg)
     }
+
+    fn signed_votes(&self, meta: Self::Metadata, id: Pubkey) -> Result<Vec<SignedVote>> {
+        info!("signed_votes rpc request received: {:?}", id);
+        meta.request_processor.signed_votes(&id)
+    }
 }
 
-fn verify_signature(sig: &Signature, pubkey: &Pubkey, msg: &[u8]) -> Result<()> {
+pub(crate) fn verify_signature(sig: &Signature, pubkey: &Pubkey, msg: &[u8]) -> Result<()> {
     if sig.verify(pubkey.as_ref(), msg) {
         Ok(())
     } else {
@@ -119,10 +136,28 @@ fn verify_signature(sig: &Signature, pubkey: &Pubkey, msg: &[u8]) -> Result<()>
     }
 }
 
+/// A vote signed on behalf of `node`, as kept in a signer's audit log
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SignedVote {
+    pub node: Pubkey,
+    pub vote_account: Pubkey,
+    /// Highest slot the vote is for
+    pub slot: u64,
+    pub message_hash: Hash,
+    pub signature: Signature,
+    /// Milliseconds since the UNIX epoch
+    pub timestamp: u64,
+}
+
 pub trait VoteSigner {
     fn register(&self, pubkey: &Pubkey, sig: &Signature, signed_msg: &[u8]) -> Result<Pubkey>;
     fn sign(&self, pubkey: &Pubkey, sig: &Signature, msg: &[u8]) -> Result<Signature>;
     fn deregister(&self, pubkey: &Pubkey, sig: &Signature, msg: &[u8]) -> Result<()>;
+
+    /// Votes signed for `pubkey`, oldest first, by signers that keep an audit log
+    fn signed_votes(&self, _pubkey: &Pubkey) -> Result<Vec<SignedVote>> {
+        Err(Error::method_not_found())
+    }
 }
 
 #[derive(Clone)]
@@ -173,7 +208,7 @@ mod tests {
     use std::mem;
 
     fn start_rpc_handler() -> (MetaIoHandler<Meta>, Meta) {
-        let request_processor = LocalVoteSigner::default();
+        let request_processor = Arc::new(LocalVoteSigner::default());
         let mut io = MetaIoHandler::default();
         let rpc = VoteSignerRpcImpl;
         io.extend_with(rpc.to_delegate());
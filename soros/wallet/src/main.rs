@@ -281,9 +281,107 @@ fn main() -> Result<(), Box<dyn error::Error>> {
                         .help("Vote account pubkey"),
                 )
         )
+        .subcommand(
+            SubCommand::with_name("create-nonce-account")
+                .about("Create a nonce account controlled by this wallet")
+                .arg(
+                    Arg::with_name("dif")
+                        .index(1)
+                        .value_name("NUM")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The number of dif to send to the nonce account"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("get-nonce")
+                .about("Get the current nonce of a nonce account")
+                .arg(
+                    Arg::with_name("nonce_account_id")
+                        .index(1)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .validator(is_pubkey)
+                        .help("Nonce account address"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("new-nonce")
+                .about("Advance a nonce account to a new nonce")
+                .arg(
+                    Arg::with_name("nonce_account_id")
+                        .index(1)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .validator(is_pubkey)
+                        .help("Nonce account address"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("pay-with-nonce")
+                .about("Send a payment that uses a nonce in place of a recent blockhash")
+                .arg(
+                    Arg::with_name("to")
+                        .index(1)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .validator(is_pubkey)
+                        .help("The pubkey of recipient"),
+                )
+                .arg(
+                    Arg::with_name("dif")
+                        .index(2)
+                        .value_name("NUM")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The number of dif to send"),
+                )
+                .arg(
+                    Arg::with_name("nonce_account_id")
+                        .index(3)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .validator(is_pubkey)
+                        .help("Nonce account to use, this wallet must be its authority"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("withdraw-from-nonce-account")
+                .about("Withdraw dif from a nonce account")
+                .arg(
+                    Arg::with_name("nonce_account_id")
+                        .index(1)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .validator(is_pubkey)
+                        .help("Nonce account address"),
+                )
+                .arg(
+                    Arg::with_name("to")
+                        .index(2)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .validator(is_pubkey)
+                        .help("The pubkey of recipient"),
+                )
+                .arg(
+                    Arg::with_name("dif")
+                        .index(3)
+                        .value_name("NUM")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The number of dif to withdraw"),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("deploy")
-                .about("Deploy a program")
+                .about("Deploy a program with the upgradeable BPF loader")
                 .arg(
                     Arg::with_name("program_location")
                         .index(1)
@@ -291,7 +389,113 @@ fn main() -> Result<(), Box<dyn error::Error>> {
                         .takes_value(true)
                         .required(true)
                         .help("/path/to/program.o"),
-                ), // TODO: Add "loader" argument; current default is bpf_loader
+                )
+                .arg(
+                    Arg::with_name("buffer")
+                        .long("buffer")
+                        .value_name("PATH")
+                        .takes_value(true)
+                        .help("Buffer keypair, resumes an interrupted deploy"),
+                )
+                .arg(
+                    Arg::with_name("program_id")
+                        .long("program-id")
+                        .value_name("PATH")
+                        .takes_value(true)
+                        .help("Keypair of the program id [default: a new keypair]"),
+                )
+                .arg(
+                    Arg::with_name("max_len")
+                        .long("max-len")
+                        .value_name("BYTES")
+                        .takes_value(true)
+                        .help("Largest program the program id can hold [default: 2x]"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("upgrade")
+                .about("Replace a deployed program, keeping its program id")
+                .arg(
+                    Arg::with_name("program_location")
+                        .index(1)
+                        .value_name("PATH")
+                        .takes_value(true)
+                        .required(true)
+                        .help("/path/to/program.o"),
+                )
+                .arg(
+                    Arg::with_name("program_id")
+                        .index(2)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The program id to upgrade"),
+                )
+                .arg(
+                    Arg::with_name("buffer")
+                        .long("buffer")
+                        .value_name("PATH")
+                        .takes_value(true)
+                        .help("Buffer keypair, resumes an interrupted upgrade"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("set-authority")
+                .about("Hand a buffer or program over to a new authority")
+                .arg(
+                    Arg::with_name("account_id")
+                        .index(1)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The buffer or program id"),
+                )
+                .arg(
+                    Arg::with_name("new_authority")
+                        .long("new-authority")
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required_unless("final")
+                        .help("The new authority"),
+                )
+                .arg(
+                    Arg::with_name("final")
+                        .long("final")
+                        .conflicts_with("new_authority")
+                        .help("Make the program impossible to upgrade"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("dump")
+                .about("Write a deployed program, padded to its max length, to a file")
+                .arg(
+                    Arg::with_name("program_id")
+                        .index(1)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The program id"),
+                )
+                .arg(
+                    Arg::with_name("output_location")
+                        .index(2)
+                        .value_name("PATH")
+                        .takes_value(true)
+                        .required(true)
+                        .help("/path/to/program.so"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("show")
+                .about("Show an upgradeable buffer, program or program data account")
+                .arg(
+                    Arg::with_name("account_id")
+                        .index(1)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The account to show"),
+                ),
         )
         .subcommand(
             SubCommand::with_name("get-transaction-count").about("Get current transaction count"),
@@ -15,20 +15,21 @@ use soros_drone::drone::request_airdrop_transaction;
 use soros_drone::drone::DRONE_PORT;
 #[cfg(test)]
 use soros_drone::drone_mock::request_airdrop_transaction;
+use soros_sdk::account::Account;
 use soros_sdk::bpf_loader;
+use soros_sdk::bpf_loader_upgradeable::{self, UpgradeableLoaderState};
 use soros_sdk::hash::Hash;
 use soros_sdk::instruction::InstructionError;
 use soros_sdk::instruction_processor_utils::DecodeError;
-use soros_sdk::loader_instruction;
 use soros_sdk::pubkey::Pubkey;
 use soros_sdk::rpc_port::DEFAULT_RPC_PORT;
-use soros_sdk::signature::{Keypair, KeypairUtil, Signature};
-use soros_sdk::system_instruction::SystemError;
+use soros_sdk::signature::{read_keypair, Keypair, KeypairUtil, Signature};
+use soros_sdk::system_instruction::{self, SystemError};
 use soros_sdk::system_transaction;
 use soros_sdk::transaction::{Transaction, TransactionError};
 use soros_vote_api::vote_instruction;
 use std::fs::File;
-use std::io::Read;
+use std::io::{self, Read, Write};
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::{error, fmt, mem};
 
@@ -45,7 +46,23 @@ pub enum WalletCommand {
     AuthorizeVoter(Pubkey),
     CreateVoteAccount(Pubkey, Pubkey, u32, u64),
     ShowVoteAccount(Pubkey),
-    Deploy(String),
+    // CreateNonceAccount(dif)
+    CreateNonceAccount(u64),
+    GetNonce(Pubkey),
+    NewNonce(Pubkey),
+    // PayWithNonce(dif, to, nonce_account_id)
+    PayWithNonce(u64, Pubkey, Pubkey),
+    // WithdrawFromNonceAccount(nonce_account_id, to, dif)
+    WithdrawFromNonceAccount(Pubkey, Pubkey, u64),
+    // Deploy(program_location, buffer_location, program_id_location, max_len)
+    Deploy(String, Option<String>, Option<String>, Option<usize>),
+    // Upgrade(program_location, program_id, buffer_location)
+    Upgrade(String, Pubkey, Option<String>),
+    // SetAuthority(buffer or program id, new_authority), a program is final without one
+    SetAuthority(Pubkey, Option<Pubkey>),
+    // Dump(program_id, output_location)
+    Dump(Pubkey, String),
+    Show(Pubkey),
     GetTransactionCount,
     // Pay(dif, to, timestamp, timestamp_pubkey, witness(es), cancelable)
     Pay(
@@ -199,11 +216,66 @@ pub fn parse_command(
             let voting_account_id = pubkey_of(matches, "voting_account_id").unwrap();
             Ok(WalletCommand::ShowVoteAccount(voting_account_id))
         }
-        ("deploy", Some(deploy_matches)) => Ok(WalletCommand::Deploy(
-            deploy_matches
-                .value_of("program_location")
-                .unwrap()
-                .to_string(),
+        ("create-nonce-account", Some(matches)) => {
+            let dif = matches.value_of("dif").unwrap().parse()?;
+            Ok(WalletCommand::CreateNonceAccount(dif))
+        }
+        ("get-nonce", Some(matches)) => {
+            let nonce_account_id = pubkey_of(matches, "nonce_account_id").unwrap();
+            Ok(WalletCommand::GetNonce(nonce_account_id))
+        }
+        ("new-nonce", Some(matches)) => {
+            let nonce_account_id = pubkey_of(matches, "nonce_account_id").unwrap();
+            Ok(WalletCommand::NewNonce(nonce_account_id))
+        }
+        ("pay-with-nonce", Some(matches)) => {
+            let dif = matches.value_of("dif").unwrap().parse()?;
+            let to = pubkey_of(matches, "to").unwrap();
+            let nonce_account_id = pubkey_of(matches, "nonce_account_id").unwrap();
+            Ok(WalletCommand::PayWithNonce(dif, to, nonce_account_id))
+        }
+        ("withdraw-from-nonce-account", Some(matches)) => {
+            let nonce_account_id = pubkey_of(matches, "nonce_account_id").unwrap();
+            let to = pubkey_of(matches, "to").unwrap();
+            let dif = matches.value_of("dif").unwrap().parse()?;
+            Ok(WalletCommand::WithdrawFromNonceAccount(
+                nonce_account_id,
+                to,
+                dif,
+            ))
+        }
+        ("deploy", Some(deploy_matches)) => {
+            let max_len = match deploy_matches.value_of("max_len") {
+                Some(max_len) => Some(max_len.parse()?),
+                None => None,
+            };
+            Ok(WalletCommand::Deploy(
+                deploy_matches
+                    .value_of("program_location")
+                    .unwrap()
+                    .to_string(),
+                deploy_matches.value_of("buffer").map(ToString::to_string),
+                deploy_matches
+                    .value_of("program_id")
+                    .map(ToString::to_string),
+                max_len,
+            ))
+        }
+        ("upgrade", Some(matches)) => Ok(WalletCommand::Upgrade(
+            matches.value_of("program_location").unwrap().to_string(),
+            pubkey_of(matches, "program_id").unwrap(),
+            matches.value_of("buffer").map(ToString::to_string),
+        )),
+        ("set-authority", Some(matches)) => Ok(WalletCommand::SetAuthority(
+            pubkey_of(matches, "account_id").unwrap(),
+            pubkey_of(matches, "new_authority"),
+        )),
+        ("dump", Some(matches)) => Ok(WalletCommand::Dump(
+            pubkey_of(matches, "program_id").unwrap(),
+            matches.value_of("output_location").unwrap().to_string(),
+        )),
+        ("show", Some(matches)) => Ok(WalletCommand::Show(
+            pubkey_of(matches, "account_id").unwrap(),
         )),
         ("get-transaction-count", Some(_matches)) => Ok(WalletCommand::GetTransactionCount),
         ("pay", Some(pay_matches)) => {
@@ -434,74 +506,274 @@ fn process_show_staking(
     Ok("".to_string())
 }
 
-fn process_deploy(
+fn process_create_nonce_account(
     rpc_client: &RpcClient,
     config: &WalletConfig,
-    program_location: &str,
+    dif: u64,
 ) -> ProcessResult {
-    let balance = rpc_client.retry_get_balance(&config.keypair.pubkey(), 5)?;
-    // if let Some(lamports) = balance {
-    if let Some(dif) = balance {
-        // if lamports < 1 {
-        if dif < 1 {
-            Err(WalletError::DynamicProgramError(
-                "Insufficient funds".to_string(),
-            ))?
-        }
+    let recent_blockhash = rpc_client.get_recent_blockhash()?;
+    let nonce_account = Keypair::new();
+    let ixs = system_instruction::create_nonce_account(
+        &config.keypair.pubkey(),
+        &nonce_account.pubkey(),
+        &config.keypair.pubkey(),
+        dif,
+    );
+    let mut tx = Transaction::new_signed_instructions(
+        &[&config.keypair, &nonce_account],
+        ixs,
+        recent_blockhash,
+    );
+    let result = rpc_client.send_and_confirm_transaction(&mut tx, &config.keypair);
+    let signature_str = log_instruction_custom_error::<SystemError>(result)?;
+    Ok(json!({
+        "signature": signature_str,
+        "nonceAccountId": format!("{}", nonce_account.pubkey()),
+    })
+    .to_string())
+}
+
+fn process_get_nonce(rpc_client: &RpcClient, nonce_account_id: &Pubkey) -> ProcessResult {
+    let nonce = rpc_client.get_nonce(nonce_account_id)?;
+    Ok(nonce.to_string())
+}
+
+// A nonce transaction can't be re-signed with a newer blockhash, so it's sent once and polled
+// for instead of going through `send_and_confirm_transaction`
+fn send_and_confirm_nonce_transaction(
+    rpc_client: &RpcClient,
+    tx: &Transaction,
+) -> Result<String, ClientError> {
+    let signature_str = rpc_client.send_transaction(tx)?;
+    rpc_client.poll_for_signature(&tx.signatures[0])?;
+    match rpc_client.get_signature_status(&signature_str)? {
+        Some(Ok(())) => Ok(signature_str),
+        Some(Err(err)) => Err(err.into()),
+        None => Err(io::Error::new(
+            io::ErrorKind::Other,
+            "nonce transaction not confirmed",
+        ))?,
     }
+}
 
-    let blockhash = rpc_client.get_recent_blockhash()?;
-    let program_id = Keypair::new();
+fn process_new_nonce(
+    rpc_client: &RpcClient,
+    config: &WalletConfig,
+    nonce_account_id: &Pubkey,
+) -> ProcessResult {
+    let recent_blockhash = rpc_client.get_recent_blockhash()?;
+    let ix = system_instruction::advance_nonce_account(nonce_account_id, &config.keypair.pubkey());
+    let mut tx =
+        Transaction::new_signed_instructions(&[&config.keypair], vec![ix], recent_blockhash);
+    let result = rpc_client.send_and_confirm_transaction(&mut tx, &config.keypair);
+    log_instruction_custom_error::<SystemError>(result)?;
+    let nonce = rpc_client.get_nonce(nonce_account_id)?;
+    Ok(nonce.to_string())
+}
+
+fn process_pay_with_nonce(
+    rpc_client: &RpcClient,
+    config: &WalletConfig,
+    dif: u64,
+    to: &Pubkey,
+    nonce_account_id: &Pubkey,
+) -> ProcessResult {
+    let nonce = rpc_client.get_nonce(nonce_account_id)?;
+    let tx = system_transaction::nonced_transfer(&config.keypair, to, dif, nonce_account_id, nonce);
+    let result = send_and_confirm_nonce_transaction(rpc_client, &tx);
+    let signature_str = log_instruction_custom_error::<SystemError>(result)?;
+    Ok(signature_str.to_string())
+}
+
+fn process_withdraw_from_nonce_account(
+    rpc_client: &RpcClient,
+    config: &WalletConfig,
+    nonce_account_id: &Pubkey,
+    to: &Pubkey,
+    dif: u64,
+) -> ProcessResult {
+    let recent_blockhash = rpc_client.get_recent_blockhash()?;
+    let ix = system_instruction::withdraw_nonce_account(
+        nonce_account_id,
+        to,
+        &config.keypair.pubkey(),
+        dif,
+    );
+    let mut tx =
+        Transaction::new_signed_instructions(&[&config.keypair], vec![ix], recent_blockhash);
+    let result = rpc_client.send_and_confirm_transaction(&mut tx, &config.keypair);
+    let signature_str = log_instruction_custom_error::<SystemError>(result)?;
+    Ok(signature_str.to_string())
+}
+
+fn read_program(program_location: &str) -> Result<Vec<u8>, WalletError> {
     let mut file = File::open(program_location).map_err(|err| {
-        WalletError::DynamicProgramError(
-            format!("Unable to open program file: {}", err).to_string(),
-        )
+        WalletError::DynamicProgramError(format!("Unable to open program file: {}", err))
     })?;
     let mut program_data = Vec::new();
     file.read_to_end(&mut program_data).map_err(|err| {
-        WalletError::DynamicProgramError(
-            format!("Unable to read program file: {}", err).to_string(),
-        )
+        WalletError::DynamicProgramError(format!("Unable to read program file: {}", err))
     })?;
+    Ok(program_data)
+}
 
-    let mut tx = system_transaction::create_account(
-        &config.keypair,
-        &program_id.pubkey(),
-        blockhash,
-        1,
-        program_data.len() as u64,
-        &bpf_loader::id(),
-        0,
-    );
-    trace!("Creating program account");
-    let result = rpc_client.send_and_confirm_transaction(&mut tx, &config.keypair);
-    log_instruction_custom_error::<SystemError>(result).map_err(|_| {
-        WalletError::DynamicProgramError("Program allocate space failed".to_string())
+fn read_keypair_or_new(keypair_location: &Option<String>) -> Result<Keypair, WalletError> {
+    match keypair_location {
+        Some(keypair_location) => read_keypair(keypair_location).map_err(|err| {
+            WalletError::BadParameter(format!(
+                "{}: Unable to open keypair file: {}",
+                err, keypair_location
+            ))
+        }),
+        None => Ok(Keypair::new()),
+    }
+}
+
+fn get_upgradeable_account(
+    rpc_client: &RpcClient,
+    pubkey: &Pubkey,
+) -> Result<(Account, UpgradeableLoaderState), Box<dyn error::Error>> {
+    let account = rpc_client.get_account(pubkey)?;
+    if !bpf_loader_upgradeable::check_id(&account.owner) {
+        Err(WalletError::BadParameter(format!(
+            "{} isn't an upgradeable loader account",
+            pubkey
+        )))?
+    }
+    let state = account.deserialize_data().map_err(|_| {
+        WalletError::BadParameter(format!("{} holds no upgradeable loader state", pubkey))
     })?;
+    Ok((account, state))
+}
+
+// Write `program_data` into `buffer`, creating the buffer unless `resume` and it already
+// exists.  Only the chunks that differ from the buffer's contents are written, so a
+// deploy that was interrupted picks up where it stopped when given the same buffer.
+fn write_buffer(
+    rpc_client: &RpcClient,
+    config: &WalletConfig,
+    buffer: &Keypair,
+    resume: bool,
+    program_data: &[u8],
+) -> Result<(), Box<dyn error::Error>> {
+    let buffer_id = &buffer.pubkey();
+    let existing_buffer = if resume {
+        rpc_client.get_account(buffer_id).ok()
+    } else {
+        None
+    };
+
+    let blockhash = rpc_client.get_recent_blockhash()?;
+    let buffer_data = match existing_buffer {
+        Some(account) => {
+            let expected_state = UpgradeableLoaderState::Buffer {
+                authority: Some(config.keypair.pubkey()),
+            };
+            if !bpf_loader_upgradeable::check_id(&account.owner)
+                || account.deserialize_data::<UpgradeableLoaderState>().ok() != Some(expected_state)
+                || account.data.len() != UpgradeableLoaderState::buffer_len(program_data.len())
+            {
+                Err(WalletError::BadParameter(format!(
+                    "{} isn't a buffer of this wallet for this program",
+                    buffer_id
+                )))?
+            }
+            trace!("Resuming writes to buffer {}", buffer_id);
+            account.data[UpgradeableLoaderState::buffer_metadata_size()..].to_vec()
+        }
+        None => {
+            trace!("Creating buffer {}", buffer_id);
+            let instructions = bpf_loader_upgradeable::create_buffer(
+                &config.keypair.pubkey(),
+                buffer_id,
+                &config.keypair.pubkey(),
+                1,
+                program_data.len(),
+            );
+            let signers = [&config.keypair, buffer];
+            let mut tx = Transaction::new_signed_instructions(&signers, instructions, blockhash);
+            let result = rpc_client.send_and_confirm_transaction_with_signers(&mut tx, &signers);
+            log_instruction_custom_error::<SystemError>(result).map_err(|_| {
+                WalletError::DynamicProgramError("Buffer allocate space failed".to_string())
+            })?;
+            vec![0; program_data.len()]
+        }
+    };
 
     trace!("Writing program data");
     let write_transactions: Vec<_> = program_data
         .chunks(USERDATA_CHUNK_SIZE)
+        .zip(buffer_data.chunks(USERDATA_CHUNK_SIZE))
         .zip(0..)
-        .map(|(chunk, i)| {
-            let instruction = loader_instruction::write(
-                &program_id.pubkey(),
-                &bpf_loader::id(),
+        .filter(|((chunk, written), _)| chunk != written)
+        .map(|((chunk, _), i)| {
+            let instruction = bpf_loader_upgradeable::write(
+                buffer_id,
+                &config.keypair.pubkey(),
                 (i * USERDATA_CHUNK_SIZE) as u32,
                 chunk.to_vec(),
             );
-            Transaction::new_signed_instructions(&[&program_id], vec![instruction], blockhash)
+            Transaction::new_signed_instructions(&[&config.keypair], vec![instruction], blockhash)
         })
         .collect();
-    rpc_client.send_and_confirm_transactions(write_transactions, &program_id)?;
+    rpc_client.send_and_confirm_transactions(write_transactions, &config.keypair)?;
+    Ok(())
+}
 
-    trace!("Finalizing program account");
-    let instruction = loader_instruction::finalize(&program_id.pubkey(), &bpf_loader::id());
-    let mut tx = Transaction::new_signed_instructions(&[&program_id], vec![instruction], blockhash);
+fn process_deploy(
+    rpc_client: &RpcClient,
+    config: &WalletConfig,
+    program_location: &str,
+    buffer_location: &Option<String>,
+    program_id_location: &Option<String>,
+    max_len: Option<usize>,
+) -> ProcessResult {
+    let balance = rpc_client.retry_get_balance(&config.keypair.pubkey(), 5)?;
+    if let Some(dif) = balance {
+        // The buffer, program and program data accounts
+        if dif < 3 {
+            Err(WalletError::DynamicProgramError(
+                "Insufficient funds".to_string(),
+            ))?
+        }
+    }
+
+    let program_data = read_program(program_location)?;
+    let max_len = max_len.unwrap_or(program_data.len() * 2);
+    if max_len < program_data.len() {
+        Err(WalletError::BadParameter(format!(
+            "Max length {} is smaller than the program's {} bytes",
+            max_len,
+            program_data.len()
+        )))?
+    }
+    let buffer = read_keypair_or_new(buffer_location)?;
+    let program_id = read_keypair_or_new(program_id_location)?;
+
+    write_buffer(
+        rpc_client,
+        config,
+        &buffer,
+        buffer_location.is_some(),
+        &program_data,
+    )?;
+
+    trace!("Deploying program");
+    let blockhash = rpc_client.get_recent_blockhash()?;
+    let instructions = bpf_loader_upgradeable::deploy_with_max_program_len(
+        &config.keypair.pubkey(),
+        &program_id.pubkey(),
+        &buffer.pubkey(),
+        &config.keypair.pubkey(),
+        1,
+        max_len,
+    );
+    let signers = [&config.keypair, &program_id];
+    let mut tx = Transaction::new_signed_instructions(&signers, instructions, blockhash);
     rpc_client
-        .send_and_confirm_transaction(&mut tx, &program_id)
+        .send_and_confirm_transaction_with_signers(&mut tx, &signers)
         .map_err(|_| {
-            WalletError::DynamicProgramError("Program finalize transaction failed".to_string())
+            WalletError::DynamicProgramError("Program deploy transaction failed".to_string())
         })?;
 
     Ok(json!({
@@ -510,6 +782,179 @@ fn process_deploy(
     .to_string())
 }
 
+fn process_upgrade(
+    rpc_client: &RpcClient,
+    config: &WalletConfig,
+    program_location: &str,
+    program_id: &Pubkey,
+    buffer_location: &Option<String>,
+) -> ProcessResult {
+    let program_data = read_program(program_location)?;
+    let (programdata, state) = get_upgradeable_account(
+        rpc_client,
+        &bpf_loader_upgradeable::get_programdata_address(program_id),
+    )?;
+    match state {
+        UpgradeableLoaderState::ProgramData {
+            upgrade_authority, ..
+        } if upgrade_authority == Some(config.keypair.pubkey()) => (),
+        _ => Err(WalletError::BadParameter(format!(
+            "Program {} can't be upgraded by this wallet",
+            program_id
+        )))?,
+    }
+    let max_len = programdata.data.len() - UpgradeableLoaderState::programdata_metadata_size();
+    if program_data.len() > max_len {
+        Err(WalletError::BadParameter(format!(
+            "Program {} has room for {} bytes, not {}",
+            program_id,
+            max_len,
+            program_data.len()
+        )))?
+    }
+
+    let buffer = read_keypair_or_new(buffer_location)?;
+    write_buffer(
+        rpc_client,
+        config,
+        &buffer,
+        buffer_location.is_some(),
+        &program_data,
+    )?;
+
+    trace!("Upgrading program");
+    let blockhash = rpc_client.get_recent_blockhash()?;
+    let instruction =
+        bpf_loader_upgradeable::upgrade(program_id, &buffer.pubkey(), &config.keypair.pubkey());
+    let mut tx =
+        Transaction::new_signed_instructions(&[&config.keypair], vec![instruction], blockhash);
+    let signature_str = rpc_client
+        .send_and_confirm_transaction(&mut tx, &config.keypair)
+        .map_err(|_| {
+            WalletError::DynamicProgramError("Program upgrade transaction failed".to_string())
+        })?;
+    Ok(signature_str.to_string())
+}
+
+fn process_set_authority(
+    rpc_client: &RpcClient,
+    config: &WalletConfig,
+    account_id: &Pubkey,
+    new_authority: Option<Pubkey>,
+) -> ProcessResult {
+    let instruction = match get_upgradeable_account(rpc_client, account_id)?.1 {
+        UpgradeableLoaderState::Buffer { .. } => {
+            let new_authority = new_authority.ok_or_else(|| {
+                WalletError::BadParameter("A buffer can't be made final".to_string())
+            })?;
+            bpf_loader_upgradeable::set_buffer_authority(
+                account_id,
+                &config.keypair.pubkey(),
+                &new_authority,
+            )
+        }
+        UpgradeableLoaderState::Program { .. } => bpf_loader_upgradeable::set_upgrade_authority(
+            account_id,
+            &config.keypair.pubkey(),
+            new_authority.as_ref(),
+        ),
+        _ => Err(WalletError::BadParameter(format!(
+            "{} is neither a buffer nor a program",
+            account_id
+        )))?,
+    };
+    let blockhash = rpc_client.get_recent_blockhash()?;
+    let mut tx =
+        Transaction::new_signed_instructions(&[&config.keypair], vec![instruction], blockhash);
+    let signature_str = rpc_client.send_and_confirm_transaction(&mut tx, &config.keypair)?;
+    Ok(signature_str.to_string())
+}
+
+fn process_dump(
+    rpc_client: &RpcClient,
+    program_id: &Pubkey,
+    output_location: &str,
+) -> ProcessResult {
+    let account = rpc_client.get_account(program_id)?;
+    let program_data = if account.owner == bpf_loader::id() {
+        account.data
+    } else {
+        let programdata_address = match get_upgradeable_account(rpc_client, program_id)?.1 {
+            UpgradeableLoaderState::Program {
+                programdata_address,
+            } => programdata_address,
+            _ => Err(WalletError::BadParameter(format!(
+                "{} isn't a program",
+                program_id
+            )))?,
+        };
+        let (programdata, _) = get_upgradeable_account(rpc_client, &programdata_address)?;
+        programdata.data[UpgradeableLoaderState::programdata_metadata_size()..].to_vec()
+    };
+
+    let mut file = File::create(output_location).map_err(|err| {
+        WalletError::BadParameter(format!("Unable to create {}: {}", output_location, err))
+    })?;
+    file.write_all(&program_data)?;
+    Ok(format!(
+        "Wrote {} bytes of program {} to {}",
+        program_data.len(),
+        program_id,
+        output_location
+    ))
+}
+
+fn process_show(rpc_client: &RpcClient, account_id: &Pubkey) -> ProcessResult {
+    let show_authority = |authority: Option<Pubkey>| match authority {
+        Some(authority) => authority.to_string(),
+        None => "none".to_string(),
+    };
+    let (account, state) = get_upgradeable_account(rpc_client, account_id)?;
+    match state {
+        UpgradeableLoaderState::Uninitialized => println!("uninitialized"),
+        UpgradeableLoaderState::Buffer { authority } => {
+            println!("buffer: {}", account_id);
+            println!("authority: {}", show_authority(authority));
+            println!(
+                "data length: {}",
+                account.data.len() - UpgradeableLoaderState::buffer_metadata_size()
+            );
+        }
+        UpgradeableLoaderState::Program {
+            programdata_address,
+        } => {
+            let (programdata, state) = get_upgradeable_account(rpc_client, &programdata_address)?;
+            println!("program id: {}", account_id);
+            println!("program data address: {}", programdata_address);
+            if let UpgradeableLoaderState::ProgramData {
+                tick_height,
+                upgrade_authority,
+            } = state
+            {
+                println!("upgrade authority: {}", show_authority(upgrade_authority));
+                println!("last deployed at tick height: {}", tick_height);
+            }
+            println!(
+                "max data length: {}",
+                programdata.data.len() - UpgradeableLoaderState::programdata_metadata_size()
+            );
+        }
+        UpgradeableLoaderState::ProgramData {
+            tick_height,
+            upgrade_authority,
+        } => {
+            println!("program data: {}", account_id);
+            println!("upgrade authority: {}", show_authority(upgrade_authority));
+            println!("last deployed at tick height: {}", tick_height);
+            println!(
+                "max data length: {}",
+                account.data.len() - UpgradeableLoaderState::programdata_metadata_size()
+            );
+        }
+    }
+    Ok("".to_string())
+}
+
 fn process_pay(
     rpc_client: &RpcClient,
     config: &WalletConfig,
@@ -720,11 +1165,65 @@ pub fn process_command(config: &WalletConfig) -> ProcessResult {
             process_show_staking(&rpc_client, config, &voting_account_id)
         }
 
+        // Create a nonce account controlled by this client
+        WalletCommand::CreateNonceAccount(dif) => {
+            process_create_nonce_account(&rpc_client, config, dif)
+        }
+
+        WalletCommand::GetNonce(nonce_account_id) => {
+            process_get_nonce(&rpc_client, &nonce_account_id)
+        }
+
+        WalletCommand::NewNonce(nonce_account_id) => {
+            process_new_nonce(&rpc_client, config, &nonce_account_id)
+        }
+
+        // Pay dif to another address with a transaction that uses a nonce in place of a
+        // recent blockhash
+        WalletCommand::PayWithNonce(dif, to, nonce_account_id) => {
+            process_pay_with_nonce(&rpc_client, config, dif, &to, &nonce_account_id)
+        }
+
+        WalletCommand::WithdrawFromNonceAccount(nonce_account_id, to, dif) => {
+            process_withdraw_from_nonce_account(&rpc_client, config, &nonce_account_id, &to, dif)
+        }
+
         // Deploy a custom program to the chain
-        WalletCommand::Deploy(ref program_location) => {
-            process_deploy(&rpc_client, config, program_location)
+        WalletCommand::Deploy(
+            ref program_location,
+            ref buffer_location,
+            ref program_id_location,
+            max_len,
+        ) => process_deploy(
+            &rpc_client,
+            config,
+            program_location,
+            buffer_location,
+            program_id_location,
+            max_len,
+        ),
+
+        // Replace the program of a deployed program id
+        WalletCommand::Upgrade(ref program_location, program_id, ref buffer_location) => {
+            process_upgrade(
+                &rpc_client,
+                config,
+                program_location,
+                &program_id,
+                buffer_location,
+            )
+        }
+
+        WalletCommand::SetAuthority(account_id, new_authority) => {
+            process_set_authority(&rpc_client, config, &account_id, new_authority)
+        }
+
+        WalletCommand::Dump(program_id, ref output_location) => {
+            process_dump(&rpc_client, &program_id, output_location)
         }
 
+        WalletCommand::Show(account_id) => process_show(&rpc_client, &account_id),
+
         WalletCommand::GetTransactionCount => process_get_transaction_count(&rpc_client),
 
         // If client has positive balance, pay dif to another address
@@ -849,7 +1348,7 @@ mod tests {
     use super::*;
     use clap::{App, Arg, SubCommand};
     use serde_json::Value;
-    use soros_client::mock_rpc_client_request::SIGNATURE;
+    use soros_client::mock_rpc_client_request::{PUBKEY, SIGNATURE};
     use soros_sdk::transaction::TransactionError;
     use std::net::{Ipv4Addr, SocketAddr};
     use std::path::PathBuf;
@@ -971,9 +1470,49 @@ mod tests {
                             .help("The commission taken on reward redemption"),
                     ),
             )
+            .subcommand(
+                SubCommand::with_name("get-nonce")
+                    .about("Get the current nonce of a nonce account")
+                    .arg(
+                        Arg::with_name("nonce_account_id")
+                            .index(1)
+                            .value_name("PUBKEY")
+                            .takes_value(true)
+                            .required(true)
+                            .help("Nonce account address"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name("pay-with-nonce")
+                    .about("Send a payment that uses a nonce in place of a recent blockhash")
+                    .arg(
+                        Arg::with_name("to")
+                            .index(1)
+                            .value_name("PUBKEY")
+                            .takes_value(true)
+                            .required(true)
+                            .help("The pubkey of recipient"),
+                    )
+                    .arg(
+                        Arg::with_name("dif")
+                            .index(2)
+                            .value_name("NUM")
+                            .takes_value(true)
+                            .required(true)
+                            .help("The number of dif to send"),
+                    )
+                    .arg(
+                        Arg::with_name("nonce_account_id")
+                            .index(3)
+                            .value_name("PUBKEY")
+                            .takes_value(true)
+                            .required(true)
+                            .help("Nonce account to use"),
+                    ),
+            )
             .subcommand(
                 SubCommand::with_name("deploy")
-                    .about("Deploy a program")
+                    .about("Deploy a program with the upgradeable BPF loader")
                     .arg(
                         Arg::with_name("program_location")
                             .index(1)
@@ -981,7 +1520,113 @@ mod tests {
                             .takes_value(true)
                             .required(true)
                             .help("/path/to/program.o"),
-                    ), // TODO: Add "loader" argument; current default is bpf_loader
+                    )
+                    .arg(
+                        Arg::with_name("buffer")
+                            .long("buffer")
+                            .value_name("PATH")
+                            .takes_value(true)
+                            .help("Buffer keypair, resumes an interrupted deploy"),
+                    )
+                    .arg(
+                        Arg::with_name("program_id")
+                            .long("program-id")
+                            .value_name("PATH")
+                            .takes_value(true)
+                            .help("Keypair of the program id [default: a new keypair]"),
+                    )
+                    .arg(
+                        Arg::with_name("max_len")
+                            .long("max-len")
+                            .value_name("BYTES")
+                            .takes_value(true)
+                            .help("Largest program the program id can hold [default: 2x]"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name("upgrade")
+                    .about("Replace a deployed program, keeping its program id")
+                    .arg(
+                        Arg::with_name("program_location")
+                            .index(1)
+                            .value_name("PATH")
+                            .takes_value(true)
+                            .required(true)
+                            .help("/path/to/program.o"),
+                    )
+                    .arg(
+                        Arg::with_name("program_id")
+                            .index(2)
+                            .value_name("PUBKEY")
+                            .takes_value(true)
+                            .required(true)
+                            .help("The program id to upgrade"),
+                    )
+                    .arg(
+                        Arg::with_name("buffer")
+                            .long("buffer")
+                            .value_name("PATH")
+                            .takes_value(true)
+                            .help("Buffer keypair, resumes an interrupted upgrade"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name("set-authority")
+                    .about("Hand a buffer or program over to a new authority")
+                    .arg(
+                        Arg::with_name("account_id")
+                            .index(1)
+                            .value_name("PUBKEY")
+                            .takes_value(true)
+                            .required(true)
+                            .help("The buffer or program id"),
+                    )
+                    .arg(
+                        Arg::with_name("new_authority")
+                            .long("new-authority")
+                            .value_name("PUBKEY")
+                            .takes_value(true)
+                            .required_unless("final")
+                            .help("The new authority"),
+                    )
+                    .arg(
+                        Arg::with_name("final")
+                            .long("final")
+                            .conflicts_with("new_authority")
+                            .help("Make the program impossible to upgrade"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name("dump")
+                    .about("Write a deployed program, padded to its max length, to a file")
+                    .arg(
+                        Arg::with_name("program_id")
+                            .index(1)
+                            .value_name("PUBKEY")
+                            .takes_value(true)
+                            .required(true)
+                            .help("The program id"),
+                    )
+                    .arg(
+                        Arg::with_name("output_location")
+                            .index(2)
+                            .value_name("PATH")
+                            .takes_value(true)
+                            .required(true)
+                            .help("/path/to/program.so"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name("show")
+                    .about("Show an upgradeable buffer, program or program data account")
+                    .arg(
+                        Arg::with_name("account_id")
+                            .index(1)
+                            .value_name("PUBKEY")
+                            .takes_value(true)
+                            .required(true)
+                            .help("The account to show"),
+                    ),
             )
             .subcommand(
                 SubCommand::with_name("get-transaction-count")
@@ -1168,6 +1813,31 @@ mod tests {
             WalletCommand::CreateVoteAccount(pubkey, node_id, 0, 50)
         );
 
+        // Test GetNonce Subcommand
+        let test_get_nonce =
+            test_commands
+                .clone()
+                .get_matches_from(vec!["test", "get-nonce", &pubkey_string]);
+        assert_eq!(
+            parse_command(&pubkey, &test_get_nonce).unwrap(),
+            WalletCommand::GetNonce(pubkey)
+        );
+
+        // Test PayWithNonce Subcommand
+        let nonce_account_id = Pubkey::new_rand();
+        let nonce_account_string = format!("{}", nonce_account_id);
+        let test_pay_with_nonce = test_commands.clone().get_matches_from(vec![
+            "test",
+            "pay-with-nonce",
+            &pubkey_string,
+            "50",
+            &nonce_account_string,
+        ]);
+        assert_eq!(
+            parse_command(&pubkey, &test_pay_with_nonce).unwrap(),
+            WalletCommand::PayWithNonce(50, pubkey, nonce_account_id)
+        );
+
         // Test Deploy Subcommand
         let test_deploy =
             test_commands
@@ -1175,7 +1845,86 @@ mod tests {
                 .get_matches_from(vec!["test", "deploy", "/Users/test/program.o"]);
         assert_eq!(
             parse_command(&pubkey, &test_deploy).unwrap(),
-            WalletCommand::Deploy("/Users/test/program.o".to_string())
+            WalletCommand::Deploy("/Users/test/program.o".to_string(), None, None, None)
+        );
+        let test_deploy = test_commands.clone().get_matches_from(vec![
+            "test",
+            "deploy",
+            "/Users/test/program.o",
+            "--buffer",
+            "/Users/test/buffer.json",
+            "--program-id",
+            "/Users/test/program.json",
+            "--max-len",
+            "4096",
+        ]);
+        assert_eq!(
+            parse_command(&pubkey, &test_deploy).unwrap(),
+            WalletCommand::Deploy(
+                "/Users/test/program.o".to_string(),
+                Some("/Users/test/buffer.json".to_string()),
+                Some("/Users/test/program.json".to_string()),
+                Some(4096)
+            )
+        );
+
+        // Test Upgrade Subcommand
+        let test_upgrade = test_commands.clone().get_matches_from(vec![
+            "test",
+            "upgrade",
+            "/Users/test/program.o",
+            &pubkey_string,
+        ]);
+        assert_eq!(
+            parse_command(&pubkey, &test_upgrade).unwrap(),
+            WalletCommand::Upgrade("/Users/test/program.o".to_string(), pubkey, None)
+        );
+
+        // Test SetAuthority Subcommand
+        let test_set_authority = test_commands.clone().get_matches_from(vec![
+            "test",
+            "set-authority",
+            &pubkey_string,
+            "--new-authority",
+            &nonce_account_string,
+        ]);
+        assert_eq!(
+            parse_command(&pubkey, &test_set_authority).unwrap(),
+            WalletCommand::SetAuthority(pubkey, Some(nonce_account_id))
+        );
+        let test_set_final = test_commands.clone().get_matches_from(vec![
+            "test",
+            "set-authority",
+            &pubkey_string,
+            "--final",
+        ]);
+        assert_eq!(
+            parse_command(&pubkey, &test_set_final).unwrap(),
+            WalletCommand::SetAuthority(pubkey, None)
+        );
+        assert!(test_commands
+            .clone()
+            .get_matches_from_safe(vec!["test", "set-authority", &pubkey_string])
+            .is_err());
+
+        // Test Dump and Show Subcommands
+        let test_dump = test_commands.clone().get_matches_from(vec![
+            "test",
+            "dump",
+            &pubkey_string,
+            "/Users/test/program.so",
+        ]);
+        assert_eq!(
+            parse_command(&pubkey, &test_dump).unwrap(),
+            WalletCommand::Dump(pubkey, "/Users/test/program.so".to_string())
+        );
+        let test_show =
+            test_commands
+                .clone()
+                .get_matches_from(vec!["test", "show", &pubkey_string]);
+        assert_eq!(
+            parse_command(&pubkey, &test_show).unwrap(),
+            WalletCommand::Show(pubkey)
         );
 
         // Test Simple Pay Subcommand
@@ -1326,6 +2075,27 @@ mod tests {
         let signature = process_command(&config);
         assert_eq!(signature.unwrap(), SIGNATURE.to_string());
 
+        config.command = WalletCommand::CreateNonceAccount(10);
+        let result = process_command(&config);
+        let json: Value = serde_json::from_str(&result.unwrap()).unwrap();
+        assert_eq!(
+            json.as_object()
+                .unwrap()
+                .get("signature")
+                .unwrap()
+                .as_str()
+                .unwrap(),
+            SIGNATURE.to_string()
+        );
+
+        let nonce_account_id = Pubkey::new_rand();
+        config.command = WalletCommand::WithdrawFromNonceAccount(nonce_account_id, bob_pubkey, 10);
+        let signature = process_command(&config);
+        assert_eq!(signature.unwrap(), SIGNATURE.to_string());
+
+        config.command = WalletCommand::GetNonce(nonce_account_id);
+        assert!(process_command(&config).is_err());
+
         config.command = WalletCommand::GetTransactionCount;
         assert_eq!(process_command(&config).unwrap(), "1234");
 
@@ -1400,6 +2170,10 @@ mod tests {
         let signature = process_command(&config);
         assert_eq!(signature.unwrap(), SIGNATURE.to_string());
 
+        config.rpc_client = Some(RpcClient::new_mock("nonce".to_string()));
+        config.command = WalletCommand::GetNonce(nonce_account_id);
+        assert_eq!(process_command(&config).unwrap(), PUBKEY.to_string());
+
         // sig_not_found case
         config.rpc_client = Some(RpcClient::new_mock("sig_not_found".to_string()));
         let missing_signature = Signature::new(&bs58::decode("5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnbJLgp8uirBgmQpjKhoR4tjF3ZpRzrFmBV6UjKdiSZkQUW").into_vec().unwrap());
@@ -1476,7 +2250,8 @@ mod tests {
         let mut config = WalletConfig::default();
         config.rpc_client = Some(RpcClient::new_mock("succeeds".to_string()));
 
-        config.command = WalletCommand::Deploy(pathbuf.to_str().unwrap().to_string());
+        let program_location = pathbuf.to_str().unwrap().to_string();
+        config.command = WalletCommand::Deploy(program_location.clone(), None, None, None);
         let result = process_command(&config);
         let json: Value = serde_json::from_str(&result.unwrap()).unwrap();
         let program_id = json
@@ -1493,7 +2268,39 @@ mod tests {
         config.rpc_client = Some(RpcClient::new_mock("airdrop".to_string()));
         assert!(process_command(&config).is_err());
 
-        config.command = WalletCommand::Deploy("bad/file/location.so".to_string());
+        config.rpc_client = Some(RpcClient::new_mock("succeeds".to_string()));
+        config.command = WalletCommand::Deploy(program_location, None, None, Some(1));
+        assert!(process_command(&config).is_err());
+
+        config.command =
+            WalletCommand::Deploy("bad/file/location.so".to_string(), None, None, None);
+        assert!(process_command(&config).is_err());
+    }
+
+    #[test]
+    fn test_wallet_upgradeable_accounts_required() {
+        let mut pathbuf = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        pathbuf.push("tests");
+        pathbuf.push("fixtures");
+        pathbuf.push("noop");
+        pathbuf.set_extension("so");
+        let program_location = pathbuf.to_str().unwrap().to_string();
+
+        // The mock's accounts all belong to the system program
+        let mut config = WalletConfig::default();
+        config.rpc_client = Some(RpcClient::new_mock("succeeds".to_string()));
+        let program_id = Pubkey::new_rand();
+
+        config.command = WalletCommand::Upgrade(program_location, program_id, None);
+        assert!(process_command(&config).is_err());
+
+        config.command = WalletCommand::SetAuthority(program_id, None);
+        assert!(process_command(&config).is_err());
+
+        config.command = WalletCommand::Dump(program_id, "program.so".to_string());
+        assert!(process_command(&config).is_err());
+
+        config.command = WalletCommand::Show(program_id);
         assert!(process_command(&config).is_err());
     }
 }
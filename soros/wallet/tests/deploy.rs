@@ -3,7 +3,8 @@ use soros::fullnode::new_fullnode_for_tests;
 use soros_client::rpc_client::RpcClient;
 use soros_client::rpc_request::RpcRequest;
 use soros_drone::drone::run_local_drone;
-use soros_sdk::bpf_loader;
+use soros_sdk::bpf_loader_upgradeable::{self, UpgradeableLoaderState};
+use soros_sdk::pubkey::Pubkey;
 use soros_wallet::wallet::{process_command, WalletCommand, WalletConfig};
 use std::fs::{remove_dir_all, File};
 use std::io::Read;
@@ -34,7 +35,8 @@ fn test_wallet_deploy_program() {
     config.command = WalletCommand::Airdrop(50);
     process_command(&config).unwrap();
 
-    config.command = WalletCommand::Deploy(pathbuf.to_str().unwrap().to_string());
+    let program_location = pathbuf.to_str().unwrap().to_string();
+    config.command = WalletCommand::Deploy(program_location.clone(), None, None, None);
 
     let response = process_command(&config);
     let json: Value = serde_json::from_str(&response.unwrap()).unwrap();
@@ -47,16 +49,17 @@ fn test_wallet_deploy_program() {
         .unwrap();
 
     let params = json!([program_id_str]);
-    let account_info = rpc_client
+    let response = rpc_client
         .retry_make_rpc_request(&RpcRequest::GetAccountInfo, Some(params), 0)
         .unwrap();
+    let account_info = &response["value"];
     let account_info_obj = account_info.as_object().unwrap();
     assert_eq!(
         account_info_obj.get("dif").unwrap().as_u64().unwrap(),
         1
     );
     let owner_array = account_info.get("owner").unwrap();
-    assert_eq!(owner_array, &json!(bpf_loader::id()));
+    assert_eq!(owner_array, &json!(bpf_loader_upgradeable::id()));
     assert_eq!(
         account_info_obj
             .get("executable")
@@ -66,14 +69,41 @@ fn test_wallet_deploy_program() {
         true
     );
 
-    let mut file = File::open(pathbuf.to_str().unwrap().to_string()).unwrap();
+    assert_eq!(
+        account_info_obj
+            .get("data")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .len(),
+        UpgradeableLoaderState::program_len()
+    );
+
+    let mut file = File::open(&program_location).unwrap();
     let mut elf = Vec::new();
     file.read_to_end(&mut elf).unwrap();
 
-    assert_eq!(
-        account_info_obj.get("data").unwrap().as_array().unwrap(),
-        &elf
-    );
+    // The dump is the ELF padded with zeros to twice its length
+    let program_id = program_id_str.parse::<Pubkey>().unwrap();
+    let mut dump_path = PathBuf::from(&ledger_path);
+    dump_path.push("noop.so");
+    let dump_location = dump_path.to_str().unwrap().to_string();
+    config.command = WalletCommand::Dump(program_id, dump_location.clone());
+    process_command(&config).unwrap();
+    let mut file = File::open(&dump_location).unwrap();
+    let mut dump = Vec::new();
+    file.read_to_end(&mut dump).unwrap();
+    assert_eq!(dump.len(), elf.len() * 2);
+    assert_eq!(dump[..elf.len()], elf[..]);
+    assert!(dump[elf.len()..].iter().all(|byte| *byte == 0));
+
+    // Upgrades stop once the program is final
+    config.command = WalletCommand::Upgrade(program_location.clone(), program_id, None);
+    process_command(&config).unwrap();
+    config.command = WalletCommand::SetAuthority(program_id, None);
+    process_command(&config).unwrap();
+    config.command = WalletCommand::Upgrade(program_location, program_id, None);
+    assert!(process_command(&config).is_err());
 
     server.close().unwrap();
     remove_dir_all(ledger_path).unwrap();